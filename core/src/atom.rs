@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+
+use crate::config::FeedConfig;
+use crate::html;
+use crate::notes::Note;
+
+/// The notes `config` puts in the feed: those with its tag, most recently
+/// updated first, at most `config.limit` of them.
+pub fn entries<'a>(notes: &'a [Note], config: &FeedConfig) -> Vec<&'a Note> {
+    let tag = config.tag.trim_start_matches('#');
+    let mut entries: Vec<&Note> = notes.iter().filter(|note| note.tags.iter().any(|t| t == tag)).collect();
+    entries.sort_by_key(|note| Reverse(note.updated_at));
+    entries.truncate(config.limit);
+    entries
+}
+
+/// An Atom (RFC 4287) feed of the notes `config` picks, each with its
+/// content rendered from Markdown. `self_url` is where the feed itself is
+/// fetched from, when known.
+pub fn feed(notes: &[Note], config: &FeedConfig, self_url: Option<&str>, now: DateTime<Utc>) -> String {
+    let entries = entries(notes, config);
+    let updated = entries.iter().map(|note| note.updated_at).max().unwrap_or(now);
+    let author = if config.author.is_empty() { &config.title } else { &config.author };
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str(&format!("  <title>{}</title>\n", html::escape(&config.title)));
+    out.push_str(&format!("  <id>urn:turbo-notes:feed:{}</id>\n", html::escape(config.tag.trim_start_matches('#'))));
+    out.push_str(&format!("  <updated>{}</updated>\n", timestamp(updated)));
+    out.push_str(&format!("  <author><name>{}</name></author>\n", html::escape(author)));
+    out.push_str(&format!(
+        "  <generator version=\"{}\">Turbo Notes</generator>\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    if !config.link.is_empty() {
+        out.push_str(&format!("  <link rel=\"alternate\" href=\"{}\"/>\n", html::escape(&config.link)));
+    }
+    if let Some(url) = self_url {
+        out.push_str(&format!("  <link rel=\"self\" href=\"{}\"/>\n", html::escape(url)));
+    }
+    for note in entries {
+        let title = match note.title() {
+            "" => "Note",
+            title => title,
+        };
+        out.push_str("  <entry>\n");
+        out.push_str(&format!("    <title>{}</title>\n", html::escape(title)));
+        out.push_str(&format!("    <id>urn:turbo-notes:note:{}</id>\n", html::escape(&note.id)));
+        out.push_str(&format!("    <published>{}</published>\n", timestamp(note.created_at)));
+        out.push_str(&format!("    <updated>{}</updated>\n", timestamp(note.updated_at)));
+        if !config.link.is_empty() {
+            let href = format!("{}#{}", config.link, note.id);
+            out.push_str(&format!("    <link rel=\"alternate\" href=\"{}\"/>\n", html::escape(&href)));
+        }
+        for tag in &note.tags {
+            out.push_str(&format!("    <category term=\"{}\"/>\n", html::escape(tag)));
+        }
+        out.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            html::escape(&html::render_markdown(&note.content))
+        ));
+        out.push_str("  </entry>\n");
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
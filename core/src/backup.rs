@@ -0,0 +1,763 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{BackupRetention, Config};
+use crate::error::UserError;
+use crate::merge::{diff, Segment};
+use crate::notes::{self, Note};
+
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+/// Label of backups taken by the scheduler
+pub const AUTO_LABEL: &str = "auto";
+/// Extension of the checksum manifest written next to each backup
+const MANIFEST_EXTENSION: &str = "sha256";
+/// Where snapshots keep their notes, each stored once
+const OBJECTS_DIR: &str = "objects";
+/// Ending of a stored note's file: its JSON, zstd-compressed
+const OBJECT_EXTENSION: &str = ".json.zst";
+/// zstd level stored notes are compressed at
+const OBJECT_LEVEL: i32 = 3;
+
+/// A backup of notes.json in the backup directory: a snapshot, or a plain
+/// copy of the file as taken by older versions.
+#[derive(Debug, Clone, Serialize)]
+pub struct Backup {
+    /// Timestamp-based id, optionally followed by a label
+    pub id: String,
+    pub path: PathBuf,
+    pub created_at: Option<DateTime<Local>>,
+    /// Size of the notes file it holds
+    pub size: u64,
+}
+
+/// Contents of `snapshot-<id>.json`. Notes are stored compressed in
+/// `objects/`, named by the SHA-256 of their JSON, so a note that did not
+/// change takes no extra space in later snapshots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    /// Size of the notes file when the snapshot was taken
+    size: u64,
+    /// Hash of each note, in the notes file's order
+    notes: Vec<String>,
+}
+
+/// How a backup holds up against the checksums taken when it was made.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status", content = "error")]
+pub enum Integrity {
+    /// Matches its checksums and parses
+    Verified,
+    /// Parses, but was made before backups had checksums
+    Unverified,
+    Damaged(String),
+}
+
+impl Backup {
+    /// Whether the scheduler took this backup, making it subject to pruning.
+    pub fn is_auto(&self) -> bool {
+        self.id.strip_suffix(AUTO_LABEL).is_some_and(|stamp| stamp.ends_with('-'))
+    }
+
+    /// Checksums of the backup's files, one `<sha256>  <file name>` line
+    /// each, as `sha256sum -c` reads them.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.path.with_extension(MANIFEST_EXTENSION)
+    }
+
+    fn is_snapshot(&self) -> bool {
+        self.path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("snapshot-"))
+    }
+
+    /// Checks the backup against its manifest, if it has one, and that it
+    /// holds a valid note list. Returns its notes.
+    pub fn verify(&self) -> Result<Vec<Note>> {
+        let manifest = self.manifest_path();
+        if manifest.exists() {
+            check_manifest(&manifest)?;
+        }
+        if !self.is_snapshot() {
+            return verify(&self.path);
+        }
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        let notes = read_snapshot(&self.path)?
+            .notes
+            .iter()
+            .map(|hash| read_object(dir, hash))
+            .collect::<Result<Vec<Note>>>()?;
+        check_ids(&self.path, &notes)?;
+        Ok(notes)
+    }
+
+    /// Whether the backup holds exactly what is in `notes_file` now.
+    fn holds(&self, notes_file: &Path) -> Result<bool> {
+        if !self.is_snapshot() {
+            return Ok(fs::read(&self.path)? == fs::read(notes_file)?);
+        }
+        let Ok(notes) = notes::read_notes(notes_file) else {
+            return Ok(false);
+        };
+        let hashes = notes
+            .iter()
+            .map(|note| Ok(sha256(&serde_json::to_vec(note)?)))
+            .collect::<Result<Vec<String>>>()?;
+        Ok(read_snapshot(&self.path)?.notes == hashes)
+    }
+
+    pub fn integrity(&self) -> Integrity {
+        match self.verify() {
+            Ok(_) if self.manifest_path().exists() => Integrity::Verified,
+            Ok(_) => Integrity::Unverified,
+            Err(e) => Integrity::Damaged(e.to_string()),
+        }
+    }
+}
+
+/// Backups live next to the notes as `backups/snapshot-<id>.json`, with
+/// their notes in `backups/objects/`.
+pub struct BackupStore {
+    dir: PathBuf,
+    notes_file: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            dir: config.backup_dir(),
+            notes_file: config.notes_dir.join("notes.json"),
+        }
+    }
+
+    /// Snapshots the current notes file into a new backup. `label` is
+    /// appended to the id, e.g. `pre-restore`.
+    pub fn create(&self, label: Option<&str>) -> Result<Backup> {
+        if !self.notes_file.exists() {
+            return Err(UserError::NotFound(format!("Nothing to back up: {} does not exist", self.notes_file.display())).into());
+        }
+        let notes = notes::read_notes(&self.notes_file)
+            .map_err(|e| UserError::Invalid(format!("Cannot back up {}: {}", self.notes_file.display(), e)))?;
+        fs::create_dir_all(&self.dir)?;
+
+        let mut id = Local::now().format(ID_FORMAT).to_string();
+        if let Some(label) = label {
+            id = format!("{}-{}", id, label);
+        }
+        let mut snapshot = Snapshot {
+            // Large notes' content counts too, as restoring writes it inline
+            size: serde_json::to_vec_pretty(&notes)?.len() as u64,
+            notes: Vec::new(),
+        };
+        let mut files = Vec::new();
+        for note in &notes {
+            let (hash, path) = write_object(&self.dir, &serde_json::to_vec(note)?)?;
+            snapshot.notes.push(hash);
+            files.push(path);
+        }
+        let path = self.dir.join(format!("snapshot-{}.json", id));
+        fs::write(&path, serde_json::to_vec(&snapshot)?)?;
+        files.insert(0, path.clone());
+        write_manifest(&self.dir, &path.with_extension(MANIFEST_EXTENSION), &files)?;
+        Self::read(&path).ok_or_else(|| anyhow::anyhow!("Backup {} was not written", id))
+    }
+
+    /// All backups, newest first.
+    pub fn list(&self) -> Result<Vec<Backup>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut backups: Vec<Backup> = fs::read_dir(&self.dir)?
+            .flatten()
+            .filter_map(|entry| Self::read(&entry.path()))
+            .collect();
+        backups.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(backups)
+    }
+
+    /// Finds a backup by full id or unique id prefix.
+    pub fn find(&self, prefix: &str) -> Result<Backup> {
+        let backups = self.list()?;
+        if let Some(backup) = backups.iter().find(|backup| backup.id == prefix) {
+            return Ok(backup.clone());
+        }
+        let matches: Vec<&Backup> = backups.iter().filter(|backup| backup.id.starts_with(prefix)).collect();
+        match matches.as_slice() {
+            [] => Err(UserError::NotFound(format!("No backup matches id '{}'", prefix)).into()),
+            [backup] => Ok((*backup).clone()),
+            _ => Err(UserError::Invalid(format!("Backup id '{}' is ambiguous ({} backups match)", prefix, matches.len())).into()),
+        }
+    }
+
+    /// Takes the backup made before the notes are replaced, labelled
+    /// `pre-restore`, if there are any notes to save.
+    pub fn create_safety(&self) -> Result<Option<Backup>> {
+        match self.notes_file.exists() {
+            true => Ok(Some(self.create(Some("pre-restore"))?)),
+            false => Ok(None),
+        }
+    }
+
+    /// Automatic backups that `retention` does not keep, newest first. The
+    /// newest backup that verifies is always kept, so damaged newer ones
+    /// cannot push the last good one out.
+    pub fn expired(&self, retention: &BackupRetention) -> Result<Vec<Backup>> {
+        let backups = self.list()?;
+        let last_good = backups.iter().find(|backup| backup.verify().is_ok()).map(|backup| backup.id.clone());
+        let mut expired = expired(backups, retention);
+        expired.retain(|backup| Some(&backup.id) != last_good.as_ref());
+        Ok(expired)
+    }
+
+    /// Deletes the automatic backups that `retention` does not keep, with
+    /// their manifests and the notes no other snapshot shares. Returns the
+    /// deleted backups.
+    pub fn prune(&self, retention: &BackupRetention) -> Result<Vec<Backup>> {
+        let expired = self.expired(retention)?;
+        for backup in &expired {
+            fs::remove_file(&backup.path)?;
+            let manifest = backup.manifest_path();
+            if manifest.exists() {
+                fs::remove_file(manifest)?;
+            }
+        }
+        if !expired.is_empty() {
+            self.remove_unused_objects()?;
+        }
+        Ok(expired)
+    }
+
+    /// Deletes stored notes that no snapshot refers to. Does nothing if a
+    /// snapshot cannot be read, since the notes it needs are unknown.
+    fn remove_unused_objects(&self) -> Result<()> {
+        let objects = self.dir.join(OBJECTS_DIR);
+        if !objects.exists() {
+            return Ok(());
+        }
+        let mut used = HashSet::new();
+        // Every snapshot file, including ones too damaged to list
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !(name.starts_with("snapshot-") && name.ends_with(".json")) {
+                continue;
+            }
+            let Ok(snapshot) = read_snapshot(&entry.path()) else {
+                return Ok(());
+            };
+            used.extend(snapshot.notes);
+        }
+        for shard in fs::read_dir(&objects)?.flatten() {
+            for object in fs::read_dir(shard.path())?.flatten() {
+                let path = object.path();
+                let hash = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(OBJECT_EXTENSION));
+                if hash.is_some_and(|hash| !used.contains(hash)) {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(path: &Path) -> Option<Backup> {
+        let name = path.file_name()?.to_str()?.strip_suffix(".json")?;
+        let (id, size) = match name.strip_prefix("snapshot-") {
+            Some(id) => (id, read_snapshot(path).ok()?.size),
+            None => (name.strip_prefix("notes-")?, path.metadata().ok()?.len()),
+        };
+        let created_at = id
+            .get(..18)
+            .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, ID_FORMAT).ok())
+            .and_then(|stamp| Local.from_local_datetime(&stamp).single());
+
+        Some(Backup {
+            id: id.to_string(),
+            path: path.to_path_buf(),
+            created_at,
+            size,
+        })
+    }
+}
+
+/// Takes an automatic backup if backups are enabled and the newest backup
+/// is older than `backup_interval_hours`. Skipped when the notes did not
+/// change since the newest backup. Returns the backup taken, if any.
+pub fn backup_if_due(config: &Config) -> Result<Option<Backup>> {
+    let store = BackupStore::new(config);
+    if !config.should_backup() || !store.notes_file.exists() {
+        return Ok(None);
+    }
+    let interval = Duration::hours(config.backup_interval_hours.min(i64::MAX as u64) as i64);
+    let newest = store.list()?.into_iter().next();
+    if let Some(newest) = &newest {
+        let recent = newest.created_at.is_some_and(|created| Local::now() - created < interval);
+        if recent || newest.holds(&store.notes_file)? {
+            return Ok(None);
+        }
+    }
+    let backup = store.create(Some(AUTO_LABEL))?;
+    store.prune(&config.backup_retention)?;
+    Ok(Some(backup))
+}
+
+/// Names the day, week or month a backup was taken in.
+type Period = fn(&DateTime<Local>) -> String;
+
+/// Picks the automatic backups outside `retention` from `backups` (newest
+/// first). Only the newest backup of a day, week or month counts towards
+/// that period; backups without a readable date are always kept.
+fn expired(backups: Vec<Backup>, retention: &BackupRetention) -> Vec<Backup> {
+    let auto: Vec<Backup> = backups.into_iter().filter(Backup::is_auto).collect();
+    let mut keep: HashSet<String> = auto.iter().take(retention.keep_last).map(|backup| backup.id.clone()).collect();
+
+    let periods: [(usize, Period); 3] = [
+        (retention.keep_daily, |time| time.format("%Y-%m-%d").to_string()),
+        (retention.keep_weekly, |time| {
+            let week = time.iso_week();
+            format!("{}-W{}", week.year(), week.week())
+        }),
+        (retention.keep_monthly, |time| time.format("%Y-%m").to_string()),
+    ];
+    for (limit, period) in periods {
+        let mut seen = HashSet::new();
+        for backup in &auto {
+            if seen.len() == limit {
+                break;
+            }
+            if let Some(created) = &backup.created_at {
+                if seen.insert(period(created)) {
+                    keep.insert(backup.id.clone());
+                }
+            }
+        }
+    }
+
+    auto.into_iter()
+        .filter(|backup| backup.created_at.is_some() && !keep.contains(&backup.id))
+        .collect()
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| UserError::Invalid(format!("{} is not a valid snapshot: {}", path.display(), e)).into())
+}
+
+fn object_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(OBJECTS_DIR).join(&hash[..2]).join(format!("{}{}", hash, OBJECT_EXTENSION))
+}
+
+/// Stores one note's JSON under its hash, unless it is already stored.
+/// Returns the hash and the file.
+fn write_object(dir: &Path, json: &[u8]) -> Result<(String, PathBuf)> {
+    let hash = sha256(json);
+    let path = object_path(dir, &hash);
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("partial");
+        fs::write(&partial, zstd::encode_all(json, OBJECT_LEVEL)?)?;
+        fs::rename(&partial, &path)?;
+    }
+    Ok((hash, path))
+}
+
+/// Reads a stored note, checking it still matches its hash.
+fn read_object(dir: &Path, hash: &str) -> Result<Note> {
+    let path = object_path(dir, hash);
+    if !path.exists() {
+        return Err(UserError::Invalid(format!("{} is missing", path.display())).into());
+    }
+    let json = zstd::decode_all(fs::File::open(&path)?)
+        .map_err(|e| UserError::Invalid(format!("{} is damaged: {}", path.display(), e)))?;
+    if sha256(&json) != hash {
+        return Err(UserError::Invalid(format!("{} does not match its hash", path.display())).into());
+    }
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Writes the checksums of `files` to `path`, naming them relative to `dir`.
+fn write_manifest(dir: &Path, path: &Path, files: &[PathBuf]) -> Result<()> {
+    let mut manifest = String::new();
+    for file in files {
+        let name = file.strip_prefix(dir).unwrap_or(file).to_string_lossy().replace('\\', "/");
+        manifest.push_str(&format!("{}  {}\n", sha256_file(file)?, name));
+    }
+    fs::write(path, manifest)?;
+    Ok(())
+}
+
+/// Checks every file listed in the manifest at `path`, relative to the
+/// manifest's directory, against its checksum.
+fn check_manifest(path: &Path) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for line in fs::read_to_string(path)?.lines().filter(|line| !line.trim().is_empty()) {
+        let Some((hash, name)) = line.split_once("  ") else {
+            return Err(UserError::Invalid(format!("{} is malformed", path.display())).into());
+        };
+        let file = dir.join(name);
+        if !file.exists() {
+            return Err(UserError::Invalid(format!("{} is missing", file.display())).into());
+        }
+        if sha256_file(&file)? != hash {
+            return Err(UserError::Invalid(format!("{} does not match its checksum", file.display())).into());
+        }
+    }
+    Ok(())
+}
+
+fn sha256(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    Ok(sha256(&fs::read(path)?))
+}
+
+/// Checks that a backup parses as a note list with unique, non-empty ids.
+pub fn verify(path: &Path) -> Result<Vec<Note>> {
+    let content = fs::read_to_string(path)?;
+    let notes: Vec<Note> = serde_json::from_str(&content)
+        .map_err(|e| UserError::Invalid(format!("{} is not a valid notes file: {}", path.display(), e)))?;
+    check_ids(path, &notes)?;
+    Ok(notes)
+}
+
+/// Checks that the notes read from `path` have unique, non-empty ids.
+fn check_ids(path: &Path, notes: &[Note]) -> Result<()> {
+    let mut ids = HashSet::new();
+    for note in notes {
+        if note.id.is_empty() {
+            return Err(UserError::Invalid(format!("{} contains a note without an id", path.display())).into());
+        }
+        if !ids.insert(note.id.as_str()) {
+            return Err(UserError::Invalid(format!("{} contains duplicate note id {}", path.display(), note.id)).into());
+        }
+    }
+    Ok(())
+}
+
+/// How a note in a backup compares with the current notes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteChange {
+    Unchanged,
+    /// Text or tags differ now
+    Changed,
+    /// Deleted since the backup
+    Deleted,
+}
+
+/// Each note in `backup` with how it compares with `current`, and the
+/// current notes created since the backup.
+pub fn compare(backup: Vec<Note>, current: &[Note]) -> (Vec<(NoteChange, Note)>, Vec<Note>) {
+    let in_backup: HashSet<&str> = backup.iter().map(|note| note.id.as_str()).collect();
+    let new_since = current
+        .iter()
+        .filter(|note| !in_backup.contains(note.id.as_str()))
+        .cloned()
+        .collect();
+    let notes = backup
+        .into_iter()
+        .map(|note| {
+            let change = match current.iter().find(|now| now.id == note.id) {
+                None => NoteChange::Deleted,
+                Some(now) if now.content != note.content || now.tags != note.tags => NoteChange::Changed,
+                Some(_) => NoteChange::Unchanged,
+            };
+            (change, note)
+        })
+        .collect();
+    (notes, new_since)
+}
+
+/// State of the backup browser: every backup with the selected one
+/// compared against the current notes and, once it is opened, the note
+/// selected in it.
+#[derive(Debug, Clone)]
+pub struct BackupBrowser {
+    pub backups: Vec<Backup>,
+    /// Notes in each backup; `None` if it does not verify
+    pub counts: Vec<Option<usize>>,
+    pub selected: usize,
+    /// Notes of the selected backup and how each compares with now
+    pub notes: Vec<(NoteChange, Note)>,
+    /// Current notes created since the selected backup
+    pub new_since: Vec<Note>,
+    /// Why the selected backup could not be read
+    pub error: Option<String>,
+    /// Selected note, once the backup is opened to pick notes from
+    pub note: Option<usize>,
+    /// Ids of the notes picked for restoring
+    pub picked: HashSet<String>,
+    /// Line diff of the selected note, current text against the backup's
+    pub preview: Vec<Segment>,
+}
+
+impl BackupBrowser {
+    /// Lists the backups in `store`, selecting the newest. `None` if there
+    /// are none.
+    pub fn open(store: &BackupStore, current: &[Note]) -> Result<Option<Self>> {
+        let backups = store.list()?;
+        if backups.is_empty() {
+            return Ok(None);
+        }
+        let counts = backups.iter().map(|backup| backup.verify().ok().map(|notes| notes.len())).collect();
+        let mut browser = Self {
+            backups,
+            counts,
+            selected: 0,
+            notes: Vec::new(),
+            new_since: Vec::new(),
+            error: None,
+            note: None,
+            picked: HashSet::new(),
+            preview: Vec::new(),
+        };
+        browser.load(current);
+        Ok(Some(browser))
+    }
+
+    pub fn backup(&self) -> &Backup {
+        &self.backups[self.selected]
+    }
+
+    /// Re-reads the selected backup and compares it with `current`.
+    pub fn load(&mut self, current: &[Note]) {
+        match self.backup().verify() {
+            Ok(notes) => {
+                (self.notes, self.new_since) = compare(notes, current);
+                self.error = None;
+            }
+            Err(e) => {
+                self.notes.clear();
+                self.new_since.clear();
+                self.error = Some(e.to_string());
+            }
+        }
+        let last = self.notes.len().checked_sub(1);
+        self.note = self.note.zip(last).map(|(note, last)| note.min(last));
+        self.update_preview(current);
+    }
+
+    /// Moves the selection among the backups, or among the notes of an
+    /// opened backup.
+    pub fn select(&mut self, delta: isize, current: &[Note]) {
+        let last = |len: usize| len.saturating_sub(1);
+        match self.note {
+            Some(note) => {
+                self.note = Some(note.saturating_add_signed(delta).min(last(self.notes.len())));
+                self.update_preview(current);
+            }
+            None => {
+                let selected = self.selected.saturating_add_signed(delta).min(last(self.backups.len()));
+                if selected != self.selected {
+                    self.selected = selected;
+                    self.load(current);
+                }
+            }
+        }
+    }
+
+    /// Opens the selected backup to pick notes from. Returns false if it
+    /// has none.
+    pub fn open_notes(&mut self, current: &[Note]) -> bool {
+        if self.notes.is_empty() {
+            return false;
+        }
+        self.note = Some(0);
+        self.update_preview(current);
+        true
+    }
+
+    pub fn close_notes(&mut self) {
+        self.note = None;
+        self.picked.clear();
+        self.preview.clear();
+    }
+
+    pub fn toggle_pick(&mut self) {
+        if let Some((_, note)) = self.note.and_then(|i| self.notes.get(i)) {
+            if !self.picked.remove(&note.id) {
+                self.picked.insert(note.id.clone());
+            }
+        }
+    }
+
+    /// The picked notes, or the selected one if none are picked, leaving
+    /// out those that are the same now.
+    pub fn to_restore(&self) -> Vec<Note> {
+        let selected = self.note.and_then(|i| self.notes.get(i)).map(|(_, note)| note.id.as_str());
+        self.notes
+            .iter()
+            .filter(|(change, _)| *change != NoteChange::Unchanged)
+            .filter(|(_, note)| {
+                if self.picked.is_empty() {
+                    Some(note.id.as_str()) == selected
+                } else {
+                    self.picked.contains(&note.id)
+                }
+            })
+            .map(|(_, note)| note.clone())
+            .collect()
+    }
+
+    fn update_preview(&mut self, current: &[Note]) {
+        self.preview = match self.note.and_then(|i| self.notes.get(i)) {
+            Some((_, note)) => {
+                let now = current.iter().find(|now| now.id == note.id);
+                diff(now.map(|now| now.content.as_str()).unwrap_or(""), &note.content)
+            }
+            None => Vec::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store in a fresh directory, with `contents` as its notes.
+    fn store(dir: &Path, contents: &[&str]) -> (BackupStore, Vec<Note>) {
+        let store = BackupStore::new(&Config { notes_dir: dir.to_path_buf(), ..Config::default() });
+        let notes: Vec<Note> = contents.iter().map(|content| Note::new(content.to_string())).collect();
+        write_notes(&store, &notes);
+        (store, notes)
+    }
+
+    fn write_notes(store: &BackupStore, notes: &[Note]) {
+        fs::write(&store.notes_file, serde_json::to_string_pretty(notes).unwrap()).unwrap();
+    }
+
+    fn objects(store: &BackupStore) -> Vec<PathBuf> {
+        let mut objects: Vec<PathBuf> = fs::read_dir(store.dir.join(OBJECTS_DIR))
+            .unwrap()
+            .flat_map(|shard| fs::read_dir(shard.unwrap().path()).unwrap())
+            .map(|object| object.unwrap().path())
+            .collect();
+        objects.sort();
+        objects
+    }
+
+    fn backup(id: &str, created_at: Option<DateTime<Local>>) -> Backup {
+        Backup { id: id.to_string(), path: PathBuf::from(format!("snapshot-{}.json", id)), created_at, size: 0 }
+    }
+
+    #[test]
+    fn snapshot_round_trips_and_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, notes) = store(dir.path(), &["first", "second"]);
+        let backup = store.create(None).unwrap();
+
+        assert!(backup.is_snapshot());
+        assert_eq!(backup.integrity(), Integrity::Verified);
+        let restored = backup.verify().unwrap();
+        assert_eq!(restored.iter().map(|note| &note.id).collect::<Vec<_>>(), notes.iter().map(|note| &note.id).collect::<Vec<_>>());
+        assert!(backup.holds(&store.notes_file).unwrap());
+    }
+
+    #[test]
+    fn damaged_object_fails_verification() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = store(dir.path(), &["first"]);
+        let backup = store.create(None).unwrap();
+
+        let object = objects(&store).remove(0);
+        fs::write(&object, zstd::encode_all(&b"[]"[..], OBJECT_LEVEL).unwrap()).unwrap();
+        let error = backup.verify().unwrap_err().to_string();
+        assert!(error.contains("does not match its checksum"), "{}", error);
+        assert!(matches!(backup.integrity(), Integrity::Damaged(_)));
+
+        // Without the manifest, the object's own hash still catches it
+        fs::remove_file(backup.manifest_path()).unwrap();
+        let error = backup.verify().unwrap_err().to_string();
+        assert!(error.contains("does not match its hash"), "{}", error);
+    }
+
+    #[test]
+    fn manifest_lists_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = store(dir.path(), &["first"]);
+        let backup = store.create(None).unwrap();
+
+        fs::remove_file(objects(&store).remove(0)).unwrap();
+        let error = backup.verify().unwrap_err().to_string();
+        assert!(error.contains("is missing"), "{}", error);
+    }
+
+    #[test]
+    fn unchanged_notes_are_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, mut notes) = store(dir.path(), &["kept", "edited"]);
+        store.create(Some("a")).unwrap();
+        assert_eq!(objects(&store).len(), 2);
+
+        notes[1].content = "edited again".to_string();
+        write_notes(&store, &notes);
+        let second = store.create(Some("b")).unwrap();
+        assert_eq!(objects(&store).len(), 3);
+        assert_eq!(second.verify().unwrap()[1].content, "edited again");
+    }
+
+    #[test]
+    fn unused_objects_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, mut notes) = store(dir.path(), &["kept", "edited"]);
+        let first = store.create(Some("a")).unwrap();
+        notes[1].content = "edited again".to_string();
+        write_notes(&store, &notes);
+        let second = store.create(Some("b")).unwrap();
+
+        fs::remove_file(&first.path).unwrap();
+        store.remove_unused_objects().unwrap();
+        assert_eq!(objects(&store).len(), 2);
+        assert_eq!(second.integrity(), Integrity::Verified);
+    }
+
+    #[test]
+    fn unreadable_snapshot_keeps_every_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, mut notes) = store(dir.path(), &["first"]);
+        let first = store.create(Some("a")).unwrap();
+        notes[0].content = "changed".to_string();
+        write_notes(&store, &notes);
+        store.create(Some("b")).unwrap();
+
+        fs::write(&first.path, "not json").unwrap();
+        store.remove_unused_objects().unwrap();
+        assert_eq!(objects(&store).len(), 2);
+    }
+
+    #[test]
+    fn retention_keeps_latest_and_one_per_period() {
+        let day = |d: u32, h: u32| Some(Local.with_ymd_and_hms(2024, 3, d, h, 0, 0).unwrap());
+        // Newest first: two on the 10th, one on the 9th, two on the 1st
+        let backups = vec![
+            backup("20240310-180000000-auto", day(10, 18)),
+            backup("20240310-090000000-auto", day(10, 9)),
+            backup("20240309-090000000-auto", day(9, 9)),
+            backup("20240301-180000000-auto", day(1, 18)),
+            backup("20240301-090000000-auto", day(1, 9)),
+        ];
+        let retention = BackupRetention { keep_last: 1, keep_daily: 2, keep_weekly: 0, keep_monthly: 0 };
+        let ids: Vec<String> = expired(backups.clone(), &retention).into_iter().map(|backup| backup.id).collect();
+        assert_eq!(ids, ["20240310-090000000-auto", "20240301-180000000-auto", "20240301-090000000-auto"]);
+
+        // The month keeps its newest backup, the 10th's
+        let retention = BackupRetention { keep_last: 0, keep_daily: 0, keep_weekly: 0, keep_monthly: 1 };
+        assert_eq!(expired(backups, &retention).len(), 4);
+    }
+
+    #[test]
+    fn retention_skips_manual_and_undated_backups() {
+        let backups = vec![
+            backup("20240310-180000000-pre-restore", None),
+            backup("20240310-090000000", None),
+            backup("20240309-090000000-auto", None),
+        ];
+        let retention = BackupRetention { keep_last: 0, keep_daily: 0, keep_weekly: 0, keep_monthly: 0 };
+        assert!(expired(backups, &retention).is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::config::CaptureConfig;
+use crate::notes::Note;
+
+/// Where a new note was written, for the per-source tags in the config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    App,
+    Widget,
+    Cli,
+    Api,
+}
+
+/// Note text typed in one go, with its inline `#tag` and `@notebook` words
+/// pulled out. Shared by the widget, insert mode and the `add` command.
+///
+/// Syntax: a word starting with `#` is a tag and one starting with `@` names
+/// the notebook (the last one wins). Both are removed from the text, along
+/// with trailing punctuation. A lone `#` (a Markdown heading), numbers like
+/// `#1`, and anything in backticks or a fenced code block are left alone;
+/// `\#word` and `\@word` keep the word, without the backslash.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capture {
+    pub content: String,
+    pub tags: Vec<String>,
+    pub notebook: Option<String>,
+}
+
+impl Capture {
+    pub fn parse(text: &str) -> Self {
+        let mut capture = Capture::default();
+        let mut lines = Vec::new();
+        let mut fenced = false;
+
+        for line in text.lines() {
+            if line.trim_start().starts_with("```") {
+                fenced = !fenced;
+            }
+            if fenced || line.trim_start().starts_with("```") {
+                lines.push(line.to_string());
+                continue;
+            }
+
+            let mut words = Vec::new();
+            let mut changed = false;
+            let mut in_code = false;
+            for word in line.split_whitespace() {
+                if !in_code {
+                    if let Some(kept) = word.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '@'])) {
+                        words.push(kept);
+                        changed = true;
+                        continue;
+                    }
+                    if let Some(tag) = word.strip_prefix('#').map(trim_punctuation).filter(|tag| is_name(tag)) {
+                        if !capture.tags.iter().any(|existing| existing == tag) {
+                            capture.tags.push(tag.to_string());
+                        }
+                        changed = true;
+                        continue;
+                    }
+                    if let Some(notebook) = word.strip_prefix('@').map(trim_punctuation).filter(|name| is_name(name)) {
+                        capture.notebook = Some(notebook.to_string());
+                        changed = true;
+                        continue;
+                    }
+                }
+                // Backticks toggle inline code, which is left as typed
+                if word.matches('`').count() % 2 == 1 {
+                    in_code = !in_code;
+                }
+                words.push(word);
+            }
+
+            match (changed, words.is_empty()) {
+                // A line of nothing but tags goes away entirely
+                (true, true) => {}
+                (true, false) => lines.push(format!("{}{}", indentation(line), words.join(" "))),
+                (false, _) => lines.push(line.to_string()),
+            }
+        }
+
+        capture.content = lines.join("\n").trim().to_string();
+        capture
+    }
+
+    /// Adds the captured tags to `note` and files it under the notebook, if
+    /// one was given.
+    pub fn apply(&self, note: &mut Note) {
+        for tag in &self.tags {
+            if !note.tags.contains(tag) {
+                note.tags.push(tag.clone());
+            }
+        }
+        if let Some(notebook) = &self.notebook {
+            note.notebook = Some(notebook.clone());
+        }
+    }
+
+    /// A new note from the capture.
+    pub fn into_note(self) -> Note {
+        let mut note = Note::new(self.content.clone());
+        self.apply(&mut note);
+        note
+    }
+}
+
+/// Adds the tags the config gives new notes: the default ones, those for
+/// `source` and those of the rules matching the note's text.
+pub fn add_config_tags(note: &mut Note, source: Source, config: &CaptureConfig) {
+    let by_source = match source {
+        Source::App => &config.app_tags,
+        Source::Widget => &config.widget_tags,
+        Source::Cli => &config.cli_tags,
+        Source::Api => &config.api_tags,
+    };
+    let by_rule = config
+        .rules
+        .iter()
+        .filter(|rule| !rule.contains.is_empty() && note.content.contains(&rule.contains))
+        .map(|rule| &rule.tag);
+    let tags: Vec<String> = config
+        .default_tags
+        .iter()
+        .chain(by_source)
+        .chain(by_rule)
+        .map(|tag| tag.trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    for tag in tags {
+        if !note.tags.contains(&tag) {
+            note.tags.push(tag);
+        }
+    }
+}
+
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')'])
+}
+
+/// Whether `name` can be a tag or notebook: not empty, not just a number and
+/// not another marker, so `##` and `#1` stay text.
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(['#', '@']) && !name.chars().all(|c| c.is_ascii_digit())
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TagRule;
+
+    #[test]
+    fn pulls_out_tags_and_the_last_notebook() {
+        let capture = Capture::parse("Call Sam about #work, then #home! @inbox @Errands.");
+        assert_eq!(capture.content, "Call Sam about then");
+        assert_eq!(capture.tags, ["work", "home"]);
+        assert_eq!(capture.notebook.as_deref(), Some("Errands"));
+    }
+
+    #[test]
+    fn drops_lines_of_only_tags_and_keeps_indentation() {
+        let capture = Capture::parse("# Plan\n  - step #todo #todo\n#later #todo\n");
+        assert_eq!(capture.content, "# Plan\n  - step");
+        assert_eq!(capture.tags, ["todo", "later"]);
+    }
+
+    #[test]
+    fn leaves_numbers_markers_and_escapes_as_text() {
+        let capture = Capture::parse(r"Fix issue #1 ## then \#hashtag and \@name");
+        assert_eq!(capture.content, "Fix issue #1 ## then #hashtag and @name");
+        assert!(capture.tags.is_empty());
+        assert_eq!(capture.notebook, None);
+    }
+
+    #[test]
+    fn leaves_code_alone() {
+        let capture = Capture::parse("Run `git log #x` and `#y` #real\n```\n#include <stdio.h> @main\n```\n#after");
+        assert_eq!(capture.content, "Run `git log #x` and `#y`\n```\n#include <stdio.h> @main\n```");
+        assert_eq!(capture.tags, ["real", "after"]);
+        assert_eq!(capture.notebook, None);
+    }
+
+    #[test]
+    fn applies_to_notes_without_repeating_tags() {
+        let mut note = Note::new("x".to_string());
+        note.tags.push("work".to_string());
+        let capture = Capture::parse("#work #new @books");
+        capture.apply(&mut note);
+        assert_eq!(note.tags, ["work", "new"]);
+        assert_eq!(note.notebook.as_deref(), Some("books"));
+    }
+
+    #[test]
+    fn adds_tags_from_the_config() {
+        let config = CaptureConfig {
+            default_tags: vec!["#inbox".to_string(), "#".to_string()],
+            cli_tags: vec!["cli".to_string()],
+            widget_tags: vec!["widget".to_string()],
+            rules: vec![
+                TagRule { contains: "invoice".to_string(), tag: "money".to_string() },
+                TagRule { contains: String::new(), tag: "never".to_string() },
+            ],
+            ..CaptureConfig::default()
+        };
+        let mut note = Note::new("Pay the invoice".to_string());
+        note.tags.push("inbox".to_string());
+        add_config_tags(&mut note, Source::Cli, &config);
+        assert_eq!(note.tags, ["inbox", "cli", "money"]);
+    }
+}
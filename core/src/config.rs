@@ -0,0 +1,884 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::dates::{self, TimestampStyle, Zone};
+use crate::error::UserError;
+use crate::meeting;
+use crate::natural::{self, WeekStart};
+use crate::notes::{ExportFormat, SortMode};
+use crate::profile;
+use crate::webhooks::Webhook;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub notes_dir: PathBuf,
+    pub auto_start: bool,
+    pub widget_hotkey: String,
+    pub theme: Theme,
+    pub max_recent_notes: usize,
+    pub backup_enabled: bool,
+    pub backup_interval_hours: u64,
+    /// How many automatic backups to keep
+    #[serde(default)]
+    pub backup_retention: BackupRetention,
+    #[serde(default)]
+    pub sort_mode: SortMode,
+    #[serde(default = "default_zen_max_width")]
+    pub zen_max_width: u16,
+    #[serde(default)]
+    pub zen_typewriter_scrolling: bool,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    #[serde(default)]
+    pub timestamp_style: TimestampStyle,
+    /// Time zone dates are shown and typed in: empty or `system`, `utc`, or
+    /// an offset such as `+05:30`
+    #[serde(default)]
+    pub timezone: String,
+    #[serde(default)]
+    pub onboarding_completed: bool,
+    /// High contrast, text markers instead of color/emoji, simpler layout
+    #[serde(default)]
+    pub accessibility_mode: bool,
+    /// External editor command; falls back to $VISUAL, then $EDITOR
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Command recording a voice memo into the file named last, until
+    /// interrupted; a known recorder on the PATH when unset
+    #[serde(default)]
+    pub recorder: Option<String>,
+    /// Periodic export run by the background mode
+    #[serde(default)]
+    pub scheduled_export: ScheduledExport,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub pomodoro: PomodoroConfig,
+    #[serde(default)]
+    pub ocr: OcrConfig,
+    #[serde(default)]
+    pub tray: TrayConfig,
+    #[serde(default)]
+    pub keymap: Keymap,
+    /// Locale such as `en-US`; empty for the one in `LC_ALL`, `LC_TIME` or
+    /// `LANG`. Sets the defaults of the two settings below.
+    #[serde(default)]
+    pub locale: String,
+    #[serde(default)]
+    pub week_start: WeekStart,
+    /// strftime formats accepted for typed dates, besides words like
+    /// `tomorrow`; empty for the locale's
+    #[serde(default)]
+    pub date_input_formats: Vec<String>,
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// URLs told when notes are created, changed, deleted or tagged
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub feed: FeedConfig,
+    #[serde(default)]
+    pub shell_log: ShellLogConfig,
+    /// Words to write in the app each day, shown in the footer; 0 for none
+    #[serde(default)]
+    pub word_goal: usize,
+    #[serde(default)]
+    pub meeting: MeetingConfig,
+    #[serde(default)]
+    pub shorthand: ShorthandConfig,
+    #[serde(default)]
+    pub tidy: TidyConfig,
+}
+
+/// Keys for moving around the notes list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    /// Arrow keys, Home and End
+    #[default]
+    Standard,
+    /// Also `j`/`k` to move, `g`/`G` for the first and last note and `/`
+    /// to search
+    Vim,
+}
+
+fn default_zen_max_width() -> u16 {
+    80
+}
+
+fn default_date_format() -> String {
+    dates::DEFAULT_DATE_FORMAT.to_string()
+}
+
+/// Keeps an always-current copy of the notes in a directory, for other
+/// tools to index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledExport {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory to export into; defaults to `export` in the notes directory
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// `markdown` writes one file per note; `json` and `html` write a
+    /// single `notes.json` or `notes.html`, and a format a plugin adds a
+    /// `notes` file with its extension
+    #[serde(default = "default_export_format")]
+    pub format: String,
+    #[serde(default = "default_export_interval")]
+    pub interval_hours: u64,
+}
+
+fn default_export_format() -> String {
+    "markdown".to_string()
+}
+
+fn default_export_interval() -> u64 {
+    24
+}
+
+impl Default for ScheduledExport {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            format: default_export_format(),
+            interval_hours: default_export_interval(),
+        }
+    }
+}
+
+/// Automatic backups kept when old ones are pruned: the newest
+/// `keep_last`, plus the newest of each of the last `keep_daily` days,
+/// `keep_weekly` weeks and `keep_monthly` months that have one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupRetention {
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+}
+
+impl Default for BackupRetention {
+    fn default() -> Self {
+        Self {
+            keep_last: 10,
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_monthly: 12,
+        }
+    }
+}
+
+/// Pomodoro timer lengths, in minutes. A long break follows every
+/// `long_break_every` pomodoros.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_minutes: u64,
+    pub short_break_minutes: u64,
+    pub long_break_minutes: u64,
+    pub long_break_every: u32,
+    /// Title of a note to log finished pomodoros in, created if missing;
+    /// by default they are logged in the note worked on
+    pub log_note: Option<String>,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            log_note: None,
+        }
+    }
+}
+
+/// Reading the text in images attached to notes, in builds with the `ocr`
+/// feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrConfig {
+    pub enabled: bool,
+    /// Tesseract language codes, joined with `+` for several: `eng+deu`
+    pub language: String,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self { enabled: true, language: "eng".to_string() }
+    }
+}
+
+/// The system tray icon of background mode, in builds with the `tray`
+/// feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayConfig {
+    pub enabled: bool,
+    /// Command that opens a terminal window running the command after it,
+    /// e.g. `alacritty -e`; empty for `$TERMINAL -e` or else
+    /// `x-terminal-emulator -e`. Windows opens a console of its own.
+    pub terminal: String,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self { enabled: true, terminal: String::new() }
+    }
+}
+
+/// Tags given to new notes by where they were written and what they say,
+/// on top of their inline `#tags`, and whether repeats are kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Tags for every new note
+    pub default_tags: Vec<String>,
+    /// Tags for notes written in the app
+    pub app_tags: Vec<String>,
+    /// Tags for notes from the quick widget
+    pub widget_tags: Vec<String>,
+    /// Tags for notes from `turbo-notes add` and `turbo-notes quick`
+    pub cli_tags: Vec<String>,
+    /// Tags for notes created through the REST API
+    pub api_tags: Vec<String>,
+    /// Tags for notes whose text matches
+    pub rules: Vec<TagRule>,
+    /// Do not add a note with the same text as one already there
+    pub skip_duplicates: bool,
+    /// Offer tags already in use that fit a note written in the app, when it
+    /// is saved
+    pub suggest_tags: bool,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            default_tags: Vec::new(),
+            app_tags: Vec::new(),
+            widget_tags: Vec::new(),
+            cli_tags: Vec::new(),
+            api_tags: Vec::new(),
+            rules: Vec::new(),
+            skip_duplicates: false,
+            suggest_tags: true,
+        }
+    }
+}
+
+/// Tags new notes whose text contains `contains` (case-sensitive) with
+/// `tag`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagRule {
+    pub contains: String,
+    pub tag: String,
+}
+
+/// The REST API served by `turbo-notes serve`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Address to listen on; `0.0.0.0` to be reachable from other devices
+    pub bind: String,
+    pub port: u16,
+    /// Sent by clients as `Authorization: Bearer <token>`; made up by the
+    /// first `serve` if empty
+    pub token: String,
+    /// Refuse to create, change or delete notes
+    pub read_only: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".to_string(),
+            port: 7777,
+            token: String::new(),
+            read_only: true,
+        }
+    }
+}
+
+/// The Atom feed of `turbo-notes feed` and the API's `/v1/feed.atom`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Only notes with this tag are in the feed
+    pub tag: String,
+    pub title: String,
+    /// Where the feed is published, such as `https://example.com/notes/`;
+    /// entries link to `<link>#<note id>` when set
+    pub link: String,
+    pub author: String,
+    /// Most recently updated notes to include
+    pub limit: usize,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            tag: "public".to_string(),
+            title: "Turbo Notes".to_string(),
+            link: String::new(),
+            author: String::new(),
+            limit: 20,
+        }
+    }
+}
+
+/// The shell hook of `turbo-notes shell-hook`, which logs commands to a
+/// note for each day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellLogConfig {
+    /// Only commands containing this are logged, with what follows it as a
+    /// remark; empty to log every command
+    pub marker: String,
+    /// strftime format of the day's log note title
+    pub title: String,
+}
+
+impl Default for ShellLogConfig {
+    fn default() -> Self {
+        Self {
+            marker: "# note:".to_string(),
+            title: "Shell log %Y-%m-%d".to_string(),
+        }
+    }
+}
+
+/// Meeting notes started with `N` in the app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeetingConfig {
+    /// Text the note starts with, with the snippet variables such as
+    /// `{{date}}` filled in
+    pub template: String,
+    /// Tag put on meeting notes
+    pub tag: String,
+}
+
+impl Default for MeetingConfig {
+    fn default() -> Self {
+        Self {
+            template: meeting::DEFAULT_TEMPLATE.to_string(),
+            tag: "meeting".to_string(),
+        }
+    }
+}
+
+/// Text expanded as it is typed in the app: `:name:` emoji and these
+/// shortcuts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShorthandConfig {
+    pub enabled: bool,
+    /// What each shortcut, such as `;sig`, expands to
+    pub shortcuts: BTreeMap<String, String>,
+}
+
+impl Default for ShorthandConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shortcuts: BTreeMap::new(),
+        }
+    }
+}
+
+/// Tidying notes' Markdown when they are saved; `#tidy` and `#notidy` tags
+/// turn it on and off for a note.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TidyConfig {
+    pub enabled: bool,
+    /// Column to wrap long lines at; 0 leaves them
+    pub wrap: usize,
+}
+
+/// Where `turbo-notes sync` keeps the shared copy of the notes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    #[serde(default)]
+    pub backend: SyncBackendKind,
+    #[serde(default)]
+    pub s3: S3Config,
+    #[serde(default)]
+    pub server: ServerSyncConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncBackendKind {
+    #[default]
+    None,
+    /// Amazon S3 or a compatible store such as MinIO or Backblaze B2
+    S3,
+    /// A `turbo-notes-server`
+    Server,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct S3Config {
+    /// Service URL, e.g. `http://localhost:9000` for MinIO; empty means AWS
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Key prefix, so one bucket can hold several stores
+    pub prefix: String,
+    /// Falls back to `AWS_ACCESS_KEY_ID` when empty
+    pub access_key_id: String,
+    /// Falls back to `AWS_SECRET_ACCESS_KEY` when empty
+    pub secret_access_key: String,
+    /// Address buckets as `endpoint/bucket` instead of `bucket.endpoint`
+    pub path_style: bool,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            prefix: "turbo-notes/".to_string(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            path_style: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSyncConfig {
+    /// e.g. `https://notes.example.com`
+    pub url: String,
+    /// Falls back to `TURBO_NOTES_SYNC_TOKEN` when empty
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Theme {
+    pub primary_color: String,
+    pub secondary_color: String,
+    pub background_color: String,
+    pub text_color: String,
+    pub accent_color: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let notes_dir = Self::default_notes_dir(profile::active().as_deref());
+        
+        Self {
+            notes_dir,
+            auto_start: false,
+            widget_hotkey: "Ctrl+Shift+N".to_string(),
+            theme: Theme::default(),
+            max_recent_notes: 100,
+            backup_enabled: true,
+            backup_interval_hours: 24,
+            backup_retention: BackupRetention::default(),
+            sort_mode: SortMode::default(),
+            zen_max_width: default_zen_max_width(),
+            zen_typewriter_scrolling: false,
+            date_format: default_date_format(),
+            timestamp_style: TimestampStyle::default(),
+            timezone: String::new(),
+            onboarding_completed: false,
+            accessibility_mode: false,
+            editor: None,
+            recorder: None,
+            scheduled_export: ScheduledExport::default(),
+            sync: SyncConfig::default(),
+            pomodoro: PomodoroConfig::default(),
+            ocr: OcrConfig::default(),
+            tray: TrayConfig::default(),
+            keymap: Keymap::default(),
+            locale: String::new(),
+            week_start: WeekStart::default(),
+            date_input_formats: Vec::new(),
+            capture: CaptureConfig::default(),
+            api: ApiConfig::default(),
+            webhooks: Vec::new(),
+            feed: FeedConfig::default(),
+            shell_log: ShellLogConfig::default(),
+            word_goal: 0,
+            meeting: MeetingConfig::default(),
+            shorthand: ShorthandConfig::default(),
+            tidy: TidyConfig::default(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            primary_color: "cyan".to_string(),
+            secondary_color: "blue".to_string(),
+            background_color: "black".to_string(),
+            text_color: "white".to_string(),
+            accent_color: "yellow".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn colors(&self) -> [(&'static str, &str); 5] {
+        [
+            ("primary_color", &self.primary_color),
+            ("secondary_color", &self.secondary_color),
+            ("background_color", &self.background_color),
+            ("text_color", &self.text_color),
+            ("accent_color", &self.accent_color),
+        ]
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let config_path = Self::config_file_path()?;
+        
+        if config_path.exists() {
+            Self::load_from(&config_path)
+        } else {
+            let config = Config::default();
+            config.save()?;
+            Ok(config)
+        }
+    }
+
+    /// Reads a config file without checking it, e.g. another profile's.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Reads the config file as it is now, rejecting it if it does not
+    /// parse or validate. Unlike `load`, never writes a default.
+    pub fn read() -> Result<Self> {
+        let content = fs::read_to_string(Self::config_file_path()?)?;
+        let config: Config = serde_json::from_str(&content).map_err(|e| UserError::Invalid(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// When the config file was last written, to notice edits.
+    pub fn modified() -> Option<SystemTime> {
+        fs::metadata(Self::config_file_path().ok()?).ok()?.modified().ok()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::config_file_path()?)
+    }
+
+    pub fn save_to(&self, config_path: &Path) -> Result<()> {
+        // Create config directory if it doesn't exist
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(config_path, content)?;
+        Ok(())
+    }
+
+    /// Config file of the profile in use.
+    pub fn config_file_path() -> Result<PathBuf> {
+        profile::config_file(profile::active().as_deref())
+    }
+
+    /// Where a profile keeps its notes unless told otherwise: `profiles/<name>`
+    /// inside the default profile's directory.
+    pub fn default_notes_dir(profile: Option<&str>) -> PathBuf {
+        let dir = if let Some(data_dir) = dirs::data_dir() {
+            data_dir.join("turbo-notes")
+        } else if let Some(home_dir) = dirs::home_dir() {
+            home_dir.join(".turbo-notes")
+        } else {
+            PathBuf::from("./turbo-notes-data")
+        };
+        match profile {
+            Some(name) => dir.join("profiles").join(name),
+            None => dir,
+        }
+    }
+
+    pub fn set_notes_dir(&mut self, path: PathBuf) -> Result<()> {
+        self.notes_dir = path;
+        self.save()
+    }
+
+    pub fn enable_auto_start(&mut self) -> Result<()> {
+        self.auto_start = true;
+        self.save()
+    }
+
+    pub fn disable_auto_start(&mut self) -> Result<()> {
+        self.auto_start = false;
+        self.save()
+    }
+
+    pub fn set_widget_hotkey(&mut self, hotkey: String) -> Result<()> {
+        self.widget_hotkey = hotkey;
+        self.save()
+    }
+
+    pub fn update_theme(&mut self, theme: Theme) -> Result<()> {
+        self.theme = theme;
+        self.save()
+    }
+
+    pub fn set_sort_mode(&mut self, mode: SortMode) -> Result<()> {
+        self.sort_mode = mode;
+        self.save()
+    }
+
+    /// Formats a timestamp for display according to the configured style.
+    pub fn format_timestamp(&self, timestamp: DateTime<Utc>) -> String {
+        dates::format_timestamp(timestamp, self.timestamp_style, &self.date_format, self.zone())
+    }
+
+    /// The configured time zone; the system's if it is not valid.
+    pub fn zone(&self) -> Zone {
+        Zone::parse(&self.timezone).unwrap_or_default()
+    }
+
+    /// Looks up a setting by its dotted key, e.g. `theme.primary_color`.
+    pub fn get_value(&self, key: &str) -> Result<Value> {
+        let mut value = serde_json::to_value(self)?;
+        Ok(lookup(&mut value, key)?.take())
+    }
+
+    /// Sets a setting by its dotted key. `value` is parsed according to the
+    /// setting's current type and the result is validated before it is saved.
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut json = serde_json::to_value(&*self)?;
+        let slot = lookup(&mut json, key)?;
+
+        *slot = match slot {
+            Value::Bool(_) => match value.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Value::Bool(true),
+                "false" | "no" | "off" | "0" => Value::Bool(false),
+                _ => return Err(UserError::Invalid(format!("{} expects true or false, got '{}'", key, value)).into()),
+            },
+            Value::Number(_) => match serde_json::from_str::<Value>(value) {
+                Ok(number @ Value::Number(_)) => number,
+                _ => return Err(UserError::Invalid(format!("{} expects a number, got '{}'", key, value)).into()),
+            },
+            // A JSON list, for items with commas in them or lists of groups
+            // such as `webhooks`; their fields are checked below
+            Value::Array(_) if value.trim_start().starts_with('[') => serde_json::from_str::<Value>(value)
+                .ok()
+                .filter(Value::is_array)
+                .ok_or_else(|| UserError::Invalid(format!("{} expects a JSON list, got '{}'", key, value)))?,
+            // Lists of words such as tags: comma-separated
+            Value::Array(items) if items.iter().all(Value::is_string) => Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            ),
+            Value::Object(_) | Value::Array(_) => {
+                return Err(UserError::Invalid(format!("{} is a group of settings; set its fields individually", key)).into())
+            }
+            // "null" unsets optional settings such as `editor`
+            _ if value == "null" => Value::Null,
+            _ => Value::String(value.to_string()),
+        };
+
+        let updated: Config = serde_json::from_value(json)
+            .map_err(|e| UserError::Invalid(format!("Invalid value for {}: {}", key, e)))?;
+        updated.validate()?;
+        *self = updated;
+        self.save()
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        self.notes_dir.join("backups")
+    }
+
+    pub fn scheduled_export_dir(&self) -> PathBuf {
+        match &self.scheduled_export.dir {
+            Some(dir) => dir.clone(),
+            None => self.notes_dir.join("export"),
+        }
+    }
+
+    pub fn should_backup(&self) -> bool {
+        self.backup_enabled
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        // Ensure notes directory exists or can be created
+        if !self.notes_dir.exists() {
+            fs::create_dir_all(&self.notes_dir)?;
+        }
+
+        // Validate hotkey format (basic check)
+        if self.widget_hotkey.is_empty() {
+            return Err(UserError::Invalid("Widget hotkey cannot be empty".into()).into());
+        }
+
+        if !dates::is_valid_format(&self.date_format) {
+            return Err(UserError::Invalid(format!("Invalid date format: {}", self.date_format)).into());
+        }
+
+        if Zone::parse(&self.timezone).is_none() {
+            return Err(UserError::Invalid(format!(
+                "Invalid timezone: {} (expected system, utc or an offset such as +05:30)",
+                self.timezone
+            ))
+            .into());
+        }
+
+        let locale = &self.locale;
+        if !locale.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@')) {
+            return Err(UserError::Invalid(format!("Invalid locale: {} (e.g. en-US or de_DE.UTF-8)", locale)).into());
+        }
+        if let Some(format) = self.date_input_formats.iter().find(|format| !natural::is_date_format(format)) {
+            return Err(UserError::Invalid(format!("Invalid date input format: {} (e.g. %d/%m/%Y)", format)).into());
+        }
+
+        for (name, color) in self.theme.colors() {
+            if !is_color(color) {
+                return Err(UserError::Invalid(format!("Invalid color for theme.{}: {}", name, color)).into());
+            }
+        }
+
+        if self.zen_max_width < 20 {
+            return Err(UserError::Invalid("Zen max width must be at least 20 columns".into()).into());
+        }
+
+        // Other names are export formats of plugins, looked up as the export runs
+        let format = &self.scheduled_export.format;
+        if ExportFormat::from_name(format).is_none() && (format.trim().is_empty() || format == "jex") {
+            return Err(UserError::Invalid(format!(
+                "Invalid scheduled export format: {} (expected markdown, json, html or a plugin's format)",
+                format
+            ))
+            .into());
+        }
+        if self.scheduled_export.interval_hours == 0 {
+            return Err(UserError::Invalid("Scheduled export interval must be greater than 0".into()).into());
+        }
+
+        let capture = &self.capture;
+        let rule_tags = capture.rules.iter().map(|rule| &rule.tag);
+        let tags = [
+            &capture.default_tags,
+            &capture.app_tags,
+            &capture.widget_tags,
+            &capture.cli_tags,
+            &capture.api_tags,
+        ];
+        if let Some(tag) = tags.into_iter().flatten().chain(rule_tags).find(|tag| !is_tag(tag)) {
+            return Err(UserError::Invalid(format!("Invalid tag in capture: '{}' (one word, no spaces)", tag)).into());
+        }
+        if capture.rules.iter().any(|rule| rule.contains.is_empty()) {
+            return Err(UserError::Invalid("capture.rules need text to look for in contains".into()).into());
+        }
+
+        if self.api.port == 0 {
+            return Err(UserError::Invalid("api.port must be 1 to 65535".into()).into());
+        }
+
+        if let Some(hook) = self
+            .webhooks
+            .iter()
+            .find(|hook| !(hook.url.starts_with("http://") || hook.url.starts_with("https://")))
+        {
+            return Err(UserError::Invalid(format!("Invalid webhook URL: '{}' (expected http:// or https://)", hook.url)).into());
+        }
+
+        if !is_tag(&self.feed.tag) {
+            return Err(UserError::Invalid(format!("Invalid feed.tag: '{}' (one word, no spaces)", self.feed.tag)).into());
+        }
+        if !(self.feed.link.is_empty() || self.feed.link.starts_with("http://") || self.feed.link.starts_with("https://")) {
+            return Err(UserError::Invalid(format!("Invalid feed.link: '{}' (expected http:// or https://)", self.feed.link)).into());
+        }
+        if self.feed.limit == 0 {
+            return Err(UserError::Invalid("feed.limit must be at least 1".into()).into());
+        }
+
+        if !dates::is_valid_format(&self.shell_log.title) || self.shell_log.title.trim().is_empty() {
+            return Err(UserError::Invalid(format!("Invalid shell_log.title format: '{}'", self.shell_log.title)).into());
+        }
+
+        if self.meeting.tag.trim().is_empty() || self.meeting.tag.contains(char::is_whitespace) {
+            return Err(UserError::Invalid(format!("Invalid meeting.tag: '{}' (expected one word)", self.meeting.tag)).into());
+        }
+
+        if let Some(shortcut) = self.shorthand.shortcuts.keys().find(|key| key.is_empty() || key.contains(char::is_whitespace)) {
+            return Err(UserError::Invalid(format!("Invalid shorthand shortcut: '{}' (expected one word)", shortcut)).into());
+        }
+
+        let pomodoro = &self.pomodoro;
+        let lengths = [pomodoro.work_minutes, pomodoro.short_break_minutes, pomodoro.long_break_minutes];
+        if lengths.iter().any(|minutes| !(1..=24 * 60).contains(minutes)) {
+            return Err(UserError::Invalid("Pomodoro and break lengths must be 1 to 1440 minutes".into()).into());
+        }
+        if pomodoro.long_break_every == 0 {
+            return Err(UserError::Invalid("pomodoro.long_break_every must be at least 1".into()).into());
+        }
+
+        // Validate backup interval
+        if self.backup_interval_hours == 0 {
+            return Err(UserError::Invalid("Backup interval must be greater than 0".into()).into());
+        }
+        if self.backup_retention.keep_last == 0 {
+            return Err(UserError::Invalid("backup_retention.keep_last must be at least 1".into()).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `color` is a color the TUI can draw: a name like `blue` or
+/// `light-red`, a 256-color index, or `#rrggbb`.
+fn is_color(color: &str) -> bool {
+    const NAMES: [&str; 17] = [
+        "reset", "black", "red", "green", "yellow", "blue", "magenta", "cyan", "gray", "darkgray", "lightred",
+        "lightgreen", "lightyellow", "lightblue", "lightmagenta", "lightcyan", "white",
+    ];
+    let name = color
+        .to_lowercase()
+        .replace([' ', '-', '_'], "")
+        .replace("bright", "light")
+        .replace("grey", "gray")
+        .replace("silver", "gray")
+        .replace("lightblack", "darkgray")
+        .replace("lightwhite", "white")
+        .replace("lightgray", "white");
+    NAMES.contains(&name.as_str())
+        || color.parse::<u8>().is_ok()
+        || (color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Whether `tag` works as a tag, with or without its `#`.
+fn is_tag(tag: &str) -> bool {
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+    !tag.is_empty() && !tag.contains(char::is_whitespace)
+}
+
+fn lookup<'a>(value: &'a mut Value, key: &str) -> Result<&'a mut Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get_mut(part))
+        .ok_or_else(|| UserError::NotFound(format!("Unknown config key '{}'", key)).into())
+}
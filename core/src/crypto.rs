@@ -0,0 +1,116 @@
+use anyhow::Result;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use crate::error::UserError;
+
+/// Prefix of every encrypted payload, so the format can change later
+const MAGIC: &[u8] = b"TNE1";
+const NONCE_LEN: usize = 24;
+pub const SALT_LEN: usize = 16;
+
+/// Symmetric encryption for data leaving the machine: XChaCha20-Poly1305
+/// with a key derived from a passphrase by Argon2id.
+pub struct Cipher {
+    aead: XChaCha20Poly1305,
+}
+
+impl Cipher {
+    pub fn derive(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        Ok(Self::from_key(&derive_key(passphrase, salt)?))
+    }
+
+    pub fn from_key(key: &[u8; 32]) -> Self {
+        Self {
+            aead: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let ciphertext = self
+            .aead
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        Ok([MAGIC, &nonce, &ciphertext].concat())
+    }
+
+    /// Decrypts a payload from [`Cipher::encrypt`]. Fails with
+    /// `UserError::Invalid` if it was encrypted with another key or altered.
+    pub fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let body = payload
+            .strip_prefix(MAGIC)
+            .filter(|body| body.len() > NONCE_LEN)
+            .ok_or_else(|| UserError::Invalid("Not an encrypted Turbo Notes payload".into()))?;
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        self.aead
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| UserError::Invalid("Could not decrypt: wrong passphrase or damaged data".into()).into())
+    }
+}
+
+/// Argon2id key for `passphrase`, for callers that need the key itself.
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Could not derive the encryption key: {}", e))?;
+    Ok(key)
+}
+
+pub fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow::anyhow!("No system randomness: {}", e))?;
+    Ok(bytes)
+}
+
+/// Compares without stopping at the first difference, so response times do
+/// not reveal how much of a guessed token was right.
+pub fn same_bytes(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: [u8; SALT_LEN] = [7; SALT_LEN];
+
+    #[test]
+    fn round_trips() {
+        let cipher = Cipher::derive("correct horse", &SALT).unwrap();
+        let payload = cipher.encrypt(b"a note").unwrap();
+        assert!(payload.starts_with(MAGIC));
+        assert_eq!(cipher.decrypt(&payload).unwrap(), b"a note");
+        // A fresh nonce each time
+        assert_ne!(cipher.encrypt(b"a note").unwrap(), payload);
+    }
+
+    #[test]
+    fn same_passphrase_and_salt_give_same_key() {
+        assert_eq!(derive_key("correct horse", &SALT).unwrap(), derive_key("correct horse", &SALT).unwrap());
+        assert_ne!(derive_key("correct horse", &SALT).unwrap(), derive_key("correct horse", &[8; SALT_LEN]).unwrap());
+    }
+
+    #[test]
+    fn compares_whole_tokens() {
+        assert!(same_bytes(b"secret", b"secret"));
+        assert!(!same_bytes(b"secret", b"secreT"));
+        assert!(!same_bytes(b"secret", b"secret!"));
+        assert!(same_bytes(b"", b""));
+    }
+
+    #[test]
+    fn rejects_wrong_key_and_altered_data() {
+        let cipher = Cipher::from_key(&[1; 32]);
+        let mut payload = cipher.encrypt(b"a note").unwrap();
+        assert!(Cipher::from_key(&[2; 32]).decrypt(&payload).is_err());
+
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        assert!(cipher.decrypt(&payload).is_err());
+        assert!(cipher.decrypt(b"plain text").is_err());
+        assert!(cipher.decrypt(MAGIC).is_err());
+    }
+}
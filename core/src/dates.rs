@@ -0,0 +1,138 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono::format::{Item, StrftimeItems};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+pub const DEFAULT_DATE_FORMAT: &str = "%m/%d %H:%M";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    /// Always use the configured date format
+    #[default]
+    Absolute,
+    /// "just now", "5m ago", "yesterday", then the configured format
+    Smart,
+}
+
+/// Time zone dates are shown and typed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Zone {
+    /// The system's, following its daylight saving changes
+    #[default]
+    System,
+    Utc,
+    /// A fixed offset from UTC, such as `+05:30`
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    /// Reads `system` (or nothing), `utc`, or an offset such as `+05:30`,
+    /// `-0800` or `UTC+2`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        let offset = match text.as_str() {
+            "" | "system" | "local" => return Some(Zone::System),
+            "utc" | "gmt" | "z" => return Some(Zone::Utc),
+            text => text.strip_prefix("utc").or_else(|| text.strip_prefix("gmt")).unwrap_or(text),
+        };
+        let (sign, offset) = match offset.split_at_checked(1)? {
+            ("+", rest) => (1, rest),
+            ("-", rest) => (-1, rest),
+            _ => return None,
+        };
+        let (hours, minutes) = match offset.split_once(':') {
+            Some(split) => split,
+            None if offset.len() == 4 => offset.split_at(2),
+            None => (offset, "0"),
+        };
+        let hours: i32 = hours.parse().ok().filter(|hours| *hours <= 14)?;
+        let minutes: i32 = minutes.parse().ok().filter(|minutes| *minutes < 60)?;
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(Zone::Fixed)
+    }
+
+    /// The clock time in this zone at `time`.
+    pub fn to_local(self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::System => time.with_timezone(&Local).fixed_offset(),
+            Zone::Utc => time.fixed_offset(),
+            Zone::Fixed(offset) => time.with_timezone(&offset),
+        }
+    }
+
+    /// The day it is in this zone at `time`.
+    pub fn day(self, time: DateTime<Utc>) -> NaiveDate {
+        self.to_local(time).date_naive()
+    }
+
+    /// When the clock in this zone shows `local`. A time skipped when the
+    /// clocks go forward is taken as the same time after the change, and a
+    /// time shown twice when they go back as the first of the two.
+    pub fn from_local(self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::System => match Local.from_local_datetime(&local) {
+                LocalResult::Single(time) => Some(time.to_utc()),
+                LocalResult::Ambiguous(first, second) => Some(first.min(second).to_utc()),
+                LocalResult::None => {
+                    // The gap is an hour nearly everywhere there is one
+                    let after = Local.from_local_datetime(&(local + Duration::hours(1))).earliest()?;
+                    Some(after.to_utc())
+                }
+            },
+            Zone::Utc => Some(local.and_utc()),
+            Zone::Fixed(offset) => offset.from_local_datetime(&local).single().map(|time| time.to_utc()),
+        }
+    }
+}
+
+/// Returns true if `format` only contains valid strftime specifiers.
+pub fn is_valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+pub fn format_timestamp(timestamp: DateTime<Utc>, style: TimestampStyle, format: &str, zone: Zone) -> String {
+    if style == TimestampStyle::Smart {
+        if let Some(relative) = format_relative(timestamp, Utc::now(), zone) {
+            return relative;
+        }
+    }
+    format_absolute(timestamp, format, zone)
+}
+
+/// Formats in `zone`, falling back to the default format if `format` is
+/// invalid rather than panicking inside chrono.
+pub fn format_absolute(timestamp: DateTime<Utc>, format: &str, zone: Zone) -> String {
+    let local = zone.to_local(timestamp);
+    let mut output = String::new();
+    if write!(output, "{}", local.format(format)).is_err() {
+        output.clear();
+        let _ = write!(output, "{}", local.format(DEFAULT_DATE_FORMAT));
+    }
+    output
+}
+
+/// Describes `timestamp` relative to `now`, or `None` once it is old enough
+/// that a full date reads better.
+pub fn format_relative(timestamp: DateTime<Utc>, now: DateTime<Utc>, zone: Zone) -> Option<String> {
+    let elapsed = now.signed_duration_since(timestamp);
+    if elapsed.num_seconds() < 0 {
+        return None;
+    }
+
+    if elapsed.num_minutes() < 1 {
+        return Some("just now".to_string());
+    }
+    if elapsed.num_hours() < 1 {
+        return Some(format!("{}m ago", elapsed.num_minutes()));
+    }
+
+    let local_day = zone.day(timestamp);
+    let today = zone.day(now);
+    if local_day == today {
+        return Some(format!("{}h ago", elapsed.num_hours()));
+    }
+    if today.pred_opt() == Some(local_day) {
+        return Some("yesterday".to_string());
+    }
+    None
+}
@@ -0,0 +1,11 @@
+/// Errors caused by what the user asked for rather than by the environment.
+/// The command line maps them to their own exit statuses.
+#[derive(Debug, thiserror::Error)]
+pub enum UserError {
+    /// A note, backup, setting or file that does not exist
+    #[error("{0}")]
+    NotFound(String),
+    /// Input that was understood but not accepted
+    #[error("{0}")]
+    Invalid(String),
+}
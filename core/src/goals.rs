@@ -0,0 +1,86 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LOG_FILE: &str = "goals.json";
+
+/// Words written on a day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Day {
+    pub words: usize,
+    /// Set when the goal was reached that day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reached: Option<Reached>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Reached {
+    pub goal: usize,
+    pub at: DateTime<Utc>,
+}
+
+/// Words written in the app each day, toward the daily word goal, kept in
+/// `goals.json` in the notes directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WordLog {
+    pub days: BTreeMap<NaiveDate, Day>,
+}
+
+fn path(notes_dir: &Path) -> PathBuf {
+    notes_dir.join(LOG_FILE)
+}
+
+impl WordLog {
+    pub fn load(notes_dir: &Path) -> Result<Self> {
+        match fs::read_to_string(path(notes_dir)) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the log through a temporary file, so a crash mid-write leaves
+    /// the previous one.
+    pub fn save(&self, notes_dir: &Path) -> Result<()> {
+        let path = path(notes_dir);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    pub fn words(&self, day: NaiveDate) -> usize {
+        self.days.get(&day).map_or(0, |day| day.words)
+    }
+
+    /// Counts `words` more written on `day`. Returns whether that reached
+    /// `goal`, only the first time it is reached that day; a goal of 0 is
+    /// none.
+    pub fn add(&mut self, day: NaiveDate, words: usize, goal: usize) -> bool {
+        let entry = self.days.entry(day).or_default();
+        entry.words += words;
+        if goal == 0 || entry.words < goal || entry.reached.is_some() {
+            return false;
+        }
+        entry.reached = Some(Reached { goal, at: Utc::now() });
+        true
+    }
+
+    /// Days the goal was reached, newest first.
+    pub fn reached(&self) -> Vec<(NaiveDate, Reached)> {
+        self.days
+            .iter()
+            .rev()
+            .filter_map(|(day, entry)| entry.reached.map(|reached| (*day, reached)))
+            .collect()
+    }
+}
+
+/// Words added by changing `old` into `new`. Only the count is compared, so
+/// rewriting counts what it adds and deleting counts nothing.
+pub fn written(old: &str, new: &str) -> usize {
+    new.split_whitespace().count().saturating_sub(old.split_whitespace().count())
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::links;
+use crate::notes::Note;
+
+/// Rounds of the force layout
+const ROUNDS: usize = 200;
+
+/// Notes joined by `[[wiki links]]`: the ones that link or are linked to,
+/// and the links between them.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    /// Indexes of the notes, in the order of the notes list
+    pub nodes: Vec<usize>,
+    /// Links as positions in `nodes`, from the linking note to the linked one
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    /// Links go to the note with the target as its title, ignoring case, or
+    /// failing that as its id; ones to no note, or to the linking note
+    /// itself, are left out.
+    pub fn build(notes: &[Note]) -> Self {
+        let mut titles = HashMap::new();
+        for (i, note) in notes.iter().enumerate() {
+            titles.entry(note.title().to_lowercase()).or_insert(i);
+        }
+        let mut links = Vec::new();
+        for (from, note) in notes.iter().enumerate() {
+            for target in links::wiki(&note.content) {
+                let to = titles.get(&target.to_lowercase()).copied().or_else(|| notes.iter().position(|note| note.id == target));
+                if let Some(to) = to.filter(|&to| to != from && !links.contains(&(from, to))) {
+                    links.push((from, to));
+                }
+            }
+        }
+        let mut nodes: Vec<usize> = links.iter().flat_map(|&(from, to)| [from, to]).collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        let node = |i: usize| nodes.binary_search(&i).unwrap_or_default();
+        let edges = links.iter().map(|&(from, to)| (node(from), node(to))).collect();
+        Self { nodes, edges }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Nodes linked to or from `node`.
+    pub fn neighbours(&self, node: usize) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter_map(|&(from, to)| match (from == node, to == node) {
+                (true, _) => Some(to),
+                (_, true) => Some(from),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The graph in Graphviz DOT, with the notes' titles as labels.
+    pub fn to_dot(&self, notes: &[Note]) -> String {
+        let mut dot = String::from("digraph notes {\n    node [shape=box];\n");
+        for &i in &self.nodes {
+            let note = &notes[i];
+            dot.push_str(&format!("    \"{}\" [label=\"{}\"];\n", note.id, escape(note.title())));
+        }
+        for &(from, to) in &self.edges {
+            dot.push_str(&format!("    \"{}\" -> \"{}\";\n", notes[self.nodes[from]].id, notes[self.nodes[to]].id));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Cells of a `width` by `height` area to put the nodes in, by a force
+/// layout: linked notes pull together and all notes push apart. Cells are
+/// about twice as tall as wide, so the layout is worked out with rows
+/// counted twice. The same graph always comes out the same, and no two
+/// nodes share a cell while there is room.
+pub fn layout(graph: &Graph, width: u16, height: u16) -> Vec<(u16, u16)> {
+    let count = graph.nodes.len();
+    let (w, h) = (f64::from(width.max(1)), f64::from(height.max(1)) * 2.0);
+    let k = (w * h / count.max(1) as f64).sqrt();
+
+    // Start on a sunflower spiral around the middle
+    let mut points: Vec<(f64, f64)> = (0..count)
+        .map(|i| {
+            let radius = (i as f64 + 0.5).sqrt() / (count as f64).sqrt() * w.min(h) / 2.5;
+            let angle = i as f64 * PI * (3.0 - 5f64.sqrt());
+            (w / 2.0 + radius * angle.cos(), h / 2.0 + radius * angle.sin())
+        })
+        .collect();
+    for round in 0..ROUNDS {
+        let mut moves = vec![(0.0, 0.0); count];
+        for a in 0..count {
+            for b in a + 1..count {
+                let (dx, dy) = (points[a].0 - points[b].0, points[a].1 - points[b].1);
+                let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+                let push = k * k / distance / distance;
+                moves[a].0 += dx * push;
+                moves[a].1 += dy * push;
+                moves[b].0 -= dx * push;
+                moves[b].1 -= dy * push;
+            }
+        }
+        for &(a, b) in &graph.edges {
+            let (dx, dy) = (points[a].0 - points[b].0, points[a].1 - points[b].1);
+            let pull = (dx * dx + dy * dy).sqrt() / k;
+            moves[a].0 -= dx * pull;
+            moves[a].1 -= dy * pull;
+            moves[b].0 += dx * pull;
+            moves[b].1 += dy * pull;
+        }
+        // Steps shrink as the layout settles, and a little pull to the
+        // middle keeps unlinked groups in view
+        let step = w.max(h) / 10.0 * (1.0 - round as f64 / ROUNDS as f64);
+        for (point, (mx, my)) in points.iter_mut().zip(moves) {
+            let (mx, my) = (mx + (w / 2.0 - point.0) * 0.1, my + (h / 2.0 - point.1) * 0.1);
+            let length = (mx * mx + my * my).sqrt().max(0.01);
+            point.0 = (point.0 + mx / length * length.min(step)).clamp(0.0, w);
+            point.1 = (point.1 + my / length * length.min(step)).clamp(0.0, h);
+        }
+    }
+
+    let (right, bottom) = (width.saturating_sub(1), height.saturating_sub(1));
+    let mut cells: Vec<(u16, u16)> = Vec::with_capacity(count);
+    for (x, y) in points {
+        let mut cell = ((x / w * f64::from(right)).round() as u16, (y / h * f64::from(bottom)).round() as u16);
+        for _ in 0..usize::from(width) * usize::from(height) {
+            if !cells.contains(&cell) {
+                break;
+            }
+            cell = match cell.0 < right {
+                true => (cell.0 + 1, cell.1),
+                false => (0, if cell.1 < bottom { cell.1 + 1 } else { 0 }),
+            };
+        }
+        cells.push(cell);
+    }
+    cells
+}
+
+/// The node to go to from `from` in the direction `(dx, dy)`, one of the
+/// four arrows: the nearest one that way, nodes off to the side counting as
+/// further.
+pub fn step(cells: &[(u16, u16)], from: usize, (dx, dy): (i32, i32)) -> Option<usize> {
+    let (x, y) = cells.get(from).map(|&(x, y)| (i32::from(x), i32::from(y)))?;
+    cells
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != from)
+        .filter_map(|(i, &(cx, cy))| {
+            // Rows count twice, as cells are about twice as tall as wide
+            let (ox, oy) = (i32::from(cx) - x, (i32::from(cy) - y) * 2);
+            let along = ox * dx + oy * dy;
+            let across = (ox * dy - oy * dx).abs();
+            (along > 0).then_some((along + 2 * across, i))
+        })
+        .min()
+        .map(|(_, i)| i)
+}
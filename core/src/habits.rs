@@ -0,0 +1,38 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::notes::Note;
+
+/// Notes with this tag are habits, tracked by the days they were done
+pub const HABIT_TAG: &str = "habit";
+
+pub fn is_habit(note: &Note) -> bool {
+    note.tags.iter().any(|tag| tag.eq_ignore_ascii_case(HABIT_TAG))
+}
+
+/// Streaks of days in a row a habit was done.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Streaks {
+    /// Up to today, or yesterday while today is not done yet
+    pub current: usize,
+    pub longest: usize,
+}
+
+/// The streaks in `days`, sorted as `Note.habit_days` are.
+pub fn streaks(days: &[NaiveDate], today: NaiveDate) -> Streaks {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days.iter().filter(|day| **day <= today) {
+        run = match previous {
+            Some(previous) if day - previous == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+    let current = match previous {
+        Some(last) if today - last <= Duration::days(1) => run,
+        _ => 0,
+    };
+    Streaks { current, longest }
+}
@@ -0,0 +1,48 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::natural;
+
+/// A week of a calendar grid, from its first day; days after the grid's
+/// last day are `None`.
+pub type Week = [Option<NaiveDate>; 7];
+
+/// The `count` weeks up to and including the one with `last`, oldest
+/// first, as columns of a GitHub-style calendar: a row per weekday, from
+/// `first`.
+pub fn weeks(last: NaiveDate, count: usize, first: Weekday) -> Vec<Week> {
+    let start = natural::start_of_week(last, first) - Duration::weeks(count.saturating_sub(1) as i64);
+    (0..count)
+        .map(|week| {
+            let mut days = [None; 7];
+            for (i, day) in days.iter_mut().enumerate() {
+                let date = start + Duration::days((week * 7 + i) as i64);
+                *day = (date <= last).then_some(date);
+            }
+            days
+        })
+        .collect()
+}
+
+/// The month to label a week's column with: the one starting in that week,
+/// and the first week's own, so labels appear where months change.
+pub fn month_label(weeks: &[Week], index: usize) -> Option<String> {
+    let days = weeks.get(index)?;
+    let starting = days.iter().flatten().find(|day| day.day() == 1);
+    match (index, starting) {
+        (_, Some(day)) => Some(day.format("%b").to_string()),
+        (0, None) => days[0].map(|day| day.format("%b").to_string()),
+        _ => None,
+    }
+}
+
+/// Shades a heatmap cell can take, from none to the busiest
+pub const LEVELS: usize = 5;
+
+/// The shade for `count` when the busiest day has `max`: 0 for nothing,
+/// then up to `LEVELS - 1` in even steps.
+pub fn level(count: usize, max: usize) -> usize {
+    match count {
+        0 => 0,
+        _ => (count * (LEVELS - 1)).div_ceil(max.max(count)),
+    }
+}
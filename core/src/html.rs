@@ -0,0 +1,175 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+
+use crate::config::Config;
+use crate::metadata::{note_metadata, MetadataChip};
+use crate::notes::Note;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; color: #222; line-height: 1.5; }
+header.export { border-bottom: 2px solid #0aa; margin-bottom: 1.5rem; }
+nav.tags { background: #f4f8f8; border-radius: 6px; padding: 0.5rem 1rem; margin-bottom: 2rem; }
+nav.tags h2 { margin: 0.25rem 0; font-size: 1.1rem; }
+nav.tags details { margin: 0.25rem 0; }
+nav.tags ul { margin: 0.25rem 0 0.5rem; }
+a { color: #077; }
+a.tag { background: #e0f0f0; border-radius: 4px; padding: 0 0.35rem; text-decoration: none; font-size: 0.9rem; }
+article { border: 1px solid #ddd; border-radius: 6px; padding: 0.75rem 1rem; margin-bottom: 1.25rem; }
+article .meta { color: #666; font-size: 0.85rem; display: flex; gap: 0.5rem; flex-wrap: wrap; align-items: baseline; }
+article .meta a.anchor { color: #aaa; text-decoration: none; }
+article .content img { max-width: 100%; }
+pre { background: #f6f6f6; padding: 0.5rem; overflow-x: auto; }
+code { background: #f6f6f6; padding: 0 0.2rem; }
+"#;
+
+/// Renders notes as a standalone HTML page with embedded CSS, a tag index
+/// and one anchored section per note. Note content is rendered as Markdown;
+/// raw HTML and script links in notes are neutralized.
+pub fn render(notes: &[&Note], config: &Config) -> String {
+    let mut tags: Vec<&str> = notes.iter().flat_map(|note| note.tags.iter().map(String::as_str)).collect();
+    tags.sort_unstable();
+    tags.dedup();
+    let tag_ids = anchor_ids(&tags);
+    let tag_id = |tag: &str| {
+        let index = tags.binary_search(&tag).expect("tag is indexed");
+        tag_ids[index].as_str()
+    };
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    out.push_str("<title>Turbo Notes Export</title>\n<style>");
+    out.push_str(STYLE);
+    out.push_str("</style>\n</head>\n<body>\n");
+
+    out.push_str("<header class=\"export\">\n<h1>Turbo Notes</h1>\n");
+    out.push_str(&format!(
+        "<p>{} notes, exported {}</p>\n</header>\n",
+        notes.len(),
+        escape(&config.format_timestamp(chrono::Utc::now()))
+    ));
+
+    if !tags.is_empty() {
+        out.push_str("<nav class=\"tags\">\n<h2>Tags</h2>\n");
+        for tag in &tags {
+            let tagged: Vec<&&Note> = notes.iter().filter(|note| note.tags.iter().any(|t| t == tag)).collect();
+            out.push_str(&format!(
+                "<details id=\"{}\"><summary>#{} ({})</summary>\n<ul>\n",
+                tag_id(tag),
+                escape(tag),
+                tagged.len()
+            ));
+            for note in tagged {
+                out.push_str(&format!(
+                    "<li><a href=\"#note-{}\">{}</a></li>\n",
+                    escape(&note.id),
+                    escape(&note_title(note))
+                ));
+            }
+            out.push_str("</ul></details>\n");
+        }
+        out.push_str("</nav>\n");
+    }
+
+    out.push_str("<main>\n");
+    for note in notes {
+        out.push_str(&format!("<article id=\"note-{}\">\n<div class=\"meta\">", escape(&note.id)));
+        out.push_str(&format!("<a class=\"anchor\" href=\"#note-{}\">#</a>", escape(&note.id)));
+        for chip in note_metadata(note, config) {
+            match &chip {
+                MetadataChip::Tag(tag) => out.push_str(&format!(
+                    "<a class=\"tag\" href=\"#{}\">{}</a>",
+                    tag_id(tag),
+                    escape(&chip.label())
+                )),
+                _ => out.push_str(&format!("<span>{}</span>", escape(&chip.label()))),
+            }
+        }
+        out.push_str("</div>\n<div class=\"content\">\n");
+        out.push_str(&render_markdown(&note.content));
+        out.push_str("</div>\n</article>\n");
+    }
+    out.push_str("</main>\n</body>\n</html>\n");
+    out
+}
+
+/// Renders one note as a page for sharing: its content and when it was last
+/// edited, leaving out tags, the notebook and other metadata.
+pub fn render_shared(note: &Note, config: &Config) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    out.push_str("<meta name=\"robots\" content=\"noindex\">\n");
+    out.push_str(&format!("<title>{}</title>\n<style>", escape(&note_title(note))));
+    out.push_str(STYLE);
+    out.push_str("</style>\n</head>\n<body>\n<main>\n<article>\n<div class=\"meta\">");
+    out.push_str(&format!("<span>updated {}</span>", escape(&config.format_timestamp(note.updated_at))));
+    out.push_str("</div>\n<div class=\"content\">\n");
+    out.push_str(&render_markdown(&note.content));
+    out.push_str("</div>\n</article>\n</main>\n</body>\n</html>\n");
+    out
+}
+
+pub fn render_markdown(content: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let events = Parser::new_ext(content, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(CowStr::from(raw.into_string())),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) if is_script_url(&dest_url) => {
+            Event::Start(Tag::Link { link_type, dest_url: CowStr::from("#"), title, id })
+        }
+        event => event,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+fn is_script_url(url: &str) -> bool {
+    let scheme = url.trim_start().to_ascii_lowercase();
+    scheme.starts_with("javascript:") || scheme.starts_with("vbscript:") || scheme.starts_with("data:text/html")
+}
+
+/// The note's title, shortened to 60 characters.
+fn note_title(note: &Note) -> String {
+    let title = match note.title() {
+        "" => "(empty)",
+        title => title,
+    };
+    match title.char_indices().nth(60) {
+        Some((end, _)) => format!("{}...", &title[..end]),
+        None => title.to_string(),
+    }
+}
+
+/// Unique, URL-safe `tag-...` ids for each tag.
+fn anchor_ids(tags: &[&str]) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let slug: String = tag
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+            .collect();
+        let mut id = format!("tag-{}", slug);
+        let mut n = 2;
+        while ids.contains(&id) {
+            id = format!("tag-{}-{}", slug, n);
+            n += 1;
+        }
+        ids.push(id);
+    }
+    ids
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,160 @@
+use chrono::{DateTime, Utc};
+
+use crate::notes::Note;
+
+/// How long each reminder's event lasts in the calendar
+const EVENT_MINUTES: u32 = 15;
+
+/// An iCalendar (RFC 5545) calendar with an event, and an alarm going off
+/// at its start, for each note with a reminder.
+pub fn calendar(notes: &[Note], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Turbo Notes//Reminders//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Turbo Notes reminders".to_string(),
+    ];
+    for note in notes {
+        let Some(at) = note.remind_at else { continue };
+        let summary = match note.title() {
+            "" => "Note",
+            title => title,
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@turbo-notes", note.id),
+            format!("DTSTAMP:{}", timestamp(now)),
+            format!("DTSTART:{}", timestamp(at)),
+            format!("DURATION:PT{}M", EVENT_MINUTES),
+            format!("LAST-MODIFIED:{}", timestamp(note.updated_at)),
+            format!("SUMMARY:{}", escape(summary)),
+            format!("DESCRIPTION:{}", escape(&note.content)),
+        ]);
+        if !note.tags.is_empty() {
+            let tags: Vec<String> = note.tags.iter().map(|tag| escape(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.extend([
+            "BEGIN:VALARM".to_string(),
+            "ACTION:DISPLAY".to_string(),
+            format!("DESCRIPTION:{}", escape(summary)),
+            "TRIGGER:PT0M".to_string(),
+            "END:VALARM".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes text values: backslashes, separators and line breaks.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Ends `line` with CRLF, split into lines of at most 75 bytes continued
+/// with a leading space, without breaking up characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        // Continuation lines start with a space, which counts
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 5, hour, 30, 0).unwrap()
+    }
+
+    fn note(id: &str, content: &str, remind_at: Option<DateTime<Utc>>) -> Note {
+        Note { id: id.to_string(), remind_at, updated_at: at(8), ..Note::new(content.to_string()) }
+    }
+
+    #[test]
+    fn writes_an_event_per_reminder() {
+        let mut tagged = note("a1", "# Call Sam\nabout the rent; then, \\done", Some(at(17)));
+        tagged.tags = vec!["home".to_string(), "a,b".to_string()];
+        let notes = [tagged, note("b2", "No reminder", None), note("c3", "  \n", Some(at(18)))];
+        let lines: Vec<String> = calendar(&notes, at(9)).split("\r\n").map(str::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//Turbo Notes//Reminders//EN",
+                "CALSCALE:GREGORIAN",
+                "X-WR-CALNAME:Turbo Notes reminders",
+                "BEGIN:VEVENT",
+                "UID:a1@turbo-notes",
+                "DTSTAMP:20240605T093000Z",
+                "DTSTART:20240605T173000Z",
+                "DURATION:PT15M",
+                "LAST-MODIFIED:20240605T083000Z",
+                "SUMMARY:Call Sam",
+                "DESCRIPTION:# Call Sam\\nabout the rent\\; then\\, \\\\done",
+                "CATEGORIES:home,a\\,b",
+                "BEGIN:VALARM",
+                "ACTION:DISPLAY",
+                "DESCRIPTION:Call Sam",
+                "TRIGGER:PT0M",
+                "END:VALARM",
+                "END:VEVENT",
+                "BEGIN:VEVENT",
+                "UID:c3@turbo-notes",
+                "DTSTAMP:20240605T093000Z",
+                "DTSTART:20240605T183000Z",
+                "DURATION:PT15M",
+                "LAST-MODIFIED:20240605T083000Z",
+                "SUMMARY:Note",
+                "DESCRIPTION:  \\n",
+                "BEGIN:VALARM",
+                "ACTION:DISPLAY",
+                "DESCRIPTION:Note",
+                "TRIGGER:PT0M",
+                "END:VALARM",
+                "END:VEVENT",
+                "END:VCALENDAR",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_windows_line_breaks_once() {
+        assert_eq!(escape("a\r\nb\nc"), "a\\nb\\nc");
+    }
+
+    #[test]
+    fn folds_long_lines_between_characters() {
+        let long = "a".repeat(80);
+        assert_eq!(fold(&long), format!("{}\r\n {}\r\n", "a".repeat(75), "a".repeat(5)));
+        let wide = format!("{}é", "a".repeat(74));
+        assert_eq!(fold(&wide), format!("{}\r\n é\r\n", "a".repeat(74)));
+        // Continuation lines hold 74 bytes after their space
+        let longer = "b".repeat(75 + 74 + 1);
+        assert_eq!(fold(&longer), format!("{}\r\n {}\r\n b\r\n", "b".repeat(75), "b".repeat(74)));
+        assert_eq!(fold("short"), "short\r\n");
+    }
+}
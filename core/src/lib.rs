@@ -0,0 +1,67 @@
+//! Notes, their storage, search and export, as used by Turbo Notes, with no
+//! terminal UI attached. Embed it to read and change a notes directory from
+//! your own tool.
+//!
+//! [`NotesManager`] is the way in: it loads the notes of a directory, and
+//! every change made through it is saved back.
+//!
+//! ```no_run
+//! use turbo_notes_core::{Config, ExportFilter, ExportFormat, Note, NotesManager, Query};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::load()?;
+//! let mut notes = NotesManager::new(&config.notes_dir).await?;
+//! notes.add_note(Note::new("Call the vendor #work".into())).await?;
+//!
+//! for (_, note) in notes.search_notes_with(&Query::parse("vendor #work")) {
+//!     println!("{}", note.title());
+//! }
+//! let markdown = notes.export_notes(ExportFormat::Markdown, &config, &ExportFilter::default())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The items exported here and the public items of the modules follow
+//! semantic versioning: until 1.0, a minor release may break them and a
+//! patch release does not. The notes file format stays readable by later
+//! releases either way.
+
+#![allow(dead_code)]
+
+pub mod atom;
+pub mod backup;
+pub mod capture;
+pub mod config;
+pub mod crypto;
+pub mod dates;
+pub mod error;
+pub mod goals;
+pub mod graph;
+pub mod habits;
+pub mod heatmap;
+pub mod html;
+pub mod ics;
+pub mod links;
+pub mod meeting;
+pub mod merge;
+pub mod metadata;
+pub mod natural;
+pub mod notes;
+pub mod outline;
+pub mod profile;
+pub mod query;
+pub mod reading;
+pub mod reminders;
+pub mod saver;
+pub mod search_index;
+pub mod shorthand;
+pub mod snippets;
+pub mod stats;
+pub mod suggest;
+pub mod tidy;
+pub mod webhooks;
+
+pub use config::Config;
+pub use error::UserError;
+pub use notes::{ExportFilter, ExportFormat, Note, NotesManager, SortMode};
+pub use query::Query;
@@ -0,0 +1,115 @@
+use std::ops::Range;
+
+/// What a link starts with
+const SCHEMES: [&str; 3] = ["https://", "http://", "www."];
+
+/// Byte ranges of the web links in `text`: `http://` and `https://` URLs,
+/// and addresses starting with `www.`. Punctuation ending a sentence is left
+/// out, as is a closing bracket the link did not open, so a link in
+/// parentheses or Markdown's `[text](url)` comes out without it.
+pub fn find(text: &str) -> Vec<Range<usize>> {
+    let lower = text.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut from = 0;
+    while let Some((start, scheme)) = next_scheme(text, &lower, from) {
+        let rest = &text[start..];
+        let end = rest.find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '`')).unwrap_or(rest.len());
+        let link = trim_end(&rest[..end]);
+        if link.len() > scheme.len() {
+            links.push(start..start + link.len());
+        }
+        from = start + end.max(scheme.len());
+    }
+    links
+}
+
+/// The links in `text` as URLs to open, without repeats, in the order they
+/// appear. `www.` addresses get `https://` in front.
+pub fn urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for range in find(text) {
+        let url = url(&text[range]);
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Targets of the `[[wiki links]]` in `text`, without repeats, in the order
+/// they appear: the note title in the brackets, before any `|label`. Links
+/// do not span lines.
+pub fn wiki(text: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        let inside = &rest[..end];
+        // An unclosed `[[` before another link is text
+        if inside.contains("[[") || inside.contains('\n') {
+            continue;
+        }
+        let target = inside.split('|').next().unwrap_or_default().trim();
+        if !target.is_empty() && !targets.iter().any(|known| known.eq_ignore_ascii_case(target)) {
+            targets.push(target.to_string());
+        }
+        rest = &rest[end + 2..];
+    }
+    targets
+}
+
+/// `link` as a URL a browser opens.
+pub fn url(link: &str) -> String {
+    if link.to_ascii_lowercase().starts_with("www.") {
+        format!("https://{}", link)
+    } else {
+        link.to_string()
+    }
+}
+
+/// The first scheme at or after `from` that starts a word, and where.
+/// `lower` is `text` in lowercase, for matching `HTTPS://` too.
+fn next_scheme(text: &str, lower: &str, from: usize) -> Option<(usize, &'static str)> {
+    let mut at = from;
+    while at < text.len() {
+        let (start, scheme) = SCHEMES
+            .iter()
+            .filter_map(|scheme| lower[at..].find(scheme).map(|i| (at + i, *scheme)))
+            .min_by_key(|(start, _)| *start)?;
+        let after_word = text[..start].chars().next_back().is_none_or(|c| !c.is_alphanumeric() && c != '/');
+        if after_word {
+            return Some((start, scheme));
+        }
+        at = start + scheme.len();
+    }
+    None
+}
+
+fn trim_end(link: &str) -> &str {
+    let mut link = link;
+    loop {
+        let trimmed = link.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '*', '_']);
+        let trimmed = match trimmed.chars().next_back() {
+            Some(close @ (')' | ']' | '}')) => {
+                let open = match close {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if trimmed.matches(close).count() > trimmed.matches(open).count() {
+                    &trimmed[..trimmed.len() - 1]
+                } else {
+                    trimmed
+                }
+            }
+            _ => trimmed,
+        };
+        if trimmed.len() == link.len() {
+            return link;
+        }
+        link = trimmed;
+    }
+}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Local};
+
+/// Note a meeting starts as. `{{date}}`, `{{time}}` and the other snippet
+/// variables are filled in; the cursor goes to the end.
+pub const DEFAULT_TEMPLATE: &str = "# Meeting {{date}} {{time}}\n\nAttendees: \nAgenda: \n\n## Actions\n\n## Notes\n- {{time}} ";
+
+/// Whether the line of `text` at `cursor` is in the last section, below
+/// the last heading, where the notes are taken.
+pub fn in_entries(text: &str, cursor: usize) -> bool {
+    let line_start = line_start(text, cursor);
+    let headings = text.match_indices('\n').map(|(at, _)| at + 1).chain([0]).filter(|&start| {
+        text[start..].starts_with('#') && text[start..].trim_start_matches('#').starts_with(' ')
+    });
+    headings.max().is_some_and(|heading| heading <= line_start)
+}
+
+/// What a new line of a meeting note starts with, typed after `text`'s
+/// line at `cursor`: a bullet with the time in the last section, and
+/// nothing elsewhere.
+pub fn entry_prefix(text: &str, cursor: usize, now: DateTime<Local>) -> Option<String> {
+    in_entries(text, cursor).then(|| format!("- {} ", now.format("%H:%M")))
+}
+
+/// Whether the line of `text` at `cursor` is an entry with a time and
+/// nothing after it, in the last section.
+pub fn is_empty_entry(text: &str, cursor: usize) -> bool {
+    let line = &text[line_start(text, cursor)..cursor];
+    let Some(rest) = line.trim_end().strip_prefix("- ") else {
+        return false;
+    };
+    let is_time = rest.len() == 5 && rest.as_bytes()[2] == b':' && rest.bytes().filter(u8::is_ascii_digit).count() == 4;
+    is_time && in_entries(text, cursor)
+}
+
+fn line_start(text: &str, cursor: usize) -> usize {
+    text[..cursor].rfind('\n').map_or(0, |newline| newline + 1)
+}
@@ -0,0 +1,238 @@
+use chrono::Utc;
+use std::path::PathBuf;
+
+use crate::notes::Note;
+
+/// Larger texts are shown as a single changed hunk instead of being diffed
+/// line by line, which would need a table of this many cells.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A run of lines that is the same on both sides, or differs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Same(Vec<String>),
+    Changed { local: Vec<String>, remote: Vec<String> },
+}
+
+/// Which side a changed hunk takes in the merged note.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pick {
+    Local,
+    Remote,
+    /// Local lines followed by remote lines
+    Both,
+}
+
+/// Line diff of two texts, from their longest common subsequence.
+pub fn diff(local: &str, remote: &str) -> Vec<Segment> {
+    let a: Vec<&str> = local.lines().collect();
+    let b: Vec<&str> = remote.lines().collect();
+    let owned = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+    if (a.len() + 1) * (b.len() + 1) > MAX_DIFF_CELLS {
+        return vec![Segment::Changed { local: owned(&a), remote: owned(&b) }];
+    }
+
+    // common[i][j]: length of the longest common subsequence of a[i..] and b[j..]
+    let mut common = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut segments = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let start = (i, j);
+        while i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        }
+        if i > start.0 {
+            segments.push(Segment::Same(owned(&a[start.0..i])));
+        }
+
+        let start = (i, j);
+        while (i < a.len() || j < b.len()) && !(i < a.len() && j < b.len() && a[i] == b[j]) {
+            if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        if (i, j) != start {
+            segments.push(Segment::Changed {
+                local: owned(&a[start.0..i]),
+                remote: owned(&b[start.1..j]),
+            });
+        }
+    }
+    segments
+}
+
+/// Joins the segments back into text, taking each changed hunk from the
+/// side picked for it (one pick per hunk, in order).
+pub fn merge(segments: &[Segment], picks: &[Pick]) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut picks = picks.iter();
+    for segment in segments {
+        match segment {
+            Segment::Same(same) => lines.extend(same.iter().map(String::as_str)),
+            Segment::Changed { local, remote } => {
+                let pick = picks.next().copied().unwrap_or(Pick::Local);
+                if pick != Pick::Remote {
+                    lines.extend(local.iter().map(String::as_str));
+                }
+                if pick != Pick::Local {
+                    lines.extend(remote.iter().map(String::as_str));
+                }
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// Where the other version of a conflicted note comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictSource {
+    /// The sync store
+    Sync,
+    /// A conflicted copy of the notes file, left by a file sync tool
+    Copy(PathBuf),
+}
+
+/// State of the conflict resolution screen for one conflicted note: the
+/// diff between the two versions and the side picked for each hunk.
+#[derive(Debug, Clone)]
+pub struct ConflictView {
+    pub id: String,
+    /// `None` when the note was deleted here
+    pub local: Option<Note>,
+    pub remote: Note,
+    pub segments: Vec<Segment>,
+    pub picks: Vec<Pick>,
+    /// Selected hunk
+    pub hunk: usize,
+    /// Conflicts still open, this one included
+    pub remaining: usize,
+    pub source: ConflictSource,
+}
+
+impl ConflictView {
+    pub fn new(local: Option<&Note>, remote: &Note, remaining: usize, source: ConflictSource) -> Self {
+        let local_text = local.map(|note| note.content.as_str()).unwrap_or("");
+        let segments = diff(local_text, &remote.content);
+        let hunks = segments.iter().filter(|segment| matches!(segment, Segment::Changed { .. })).count();
+        Self {
+            id: remote.id.clone(),
+            local: local.cloned(),
+            remote: remote.clone(),
+            segments,
+            picks: vec![Pick::Local; hunks],
+            hunk: 0,
+            remaining,
+            source,
+        }
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.picks.len().saturating_sub(1);
+        self.hunk = self.hunk.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn pick(&mut self, pick: Pick) {
+        if let Some(current) = self.picks.get_mut(self.hunk) {
+            *current = pick;
+        }
+    }
+
+    /// The note as merged from the current picks: local metadata (or the
+    /// remote note's, if deleted here) with tags from both sides.
+    pub fn merged(&self) -> Note {
+        let mut note = self.local.clone().unwrap_or_else(|| self.remote.clone());
+        note.content = merge(&self.segments, &self.picks);
+        for tag in &self.remote.tags {
+            if !note.tags.contains(tag) {
+                note.tags.push(tag.clone());
+            }
+        }
+        note.habit_days.extend(&self.remote.habit_days);
+        note.habit_days.sort();
+        note.habit_days.dedup();
+        note.updated_at = Utc::now();
+        note
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn diff_finds_changed_hunks() {
+        let segments = diff("a\nb\nc\nd", "a\nB\nc\nd\ne");
+        assert_eq!(
+            segments,
+            [
+                Segment::Same(lines(&["a"])),
+                Segment::Changed { local: lines(&["b"]), remote: lines(&["B"]) },
+                Segment::Same(lines(&["c", "d"])),
+                Segment::Changed { local: vec![], remote: lines(&["e"]) },
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_texts_have_no_changes() {
+        assert_eq!(diff("a\nb", "a\nb"), [Segment::Same(lines(&["a", "b"]))]);
+        assert!(diff("", "").is_empty());
+    }
+
+    #[test]
+    fn merge_takes_each_hunk_from_its_pick() {
+        let segments = diff("a\nb\nc\nd", "a\nB\nc\nD");
+        assert_eq!(merge(&segments, &[Pick::Local, Pick::Local]), "a\nb\nc\nd");
+        assert_eq!(merge(&segments, &[Pick::Remote, Pick::Local]), "a\nB\nc\nd");
+        assert_eq!(merge(&segments, &[Pick::Local, Pick::Both]), "a\nb\nc\nd\nD");
+        // Missing picks keep the local side
+        assert_eq!(merge(&segments, &[Pick::Remote]), "a\nB\nc\nd");
+    }
+
+    #[test]
+    fn merged_note_joins_both_sides() {
+        let mut local = Note::new("title\nmine".to_string());
+        local.tags = vec!["work".to_string()];
+        local.habit_days = vec![chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()];
+        let mut remote = local.clone();
+        remote.content = "title\ntheirs".to_string();
+        remote.tags = vec!["work".to_string(), "home".to_string()];
+        remote.habit_days.push(chrono::NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+
+        let mut view = ConflictView::new(Some(&local), &remote, 1, ConflictSource::Sync);
+        assert_eq!(view.picks.len(), 1);
+        view.pick(Pick::Remote);
+        let merged = view.merged();
+        assert_eq!(merged.id, local.id);
+        assert_eq!(merged.content, "title\ntheirs");
+        assert_eq!(merged.tags, ["work", "home"]);
+        assert_eq!(merged.habit_days.len(), 2);
+    }
+
+    #[test]
+    fn note_deleted_here_merges_from_remote() {
+        let remote = Note::new("only theirs".to_string());
+        let view = ConflictView::new(None, &remote, 1, ConflictSource::Sync);
+        let merged = view.merged();
+        assert_eq!(merged.id, remote.id);
+        // Keeping the empty local side drops the remote lines
+        assert_eq!(merged.content, "");
+    }
+}
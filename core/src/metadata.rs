@@ -0,0 +1,111 @@
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::habits;
+use crate::notes::{Memo, Note};
+use crate::reading;
+
+/// One piece of note metadata. The TUI renders these as colored chips and
+/// plain-text outputs use `label`, so every view shows the same facts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataChip {
+    Pinned,
+    Locked,
+    Notebook(String),
+    Tag(String),
+    Reminder(String),
+    /// Where it was captured
+    Origin(String),
+    /// A voice memo, by its length in seconds
+    Memo(u64),
+    /// Attached images, by how many
+    Images(usize),
+    /// A habit's current streak in days
+    Streak(usize),
+    /// How much of a note started and not finished was read, in percent
+    Read(usize),
+    Created(String),
+    Updated(String),
+    Words(usize),
+}
+
+impl MetadataChip {
+    pub fn label(&self) -> String {
+        match self {
+            MetadataChip::Pinned => "pinned".to_string(),
+            MetadataChip::Locked => "locked".to_string(),
+            MetadataChip::Notebook(name) => format!("@{}", name),
+            MetadataChip::Tag(tag) => format!("#{}", tag),
+            MetadataChip::Reminder(time) => format!("remind {}", time),
+            MetadataChip::Origin(place) => format!("from {}", place),
+            MetadataChip::Memo(seconds) => format!("memo {}", Memo::format_length(*seconds)),
+            MetadataChip::Images(1) => "1 image".to_string(),
+            MetadataChip::Images(count) => format!("{} images", count),
+            MetadataChip::Streak(days) => format!("{}-day streak", days),
+            MetadataChip::Read(percent) => format!("{}% read", percent),
+            MetadataChip::Created(time) => format!("created {}", time),
+            MetadataChip::Updated(time) => format!("updated {}", time),
+            MetadataChip::Words(1) => "1 word".to_string(),
+            MetadataChip::Words(count) => format!("{} words", count),
+        }
+    }
+}
+
+pub fn note_metadata(note: &Note, config: &Config) -> Vec<MetadataChip> {
+    let mut chips = Vec::new();
+
+    if note.pinned {
+        chips.push(MetadataChip::Pinned);
+    }
+    if note.locked {
+        chips.push(MetadataChip::Locked);
+    }
+    if let Some(notebook) = &note.notebook {
+        chips.push(MetadataChip::Notebook(notebook.clone()));
+    }
+    chips.extend(note.tags.iter().cloned().map(MetadataChip::Tag));
+    if let Some(remind_at) = note.remind_at {
+        chips.push(MetadataChip::Reminder(config.format_timestamp(remind_at)));
+    }
+    if let Some(origin) = &note.origin {
+        let place = match (origin.path.as_str(), origin.title.as_str()) {
+            ("", title) => title.to_string(),
+            (path, "") => path.to_string(),
+            (path, title) => format!("{} ({})", path, title),
+        };
+        if !place.is_empty() {
+            chips.push(MetadataChip::Origin(place));
+        }
+    }
+
+    chips.extend(note.memos.iter().map(|memo| MetadataChip::Memo(memo.seconds)));
+    if !note.images.is_empty() {
+        chips.push(MetadataChip::Images(note.images.len()));
+    }
+    if habits::is_habit(note) {
+        let streak = habits::streaks(&note.habit_days, config.zone().day(Utc::now())).current;
+        if streak > 0 {
+            chips.push(MetadataChip::Streak(streak));
+        }
+    }
+    if let Some(percent) = reading::progress(note).filter(|&percent| percent < 100) {
+        chips.push(MetadataChip::Read(percent));
+    }
+
+    chips.push(MetadataChip::Created(config.format_timestamp(note.created_at)));
+    if note.updated_at != note.created_at {
+        chips.push(MetadataChip::Updated(config.format_timestamp(note.updated_at)));
+    }
+    chips.push(MetadataChip::Words(note.word_count()));
+
+    chips
+}
+
+/// Plain-text form of the metadata, e.g. for exports.
+pub fn metadata_line(note: &Note, config: &Config) -> String {
+    note_metadata(note, config)
+        .iter()
+        .map(MetadataChip::label)
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
@@ -0,0 +1,281 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use crate::config::Config;
+use crate::dates;
+use crate::error::UserError;
+
+/// First day of the week.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    /// The usual one where the locale's region is
+    #[default]
+    Locale,
+    Monday,
+    Saturday,
+    Sunday,
+}
+
+/// Regions whose weeks start on Sunday or Saturday; elsewhere on Monday.
+const SUNDAY_REGIONS: &[&str] = &["BR", "CA", "HK", "IL", "IN", "JP", "KR", "MX", "PH", "SA", "TW", "US", "ZA"];
+const SATURDAY_REGIONS: &[&str] = &["AF", "DZ", "EG", "IR", "LY"];
+
+/// Regions writing numeric dates month first, and day first with dots.
+/// Elsewhere day first with slashes.
+const MONTH_FIRST_REGIONS: &[&str] = &["PH", "US"];
+const DOT_REGIONS: &[&str] = &["AT", "CH", "CZ", "DE", "DK", "FI", "NO", "PL", "RU", "SK", "TR", "UA"];
+
+const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// The locale in use, like `en-US`: `locale` from the config, else the one
+/// in `LC_ALL`, `LC_TIME` or `LANG`. Empty when unknown, or C or POSIX.
+pub fn locale(config: &Config) -> String {
+    let raw = if config.locale.is_empty() {
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+    } else {
+        config.locale.clone()
+    };
+    // de_DE.UTF-8 and de-DE are the same locale
+    let name = raw.split(['.', '@']).next().unwrap_or_default().replace('_', "-");
+    match name.as_str() {
+        "C" | "POSIX" => String::new(),
+        _ => name,
+    }
+}
+
+fn region(config: &Config) -> Option<String> {
+    locale(config)
+        .split('-')
+        .nth(1)
+        .filter(|region| region.len() == 2)
+        .map(str::to_uppercase)
+}
+
+/// The configured first day of the week, or the locale's.
+pub fn first_day_of_week(config: &Config) -> Weekday {
+    match config.week_start {
+        WeekStart::Monday => Weekday::Mon,
+        WeekStart::Saturday => Weekday::Sat,
+        WeekStart::Sunday => Weekday::Sun,
+        WeekStart::Locale => match region(config).as_deref() {
+            Some(region) if SUNDAY_REGIONS.contains(&region) => Weekday::Sun,
+            Some(region) if SATURDAY_REGIONS.contains(&region) => Weekday::Sat,
+            _ => Weekday::Mon,
+        },
+    }
+}
+
+/// Formats tried for typed dates: `date_input_formats` from the config, or
+/// else `2024-06-01` and the locale's numeric form, e.g. `06/01/2024` in
+/// the US.
+pub fn input_formats(config: &Config) -> Vec<String> {
+    if !config.date_input_formats.is_empty() {
+        // Checked when the config is loaded; skipped here rather than
+        // panicking inside chrono should one slip through
+        return config
+            .date_input_formats
+            .iter()
+            .filter(|format| is_date_format(format))
+            .cloned()
+            .collect();
+    }
+    let mut formats = vec!["%Y-%m-%d".to_string()];
+    match region(config).as_deref() {
+        Some(region) if MONTH_FIRST_REGIONS.contains(&region) => formats.push("%m/%d/%Y".to_string()),
+        Some(region) if DOT_REGIONS.contains(&region) => formats.push("%d.%m.%Y".to_string()),
+        Some(_) => formats.push("%d/%m/%Y".to_string()),
+        None => {}
+    }
+    formats
+}
+
+fn sample_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 1).expect("valid date")
+}
+
+/// 2024-06-01 written in `format`, or `None` if the format needs more than
+/// a date (e.g. `%H`), which chrono reports by failing to write.
+fn sample(format: &str) -> Option<String> {
+    let mut text = String::new();
+    (dates::is_valid_format(format) && write!(text, "{}", sample_date().format(format)).is_ok()).then_some(text)
+}
+
+/// Whether `format` reads back a whole date, as `date_input_formats` must.
+pub fn is_date_format(format: &str) -> bool {
+    sample(format).is_some_and(|text| NaiveDate::parse_from_str(&text, format).is_ok_and(|date| date == sample_date()))
+}
+
+/// A weekday by its English name or first three letters or more.
+fn weekday(name: &str) -> Option<Weekday> {
+    let index = WEEKDAYS.iter().position(|day| name.len() >= 3 && day.starts_with(name))?;
+    Weekday::try_from(index as u8).ok()
+}
+
+fn days_in(amount: &str, unit: &str) -> Option<i64> {
+    let amount: i64 = amount.parse().ok().filter(|amount| *amount >= 0)?;
+    match unit {
+        "d" | "day" | "days" => Some(amount),
+        "w" | "week" | "weeks" => amount.checked_mul(7),
+        _ => None,
+    }
+}
+
+/// The first day of the week `date` is in.
+pub fn start_of_week(date: NaiveDate, first: Weekday) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().days_since(first)))
+}
+
+/// Reads a day typed by the user, relative to `today`: `today`,
+/// `tomorrow`, `yesterday`, a weekday (`fri`, the coming one), `next fri`
+/// (the one in next week), `last fri`, `this week` / `next week` /
+/// `last week` (their first day), `in 3 days`, `2 weeks ago`, or a date
+/// in one of the `input_formats`.
+pub fn parse_date(text: &str, today: NaiveDate, config: &Config) -> Option<NaiveDate> {
+    let lower = text.trim().to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    let first = first_day_of_week(config);
+    let week = start_of_week(today, first);
+    let in_week = |start: NaiveDate, day: Weekday| start + Duration::days(i64::from(day.days_since(first)));
+
+    match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => today.succ_opt(),
+        ["yesterday"] => today.pred_opt(),
+        ["this", "week"] => Some(week),
+        ["next", "week"] => Some(week + Duration::weeks(1)),
+        ["last", "week"] => Some(week - Duration::weeks(1)),
+        ["in", amount, unit] => today.checked_add_signed(Duration::days(days_in(amount, unit)?)),
+        [amount, unit, "ago"] => today.checked_sub_signed(Duration::days(days_in(amount, unit)?)),
+        ["next", name] => Some(in_week(week + Duration::weeks(1), weekday(name)?)),
+        ["last", name] => {
+            let back = today.weekday().days_since(weekday(name)?);
+            Some(today - Duration::days(if back == 0 { 7 } else { i64::from(back) }))
+        }
+        [name] | ["this", name] if weekday(name).is_some() => {
+            let ahead = weekday(name)?.days_since(today.weekday());
+            Some(today + Duration::days(if ahead == 0 { 7 } else { i64::from(ahead) }))
+        }
+        _ => input_formats(config)
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(text.trim(), format).ok()),
+    }
+}
+
+/// `parse_date`, failing with the forms accepted.
+pub fn parse_day(text: &str, today: NaiveDate, config: &Config) -> Result<NaiveDate> {
+    parse_date(text, today, config).ok_or_else(|| {
+        let examples: Vec<String> = input_formats(config).iter().filter_map(|format| sample(format)).collect();
+        UserError::Invalid(format!(
+            "Could not read '{}' as a date; use e.g. {}, tomorrow, fri or next fri",
+            text.trim(),
+            examples.join(", ")
+        ))
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(locale: &str, week_start: WeekStart) -> Config {
+        Config { locale: locale.to_string(), week_start, ..Config::default() }
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    // A Wednesday
+    fn today() -> NaiveDate {
+        day(2024, 6, 5)
+    }
+
+    #[test]
+    fn reads_relative_days() {
+        let config = config("en-GB", WeekStart::Locale);
+        let parse = |text| parse_date(text, today(), &config);
+        assert_eq!(parse(" Today "), Some(today()));
+        assert_eq!(parse("tomorrow"), Some(day(2024, 6, 6)));
+        assert_eq!(parse("yesterday"), Some(day(2024, 6, 4)));
+        assert_eq!(parse("in 3 days"), Some(day(2024, 6, 8)));
+        assert_eq!(parse("in 1 w"), Some(day(2024, 6, 12)));
+        assert_eq!(parse("2 weeks ago"), Some(day(2024, 5, 22)));
+        assert_eq!(parse("in -1 days"), None);
+        assert_eq!(parse("in 3 months"), None);
+    }
+
+    #[test]
+    fn reads_weekdays() {
+        let config = config("en-GB", WeekStart::Locale);
+        let parse = |text| parse_date(text, today(), &config);
+        assert_eq!(parse("fri"), Some(day(2024, 6, 7)));
+        assert_eq!(parse("this Friday"), Some(day(2024, 6, 7)));
+        // The coming one, never today
+        assert_eq!(parse("wed"), Some(day(2024, 6, 12)));
+        assert_eq!(parse("last wed"), Some(day(2024, 5, 29)));
+        assert_eq!(parse("last fri"), Some(day(2024, 5, 31)));
+        assert_eq!(parse("next mon"), Some(day(2024, 6, 10)));
+        assert_eq!(parse("next fri"), Some(day(2024, 6, 14)));
+        assert_eq!(parse("fr"), None);
+    }
+
+    #[test]
+    fn weeks_start_where_the_locale_or_config_says() {
+        let monday = config("de-DE", WeekStart::Locale);
+        let sunday = config("en-US", WeekStart::Locale);
+        let saturday = config("en-US", WeekStart::Saturday);
+        assert_eq!(first_day_of_week(&monday), Weekday::Mon);
+        assert_eq!(first_day_of_week(&sunday), Weekday::Sun);
+        assert_eq!(first_day_of_week(&config("ar-EG", WeekStart::Locale)), Weekday::Sat);
+        assert_eq!(parse_date("this week", today(), &monday), Some(day(2024, 6, 3)));
+        assert_eq!(parse_date("this week", today(), &sunday), Some(day(2024, 6, 2)));
+        assert_eq!(parse_date("this week", today(), &saturday), Some(day(2024, 6, 1)));
+        assert_eq!(parse_date("next week", today(), &monday), Some(day(2024, 6, 10)));
+        assert_eq!(parse_date("last week", today(), &monday), Some(day(2024, 5, 27)));
+        // Sunday ends a Monday week but starts a Sunday one
+        assert_eq!(parse_date("next sun", today(), &monday), Some(day(2024, 6, 16)));
+        assert_eq!(parse_date("next sun", today(), &sunday), Some(day(2024, 6, 9)));
+    }
+
+    #[test]
+    fn reads_numeric_dates_in_the_locale_order() {
+        assert_eq!(parse_date("06/01/2024", today(), &config("en-US", WeekStart::Locale)), Some(day(2024, 6, 1)));
+        assert_eq!(parse_date("06/01/2024", today(), &config("en-GB", WeekStart::Locale)), Some(day(2024, 1, 6)));
+        assert_eq!(parse_date("01.06.2024", today(), &config("de_DE.UTF-8", WeekStart::Locale)), Some(day(2024, 6, 1)));
+        assert_eq!(parse_date("2024-06-01", today(), &config("C", WeekStart::Locale)), Some(day(2024, 6, 1)));
+        assert_eq!(parse_date("01/06/2024", today(), &config("C", WeekStart::Locale)), None);
+    }
+
+    #[test]
+    fn normalises_locale_names() {
+        assert_eq!(locale(&config("de_DE.UTF-8", WeekStart::Locale)), "de-DE");
+        assert_eq!(locale(&config("sr_RS@latin", WeekStart::Locale)), "sr-RS");
+        assert_eq!(locale(&config("POSIX", WeekStart::Locale)), "");
+    }
+
+    #[test]
+    fn configured_formats_replace_the_locale_ones() {
+        let mut config = config("en-US", WeekStart::Locale);
+        config.date_input_formats = vec!["%d %b %Y".to_string(), "%H:%M".to_string(), "%m/%d".to_string()];
+        assert_eq!(input_formats(&config), ["%d %b %Y"]);
+        assert_eq!(parse_date("1 Jun 2024", today(), &config), Some(day(2024, 6, 1)));
+        assert_eq!(parse_date("06/01/2024", today(), &config), None);
+    }
+
+    #[test]
+    fn errors_show_the_accepted_forms() {
+        let error = parse_day("someday", today(), &config("en-US", WeekStart::Locale)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Could not read 'someday' as a date; use e.g. 2024-06-01, 06/01/2024, tomorrow, fri or next fri"
+        );
+    }
+}
@@ -0,0 +1,1473 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::fs as async_fs;
+
+use crate::config::Config;
+use crate::dates::Zone;
+use crate::error::UserError;
+use crate::html;
+use crate::metadata::metadata_line;
+use crate::query::Query;
+use crate::reading::Reading;
+use crate::saver::{BackgroundSaver, SaveState};
+use crate::search_index::SearchIndex;
+use crate::webhooks::{self, Event, Webhook};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub id: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub sort_order: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notebook: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub locked: bool,
+    /// When to remind about the note; cleared once it is done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<DateTime<Utc>>,
+    /// Where the note was written, e.g. the tmux pane a popup was opened over
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<Origin>,
+    /// Voice memos recorded for the note, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memos: Vec<Memo>,
+    /// Images attached to the note, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<Image>,
+    /// Days a habit note was done, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub habit_days: Vec<NaiveDate>,
+    /// How far the note was read, once it was opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<Reading>,
+    /// Set when `content` is only the start of a large note, the whole of
+    /// it being in `bodies/<id>.md`: in the notes file, and in memory in
+    /// managers opened [`with_previews`](NotesManager::with_previews)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stored_apart: bool,
+    /// Bytes in the whole content of a note in memory as its start
+    #[serde(skip)]
+    pub stored_length: usize,
+}
+
+/// Notes longer than this, in bytes, keep their content in a file of their
+/// own, so saves do not rewrite it when other notes change
+const LARGE_NOTE: usize = 64 * 1024;
+/// Characters of a large note's content left in the notes file, for what
+/// reads it directly, such as shell completion
+const STORED_PREVIEW: usize = 500;
+/// Where large notes' content is kept, in the notes directory
+const BODIES_DIR: &str = "bodies";
+/// Directory next to the notes file with the files attached to notes, in a
+/// directory per note
+const ATTACHMENTS_DIR: &str = "attachments";
+
+/// The window or pane a note was captured in.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Origin {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    /// Working directory
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+}
+
+/// An audio recording attached to a note.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Memo {
+    /// File name in the note's attachments directory
+    pub file: String,
+    pub recorded_at: DateTime<Utc>,
+    pub seconds: u64,
+}
+
+impl Memo {
+    /// `seconds` as `m:ss`, or `h:mm:ss` from an hour on.
+    pub fn format_length(seconds: u64) -> String {
+        match seconds / 3600 {
+            0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+            hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+        }
+    }
+}
+
+/// An image attached to a note, with the text read from it so searches
+/// find what it shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Image {
+    /// File name in the note's attachments directory
+    pub file: String,
+    /// Recognized text; empty when there is none or it was not read
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+}
+
+impl Note {
+    pub fn new(content: String) -> Self {
+        let now = Utc::now();
+        let id = format!("{}", now.timestamp_nanos_opt().unwrap_or(0));
+        
+        Self {
+            id,
+            content,
+            created_at: now,
+            updated_at: now,
+            tags: Vec::new(),
+            sort_order: 0,
+            notebook: None,
+            pinned: false,
+            locked: false,
+            remind_at: None,
+            origin: None,
+            memos: Vec::new(),
+            images: Vec::new(),
+            habit_days: Vec::new(),
+            reading: None,
+            stored_apart: false,
+            stored_length: 0,
+        }
+    }
+
+    /// Whether `id` can be a note's: letters, digits, `-` and `_`, as it
+    /// names the note's files.
+    pub fn is_valid_id(id: &str) -> bool {
+        !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    /// Whether the note has no text and nothing attached.
+    pub fn is_empty(&self) -> bool {
+        self.content.trim().is_empty() && self.memos.is_empty() && self.images.is_empty()
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.stored_apart || self.content.len() > LARGE_NOTE
+    }
+
+    /// Bytes in the content, all of it for a note in memory as its start.
+    pub fn content_len(&self) -> usize {
+        match self.stored_apart {
+            true => self.stored_length,
+            false => self.content.len(),
+        }
+    }
+
+    /// The note as the notes file has it: a large one cut to its start.
+    fn stored(&self) -> Note {
+        if self.stored_apart {
+            return self.clone();
+        }
+        if !self.is_large() {
+            return Note { stored_apart: false, ..self.clone() };
+        }
+        // Spelled out so the content is not copied whole first
+        let Note {
+            id,
+            content,
+            created_at,
+            updated_at,
+            tags,
+            sort_order,
+            notebook,
+            pinned,
+            locked,
+            remind_at,
+            origin,
+            memos,
+            images,
+            habit_days,
+            reading,
+            stored_apart: _,
+            stored_length: _,
+        } = self;
+        let end = content.char_indices().nth(STORED_PREVIEW).map_or(content.len(), |(end, _)| end);
+        Note {
+            id: id.clone(),
+            content: content[..end].to_string(),
+            created_at: *created_at,
+            updated_at: *updated_at,
+            tags: tags.clone(),
+            sort_order: *sort_order,
+            notebook: notebook.clone(),
+            pinned: *pinned,
+            locked: *locked,
+            remind_at: *remind_at,
+            origin: origin.clone(),
+            memos: memos.clone(),
+            images: images.clone(),
+            habit_days: habit_days.clone(),
+            reading: *reading,
+            stored_apart: true,
+            stored_length: 0,
+        }
+    }
+
+    pub fn update_content(&mut self, content: String) {
+        self.content = content;
+        self.stored_apart = false;
+        self.updated_at = Utc::now();
+    }
+
+    pub fn add_tag(&mut self, tag: String) {
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+            self.updated_at = Utc::now();
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        if let Some(pos) = self.tags.iter().position(|t| t == tag) {
+            self.tags.remove(pos);
+            self.updated_at = Utc::now();
+        }
+    }
+
+    pub fn matches_search(&self, query: &str) -> bool {
+        Query::parse(query).matches(self)
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.content.split_whitespace().count()
+    }
+
+    /// First non-empty line, without Markdown heading markers.
+    pub fn title(&self) -> &str {
+        self.content
+            .lines()
+            .map(|line| line.trim().trim_start_matches('#').trim())
+            .find(|line| !line.is_empty())
+            .unwrap_or("")
+    }
+
+    pub fn preview(&self, max_length: usize) -> String {
+        match self.content.char_indices().nth(max_length) {
+            None => self.content.clone(),
+            Some((end, _)) => format!("{}...", &self.content[..end]),
+        }
+    }
+
+    /// Hash of the content, to find notes with the same text.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Hash of the content's words, lowercased and without punctuation, so
+    /// notes differing only in case, spacing or punctuation hash the same.
+    pub fn normalized_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for word in self.content.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            word.to_lowercase().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// How closely a note repeats another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Duplicate {
+    /// The same text
+    Exact,
+    /// The same words apart from case, spacing and punctuation
+    Near,
+}
+
+/// The notes of one notes directory. Changes made through it are saved to
+/// the directory, by itself or by a background task once
+/// [`save_in_background`](Self::save_in_background) is called.
+pub struct NotesManager {
+    pub notes: Vec<Note>,
+    notes_dir: PathBuf,
+    notes_file: PathBuf,
+    /// Set while saves go to a background task
+    saver: Option<BackgroundSaver>,
+    /// Ids of notes added, changed or removed since the last save
+    dirty: HashSet<String>,
+    /// Set while searches are narrowed down by the index
+    index: Option<SearchIndex>,
+    /// Ids of large notes whose content is in the bodies directory as it
+    /// is now
+    bodies_stored: HashSet<String>,
+    /// Set when the notes directory cannot be written: changes are kept in
+    /// memory until [`save_elsewhere`](Self::save_elsewhere)
+    read_only: bool,
+    /// Told about notes created, changed, tagged and deleted
+    webhooks: Vec<Webhook>,
+    /// Set to hand changes to whoever holds the notes instead of saving them
+    forward: Option<Forward>,
+    /// Set when large notes are kept in memory as their start, the rest
+    /// read when it is needed
+    previews: bool,
+}
+
+/// Hands the notes added or changed, and the ids of those removed, to
+/// another process holding the notes in memory, which saves them. Resolves
+/// to false if there is none, for the manager to save them itself.
+pub type Forward = fn(Vec<Note>, Vec<String>) -> Pin<Box<dyn Future<Output = Result<bool>> + Send>>;
+
+/// What a save writes.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    /// The notes file, large notes cut to their start
+    pub notes: Vec<Note>,
+    /// Content of large notes to write, by id; `None` for ones to remove
+    pub bodies: HashMap<String, Option<String>>,
+}
+
+impl Snapshot {
+    /// Takes in what a later save writes, keeping bodies it does not touch.
+    pub fn absorb(&mut self, newer: Snapshot) {
+        self.notes = newer.notes;
+        self.bodies.extend(newer.bodies);
+    }
+}
+
+fn body_path(notes_dir: &Path, id: &str) -> PathBuf {
+    notes_dir.join(BODIES_DIR).join(format!("{}.md", id))
+}
+
+/// The whole content of `note`, stored apart in `notes_dir`.
+fn read_body(notes_dir: &Path, note: &Note) -> Result<String> {
+    let body = body_path(notes_dir, &note.id);
+    fs::read_to_string(&body).map_err(|e| unreadable_body(note, &body, e))
+}
+
+fn unreadable_body(note: &Note, body: &Path, e: std::io::Error) -> anyhow::Error {
+    UserError::Invalid(format!("Note {} keeps its content in {}, which cannot be read: {}", note.id, body.display(), e)).into()
+}
+
+/// `note` with all of its content, read from `notes_dir` if it is in
+/// memory as its start.
+fn with_body(notes_dir: &Path, note: &Note) -> Result<Note> {
+    let mut note = note.clone();
+    if note.stored_apart {
+        note.content = read_body(notes_dir, &note)?;
+        note.stored_apart = false;
+    }
+    Ok(note)
+}
+
+/// Writes `contents` to a file beside `path`, then moves it into place, so
+/// other processes never read half of it and a crash leaves the old one.
+async fn write_replacing(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    async_fs::write(&partial, contents).await?;
+    async_fs::rename(&partial, path).await?;
+    Ok(())
+}
+
+/// Writes `snapshot` as the notes file at `path`, and its bodies next to it.
+pub async fn write_snapshot(path: &Path, snapshot: &Snapshot) -> Result<()> {
+    let notes_dir = path.parent().unwrap_or(Path::new("."));
+    // Bodies first, so the notes file never points at one not written yet
+    for (id, body) in &snapshot.bodies {
+        if let Some(body) = body {
+            async_fs::create_dir_all(notes_dir.join(BODIES_DIR)).await?;
+            write_replacing(&body_path(notes_dir, id), body).await?;
+        }
+    }
+    let content = serde_json::to_string_pretty(&snapshot.notes)?;
+    write_replacing(path, content).await?;
+    for (id, body) in &snapshot.bodies {
+        if body.is_none() {
+            match async_fs::remove_file(body_path(notes_dir, id)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the notes file at `path`, with the content of large notes from
+/// next to it.
+pub fn read_notes(path: &Path) -> Result<Vec<Note>> {
+    read_notes_file(path, true)
+}
+
+/// Reads the notes file at `path`; with `bodies`, large notes get their
+/// content from next to it, otherwise they keep their start.
+fn read_notes_file(path: &Path, bodies: bool) -> Result<Vec<Note>> {
+    let notes_dir = path.parent().unwrap_or(Path::new("."));
+    let mut notes: Vec<Note> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if let Some(note) = notes.iter().find(|note| !Note::is_valid_id(&note.id)) {
+        return Err(UserError::Invalid(format!(
+            "{} has a note with id {:?}; ids are letters, digits, '-' and '_'",
+            path.display(),
+            note.id
+        ))
+        .into());
+    }
+    for note in notes.iter_mut().filter(|note| note.stored_apart) {
+        if bodies {
+            note.content = read_body(notes_dir, note)?;
+            note.stored_apart = false;
+        } else {
+            let body = body_path(notes_dir, &note.id);
+            note.stored_length = fs::metadata(&body).map_err(|e| unreadable_body(note, &body, e))?.len() as usize;
+        }
+    }
+    Ok(notes)
+}
+
+/// Whether files can be created in `dir`, found by writing one. A
+/// read-only mount or missing permissions make it false.
+pub fn dir_is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Files in the bodies directory of `notes_dir` that belong to none of
+/// `notes`' large ones, e.g. left over from restoring a backup.
+pub fn stray_bodies(notes_dir: &Path, notes: &[Note]) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(notes_dir.join(BODIES_DIR)) else {
+        return Vec::new();
+    };
+    let large: HashSet<&str> = notes.iter().filter(|note| note.is_large()).map(|note| note.id.as_str()).collect();
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| !path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|id| large.contains(id)))
+        .collect()
+}
+
+/// Directory of the files attached to the note `id` in `notes_dir`.
+pub fn attachments_dir(notes_dir: &Path, id: &str) -> PathBuf {
+    notes_dir.join(ATTACHMENTS_DIR).join(id)
+}
+
+/// Files in the attachments directory of `notes_dir` that no memo or image
+/// of `notes` points to, such as those of deleted notes.
+pub fn stray_attachments(notes_dir: &Path, notes: &[Note]) -> Vec<PathBuf> {
+    let Ok(dirs) = fs::read_dir(notes_dir.join(ATTACHMENTS_DIR)) else {
+        return Vec::new();
+    };
+    let kept: HashSet<PathBuf> = notes
+        .iter()
+        .flat_map(|note| {
+            let files = note.memos.iter().map(|memo| &memo.file).chain(note.images.iter().map(|image| &image.file));
+            files.map(|file| attachments_dir(notes_dir, &note.id).join(file))
+        })
+        .collect();
+    dirs.filter_map(|dir| fs::read_dir(dir.ok()?.path()).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && !kept.contains(path))
+        .collect()
+}
+
+impl NotesManager {
+    pub async fn new(notes_dir: &Path) -> Result<Self> {
+        Self::open(notes_dir, false).await
+    }
+
+    /// Like [`new`](Self::new), but large notes are kept in memory as their
+    /// start, for a manager held for long. Their content is read when it
+    /// is needed: by [`load_content`](Self::load_content) and
+    /// [`load_all`](Self::load_all), and by searches.
+    pub async fn with_previews(notes_dir: &Path) -> Result<Self> {
+        Self::open(notes_dir, true).await
+    }
+
+    async fn open(notes_dir: &Path, previews: bool) -> Result<Self> {
+        let notes_file = notes_dir.join("notes.json");
+        
+        // Create notes directory if it doesn't exist
+        if !notes_dir.exists() {
+            async_fs::create_dir_all(notes_dir).await?;
+        }
+
+        let notes = if notes_file.exists() {
+            Self::load_notes(&notes_file, !previews).await?
+        } else {
+            Vec::new()
+        };
+        let bodies_stored = notes.iter().filter(|note| note.is_large()).map(|note| note.id.clone()).collect();
+        tracing::debug!(notes = notes.len(), dir = %notes_dir.display(), "Loaded the notes");
+
+        Ok(Self {
+            notes,
+            notes_dir: notes_dir.to_path_buf(),
+            notes_file,
+            saver: None,
+            dirty: HashSet::new(),
+            index: None,
+            bodies_stored,
+            read_only: false,
+            webhooks: Vec::new(),
+            forward: None,
+            previews,
+        })
+    }
+
+    /// Reads the whole content of the note at `index` into memory, if it
+    /// only has its start.
+    pub fn load_content(&mut self, index: usize) -> Result<()> {
+        if let Some(note) = self.notes.get_mut(index).filter(|note| note.stored_apart) {
+            note.content = read_body(&self.notes_dir, note)?;
+            note.stored_apart = false;
+        }
+        Ok(())
+    }
+
+    /// Reads the whole content of every note into memory, for what goes
+    /// through all of it, such as a sync or an export.
+    pub fn load_all(&mut self) -> Result<()> {
+        for index in 0..self.notes.len() {
+            self.load_content(index)?;
+        }
+        Ok(())
+    }
+
+    /// Cuts large notes read into memory back to their start, but for the
+    /// one at `keep`, in a manager opened with previews. Notes not saved
+    /// yet are left whole.
+    pub fn unload_contents(&mut self, keep: Option<usize>) {
+        if !self.previews {
+            return;
+        }
+        for (index, note) in self.notes.iter_mut().enumerate() {
+            let saved = self.bodies_stored.contains(&note.id) && !self.dirty.contains(&note.id);
+            if !note.stored_apart && note.is_large() && saved && keep != Some(index) {
+                let length = note.content.len();
+                *note = note.stored();
+                note.stored_length = length;
+            }
+        }
+    }
+
+    /// `note` with all of its content, read from disk if it only has its
+    /// start.
+    pub fn full_note(&self, note: &Note) -> Result<Note> {
+        with_body(&self.notes_dir, note)
+    }
+
+    fn wants(&self, event: Event) -> bool {
+        self.webhooks.iter().any(|hook| hook.wants(event))
+    }
+
+    /// Tells the webhooks about `event` on `note`, with all of its content.
+    fn notify(&self, event: Event, note: &Note) {
+        let whole = (note.stored_apart && self.wants(event)).then(|| self.full_note(note).ok()).flatten();
+        webhooks::notify(&self.webhooks, event, whole.as_ref().unwrap_or(note));
+    }
+
+    /// Whether `note` matches `query`, reading its whole content when the
+    /// query looks at text and only the start is in memory.
+    fn query_matches(&self, query: &Query, note: &Note) -> bool {
+        if !note.stored_apart || query.terms.is_empty() && query.excluded.is_empty() {
+            return query.matches(note);
+        }
+        self.full_note(note).is_ok_and(|note| query.matches(&note))
+    }
+
+    /// Keeps changes in memory instead of saving them, for a notes
+    /// directory that cannot be written.
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Sends note events to `webhooks` from here on, usually the config's.
+    pub fn set_webhooks(&mut self, webhooks: Vec<Webhook>) {
+        self.webhooks = webhooks;
+    }
+
+    /// Saves through `forward` from here on, writing the notes file only
+    /// when it finds nobody to take the changes.
+    pub fn set_forward(&mut self, forward: Forward) {
+        self.forward = Some(forward);
+    }
+
+    /// Number of notes added, changed or removed but not saved yet, such as
+    /// those held while read-only.
+    pub fn unsaved(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Writes all notes to `dir` instead, e.g. when their own directory
+    /// cannot be written, and saves there from then on.
+    pub async fn save_elsewhere(&mut self, dir: &Path) -> Result<()> {
+        let background = self.saves_in_background();
+        self.save_in_foreground().await?;
+        async_fs::create_dir_all(dir).await?;
+        if !dir_is_writable(dir) {
+            return Err(UserError::Invalid(format!("Cannot write to {}", dir.display())).into());
+        }
+        if dir.join("notes.json").exists() {
+            return Err(UserError::Invalid(format!("{} already has notes; pick an empty directory", dir.display())).into());
+        }
+        self.load_all()?;
+        self.notes_dir = dir.to_path_buf();
+        self.notes_file = dir.join("notes.json");
+        // Everything is new there, bodies of large notes included
+        self.index = None;
+        self.bodies_stored.clear();
+        self.read_only = false;
+        self.write_all().await?;
+        if background {
+            self.save_in_background();
+        }
+        Ok(())
+    }
+
+    async fn load_notes(file_path: &Path, bodies: bool) -> Result<Vec<Note>> {
+        let file_path = file_path.to_path_buf();
+        tokio::task::spawn_blocking(move || read_notes_file(&file_path, bodies)).await?
+    }
+
+    /// Saves the notes if any changed. The JSON file has no way to update
+    /// single notes, so it is written whole, but for the content of large
+    /// notes that did not change. Returns whether the changes were saved or
+    /// handed on, false while read-only.
+    async fn save_notes(&mut self) -> Result<bool> {
+        if self.read_only {
+            return Ok(false);
+        }
+        if self.dirty.is_empty() || self.forwarded().await? {
+            return Ok(true);
+        }
+        self.write_all().await?;
+        Ok(true)
+    }
+
+    /// Hands the unsaved changes to `forward`, if set. Returns whether it
+    /// took them.
+    async fn forwarded(&mut self) -> Result<bool> {
+        let Some(forward) = self.forward else {
+            return Ok(false);
+        };
+        let upserts = self.notes.iter().filter(|note| self.dirty.contains(&note.id)).cloned().collect();
+        let present: HashSet<&str> = self.notes.iter().map(|note| note.id.as_str()).collect();
+        let removed = self.dirty.iter().filter(|id| !present.contains(id.as_str())).cloned().collect();
+        if !forward(upserts, removed).await? {
+            return Ok(false);
+        }
+        self.dirty.clear();
+        Ok(true)
+    }
+
+    async fn write_all(&mut self) -> Result<()> {
+        let large: HashSet<String> = self.notes.iter().filter(|note| note.is_large()).map(|note| note.id.clone()).collect();
+        let mut bodies: HashMap<String, Option<String>> = self
+            .notes
+            .iter()
+            .filter(|note| large.contains(&note.id) && !note.stored_apart)
+            .filter(|note| self.dirty.contains(&note.id) || !self.bodies_stored.contains(&note.id))
+            .map(|note| (note.id.clone(), Some(note.content.clone())))
+            .collect();
+        bodies.extend(self.bodies_stored.difference(&large).map(|id| (id.clone(), None)));
+        let snapshot = Snapshot {
+            notes: self.notes.iter().map(Note::stored).collect(),
+            bodies,
+        };
+        tracing::debug!(
+            changed = self.dirty.len(),
+            bodies = snapshot.bodies.len(),
+            background = self.saver.is_some(),
+            "Saving the notes"
+        );
+        match &mut self.saver {
+            // The saver tries bodies it could not write again with the next save
+            Some(saver) => saver.save(snapshot)?,
+            None => write_snapshot(&self.notes_file, &snapshot).await?,
+        }
+        self.bodies_stored = large;
+        // An index that could not be kept up to date is left alone; the
+        // next one opened brings it up to date
+        if let Some(index) = &mut self.index {
+            if index.update(&self.notes, &self.dirty).is_err() {
+                self.index = None;
+            }
+        }
+        self.dirty.clear();
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        if let Some(note) = self.notes.get(index) {
+            self.dirty.insert(note.id.clone());
+        }
+    }
+
+    /// Makes later saves return at once, writing the notes file on a
+    /// background task; see `flush` and `save_in_foreground`.
+    pub fn save_in_background(&mut self) {
+        if self.saver.is_none() {
+            self.saver = Some(BackgroundSaver::start(self.notes_file.clone()));
+        }
+    }
+
+    pub fn saves_in_background(&self) -> bool {
+        self.saver.is_some()
+    }
+
+    /// Waits for background saves to reach the notes file, e.g. before it
+    /// is read or replaced. Fails if the last one did.
+    pub async fn flush(&mut self) -> Result<()> {
+        match &mut self.saver {
+            Some(saver) => saver.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for background saves, then goes back to saving before
+    /// returning.
+    pub async fn save_in_foreground(&mut self) -> Result<()> {
+        match self.saver.take() {
+            Some(saver) => saver.finish().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps a search index from here on, for a manager searched again and
+    /// again. Without one, as when it cannot be written, searches go
+    /// through every note.
+    pub fn use_search_index(&mut self) {
+        if self.index.is_none() {
+            self.index = SearchIndex::open(&self.notes_dir, &self.notes).ok();
+        }
+    }
+
+    pub fn uses_search_index(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Indexes the notes for search from scratch. Returns the number of
+    /// notes indexed.
+    pub fn reindex(&mut self) -> Result<usize> {
+        let count = SearchIndex::rebuild(&self.notes_dir, &self.notes)?;
+        if self.index.is_some() {
+            self.index = Some(SearchIndex::open(&self.notes_dir, &self.notes)?);
+        }
+        Ok(count)
+    }
+
+    pub fn save_state(&self) -> SaveState {
+        self.saver.as_ref().map_or(SaveState::Idle, BackgroundSaver::state)
+    }
+
+    pub async fn add_note(&mut self, mut note: Note) -> Result<()> {
+        // New notes go to the top of the manual arrangement as well
+        note.sort_order = self.notes.iter().map(|n| n.sort_order).min().unwrap_or(0) - 1;
+        self.notes.insert(0, note); // Insert at the beginning for recent-first order
+        self.mark_dirty(0);
+        if self.save_notes().await? {
+            webhooks::notify(&self.webhooks, Event::Created, &self.notes[0]);
+        }
+        Ok(())
+    }
+
+    pub async fn update_note(&mut self, index: usize, content: String) -> Result<()> {
+        if index < self.notes.len() && self.notes[index].content != content {
+            self.notes[index].update_content(content);
+            self.mark_dirty(index);
+            if self.save_notes().await? {
+                webhooks::notify(&self.webhooks, Event::Updated, &self.notes[index]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `text` as new lines at the end of the note.
+    pub async fn append_to_note(&mut self, index: usize, text: &str) -> Result<()> {
+        self.load_content(index)?;
+        if let Some(note) = self.notes.get(index) {
+            let content = format!("{}\n{}", note.content.trim_end(), text);
+            self.update_note(index, content).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn add_tag(&mut self, index: usize, tag: String) -> Result<()> {
+        if index < self.notes.len() && !self.notes[index].tags.contains(&tag) {
+            self.notes[index].add_tag(tag);
+            self.mark_dirty(index);
+            if self.save_notes().await? {
+                self.notify(Event::Tagged, &self.notes[index]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces all of the note's tags.
+    pub async fn set_tags(&mut self, index: usize, tags: Vec<String>) -> Result<()> {
+        if let Some(note) = self.notes.get_mut(index).filter(|note| note.tags != tags) {
+            note.tags = tags;
+            note.updated_at = Utc::now();
+            self.mark_dirty(index);
+            if self.save_notes().await? {
+                self.notify(Event::Tagged, &self.notes[index]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets or, with `None`, clears the note's reminder.
+    pub async fn set_reminder(&mut self, index: usize, remind_at: Option<DateTime<Utc>>) -> Result<()> {
+        if index < self.notes.len() && self.notes[index].remind_at != remind_at {
+            self.notes[index].remind_at = remind_at;
+            self.mark_dirty(index);
+            self.save_notes().await?;
+        }
+        Ok(())
+    }
+
+    /// Attaches `memo`, whose file is already in the note's attachments
+    /// directory.
+    pub async fn add_memo(&mut self, index: usize, memo: Memo) -> Result<()> {
+        if index < self.notes.len() {
+            self.notes[index].memos.push(memo);
+            self.mark_dirty(index);
+            self.save_notes().await?;
+        }
+        Ok(())
+    }
+
+    /// Attaches images, whose files are already in their notes' attachments
+    /// directories, with a single save.
+    pub async fn add_images(&mut self, images: Vec<(usize, Image)>) -> Result<()> {
+        for (index, image) in images {
+            if index < self.notes.len() {
+                self.notes[index].images.push(image);
+                self.mark_dirty(index);
+            }
+        }
+        self.save_notes().await?;
+        Ok(())
+    }
+
+    /// Marks the habit note at `index` done on `day`, or not done. Returns
+    /// whether that changed anything.
+    pub async fn set_habit_day(&mut self, index: usize, day: NaiveDate, done: bool) -> Result<bool> {
+        let Some(note) = self.notes.get_mut(index) else {
+            return Ok(false);
+        };
+        match (note.habit_days.binary_search(&day), done) {
+            (Err(at), true) => note.habit_days.insert(at, day),
+            (Ok(at), false) => {
+                note.habit_days.remove(at);
+            }
+            _ => return Ok(false),
+        }
+        self.mark_dirty(index);
+        self.save_notes().await?;
+        Ok(true)
+    }
+
+    /// Records how far a note was read, keeping the furthest it got. Not an
+    /// edit, so the note keeps its updated time.
+    pub async fn set_reading(&mut self, index: usize, line: usize, read: usize) -> Result<()> {
+        let Some(note) = self.notes.get_mut(index) else {
+            return Ok(());
+        };
+        let before = note.reading.unwrap_or_default();
+        let reading = Reading { line, read: read.max(before.read) };
+        if note.reading == Some(reading) {
+            return Ok(());
+        }
+        note.reading = Some(reading);
+        self.mark_dirty(index);
+        self.save_notes().await?;
+        Ok(())
+    }
+
+    /// Where the audio of `note`'s `memo` is.
+    pub fn memo_path(&self, note: &Note, memo: &Memo) -> PathBuf {
+        attachments_dir(&self.notes_dir, &note.id).join(&memo.file)
+    }
+
+    pub async fn remove_tag(&mut self, index: usize, tag: &str) -> Result<()> {
+        if index < self.notes.len() && self.notes[index].tags.iter().any(|t| t == tag) {
+            self.notes[index].remove_tag(tag);
+            self.mark_dirty(index);
+            if self.save_notes().await? {
+                self.notify(Event::Tagged, &self.notes[index]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames `old` to `new` on every note carrying it, with a single save.
+    /// Returns the number of notes changed.
+    pub async fn rename_tag(&mut self, old: &str, new: &str) -> Result<usize> {
+        let mut changed = Vec::new();
+        for (index, note) in self.notes.iter_mut().enumerate().filter(|(_, note)| note.tags.iter().any(|t| t == old)) {
+            note.remove_tag(old);
+            note.add_tag(new.to_string());
+            changed.push(index);
+        }
+        for index in &changed {
+            self.mark_dirty(*index);
+        }
+        if !changed.is_empty() && self.save_notes().await? {
+            for index in &changed {
+                self.notify(Event::Tagged, &self.notes[*index]);
+            }
+        }
+        Ok(changed.len())
+    }
+
+    pub async fn delete_note(&mut self, index: usize) -> Result<()> {
+        if index < self.notes.len() {
+            let note = self.notes.remove(index);
+            // Read while it is still there
+            let whole = self.wants(Event::Deleted).then(|| self.full_note(&note).ok()).flatten();
+            self.dirty.insert(note.id.clone());
+            if self.save_notes().await? {
+                webhooks::notify(&self.webhooks, Event::Deleted, whole.as_ref().unwrap_or(&note));
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops notes whose content is only whitespace and rewrites the notes
+    /// file. Returns the number of notes removed.
+    pub async fn compact(&mut self) -> Result<usize> {
+        if self.read_only {
+            return Err(UserError::Invalid(format!("The notes directory {} cannot be written", self.notes_dir.display())).into());
+        }
+        let before = self.notes.len();
+        let dirty = &mut self.dirty;
+        self.notes.retain(|note| {
+            let keep = !note.is_empty();
+            if !keep {
+                dirty.insert(note.id.clone());
+            }
+            keep
+        });
+        if !self.forwarded().await? {
+            self.write_all().await?;
+        }
+        Ok(before - self.notes.len())
+    }
+
+    /// Moves the note at `from` to position `to` and persists the resulting
+    /// order. The current order becomes the manual arrangement.
+    pub async fn move_note(&mut self, from: usize, to: usize) -> Result<()> {
+        if from >= self.notes.len() || to >= self.notes.len() || from == to {
+            return Ok(());
+        }
+
+        let note = self.notes.remove(from);
+        self.notes.insert(to, note);
+        for (i, note) in self.notes.iter_mut().enumerate() {
+            if note.sort_order != i as i64 {
+                note.sort_order = i as i64;
+                self.dirty.insert(note.id.clone());
+            }
+        }
+        self.save_notes().await?;
+        Ok(())
+    }
+
+    pub fn sort_notes(&mut self, mode: SortMode) {
+        match mode {
+            SortMode::Recent => self.notes.sort_by_key(|note| std::cmp::Reverse(note.created_at)),
+            SortMode::Manual => self.notes.sort_by_key(|note| note.sort_order),
+        }
+    }
+
+    pub fn search_notes(&self, query: &str) -> Vec<(usize, &Note)> {
+        self.search_notes_with(&Query::parse(query))
+    }
+
+    pub fn search_notes_with(&self, query: &Query) -> Vec<(usize, &Note)> {
+        let candidates = self.index.as_ref().and_then(|index| index.candidates(query));
+        self.notes
+            .iter()
+            .enumerate()
+            // Large notes are indexed as they were in memory, maybe just their start
+            .filter(|(_, note)| note.is_large() || candidates.as_ref().is_none_or(|ids| ids.contains(note.id.as_str())))
+            .filter(|(_, note)| self.query_matches(query, note))
+            .collect()
+    }
+
+    /// The `limit` most recently changed notes, newest first.
+    pub fn get_recent_notes(&self, limit: usize) -> Vec<&Note> {
+        let mut notes: Vec<&Note> = self.notes.iter().collect();
+        notes.sort_by_key(|note| std::cmp::Reverse(note.updated_at));
+        notes.truncate(limit);
+        notes
+    }
+
+    pub fn get_note_by_id(&self, id: &str) -> Option<&Note> {
+        self.notes.iter().find(|note| note.id == id)
+    }
+
+    /// The note titled `title`, ignoring case and leading `#`s; the most
+    /// recently updated one if several are.
+    pub fn find_by_title(&self, title: &str) -> Option<usize> {
+        let title = title.trim().trim_start_matches('#').trim().to_lowercase();
+        self.notes
+            .iter()
+            .enumerate()
+            .filter(|(_, note)| !title.is_empty() && note.title().to_lowercase() == title)
+            .max_by_key(|(_, note)| note.updated_at)
+            .map(|(index, _)| index)
+    }
+
+    /// Resolves an id, unique id prefix or title, as `append` takes, to a
+    /// note index.
+    pub fn resolve_target(&self, target: &str) -> Result<usize, NoteLookupError> {
+        self.resolve_id(target).or_else(|e| self.find_by_title(target).ok_or(e))
+    }
+
+    /// Resolves a full id or unique id prefix (like git) to a note index.
+    pub fn resolve_id(&self, prefix: &str) -> Result<usize, NoteLookupError> {
+        if let Some(index) = self.notes.iter().position(|note| note.id == prefix) {
+            return Ok(index);
+        }
+
+        let mut matches = self
+            .notes
+            .iter()
+            .enumerate()
+            .filter(|(_, note)| !prefix.is_empty() && note.id.starts_with(prefix))
+            .map(|(i, _)| i);
+        match (matches.next(), matches.count()) {
+            (None, _) => Err(NoteLookupError::NotFound(prefix.to_string())),
+            (Some(index), 0) => Ok(index),
+            (Some(_), rest) => Err(NoteLookupError::Ambiguous {
+                prefix: prefix.to_string(),
+                matches: rest + 1,
+            }),
+        }
+    }
+
+    pub fn get_all_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.notes
+            .iter()
+            .flat_map(|note| note.tags.iter())
+            .cloned()
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Each tag with the number of notes carrying it, by tag.
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for tag in self.notes.iter().flat_map(|note| note.tags.iter()) {
+            *counts.entry(tag.as_str()).or_default() += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().map(|(tag, count)| (tag.to_string(), count)).collect();
+        counts.sort();
+        counts
+    }
+
+    pub fn get_notes_by_tag(&self, tag: &str) -> Vec<&Note> {
+        self.notes
+            .iter()
+            .filter(|note| note.tags.iter().any(|t| t == tag))
+            .collect()
+    }
+
+    pub fn get_notes_directory(&self) -> &PathBuf {
+        &self.notes_dir
+    }
+
+    /// `other` with all of its content, to compare with notes being added.
+    /// A note in memory as its start is read only if one of those is large
+    /// too, as short ones cannot repeat it.
+    fn comparable<'a>(&self, other: &'a Note, large: bool) -> Option<Cow<'a, Note>> {
+        match (other.stored_apart, large) {
+            (false, _) => Some(Cow::Borrowed(other)),
+            (true, true) => self.full_note(other).ok().map(Cow::Owned),
+            (true, false) => None,
+        }
+    }
+
+    /// The note `note` repeats, if any: its index and how closely.
+    pub fn find_duplicate(&self, note: &Note) -> Option<(usize, Duplicate)> {
+        let (exact, near) = (note.content_hash(), note.normalized_hash());
+        let mut found = None;
+        for (index, other) in self.notes.iter().enumerate() {
+            let Some(other) = self.comparable(other, note.is_large()) else {
+                continue;
+            };
+            if other.content_hash() == exact {
+                return Some((index, Duplicate::Exact));
+            }
+            if found.is_none() && other.normalized_hash() == near {
+                found = Some((index, Duplicate::Near));
+            }
+        }
+        found
+    }
+
+    /// Adds `note` unless a note with the same text is already there, in
+    /// which case that note's index is returned and nothing is added.
+    pub async fn add_note_unless_duplicate(&mut self, note: Note) -> Result<Option<usize>> {
+        let hash = note.content_hash();
+        let repeated = |other: &Note| self.comparable(other, note.is_large()).is_some_and(|other| other.content_hash() == hash);
+        if let Some(index) = self.notes.iter().position(repeated) {
+            return Ok(Some(index));
+        }
+        self.add_note(note).await?;
+        Ok(None)
+    }
+
+    /// Splits `notes` into those to import and those repeating a note
+    /// already here or earlier in `notes`, with how closely they do.
+    pub fn split_duplicates(&self, notes: Vec<Note>) -> (Vec<Note>, Vec<(Note, Duplicate)>) {
+        let large = notes.iter().any(Note::is_large);
+        let here: Vec<Cow<Note>> = self.notes.iter().filter_map(|note| self.comparable(note, large)).collect();
+        let mut exact: HashSet<u64> = here.iter().map(|note| note.content_hash()).collect();
+        let mut near: HashSet<u64> = here.iter().map(|note| note.normalized_hash()).collect();
+        let mut unique = Vec::new();
+        let mut duplicates = Vec::new();
+        for note in notes {
+            if !exact.insert(note.content_hash()) {
+                duplicates.push((note, Duplicate::Exact));
+            } else if !near.insert(note.normalized_hash()) {
+                duplicates.push((note, Duplicate::Near));
+            } else {
+                unique.push(note);
+            }
+        }
+        (unique, duplicates)
+    }
+
+    /// Adds a batch of notes with a single save. Notes whose id is already
+    /// taken, or not valid, get a fresh one.
+    pub async fn import_notes(&mut self, notes: Vec<Note>) -> Result<usize> {
+        let count = notes.len();
+        let mut next_order = self.notes.iter().map(|n| n.sort_order).max().unwrap_or(0);
+        let mut taken: HashSet<String> = self.notes.iter().map(|n| n.id.clone()).collect();
+        for mut note in notes {
+            while !Note::is_valid_id(&note.id) || !taken.insert(note.id.clone()) {
+                note.id = Note::new(String::new()).id;
+            }
+            next_order += 1;
+            note.sort_order = next_order;
+            self.dirty.insert(note.id.clone());
+            self.notes.push(note);
+        }
+        self.save_notes().await?;
+        Ok(count)
+    }
+
+    /// Applies changes from elsewhere with a single save: notes in `upserts`
+    /// replace the note with the same id in place, or are added, and notes
+    /// with an id in `removed` are dropped. Fails, changing nothing, if an
+    /// id in `upserts` is not valid.
+    pub async fn merge_notes(&mut self, upserts: Vec<Note>, removed: &[String]) -> Result<()> {
+        if let Some(note) = upserts.iter().find(|note| !Note::is_valid_id(&note.id)) {
+            return Err(UserError::Invalid(format!("Note id {:?} is not valid; ids are letters, digits, '-' and '_'", note.id)).into());
+        }
+        let removed: HashSet<&String> = removed.iter().collect();
+        let dirty = &mut self.dirty;
+        self.notes.retain(|note| {
+            let keep = !removed.contains(&note.id);
+            if !keep {
+                dirty.insert(note.id.clone());
+            }
+            keep
+        });
+        let mut positions: HashMap<String, usize> =
+            self.notes.iter().enumerate().map(|(index, note)| (note.id.clone(), index)).collect();
+        for note in upserts {
+            match positions.get(&note.id) {
+                Some(&index) if self.notes[index] == note => {}
+                Some(&index) => {
+                    self.dirty.insert(note.id.clone());
+                    self.notes[index] = note;
+                }
+                None => {
+                    self.dirty.insert(note.id.clone());
+                    positions.insert(note.id.clone(), self.notes.len());
+                    self.notes.push(note);
+                }
+            }
+        }
+        self.save_notes().await?;
+        Ok(())
+    }
+
+    pub fn export_notes(&self, format: ExportFormat, config: &Config, filter: &ExportFilter) -> Result<String> {
+        let notes = self.filter_notes(filter).into_iter().map(|note| self.full_note(note)).collect::<Result<Vec<_>>>()?;
+        Self::export_selection(&notes.iter().collect::<Vec<_>>(), format, config)
+    }
+
+    /// Notes matching `filter`, in list order.
+    pub fn filter_notes(&self, filter: &ExportFilter) -> Vec<&Note> {
+        self.notes
+            .iter()
+            .filter(|note| filter.matches_fields(note) && filter.query.as_ref().is_none_or(|query| self.query_matches(query, note)))
+            .collect()
+    }
+
+    /// Renders the given notes in `format`.
+    pub fn export_selection(notes: &[&Note], format: ExportFormat, config: &Config) -> Result<String> {
+        match format {
+            ExportFormat::Json => Ok(serde_json::to_string_pretty(notes)?),
+            ExportFormat::Markdown => {
+                let mut output = String::new();
+                output.push_str("# Turbo Notes Export\n\n");
+                
+                for note in notes {
+                    output.push_str(&format!("## Note ({})\n\n", config.format_timestamp(note.created_at)));
+                    output.push_str(&format!("_{}_\n\n", metadata_line(note, config)));
+                    output.push_str(&note.content);
+                    output.push_str("\n\n");
+                    
+                    output.push_str("---\n\n");
+                }
+                
+                Ok(output)
+            }
+            ExportFormat::Html => Ok(html::render(notes, config)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NoteLookupError {
+    #[error("No note matches id '{0}'")]
+    NotFound(String),
+    #[error("Id prefix '{prefix}' is ambiguous ({matches} notes match)")]
+    Ambiguous { prefix: String, matches: usize },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    #[default]
+    Recent,
+    Manual,
+}
+
+impl SortMode {
+    pub fn toggle(self) -> Self {
+        match self {
+            SortMode::Recent => SortMode::Manual,
+            SortMode::Manual => SortMode::Recent,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortMode::Recent => "recent",
+            SortMode::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Markdown, ExportFormat::Json, ExportFormat::Html];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(ExportFormat::Json),
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "markdown",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    /// The next format in [`ExportFormat::ALL`], wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&format| format == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Narrows an export down to some notes. Empty fields match every note.
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    /// Notes with any of these tags
+    pub tags: Vec<String>,
+    /// First local day of creation to include
+    pub since: Option<NaiveDate>,
+    /// Last local day of creation to include
+    pub until: Option<NaiveDate>,
+    /// Time zone `since` and `until` are days in
+    pub zone: Zone,
+    /// Exact note ids, such as a selection in the TUI
+    pub ids: Vec<String>,
+    /// Search query, such as the TUI's active filter
+    pub query: Option<Query>,
+}
+
+impl ExportFilter {
+    pub fn matches(&self, note: &Note) -> bool {
+        self.matches_fields(note) && self.query.as_ref().is_none_or(|query| query.matches(note))
+    }
+
+    /// Whether `note` matches all but the query.
+    fn matches_fields(&self, note: &Note) -> bool {
+        let created = self.zone.day(note.created_at);
+        (self.tags.is_empty() || self.tags.iter().any(|tag| note.tags.contains(tag)))
+            && self.since.is_none_or(|since| created >= since)
+            && self.until.is_none_or(|until| created <= until)
+            && (self.ids.is_empty() || self.ids.contains(&note.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    fn note(id: &str, content: &str) -> Note {
+        Note { id: id.to_string(), ..Note::new(content.to_string()) }
+    }
+
+    #[test]
+    fn ids_are_safe_file_names() {
+        assert!(Note::is_valid_id("1792157766000000000"));
+        assert!(Note::is_valid_id("a1b2-c3_d4"));
+        for id in ["", "../notes", "a/b", "a\\b", "..", "a.b", "é", &"a".repeat(129)] {
+            assert!(!Note::is_valid_id(id), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn notes_file_with_a_bad_id_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.json");
+        let mut outside = note("../../escape", "text");
+        outside.stored_apart = true;
+        fs::write(&path, serde_json::to_string(&[outside]).unwrap()).unwrap();
+
+        let error = read_notes(&path).unwrap_err().to_string();
+        assert!(error.contains("\"../../escape\""), "{}", error);
+    }
+
+    #[test]
+    fn snapshots_replace_the_notes_file_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.json");
+        let body = "word ".repeat(LARGE_NOTE / 5);
+        block_on(async {
+            fs::write(&path, "[]").unwrap();
+            let snapshot = Snapshot {
+                notes: vec![note("1", &body).stored()],
+                bodies: HashMap::from([("1".to_string(), Some(body.clone()))]),
+            };
+            write_snapshot(&path, &snapshot).await.unwrap();
+        });
+
+        let notes = read_notes(&path).unwrap();
+        assert_eq!(notes[0].content, body);
+        let mut files: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        files.extend(fs::read_dir(dir.path().join(BODIES_DIR)).unwrap().map(|entry| entry.unwrap().file_name()));
+        files.sort();
+        assert_eq!(files, ["1.md", BODIES_DIR, "notes.json"]);
+    }
+
+    #[test]
+    fn read_only_notes_are_not_compacted() {
+        let dir = tempfile::tempdir().unwrap();
+        block_on(async {
+            let mut notes = NotesManager::new(dir.path()).await.unwrap();
+            notes.import_notes(vec![note("1", "kept"), note("2", "  ")]).await.unwrap();
+            let before = fs::read_to_string(dir.path().join("notes.json")).unwrap();
+
+            notes.set_read_only();
+            notes.add_note(note("3", "held in memory")).await.unwrap();
+            assert!(notes.compact().await.is_err());
+            assert_eq!(notes.notes.len(), 3);
+            assert_eq!(fs::read_to_string(dir.path().join("notes.json")).unwrap(), before);
+        });
+    }
+
+    #[test]
+    fn imports_renumber_bad_ids_and_merges_refuse_them() {
+        let dir = tempfile::tempdir().unwrap();
+        block_on(async {
+            let mut notes = NotesManager::new(dir.path()).await.unwrap();
+            notes.import_notes(vec![note("../escape", "imported")]).await.unwrap();
+            assert!(Note::is_valid_id(&notes.notes[0].id));
+
+            let error = notes.merge_notes(vec![note("a/b", "merged")], &[]).await.unwrap_err();
+            assert!(error.to_string().contains("\"a/b\""), "{}", error);
+            assert_eq!(notes.notes.len(), 1);
+        });
+    }
+
+    #[test]
+    fn previews_keep_the_start_of_large_notes_and_read_the_rest_on_demand() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = format!("Long read\n{}\nfarthest", "word ".repeat(LARGE_NOTE / 5));
+        block_on(async {
+            let mut notes = NotesManager::new(dir.path()).await.unwrap();
+            notes.add_note(note("1", &content)).await.unwrap();
+            notes.flush().await.unwrap();
+
+            let mut previews = NotesManager::with_previews(dir.path()).await.unwrap();
+            let preview = &previews.notes[0];
+            assert!(preview.stored_apart);
+            assert!(preview.content.len() < content.len());
+            assert_eq!(preview.content_len(), content.len());
+            assert_eq!(previews.search_notes("farthest").len(), 1);
+
+            // Saving a change to its tags leaves the content on disk whole
+            previews.add_tag(0, "books".to_string()).await.unwrap();
+            previews.flush().await.unwrap();
+            assert!(previews.notes[0].stored_apart);
+
+            previews.load_content(0).unwrap();
+            assert_eq!(previews.notes[0].content, content);
+            previews.unload_contents(None);
+            assert!(previews.notes[0].stored_apart);
+        });
+        let notes = block_on(NotesManager::new(dir.path())).unwrap();
+        assert_eq!(notes.notes[0].content, content);
+        assert_eq!(notes.notes[0].tags, ["books"]);
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+/// A Markdown heading of a note.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    /// 1 for `#`, up to 6 for `######`
+    pub level: usize,
+    pub text: String,
+    /// Line of the note it is on, from 0
+    pub line: usize,
+}
+
+/// The `#` headings of `content`, in order. Lines in fenced code blocks
+/// are not headings, nor is `#tag` without a space after the `#`.
+pub fn headings(content: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (line, text) in content.lines().enumerate() {
+        let trimmed = text.trim_start();
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = match fence {
+                Some(open) if open == marker => None,
+                Some(open) => Some(open),
+                None => Some(marker),
+            };
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let rest = &trimmed[level..];
+        if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+            continue;
+        }
+        let text = rest.trim().trim_end_matches('#').trim_end();
+        if !text.is_empty() {
+            headings.push(Heading { level, text: text.to_string(), line });
+        }
+    }
+    headings
+}
+
+/// Whether the heading at `index` has headings under it.
+pub fn has_children(headings: &[Heading], index: usize) -> bool {
+    match (headings.get(index), headings.get(index + 1)) {
+        (Some(heading), Some(next)) => next.level > heading.level,
+        _ => false,
+    }
+}
+
+/// The heading `index` is under, if any.
+pub fn parent(headings: &[Heading], index: usize) -> Option<usize> {
+    let level = headings.get(index)?.level;
+    headings[..index].iter().rposition(|heading| heading.level < level)
+}
+
+/// Indexes of the headings an outline shows: all but those under a
+/// heading in `collapsed`.
+pub fn shown(headings: &[Heading], collapsed: &HashSet<usize>) -> Vec<usize> {
+    let mut shown = Vec::new();
+    // Level of the collapsed heading being skipped under
+    let mut hidden_under: Option<usize> = None;
+    for (i, heading) in headings.iter().enumerate() {
+        match hidden_under {
+            Some(level) if heading.level > level => continue,
+            _ => hidden_under = None,
+        }
+        shown.push(i);
+        if collapsed.contains(&i) {
+            hidden_under = Some(heading.level);
+        }
+    }
+    shown
+}
+
+/// Index of the heading whose section `line` is in: the last one at or
+/// before it.
+pub fn current(headings: &[Heading], line: usize) -> Option<usize> {
+    headings.iter().rposition(|heading| heading.line <= line)
+}
+
+/// The sections `line` is in, outermost first, as a breadcrumb.
+pub fn breadcrumb(headings: &[Heading], line: usize) -> Vec<&str> {
+    let mut trail = Vec::new();
+    let mut at = current(headings, line);
+    while let Some(index) = at {
+        trail.push(headings[index].text.as_str());
+        at = parent(headings, index);
+    }
+    trail.reverse();
+    trail
+}
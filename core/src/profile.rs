@@ -0,0 +1,141 @@
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+use crate::config::Config;
+use crate::error::UserError;
+
+/// Environment variable selecting the profile, like `--profile`
+pub const PROFILE_VAR: &str = "TURBO_NOTES_PROFILE";
+
+/// Name that stands for the default profile, which has no name of its own
+pub const DEFAULT: &str = "default";
+
+/// The profile in use: `None` for the default one. Read from
+/// `TURBO_NOTES_PROFILE` until `select` is called.
+fn slot() -> &'static RwLock<Option<String>> {
+    static ACTIVE: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| {
+        let name = std::env::var(PROFILE_VAR).ok().filter(|name| check_name(name).is_ok() && name != DEFAULT);
+        RwLock::new(name)
+    })
+}
+
+/// The profile in use, `None` for the default one.
+pub fn active() -> Option<String> {
+    slot().read().map(|name| name.clone()).unwrap_or(None)
+}
+
+/// Switches to the profile `name`; `default` or `None` is the default one.
+/// Everything that reads the config, notes or control socket afterwards
+/// uses the profile's.
+pub fn select(name: Option<&str>) -> Result<()> {
+    let name = name.filter(|name| *name != DEFAULT);
+    if let Some(name) = name {
+        check_name(name)?;
+    }
+    if let Ok(mut active) = slot().write() {
+        *active = name.map(str::to_string);
+    }
+    Ok(())
+}
+
+/// Display name of the profile in use.
+pub fn active_name() -> String {
+    active().unwrap_or_else(|| DEFAULT.to_string())
+}
+
+/// Appended to the names of per-instance files such as the control socket,
+/// so each profile can run its own instance: empty for the default profile,
+/// `-work` for the "work" one.
+pub fn suffix() -> String {
+    active().map(|name| format!("-{}", name)).unwrap_or_default()
+}
+
+/// Profile names become directory and socket names: letters, digits, `-`
+/// and `_` only.
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(UserError::Invalid(format!(
+            "Invalid profile name '{}': use letters, digits, '-' and '_'",
+            name
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn config_root() -> Result<PathBuf> {
+    let dir = dirs::config_dir().ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
+    Ok(dir.join("turbo-notes"))
+}
+
+/// Config file of profile `name`: `config.json` in the config directory for
+/// the default profile, `profiles/<name>/config.json` beside it for others.
+pub fn config_file(name: Option<&str>) -> Result<PathBuf> {
+    let root = config_root()?;
+    Ok(match name {
+        Some(name) => root.join("profiles").join(name).join("config.json"),
+        None => root.join("config.json"),
+    })
+}
+
+/// Whether profile `name` has been created. The default one always exists.
+pub fn exists(name: Option<&str>) -> bool {
+    match name {
+        Some(name) => config_file(Some(name)).is_ok_and(|path| path.exists()),
+        None => true,
+    }
+}
+
+/// All profiles, the default one first, then the others by name.
+pub fn list() -> Result<Vec<String>> {
+    let mut names: Vec<String> = match fs::read_dir(config_root()?.join("profiles")) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| check_name(name).is_ok() && exists(Some(name)))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    names.insert(0, DEFAULT.to_string());
+    Ok(names)
+}
+
+/// Creates profile `name` with default settings, keeping its notes in
+/// `notes_dir` or its own directory. Returns its config.
+pub fn create(name: &str, notes_dir: Option<PathBuf>) -> Result<Config> {
+    check_name(name)?;
+    if name == DEFAULT || exists(Some(name)) {
+        return Err(UserError::Invalid(format!("Profile '{}' already exists", name)).into());
+    }
+    let config = Config {
+        notes_dir: notes_dir.unwrap_or_else(|| Config::default_notes_dir(Some(name))),
+        ..Config::default()
+    };
+    config.validate()?;
+    config.save_to(&config_file(Some(name))?)?;
+    Ok(config)
+}
+
+/// Removes profile `name`'s config. Its notes are left where they are.
+pub fn remove(name: &str) -> Result<()> {
+    if name == DEFAULT {
+        return Err(UserError::Invalid("The default profile cannot be removed".into()).into());
+    }
+    check_name(name)?;
+    if !exists(Some(name)) {
+        return Err(UserError::NotFound(format!("No profile '{}'", name)).into());
+    }
+    if active().as_deref() == Some(name) {
+        return Err(UserError::Invalid(format!("Profile '{}' is in use; switch to another one first", name)).into());
+    }
+    fs::remove_dir_all(config_root()?.join("profiles").join(name))?;
+    Ok(())
+}
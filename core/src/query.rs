@@ -0,0 +1,200 @@
+use crate::notes::Note;
+
+/// A parsed search query, shared by the TUI filter and the `search` command.
+///
+/// Syntax: plain words and `"quoted phrases"` must all appear in the note's
+/// content or tags, `#tag` / `tag:name` require a tag, and a leading `-`
+/// excludes notes matching the term. Matching is case-insensitive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub terms: Vec<String>,
+    pub tags: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Self {
+        let mut query = Query::default();
+
+        for token in tokenize(input) {
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+                _ => (false, token),
+            };
+            let token = token.to_lowercase();
+
+            if let Some(tag) = token.strip_prefix('#').or_else(|| token.strip_prefix("tag:")) {
+                if tag.is_empty() {
+                    continue;
+                }
+                if negated {
+                    query.excluded.push(format!("#{}", tag));
+                } else {
+                    query.tags.push(tag.to_string());
+                }
+            } else if negated {
+                query.excluded.push(token);
+            } else {
+                query.terms.push(token);
+            }
+        }
+
+        query
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.tags.is_empty() && self.excluded.is_empty()
+    }
+
+    pub fn matches(&self, note: &Note) -> bool {
+        let mut content = note.content.to_lowercase();
+        // Text read from attached images counts as part of the note
+        for image in &note.images {
+            content.push('\n');
+            content.push_str(&image.text.to_lowercase());
+        }
+        let tags: Vec<String> = note.tags.iter().map(|tag| tag.to_lowercase()).collect();
+        let contains = |term: &str| {
+            content.contains(term) || tags.iter().any(|tag| tag.contains(term))
+        };
+
+        self.terms.iter().all(|term| contains(term))
+            && self.tags.iter().all(|tag| tags.contains(tag))
+            && !self.excluded.iter().any(|term| match term.strip_prefix('#') {
+                Some(tag) => tags.iter().any(|t| t == tag),
+                None => contains(term),
+            })
+    }
+
+    /// Returns the 1-based line numbers and text of lines containing any of
+    /// the query's terms.
+    pub fn matching_lines<'a>(&self, content: &'a str) -> Vec<(usize, &'a str)> {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.to_lowercase();
+                self.terms.iter().any(|term| line.contains(term.as_str()))
+            })
+            .map(|(i, line)| (i + 1, line))
+            .collect()
+    }
+
+    /// Byte ranges of term occurrences in `line`, for highlighting.
+    pub fn match_ranges(&self, line: &str) -> Vec<(usize, usize)> {
+        let lower = line.to_lowercase();
+        // Case folding can change byte lengths; skip highlighting then
+        if lower.len() != line.len() {
+            return Vec::new();
+        }
+
+        let mut ranges = Vec::new();
+        for term in &self.terms {
+            ranges.extend(lower.match_indices(term.as_str()).map(|(start, m)| (start, start + m.len())));
+        }
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
+
+/// Splits on whitespace, keeping `"quoted phrases"` (optionally prefixed
+/// with `-`) together.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(content: &str, tags: &[&str]) -> Note {
+        Note { tags: tags.iter().map(|tag| tag.to_string()).collect(), ..Note::new(content.to_string()) }
+    }
+
+    #[test]
+    fn splits_terms_tags_and_exclusions() {
+        let query = Query::parse(r#"Milk "Oat Drink" #Shop tag:urgent -cheese -#old -tag:done"#);
+        assert_eq!(query.terms, ["milk", "oat drink"]);
+        assert_eq!(query.tags, ["shop", "urgent"]);
+        assert_eq!(query.excluded, ["cheese", "#old", "#done"]);
+    }
+
+    #[test]
+    fn bare_markers_are_not_terms() {
+        let query = Query::parse("- # tag: -#");
+        assert_eq!(query.terms, ["-"]);
+        assert!(query.tags.is_empty());
+        assert!(query.excluded.is_empty());
+        assert!(Query::parse("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_phrases_together_even_unclosed() {
+        assert_eq!(tokenize(r#"a "b c" -"d e""#), ["a", "b c", "-d e"]);
+        assert_eq!(tokenize(r#"a "b c"#), ["a", "b c"]);
+        assert_eq!(Query::parse(r#"-"two words""#).excluded, ["two words"]);
+    }
+
+    #[test]
+    fn tags_must_match_whole_but_terms_match_inside_tags() {
+        let tagged = note("Buy oat milk", &["Shopping"]);
+        assert!(Query::parse("#shopping").matches(&tagged));
+        assert!(!Query::parse("#shop").matches(&tagged));
+        assert!(Query::parse("shop").matches(&tagged));
+        assert!(Query::parse("OAT milk").matches(&tagged));
+        assert!(!Query::parse("oat cheese").matches(&tagged));
+    }
+
+    #[test]
+    fn exclusions_drop_notes() {
+        let tagged = note("Buy oat milk", &["shopping", "done"]);
+        assert!(!Query::parse("milk -oat").matches(&tagged));
+        assert!(!Query::parse("milk -#done").matches(&tagged));
+        // An excluded tag must match whole too
+        assert!(Query::parse("milk -#don").matches(&tagged));
+        assert!(Query::parse("milk -cheese").matches(&tagged));
+    }
+
+    #[test]
+    fn finds_lines_with_any_term() {
+        let query = Query::parse("milk bread #shopping");
+        let lines = query.matching_lines("Groceries\nMilk\neggs\nbread and MILK");
+        assert_eq!(lines, [(2, "Milk"), (4, "bread and MILK")]);
+    }
+
+    #[test]
+    fn merges_overlapping_and_touching_ranges() {
+        let query = Query::parse("ab bc cd x");
+        assert_eq!(query.match_ranges("abcd x ab"), [(0, 4), (5, 6), (7, 9)]);
+        assert_eq!(Query::parse("a").match_ranges("AaA"), [(0, 3)]);
+        assert!(Query::parse("z").match_ranges("abc").is_empty());
+        // Lowercasing changes the length of "İ", so nothing is highlighted
+        assert!(Query::parse("i").match_ranges("İi").is_empty());
+    }
+}
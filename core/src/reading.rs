@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+use crate::notes::Note;
+
+/// Notes with this tag are queued to read later
+pub const READ_LATER_TAG: &str = "readlater";
+
+/// How far a note was read in the note view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    /// Line the view was scrolled to, where it opens again
+    pub line: usize,
+    /// Bytes of the content shown so far, the furthest it got
+    pub read: usize,
+}
+
+pub fn is_read_later(note: &Note) -> bool {
+    note.tags.iter().any(|tag| tag.eq_ignore_ascii_case(READ_LATER_TAG))
+}
+
+/// How much of the note was read, in percent, once it was opened.
+pub fn progress(note: &Note) -> Option<usize> {
+    let reading = note.reading?;
+    match note.content_len() {
+        0 => Some(100),
+        len => Some(reading.read.min(len) * 100 / len),
+    }
+}
+
+/// The text not read yet.
+pub fn unread(note: &Note) -> &str {
+    let read = note.reading.map_or(0, |reading| reading.read);
+    note.content.get(read..).unwrap_or_default()
+}
+
+/// Bytes of the content not read yet.
+pub fn left(note: &Note) -> usize {
+    note.content_len().saturating_sub(note.reading.map_or(0, |reading| reading.read))
+}
+
+/// Whether the note is in the read-later queue: tagged `#readlater`, or
+/// started and not finished, either way until it is read to the end.
+pub fn is_queued(note: &Note) -> bool {
+    match progress(note) {
+        Some(100) => false,
+        Some(_) => true,
+        None => is_read_later(note),
+    }
+}
+
+/// Indexes of the notes in the read-later queue, the least left to read
+/// first.
+pub fn queue(notes: &[Note]) -> Vec<usize> {
+    let mut queued: Vec<usize> = (0..notes.len()).filter(|&i| is_queued(&notes[i])).collect();
+    queued.sort_by_key(|&i| left(&notes[i]));
+    queued
+}
+
+/// Where `line` starts in `content`, as a byte offset; the end for lines
+/// past the last.
+pub fn line_start(content: &str, line: usize) -> usize {
+    match line {
+        0 => 0,
+        line => content.match_indices('\n').nth(line - 1).map_or(content.len(), |(at, _)| at + 1),
+    }
+}
+
+/// Where the text shown from `line` on ends, as a byte offset into
+/// `content`, in `rows` rows of `width` columns with long lines wrapped. A
+/// line that does not fit whole is not counted.
+pub fn shown_to(content: &str, line: usize, width: usize, rows: usize) -> usize {
+    let start = line_start(content, line);
+    let mut end = start;
+    let mut left = rows;
+    for text in content[start..].split_inclusive('\n') {
+        let needed = rows_for(text, width);
+        if needed > left {
+            break;
+        }
+        left -= needed;
+        end += text.len();
+    }
+    end
+}
+
+/// The line to scroll to for the end of `content` to be shown last, in
+/// `rows` rows of `width` columns.
+pub fn last_page(content: &str, width: usize, rows: usize) -> usize {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut left = rows;
+    let mut first = lines.len();
+    for (i, text) in lines.iter().enumerate().rev() {
+        let needed = rows_for(text, width);
+        if needed > left {
+            break;
+        }
+        left -= needed;
+        first = i;
+    }
+    first.min(lines.len().saturating_sub(1))
+}
+
+/// Rows a line takes when wrapped at `width` columns.
+fn rows_for(line: &str, width: usize) -> usize {
+    line.trim_end_matches(['\n', '\r']).chars().count().div_ceil(width.max(1)).max(1)
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use std::fs;
+
+use crate::config::Config;
+use crate::error::UserError;
+use crate::natural;
+use crate::notes::Note;
+
+/// File in the notes directory recording up to when reminders have fired
+const STAMP_FILE: &str = ".reminders-checked";
+
+/// Reminders due longer ago than this when they fire were missed, e.g.
+/// while nothing was running
+const MISSED_AFTER: Duration = Duration::minutes(2);
+
+/// Snoozes offered in the reminders inbox: key, label and length in minutes.
+pub const SNOOZES: [(char, &str, i64); 3] = [('1', "10m", 10), ('2', "1h", 60), ('3', "1d", 24 * 60)];
+
+/// Reads a length of time like `10m`, `90m`, `2h`, `1d` or `1w`.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let unit = text.chars().last()?;
+    let amount: i64 = text[..text.len() - unit.len_utf8()].parse().ok().filter(|amount| *amount > 0)?;
+    match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+/// Reads when to be reminded, relative to `now`: a length of time (`10m`,
+/// `in 2h`), a time of day (`14:30`, the next one), a day as read by
+/// `natural::parse_date` (`tomorrow`, `next fri`, `2026-10-20`, at 09:00)
+/// or a day and time (`fri 14:30`), all in the configured time zone, or an
+/// RFC 3339 timestamp. A time skipped by a daylight saving change is taken
+/// as the time after it.
+pub fn parse_when(text: &str, now: DateTime<Utc>, config: &Config) -> Result<DateTime<Utc>> {
+    let text = text.trim();
+    let zone = config.zone();
+    let today = zone.day(now);
+    let morning = NaiveTime::from_hms_opt(9, 0, 0).expect("valid time");
+    let at = |date: NaiveDate, time: NaiveTime| zone.from_local(date.and_time(time));
+    let day_and_time = || {
+        let (day, time) = text.rsplit_once(' ')?;
+        Some((natural::parse_date(day, today, config)?, NaiveTime::parse_from_str(time, "%H:%M").ok()?))
+    };
+
+    let when = if let Some(duration) = parse_duration(text.strip_prefix("in ").unwrap_or(text)) {
+        Some(now + duration)
+    } else if let Ok(time) = NaiveTime::parse_from_str(text, "%H:%M") {
+        at(today, time)
+            .filter(|when| *when > now)
+            .or_else(|| at(today.succ_opt()?, time))
+    } else if let Some(date) = natural::parse_date(text, today, config) {
+        at(date, morning)
+    } else if let Some((date, time)) = day_and_time() {
+        at(date, time)
+    } else {
+        DateTime::parse_from_rfc3339(text).ok().map(|time| time.to_utc())
+    };
+
+    when.ok_or_else(|| {
+        UserError::Invalid(format!(
+            "Could not read '{}' as a time; use e.g. 10m, 2h, 14:30, tomorrow, next fri or 2026-10-20 09:00",
+            text
+        ))
+        .into()
+    })
+}
+
+/// Notes with a reminder, soonest first.
+pub fn pending(notes: &[Note]) -> Vec<&Note> {
+    let mut pending: Vec<&Note> = notes.iter().filter(|note| note.remind_at.is_some()).collect();
+    pending.sort_by_key(|note| note.remind_at);
+    pending
+}
+
+/// Whether the note's reminder time has passed.
+pub fn is_due(note: &Note, now: DateTime<Utc>) -> bool {
+    note.remind_at.is_some_and(|at| at <= now)
+}
+
+/// Whether the note's reminder went off well before `now`, so it is fired
+/// late.
+pub fn is_missed(note: &Note, now: DateTime<Utc>) -> bool {
+    note.remind_at.is_some_and(|at| now - at > MISSED_AFTER)
+}
+
+/// How long until `at`, e.g. "in 5m", or how long ago it was.
+pub fn until(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let left = at - now;
+    let span = left.abs();
+    let amount = if span.num_days() > 0 {
+        format!("{}d", span.num_days())
+    } else if span.num_hours() > 0 {
+        format!("{}h", span.num_hours())
+    } else {
+        format!("{}m", span.num_minutes().max(1))
+    };
+    if left > Duration::zero() {
+        format!("in {}", amount)
+    } else {
+        format!("{} ago", amount)
+    }
+}
+
+/// Up to when reminders have fired, if they ever have.
+pub fn last_checked(config: &Config) -> Option<DateTime<Utc>> {
+    let stamp = fs::read_to_string(config.notes_dir.join(STAMP_FILE)).ok()?;
+    DateTime::parse_from_rfc3339(stamp.trim()).ok().map(|time| time.with_timezone(&Utc))
+}
+
+/// Reminders that came due since they last fired, including any missed
+/// while nothing was running, and records that they have now. The caller
+/// shows them.
+pub fn take_due<'a>(config: &Config, notes: &'a [Note], now: DateTime<Utc>) -> Result<Vec<&'a Note>> {
+    let since = last_checked(config);
+    let due: Vec<&Note> = pending(notes)
+        .into_iter()
+        .filter(|note| is_due(note, now) && since.is_none_or(|since| note.remind_at > Some(since)))
+        .collect();
+    // Only written when something fired, so a synced notes folder is not
+    // touched every minute
+    if !due.is_empty() {
+        fs::write(config.notes_dir.join(STAMP_FILE), now.to_rfc3339())?;
+    }
+    Ok(due)
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::notes::{self, Snapshot};
+
+/// How long "Saved" shows after a write
+const SAVED_FOR: Duration = Duration::from_secs(2);
+
+/// Where writes of the notes file stand, for the status bar.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveState {
+    /// Nothing written lately
+    Idle,
+    Saving,
+    /// Written a moment ago
+    Saved,
+    Failed(String),
+}
+
+/// What the writing task has done.
+#[derive(Debug, Default)]
+struct Written {
+    /// Number of the last snapshot written, or tried
+    number: u64,
+    at: Option<Instant>,
+    error: Option<String>,
+}
+
+/// Writes the notes file on a task of its own, so the caller never waits on
+/// the disk. Snapshots sent while a write is under way are coalesced: only
+/// the newest notes file is written after it, with every body sent.
+pub struct BackgroundSaver {
+    snapshots: mpsc::UnboundedSender<(u64, Snapshot)>,
+    written: watch::Receiver<Written>,
+    sent: u64,
+    task: JoinHandle<()>,
+}
+
+impl BackgroundSaver {
+    pub fn start(path: PathBuf) -> Self {
+        let (snapshots, mut queue) = mpsc::unbounded_channel::<(u64, Snapshot)>();
+        let (report, written) = watch::channel(Written::default());
+        let task = tokio::spawn(async move {
+            // Bodies of a failed write go out with the next one
+            let mut unwritten: Option<Snapshot> = None;
+            while let Some((mut number, newer)) = queue.recv().await {
+                let mut snapshot = unwritten.take().unwrap_or_default();
+                snapshot.absorb(newer);
+                while let Ok((newer_number, newer)) = queue.try_recv() {
+                    number = newer_number;
+                    snapshot.absorb(newer);
+                }
+                let result = notes::write_snapshot(&path, &snapshot).await;
+                match &result {
+                    Ok(()) => tracing::debug!(number, "Wrote the notes file"),
+                    Err(e) => {
+                        tracing::warn!(number, "Could not write the notes file: {:#}", e);
+                        unwritten = Some(snapshot);
+                    }
+                }
+                report.send_replace(Written {
+                    number,
+                    at: Some(Instant::now()),
+                    error: result.err().map(|e| format!("{:#}", e)),
+                });
+            }
+        });
+        Self {
+            snapshots,
+            written,
+            sent: 0,
+            task,
+        }
+    }
+
+    /// Queues `snapshot` to be written.
+    pub fn save(&mut self, snapshot: Snapshot) -> Result<()> {
+        self.sent += 1;
+        self.snapshots
+            .send((self.sent, snapshot))
+            .map_err(|_| anyhow::anyhow!("The notes can no longer be saved: the writer stopped"))
+    }
+
+    pub fn state(&self) -> SaveState {
+        let written = self.written.borrow();
+        if written.number < self.sent {
+            SaveState::Saving
+        } else if let Some(error) = &written.error {
+            SaveState::Failed(error.clone())
+        } else if written.at.is_some_and(|at| at.elapsed() < SAVED_FOR) {
+            SaveState::Saved
+        } else {
+            SaveState::Idle
+        }
+    }
+
+    /// Waits for what was queued so far to be written. Fails if the last
+    /// write did.
+    pub async fn flush(&mut self) -> Result<()> {
+        let sent = self.sent;
+        let written = self
+            .written
+            .wait_for(|written| written.number >= sent)
+            .await
+            .map_err(|_| anyhow::anyhow!("The notes were not saved: the writer stopped"))?;
+        match &written.error {
+            Some(error) => Err(anyhow::anyhow!("Could not save the notes: {}", error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes what is queued and stops.
+    pub async fn finish(mut self) -> Result<()> {
+        let flushed = self.flush().await;
+        drop(self.snapshots);
+        let _ = self.task.await;
+        flushed
+    }
+}
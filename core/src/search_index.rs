@@ -0,0 +1,281 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::notes::Note;
+use crate::query::Query;
+
+/// The index, in the notes directory
+const INDEX_FILE: &str = "search-index.jsonl";
+const VERSION: u32 = 2;
+
+/// First line of the file.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// A later line: a note's words as of `stamp`, or that it is gone. Later
+/// lines win over earlier ones for the same note.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    id: String,
+    #[serde(default)]
+    stamp: String,
+    #[serde(default)]
+    words: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    removed: bool,
+}
+
+struct Doc {
+    id: String,
+    stamp: String,
+    words: Vec<String>,
+    /// False once the note changed or went away
+    live: bool,
+}
+
+/// Which notes have which words, to narrow searches down before notes are
+/// matched one by one. Kept in the notes directory as lines of JSON, one per
+/// note indexed: changes are appended, and the file is rewritten once most
+/// of it is out of date.
+pub struct SearchIndex {
+    path: PathBuf,
+    docs: Vec<Doc>,
+    by_id: HashMap<String, usize>,
+    /// Docs with each word, live or not
+    postings: HashMap<String, Vec<usize>>,
+    /// Lines in the file after the header
+    lines: usize,
+}
+
+/// The lowercase words of `text`: runs of letters and digits.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        // Lowercasing can bring in characters that are not letters
+        .flat_map(|word| {
+            word.split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+}
+
+/// What a search can match in `note`, deduplicated.
+fn note_words(note: &Note) -> Vec<String> {
+    let mut unique: HashSet<String> = words(&note.content).collect();
+    unique.extend(note.tags.iter().flat_map(|tag| words(tag)));
+    unique.extend(note.images.iter().flat_map(|image| words(&image.text)));
+    let mut words: Vec<String> = unique.into_iter().collect();
+    words.sort_unstable();
+    words
+}
+
+/// Changes whenever what a search can match in `note` does: SHA-256 of its
+/// text, tags and image text, each part prefixed with its length, the same
+/// from one build to the next.
+fn stamp(note: &Note) -> String {
+    let mut hasher = Sha256::new();
+    let parts = std::iter::once(&note.content).chain(&note.tags).chain(note.images.iter().map(|image| &image.text));
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+impl SearchIndex {
+    fn empty(path: PathBuf) -> Self {
+        Self {
+            path,
+            docs: Vec::new(),
+            by_id: HashMap::new(),
+            postings: HashMap::new(),
+            lines: 0,
+        }
+    }
+
+    /// The index of `notes_dir`, brought up to date with `notes`: notes
+    /// changed by other programs, or without the app, are indexed again. A
+    /// missing, older or unreadable file is rebuilt.
+    pub fn open(notes_dir: &Path, notes: &[Note]) -> Result<Self> {
+        let path = notes_dir.join(INDEX_FILE);
+        let mut index = Self::load(&path).unwrap_or_else(|| Self::empty(path.clone()));
+        let current: HashSet<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+        let gone: Vec<String> = index.by_id.keys().filter(|id| !current.contains(id.as_str())).cloned().collect();
+        let stale: Vec<&Note> = notes
+            .iter()
+            .filter(|note| index.by_id.get(&note.id).is_none_or(|&doc| index.docs[doc].stamp != stamp(note)))
+            .collect();
+        if index.lines == 0 {
+            index.insert_all(notes);
+            index.rewrite()?;
+        } else if !gone.is_empty() || !stale.is_empty() {
+            let mut entries = Vec::new();
+            for id in gone {
+                index.remove(&id);
+                entries.push(Entry { id, stamp: String::new(), words: Vec::new(), removed: true });
+            }
+            for note in stale {
+                entries.push(index.insert(note));
+            }
+            index.append(&entries)?;
+        }
+        Ok(index)
+    }
+
+    /// Indexes `notes` from scratch, replacing the file. Returns the number
+    /// of notes indexed.
+    pub fn rebuild(notes_dir: &Path, notes: &[Note]) -> Result<usize> {
+        let mut index = Self::empty(notes_dir.join(INDEX_FILE));
+        index.insert_all(notes);
+        index.rewrite()?;
+        Ok(index.by_id.len())
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let mut lines = BufReader::new(fs::File::open(path).ok()?).lines();
+        let header: Header = serde_json::from_str(&lines.next()?.ok()?).ok()?;
+        if header.version != VERSION {
+            return None;
+        }
+        let mut index = Self::empty(path.to_path_buf());
+        for line in lines {
+            let entry: Entry = serde_json::from_str(&line.ok()?).ok()?;
+            index.lines += 1;
+            if entry.removed {
+                index.remove(&entry.id);
+            } else {
+                index.add(Doc { id: entry.id, stamp: entry.stamp, words: entry.words, live: true });
+            }
+        }
+        Some(index)
+    }
+
+    fn insert_all(&mut self, notes: &[Note]) {
+        for note in notes {
+            self.insert(note);
+        }
+    }
+
+    /// Indexes `note`, replacing what was indexed for it. Returns the line
+    /// recording it.
+    fn insert(&mut self, note: &Note) -> Entry {
+        let entry = Entry { id: note.id.clone(), stamp: stamp(note), words: note_words(note), removed: false };
+        self.add(Doc { id: entry.id.clone(), stamp: entry.stamp.clone(), words: entry.words.clone(), live: true });
+        entry
+    }
+
+    fn add(&mut self, doc: Doc) {
+        self.remove(&doc.id);
+        let number = self.docs.len();
+        for word in &doc.words {
+            self.postings.entry(word.clone()).or_default().push(number);
+        }
+        self.by_id.insert(doc.id.clone(), number);
+        self.docs.push(doc);
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(number) = self.by_id.remove(id) {
+            self.docs[number].live = false;
+        }
+    }
+
+    /// Indexes the notes with ids in `changed` again, or drops them if they
+    /// are no longer in `notes`.
+    pub fn update(&mut self, notes: &[Note], changed: &HashSet<String>) -> Result<()> {
+        if changed.is_empty() {
+            return Ok(());
+        }
+        let positions: HashMap<&str, usize> = notes.iter().enumerate().map(|(i, note)| (note.id.as_str(), i)).collect();
+        let mut entries = Vec::new();
+        for id in changed {
+            match positions.get(id.as_str()) {
+                Some(&i) => entries.push(self.insert(&notes[i])),
+                None if self.by_id.contains_key(id) => {
+                    self.remove(id);
+                    entries.push(Entry { id: id.clone(), stamp: String::new(), words: Vec::new(), removed: true });
+                }
+                None => {}
+            }
+        }
+        self.append(&entries)
+    }
+
+    /// Ids of the notes that may match `query`; every other note cannot.
+    /// `None` if the query has no words to narrow down by, e.g. only
+    /// exclusions or punctuation.
+    pub fn candidates(&self, query: &Query) -> Option<HashSet<&str>> {
+        let parts: Vec<String> = query.terms.iter().chain(&query.tags).flat_map(|term| words(term)).collect();
+        if parts.is_empty() {
+            return None;
+        }
+        let mut found: Option<HashSet<usize>> = None;
+        for part in parts {
+            // A term matches inside words too, as in "meet" for "meeting"
+            let docs: HashSet<usize> = self
+                .postings
+                .iter()
+                .filter(|(word, _)| word.contains(part.as_str()))
+                .flat_map(|(_, docs)| docs.iter().copied())
+                .filter(|&doc| self.docs[doc].live)
+                .filter(|doc| found.as_ref().is_none_or(|found| found.contains(doc)))
+                .collect();
+            let none_left = docs.is_empty();
+            found = Some(docs);
+            if none_left {
+                break;
+            }
+        }
+        Some(found.unwrap_or_default().into_iter().map(|doc| self.docs[doc].id.as_str()).collect())
+    }
+
+    /// Adds `entries` to the file, or rewrites it when most of it would be
+    /// out of date.
+    fn append(&mut self, entries: &[Entry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        if self.lines + entries.len() > 2 * self.by_id.len() + 64 {
+            return self.rewrite();
+        }
+        let mut out = String::new();
+        for entry in entries {
+            out.push_str(&serde_json::to_string(entry)?);
+            out.push('\n');
+        }
+        OpenOptions::new().append(true).open(&self.path)?.write_all(out.as_bytes())?;
+        self.lines += entries.len();
+        Ok(())
+    }
+
+    /// Writes the live docs alone, dropping the ones out of date from
+    /// memory too.
+    fn rewrite(&mut self) -> Result<()> {
+        let docs = std::mem::take(&mut self.docs);
+        self.by_id.clear();
+        self.postings.clear();
+        let mut out = serde_json::to_string(&Header { version: VERSION })?;
+        out.push('\n');
+        for doc in docs.into_iter().filter(|doc| doc.live) {
+            let entry = Entry { id: doc.id.clone(), stamp: doc.stamp.clone(), words: doc.words.clone(), removed: false };
+            out.push_str(&serde_json::to_string(&entry)?);
+            out.push('\n');
+            self.add(doc);
+        }
+        // Written aside first, so a crash never leaves half an index
+        let partial = self.path.with_extension("jsonl.tmp");
+        fs::write(&partial, out)?;
+        fs::rename(&partial, &self.path)?;
+        self.lines = self.by_id.len();
+        Ok(())
+    }
+}
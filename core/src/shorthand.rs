@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Emoji typed as `:name:`
+pub const EMOJI: [(&str, &str); 24] = [
+    ("shrug", "¯\\_(ツ)_/¯"),
+    ("check", "✅"),
+    ("x", "❌"),
+    ("warning", "⚠️"),
+    ("smile", "😄"),
+    ("grin", "😁"),
+    ("wink", "😉"),
+    ("laugh", "😂"),
+    ("sad", "😢"),
+    ("thinking", "🤔"),
+    ("heart", "❤️"),
+    ("thumbsup", "👍"),
+    ("+1", "👍"),
+    ("thumbsdown", "👎"),
+    ("-1", "👎"),
+    ("clap", "👏"),
+    ("fire", "🔥"),
+    ("star", "⭐"),
+    ("tada", "🎉"),
+    ("rocket", "🚀"),
+    ("bulb", "💡"),
+    ("question", "❓"),
+    ("eyes", "👀"),
+    ("coffee", "☕"),
+];
+
+/// What was just typed at the end of `before` expands to: the range of
+/// `before` to replace and what with. `:name:` becomes its emoji as its
+/// closing colon is typed, and one of `shortcuts` as it is followed by a
+/// space or punctuation; `shortcuts` may also name `:name:`s, over the
+/// emoji. A backslash in front keeps the text as typed, taking the
+/// backslash away.
+pub fn expand(before: &str, shortcuts: &BTreeMap<String, String>) -> Option<(Range<usize>, String)> {
+    let typed = before.chars().next_back()?;
+    if typed == ':' {
+        if let Some(expansion) = emoji(before, shortcuts) {
+            return Some(expansion);
+        }
+    }
+    if typed.is_alphanumeric() {
+        return None;
+    }
+    // The word before the space or punctuation just typed
+    let end = before.len() - typed.len_utf8();
+    let start = before[..end].rfind(char::is_whitespace).map_or(0, |space| space + 1);
+    let word = &before[start..end];
+    // A `:name:` kept as typed stays so
+    if word.len() > 1 && word.starts_with(':') && word.ends_with(':') {
+        return None;
+    }
+    match word.strip_prefix('\\') {
+        Some(escaped) if shortcuts.contains_key(escaped) => Some((start..start + 1, String::new())),
+        None => shortcuts.get(word).map(|text| (start..end, text.clone())),
+        _ => None,
+    }
+}
+
+/// A `:name:` ending `before`, as `expand` replaces it.
+fn emoji(before: &str, shortcuts: &BTreeMap<String, String>) -> Option<(Range<usize>, String)> {
+    let end = before.len();
+    let start = before[..end - 1].rfind(':')?;
+    let name = &before[start + 1..end - 1];
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '+' | '-')) {
+        return None;
+    }
+    let text = shortcuts
+        .get(&before[start..end])
+        .map(String::as_str)
+        .or_else(|| EMOJI.iter().find(|(known, _)| *known == name).map(|(_, emoji)| *emoji))?;
+    match before[..start].strip_suffix('\\') {
+        Some(_) => Some((start - 1..start, String::new())),
+        None => Some((start..end, text.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcuts() -> BTreeMap<String, String> {
+        [("brb", "be right back"), (":me:", "Sam")]
+            .into_iter()
+            .map(|(name, text)| (name.to_string(), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expands_emoji_on_the_closing_colon() {
+        let none = BTreeMap::new();
+        assert_eq!(expand("I :smile:", &none), Some((2..9, "😄".to_string())));
+        assert_eq!(expand("café :coffee:", &none), Some((6..14, "☕".to_string())));
+        assert_eq!(expand(":+1:", &none), Some((0..4, "👍".to_string())));
+        assert_eq!(expand("I :smile", &none), None);
+        assert_eq!(expand("a :nope:", &none), None);
+        assert_eq!(expand("a::", &none), None);
+        assert_eq!(expand("", &none), None);
+    }
+
+    #[test]
+    fn expands_shortcuts_after_the_word() {
+        let shortcuts = shortcuts();
+        assert_eq!(expand("ok brb", &shortcuts), None);
+        assert_eq!(expand("ok brb ", &shortcuts), Some((3..6, "be right back".to_string())));
+        assert_eq!(expand("brb,", &shortcuts), Some((0..3, "be right back".to_string())));
+        assert_eq!(expand("ok brbx ", &shortcuts), None);
+        // Shortcuts win over emoji of the same name
+        assert_eq!(expand("by :me:", &shortcuts), Some((3..7, "Sam".to_string())));
+    }
+
+    #[test]
+    fn backslash_keeps_the_text() {
+        let shortcuts = shortcuts();
+        assert_eq!(expand("\\brb ", &shortcuts), Some((0..1, String::new())));
+        assert_eq!(expand("\\other ", &shortcuts), None);
+        assert_eq!(expand("a \\:fire:", &shortcuts), Some((2..3, String::new())));
+        // The `:fire:` left once the backslash is gone is not expanded again
+        assert_eq!(expand("a :fire: ", &shortcuts), None);
+    }
+}
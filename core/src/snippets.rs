@@ -0,0 +1,312 @@
+use chrono::{DateTime, Local};
+
+use crate::notes::Note;
+
+/// Notes with this tag are snippets
+pub const SNIPPET_TAG: &str = "snippet";
+
+pub fn is_snippet(note: &Note) -> bool {
+    note.tags.iter().any(|tag| tag.eq_ignore_ascii_case(SNIPPET_TAG))
+}
+
+/// The text a snippet copies: what follows its title line, without the blank
+/// lines around it. A snippet of a single line copies that line.
+pub fn body(note: &Note) -> &str {
+    let content = note.content.trim_end();
+    match content.split_once('\n') {
+        Some((_, rest)) if !rest.trim().is_empty() => rest.trim_start_matches(['\n', '\r']),
+        _ => content.trim(),
+    }
+}
+
+/// Indexes of the snippets among `notes` whose title or tags fuzzily match
+/// `query`, best first. An empty query matches every snippet, in list order.
+pub fn search(notes: &[Note], query: &str) -> Vec<usize> {
+    let query = query.trim();
+    let mut found: Vec<(i64, usize)> = notes
+        .iter()
+        .enumerate()
+        .filter(|(_, note)| is_snippet(note))
+        .filter_map(|(i, note)| {
+            let text = format!("{} {}", note.title(), note.tags.join(" "));
+            fuzzy_score(query, &text).map(|score| (score, i))
+        })
+        .collect();
+    // Stable, so equal scores keep the list order
+    found.sort_by_key(|(score, _)| -score);
+    found.into_iter().map(|(_, i)| i).collect()
+}
+
+/// How well `query` matches `text`, ignoring case, if all of its characters
+/// appear there in order. Runs of adjacent characters and characters
+/// starting a word score higher, and gaps between them lower.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut from = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let at = from + text[from..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if at == 0 || !text[at - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match previous {
+            Some(previous) if previous + 1 == at => score += 5,
+            Some(previous) => score -= (at - previous - 1).min(5) as i64,
+            None => score -= at.min(10) as i64,
+        }
+        previous = Some(at);
+        from = at + 1;
+    }
+    Some(score)
+}
+
+/// A field of a snippet to fill in, `${1:name}` in its text. Fields with the
+/// same number get the same value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub number: u32,
+    /// Taken when the field is left empty
+    pub default: String,
+}
+
+impl Field {
+    /// What to ask for: the default, or the field's number without one.
+    pub fn label(&self) -> String {
+        if self.default.is_empty() {
+            format!("field {}", self.number)
+        } else {
+            self.default.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    /// `{{name}}`
+    Variable(String),
+    /// `${number}` or `${number:default}`
+    Field(u32),
+}
+
+/// A snippet's text split into plain text, `{{date}}`-style variables and
+/// `${1:name}` fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+    /// In order of their numbers
+    fields: Vec<Field>,
+}
+
+/// Variables a snippet can use, each `{{name}}`
+pub const VARIABLES: [&str; 5] = ["date", "time", "datetime", "weekday", "clipboard"];
+
+impl Template {
+    /// Reads the variables and fields in `text`. Anything that is not one,
+    /// such as an unknown `{{name}}` or shell's `${1:-default}`, stays as it
+    /// is, and `\${` writes `${`.
+    pub fn parse(text: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut fields: Vec<Field> = Vec::new();
+        let mut plain = String::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("\\${") {
+                plain.push_str("${");
+                rest = after;
+                continue;
+            }
+            let found = if rest.starts_with("{{") {
+                variable(rest).map(|(name, len)| (Part::Variable(name), len))
+            } else if rest.starts_with("${") {
+                field(rest).map(|(field, len)| {
+                    let number = field.number;
+                    match fields.iter_mut().find(|known| known.number == number) {
+                        Some(known) if known.default.is_empty() => known.default = field.default,
+                        Some(_) => {}
+                        None => fields.push(field),
+                    }
+                    (Part::Field(number), len)
+                })
+            } else {
+                None
+            };
+            match found {
+                Some((part, len)) => {
+                    if !plain.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut plain)));
+                    }
+                    parts.push(part);
+                    rest = &rest[len..];
+                }
+                None => {
+                    let c = rest.chars().next().unwrap_or_default();
+                    plain.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        if !plain.is_empty() {
+            parts.push(Part::Text(plain));
+        }
+        fields.sort_by_key(|field| field.number);
+        Template { parts, fields }
+    }
+
+    /// The fields to fill in, in order of their numbers.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn uses_clipboard(&self) -> bool {
+        self.parts.contains(&Part::Variable("clipboard".to_string()))
+    }
+
+    /// The text with `values` for the fields, in the order of `fields`;
+    /// fields without a value, or an empty one, get their default.
+    /// `{{clipboard}}` becomes `clipboard`, and the dates are `now`'s.
+    pub fn expand(&self, values: &[String], clipboard: &str, now: DateTime<Local>) -> String {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(plain) => text.push_str(plain),
+                Part::Variable(name) => match name.as_str() {
+                    "date" => text.push_str(&now.format("%Y-%m-%d").to_string()),
+                    "time" => text.push_str(&now.format("%H:%M").to_string()),
+                    "datetime" => text.push_str(&now.format("%Y-%m-%d %H:%M").to_string()),
+                    "weekday" => text.push_str(&now.format("%A").to_string()),
+                    _ => text.push_str(clipboard),
+                },
+                Part::Field(number) => {
+                    let i = self.fields.iter().position(|field| field.number == *number).unwrap_or_default();
+                    match values.get(i).filter(|value| !value.is_empty()) {
+                        Some(value) => text.push_str(value),
+                        None => text.push_str(&self.fields[i].default),
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+/// The known variable `{{name}}` at the start of `text`, and its length.
+fn variable(text: &str) -> Option<(String, usize)> {
+    let end = text.find("}}")?;
+    let name = text[2..end].trim();
+    VARIABLES.contains(&name).then(|| (name.to_string(), end + 2))
+}
+
+/// The field at the start of `text`, and its length. The default runs to
+/// the matching `}`, so it may hold braces of its own.
+fn field(text: &str) -> Option<(Field, usize)> {
+    let inner = &text[2..];
+    let digits = inner.find(|c: char| !c.is_ascii_digit()).unwrap_or(inner.len());
+    let number = inner[..digits].parse().ok()?;
+    let after = &inner[digits..];
+    if after.starts_with('}') {
+        return Some((Field { number, default: String::new() }, 2 + digits + 1));
+    }
+    let default = after.strip_prefix(':')?;
+    if default.starts_with(['-', '=', '?', '+']) {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in default.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => {
+                let field = Field { number, default: default[..i].to_string() };
+                return Some((field, 2 + digits + 1 + i + 1));
+            }
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn snippet(content: &str, tags: &[&str]) -> Note {
+        Note { tags: tags.iter().map(|tag| tag.to_string()).collect(), ..Note::new(content.to_string()) }
+    }
+
+    #[test]
+    fn copies_what_follows_the_title() {
+        assert_eq!(body(&snippet("Title\n\n  code\n\n", &[])), "  code");
+        assert_eq!(body(&snippet(" just this \n", &[])), "just this");
+        assert_eq!(body(&snippet("Title\n\n", &[])), "Title");
+    }
+
+    #[test]
+    fn scores_word_starts_and_runs_higher() {
+        assert_eq!(fuzzy_score("ab", "abc"), Some(15));
+        assert_eq!(fuzzy_score("AB", "ab"), Some(15));
+        assert_eq!(fuzzy_score("ac", "abc"), Some(9));
+        assert_eq!(fuzzy_score("ba", "abc"), None);
+        assert_eq!(fuzzy_score("", "abc"), Some(0));
+        assert!(fuzzy_score("mn", "meeting notes") > fuzzy_score("mn", "common"));
+    }
+
+    #[test]
+    fn searches_only_snippets() {
+        let notes = [
+            snippet("Email reply\nThanks!", &["Snippet"]),
+            snippet("Email", &[]),
+            snippet("Meeting notes template", &["snippet", "work"]),
+        ];
+        assert_eq!(search(&notes, " "), [0, 2]);
+        assert_eq!(search(&notes, "em"), [0, 2]);
+        assert_eq!(search(&notes, "mt"), [2, 0]);
+        assert_eq!(search(&notes, "wrk"), [2]);
+        assert!(search(&notes, "xyz").is_empty());
+    }
+
+    #[test]
+    fn reads_fields_and_variables() {
+        let template = Template::parse("Hi ${1:name}, see {{ date }} at {{time}} {{unknown}} ${2} ${1} \\${3} ${1:-x} ${3:{a}b}");
+        assert_eq!(
+            template.fields(),
+            [
+                Field { number: 1, default: "name".to_string() },
+                Field { number: 2, default: String::new() },
+                Field { number: 3, default: "{a}b".to_string() },
+            ]
+        );
+        assert_eq!(template.fields()[0].label(), "name");
+        assert_eq!(template.fields()[1].label(), "field 2");
+        assert!(!template.uses_clipboard());
+
+        let now = Local.with_ymd_and_hms(2024, 6, 5, 9, 30, 0).unwrap();
+        assert_eq!(
+            template.expand(&["Ann".to_string()], "", now),
+            "Hi Ann, see 2024-06-05 at 09:30 {{unknown}}  Ann ${3} ${1:-x} {a}b"
+        );
+        assert_eq!(
+            template.expand(&[String::new(), "x".to_string(), "y".to_string()], "", now),
+            "Hi name, see 2024-06-05 at 09:30 {{unknown}} x name ${3} ${1:-x} y"
+        );
+    }
+
+    #[test]
+    fn later_fields_give_a_missing_default() {
+        let template = Template::parse("${1} ${1:x} ${2:unclosed");
+        assert_eq!(template.fields(), [Field { number: 1, default: "x".to_string() }]);
+        let now = Local.with_ymd_and_hms(2024, 6, 5, 9, 30, 0).unwrap();
+        assert_eq!(template.expand(&[], "", now), "x x ${2:unclosed");
+    }
+
+    #[test]
+    fn fills_in_the_clipboard_and_dates() {
+        let template = Template::parse("{{weekday}} {{datetime}}: {{clipboard}}");
+        assert!(template.uses_clipboard());
+        let now = Local.with_ymd_and_hms(2024, 6, 5, 9, 30, 0).unwrap();
+        assert_eq!(template.expand(&[], "copied", now), "Wednesday 2024-06-05 09:30: copied");
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::dates::Zone;
+use crate::goals::WordLog;
+use crate::notes::Note;
+
+/// Aggregate numbers about the note collection, shared by the `stats`
+/// command and any statistics view.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotesStats {
+    pub total_notes: usize,
+    pub total_words: usize,
+    pub pinned_notes: usize,
+    pub untagged_notes: usize,
+    /// Tags with their note counts, most used first
+    pub notes_per_tag: Vec<(String, usize)>,
+    /// Notes created on each of the last `days` days, oldest first
+    pub notes_per_day: Vec<(NaiveDate, usize)>,
+    /// Bytes used by the notes directory, including backups
+    pub storage_bytes: u64,
+    /// Words written in the app today, toward the daily word goal
+    pub words_today: usize,
+    /// Days the daily word goal was reached, with the goal, newest first
+    pub goals_reached: Vec<(NaiveDate, usize)>,
+}
+
+impl NotesStats {
+    /// Counts `notes`, with the days in `zone`.
+    pub fn collect(notes: &[Note], notes_dir: &Path, days: usize, zone: Zone) -> Self {
+        let mut notes_per_tag: Vec<(String, usize)> = Vec::new();
+        for tag in notes.iter().flat_map(|note| note.tags.iter()) {
+            match notes_per_tag.iter_mut().find(|(t, _)| t == tag) {
+                Some((_, count)) => *count += 1,
+                None => notes_per_tag.push((tag.clone(), 1)),
+            }
+        }
+        notes_per_tag.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let today = zone.day(Utc::now());
+        let notes_per_day = (0..days as i64)
+            .rev()
+            .map(|ago| {
+                let day = today - Duration::days(ago);
+                let count = notes
+                    .iter()
+                    .filter(|note| zone.day(note.created_at) == day)
+                    .count();
+                (day, count)
+            })
+            .collect();
+
+        let log = WordLog::load(notes_dir).unwrap_or_default();
+
+        Self {
+            total_notes: notes.len(),
+            total_words: notes.iter().map(Note::word_count).sum(),
+            pinned_notes: notes.iter().filter(|note| note.pinned).count(),
+            untagged_notes: notes.iter().filter(|note| note.tags.is_empty()).count(),
+            notes_per_tag,
+            notes_per_day,
+            storage_bytes: dir_size(notes_dir),
+            words_today: log.words(today),
+            goals_reached: log.reached().into_iter().map(|(day, reached)| (day, reached.goal)).collect(),
+        }
+    }
+}
+
+/// Notes created or edited on each day, with the days in `zone`. Only a
+/// note's last edit is known, and one edited the day it was created counts
+/// once.
+pub fn activity(notes: &[Note], zone: Zone) -> HashMap<NaiveDate, usize> {
+    let mut days = HashMap::new();
+    for note in notes {
+        let (created, updated) = (zone.day(note.created_at), zone.day(note.updated_at));
+        *days.entry(created).or_default() += 1;
+        if updated != created {
+            *days.entry(updated).or_default() += 1;
+        }
+    }
+    days
+}
+
+/// Indexes of the notes created or last edited on `day` in `zone`.
+pub fn active_on(notes: &[Note], day: NaiveDate, zone: Zone) -> Vec<usize> {
+    (0..notes.len())
+        .filter(|&i| zone.day(notes[i].created_at) == day || zone.day(notes[i].updated_at) == day)
+        .collect()
+}
+
+/// Total size of the files under `path`.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Renders counts as a one-line sparkline. With `ascii` set, uses plain
+/// characters for terminals and screen readers that lack block glyphs.
+pub fn sparkline(counts: &[usize], ascii: bool) -> String {
+    let levels: &[char] = if ascii {
+        &['_', '.', '-', '=', '#']
+    } else {
+        &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█']
+    };
+    let max = counts.iter().copied().max().unwrap_or(0);
+
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => ' ',
+            _ => levels[(count * levels.len()).div_ceil(max) - 1],
+        })
+        .collect()
+}
+
+/// Formats a byte count as B, KB or MB.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{} B", b),
+        b if b < 1024 * 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
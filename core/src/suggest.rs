@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+/// Tags suggested for a note at most
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Words too common to say what a note is about
+const STOP_WORDS: [&str; 40] = [
+    "the", "and", "for", "are", "but", "not", "you", "all", "any", "can", "had", "her", "was", "one", "our", "out",
+    "has", "have", "this", "that", "with", "from", "they", "will", "what", "when", "where", "which", "there",
+    "their", "then", "than", "them", "into", "about", "just", "some", "also", "been", "were",
+];
+
+/// Tags from `vocabulary`, the tags in use with their note counts, that
+/// fit `content`, best first, leaving out the ones in `taken`. A tag fits
+/// when its words, or words close to them, are among the note's keywords;
+/// ties go to the tags on more notes.
+pub fn tags(content: &str, taken: &[String], vocabulary: &[(String, usize)]) -> Vec<String> {
+    let keywords = keywords(content);
+    if keywords.is_empty() {
+        return Vec::new();
+    }
+    let mut scored: Vec<(usize, usize, &str)> = vocabulary
+        .iter()
+        .filter(|(tag, _)| !taken.iter().any(|taken| taken.eq_ignore_ascii_case(tag)))
+        .filter_map(|(tag, count)| {
+            let score = tag
+                .split(['-', '_', '/', '.'])
+                .filter(|part| part.chars().count() >= 3)
+                .map(|part| part_score(&stem(&part.to_lowercase()), &keywords))
+                .sum::<usize>();
+            (score > 0).then_some((score, *count, tag.as_str()))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(b.2)));
+    scored.into_iter().take(MAX_SUGGESTIONS).map(|(_, _, tag)| tag.to_string()).collect()
+}
+
+/// The stems of the words of `content` that may say what it is about, with
+/// how often each appears.
+fn keywords(content: &str) -> HashMap<String, usize> {
+    let mut keywords = HashMap::new();
+    for word in content.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if word.chars().count() < 3 || word.chars().all(|c| c.is_ascii_digit()) || STOP_WORDS.contains(&word.as_str()) {
+            continue;
+        }
+        *keywords.entry(stem(&word)).or_default() += 1;
+    }
+    keywords
+}
+
+/// `word` without a plural or verb ending, so "meetings" and "meet"
+/// compare equal.
+fn stem(word: &str) -> String {
+    let long_enough = |rest: &&str| rest.chars().count() >= 3;
+    let mut word = match (word.strip_suffix("ies").filter(long_enough), word.strip_suffix('s')) {
+        (Some(rest), _) => format!("{}y", rest),
+        (None, Some(rest)) if long_enough(&rest) && !rest.ends_with('s') => rest.to_string(),
+        _ => word.to_string(),
+    };
+    for ending in ["ing", "ed"] {
+        if let Some(rest) = word.strip_suffix(ending).filter(long_enough) {
+            word = rest.to_string();
+            break;
+        }
+    }
+    word
+}
+
+/// How well a stemmed word of a tag matches the keywords: a keyword that is
+/// the word counts most, one starting with it or it with one less, and one
+/// a typo away least, each by how often it appears.
+fn part_score(part: &str, keywords: &HashMap<String, usize>) -> usize {
+    keywords
+        .iter()
+        .map(|(keyword, &count)| {
+            let weight = if keyword == part {
+                4
+            } else if part.len() >= 4 && keyword.len() >= 4 && (keyword.starts_with(part) || part.starts_with(keyword.as_str())) {
+                2
+            } else if part.chars().count() >= 5 && keyword.len().abs_diff(part.len()) <= 1 && distance(keyword, part) == 1 {
+                1
+            } else {
+                0
+            };
+            weight * count
+        })
+        .sum()
+}
+
+/// Levenshtein distance between `a` and `b`, in characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
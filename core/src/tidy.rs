@@ -0,0 +1,227 @@
+use crate::config::TidyConfig;
+
+/// Notes with this tag are tidied on save even when `tidy.enabled` is off
+pub const TIDY_TAG: &str = "tidy";
+/// Notes with this tag are never tidied
+pub const NO_TIDY_TAG: &str = "notidy";
+
+/// Blank lines kept in a row
+const MAX_BLANK_LINES: usize = 2;
+
+/// Whether a note with `tags` is tidied when saved: with `tidy.enabled`,
+/// unless tagged `#notidy`, and otherwise when tagged `#tidy`.
+pub fn wanted(tags: &[String], config: &TidyConfig) -> bool {
+    let tagged = |name: &str| tags.iter().any(|tag| tag.eq_ignore_ascii_case(name));
+    !tagged(NO_TIDY_TAG) && (config.enabled || tagged(TIDY_TAG))
+}
+
+/// `content` with its Markdown tidied: no trailing whitespace, `-` for
+/// bullets, one space after a heading's `#`s and a blank line around it,
+/// no more than two blank lines in a row, and lines longer than `wrap`
+/// columns wrapped, 0 leaving them. Fenced code stays as it is.
+pub fn tidy(content: &str, wrap: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut after_heading = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            lines.push(line.to_string());
+            continue;
+        }
+        let line = line.trim_end();
+        if after_heading && !line.is_empty() {
+            lines.push(String::new());
+        }
+        after_heading = false;
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = Some(marker);
+            lines.push(line.to_string());
+        } else if let Some(heading) = heading(line) {
+            if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(heading);
+            after_heading = true;
+        } else if line.is_empty() {
+            // None at the start, and only so many in a row
+            let blank = lines.iter().rev().take_while(|last| last.is_empty()).count();
+            if !lines.is_empty() && blank < MAX_BLANK_LINES {
+                lines.push(String::new());
+            }
+        } else {
+            wrap_line(&bullet(line), wrap, &mut lines);
+        }
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// `line` as a heading with one space after its `#`s and no closing ones,
+/// if it is one. `#tag`, without the space, is not.
+fn heading(line: &str) -> Option<String> {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[hashes..];
+    if !(1..=6).contains(&hashes) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some(match text {
+        "" => line[..hashes].to_string(),
+        text => format!("{} {}", &line[..hashes], text),
+    })
+}
+
+/// `line` with a `*` or `+` bullet made `-`. A rule such as `* * *` is
+/// left alone.
+fn bullet(line: &str) -> String {
+    let text = line.trim_start();
+    let indent = &line[..line.len() - text.len()];
+    let is_rule = text.chars().all(|c| matches!(c, '*' | '-' | '_' | ' '));
+    match text.strip_prefix("* ").or_else(|| text.strip_prefix("+ ")) {
+        Some(item) if !is_rule => format!("{}- {}", indent, item),
+        _ => line.to_string(),
+    }
+}
+
+/// Pushes `line` onto `lines`, wrapped at `width` columns when it is
+/// longer. Wrapped lines of a list item or quote line up under its text;
+/// tables, indented code and words longer than a line are left whole.
+fn wrap_line(line: &str, width: usize, lines: &mut Vec<String>) {
+    let text = line.trim_start();
+    if width == 0 || line.chars().count() <= width || text.starts_with('|') || (line.starts_with("    ") && !is_item(text)) {
+        lines.push(line.to_string());
+        return;
+    }
+    let (first, rest) = line.split_at(prefix_len(line));
+    let hanging = match first.trim_start().starts_with('>') {
+        true => first.to_string(),
+        false => " ".repeat(first.chars().count()),
+    };
+    let mut current = first.to_string();
+    let mut empty = true;
+    for word in rest.split_whitespace() {
+        if !empty && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::replace(&mut current, hanging.clone()));
+            empty = true;
+        }
+        if !empty {
+            current.push(' ');
+        }
+        current.push_str(word);
+        empty = false;
+    }
+    lines.push(current);
+}
+
+fn is_item(text: &str) -> bool {
+    text.starts_with("- ") || (text.starts_with(|c: char| c.is_ascii_digit()) && text.contains(". "))
+}
+
+/// Bytes of `line`'s indentation, quote markers and list marker, with a
+/// task's checkbox, that the wrapped text goes after.
+fn prefix_len(line: &str) -> usize {
+    let mut at = line.len() - line.trim_start().len();
+    while line[at..].starts_with("> ") {
+        at += 2;
+    }
+    let rest = &line[at..];
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    if rest.starts_with("- ") {
+        at += 2;
+    } else if digits > 0 && rest[digits..].starts_with(". ") {
+        at += digits + 2;
+    }
+    for checkbox in ["[ ] ", "[x] ", "[X] "] {
+        if line[at..].starts_with(checkbox) {
+            at += checkbox.len();
+        }
+    }
+    at
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn tags_override_the_config() {
+        let on = TidyConfig { enabled: true, wrap: 0 };
+        let off = TidyConfig { enabled: false, wrap: 0 };
+        assert!(wanted(&[], &on));
+        assert!(!wanted(&[], &off));
+        assert!(wanted(&tags(&["Tidy"]), &off));
+        assert!(!wanted(&tags(&["notidy"]), &on));
+        assert!(!wanted(&tags(&["tidy", "NOTIDY"]), &off));
+    }
+
+    #[test]
+    fn spaces_headings_but_not_tags() {
+        let tidied = tidy("Intro\n#  Title ##\nText\n#tag here\n###\n", 0);
+        assert_eq!(tidied, "Intro\n\n# Title\n\nText\n#tag here\n\n###");
+        // Already tidy stays the same
+        assert_eq!(tidy(&tidied, 0), tidied);
+    }
+
+    #[test]
+    fn makes_bullets_dashes_and_trims_lines() {
+        let tidied = tidy("* one  \n+ two\n  * nested\n* * *\n*emphasis*", 0);
+        assert_eq!(tidied, "- one\n- two\n  - nested\n* * *\n*emphasis*");
+    }
+
+    #[test]
+    fn limits_blank_lines() {
+        assert_eq!(tidy("\n\nA\n\n\n\n\nB\n\n", 0), "A\n\n\nB");
+    }
+
+    #[test]
+    fn leaves_fenced_code_alone() {
+        let content = "```\n* x   \n\n\n\n# h\n```\n~~~\n```\n~~~\n* y";
+        assert_eq!(tidy(content, 10), "```\n* x   \n\n\n\n# h\n```\n~~~\n```\n~~~\n- y");
+    }
+
+    #[test]
+    fn wraps_under_the_item_text() {
+        let mut lines = Vec::new();
+        wrap_line("- [ ] buy some oat milk and bread today", 20, &mut lines);
+        wrap_line("> one two three four five six", 20, &mut lines);
+        wrap_line("12. alpha beta gamma delta", 15, &mut lines);
+        assert_eq!(
+            lines,
+            [
+                "- [ ] buy some oat",
+                "      milk and bread",
+                "      today",
+                "> one two three four",
+                "> five six",
+                "12. alpha beta",
+                "    gamma delta",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_unwrappable_lines_whole() {
+        let mut lines = Vec::new();
+        wrap_line("| a table row that is much too long |", 10, &mut lines);
+        wrap_line("    let indented = code_that_is_too_long();", 10, &mut lines);
+        wrap_line("a line left as it is", 0, &mut lines);
+        wrap_line("supercalifragilisticexpialidocious word", 10, &mut lines);
+        assert_eq!(
+            lines,
+            [
+                "| a table row that is much too long |",
+                "    let indented = code_that_is_too_long();",
+                "a line left as it is",
+                "supercalifragilisticexpialidocious",
+                "word",
+            ]
+        );
+    }
+}
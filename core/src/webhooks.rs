@@ -0,0 +1,178 @@
+use anyhow::Result;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::notes::Note;
+
+/// Header carrying the event name
+pub const EVENT_HEADER: &str = "X-Turbo-Notes-Event";
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>` when the webhook
+/// has a secret
+pub const SIGNATURE_HEADER: &str = "X-Turbo-Notes-Signature";
+
+/// How long a delivery may take, and how long a command waits for the
+/// ones still going before it exits
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that happened to a note.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Event {
+    Created,
+    Updated,
+    Deleted,
+    /// Tags added, removed or renamed
+    Tagged,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Created => "created",
+            Event::Updated => "updated",
+            Event::Deleted => "deleted",
+            Event::Tagged => "tagged",
+        }
+    }
+}
+
+/// A URL told about note events with a JSON POST.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// Events to send; empty for all of them
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Key signing each body, so the receiver can tell it came from here
+    #[serde(default)]
+    pub secret: String,
+}
+
+impl Webhook {
+    pub fn wants(&self, event: Event) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Webhook calls for one event, queued for the delivery worker.
+struct Delivery {
+    hooks: Vec<Webhook>,
+    event: Event,
+    body: String,
+}
+
+/// Deliveries queued or under way, and why finished ones failed.
+struct Deliveries {
+    pending: usize,
+    failures: Vec<String>,
+}
+
+static DELIVERIES: Mutex<Deliveries> = Mutex::new(Deliveries {
+    pending: 0,
+    failures: Vec::new(),
+});
+
+/// Feeds the one thread that sends every webhook, in order, started with
+/// the first event.
+static QUEUE: OnceLock<Sender<Delivery>> = OnceLock::new();
+
+fn queue() -> &'static Sender<Delivery> {
+    QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Delivery>();
+        thread::spawn(move || {
+            for delivery in receiver {
+                let failures: Vec<String> = delivery
+                    .hooks
+                    .iter()
+                    .filter_map(|hook| {
+                        let error = deliver(hook, delivery.event, &delivery.body).err()?;
+                        Some(format!("Webhook {} failed: {:#}", hook.url, error))
+                    })
+                    .collect();
+                if let Ok(mut deliveries) = DELIVERIES.lock() {
+                    deliveries.pending -= 1;
+                    deliveries.failures.extend(failures);
+                }
+            }
+        });
+        sender
+    })
+}
+
+/// Tells those of `webhooks` that want `event` about it on `note`, off the
+/// caller's thread.
+pub fn notify(webhooks: &[Webhook], event: Event, note: &Note) {
+    let hooks: Vec<Webhook> = webhooks.iter().filter(|hook| hook.wants(event)).cloned().collect();
+    if hooks.is_empty() {
+        return;
+    }
+    let body = json!({
+        "event": event.name(),
+        "note": note,
+        "sent_at": Utc::now().to_rfc3339(),
+    })
+    .to_string();
+
+    let Ok(mut deliveries) = DELIVERIES.lock() else {
+        return;
+    };
+    if queue().send(Delivery { hooks, event, body }).is_ok() {
+        deliveries.pending += 1;
+    }
+}
+
+fn deliver(hook: &Webhook, event: Event, body: &str) -> Result<()> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let mut request = agent
+        .post(&hook.url)
+        .set("Content-Type", "application/json")
+        .set("User-Agent", concat!("turbo-notes/", env!("CARGO_PKG_VERSION")))
+        .set(EVENT_HEADER, event.name());
+    if !hook.secret.is_empty() {
+        request = request.set(SIGNATURE_HEADER, &format!("sha256={}", sign(&hook.secret, body)));
+    }
+    match request.send_string(body) {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) => Err(anyhow::anyhow!("status {}", status)),
+        Err(ureq::Error::Transport(error)) => {
+            let message = error.to_string();
+            Err(anyhow::anyhow!(message.trim_start_matches(&format!("{}: ", hook.url)).to_string()))
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes any key length");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Why deliveries failed since the last call.
+pub fn take_failures() -> Vec<String> {
+    DELIVERIES
+        .lock()
+        .map(|mut deliveries| std::mem::take(&mut deliveries.failures))
+        .unwrap_or_default()
+}
+
+/// Waits for deliveries still going, so a command does not exit before its
+/// webhooks are sent. Returns why any failed.
+pub fn finish() -> Vec<String> {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let pending = DELIVERIES.lock().map_or(0, |deliveries| deliveries.pending);
+        if pending == 0 || Instant::now() >= deadline {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    take_failures()
+}
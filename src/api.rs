@@ -0,0 +1,309 @@
+use anyhow::Result;
+use chrono::Utc;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::atom;
+use crate::capture::{self, Capture, Source};
+use crate::config::Config;
+use crate::crypto;
+use crate::error::UserError;
+use crate::ics;
+use crate::ipc;
+use crate::notes::{Note, NoteLookupError, NotesManager};
+use crate::query::Query as SearchQuery;
+use crate::webhooks;
+
+#[derive(Clone)]
+struct ApiState {
+    config: Arc<Config>,
+    /// Held while changing the notes file, so requests do not write over
+    /// each other's changes
+    writing: Arc<Mutex<()>>,
+}
+
+impl ApiState {
+    /// The notes as they are on disk now: other commands and the app
+    /// change them too. Changes go through the running instance, like
+    /// other commands'.
+    async fn notes(&self) -> Result<NotesManager> {
+        let mut notes_manager = ipc::open_notes(&self.config).await?;
+        notes_manager.sort_notes(self.config.sort_mode);
+        Ok(notes_manager)
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.config.api.read_only {
+            return Err(ReadOnly.into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The API is read-only; set api.read_only to false to allow changes")]
+struct ReadOnly;
+
+/// Turns a failure into a JSON `{"error": ...}` body, with a status that
+/// says whose fault it was.
+struct ApiError(anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(error: E) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let error = &self.0;
+        let status = if error.is::<ReadOnly>() {
+            StatusCode::FORBIDDEN
+        } else if let Some(error) = error.downcast_ref::<UserError>() {
+            match error {
+                UserError::NotFound(_) => StatusCode::NOT_FOUND,
+                UserError::Invalid(_) => StatusCode::BAD_REQUEST,
+            }
+        } else if let Some(error) = error.downcast_ref::<NoteLookupError>() {
+            match error {
+                NoteLookupError::NotFound(_) => StatusCode::NOT_FOUND,
+                NoteLookupError::Ambiguous { .. } => StatusCode::BAD_REQUEST,
+            }
+        } else {
+            tracing::error!("{:#}", error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        (status, Json(json!({ "error": format!("{:#}", error) }))).into_response()
+    }
+}
+
+type Reply<T> = std::result::Result<T, ApiError>;
+
+/// Makes up the API token on first use and saves it in the config. Returns
+/// whether it did.
+pub fn ensure_token(config: &mut Config) -> Result<bool> {
+    if !config.api.token.is_empty() {
+        return Ok(false);
+    }
+    config.api.token = hex::encode(crypto::random_bytes::<24>()?);
+    config.save()?;
+    Ok(true)
+}
+
+/// Serves the notes over HTTP on `address` until Ctrl+C.
+pub async fn serve(config: Config, address: &str) -> Result<()> {
+    let state = ApiState {
+        config: Arc::new(config),
+        writing: Arc::new(Mutex::new(())),
+    };
+    let api = Router::new()
+        .route("/v1/notes", get(list).post(create))
+        .route("/v1/notes/{id}", get(show).put(update).delete(delete))
+        .route("/v1/search", get(search))
+        .route(CALENDAR_PATH, get(calendar))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate));
+    let app = Router::new()
+        .route("/v1/health", get(|| async { "ok" }))
+        .route(FEED_PATH, get(feed))
+        .merge(api)
+        .with_state(state.clone());
+
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not listen on {}: {}", address, e))?;
+    let mode = if state.config.api.read_only { "read-only" } else { "read-write" };
+    println!("Serving {} ({}) on http://{}", state.config.notes_dir.display(), mode, listener.local_addr()?);
+    tokio::spawn(async {
+        let mut tick = tokio::time::interval(std::time::Duration::from_secs(5));
+        loop {
+            tick.tick().await;
+            for failure in webhooks::take_failures() {
+                tracing::warn!("{}", failure);
+            }
+        }
+    });
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+/// Calendar apps subscribe to a URL and cannot send a header, so this one
+/// also takes the token as `?token=`
+const CALENDAR_PATH: &str = "/v1/calendar.ics";
+
+/// Holds only notes tagged to be public, so feed readers need no token
+const FEED_PATH: &str = "/v1/feed.atom";
+
+#[derive(Deserialize)]
+struct TokenParam {
+    token: String,
+}
+
+async fn authenticate(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let given = header
+        .or_else(|| match request.uri().path() {
+            CALENDAR_PATH => Query::<TokenParam>::try_from_uri(request.uri()).ok().map(|Query(param)| param.token),
+            _ => None,
+        })
+        .unwrap_or_default();
+    if !crypto::same_bytes(given.as_bytes(), state.config.api.token.as_bytes()) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Missing or wrong token" }))).into_response();
+    }
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    tag: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn list(State(state): State<ApiState>, Query(params): Query<ListParams>) -> Reply<Json<Vec<Note>>> {
+    let notes_manager = state.notes().await?;
+    let notes = match params.tag.as_deref() {
+        Some(tag) => notes_manager.get_notes_by_tag(tag.trim_start_matches('#')),
+        None => notes_manager.notes.iter().collect(),
+    };
+    let limit = params.limit.unwrap_or(usize::MAX);
+    Ok(Json(notes.into_iter().take(limit).cloned().collect()))
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+/// Notes matching `q`, in the search syntax of the app and `search`.
+async fn search(State(state): State<ApiState>, Query(params): Query<SearchParams>) -> Reply<Json<Vec<Note>>> {
+    let notes_manager = state.notes().await?;
+    let query = SearchQuery::parse(&params.q);
+    let limit = params.limit.unwrap_or(usize::MAX);
+    Ok(Json(
+        notes_manager
+            .search_notes_with(&query)
+            .into_iter()
+            .take(limit)
+            .map(|(_, note)| note.clone())
+            .collect(),
+    ))
+}
+
+/// Reminders as an iCalendar feed, for subscribing from calendar apps.
+async fn calendar(State(state): State<ApiState>) -> Reply<Response> {
+    let notes_manager = state.notes().await?;
+    let calendar = ics::calendar(&notes_manager.notes, Utc::now());
+    Ok(([(header::CONTENT_TYPE, "text/calendar; charset=utf-8")], calendar).into_response())
+}
+
+/// Recently updated public notes as an Atom feed.
+async fn feed(State(state): State<ApiState>, headers: HeaderMap) -> Reply<Response> {
+    let notes_manager = state.notes().await?;
+    let self_url = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| format!("http://{}{}", host, FEED_PATH));
+    let feed = atom::feed(&notes_manager.notes, &state.config.feed, self_url.as_deref(), Utc::now());
+    Ok(([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], feed).into_response())
+}
+
+/// The note with id, or unique id prefix, `id`.
+async fn show(State(state): State<ApiState>, Path(id): Path<String>) -> Reply<Json<Note>> {
+    let notes_manager = state.notes().await?;
+    let index = notes_manager.resolve_id(&id)?;
+    Ok(Json(notes_manager.notes[index].clone()))
+}
+
+#[derive(Deserialize)]
+struct NewNote {
+    content: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    notebook: Option<String>,
+}
+
+/// Adds a note like `turbo-notes add`: inline `#tags` and `@notebook` are
+/// read from the text, and the config's capture tags are added.
+async fn create(State(state): State<ApiState>, Json(new): Json<NewNote>) -> Reply<(StatusCode, Json<Note>)> {
+    state.check_writable()?;
+    let mut note = Capture::parse(&new.content).into_note();
+    if note.content.is_empty() {
+        return Err(UserError::Invalid("Note content cannot be empty".into()).into());
+    }
+    for tag in new.tags {
+        let tag = tag.trim_start_matches('#').to_string();
+        if !tag.is_empty() && !note.tags.contains(&tag) {
+            note.tags.push(tag);
+        }
+    }
+    if new.notebook.is_some() {
+        note.notebook = new.notebook;
+    }
+    capture::add_config_tags(&mut note, Source::Api, &state.config.capture);
+
+    let _writing = state.writing.lock().await;
+    let note = ipc::add_note(note, &state.config).await?;
+    Ok((StatusCode::CREATED, Json(note)))
+}
+
+/// Changes to a note; what is left out stays as it is.
+#[derive(Deserialize)]
+struct NoteChanges {
+    content: Option<String>,
+    /// Replaces all of the note's tags
+    tags: Option<Vec<String>>,
+}
+
+async fn update(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Json(changes): Json<NoteChanges>,
+) -> Reply<Json<Note>> {
+    state.check_writable()?;
+    let _writing = state.writing.lock().await;
+    let mut notes_manager = state.notes().await?;
+    let index = notes_manager.resolve_id(&id)?;
+    if let Some(content) = changes.content {
+        if content.trim().is_empty() {
+            return Err(UserError::Invalid("Note content cannot be empty; delete the note instead".into()).into());
+        }
+        notes_manager.update_note(index, content).await?;
+    }
+    if let Some(tags) = changes.tags {
+        let mut kept: Vec<String> = Vec::new();
+        for tag in tags {
+            let tag = tag.trim_start_matches('#').to_string();
+            if !tag.is_empty() && !kept.contains(&tag) {
+                kept.push(tag);
+            }
+        }
+        notes_manager.set_tags(index, kept).await?;
+    }
+    Ok(Json(notes_manager.notes[index].clone()))
+}
+
+async fn delete(State(state): State<ApiState>, Path(id): Path<String>) -> Reply<StatusCode> {
+    state.check_writable()?;
+    let _writing = state.writing.lock().await;
+    let mut notes_manager = state.notes().await?;
+    let index = notes_manager.resolve_id(&id)?;
+    notes_manager.delete_note(index).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,19 +1,77 @@
 use anyhow::Result;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+use chrono::{Local, NaiveDate, TimeDelta, Utc};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
 };
-use ratatui::{
-    backend::{Backend, CrosstermBackend},
-    Terminal,
-};
-use std::io;
-use tokio::time::{sleep, Duration};
+use ratatui::{backend::Backend, Terminal};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::sync::oneshot;
+use tokio::time::Duration;
+
+use crate::notes::{self, ExportFilter, ExportFormat, Memo, Note, NoteLookupError, NotesManager, Origin, SortMode};
+use crate::onboarding::{Onboarding, OnboardingEvent, OnboardingStep};
+use crate::outline;
+use crate::plugins::{self, Plugins};
+use crate::pomodoro::{self, Phase, Timer};
+use crate::profile;
+use crate::qr;
+use crate::recorder::{self, Recording};
+use crate::query::Query;
+use crate::reading;
+use crate::goals::{self, WordLog};
+use crate::meeting;
+use crate::shorthand;
+use crate::tidy;
+use crate::graph::{self, Graph};
+use crate::habits;
+use crate::schedule;
+use crate::stats::{self, NotesStats};
+use crate::snippets::{self, Template};
+use crate::suggest;
+use crate::ui::{ViewState, UI};
+use crate::autostart::{self, AutostartSettings};
+use crate::backup::{self, BackupBrowser, BackupStore};
+use crate::capture::{self, Capture, Source};
+use crate::config::{Config, Keymap, SyncBackendKind};
+use crate::clipboard;
+use crate::copies::{self, ConflictCopy};
+use crate::daemon;
+use crate::draft::{self, Draft};
+use crate::editor;
+use crate::input::Input;
+use crate::error::UserError;
+use crate::ipc::{self, Request};
+use crate::html;
+use crate::links;
+use crate::logging;
+use crate::notify;
+use crate::reminders;
+use crate::remote::{self, NewShare, ServerBackend, Share};
+use crate::merge::{ConflictSource, ConflictView, Pick};
+use crate::sync::{self, Resolution, SyncHealth, SyncState};
+use crate::terminal::{self, TerminalGuard};
+use crate::tray::{self, Tray, TrayAction};
+use crate::webhooks;
+use crate::wizard::SetupWizard;
 
-use crate::notes::{Note, NotesManager};
-use crate::ui::UI;
-use crate::config::Config;
+/// Recent notes listed in the widget
+const WIDGET_RECENT: usize = 5;
+
+/// How long to wait for input before checking on timers and other
+/// instances
+const POLL: Duration = Duration::from_millis(100);
+/// How often the screen is redrawn with nothing changed, for relative
+/// timestamps like "5 min ago"
+const REDRAW_IDLE: Duration = Duration::from_secs(30);
+/// How often it is redrawn while a pomodoro counts down the seconds
+const REDRAW_TIMER: Duration = Duration::from_secs(1);
+/// How often text being typed is written to the draft file
+const DRAFT_EVERY: Duration = Duration::from_secs(2);
 
 pub struct App {
     pub notes_manager: NotesManager,
@@ -21,9 +79,129 @@ pub struct App {
     pub config: Config,
     pub widget_mode: bool,
     pub should_quit: bool,
-    pub current_input: String,
+    pub current_input: Input,
     pub selected_note: Option<usize>,
     pub mode: AppMode,
+    pub onboarding: Option<Onboarding>,
+    /// First-run setup, until it is finished
+    pub setup: Option<SetupWizard>,
+    /// Active search filter and the text it was parsed from
+    pub filter: Option<(String, Query)>,
+    /// Set when something else drew over the screen and the next frame
+    /// must be redrawn from scratch
+    pub needs_clear: bool,
+    /// Ids of notes selected with Space, e.g. for export
+    pub marked: HashSet<String>,
+    /// Format picked in the export dialog
+    pub export_format: ExportFormat,
+    /// One-off message shown in the footer until the next key press
+    pub status: Option<String>,
+    /// Sync conflict being resolved
+    pub conflict: Option<ConflictView>,
+    /// Conflicted copy of the notes file under review
+    pub copy: Option<ConflictCopy>,
+    /// Notes edited differently in `copy`, still to be settled
+    copy_queue: Vec<Note>,
+    /// Backup browser, while open
+    pub backups: Option<BackupBrowser>,
+    /// Shown in the header when sync is set up
+    pub sync_health: Option<SyncHealth>,
+    /// When `sync_health` was last worked out
+    sync_checked: Option<Instant>,
+    /// When a failed sync was last looked at for retrying
+    sync_retried: Option<Instant>,
+    /// Modification time of the config file when it was last read
+    config_modified: Option<SystemTime>,
+    /// When the config file was last looked at for edits
+    config_checked: Option<Instant>,
+    /// Control socket, held while this is the running instance
+    listener: Option<ipc::Listener>,
+    /// Tray icon, while running in the background
+    tray: Option<Tray>,
+    /// Recent note selected in the widget, while its list has focus
+    widget_pick: Option<usize>,
+    /// Selected entry in the reminders inbox
+    pub reminder_pick: usize,
+    /// When due reminders were last looked for
+    reminders_checked: Option<Instant>,
+    /// Auto-start settings, while open
+    pub autostart: Option<AutostartSettings>,
+    /// Profiles listed in the switcher, and the selected one
+    pub profiles: Vec<String>,
+    pub profile_pick: usize,
+    /// Links of the selected note in the link picker, and the picked one
+    pub links: Vec<String>,
+    pub link_pick: usize,
+    /// Shares on the sync server with their links, in the shares view
+    pub shares: Vec<(Share, String)>,
+    pub share_pick: usize,
+    /// QR code being shown, what it holds, and the view to go back to
+    pub qr: Option<(String, qr::Code)>,
+    qr_back: AppMode,
+    /// Voice memo being recorded
+    pub recording: Option<Recording>,
+    /// Selected habit in the habits view
+    pub habit_pick: usize,
+    /// Numbers on the stats screen with the notes written or edited each
+    /// day, while it is open, and the day picked on its heatmap
+    pub stats: Option<(NotesStats, HashMap<NaiveDate, usize>)>,
+    pub stats_day: NaiveDate,
+    /// Notes of the day picked on the heatmap, and the selected one
+    pub day_notes: Vec<usize>,
+    pub day_pick: usize,
+    /// Notes joined by wiki links while the graph is open, and the picked
+    /// node
+    pub graph: Option<Graph>,
+    pub graph_pick: usize,
+    /// Snippets matching what is typed in the snippet picker, and the picked one
+    pub snippets: Vec<usize>,
+    pub snippet_pick: usize,
+    /// Snippet being filled in: its title, its text and the fields filled so far
+    pub snippet: Option<(String, Template, Vec<String>)>,
+    /// Tags offered for the note being saved, each with whether it is
+    /// picked, and the one the cursor is on
+    pub tag_suggestions: Vec<(String, bool)>,
+    pub suggestion_pick: usize,
+    /// Plugins loaded from the notes directory
+    pub plugins: Plugins,
+    /// Plugin commands matching what is typed in the command palette, as
+    /// indexes of `plugins.commands()`, and the picked one
+    pub palette: Vec<usize>,
+    pub palette_pick: usize,
+    /// Lines the log view is scrolled up from its end
+    pub log_scroll: usize,
+    /// Draft left by a run that ended before it was saved, until restored
+    /// or discarded
+    pub draft: Option<Draft>,
+    /// Text in the draft file written by this run
+    draft_written: Option<String>,
+    /// When the draft file was last written
+    draft_saved: Option<Instant>,
+    /// Note being typed that was saved with Ctrl+S, and the text saved, so
+    /// saving again updates it instead of adding another
+    editing: Option<(String, String)>,
+    /// The note being typed is a meeting note, started with `N`
+    pub meeting: bool,
+    /// Running pomodoro or break
+    pub pomodoro: Option<Timer>,
+    /// Pomodoros finished since starting, for spacing the long breaks
+    pomodoros_done: u32,
+    /// Showing the read-later queue instead of all notes
+    pub read_later: bool,
+    /// Line the note view is scrolled to
+    pub detail_scroll: usize,
+    /// Note the note view was last scrolled for, to start the next one where
+    /// its reading stopped
+    detail_note: Option<String>,
+    /// Outline of the note's headings shown next to the note view, the
+    /// picked heading and the collapsed ones, as indexes into its headings
+    pub outline_open: bool,
+    pub outline_pick: usize,
+    pub outline_collapsed: HashSet<usize>,
+    /// Daily word goal given for this launch, over the config's
+    pub word_goal: Option<usize>,
+    /// Words written each day, toward the word goal
+    word_log: WordLog,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -32,83 +210,612 @@ pub enum AppMode {
     Insert,
     Search,
     Widget,
+    Detail,
+    Tag,
+    Export,
+    Conflict,
+    Copy,
+    Backups,
+    /// Reminders inbox
+    Reminders,
+    /// Asking when to remind about the selected note
+    Remind,
+    /// Auto-start settings
+    Autostart,
+    /// Picking the profile to switch to
+    Profiles,
+    /// First-run setup
+    Setup,
+    /// Picking a plugin command to run on the selected note
+    Palette,
+    /// Recent log lines
+    Log,
+    /// Asking whether to restore an unsaved draft
+    Draft,
+    /// Asking where to save notes their own directory could not take
+    SaveElsewhere,
+    /// Picking which of the selected note's links to open
+    Links,
+    /// Notes shared on the sync server
+    Shares,
+    /// A QR code of the selected note or share link
+    Qr,
+    /// Recording a voice memo for the selected note
+    Recording,
+    /// Habits with their streaks and calendar
+    Habits,
+    /// Note numbers and the activity heatmap
+    Stats,
+    /// Notes written or edited on the day picked on the heatmap
+    StatsDay,
+    /// Picking a snippet to copy
+    Snippets,
+    /// Filling in the fields of the picked snippet
+    SnippetField,
+    /// Picking tags that fit a note being saved
+    TagSuggestions,
+    /// Notes and the wiki links between them, as a graph
+    Graph,
+    /// Picking a heading in the outline next to the note view
+    Outline,
 }
 
 impl App {
     pub async fn new(widget_mode: bool) -> Result<Self> {
-        let config = Config::load()?;
-        let notes_manager = NotesManager::new(&config.notes_dir).await?;
-        let ui = UI::new();
+        // No config yet: ask for the settings rather than writing defaults
+        let first_run = !widget_mode && !Config::config_file_path()?.exists();
+        let mut config = if first_run { Config::default() } else { Config::load()? };
+        let autostart_changed = match autostart::reconcile(&mut config) {
+            _ if first_run => None,
+            Ok(message) => message,
+            Err(e) => Some(format!("Could not apply auto_start from the config: {:#}", e)),
+        };
+        Self::open(config, widget_mode, first_run, autostart_changed).await
+    }
+
+    /// The app on `config` as it is once set up, e.g. for the
+    /// [`testing`](crate::testing) harness. Unlike [`App::new`], it leaves the
+    /// config file and auto-start alone.
+    pub async fn with_config(config: Config) -> Result<Self> {
+        Self::open(config, false, false, None).await
+    }
+
+    async fn open(
+        config: Config,
+        widget_mode: bool,
+        first_run: bool,
+        autostart_changed: Option<String>,
+    ) -> Result<Self> {
+        let mut notes_manager = NotesManager::with_previews(&config.notes_dir).await?;
+        notes_manager.sort_notes(config.sort_mode);
+        notes_manager.set_webhooks(config.webhooks.clone());
+        // A read-only mount or wrong permissions: the notes can still be read
+        let read_only = !notes::dir_is_writable(&config.notes_dir);
+        if read_only {
+            tracing::warn!(dir = %config.notes_dir.display(), "The notes directory cannot be written; opening read-only");
+            notes_manager.set_read_only();
+        }
+        let mut ui = UI::new();
+        ui.accessible = config.accessibility_mode;
+        ui.theme = config.theme.clone();
+        let onboarding = (!widget_mode && !first_run && !config.onboarding_completed && notes_manager.notes.is_empty())
+            .then(Onboarding::new);
+        let backup_failed = match read_only {
+            true => None,
+            false => backup::backup_if_due(&config).err().map(|e| format!("Automatic backup failed: {}", e)),
+        };
+        let conflicts = SyncState::load(&config).map(|state| state.conflicts.len()).unwrap_or(0);
+        let copies = copies::find(&config.notes_dir).len();
+        let status = match (conflicts, copies) {
+            _ if widget_mode => None,
+            (0, 0) => None,
+            (0, copies) => Some(format!("{} conflicted copies of the notes file: press c to review", copies)),
+            (conflicts, _) => Some(format!("{} sync conflicts: press c to resolve", conflicts)),
+        };
+        let read_only = read_only
+            .then(|| "The notes directory cannot be written: changes are kept until you quit, then saved elsewhere".to_string());
+        let plugins = Plugins::load(&config);
+        let plugins_failed = match plugins.failed.len() {
+            0 => None,
+            failed => Some(format!("{} plugins could not be loaded: press L for the log", failed)),
+        };
+        let status = read_only.or(backup_failed).or(autostart_changed).or(plugins_failed).filter(|_| !widget_mode).or(status);
 
+        let setup = first_run.then(|| SetupWizard::new(config.clone()));
+        let word_log = WordLog::load(&config.notes_dir).unwrap_or_default();
+        let draft = if first_run { None } else { draft::load(&config) };
+        // The widget just takes the text back
+        let mut current_input = Input::default();
+        if let Some(draft) = draft.as_ref().filter(|_| widget_mode) {
+            current_input.set(draft.content.clone());
+        }
         Ok(Self {
             notes_manager,
             ui,
             config,
             widget_mode,
             should_quit: false,
-            current_input: String::new(),
+            current_input,
             selected_note: None,
-            mode: if widget_mode { AppMode::Widget } else { AppMode::Normal },
+            mode: match (widget_mode, first_run) {
+                (true, _) => AppMode::Widget,
+                (false, true) => AppMode::Setup,
+                (false, false) if draft.is_some() => AppMode::Draft,
+                (false, false) => AppMode::Normal,
+            },
+            onboarding,
+            setup,
+            filter: None,
+            needs_clear: false,
+            marked: HashSet::new(),
+            export_format: ExportFormat::Markdown,
+            status,
+            conflict: None,
+            copy: None,
+            copy_queue: Vec::new(),
+            backups: None,
+            sync_health: None,
+            sync_checked: None,
+            sync_retried: None,
+            config_modified: Config::modified(),
+            config_checked: None,
+            listener: None,
+            tray: None,
+            widget_pick: None,
+            reminder_pick: 0,
+            reminders_checked: None,
+            autostart: None,
+            profiles: Vec::new(),
+            profile_pick: 0,
+            links: Vec::new(),
+            link_pick: 0,
+            shares: Vec::new(),
+            share_pick: 0,
+            qr: None,
+            qr_back: AppMode::Normal,
+            recording: None,
+            habit_pick: 0,
+            stats: None,
+            stats_day: NaiveDate::default(),
+            day_notes: Vec::new(),
+            day_pick: 0,
+            graph: None,
+            graph_pick: 0,
+            snippets: Vec::new(),
+            snippet_pick: 0,
+            snippet: None,
+            tag_suggestions: Vec::new(),
+            suggestion_pick: 0,
+            plugins,
+            palette: Vec::new(),
+            palette_pick: 0,
+            log_scroll: 0,
+            draft: draft.filter(|_| !widget_mode),
+            draft_written: None,
+            draft_saved: None,
+            editing: None,
+            meeting: false,
+            pomodoro: None,
+            pomodoros_done: 0,
+            read_later: false,
+            detail_scroll: 0,
+            detail_note: None,
+            outline_open: false,
+            outline_pick: 0,
+            outline_collapsed: HashSet::new(),
+            word_goal: None,
+            word_log,
         })
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        // Setup terminal
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+    /// Re-reads the sync state every few seconds, since syncs run in other
+    /// processes (e.g. `sync --watch`). Returns whether it did.
+    fn refresh_sync_health(&mut self) -> bool {
+        const EVERY: Duration = Duration::from_secs(2);
+        if self.config.sync.backend == SyncBackendKind::None
+            || self.sync_checked.is_some_and(|checked| checked.elapsed() < EVERY)
+        {
+            return false;
+        }
+        self.sync_checked = Some(Instant::now());
+        self.sync_health = SyncState::load(&self.config)
+            .map(|state| state.health(&self.notes_manager.notes))
+            .ok();
+        true
+    }
+
+    /// Tries a failed sync again once its backoff has passed, looking every
+    /// few seconds, as `sync --watch` does. Only with the passphrase in
+    /// `TURBO_NOTES_SYNC_PASSPHRASE`, since the app cannot prompt for it.
+    /// Returns what happened, for the status bar or the log.
+    async fn retry_sync(&mut self) -> Option<String> {
+        const EVERY: Duration = Duration::from_secs(5);
+        if self.config.sync.backend == SyncBackendKind::None
+            || self.notes_manager.is_read_only()
+            || std::env::var_os(sync::PASSPHRASE_VAR).is_none_or(|passphrase| passphrase.is_empty())
+            || self.sync_retried.is_some_and(|retried| retried.elapsed() < EVERY)
+        {
+            return None;
+        }
+        self.sync_retried = Some(Instant::now());
+        let mut state = SyncState::load(&self.config).ok()?;
+        if !state.failure.as_ref().is_some_and(|failure| failure.retry_due(Utc::now())) {
+            return None;
+        }
+        let selected = self.selected_note.and_then(|index| Some((index, self.notes_manager.notes.get(index)?.id.clone())));
+        // Unlocking the store reaches it and derives the key, both slow
+        let config = self.config.clone();
+        let opened = tokio::task::spawn_blocking(move || sync::open_backend(&config))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|opened| opened);
+        let result = match opened {
+            Ok(backend) => sync::sync(&self.config, &mut self.notes_manager, &Arc::new(backend), &mut state).await,
+            Err(e) => Err(e),
+        };
+        self.sync_checked = None;
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                state.record_failure(&e);
+                let _ = state.save(&self.config);
+                let delay = state.failure.as_ref().map(|failure| failure.backoff().as_secs()).unwrap_or_default();
+                return Some(format!("Sync failed: {:#}; retrying in {} seconds", e, delay));
+            }
+        };
+        self.notes_manager.sort_notes(self.config.sort_mode);
+        if let Some((index, id)) = selected {
+            match self.notes_manager.notes.iter().position(|note| note.id == id) {
+                Some(index) => self.selected_note = Some(index),
+                None => self.select_near(index),
+            }
+        }
+        Some(format!(
+            "Synced again: uploaded {}, downloaded {}, {} conflicts",
+            report.uploaded,
+            report.downloaded,
+            report.conflicts.len()
+        ))
+    }
+
+    /// Picks up edits to the config file, looking at most once a second.
+    /// Returns what happened, for the status bar; an invalid file is
+    /// reported and the current settings kept.
+    async fn reload_config(&mut self) -> Option<String> {
+        const EVERY: Duration = Duration::from_secs(1);
+        if self.config_checked.is_some_and(|checked| checked.elapsed() < EVERY) {
+            return None;
+        }
+        self.config_checked = Some(Instant::now());
+        let modified = Config::modified();
+        if modified == self.config_modified {
+            return None;
+        }
+        self.config_modified = modified;
+
+        let config = match Config::read() {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("Config not reloaded: {:#}", e);
+                return Some(format!("Config not reloaded: {:#}", e));
+            }
+        };
+        // Also written by the app itself, e.g. when changing the sort order
+        if serde_json::to_value(&config).ok() == serde_json::to_value(&self.config).ok() {
+            return None;
+        }
+        let notes_moved = config.notes_dir != self.config.notes_dir;
+        let resort = config.sort_mode != self.config.sort_mode;
+        let auto_start = config.auto_start != self.config.auto_start;
+        self.ui.accessible = config.accessibility_mode;
+        self.ui.theme = config.theme.clone();
+        self.notes_manager.set_webhooks(config.webhooks.clone());
+        self.config = config;
+        self.needs_clear = true;
+        if notes_moved {
+            self.selected_note = None;
+            self.marked.clear();
+            self.plugins = Plugins::load(&self.config);
+            if let Err(e) = self.reload_notes().await {
+                return Some(format!("Config reloaded, but could not read the notes: {:#}", e));
+            }
+        } else if resort {
+            self.selected_note = None;
+            self.notes_manager.sort_notes(self.config.sort_mode);
+        }
+        if auto_start {
+            return Some(match autostart::reconcile(&mut self.config) {
+                Ok(Some(changed)) => format!("Config reloaded. {}", changed),
+                Ok(None) => "Config reloaded".to_string(),
+                Err(e) => format!("Config reloaded, but could not apply auto_start: {:#}", e),
+            });
+        }
+        Some("Config reloaded".to_string())
+    }
+
+    /// Indices of the notes shown in the list, honoring the search filter.
+    pub fn visible_notes(&self) -> Vec<usize> {
+        let notes = &self.notes_manager.notes;
+        let mut visible: Vec<usize> = match &self.filter {
+            Some((_, query)) => self.notes_manager.search_notes_with(query).into_iter().map(|(i, _)| i).collect(),
+            None => (0..notes.len()).collect(),
+        };
+        if self.read_later {
+            visible.retain(|&i| reading::is_queued(&notes[i]));
+            visible.sort_by_key(|&i| reading::left(&notes[i]));
+        }
+        visible
+    }
 
-        let result = self.run_app(&mut terminal).await;
+    /// What the export dialog exports: the selected notes if there are any,
+    /// otherwise the notes matching the search filter, otherwise everything.
+    pub fn export_scope(&self) -> (ExportFilter, &'static str) {
+        if !self.marked.is_empty() {
+            let filter = ExportFilter {
+                ids: self.marked.iter().cloned().collect(),
+                ..Default::default()
+            };
+            return (filter, "selected notes");
+        }
+        match &self.filter {
+            Some((_, query)) => {
+                let filter = ExportFilter {
+                    query: Some(query.clone()),
+                    ..Default::default()
+                };
+                (filter, "notes matching the search")
+            }
+            None => (ExportFilter::default(), "notes"),
+        }
+    }
+
+    /// Opens the conflict view on the first open sync conflict. Returns
+    /// false if there is none.
+    pub fn open_conflicts(&mut self) -> Result<bool> {
+        self.notes_manager.load_all()?;
+        let state = SyncState::load(&self.config)?;
+        let Some(conflict) = state.conflicts.first() else {
+            self.conflict = None;
+            return Ok(false);
+        };
+        let local = self.notes_manager.notes.iter().find(|note| note.id == conflict.id);
+        self.conflict = Some(ConflictView::new(local, &conflict.remote, state.conflicts.len(), ConflictSource::Sync));
+        self.mode = AppMode::Conflict;
+        self.status = None;
+        Ok(true)
+    }
+
+    /// Opens the review of the first conflicted copy of the notes file
+    /// left by a file sync tool. Returns false if there is none.
+    pub fn open_copies(&mut self) -> Result<bool> {
+        let Some(path) = copies::find(&self.config.notes_dir).into_iter().next() else {
+            self.copy = None;
+            return Ok(false);
+        };
+        self.notes_manager.load_all()?;
+        self.copy = Some(ConflictCopy::load(&path)?);
+        self.mode = AppMode::Copy;
+        Ok(true)
+    }
+
+    /// Shows the next note edited differently in the copy under review,
+    /// or deletes the copy once none are left.
+    fn next_copy_conflict(&mut self) -> Result<()> {
+        let Some(copy) = self.copy.as_ref() else {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        };
+        let Some(theirs) = self.copy_queue.first() else {
+            let message = format!("Merged {}", copy.file_name());
+            return self.finish_copy(message);
+        };
+        let local = self.notes_manager.notes.iter().find(|note| note.id == theirs.id);
+        let source = ConflictSource::Copy(copy.path.clone());
+        self.conflict = Some(ConflictView::new(local, theirs, self.copy_queue.len(), source));
+        self.mode = AppMode::Conflict;
+        Ok(())
+    }
+
+    /// Deletes the copy under review and moves on to the next one, if any.
+    fn finish_copy(&mut self, message: String) -> Result<()> {
+        if let Some(copy) = self.copy.take() {
+            copy.remove()?;
+        }
+        self.conflict = None;
+        self.notes_manager.sort_notes(self.config.sort_mode);
+        self.selected_note = None;
+        if !self.open_copies()? {
+            self.mode = AppMode::Normal;
+        }
+        self.status = Some(message);
+        Ok(())
+    }
 
-        // Restore terminal
-        disable_raw_mode()?;
-        execute!(
-            terminal.backend_mut(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        )?;
-        terminal.show_cursor()?;
+    /// Opens the backup browser. Returns false if there are no backups.
+    pub fn open_backups(&mut self) -> Result<bool> {
+        let store = BackupStore::new(&self.config);
+        self.notes_manager.load_all()?;
+        self.backups = BackupBrowser::open(&store, &self.notes_manager.notes)?;
+        if self.backups.is_some() {
+            self.mode = AppMode::Backups;
+        }
+        Ok(self.backups.is_some())
+    }
+
+    fn select_relative(&mut self, delta: isize) {
+        let visible = self.visible_notes();
+        if visible.is_empty() {
+            self.selected_note = None;
+            return;
+        }
+
+        let last = visible.len() as isize - 1;
+        let position = match self.selected_note.and_then(|s| visible.iter().position(|&i| i == s)) {
+            Some(position) => (position as isize + delta).clamp(0, last),
+            None if delta < 0 => last,
+            None => 0,
+        };
+        self.selected_note = Some(visible[position as usize]);
+    }
+
+    /// Selects the note with id `id`, clearing a search filter hiding it.
+    pub fn show_note(&mut self, id: &str) {
+        let Some(index) = self.notes_manager.notes.iter().position(|note| note.id == id) else {
+            return;
+        };
+        if !self.visible_notes().contains(&index) {
+            self.filter = None;
+        }
+        self.selected_note = Some(index);
+    }
+
+    /// Selects the first visible note at or after `index`, falling back to
+    /// the last visible one.
+    fn select_near(&mut self, index: usize) {
+        let visible = self.visible_notes();
+        self.selected_note = visible.iter().copied().find(|&i| i >= index).or(visible.last().copied());
+    }
 
-        result
+    pub async fn run(&mut self) -> Result<()> {
+        self.claim().await?;
+        tracing::info!(notes = self.notes_manager.notes.len(), "Opened the app");
+        // Keys are handled while the notes file is written
+        self.notes_manager.save_in_background();
+        self.notes_manager.use_search_index();
+        let result = match TerminalGuard::new() {
+            // The guard restores the terminal when dropped, even on errors
+            Ok(mut terminal) => self.run_app(&mut terminal).await,
+            Err(e) => Err(e),
+        };
+        match &result {
+            // Left on purpose, so whatever was typed was meant to go
+            Ok(()) => self.forget_draft(),
+            Err(e) => tracing::error!("The app stopped: {:#}", e),
+        }
+        // Quitting waits for the last writes
+        let saved = self.notes_manager.save_in_foreground().await;
+        result.and(saved)
     }
 
     pub async fn run_widget(&mut self) -> Result<()> {
+        self.claim().await?;
         self.mode = AppMode::Widget;
-        
-        // Setup terminal for widget
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+        self.widget_pick = None;
+
+        self.notes_manager.save_in_background();
+        self.notes_manager.use_search_index();
+        let result = match TerminalGuard::new() {
+            Ok(mut terminal) => self.run_widget_app(&mut terminal).await,
+            Err(e) => Err(e),
+        };
+        if result.is_ok() {
+            self.forget_draft();
+        }
+        let saved = self.notes_manager.save_in_foreground().await;
+        result.and(saved)
+    }
 
-        let result = self.run_widget_app(&mut terminal).await;
+    /// Keeps the text typed into a new note or the widget in the draft
+    /// file, written every few seconds while it changes, and removes the
+    /// file once the text is saved or dropped.
+    fn autosave_draft(&mut self) {
+        // The draft file would go in the notes directory too
+        if self.notes_manager.is_read_only() {
+            return;
+        }
+        let unsaved = self.editing.as_ref().is_none_or(|(_, saved)| *saved != *self.current_input);
+        let typing = matches!(self.mode, AppMode::Insert | AppMode::Widget) && !self.current_input.trim().is_empty() && unsaved;
+        if !typing {
+            if self.draft_written.is_some() {
+                self.forget_draft();
+            }
+            return;
+        }
+        if self.draft_written.as_deref() == Some(self.current_input.as_str())
+            || self.draft_saved.is_some_and(|saved| saved.elapsed() < DRAFT_EVERY)
+        {
+            return;
+        }
+        self.draft_saved = Some(Instant::now());
+        match draft::save(&self.config, &Draft::new(self.current_input.to_string())) {
+            Ok(()) => self.draft_written = Some(self.current_input.to_string()),
+            Err(e) => tracing::warn!("Could not save the draft: {:#}", e),
+        }
+    }
 
-        // Restore terminal
-        disable_raw_mode()?;
-        execute!(
-            terminal.backend_mut(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        )?;
-        terminal.show_cursor()?;
+    fn forget_draft(&mut self) {
+        self.draft_written = None;
+        if let Err(e) = draft::discard(&self.config) {
+            tracing::warn!("Could not remove the draft: {:#}", e);
+        }
+    }
 
-        result
+    /// Takes the control socket, so later launches hand over to this
+    /// instance instead of writing the notes alongside it.
+    pub(crate) async fn claim(&mut self) -> Result<()> {
+        if self.listener.is_none() {
+            self.listener = Some(ipc::listen().await?);
+        }
+        Ok(())
     }
 
     pub async fn run_background(&mut self) -> Result<()> {
-        // Run in background, listening for hotkey or system tray interaction
-        // For now, just wait for a signal file or timeout
+        self.claim().await?;
+        let _pid_file = daemon::PidFile::create()?;
+        tracing::info!(pid = std::process::id(), notes = self.notes_manager.notes.len(), "Running in the background");
+        self.tray = tray::show(&self.config).unwrap_or_else(|e| {
+            tracing::warn!("No tray icon: {:#}", e);
+            None
+        });
+        let mut shutdown = daemon::Shutdown::listen()?;
+        // Reminders missed while nothing was running go off now
+        self.log_reminders().await;
+        let period = Duration::from_secs(60);
+        let mut tick = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
         loop {
-            sleep(Duration::from_secs(60)).await;
-            
-            // Check if should activate widget
-            if self.should_show_widget().await? {
-                self.run_widget().await?;
+            // One thing at a time, so a signal never cuts a save short
+            tokio::select! {
+                signal = shutdown.recv() => {
+                    tracing::info!("Received {}, shutting down", signal);
+                    break;
+                }
+                _ = tick.tick() => {
+                    // So setting changes apply without a restart
+                    if let Some(message) = self.reload_config().await {
+                        tracing::info!("{}", message);
+                    }
+                    if let Err(e) = backup::backup_if_due(&self.config) {
+                        tracing::error!("Automatic backup failed: {}", e);
+                    }
+                    if let Err(e) = schedule::export_if_due(&self.config).await {
+                        tracing::error!("Scheduled export failed: {}", e);
+                    }
+                    self.log_reminders().await;
+                    if let Some(message) = self.retry_sync().await {
+                        tracing::info!("{}", message);
+                    }
+                    for failure in webhooks::take_failures() {
+                        tracing::warn!("{}", failure);
+                    }
+                    // Taken back once whoever it was handed to has exited
+                    if self.listener.is_none() {
+                        self.listener = ipc::listen().await.ok();
+                    }
+                }
+                _ = pomodoro::finished(&self.pomodoro) => {
+                    // Commands run meanwhile may have changed the notes
+                    if let Err(e) = self.reload_notes().await {
+                        tracing::error!("Could not reload the notes: {:#}", e);
+                    }
+                    match self.advance_pomodoro().await {
+                        Ok(message) => tracing::info!("{}", message),
+                        Err(e) => tracing::error!("Could not log the pomodoro: {:#}", e),
+                    }
+                }
+                Some((request, reply)) = ipc::next(&mut self.listener) => self.answer_in_background(request, reply).await,
+                Some(action) = tray::next(&mut self.tray) => self.answer_tray(action),
             }
-            
+
             if self.should_quit {
                 break;
             }
@@ -116,17 +823,410 @@ impl App {
         Ok(())
     }
 
+    /// Handles a request while nothing is shown, bringing up the widget or
+    /// the main interface when asked to.
+    async fn answer_in_background(&mut self, request: Request, reply: oneshot::Sender<ipc::Reply>) {
+        if matches!(request, Request::ShowWidget | Request::Open { .. }) && !io::stdout().is_terminal() {
+            // Started without a terminal: the caller shows it in its own,
+            // holding the socket until it exits
+            self.listener = None;
+            let _ = reply.send(ipc::Reply { released: true, ..ipc::Reply::ok("Handed over") });
+            return;
+        }
+        // Commands run meanwhile may have changed the notes
+        if let Err(e) = self.reload_notes().await {
+            let _ = reply.send(ipc::Reply::error(format!("{:#}", e)));
+            return;
+        }
+        let shown = match request {
+            Request::ShowWidget => {
+                // Answered first: the caller should not wait for the widget to close
+                let _ = reply.send(ipc::Reply::ok("Showing the widget"));
+                self.current_input.clear();
+                self.run_widget().await
+            }
+            Request::Open { note } => {
+                let _ = reply.send(ipc::Reply::ok("Opened Turbo Notes in the running instance"));
+                self.mode = AppMode::Normal;
+                self.selected_note = None;
+                if let Some(id) = note {
+                    self.show_note(&id);
+                }
+                self.needs_clear = true;
+                self.run().await
+            }
+            request => {
+                let _ = reply.send(self.answer(request).await);
+                return;
+            }
+        };
+        if let Err(e) = shown {
+            tracing::error!("Could not show Turbo Notes: {}", e);
+        }
+        // Closing it does not stop the background instance
+        self.should_quit = false;
+    }
+
+    /// Answers requests from other instances. Returns whether there were
+    /// any.
+    pub(crate) async fn answer_pending(&mut self) -> bool {
+        let mut answered = false;
+        while let Some((request, reply)) = self.listener.as_mut().and_then(|listener| listener.requests.try_recv().ok()) {
+            let _ = reply.send(self.answer(request).await);
+            answered = true;
+        }
+        answered
+    }
+
+    async fn answer(&mut self, request: Request) -> ipc::Reply {
+        let answer = match request {
+            Request::Ping => Ok(ipc::Reply::ok("Turbo Notes is running")),
+            Request::ShowWidget if self.mode == AppMode::Normal => {
+                self.mode = AppMode::Insert;
+                self.current_input.clear();
+                Ok(ipc::Reply::ok("Turbo Notes is open in another terminal; started a new note there"))
+            }
+            Request::Open { note: Some(id) } if self.mode == AppMode::Normal => {
+                self.show_note(&id);
+                self.status = Some("Showing the note picked in another terminal".into());
+                Ok(ipc::Reply::ok("Turbo Notes is open in another terminal; showing the note there"))
+            }
+            Request::ShowWidget | Request::Open { .. } => {
+                self.status = Some("Turbo Notes was launched again and handed over here".into());
+                Ok(ipc::Reply::ok("Turbo Notes is already open in another terminal"))
+            }
+            Request::AddNote { content, tags, notebook, origin } => {
+                self.add_requested_note(content, tags, notebook, origin).await
+            }
+            Request::ChangeNotes { upserts, removed } => self.apply_requested_changes(upserts, removed).await,
+            Request::AppendNote { target, text, create } => self.append_requested(&target, &text, create).await,
+        };
+        answer.unwrap_or_else(|e| ipc::Reply::error(format!("{:#}", e)))
+    }
+
+    async fn add_requested_note(
+        &mut self,
+        content: String,
+        tags: Vec<String>,
+        notebook: Option<String>,
+        origin: Option<Origin>,
+    ) -> Result<ipc::Reply> {
+        if content.trim().is_empty() {
+            return Err(UserError::Invalid("Note content cannot be empty".into()).into());
+        }
+        self.check_writable()?;
+        let mut note = Note::new(content);
+        for tag in tags {
+            let tag = tag.trim_start_matches('#').to_string();
+            if !tag.is_empty() && !note.tags.contains(&tag) {
+                note.tags.push(tag);
+            }
+        }
+        note.notebook = notebook;
+        note.origin = origin;
+
+        // Sent back whole, as the save may still be under way when the
+        // caller reads it
+        if let Some(index) = self.add_captured_note(note).await? {
+            let note = self.notes_manager.full_note(&self.notes_manager.notes[index])?;
+            return Ok(ipc::Reply {
+                id: Some(note.id.clone()),
+                note: Some(note),
+                ..ipc::Reply::ok("A note with the same text is already there")
+            });
+        }
+        // The new note went in at the top
+        self.selected_note = self.selected_note.map(|index| index + 1);
+        if self.mode != AppMode::Widget {
+            self.status = Some("Added a note sent from another command".into());
+        }
+        let note = self.notes_manager.notes[0].clone();
+        Ok(ipc::Reply { id: Some(note.id.clone()), note: Some(note), ..ipc::Reply::ok("Note added") })
+    }
+
+    /// Takes in the notes another command changed, keeping the same note
+    /// selected.
+    async fn apply_requested_changes(&mut self, upserts: Vec<Note>, removed: Vec<String>) -> Result<ipc::Reply> {
+        self.check_writable()?;
+        let count = upserts.len() + removed.len();
+        let selected = self.selected_note.and_then(|index| Some((index, self.notes_manager.notes.get(index)?.id.clone())));
+        self.notes_manager.merge_notes(upserts, &removed).await?;
+        self.notes_manager.sort_notes(self.config.sort_mode);
+        self.marked.retain(|id| !removed.contains(id));
+        if let Some((index, id)) = selected {
+            match self.notes_manager.notes.iter().position(|note| note.id == id) {
+                Some(index) => self.selected_note = Some(index),
+                None => self.select_near(index),
+            }
+        }
+        if count > 0 && self.mode != AppMode::Widget {
+            self.status = Some("Notes changed by another command".into());
+        }
+        Ok(ipc::Reply::ok(format!("{} notes changed", count)))
+    }
+
+    /// Appends `text` to the note `target` names, as the `append` command
+    /// and the shell hook do, starting it with `create` if there is none.
+    async fn append_requested(&mut self, target: &str, text: &str, create: bool) -> Result<ipc::Reply> {
+        self.check_writable()?;
+        let id = match self.notes_manager.resolve_target(target) {
+            Ok(index) => {
+                self.notes_manager.append_to_note(index, text).await?;
+                self.notes_manager.notes[index].id.clone()
+            }
+            Err(NoteLookupError::NotFound(_)) if create => {
+                let mut note = Note::new(format!("{}\n{}", target.trim(), text));
+                capture::add_config_tags(&mut note, Source::Cli, &self.config.capture);
+                let id = note.id.clone();
+                self.notes_manager.add_note(note).await?;
+                // The new note went in at the top
+                self.selected_note = self.selected_note.map(|index| index + 1);
+                id
+            }
+            Err(NoteLookupError::NotFound(_)) => {
+                return Err(UserError::NotFound(format!("No note matches id or title '{}'", target)).into())
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if self.mode != AppMode::Widget {
+            self.status = Some("Appended to a note from another command".into());
+        }
+        let note = self.notes_manager.get_note_by_id(&id).cloned();
+        Ok(ipc::Reply { note, ..ipc::Reply::ok("Appended") })
+    }
+
+    /// Fails for a notes directory that cannot be written, as changes sent
+    /// from elsewhere would be lost when the app quits.
+    fn check_writable(&self) -> Result<()> {
+        if self.notes_manager.is_read_only() {
+            return Err(UserError::Invalid(format!(
+                "The notes directory {} cannot be written",
+                self.config.notes_dir.display()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Fires the reminders that came due as desktop notifications. Read
+    /// from disk, since commands may have set some meanwhile. Returns a line
+    /// per reminder, for the log or the footer.
+    async fn fire_reminders(&self) -> Result<Vec<String>> {
+        // Fired reminders could not be recorded, so they would fire again
+        if self.notes_manager.is_read_only() {
+            return Ok(Vec::new());
+        }
+        let notes = NotesManager::with_previews(&self.config.notes_dir).await?.notes;
+        let now = Utc::now();
+        let mut fired = Vec::new();
+        for note in reminders::take_due(&self.config, &notes, now)? {
+            let title = if reminders::is_missed(note, now) { "Missed reminder" } else { "Reminder" };
+            let text = note.preview(80).replace('\n', " ");
+            let mut line = format!("{}: {}", title, text);
+            if let Err(e) = notify::send(title, &text) {
+                line.push_str(&format!(" ({:#})", e));
+            }
+            fired.push(line);
+        }
+        Ok(fired)
+    }
+
+    async fn log_reminders(&self) {
+        match self.fire_reminders().await {
+            Ok(fired) => fired.iter().for_each(|line| tracing::info!("{}", line)),
+            Err(e) => tracing::error!("Could not check reminders: {:#}", e),
+        }
+    }
+
+    /// Fires due reminders every few seconds while the interface is open,
+    /// pointing to the inbox in the footer.
+    async fn check_reminders(&mut self) {
+        const EVERY: Duration = Duration::from_secs(15);
+        if self.reminders_checked.is_some_and(|checked| checked.elapsed() < EVERY) {
+            return;
+        }
+        self.reminders_checked = Some(Instant::now());
+        match self.fire_reminders().await {
+            Ok(fired) if fired.len() == 1 => self.status = Some(format!("{} (i: reminders)", fired[0])),
+            Ok(fired) if fired.len() > 1 => self.status = Some(format!("{} reminders due: press i to see them", fired.len())),
+            Ok(_) => {}
+            Err(e) => self.status = Some(format!("Could not check reminders: {:#}", e)),
+        }
+    }
+
+    /// Moves a timer that ran out on: a finished pomodoro is logged and its
+    /// break starts, a finished break stops the timer. Shows a desktop
+    /// notification and returns its message.
+    async fn advance_pomodoro(&mut self) -> Result<String> {
+        let Some(timer) = self.pomodoro.take() else {
+            return Ok(String::new());
+        };
+        let (title, mut message) = match timer.phase {
+            Phase::Work => {
+                self.pomodoros_done += 1;
+                let rest = Timer::rest(timer.note_id.clone(), self.pomodoros_done, &self.config.pomodoro);
+                let message = format!("Take a {}-minute {}", rest.minutes(&self.config.pomodoro), rest.phase.label());
+                self.pomodoro = Some(rest);
+                self.log_pomodoro(&timer).await?;
+                ("Pomodoro done", message)
+            }
+            Phase::ShortBreak | Phase::LongBreak => ("Break over", "Press p to start the next pomodoro".to_string()),
+        };
+        if let Err(e) = notify::send(title, &message) {
+            message.push_str(&format!(" ({:#})", e));
+        }
+        Ok(format!("{}. {}", title, message))
+    }
+
+    /// Appends the finished pomodoro to the note worked on, or to the log
+    /// note set in the config.
+    async fn log_pomodoro(&mut self, timer: &Timer) -> Result<()> {
+        let worked_on = self.notes_manager.notes.iter().position(|note| note.id == timer.note_id);
+        let Some(log_title) = self.config.pomodoro.log_note.clone() else {
+            // Nowhere to log it if the note was deleted meanwhile
+            if let Some(index) = worked_on {
+                let line = pomodoro::log_line(timer, None, &self.config);
+                self.notes_manager.append_to_note(index, &line).await?;
+            }
+            return Ok(());
+        };
+
+        let title = worked_on.and_then(|index| self.notes_manager.notes[index].content.lines().next()).map(str::trim);
+        let line = pomodoro::log_line(timer, title, &self.config);
+        let log = self
+            .notes_manager
+            .notes
+            .iter()
+            .position(|note| note.content.lines().next().map(str::trim) == Some(log_title.trim()));
+        match log {
+            Some(index) => self.notes_manager.append_to_note(index, &line).await?,
+            None => {
+                self.notes_manager.add_note(Note::new(format!("{}\n{}", log_title.trim(), line))).await?;
+                // The log note went in at the top
+                self.selected_note = self.selected_note.map(|index| index + 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the reminders inbox.
+    pub fn open_reminders(&mut self) {
+        self.reminder_pick = 0;
+        self.mode = AppMode::Reminders;
+    }
+
+    async fn reload_notes(&mut self) -> Result<()> {
+        // Nothing else can have written them, and reading would drop the
+        // changes held in memory
+        if self.notes_manager.is_read_only() {
+            return Ok(());
+        }
+        tracing::debug!("Reloading the notes");
+        // Read back only once pending saves are in the file
+        self.notes_manager.flush().await?;
+        let background = self.notes_manager.saves_in_background();
+        let indexed = self.notes_manager.uses_search_index();
+        self.notes_manager = NotesManager::with_previews(&self.config.notes_dir).await?;
+        self.notes_manager.sort_notes(self.config.sort_mode);
+        self.notes_manager.set_webhooks(self.config.webhooks.clone());
+        self.word_log = WordLog::load(&self.config.notes_dir).unwrap_or_default();
+        if background {
+            self.notes_manager.save_in_background();
+        }
+        if indexed {
+            self.notes_manager.use_search_index();
+        }
+        Ok(())
+    }
+
+    /// Acts on what was picked from the tray icon. The widget and the
+    /// interface open in terminal windows of their own.
+    fn answer_tray(&mut self, action: TrayAction) {
+        tracing::info!(?action, "Picked from the tray icon");
+        let Some(tray) = self.tray.as_mut() else { return };
+        let done = match action {
+            TrayAction::ToggleWidget => tray.toggle_widget(&self.config),
+            TrayAction::QuickNote => tray.open(&self.config, &["quick"]),
+            TrayAction::OpenNotes => tray.open(&self.config, &[]),
+            TrayAction::ToggleAutostart => autostart::is_autostart_enabled().and_then(|enabled| {
+                autostart::setup_autostart(!enabled)?;
+                autostart::record(&mut self.config, !enabled)
+            }),
+            TrayAction::Quit => {
+                self.should_quit = true;
+                Ok(())
+            }
+        };
+        if let Err(e) = done {
+            tracing::error!("{:#}", e);
+        }
+    }
+
+    /// Draws only when something on screen may have changed: input, a
+    /// request from another instance, a new status or save state, or a
+    /// timer tick. Idle, the loop just waits for input.
     async fn run_app<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let mut redraw = true;
+        let mut drawn_at = Instant::now();
+        let mut drawn_save_state = self.notes_manager.save_state();
+        let mut drawn_logged = logging::logged();
         loop {
-            terminal.draw(|f| self.ui.draw(f, &self.notes_manager, &self.current_input, self.selected_note, &self.mode))?;
+            self.autosave_draft();
+            if std::mem::take(&mut self.needs_clear) {
+                terminal.clear()?;
+                redraw = true;
+            }
+            let status = self.status.clone();
+            if let Some(message) = self.reload_config().await {
+                // Settings such as the theme may have changed too
+                self.status = Some(message);
+                redraw = true;
+            }
+            if let Some(failure) = webhooks::take_failures().pop() {
+                self.status = Some(failure);
+            }
+            if let Some(message) = self.retry_sync().await {
+                self.status = Some(message);
+            }
+            redraw |= self.refresh_sync_health();
+            self.check_reminders().await;
+            if self.pomodoro.as_ref().is_some_and(Timer::is_over) {
+                self.status = Some(match self.advance_pomodoro().await {
+                    Ok(message) => message,
+                    Err(e) => format!("Could not log the pomodoro: {:#}", e),
+                });
+            }
+            redraw |= self.status != status;
+            let save_state = self.notes_manager.save_state();
+            redraw |= save_state != drawn_save_state;
+            if self.recording.as_mut().is_some_and(Recording::has_ended) {
+                self.finish_recording().await?;
+            }
+            let every = if self.pomodoro.is_some() || self.recording.is_some() { REDRAW_TIMER } else { REDRAW_IDLE };
+            redraw |= drawn_at.elapsed() >= every;
+            let logged = logging::logged();
+            redraw |= self.mode == AppMode::Log && logged != drawn_logged;
 
-            if event::poll(Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press {
-                        self.handle_key_event(key.code).await?;
+            if redraw {
+                self.draw(terminal)?;
+                redraw = false;
+                drawn_at = Instant::now();
+                drawn_save_state = save_state;
+                drawn_logged = logged;
+            }
+
+            if event::poll(POLL)? {
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        self.handle_key_event(key).await?;
                     }
+                    Event::Mouse(mouse) => self.handle_mouse_event(mouse).await?,
+                    _ => {}
                 }
+                // Resizes and focus changes too
+                redraw = true;
             }
+            redraw |= self.answer_pending().await;
 
             if self.should_quit {
                 break;
@@ -135,17 +1235,131 @@ impl App {
         Ok(())
     }
 
+    pub fn draw<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let visible_notes = self.visible_notes();
+        let (export_filter, export_scope) = self.export_scope();
+        let export_count = match self.mode {
+            AppMode::Export => self.notes_manager.filter_notes(&export_filter).len(),
+            _ => 0,
+        };
+        let profile = profile::active();
+        let log = match self.mode {
+            AppMode::Log => logging::recent(),
+            _ => Vec::new(),
+        };
+        // Kept within the lines there are, e.g. after Home
+        self.log_scroll = self.log_scroll.min(log.len().saturating_sub(1));
+        let log_file = logging::file();
+        if matches!(self.mode, AppMode::Detail | AppMode::Outline) {
+            self.follow_detail_note();
+        }
+        // Of the large notes only the selected one is held whole; the rest
+        // keep their start, and are read again when needed. Views comparing
+        // all the notes keep them whole while open.
+        if !matches!(self.mode, AppMode::Conflict | AppMode::Copy | AppMode::Backups) {
+            if let Some(selected) = self.selected_note {
+                self.load_note(selected);
+            }
+            self.notes_manager.unload_contents(self.selected_note);
+        }
+        let word_goal = match self.word_goal() {
+            0 => None,
+            goal => {
+                // What is typed counts before it is saved
+                let mut words = self.word_log.words(self.config.zone().day(Utc::now()));
+                if self.mode == AppMode::Insert {
+                    let saved = self.editing.as_ref().map_or("", |(_, saved)| saved.as_str());
+                    words += goals::written(saved, &self.current_input);
+                }
+                Some((words, goal))
+            }
+        };
+        let view = ViewState {
+            notes_manager: &self.notes_manager,
+            config: &self.config,
+            current_input: &self.current_input,
+            selected_note: self.selected_note,
+            mode: &self.mode,
+            onboarding: self.onboarding.as_ref(),
+            visible_notes: &visible_notes,
+            filter: self.filter.as_ref().map(|(text, _)| text.as_str()),
+            marked: &self.marked,
+            status: self.status.as_deref(),
+            export_format: self.export_format,
+            export_scope,
+            export_count,
+            conflict: self.conflict.as_ref(),
+            copy: self.copy.as_ref(),
+            backups: self.backups.as_ref(),
+            autostart: self.autostart.as_ref(),
+            setup: self.setup.as_ref(),
+            profile: profile.as_deref(),
+            profiles: &self.profiles,
+            profile_pick: self.profile_pick,
+            links: &self.links,
+            link_pick: self.link_pick,
+            shares: &self.shares,
+            share_pick: self.share_pick,
+            qr: self.qr.as_ref(),
+            recording: self.recording.as_ref().map(Recording::elapsed),
+            habit_pick: self.habit_pick,
+            stats: self.stats.as_ref(),
+            stats_day: self.stats_day,
+            day_notes: &self.day_notes,
+            day_pick: self.day_pick,
+            graph: self.graph.as_ref(),
+            graph_pick: self.graph_pick,
+            snippets: &self.snippets,
+            snippet_pick: self.snippet_pick,
+            snippet: self.snippet.as_ref(),
+            tag_suggestions: &self.tag_suggestions,
+            suggestion_pick: self.suggestion_pick,
+            plugins: &self.plugins,
+            palette: &self.palette,
+            palette_pick: self.palette_pick,
+            sync_health: self.sync_health.as_ref(),
+            reminder_pick: self.reminder_pick,
+            pomodoro: self.pomodoro.as_ref(),
+            word_goal,
+            read_later: self.read_later,
+            meeting: self.meeting,
+            detail_scroll: self.detail_scroll,
+            outline_open: self.outline_open,
+            outline_pick: self.outline_pick,
+            outline_collapsed: &self.outline_collapsed,
+            log: &log,
+            log_scroll: self.log_scroll,
+            log_file: log_file.as_deref(),
+            draft: self.draft.as_ref(),
+        };
+        terminal.draw(|f| self.ui.draw(f, &view))?;
+        Ok(())
+    }
+
     async fn run_widget_app<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let mut redraw = true;
+        let mut drawn_at = Instant::now();
         loop {
-            terminal.draw(|f| self.ui.draw_widget(f, &self.current_input))?;
+            self.autosave_draft();
+            if redraw || drawn_at.elapsed() >= REDRAW_IDLE {
+                let recent = self.notes_manager.get_recent_notes(WIDGET_RECENT);
+                let read_only = self.notes_manager.is_read_only();
+                terminal.draw(|f| {
+                    self.ui.draw_widget(f, &self.current_input, &recent, self.widget_pick, &self.config, read_only)
+                })?;
+                redraw = false;
+                drawn_at = Instant::now();
+            }
 
-            if event::poll(Duration::from_millis(100))? {
+            if event::poll(POLL)? {
                 if let Event::Key(key) = event::read()? {
                     if key.kind == KeyEventKind::Press {
-                        self.handle_widget_key_event(key.code).await?;
+                        self.handle_widget_key_event(key).await?;
                     }
                 }
+                redraw = true;
             }
+            redraw |= self.answer_pending().await;
 
             if self.should_quit {
                 break;
@@ -154,53 +1368,292 @@ impl App {
         Ok(())
     }
 
-    async fn handle_key_event(&mut self, key: KeyCode) -> Result<()> {
+    pub async fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        tracing::trace!(?key, mode = ?self.mode, "Key");
+        if key.code == KeyCode::F(11) && matches!(self.mode, AppMode::Normal | AppMode::Insert) {
+            self.ui.zen_mode = !self.ui.zen_mode;
+            return Ok(());
+        }
+
+        if self.mode == AppMode::Normal && self.handle_onboarding_key(key.code)? {
+            return Ok(());
+        }
+        self.status = None;
+        // A note saved with Ctrl+S is done with once the editor is left
+        if !matches!(self.mode, AppMode::Insert | AppMode::TagSuggestions) {
+            self.editing = None;
+        }
+
         match self.mode {
             AppMode::Normal => self.handle_normal_mode(key).await?,
             AppMode::Insert => self.handle_insert_mode(key).await?,
             AppMode::Search => self.handle_search_mode(key).await?,
             AppMode::Widget => self.handle_widget_key_event(key).await?,
+            AppMode::Detail => self.handle_detail_mode(key.code).await?,
+            AppMode::Tag => self.handle_tag_mode(key).await?,
+            AppMode::Export => self.handle_export_mode(key).await?,
+            AppMode::Conflict => self.handle_conflict_mode(key.code).await?,
+            AppMode::Copy => self.handle_copy_mode(key.code).await?,
+            AppMode::Backups => self.handle_backups_mode(key.code).await?,
+            AppMode::Reminders => self.handle_reminders_mode(key.code).await?,
+            AppMode::Remind => self.handle_remind_mode(key).await?,
+            AppMode::Autostart => self.handle_autostart_mode(key.code).await?,
+            AppMode::Profiles => self.handle_profiles_mode(key.code).await?,
+            AppMode::Links => self.handle_links_mode(key.code),
+            AppMode::Shares => self.handle_shares_mode(key.code).await,
+            AppMode::Qr => self.handle_qr_mode(key.code),
+            AppMode::Recording => self.handle_recording_mode(key.code).await?,
+            AppMode::Habits => self.handle_habits_mode(key.code).await?,
+            AppMode::Stats => self.handle_stats_mode(key.code),
+            AppMode::StatsDay => self.handle_stats_day_mode(key.code),
+            AppMode::Graph => self.handle_graph_mode(key.code),
+            AppMode::Outline => self.handle_outline_mode(key.code),
+            AppMode::Snippets => self.handle_snippets_mode(key),
+            AppMode::SnippetField => self.handle_snippet_field_mode(key),
+            AppMode::TagSuggestions => self.handle_tag_suggestions_mode(key.code).await?,
+            AppMode::Setup => self.handle_setup_mode(key.code).await?,
+            AppMode::Palette => self.handle_palette_mode(key).await?,
+            AppMode::Log => self.handle_log_mode(key.code),
+            AppMode::Draft => self.handle_draft_mode(key.code)?,
+            AppMode::SaveElsewhere => self.handle_save_elsewhere_mode(key).await?,
         }
         Ok(())
     }
 
-    async fn handle_normal_mode(&mut self, key: KeyCode) -> Result<()> {
-        match key {
+    /// Handles keys owned by the tutorial panel. Returns true if the key was
+    /// consumed.
+    fn handle_onboarding_key(&mut self, key: KeyCode) -> Result<bool> {
+        let Some(onboarding) = &mut self.onboarding else {
+            return Ok(false);
+        };
+
+        match (onboarding.step, key) {
+            (OnboardingStep::Welcome, KeyCode::Enter) => {
+                onboarding.advance(OnboardingEvent::Started);
+            }
+            (OnboardingStep::Done, _) => self.onboarding = None,
+            (_, KeyCode::Esc) => {
+                self.onboarding = None;
+                self.complete_onboarding()?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn onboarding_event(&mut self, event: OnboardingEvent) -> Result<()> {
+        if let Some(onboarding) = &mut self.onboarding {
+            if onboarding.advance(event) {
+                self.complete_onboarding()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn complete_onboarding(&mut self) -> Result<()> {
+        if !self.config.onboarding_completed {
+            self.config.onboarding_completed = true;
+            self.config.save()?;
+        }
+        Ok(())
+    }
+
+    async fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Result<()> {
+        if self.mode != AppMode::Normal {
+            return Ok(());
+        }
+
+        let Some(index) = self
+            .ui
+            .note_at(mouse.column, mouse.row)
+            .and_then(|position| self.visible_notes().get(position).copied())
+        else {
+            return Ok(());
+        };
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.selected_note = Some(index),
+            MouseEventKind::Drag(MouseButton::Left) => self.move_selected_note(index).await?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the selected note to `to`, switching to manual sort so the
+    /// arrangement sticks across sessions.
+    async fn move_selected_note(&mut self, to: usize) -> Result<()> {
+        let Some(selected) = self.selected_note else {
+            return Ok(());
+        };
+        // Positions are ambiguous while only part of the list is visible
+        if selected == to || to >= self.notes_manager.notes.len() || self.filter.is_some() {
+            return Ok(());
+        }
+
+        if self.config.sort_mode != SortMode::Manual {
+            self.config.set_sort_mode(SortMode::Manual)?;
+        }
+        self.notes_manager.move_note(selected, to).await?;
+        self.selected_note = Some(to);
+        Ok(())
+    }
+
+    async fn handle_normal_mode(&mut self, key: KeyEvent) -> Result<()> {
+        // Ctrl and Alt combinations are not the letters typed with them
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(());
+        }
+        let code = match (self.config.keymap, key.code) {
+            (Keymap::Vim, KeyCode::Char('j')) => KeyCode::Down,
+            (Keymap::Vim, KeyCode::Char('k')) => KeyCode::Up,
+            (Keymap::Vim, KeyCode::Char('g')) => KeyCode::Home,
+            (Keymap::Vim, KeyCode::Char('G')) => KeyCode::End,
+            (Keymap::Vim, KeyCode::Char('/')) => KeyCode::Char('s'),
+            (_, code) => code,
+        };
+        match code {
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                if let Some(selected) = self.selected_note {
+                    if selected > 0 {
+                        self.move_selected_note(selected - 1).await?;
+                    }
+                }
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                if let Some(selected) = self.selected_note {
+                    self.move_selected_note(selected + 1).await?;
+                }
+            }
+            KeyCode::Char('q') if self.notes_manager.is_read_only() && self.notes_manager.unsaved() > 0 => {
+                self.mode = AppMode::SaveElsewhere;
+                self.current_input.set("~/turbo-notes-saved");
+            }
             KeyCode::Char('q') => self.should_quit = true,
+            KeyCode::Esc if !self.marked.is_empty() => self.marked.clear(),
+            KeyCode::Esc if self.filter.is_some() => {
+                self.filter = None;
+            }
+            KeyCode::Esc if self.read_later => self.read_later = false,
+            KeyCode::Char('R') => {
+                self.read_later = !self.read_later;
+                self.selected_note = None;
+            }
+            KeyCode::Char('o') => {
+                let mode = self.config.sort_mode.toggle();
+                self.config.set_sort_mode(mode)?;
+                self.notes_manager.sort_notes(mode);
+                self.selected_note = None;
+            }
             KeyCode::Char('n') => {
                 self.mode = AppMode::Insert;
                 self.current_input.clear();
             }
+            KeyCode::Char('N') => self.start_meeting(),
+            KeyCode::Char('z') => {
+                self.ui.zen_mode = true;
+                self.mode = AppMode::Insert;
+                self.current_input.clear();
+            }
             KeyCode::Char('s') => {
                 self.mode = AppMode::Search;
                 self.current_input.clear();
             }
             KeyCode::Char('w') => {
                 self.run_widget().await?;
+                self.needs_clear = true;
             }
-            KeyCode::Up => {
+            KeyCode::Char('e') if self.selected_note.is_some_and(|i| i < self.notes_manager.notes.len()) => {
                 if let Some(selected) = self.selected_note {
-                    if selected > 0 {
-                        self.selected_note = Some(selected - 1);
-                    }
-                } else if !self.notes_manager.notes.is_empty() {
-                    self.selected_note = Some(self.notes_manager.notes.len() - 1);
+                    self.edit_in_external_editor(selected).await?;
                 }
             }
-            KeyCode::Down => {
-                if let Some(selected) = self.selected_note {
-                    if selected < self.notes_manager.notes.len() - 1 {
-                        self.selected_note = Some(selected + 1);
-                    }
-                } else if !self.notes_manager.notes.is_empty() {
-                    self.selected_note = Some(0);
+            KeyCode::Char('t') if self.selected_note.is_some_and(|i| i < self.notes_manager.notes.len()) => {
+                self.mode = AppMode::Tag;
+                self.current_input.clear();
+            }
+            KeyCode::Char('v') if self.selected_note.is_some_and(|i| i < self.notes_manager.notes.len()) => {
+                self.mode = AppMode::Detail;
+            }
+            KeyCode::Char('m') if self.selected_note.is_some_and(|i| i < self.notes_manager.notes.len()) => {
+                self.mode = AppMode::Remind;
+                self.current_input.clear();
+            }
+            KeyCode::Char('p') => {
+                if self.pomodoro.take().is_some() {
+                    self.status = Some("Pomodoro stopped".to_string());
+                } else if let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) {
+                    let timer = Timer::new(note.id.clone(), Phase::Work, &self.config.pomodoro);
+                    self.status = Some(format!("Started a {}-minute pomodoro", timer.minutes(&self.config.pomodoro)));
+                    self.pomodoro = Some(timer);
+                } else {
+                    self.status = Some("Select a note to start a pomodoro on".to_string());
                 }
             }
-            KeyCode::Enter => {
+            KeyCode::Char('L') => {
+                self.mode = AppMode::Log;
+                self.log_scroll = 0;
+            }
+            KeyCode::Char('O') => self.open_links(),
+            KeyCode::Char('S') => self.open_shares().await,
+            KeyCode::Char('y') => self.open_snippets(),
+            KeyCode::Char('H') => self.open_habits(),
+            KeyCode::Char('A') => self.open_stats(),
+            KeyCode::Char('M') => self.open_graph(),
+            KeyCode::Char('d') => {
                 if let Some(selected) = self.selected_note {
-                    if selected < self.notes_manager.notes.len() {
-                        self.current_input = self.notes_manager.notes[selected].content.clone();
-                        self.mode = AppMode::Insert;
+                    self.toggle_habit_today(selected).await?;
+                }
+            }
+            KeyCode::Char('i') => {
+                if reminders::pending(&self.notes_manager.notes).is_empty() {
+                    self.status = Some("No reminders; press m to set one on the selected note".to_string());
+                } else {
+                    self.open_reminders();
+                }
+            }
+            KeyCode::Char(' ') => {
+                if let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) {
+                    if !self.marked.remove(&note.id) {
+                        self.marked.insert(note.id.clone());
+                    }
+                }
+            }
+            KeyCode::Char('c') => {
+                let opened = self.open_conflicts()? || self.open_copies()?;
+                if !opened {
+                    self.status = Some("No sync conflicts or conflicted copies".to_string());
+                }
+            }
+            KeyCode::Char('b') => {
+                let opened = self.open_backups()?;
+                if !opened {
+                    self.status = Some("No backups yet".to_string());
+                }
+            }
+            KeyCode::Char('P') => {
+                self.profiles = profile::list()?;
+                let active = profile::active_name();
+                self.profile_pick = self.profiles.iter().position(|name| *name == active).unwrap_or(0);
+                self.mode = AppMode::Profiles;
+            }
+            KeyCode::Char(':') => self.open_palette(),
+            KeyCode::Char('a') => {
+                self.autostart = Some(AutostartSettings::open()?);
+                self.mode = AppMode::Autostart;
+            }
+            KeyCode::Char('x') => {
+                self.mode = AppMode::Export;
+                self.current_input.set(format!("turbo-notes-export.{}", self.export_format.extension()));
+            }
+            KeyCode::Up => self.select_relative(-1),
+            KeyCode::Down => self.select_relative(1),
+            KeyCode::Home => self.selected_note = self.visible_notes().first().copied(),
+            KeyCode::End => self.selected_note = self.visible_notes().last().copied(),
+            KeyCode::Enter => {
+                if let Some(selected) = self.selected_note {
+                    if selected < self.notes_manager.notes.len() && self.load_note(selected) {
+                        self.current_input.set(self.notes_manager.notes[selected].content.clone());
+                        self.mode = AppMode::Insert;
                     }
                 }
             }
@@ -208,11 +1661,7 @@ impl App {
                 if let Some(selected) = self.selected_note {
                     if selected < self.notes_manager.notes.len() {
                         self.notes_manager.delete_note(selected).await?;
-                        if self.notes_manager.notes.is_empty() {
-                            self.selected_note = None;
-                        } else if selected >= self.notes_manager.notes.len() {
-                            self.selected_note = Some(self.notes_manager.notes.len() - 1);
-                        }
+                        self.select_near(selected);
                     }
                 }
             }
@@ -221,73 +1670,1653 @@ impl App {
         Ok(())
     }
 
-    async fn handle_insert_mode(&mut self, key: KeyCode) -> Result<()> {
+    /// Reads all of the note at `index` into memory, for what needs more
+    /// than its start. Returns false, saying why in the status bar, if it
+    /// cannot be read.
+    fn load_note(&mut self, index: usize) -> bool {
+        match self.notes_manager.load_content(index) {
+            Ok(()) => true,
+            Err(e) => {
+                self.status = Some(format!("{:#}", e));
+                false
+            }
+        }
+    }
+
+    /// Suspends the interface, edits the note in the external editor and
+    /// saves the result.
+    async fn edit_in_external_editor(&mut self, index: usize) -> Result<()> {
+        if !self.load_note(index) {
+            return Ok(());
+        }
+        let note = &self.notes_manager.notes[index];
+        let name = note.id.clone();
+        let original = note.content.clone();
+
+        terminal::suspend()?;
+        let edited = editor::edit_text(&self.config, &name, &original);
+        terminal::resume()?;
+        self.needs_clear = true;
+
+        // The interface stays up whatever the editor did
+        match edited {
+            Ok(edited) if edited == original => {}
+            Ok(edited) if edited.trim().is_empty() => {
+                self.status = Some("Edited note is empty; not saved".to_string());
+            }
+            Ok(edited) => {
+                self.count_written(&original, &edited);
+                let edited = match tidy::wanted(&self.notes_manager.notes[index].tags, &self.config.tidy) {
+                    true => tidy::tidy(&edited, self.config.tidy.wrap),
+                    false => edited,
+                };
+                let edited = self.plugins.transform(&self.notes_manager.notes[index], edited);
+                self.notes_manager.update_note(index, edited).await?;
+            }
+            Err(e) => self.status = Some(format!("{:#}; note not changed", e)),
+        }
+        Ok(())
+    }
+
+    async fn handle_detail_mode(&mut self, key: KeyCode) -> Result<()> {
+        // What was on screen has been read, whatever the key does
+        self.record_reading().await?;
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('v') => self.mode = AppMode::Normal,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_detail(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_detail(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_detail(self.ui.detail_area.height.saturating_sub(1).max(1) as isize),
+            KeyCode::PageUp => self.scroll_detail(-(self.ui.detail_area.height.saturating_sub(1).max(1) as isize)),
+            KeyCode::Home => self.detail_scroll = 0,
+            KeyCode::End => self.scroll_detail(isize::MAX),
+            KeyCode::Enter => {
+                if let Some(selected) = self.selected_note.filter(|&i| self.load_note(i)) {
+                    self.current_input.set(self.notes_manager.notes[selected].content.clone());
+                    self.mode = AppMode::Insert;
+                }
+            }
+            KeyCode::Char('o') => self.open_links(),
+            KeyCode::Char('t') => self.open_outline(),
+            KeyCode::Char('s') => self.share_selected().await,
+            KeyCode::Char('Q') => {
+                if let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) {
+                    let (title, content) = (note.preview(40).replace('\n', " "), note.content.clone());
+                    self.show_qr(title, &content);
+                }
+            }
+            KeyCode::Char('r') => self.start_recording(),
+            KeyCode::Char('d') => {
+                if let Some(selected) = self.selected_note {
+                    self.toggle_habit_today(selected).await?;
+                }
+            }
+            KeyCode::Char('p') => self.play_memo(None),
+            KeyCode::Char(digit @ '1'..='9') => self.play_memo(Some(digit as usize - '1' as usize)),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Starts the note view of a note other than the last one viewed where
+    /// its reading stopped.
+    fn follow_detail_note(&mut self) {
+        let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) else {
+            return;
+        };
+        if self.detail_note.as_ref() != Some(&note.id) {
+            self.detail_scroll = note.reading.map_or(0, |reading| reading.line);
+            self.detail_note = Some(note.id.clone());
+            self.outline_collapsed.clear();
+        }
+    }
+
+    /// Headings of the note in the note view.
+    fn detail_headings(&self) -> Vec<outline::Heading> {
+        self.selected_note
+            .and_then(|i| self.notes_manager.notes.get(i))
+            .map_or_else(Vec::new, |note| outline::headings(&note.content))
+    }
+
+    /// Moves into the outline, opening it, at the section being read.
+    fn open_outline(&mut self) {
+        let headings = self.detail_headings();
+        if headings.is_empty() {
+            self.status = Some("No headings in this note: start a line with # to add one".to_string());
+            return;
+        }
+        let shown = outline::shown(&headings, &self.outline_collapsed);
+        let mut pick = outline::current(&headings, self.detail_scroll).unwrap_or(0);
+        // A section inside a collapsed one is picked by the collapsed one
+        while !shown.contains(&pick) {
+            match outline::parent(&headings, pick) {
+                Some(parent) => pick = parent,
+                None => break,
+            }
+        }
+        self.outline_pick = pick;
+        self.outline_open = true;
+        self.mode = AppMode::Outline;
+    }
+
+    fn handle_outline_mode(&mut self, key: KeyCode) {
+        let headings = self.detail_headings();
+        let shown = outline::shown(&headings, &self.outline_collapsed);
+        if shown.is_empty() {
+            self.outline_open = false;
+            self.mode = AppMode::Detail;
+            return;
+        }
+        let at = shown.iter().position(|&i| i == self.outline_pick).unwrap_or(0);
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('t') => {
+                self.outline_open = false;
+                self.mode = AppMode::Detail;
+                return;
+            }
+            KeyCode::Enter | KeyCode::Tab => {
+                self.mode = AppMode::Detail;
+                return;
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.outline_pick = shown[at.saturating_sub(1)],
+            KeyCode::Down | KeyCode::Char('j') => self.outline_pick = shown[(at + 1).min(shown.len() - 1)],
+            KeyCode::Home => self.outline_pick = shown[0],
+            KeyCode::End => self.outline_pick = shown[shown.len() - 1],
+            KeyCode::Left | KeyCode::Char('h') => {
+                let pick = self.outline_pick;
+                if outline::has_children(&headings, pick) && !self.outline_collapsed.contains(&pick) {
+                    self.outline_collapsed.insert(pick);
+                } else if let Some(parent) = outline::parent(&headings, pick) {
+                    self.outline_pick = parent;
+                }
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.outline_collapsed.remove(&self.outline_pick);
+            }
+            KeyCode::Char(' ') if outline::has_children(&headings, self.outline_pick) => {
+                if !self.outline_collapsed.remove(&self.outline_pick) {
+                    self.outline_collapsed.insert(self.outline_pick);
+                }
+            }
+            _ => return,
+        }
+        // The note follows the picked heading
+        if let Some(heading) = headings.get(self.outline_pick) {
+            self.detail_scroll = 0;
+            self.scroll_detail(heading.line as isize);
+        }
+    }
+
+    /// Scrolls the note view by `lines`, no further than its last page.
+    fn scroll_detail(&mut self, lines: isize) {
+        let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) else {
+            return;
+        };
+        let area = self.ui.detail_area;
+        let last = reading::last_page(&note.content, area.width as usize, area.height as usize);
+        self.detail_scroll = self.detail_scroll.saturating_add_signed(lines).min(last);
+    }
+
+    /// Records how far the note view got, from the area it was last drawn
+    /// in. Notes that fit on a screen are left alone unless they are
+    /// queued to read later.
+    async fn record_reading(&mut self) -> Result<()> {
+        let area = self.ui.detail_area;
+        let Some(index) = self.selected_note.filter(|&i| i < self.notes_manager.notes.len()) else {
+            return Ok(());
+        };
+        let note = &self.notes_manager.notes[index];
+        if area.is_empty() {
+            return Ok(());
+        }
+        let read = reading::shown_to(&note.content, self.detail_scroll, area.width as usize, area.height as usize);
+        if note.reading.is_none() && !reading::is_read_later(note) && read == note.content.len() {
+            return Ok(());
+        }
+        self.notes_manager.set_reading(index, self.detail_scroll, read).await
+    }
+
+    fn start_recording(&mut self) {
+        let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) else {
+            return;
+        };
+        if self.notes_manager.is_read_only() {
+            self.status = Some("The notes directory cannot be written; not recording".to_string());
+            return;
+        }
+        match recorder::start(&self.config, self.notes_manager.get_notes_directory(), &note.id) {
+            Ok(recording) => {
+                self.recording = Some(recording);
+                self.mode = AppMode::Recording;
+            }
+            Err(e) => self.status = Some(format!("{:#}", e)),
+        }
+    }
+
+    async fn handle_recording_mode(&mut self, key: KeyCode) -> Result<()> {
         match key {
+            KeyCode::Enter | KeyCode::Char('r') => self.finish_recording().await?,
             KeyCode::Esc => {
-                if !self.current_input.trim().is_empty() {
-                    let note = Note::new(self.current_input.clone());
-                    self.notes_manager.add_note(note).await?;
+                if let Some(recording) = self.recording.take() {
+                    recording.discard();
                 }
+                self.status = Some("Recording discarded".to_string());
+                self.mode = AppMode::Detail;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Stops the recorder and attaches the memo to the note it was for.
+    async fn finish_recording(&mut self) -> Result<()> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(());
+        };
+        self.mode = AppMode::Detail;
+        let note_id = recording.note_id.clone();
+        // Stopping waits for the recorder to write out the file
+        let memo = match tokio::task::spawn_blocking(move || recording.stop()).await? {
+            Ok(memo) => memo,
+            Err(e) => {
+                self.status = Some(format!("{:#}", e));
+                return Ok(());
+            }
+        };
+        let length = Memo::format_length(memo.seconds);
+        match self.notes_manager.notes.iter().position(|note| note.id == note_id) {
+            Some(index) => {
+                self.notes_manager.add_memo(index, memo).await?;
+                self.status = Some(format!("Attached a {} memo (p plays it)", length));
+            }
+            None => self.status = Some("The note was deleted while recording; memo not kept".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Plays the selected note's memo `number` from 0, or the newest, in the
+    /// system's audio player.
+    fn play_memo(&mut self, number: Option<usize>) {
+        let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) else {
+            return;
+        };
+        let memo = match number {
+            Some(number) => note.memos.get(number),
+            None => note.memos.last(),
+        };
+        let Some(memo) = memo else {
+            self.status = Some(match note.memos.len() {
+                0 => "No voice memos on this note; r records one".to_string(),
+                count => format!("This note has {} voice memos", count),
+            });
+            return;
+        };
+        let path = self.notes_manager.memo_path(note, memo);
+        if !path.exists() {
+            self.status = Some(format!("The memo's file is missing: {}", path.display()));
+            return;
+        }
+        let length = Memo::format_length(memo.seconds);
+        self.status = Some(match open::that_detached(&path) {
+            Ok(()) => format!("Playing the {} memo", length),
+            Err(e) => format!("Could not play {}: {}", path.display(), e),
+        });
+    }
+
+    /// Shows `text` as a QR code, coming back to the current view after.
+    fn show_qr(&mut self, title: String, text: &str) {
+        match qr::encode(text) {
+            Ok(code) => {
+                self.qr = Some((title, code));
+                self.qr_back = self.mode.clone();
+                self.mode = AppMode::Qr;
+            }
+            Err(e) => self.status = Some(format!("{:#}", e)),
+        }
+    }
+
+    fn handle_qr_mode(&mut self, key: KeyCode) {
+        if matches!(key, KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Enter) {
+            self.qr = None;
+            self.mode = self.qr_back.clone();
+        }
+    }
+
+    /// Runs a request to the sync server off the async runtime, as the
+    /// client blocks.
+    async fn on_server<T: Send + 'static>(
+        &self,
+        request: impl FnOnce(&ServerBackend) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || request(&remote::share_server(&config)?)).await?
+    }
+
+    /// Publishes the selected note on the sync server and shows its link.
+    async fn share_selected(&mut self) {
+        let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) else {
+            return;
+        };
+        let length = reminders::parse_duration(remote::SHARE_EXPIRY).expect("valid default expiry");
+        let new = NewShare {
+            title: note.preview(60).replace('\n', " "),
+            html: html::render_shared(note, &self.config),
+            expires: Utc::now() + length,
+        };
+        self.status = Some(match self.on_server(move |server| Ok(server.link(&server.share(&new)?))).await {
+            Ok(link) => format!("Shared for {}: {} (S lists shares)", remote::SHARE_EXPIRY, link),
+            Err(e) => format!("Could not share the note: {:#}", e),
+        });
+    }
+
+    async fn open_shares(&mut self) {
+        let shares = self.on_server(|server| {
+            let shares = server.shares()?;
+            let links = shares.iter().map(|share| server.link(share)).collect::<Vec<_>>();
+            Ok(shares.into_iter().zip(links).collect::<Vec<_>>())
+        });
+        match shares.await {
+            Ok(shares) => {
+                self.shares = shares;
+                self.share_pick = 0;
+                self.mode = AppMode::Shares;
+            }
+            Err(e) => self.status = Some(format!("Could not list shares: {:#}", e)),
+        }
+    }
+
+    async fn handle_shares_mode(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Normal,
+            KeyCode::Up | KeyCode::Char('k') => self.share_pick = self.share_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.share_pick = (self.share_pick + 1).min(self.shares.len().saturating_sub(1));
+            }
+            KeyCode::Enter | KeyCode::Char('o') => {
+                if let Some((_, link)) = self.shares.get(self.share_pick).cloned() {
+                    self.open_url(&link);
+                }
+            }
+            KeyCode::Char('Q') => {
+                if let Some((share, link)) = self.shares.get(self.share_pick).cloned() {
+                    self.show_qr(share.title, &link);
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                let Some((share, _)) = self.shares.get(self.share_pick).cloned() else {
+                    return;
+                };
+                let id = share.id.clone();
+                match self.on_server(move |server| server.revoke(&id)).await {
+                    Ok(_) => {
+                        self.shares.remove(self.share_pick);
+                        self.share_pick = self.share_pick.min(self.shares.len().saturating_sub(1));
+                        self.status = Some(format!("Revoked the share of \"{}\"", share.title));
+                    }
+                    Err(e) => self.status = Some(format!("Could not revoke the share: {:#}", e)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Opens the selected note's link in the browser, or the link picker if
+    /// it has several.
+    fn open_links(&mut self) {
+        let Some(note) = self.selected_note.and_then(|i| self.notes_manager.notes.get(i)) else {
+            return;
+        };
+        let urls = links::urls(&note.content);
+        match urls.as_slice() {
+            [] => self.status = Some("No links in this note".to_string()),
+            [url] => self.open_url(url),
+            _ => {
+                self.links = urls;
+                self.link_pick = 0;
+                self.mode = AppMode::Links;
+            }
+        }
+    }
+
+    fn open_url(&mut self, url: &str) {
+        self.status = Some(match open::that_detached(url) {
+            Ok(()) => format!("Opened {}", url),
+            Err(e) => format!("Could not open {}: {}", url, e),
+        });
+    }
+
+    fn open_stats(&mut self) {
+        let zone = self.config.zone();
+        let notes = &self.notes_manager.notes;
+        let numbers = NotesStats::collect(notes, &self.config.notes_dir, 30, zone);
+        self.stats = Some((numbers, stats::activity(notes, zone)));
+        self.stats_day = zone.day(Utc::now());
+        self.mode = AppMode::Stats;
+    }
+
+    fn handle_stats_mode(&mut self, key: KeyCode) {
+        // The heatmap goes a year back
+        let today = self.config.zone().day(Utc::now());
+        let first = today - TimeDelta::weeks(52);
+        let step = match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('A') => {
+                self.stats = None;
+                self.mode = AppMode::Normal;
+                return;
+            }
+            KeyCode::Enter => {
+                self.day_notes = stats::active_on(&self.notes_manager.notes, self.stats_day, self.config.zone());
+                if self.day_notes.is_empty() {
+                    self.status = Some(format!("No notes written or edited on {}", self.stats_day.format("%a %Y-%m-%d")));
+                } else {
+                    self.day_pick = 0;
+                    self.mode = AppMode::StatsDay;
+                }
+                return;
+            }
+            // A column is a week and a row a day
+            KeyCode::Left | KeyCode::Char('h') => TimeDelta::weeks(-1),
+            KeyCode::Right | KeyCode::Char('l') => TimeDelta::weeks(1),
+            KeyCode::Up | KeyCode::Char('k') => TimeDelta::days(-1),
+            KeyCode::Down | KeyCode::Char('j') => TimeDelta::days(1),
+            KeyCode::Home => first - self.stats_day,
+            KeyCode::End => today - self.stats_day,
+            _ => return,
+        };
+        self.stats_day = (self.stats_day + step).clamp(first, today);
+    }
+
+    fn handle_stats_day_mode(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Stats,
+            KeyCode::Up | KeyCode::Char('k') => self.day_pick = self.day_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.day_pick = (self.day_pick + 1).min(self.day_notes.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                if let Some(&index) = self.day_notes.get(self.day_pick) {
+                    self.stats = None;
+                    self.selected_note = Some(index);
+                    self.mode = AppMode::Detail;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn open_graph(&mut self) {
+        if let Err(e) = self.notes_manager.load_all() {
+            self.status = Some(format!("{:#}", e));
+            return;
+        }
+        let graph = Graph::build(&self.notes_manager.notes);
+        if graph.is_empty() {
+            self.status = Some("No links yet: write [[Note title]] in a note to link to it".to_string());
+            return;
+        }
+        // Start on the selected note when it is in the graph
+        self.graph_pick = self.selected_note.and_then(|i| graph.nodes.iter().position(|&node| node == i)).unwrap_or(0);
+        self.ui.graph_cells = None;
+        self.graph = Some(graph);
+        self.mode = AppMode::Graph;
+    }
+
+    fn handle_graph_mode(&mut self, key: KeyCode) {
+        let direction = match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('M') => {
+                self.graph = None;
+                self.mode = AppMode::Normal;
+                return;
+            }
+            KeyCode::Enter => {
+                if let Some(&index) = self.graph.as_ref().and_then(|graph| graph.nodes.get(self.graph_pick)) {
+                    self.graph = None;
+                    self.selected_note = Some(index);
+                    self.mode = AppMode::Detail;
+                }
+                return;
+            }
+            KeyCode::Tab => {
+                let count = self.graph.as_ref().map_or(0, |graph| graph.nodes.len());
+                self.graph_pick = (self.graph_pick + 1) % count.max(1);
+                return;
+            }
+            KeyCode::Left | KeyCode::Char('h') => (-1, 0),
+            KeyCode::Right | KeyCode::Char('l') => (1, 0),
+            KeyCode::Up | KeyCode::Char('k') => (0, -1),
+            KeyCode::Down | KeyCode::Char('j') => (0, 1),
+            _ => return,
+        };
+        // Where the nodes are is known once the graph was drawn
+        if let Some((_, cells)) = &self.ui.graph_cells {
+            if let Some(next) = graph::step(cells, self.graph_pick, direction) {
+                self.graph_pick = next;
+            }
+        }
+    }
+
+    /// Indexes of the notes tagged as habits, as the habits view lists them.
+    fn habit_notes(&self) -> Vec<usize> {
+        (0..self.notes_manager.notes.len()).filter(|&i| habits::is_habit(&self.notes_manager.notes[i])).collect()
+    }
+
+    fn open_habits(&mut self) {
+        let count = self.habit_notes().len();
+        if count == 0 {
+            self.status = Some(format!("No habits yet: tag a note #{} to track it", habits::HABIT_TAG));
+            return;
+        }
+        self.habit_pick = self.habit_pick.min(count - 1);
+        self.mode = AppMode::Habits;
+    }
+
+    async fn handle_habits_mode(&mut self, key: KeyCode) -> Result<()> {
+        let habits = self.habit_notes();
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('H') => self.mode = AppMode::Normal,
+            KeyCode::Up | KeyCode::Char('k') => self.habit_pick = self.habit_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.habit_pick = (self.habit_pick + 1).min(habits.len().saturating_sub(1));
+            }
+            KeyCode::Char('d') | KeyCode::Char(' ') | KeyCode::Enter => {
+                if let Some(&index) = habits.get(self.habit_pick) {
+                    self.toggle_habit_today(index).await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Marks the habit note at `index` done today, or not done if it was.
+    async fn toggle_habit_today(&mut self, index: usize) -> Result<()> {
+        let Some(note) = self.notes_manager.notes.get(index) else {
+            return Ok(());
+        };
+        if !habits::is_habit(note) {
+            self.status = Some(format!("Not a habit: tag the note #{} to track it", habits::HABIT_TAG));
+            return Ok(());
+        }
+        let today = self.config.zone().day(Utc::now());
+        let done = !note.habit_days.contains(&today);
+        self.notes_manager.set_habit_day(index, today, done).await?;
+        let streak = habits::streaks(&self.notes_manager.notes[index].habit_days, today).current;
+        self.status = Some(match (done, streak) {
+            (true, 1) => "Done today".to_string(),
+            (true, streak) => format!("Done today: {} days in a row", streak),
+            (false, _) => "No longer done today".to_string(),
+        });
+        Ok(())
+    }
+
+    /// Opens the snippet picker, listing the notes tagged as snippets.
+    fn open_snippets(&mut self) {
+        self.current_input.clear();
+        self.snippets = snippets::search(&self.notes_manager.notes, "");
+        if self.snippets.is_empty() {
+            self.status = Some(format!("No snippets yet: tag a note #{} to make it one", snippets::SNIPPET_TAG));
+            return;
+        }
+        self.snippet_pick = 0;
+        self.mode = AppMode::Snippets;
+    }
+
+    fn handle_snippets_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
                 self.current_input.clear();
                 self.mode = AppMode::Normal;
             }
-            KeyCode::Backspace => {
-                self.current_input.pop();
+            KeyCode::Up => self.snippet_pick = self.snippet_pick.saturating_sub(1),
+            KeyCode::Down => self.snippet_pick = (self.snippet_pick + 1).min(self.snippets.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                let Some(index) = self.snippets.get(self.snippet_pick).copied().filter(|&i| self.load_note(i)) else {
+                    return;
+                };
+                let note = &self.notes_manager.notes[index];
+                self.snippet = Some((note.title().to_string(), Template::parse(snippets::body(note)), Vec::new()));
+                self.next_snippet_field();
             }
-            KeyCode::Char(c) => {
-                self.current_input.push(c);
+            _ => {
+                if self.current_input.handle_key(key) {
+                    self.snippets = snippets::search(&self.notes_manager.notes, self.current_input.as_str());
+                    self.snippet_pick = 0;
+                }
+            }
+        }
+    }
+
+    fn handle_snippet_field_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.snippet = None;
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Enter => {
+                if let Some((_, _, values)) = &mut self.snippet {
+                    values.push(self.current_input.as_str().to_string());
+                }
+                self.next_snippet_field();
+            }
+            _ => {
+                self.current_input.handle_key(key);
+            }
+        }
+    }
+
+    /// Asks for the snippet's next field, or copies the snippet once they
+    /// are all filled in.
+    fn next_snippet_field(&mut self) {
+        self.current_input.clear();
+        let Some((title, template, values)) = &self.snippet else {
+            self.mode = AppMode::Normal;
+            return;
+        };
+        if values.len() < template.fields().len() {
+            self.mode = AppMode::SnippetField;
+            return;
+        }
+        let (pasted, unread) = match template.uses_clipboard().then(clipboard::paste) {
+            Some(Err(e)) => (String::new(), format!(", without the clipboard ({:#})", e)),
+            Some(Ok(text)) => (text, String::new()),
+            None => (String::new(), String::new()),
+        };
+        let text = template.expand(values, &pasted, Local::now());
+        self.status = Some(match clipboard::copy(&text) {
+            Ok(()) => format!("Copied '{}'{}", title, unread),
+            Err(e) => format!("Could not copy '{}': {:#}", title, e),
+        });
+        self.snippet = None;
+        self.mode = AppMode::Normal;
+    }
+
+    fn handle_links_mode(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Detail,
+            KeyCode::Up | KeyCode::Char('k') => self.link_pick = self.link_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.link_pick = (self.link_pick + 1).min(self.links.len().saturating_sub(1));
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                if let Some(url) = self.links.get(digit as usize - '1' as usize).cloned() {
+                    self.open_url(&url);
+                    self.mode = AppMode::Detail;
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(url) = self.links.get(self.link_pick).cloned() {
+                    self.open_url(&url);
+                    self.mode = AppMode::Detail;
+                }
             }
             _ => {}
         }
+    }
+
+    async fn handle_insert_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                if !self.offer_tags() {
+                    self.finish_input().await?;
+                }
+            }
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.save_input().await?;
+                if self.editing.is_some() && self.status.is_none() {
+                    self.status = Some("Saved".to_string());
+                }
+            }
+            // Enter on an entry left empty ends the list, taking it away
+            KeyCode::Enter if self.meeting && meeting::is_empty_entry(&self.current_input, self.current_input.cursor()) => {
+                let before = &self.current_input[..self.current_input.cursor()];
+                let entry = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count();
+                for _ in 0..entry {
+                    self.current_input.backspace();
+                }
+            }
+            KeyCode::Enter => {
+                let prefix = self
+                    .meeting
+                    .then(|| meeting::entry_prefix(&self.current_input, self.current_input.cursor(), Local::now()))
+                    .flatten();
+                self.current_input.insert('\n');
+                self.expand_shorthand();
+                prefix.unwrap_or_default().chars().for_each(|c| self.current_input.insert(c));
+            }
+            _ => {
+                // Typed text, as Input takes it, rather than a Ctrl shortcut
+                let typed = matches!(key.code, KeyCode::Char(_))
+                    && key.modifiers.contains(KeyModifiers::CONTROL) == key.modifiers.contains(KeyModifiers::ALT);
+                self.current_input.handle_key(key);
+                if typed {
+                    self.expand_shorthand();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Expands the `:name:` emoji or shortcut that the key just typed ends.
+    fn expand_shorthand(&mut self) {
+        if !self.config.shorthand.enabled {
+            return;
+        }
+        let before = &self.current_input[..self.current_input.cursor()];
+        if let Some((range, text)) = shorthand::expand(before, &self.config.shorthand.shortcuts) {
+            self.current_input.replace(range, &text);
+        }
+    }
+
+    /// Starts a meeting note from the template, to take notes in as the
+    /// meeting goes.
+    fn start_meeting(&mut self) {
+        let template = Template::parse(&self.config.meeting.template);
+        self.current_input.set(template.expand(&[], "", Local::now()));
+        self.editing = None;
+        self.meeting = true;
+        self.mode = AppMode::Insert;
+    }
+
+    /// Saves the note being typed and goes back to the list.
+    async fn finish_input(&mut self) -> Result<()> {
+        self.save_input().await?;
+        self.editing = None;
+        self.meeting = false;
+        self.current_input.clear();
+        self.mode = AppMode::Normal;
         Ok(())
     }
 
-    async fn handle_search_mode(&mut self, key: KeyCode) -> Result<()> {
+    /// Offers the tags in use that fit the note being saved, unless it has
+    /// them already. Returns whether there were any to offer.
+    fn offer_tags(&mut self) -> bool {
+        let text = self.current_input.to_string();
+        if !self.config.capture.suggest_tags || self.editing.as_ref().is_some_and(|(_, saved)| *saved == text) {
+            return false;
+        }
+        let capture = Capture::parse(&text);
+        let mut taken = capture.tags;
+        taken.extend(self.config.capture.default_tags.iter().chain(&self.config.capture.app_tags).cloned());
+        if self.meeting {
+            taken.push(self.config.meeting.tag.clone());
+        }
+        // Saved before with Ctrl+S, and maybe tagged since
+        if let Some(note) = self.editing.as_ref().and_then(|(id, _)| self.notes_manager.get_note_by_id(id)) {
+            taken.extend(note.tags.iter().cloned());
+        }
+        let suggestions = suggest::tags(&capture.content, &taken, &self.notes_manager.tag_counts());
+        if suggestions.is_empty() {
+            return false;
+        }
+        self.tag_suggestions = suggestions.into_iter().map(|tag| (tag, false)).collect();
+        self.suggestion_pick = 0;
+        self.mode = AppMode::TagSuggestions;
+        true
+    }
+
+    async fn handle_tag_suggestions_mode(&mut self, key: KeyCode) -> Result<()> {
         match key {
+            KeyCode::Up | KeyCode::Char('k') => self.suggestion_pick = self.suggestion_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.suggestion_pick = (self.suggestion_pick + 1).min(self.tag_suggestions.len().saturating_sub(1));
+            }
+            KeyCode::Char(' ') => {
+                if let Some((_, picked)) = self.tag_suggestions.get_mut(self.suggestion_pick) {
+                    *picked = !*picked;
+                }
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                if let Some((_, picked)) = self.tag_suggestions.get_mut(digit as usize - '1' as usize) {
+                    *picked = !*picked;
+                }
+            }
+            // Back to the note, to change it or tag it by hand
+            KeyCode::Backspace => self.mode = AppMode::Insert,
+            KeyCode::Enter => {
+                let tags: Vec<String> =
+                    self.tag_suggestions.drain(..).filter(|(_, picked)| *picked).map(|(tag, _)| tag).collect();
+                self.save_input().await?;
+                let index = self
+                    .editing
+                    .as_ref()
+                    .and_then(|(id, _)| self.notes_manager.notes.iter().position(|note| note.id == *id));
+                if let (Some(index), false) = (index, tags.is_empty()) {
+                    for tag in &tags {
+                        self.notes_manager.add_tag(index, tag.clone()).await?;
+                    }
+                    let list: Vec<String> = tags.iter().map(|tag| format!("#{}", tag)).collect();
+                    self.status = Some(format!("Tagged {}", list.join(" ")));
+                }
+                self.finish_input().await?;
+            }
+            KeyCode::Esc => {
+                self.tag_suggestions.clear();
+                self.finish_input().await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Adds a note just written, unless `capture.skip_duplicates` is set and
+    /// a note has the same text, whose index is returned instead.
+    async fn add_captured_note(&mut self, note: Note) -> Result<Option<usize>> {
+        if self.config.capture.skip_duplicates {
+            return self.notes_manager.add_note_unless_duplicate(note).await;
+        }
+        self.notes_manager.add_note(note).await?;
+        Ok(None)
+    }
+
+    /// Saves the note being typed: adds it the first time, and updates it
+    /// after that if the text changed.
+    async fn save_input(&mut self) -> Result<()> {
+        let text = self.current_input.to_string();
+        let before = self.editing.as_ref().map(|(_, saved)| saved.clone()).unwrap_or_default();
+        let saved = self.editing.as_ref().and_then(|(id, saved)| {
+            let index = self.notes_manager.notes.iter().position(|note| note.id == *id)?;
+            Some((index, saved.clone()))
+        });
+        let id = match saved {
+            Some((_, saved)) if saved == text => return Ok(()),
+            Some((index, _)) => {
+                let capture = Capture::parse(&text);
+                // Emptied by mistake, more likely than meant to be blank
+                if capture.content.is_empty() {
+                    return Ok(());
+                }
+                let note = &mut self.notes_manager.notes[index];
+                capture.apply(note);
+                let id = note.id.clone();
+                let content = match tidy::wanted(&note.tags, &self.config.tidy) {
+                    true => tidy::tidy(&capture.content, self.config.tidy.wrap),
+                    false => capture.content,
+                };
+                let content = self.plugins.transform(note, content);
+                self.notes_manager.update_note(index, content).await?;
+                id
+            }
+            None => {
+                let mut note = Capture::parse(&text).into_note();
+                capture::add_config_tags(&mut note, Source::App, &self.config.capture);
+                if self.meeting && !note.tags.contains(&self.config.meeting.tag) {
+                    note.tags.push(self.config.meeting.tag.clone());
+                }
+                if tidy::wanted(&note.tags, &self.config.tidy) {
+                    note.content = tidy::tidy(&note.content, self.config.tidy.wrap);
+                }
+                note.content = self.plugins.transform(&note, note.content.clone());
+                if note.content.is_empty() {
+                    return Ok(());
+                }
+                match self.add_captured_note(note).await? {
+                    Some(index) => {
+                        self.status = Some("A note with the same text is already there".to_string());
+                        self.notes_manager.notes[index].id.clone()
+                    }
+                    None => {
+                        self.onboarding_event(OnboardingEvent::NoteCreated)?;
+                        self.notes_manager.notes[0].id.clone()
+                    }
+                }
+            }
+        };
+        self.count_written(&before, &text);
+        self.editing = Some((id, text));
+        Ok(())
+    }
+
+    /// Today's daily word goal, 0 for none.
+    fn word_goal(&self) -> usize {
+        self.word_goal.unwrap_or(self.config.word_goal)
+    }
+
+    /// Counts the words a save added toward today's word goal, celebrating
+    /// when that reaches it.
+    fn count_written(&mut self, before: &str, after: &str) {
+        let words = goals::written(before, after);
+        if words == 0 || self.notes_manager.is_read_only() {
+            return;
+        }
+        let goal = self.word_goal();
+        if self.word_log.add(self.config.zone().day(Utc::now()), words, goal) {
+            let party = if self.ui.accessible { "" } else { "🎉 " };
+            self.status = Some(format!("{}Daily goal reached: {} words written today!", party, goal));
+        }
+        if let Err(e) = self.word_log.save(&self.config.notes_dir) {
+            tracing::warn!("Could not save the words written today: {:#}", e);
+        }
+    }
+
+    async fn handle_search_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
             KeyCode::Esc => {
                 self.current_input.clear();
                 self.mode = AppMode::Normal;
             }
             KeyCode::Enter => {
-                // Perform search and switch back to normal mode
+                // Apply the filter and switch back to normal mode
                 self.mode = AppMode::Normal;
+                let query = Query::parse(&self.current_input);
+                if query.is_empty() {
+                    self.filter = None;
+                } else {
+                    self.filter = Some((self.current_input.trim().to_string(), query));
+                    self.onboarding_event(OnboardingEvent::SearchPerformed)?;
+                }
+                self.current_input.clear();
+                self.selected_note = self.visible_notes().first().copied();
             }
-            KeyCode::Backspace => {
-                self.current_input.pop();
+            _ => {
+                self.current_input.handle_key(key);
             }
-            KeyCode::Char(c) => {
-                self.current_input.push(c);
+        }
+        Ok(())
+    }
+
+    async fn handle_tag_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Enter => {
+                let tag = self.current_input.trim().trim_start_matches('#').to_string();
+                if let Some(selected) = self.selected_note {
+                    if !tag.is_empty() {
+                        self.notes_manager.add_tag(selected, tag).await?;
+                        self.onboarding_event(OnboardingEvent::NoteTagged)?;
+                    }
+                }
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            // Tags are single words
+            KeyCode::Char(' ') => {}
+            _ => {
+                self.current_input.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_remind_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Enter => {
+                let Some(selected) = self.selected_note else {
+                    self.mode = AppMode::Normal;
+                    return Ok(());
+                };
+                // Nothing typed clears the reminder
+                let when = match self.current_input.trim() {
+                    "" => None,
+                    text => match reminders::parse_when(text, Utc::now(), &self.config) {
+                        Ok(when) => Some(when),
+                        Err(e) => {
+                            self.status = Some(e.to_string());
+                            return Ok(());
+                        }
+                    },
+                };
+                self.notes_manager.set_reminder(selected, when).await?;
+                self.status = Some(match when {
+                    Some(when) => format!("Reminder set for {}", self.config.format_timestamp(when)),
+                    None => "Reminder cleared".to_string(),
+                });
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            _ => {
+                self.current_input.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_draft_mode(&mut self, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::Enter | KeyCode::Char('r') => {
+                if let Some(draft) = self.draft.take() {
+                    self.current_input.set(draft.content);
+                }
+                self.mode = AppMode::Insert;
+            }
+            KeyCode::Esc | KeyCode::Char('d') => {
+                self.draft = None;
+                draft::discard(&self.config)?;
+                self.status = Some("Draft discarded".to_string());
+                self.mode = AppMode::Normal;
             }
             _ => {}
         }
         Ok(())
     }
 
-    async fn handle_widget_key_event(&mut self, key: KeyCode) -> Result<()> {
+    async fn handle_save_elsewhere_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => self.should_quit = true,
+            KeyCode::Enter if !self.current_input.trim().is_empty() => {
+                let dir = expand_home(self.current_input.trim());
+                // Failures are reported in place, so another directory can be tried
+                match self.notes_manager.save_elsewhere(&dir).await {
+                    Ok(()) => {
+                        self.status = Some(format!(
+                            "Saved {} notes to {}, where changes now go; set notes_dir to it to keep using them",
+                            self.notes_manager.notes.len(),
+                            dir.display()
+                        ));
+                        // For the rest of this run only; the config file is left alone
+                        self.config.notes_dir = dir;
+                        self.current_input.clear();
+                        self.mode = AppMode::Normal;
+                    }
+                    Err(e) => self.status = Some(format!("Could not save there: {:#}", e)),
+                }
+            }
+            _ => {
+                self.current_input.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_log_mode(&mut self, key: KeyCode) {
         match key {
-            KeyCode::Esc => self.should_quit = true,
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Normal,
+            KeyCode::Up | KeyCode::Char('k') => self.log_scroll += 1,
+            KeyCode::Down | KeyCode::Char('j') => self.log_scroll = self.log_scroll.saturating_sub(1),
+            KeyCode::PageUp => self.log_scroll += 10,
+            KeyCode::PageDown => self.log_scroll = self.log_scroll.saturating_sub(10),
+            KeyCode::Home => self.log_scroll = usize::MAX,
+            KeyCode::End => self.log_scroll = 0,
+            _ => {}
+        }
+    }
+
+    async fn handle_reminders_mode(&mut self, key: KeyCode) -> Result<()> {
+        let pending: Vec<String> = reminders::pending(&self.notes_manager.notes)
+            .iter()
+            .map(|note| note.id.clone())
+            .collect();
+        if pending.is_empty() {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        }
+        self.reminder_pick = self.reminder_pick.min(pending.len() - 1);
+        let Some(index) = self.notes_manager.notes.iter().position(|note| note.id == pending[self.reminder_pick]) else {
+            return Ok(());
+        };
+
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Normal,
+            KeyCode::Up | KeyCode::Char('k') => self.reminder_pick = self.reminder_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.reminder_pick = (self.reminder_pick + 1).min(pending.len() - 1),
             KeyCode::Enter => {
-                if !self.current_input.trim().is_empty() {
-                    let note = Note::new(self.current_input.clone());
-                    self.notes_manager.add_note(note).await?;
-                    self.current_input.clear();
-                    self.should_quit = true;
+                self.selected_note = Some(index);
+                self.mode = AppMode::Detail;
+            }
+            KeyCode::Char('d') => {
+                self.notes_manager.set_reminder(index, None).await?;
+                self.status = Some("Reminder done".to_string());
+                if pending.len() == 1 {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some((_, label, minutes)) = reminders::SNOOZES.iter().find(|(key, _, _)| *key == c) {
+                    let when = Utc::now() + chrono::Duration::minutes(*minutes);
+                    self.notes_manager.set_reminder(index, Some(when)).await?;
+                    self.status = Some(format!("Snoozed for {}", label));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_export_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Tab => {
+                // Keep the file extension in step with the format
+                let old = format!(".{}", self.export_format.extension());
+                self.export_format = self.export_format.next();
+                let renamed = self.current_input.strip_suffix(&old).map(|stem| format!("{}.{}", stem, self.export_format.extension()));
+                if let Some(renamed) = renamed {
+                    self.current_input.set(renamed);
                 }
             }
+            KeyCode::Enter if !self.current_input.trim().is_empty() => {
+                let path = expand_home(self.current_input.trim());
+                let (filter, _) = self.export_scope();
+                let notes = self.notes_manager.filter_notes(&filter);
+                let count = notes.len();
+                let result = match NotesManager::export_selection(&notes, self.export_format, &self.config) {
+                    Ok(output) => tokio::fs::write(&path, output).await.map_err(anyhow::Error::from),
+                    Err(e) => Err(e),
+                };
+                // Failures are reported in place instead of closing the app
+                self.status = Some(match result {
+                    Ok(()) => format!("Exported {} notes to {}", count, path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                });
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            _ => {
+                self.current_input.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_conflict_mode(&mut self, key: KeyCode) -> Result<()> {
+        let Some(view) = self.conflict.as_mut() else {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        };
+        let resolution = match key {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.conflict = None;
+                self.mode = AppMode::Normal;
+                return Ok(());
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                view.select(-1);
+                return Ok(());
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                view.select(1);
+                return Ok(());
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                view.pick(Pick::Local);
+                return Ok(());
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                view.pick(Pick::Remote);
+                return Ok(());
+            }
+            KeyCode::Char('b') => {
+                view.pick(Pick::Both);
+                return Ok(());
+            }
+            KeyCode::Enter => Resolution::Merged(Box::new(view.merged())),
+            KeyCode::Char('L') => Resolution::Local,
+            KeyCode::Char('R') => Resolution::Remote,
+            KeyCode::Char('D') => Resolution::Both,
+            _ => return Ok(()),
+        };
+
+        if let ConflictSource::Copy(_) = view.source {
+            if !self.copy_queue.is_empty() {
+                let theirs = self.copy_queue.remove(0);
+                copies::resolve(&mut self.notes_manager, &theirs, resolution).await?;
+            }
+            self.notes_manager.sort_notes(self.config.sort_mode);
+            self.selected_note = None;
+            return self.next_copy_conflict();
+        }
+
+        let id = view.id.clone();
+        let mut state = SyncState::load(&self.config)?;
+        sync::resolve(&self.config, &mut self.notes_manager, &mut state, &id, resolution).await?;
+        self.notes_manager.sort_notes(self.config.sort_mode);
+        self.selected_note = None;
+        if !self.open_conflicts()? {
+            self.mode = AppMode::Normal;
+            self.status = Some("All conflicts resolved; the next sync uploads the result".to_string());
+        }
+        Ok(())
+    }
+
+    async fn handle_copy_mode(&mut self, key: KeyCode) -> Result<()> {
+        let Some(copy) = self.copy.as_ref() else {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        };
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.copy = None;
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Char('m') => {
+                let copy = copy.clone();
+                copy.merge_added(&mut self.notes_manager).await?;
+                let (_, changed) = copy.compare(&self.notes_manager.notes);
+                self.copy_queue = changed.into_iter().cloned().collect();
+                self.next_copy_conflict()?;
+            }
+            KeyCode::Char('i') => {
+                let copy = copy.clone();
+                let imported = copy.import(&mut self.notes_manager).await?;
+                self.finish_copy(format!("Imported {} notes from {}", imported, copy.file_name()))?;
+            }
+            KeyCode::Char('d') => {
+                let message = format!("Deleted {}", copy.file_name());
+                self.finish_copy(message)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_setup_mode(&mut self, key: KeyCode) -> Result<()> {
+        let Some(wizard) = self.setup.as_mut() else {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        };
+        let finished = match key {
+            // Esc on the first question skips setup, keeping the defaults
+            KeyCode::Esc => !wizard.back(),
+            KeyCode::Enter => wizard.answer(),
+            KeyCode::Up => {
+                wizard.select(-1);
+                false
+            }
+            KeyCode::Down => {
+                wizard.select(1);
+                false
+            }
             KeyCode::Backspace => {
-                self.current_input.pop();
+                wizard.backspace();
+                false
             }
             KeyCode::Char(c) => {
-                self.current_input.push(c);
+                wizard.type_char(c);
+                false
+            }
+            _ => false,
+        };
+        if finished {
+            self.finish_setup().await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the config made in setup and starts with it. Back to the
+    /// notes directory question if it cannot be used.
+    async fn finish_setup(&mut self) -> Result<()> {
+        let Some(wizard) = self.setup.as_mut() else {
+            return Ok(());
+        };
+        let config = wizard.config.clone();
+        if let Err(e) = config.validate() {
+            wizard.fail(format!("{:#}", e));
+            return Ok(());
+        }
+        config.save()?;
+        self.setup = None;
+        self.mode = AppMode::Normal;
+        self.ui.accessible = config.accessibility_mode;
+        self.ui.theme = config.theme.clone();
+        self.config = config;
+        self.config_modified = Config::modified();
+        self.reload_notes().await?;
+        self.plugins = Plugins::load(&self.config);
+        self.status = match autostart::apply(&self.config) {
+            Ok(_) => Some(format!("Settings saved to {}", Config::config_file_path()?.display())),
+            Err(e) => Some(format!("Settings saved, but could not set up auto-start: {:#}", e)),
+        };
+        self.onboarding = (!self.config.onboarding_completed && self.notes_manager.notes.is_empty()).then(Onboarding::new);
+        Ok(())
+    }
+
+    async fn handle_profiles_mode(&mut self, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = AppMode::Normal,
+            KeyCode::Up | KeyCode::Char('k') => self.profile_pick = self.profile_pick.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.profile_pick = (self.profile_pick + 1).min(self.profiles.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let Some(name) = self.profiles.get(self.profile_pick).cloned() else {
+                    return Ok(());
+                };
+                self.mode = AppMode::Normal;
+                self.status = Some(match self.switch_profile(&name).await {
+                    Ok(()) => format!("Switched to profile '{}'", name),
+                    Err(e) => format!("Could not switch to profile '{}': {:#}", name, e),
+                });
             }
             _ => {}
         }
         Ok(())
     }
 
-    async fn should_show_widget(&self) -> Result<bool> {
-        // Check for trigger file or other conditions
-        // For now, return false to not auto-show
-        Ok(false)
+    /// Moves to another profile's settings and notes, taking its control
+    /// socket. Stays on the current one if that fails, e.g. because the
+    /// profile is open in another instance.
+    async fn switch_profile(&mut self, name: &str) -> Result<()> {
+        let previous = profile::active();
+        if previous.as_deref().unwrap_or(profile::DEFAULT) == name {
+            return Ok(());
+        }
+        if self.notes_manager.is_read_only() && self.notes_manager.unsaved() > 0 {
+            return Err(UserError::Invalid("Changes to these notes are not saved yet: press q to save them elsewhere first".into()).into());
+        }
+        // The notes of the profile left behind are written first
+        self.notes_manager.flush().await?;
+        profile::select(Some(name))?;
+        let switched = async {
+            let listener = ipc::listen().await?;
+            let config = Config::load()?;
+            let notes_manager = NotesManager::with_previews(&config.notes_dir).await?;
+            Ok::<_, anyhow::Error>((listener, config, notes_manager))
+        }
+        .await;
+        let (listener, config, mut notes_manager) = match switched {
+            Ok(switched) => switched,
+            Err(e) => {
+                profile::select(previous.as_deref())?;
+                return Err(e);
+            }
+        };
+        notes_manager.sort_notes(config.sort_mode);
+        notes_manager.set_webhooks(config.webhooks.clone());
+        if !notes::dir_is_writable(&config.notes_dir) {
+            notes_manager.set_read_only();
+        }
+        if self.notes_manager.saves_in_background() {
+            notes_manager.save_in_background();
+        }
+        if self.notes_manager.uses_search_index() {
+            notes_manager.use_search_index();
+        }
+        self.listener = Some(listener);
+        self.ui.accessible = config.accessibility_mode;
+        self.ui.theme = config.theme.clone();
+        self.config_modified = Config::modified();
+        self.config = config;
+        self.notes_manager = notes_manager;
+        self.plugins = Plugins::load(&self.config);
+        self.word_log = WordLog::load(&self.config.notes_dir).unwrap_or_default();
+        self.selected_note = None;
+        self.marked.clear();
+        self.filter = None;
+        // Its note is in the other profile
+        self.pomodoro = None;
+        self.sync_health = None;
+        self.sync_checked = None;
+        self.reminders_checked = None;
+        self.needs_clear = true;
+        Ok(())
+    }
+
+    /// Opens the command palette, listing the commands plugins add.
+    fn open_palette(&mut self) {
+        self.current_input.clear();
+        self.palette = self.palette_matches("");
+        if self.palette.is_empty() {
+            self.status = Some(format!("No plugin commands; plugins go in {}", self.config.notes_dir.join(plugins::PLUGINS_DIR).display()));
+            return;
+        }
+        self.palette_pick = 0;
+        self.mode = AppMode::Palette;
+    }
+
+    /// Indexes of the plugin commands whose plugin name or title contains
+    /// `query`, ignoring case.
+    fn palette_matches(&self, query: &str) -> Vec<usize> {
+        let query = query.trim().to_lowercase();
+        self.plugins
+            .commands()
+            .iter()
+            .enumerate()
+            .filter(|(_, (plugin, command))| {
+                let name = &self.plugins.loaded[*plugin].manifest.name;
+                format!("{} {}", name, command.title).to_lowercase().contains(&query)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    async fn handle_palette_mode(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Up => self.palette_pick = self.palette_pick.saturating_sub(1),
+            KeyCode::Down => self.palette_pick = (self.palette_pick + 1).min(self.palette.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                let Some(&pick) = self.palette.get(self.palette_pick) else {
+                    return Ok(());
+                };
+                self.current_input.clear();
+                self.mode = AppMode::Normal;
+                self.status = Some(match self.run_plugin_command(pick).await {
+                    Ok(status) => status,
+                    Err(e) => format!("{:#}", e),
+                });
+            }
+            _ => {
+                if self.current_input.handle_key(key) {
+                    self.palette = self.palette_matches(self.current_input.as_str());
+                    self.palette_pick = 0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs plugin command `pick` of `plugins.commands()` on the selected
+    /// note, saving the text it returns. Returns the line for the status bar.
+    async fn run_plugin_command(&mut self, pick: usize) -> Result<String> {
+        let (plugin, command) = self.plugins.commands()[pick];
+        let (id, title) = (command.id.clone(), command.title.clone());
+        let selected = self.selected_note.filter(|&i| i < self.notes_manager.notes.len());
+        if let Some(index) = selected {
+            self.notes_manager.load_content(index)?;
+        }
+        let output = self.plugins.run_command(plugin, &id, selected.map(|i| &self.notes_manager.notes[i]))?;
+        if let (Some(index), Some(content)) = (selected, output.content) {
+            if content != self.notes_manager.notes[index].content {
+                self.check_writable()?;
+                if content.trim().is_empty() {
+                    return Err(UserError::Invalid(format!("{} left the note empty; not saved", title)).into());
+                }
+                self.notes_manager.update_note(index, content).await?;
+            }
+        }
+        Ok(output.status.unwrap_or(title))
+    }
+
+    async fn handle_autostart_mode(&mut self, key: KeyCode) -> Result<()> {
+        let Some(settings) = self.autostart.as_mut() else {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        };
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.autostart = None;
+                self.mode = AppMode::Normal;
+            }
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Char('h') | KeyCode::Char('l') => {
+                settings.toggle_mode();
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let picked = settings.picked;
+                let result = if settings.current == Some(picked) {
+                    autostart::disable().map(|_| "Auto-start turned off".to_string())
+                } else {
+                    autostart::enable(picked).map(|_| format!("Auto-start turned on: {}", picked.label().to_lowercase()))
+                };
+                self.status = Some(match result {
+                    Ok(message) => message,
+                    Err(e) => format!("Could not change auto-start: {:#}", e),
+                });
+                settings.refresh()?;
+                autostart::record(&mut self.config, settings.current.is_some())?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_backups_mode(&mut self, key: KeyCode) -> Result<()> {
+        let Some(browser) = self.backups.as_mut() else {
+            self.mode = AppMode::Normal;
+            return Ok(());
+        };
+        let current = &self.notes_manager.notes;
+        match (browser.note, key) {
+            (_, KeyCode::Up | KeyCode::Char('k')) => browser.select(-1, current),
+            (_, KeyCode::Down | KeyCode::Char('j')) => browser.select(1, current),
+            (None, KeyCode::Esc | KeyCode::Char('q')) => {
+                self.backups = None;
+                self.mode = AppMode::Normal;
+            }
+            (None, KeyCode::Enter) => {
+                let opened = browser.open_notes(current);
+                if !opened {
+                    self.status = Some("This backup has no notes to pick from".to_string());
+                }
+            }
+            (None, KeyCode::Char('R')) => {
+                let backup = browser.backup().clone();
+                let notes = backup.verify()?;
+                let store = BackupStore::new(&self.config);
+                // The safety backup is of the file, so pending saves go in first
+                self.notes_manager.flush().await?;
+                let safety = store.create_safety()?;
+                // Through the notes manager, like `turbo-notes restore`, so the
+                // notes file is replaced whole and large notes go to bodies/
+                let restored: HashSet<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+                let removed: Vec<String> = self
+                    .notes_manager
+                    .notes
+                    .iter()
+                    .filter(|note| !restored.contains(note.id.as_str()))
+                    .map(|note| note.id.clone())
+                    .collect();
+                self.notes_manager.merge_notes(notes, &removed).await?;
+                self.notes_manager.sort_notes(self.config.sort_mode);
+                self.selected_note = None;
+                self.backups = None;
+                self.mode = AppMode::Normal;
+                let mut message = format!("Restored {} notes from backup {}", self.notes_manager.notes.len(), backup.id);
+                if let Some(safety) = safety {
+                    message.push_str(&format!("; previous notes saved as backup {}", safety.id));
+                }
+                self.status = Some(message);
+            }
+            (Some(_), KeyCode::Esc | KeyCode::Char('q')) => browser.close_notes(),
+            (Some(_), KeyCode::Char(' ')) => browser.toggle_pick(),
+            (Some(_), KeyCode::Char('r')) => {
+                let notes = browser.to_restore();
+                if notes.is_empty() {
+                    self.status = Some("Nothing to restore: the notes are the same now".to_string());
+                    return Ok(());
+                }
+                let count = notes.len();
+                let id = browser.backup().id.clone();
+                self.notes_manager.merge_notes(notes, &[]).await?;
+                self.notes_manager.sort_notes(self.config.sort_mode);
+                self.selected_note = None;
+                if let Some(browser) = self.backups.as_mut() {
+                    browser.picked.clear();
+                    browser.load(&self.notes_manager.notes);
+                }
+                self.status = Some(format!("Restored {} notes from backup {}", count, id));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_widget_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        let recent = self.notes_manager.get_recent_notes(WIDGET_RECENT).len();
+        if let Some(pick) = self.widget_pick {
+            match key.code {
+                KeyCode::Esc | KeyCode::Tab => self.widget_pick = None,
+                KeyCode::Up | KeyCode::Char('k') => self.widget_pick = Some(pick.saturating_sub(1)),
+                KeyCode::Down | KeyCode::Char('j') => self.widget_pick = Some((pick + 1).min(recent.saturating_sub(1))),
+                KeyCode::Enter => self.append_from_widget(pick).await?,
+                KeyCode::Char(c @ '1'..='9') => {
+                    let pick = c as usize - '1' as usize;
+                    if pick < recent {
+                        self.append_from_widget(pick).await?;
+                    }
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Esc => self.should_quit = true,
+            KeyCode::Tab if recent > 0 => self.widget_pick = Some(0),
+            KeyCode::Enter if !self.current_input.trim().is_empty() => {
+                let mut note = Capture::parse(&self.current_input).into_note();
+                capture::add_config_tags(&mut note, Source::Widget, &self.config.capture);
+                if !note.content.is_empty() {
+                    self.add_captured_note(note).await?;
+                }
+                self.current_input.clear();
+                self.should_quit = true;
+            }
+            // Saves and stays open for the next note
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let mut note = Capture::parse(&self.current_input).into_note();
+                capture::add_config_tags(&mut note, Source::Widget, &self.config.capture);
+                if !note.content.is_empty() {
+                    self.add_captured_note(note).await?;
+                }
+                self.current_input.clear();
+            }
+            _ => {
+                self.current_input.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the widget's input as a new line at the end of the `pick`th most
+    /// recent note, along with its `#tags` and `@notebook`, then closes the
+    /// widget. Input of only tags just tags the note.
+    async fn append_from_widget(&mut self, pick: usize) -> Result<()> {
+        if self.current_input.trim().is_empty() {
+            self.widget_pick = None;
+            return Ok(());
+        }
+        let Some(id) = self.notes_manager.get_recent_notes(WIDGET_RECENT).get(pick).map(|note| note.id.clone()) else {
+            return Ok(());
+        };
+        if let Some(index) = self.notes_manager.notes.iter().position(|note| note.id == id) {
+            self.notes_manager.load_content(index)?;
+            let capture = Capture::parse(&self.current_input);
+            self.count_written("", &capture.content);
+            let note = &mut self.notes_manager.notes[index];
+            capture.apply(note);
+            let content = match capture.content.as_str() {
+                "" => note.content.clone(),
+                text => format!("{}\n{}", note.content.trim_end(), text),
+            };
+            self.notes_manager.update_note(index, content).await?;
+        }
+        self.current_input.clear();
+        self.widget_pick = None;
+        self.should_quit = true;
+        Ok(())
+    }
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
     }
 }
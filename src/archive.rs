@@ -0,0 +1,91 @@
+use anyhow::Result;
+use chrono::{Datelike, Local, Timelike};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+use crate::config::Config;
+use crate::error::UserError;
+use crate::import::{has_extension, open_zip, Imported, Importer};
+use crate::notes::{ExportFormat, Note, NotesManager};
+use crate::quick;
+
+/// Environment variable read for the archive password before prompting
+pub const PASSWORD_VAR: &str = "TURBO_NOTES_PASSWORD";
+
+/// Name of the importable copy of the notes inside an archive
+const NOTES_FILE: &str = "notes.json";
+
+/// Writes a zip archive encrypted with AES-256, which 7-Zip, WinZip and
+/// `turbo-notes import` can open. It always holds `notes.json` for
+/// importing; other formats are added next to it as `notes.<ext>`. The
+/// archive is built in memory so nothing is written if encryption fails.
+pub fn write_archive(notes: &[&Note], format: ExportFormat, config: &Config, password: &str) -> Result<Vec<u8>> {
+    let now = Local::now();
+    // Without a timestamp entries are dated 1980
+    let modified = zip::DateTime::from_date_and_time(
+        now.year().clamp(1980, 2107) as u16,
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8,
+    )
+    .unwrap_or_default();
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(modified)
+        .with_aes_encryption(AesMode::Aes256, password);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    zip.start_file(NOTES_FILE, options)?;
+    zip.write_all(NotesManager::export_selection(notes, ExportFormat::Json, config)?.as_bytes())?;
+    if format != ExportFormat::Json {
+        zip.start_file(format!("notes.{}", format.extension()), options)?;
+        zip.write_all(NotesManager::export_selection(notes, format, config)?.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Gets the archive password from `TURBO_NOTES_PASSWORD`, or asks for it on
+/// the terminal (twice when `confirm` is set, for new archives).
+pub fn password(confirm: bool) -> Result<String> {
+    quick::read_secret(PASSWORD_VAR, "Password", confirm)
+}
+
+/// Archives written by `export --encrypt`.
+pub struct ArchiveImporter;
+
+impl Importer for ArchiveImporter {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn detect(&self, path: &Path, input: &[u8]) -> bool {
+        has_extension(path, "zip") && open_zip(input).is_ok_and(|zip| zip.index_for_name(NOTES_FILE).is_some())
+    }
+
+    fn read(&self, input: &[u8], _progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        let mut zip = open_zip(input)?;
+        let index = zip
+            .index_for_name(NOTES_FILE)
+            .ok_or_else(|| UserError::Invalid(format!("Not a Turbo Notes archive: {} is missing", NOTES_FILE)))?;
+
+        let mut json = String::new();
+        if zip.by_index_raw(index)?.encrypted() {
+            let password = password(false)?;
+            let mut file = zip.by_index_decrypt(index, password.as_bytes()).map_err(|e| match e {
+                zip::result::ZipError::InvalidPassword => UserError::Invalid("Wrong password".into()).into(),
+                e => anyhow::Error::from(e),
+            })?;
+            file.read_to_string(&mut json)?;
+        } else {
+            zip.by_index(index)?.read_to_string(&mut json)?;
+        }
+        Ok(Imported {
+            notes: crate::import::parse_json(&json)?,
+            ..Default::default()
+        })
+    }
+}
@@ -1,55 +1,96 @@
 use anyhow::Result;
 use auto_launch::AutoLaunch;
+use serde::Serialize;
 use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::profile;
+
+/// systemd unit name and launchd label of the background service
+const SERVICE_NAME: &str = "turbo-notes";
+const LAUNCHD_LABEL: &str = "com.turbo-notes";
+
+/// File in the cache directory recording the `auto_start` value last
+/// applied on this machine
+const APPLIED_FILE: &str = "autostart-applied";
+
+/// Name of the login entry, which on Windows is the Run value's name
+const AUTOSTART_NAME: &str = "turbo-notes";
+
+/// Registry key holding the current user's login entries on Windows
+const RUN_KEY: &str = r"HKCU\SOFTWARE\Microsoft\Windows\CurrentVersion\Run";
+
+/// Drops the `\\?\` prefix `current_exe` can return on Windows, which
+/// Explorer does not start from a Run value. `\\?\UNC\server` becomes
+/// `\\server`.
+pub fn strip_verbatim(path: &str) -> String {
+    match path.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{}", share),
+        None => path.strip_prefix(r"\\?\").unwrap_or(path).to_string(),
+    }
+}
+
+/// The executable as the login entry should start it. Windows reads the Run
+/// value as a command line, so there the path is quoted in case it has spaces.
+pub fn launch_path() -> Result<String> {
+    let exe = strip_verbatim(&env::current_exe()?.to_string_lossy());
+    Ok(if cfg!(windows) { format!("\"{}\"", exe) } else { exe })
+}
+
+/// The command line the Run value should hold for this executable.
+pub fn expected_run_value() -> Result<String> {
+    Ok(format!("{} --startup", launch_path()?))
+}
+
+fn auto_launch() -> Result<AutoLaunch> {
+    let path = launch_path()?;
 
-pub fn setup_autostart(enable: bool) -> Result<()> {
-    let exe_path = env::current_exe()?;
-    
     #[cfg(target_os = "macos")]
     let auto_launch = AutoLaunch::new(
-        "turbo-notes",
-        &exe_path.to_string_lossy(),
+        AUTOSTART_NAME,
+        &path,
         false, // hidden = false (not hidden)
         &["--startup"],
     );
-    
+
     #[cfg(not(target_os = "macos"))]
-    let auto_launch = AutoLaunch::new(
-        "turbo-notes",
-        &exe_path.to_string_lossy(),
-        &["--startup"],
-    );
+    let auto_launch = AutoLaunch::new(AUTOSTART_NAME, &path, &["--startup"]);
 
+    Ok(auto_launch)
+}
+
+pub fn setup_autostart(enable: bool) -> Result<()> {
+    let auto_launch = auto_launch()?;
     if enable {
         auto_launch.enable()?;
-        println!("Auto-start enabled for Turbo Notes");
     } else {
         auto_launch.disable()?;
-        println!("Auto-start disabled for Turbo Notes");
     }
 
     Ok(())
 }
 
 pub fn is_autostart_enabled() -> Result<bool> {
-    let exe_path = env::current_exe()?;
-    
-    #[cfg(target_os = "macos")]
-    let auto_launch = AutoLaunch::new(
-        "turbo-notes",
-        &exe_path.to_string_lossy(),
-        false, // hidden = false (not hidden)
-        &["--startup"],
-    );
-    
-    #[cfg(not(target_os = "macos"))]
-    let auto_launch = AutoLaunch::new(
-        "turbo-notes",
-        &exe_path.to_string_lossy(),
-        &["--startup"],
-    );
+    Ok(auto_launch()?.is_enabled()?)
+}
 
-    Ok(auto_launch.is_enabled()?)
+/// The command line registered in the Run key, if there is one. Windows only.
+pub fn registered_run_value() -> Result<Option<String>> {
+    if !cfg!(windows) {
+        return Ok(None);
+    }
+    // `reg query` fails when the value is missing
+    let Ok(output) = run_manager("reg", &["query", RUN_KEY, "/v", AUTOSTART_NAME]) else {
+        return Ok(None);
+    };
+    // The value's line reads `    turbo-notes    REG_SZ    "C:\...\turbo-notes.exe" --startup`
+    Ok(output.lines().find_map(|line| {
+        let (_, value) = line.split_once("REG_SZ")?;
+        Some(value.trim().to_string())
+    }))
 }
 
 #[cfg(target_os = "linux")]
@@ -185,8 +226,7 @@ pub fn setup_windows_autostart(enable: bool) -> Result<()> {
         }
 
         if enable {
-            let exe_path = env::current_exe()?;
-            let mut exe_with_args = format!("{} --startup", exe_path.to_string_lossy());
+            let mut exe_with_args = expected_run_value()?;
             exe_with_args.push('\0');
             let exe_wide: Vec<u16> = exe_with_args.encode_utf16().collect();
 
@@ -233,3 +273,428 @@ pub fn setup_platform_autostart(enable: bool) -> Result<()> {
         setup_autostart(enable)
     }
 }
+
+/// The background service, as the service manager sees it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub path: PathBuf,
+    pub installed: bool,
+    /// What the service manager reports, e.g. `active` or `failed`
+    pub state: Option<String>,
+}
+
+/// Where the service definition goes: a systemd user unit on Linux, a
+/// launchd agent on macOS.
+pub fn service_path() -> Result<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        let dir = dirs::config_dir().ok_or_else(|| anyhow::anyhow!("Could not find the config directory"))?;
+        Ok(dir.join("systemd/user").join(format!("{}.service", SERVICE_NAME)))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+        Ok(home.join("Library/LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL)))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        Err(crate::error::UserError::Invalid(
+            "Background services need systemd (Linux) or launchd (macOS); use 'turbo-notes autostart' instead".into(),
+        )
+        .into())
+    }
+}
+
+/// systemd user unit running the background instance, restarted if it
+/// crashes. Output goes to the journal.
+fn systemd_unit(exe: &str) -> String {
+    // Quoted for spaces; `%` starts a specifier in unit files
+    let exe = exe.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%");
+    format!(
+        r#"[Unit]
+Description=Turbo Notes background instance
+
+[Service]
+Type=simple
+ExecStart="{}" --startup
+Restart=on-failure
+RestartSec=5
+
+[Install]
+WantedBy=default.target
+"#,
+        exe
+    )
+}
+
+/// launchd agent running the background instance at login, restarted if it
+/// crashes, with its output in the daemon log.
+fn launchd_plist(exe: &str, log: &str) -> String {
+    let escape = |text: &str| text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>--startup</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>ProcessType</key>
+    <string>Background</string>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = LAUNCHD_LABEL,
+        exe = escape(exe),
+        log = escape(log),
+    )
+}
+
+/// Runs a service manager command, failing with its error output.
+fn run_manager(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run {}: {}", program, e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("{} {} failed: {}", program, args.join(" "), message.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Writes the service definition, then enables and starts it. Returns its
+/// path.
+pub fn install_service() -> Result<PathBuf> {
+    let path = service_path()?;
+    let exe = env::current_exe()?;
+    let exe = exe.to_string_lossy();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let started = |result: Result<String>| {
+        result.map_err(|e| anyhow::anyhow!("Wrote {} but could not start it: {:#}", path.display(), e))
+    };
+    #[cfg(target_os = "macos")]
+    {
+        let log = crate::daemon::log_path()?;
+        if let Some(dir) = log.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Replaced agents must be unloaded first; there may be none
+        let _ = run_manager("launchctl", &["unload", &path.to_string_lossy()]);
+        fs::write(&path, launchd_plist(&exe, &log.to_string_lossy()))?;
+        started(run_manager("launchctl", &["load", "-w", &path.to_string_lossy()]))?;
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        fs::write(&path, systemd_unit(&exe))?;
+        started(run_manager("systemctl", &["--user", "daemon-reload"]))?;
+        started(run_manager("systemctl", &["--user", "enable", "--now", &format!("{}.service", SERVICE_NAME)]))?;
+    }
+    Ok(path)
+}
+
+/// Stops and removes the service. Returns whether it was installed.
+pub fn uninstall_service() -> Result<bool> {
+    let path = service_path()?;
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = run_manager("launchctl", &["unload", "-w", &path.to_string_lossy()]);
+        fs::remove_file(&path)?;
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        // Already stopped or never enabled is fine: the unit goes either way
+        let _ = run_manager("systemctl", &["--user", "disable", "--now", &format!("{}.service", SERVICE_NAME)]);
+        fs::remove_file(&path)?;
+        // Without a user manager to tell, it reads the units again on its next start
+        let _ = run_manager("systemctl", &["--user", "daemon-reload"]);
+    }
+    Ok(true)
+}
+
+pub fn service_status() -> Result<ServiceStatus> {
+    let path = service_path()?;
+    let installed = path.exists();
+
+    #[cfg(target_os = "macos")]
+    let state = installed.then(|| match run_manager("launchctl", &["list", LAUNCHD_LABEL]) {
+        Ok(_) => "loaded".to_string(),
+        Err(_) => "not loaded".to_string(),
+    });
+    // is-active exits non-zero for anything but active, still printing the state
+    #[cfg(not(target_os = "macos"))]
+    let state = installed
+        .then(|| {
+            Command::new("systemctl")
+                .args(["--user", "is-active", &format!("{}.service", SERVICE_NAME)])
+                .output()
+                .ok()
+        })
+        .flatten()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|state| !state.is_empty());
+
+    Ok(ServiceStatus { path, installed, state })
+}
+
+/// How Turbo Notes starts on login.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    /// A login item: the desktop's autostart entry, a macOS login item or
+    /// the Windows Run key
+    LoginEntry,
+    /// A systemd user service or launchd agent, restarted if it crashes
+    Service,
+}
+
+impl Mode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mode::LoginEntry => "Login entry",
+            Mode::Service => "Background service",
+        }
+    }
+}
+
+/// What enabling a mode creates: where it goes and what it holds.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub location: String,
+    pub contents: String,
+}
+
+/// The entry `mode` would create, shown before it is.
+pub fn entry(mode: Mode) -> Result<Entry> {
+    let exe = env::current_exe()?;
+    let exe = exe.to_string_lossy();
+    match mode {
+        Mode::Service => {
+            let path = service_path()?;
+            #[cfg(target_os = "macos")]
+            let contents = launchd_plist(&exe, &crate::daemon::log_path()?.to_string_lossy());
+            #[cfg(not(target_os = "macos"))]
+            let contents = systemd_unit(&exe);
+            Ok(Entry { location: path.display().to_string(), contents })
+        }
+        // As written by auto-launch
+        Mode::LoginEntry => {
+            #[cfg(target_os = "macos")]
+            let entry = Entry {
+                location: "Login Items (System Settings > General)".to_string(),
+                contents: format!("{} ({})", SERVICE_NAME, exe),
+            };
+            #[cfg(windows)]
+            let entry = Entry {
+                location: r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run".to_string(),
+                contents: format!("{} = {} --startup", SERVICE_NAME, exe),
+            };
+            #[cfg(not(any(target_os = "macos", windows)))]
+            let entry = {
+                // Always under ~/.config, whatever XDG_CONFIG_HOME says
+                let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+                Entry {
+                    location: home.join(".config/autostart").join(format!("{}.desktop", SERVICE_NAME)).display().to_string(),
+                    contents: format!(
+                        "[Desktop Entry]\nType=Application\nVersion=1.0\nName={name}\nComment={name}startup script\n\
+                         Exec={} --startup\nStartupNotify=false\nTerminal=false",
+                        exe,
+                        name = SERVICE_NAME,
+                    ),
+                }
+            };
+            Ok(entry)
+        }
+    }
+}
+
+/// How Turbo Notes starts on login now, if it does. The service wins if
+/// both are set up.
+pub fn current_mode() -> Result<Option<Mode>> {
+    if service_path().is_ok_and(|path| path.exists()) {
+        return Ok(Some(Mode::Service));
+    }
+    Ok(is_autostart_enabled()?.then_some(Mode::LoginEntry))
+}
+
+/// Starts Turbo Notes on login in `mode`, removing the other kind so only
+/// one instance starts.
+pub fn enable(mode: Mode) -> Result<()> {
+    match mode {
+        Mode::LoginEntry => {
+            setup_autostart(true)?;
+            if service_path().is_ok() {
+                uninstall_service()?;
+            }
+        }
+        Mode::Service => {
+            install_service()?;
+            if is_autostart_enabled()? {
+                setup_autostart(false)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the login entry and the service. Returns whether each was set up.
+pub fn disable() -> Result<(bool, bool)> {
+    let login_entry = is_autostart_enabled()?;
+    if login_entry {
+        setup_autostart(false)?;
+    }
+    let service = match service_path() {
+        Ok(_) => uninstall_service()?,
+        Err(_) => false,
+    };
+    Ok((login_entry, service))
+}
+
+/// State of the auto-start settings screen: how Turbo Notes starts now, the
+/// mode picked and the entry it would create.
+#[derive(Debug, Clone)]
+pub struct AutostartSettings {
+    pub current: Option<Mode>,
+    pub picked: Mode,
+    /// The picked mode's entry, or why it cannot be created here
+    pub entry: Result<Entry, String>,
+    /// What the service manager reports about an installed service
+    pub service_state: Option<String>,
+}
+
+impl AutostartSettings {
+    pub fn open() -> Result<Self> {
+        let current = current_mode()?;
+        let picked = current.unwrap_or(Mode::LoginEntry);
+        let mut settings = Self {
+            current,
+            picked,
+            entry: Err(String::new()),
+            service_state: None,
+        };
+        settings.refresh()?;
+        Ok(settings)
+    }
+
+    /// Reads the state again, e.g. after a change.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.current = current_mode()?;
+        self.service_state = service_status().ok().and_then(|status| status.state);
+        self.entry = entry(self.picked).map_err(|e| format!("{:#}", e));
+        Ok(())
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.picked = match self.picked {
+            Mode::LoginEntry => Mode::Service,
+            Mode::Service => Mode::LoginEntry,
+        };
+        self.entry = entry(self.picked).map_err(|e| format!("{:#}", e));
+    }
+}
+
+fn applied_path() -> Result<PathBuf> {
+    let dir = dirs::cache_dir().ok_or_else(|| anyhow::anyhow!("Could not find a cache directory"))?;
+    Ok(dir.join("turbo-notes").join(APPLIED_FILE))
+}
+
+/// The `auto_start` value last applied here; `None` before the first run.
+fn last_applied() -> Option<bool> {
+    fs::read_to_string(applied_path().ok()?).ok()?.trim().parse().ok()
+}
+
+fn record_applied(enabled: bool) -> Result<()> {
+    let path = applied_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, enabled.to_string())?;
+    Ok(())
+}
+
+/// Records auto-start being turned on or off by hand, in the config and as
+/// applied here, so `reconcile` leaves it be. Auto-start runs the default
+/// profile, so only its config says whether it is on.
+pub fn record(config: &mut Config, enabled: bool) -> Result<()> {
+    if profile::active().is_some() {
+        return Ok(());
+    }
+    if config.auto_start != enabled {
+        if enabled {
+            config.enable_auto_start()?;
+        } else {
+            config.disable_auto_start()?;
+        }
+    }
+    record_applied(enabled)
+}
+
+/// Registers or removes auto-start to match `auto_start` in the config. A
+/// new registration is a login entry. Returns what changed, if anything.
+pub fn apply(config: &Config) -> Result<Option<String>> {
+    if profile::active().is_some() {
+        return Ok(None);
+    }
+    let message = match (config.auto_start, current_mode()?) {
+        (true, None) => {
+            enable(Mode::LoginEntry)?;
+            Some("Added the auto-start login entry, as auto_start is on in the config".to_string())
+        }
+        (false, Some(mode)) => {
+            disable()?;
+            Some(format!(
+                "Removed the auto-start {}, as auto_start is off in the config",
+                mode.label().to_lowercase()
+            ))
+        }
+        _ => None,
+    };
+    record_applied(config.auto_start)?;
+    Ok(message)
+}
+
+/// Brings the registration in line with the config on the first run on this
+/// machine, and after `auto_start` was changed by editing the config file.
+/// On the first run a registration made before the setting was honored is
+/// kept, turning the setting on. Returns what changed, if anything.
+pub fn reconcile(config: &mut Config) -> Result<Option<String>> {
+    if profile::active().is_some() {
+        return Ok(None);
+    }
+    let applied = last_applied();
+    if applied == Some(config.auto_start) {
+        return Ok(None);
+    }
+    if applied.is_none() && !config.auto_start {
+        if let Some(mode) = current_mode()? {
+            record(config, true)?;
+            return Ok(Some(format!(
+                "Kept the auto-start {} and turned auto_start on in the config",
+                mode.label().to_lowercase()
+            )));
+        }
+    }
+    apply(config)
+}
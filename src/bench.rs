@@ -0,0 +1,63 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::notes::Note;
+
+const WORDS: [&str; 48] = [
+    "meeting", "project", "review", "deploy", "release", "budget", "design", "draft", "follow", "up", "with",
+    "the", "team", "about", "server", "client", "notes", "idea", "plan", "fix", "bug", "in", "login", "page",
+    "call", "vendor", "invoice", "weekly", "sync", "roadmap", "migrate", "database", "backup", "check", "logs",
+    "write", "tests", "for", "parser", "update", "docs", "and", "ship", "it", "before", "friday", "coffee", "chat",
+];
+
+const TAGS: [&str; 24] = [
+    "work", "home", "ideas", "todo", "reading", "meeting", "travel", "health", "finance", "project", "later",
+    "urgent", "journal", "recipes", "books", "music", "code", "ops", "family", "errands", "learning", "review",
+    "garden", "car",
+];
+
+const NOTEBOOKS: [&str; 4] = ["Work", "Personal", "Archive", "Research"];
+
+/// Xorshift, so every run makes the same notes.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn words(&mut self, min: usize, max: usize) -> String {
+        let count = min + self.below(max - min + 1);
+        (0..count).map(|_| WORDS[self.below(WORDS.len())]).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// `count` made-up notes, one written every few minutes before `now`, for
+/// trying the app with many notes.
+pub fn synthetic_notes(count: usize, now: DateTime<Utc>) -> Vec<Note> {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    let base = now.timestamp_nanos_opt().unwrap_or(0);
+    (0..count)
+        .map(|i| {
+            let lines = 1 + random.below(5);
+            let body: Vec<String> = (0..lines).map(|_| random.words(6, 16)).collect();
+            let mut note = Note::new(format!("{}\n{}", random.words(2, 6), body.join("\n")));
+            note.id = (base - i as i64).to_string();
+            note.created_at = now - Duration::minutes(3 * i as i64);
+            note.updated_at = note.created_at + Duration::minutes(random.below(600) as i64);
+            for _ in 0..random.below(4) {
+                note.add_tag(TAGS[random.below(TAGS.len())].into());
+            }
+            if random.below(10) == 0 {
+                note.notebook = Some(NOTEBOOKS[random.below(NOTEBOOKS.len())].to_string());
+            }
+            note
+        })
+        .collect()
+}
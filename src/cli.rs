@@ -0,0 +1,3543 @@
+use anyhow::Result;
+use chrono::{Local, NaiveDate, Utc};
+use clap::builder::PossibleValuesParser;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::Shells;
+use serde_json::json;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// `println!` for command output, through [`write_out`].
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        write_out(|out| writeln!(out, $($arg)*))
+    };
+}
+
+/// `print!` for command output, through [`write_out`].
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write_out(|out| write!(out, $($arg)*))
+    };
+}
+
+use crate::api;
+use crate::app::App;
+use crate::archive;
+use crate::atom;
+use crate::autostart;
+use crate::backup::{Backup, BackupStore, Integrity};
+use crate::bench;
+use crate::capture::{self, Capture, Source};
+use crate::config::{Config, SyncBackendKind};
+use crate::clipboard;
+use crate::copies::{self, ConflictCopy};
+use crate::daemon;
+use crate::doctor::{self, CheckStatus};
+use crate::editor;
+use crate::error::UserError;
+use crate::html;
+use crate::ics;
+use crate::images;
+use crate::import;
+use crate::ipc::{self, Request};
+use crate::joplin;
+use crate::logging;
+use crate::natural;
+use crate::metadata::metadata_line;
+use crate::notes::{self, Duplicate, ExportFilter, ExportFormat, Note, NoteLookupError, NotesManager};
+use crate::plugins::{self, Plugins};
+use crate::profile;
+use crate::query::Query;
+use crate::graph::Graph;
+use crate::habits;
+use crate::quick;
+use crate::reading;
+use crate::snippets::{self, Template};
+use crate::reminders;
+use crate::schedule;
+use crate::shell_hook;
+use crate::stats::{self as note_stats, NotesStats};
+use crate::remote::{self, ChangeFeed, NewShare};
+use crate::taskwarrior;
+use crate::terminal::Capabilities;
+use crate::tidy;
+use crate::tmux;
+use crate::sync::{self as note_sync, Resolution, SyncReport, SyncState};
+use crate::vault;
+
+pub fn build_cli() -> Command {
+    Command::new("turbo-notes")
+        .version("0.1.0")
+        .about("A minimalist cross-platform terminal-based notes application")
+        .arg(
+            Arg::new("setup-autostart")
+                .long("setup-autostart")
+                .help("Setup auto-start functionality")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("disable-autostart")
+                .long("disable-autostart")
+                .help("Disable auto-start functionality")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("widget")
+                .short('w')
+                .long("widget")
+                .help("Launch quick note widget")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("startup")
+                .long("startup")
+                .help("Run in the background, as started on login")
+                .hide(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("daemon")
+                .long("daemon")
+                .help("Start in the background, detached from the terminal")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("goal")
+                .long("goal")
+                .value_name("WORDS")
+                .help("Words to write today, for this launch only (see word_goal in the config)")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("bench-populate")
+                .long("bench-populate")
+                .value_name("N")
+                .help("Add N made-up notes, then time loading and searching them")
+                .value_parser(value_parser!(usize))
+                .hide(true),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .help("Use a profile's own settings and notes (or set TURBO_NOTES_PROFILE)")
+                .global(true)
+                .add(ArgValueCandidates::new(profile_names)),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .help("How much to log (or set TURBO_NOTES_LOG); L in the app shows the log")
+                .value_parser(logging::LEVELS)
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Append the log to a file, e.g. to attach to a bug report")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Add a note without launching the interface")
+                .arg(
+                    Arg::new("text")
+                        .help("Note content, or '-' to read it from stdin"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Tag to attach (repeatable)")
+                        .add(ArgValueCandidates::new(tag_names))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("notebook")
+                        .short('b')
+                        .long("notebook")
+                        .help("Notebook to file the note under"),
+                )
+                .arg(
+                    Arg::new("title")
+                        .long("title")
+                        .help("Title, stored as the first line of the note"),
+                )
+                .arg(
+                    Arg::new("raw")
+                        .long("raw")
+                        .help("Keep #tag and @notebook words as text")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("append")
+                .about("Add text to the end of a note")
+                .arg(
+                    Arg::new("note")
+                        .help("Note id, unique id prefix or title")
+                        .required(true)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(
+                    Arg::new("text")
+                        .help("Text to add, or '-' to read it from stdin"),
+                )
+                .arg(
+                    Arg::new("create")
+                        .long("create")
+                        .help("Start a note with this title if no note matches")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("attach")
+                .about("Attach images to a note, reading the text in them")
+                .long_about(
+                    "Attach images to a note. They are copied next to the notes, and in builds \
+                     with the ocr feature the text in them is read, so searches find what \
+                     they show.",
+                )
+                .arg(
+                    Arg::new("id")
+                        .help("Note id or unique id prefix")
+                        .required(true)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(Arg::new("images").help("Image files").required(true).num_args(1..))
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("quick")
+                .about("Capture a note from a one-line prompt, without taking over the screen")
+                .long_about(
+                    "Capture a note from a one-line prompt drawn in place, without the \
+                     full-screen interface. Enter saves, Esc cancels. Handy for terminal \
+                     hotkeys and tmux popups.",
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Tag to attach (repeatable)")
+                        .add(ArgValueCandidates::new(tag_names))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("notebook")
+                        .short('b')
+                        .long("notebook")
+                        .help("Notebook to file the note under"),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("tmux-capture")
+                .about("Capture a note in a tmux popup, noting the pane it was opened over")
+                .long_about(
+                    "Capture a note from a one-line prompt sized for a tmux popup, with the \
+                     title and directory of the pane underneath kept with the note. Enter \
+                     saves and exits at once, Esc cancels. Bind it with e.g.\n\n  \
+                     bind-key N display-popup -E -w 60% -h 3 \"turbo-notes tmux-capture\"",
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Tag to attach (repeatable)")
+                        .add(ArgValueCandidates::new(tag_names))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("notebook")
+                        .short('b')
+                        .long("notebook")
+                        .help("Notebook to file the note under"),
+                ),
+        )
+        .subcommand(
+            Command::new("pick")
+                .about("List notes for rofi, dmenu or fzf, and act on the one picked")
+                .long_about(
+                    "Without an action, prints one line per note for a launcher to show. \
+                     With --print, --edit or --open, reads the line picked, from --choice \
+                     or stdin, and acts on its note. With --capture, a line that is not a \
+                     note's becomes a new note, so text typed at the launcher prompt is \
+                     saved. For example:\n\n  \
+                     turbo-notes pick | fzf | turbo-notes pick --edit\n  \
+                     turbo-notes pick | rofi -dmenu -p note | turbo-notes pick --open --capture",
+                )
+                .arg(
+                    Arg::new("print")
+                        .long("print")
+                        .help("Print the picked note (the default with --choice or --capture)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("edit")
+                        .long("edit")
+                        .help("Open the picked note in the editor")
+                        .conflicts_with("print")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("open")
+                        .long("open")
+                        .help("Show the picked note in the app")
+                        .conflicts_with_all(["print", "edit"])
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("choice")
+                        .long("choice")
+                        .value_name("LINE")
+                        .help("The line picked, instead of reading it from stdin"),
+                )
+                .arg(
+                    Arg::new("capture")
+                        .long("capture")
+                        .help("Save a picked line that is not a note's as a new note")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Tag to attach to a captured note (repeatable)")
+                        .add(ArgValueCandidates::new(tag_names))
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("notebook")
+                        .short('b')
+                        .long("notebook")
+                        .help("Notebook to file a captured note under"),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("snippet")
+                .about("Copy a snippet, a note tagged #snippet, with its fields filled in")
+                .long_about(
+                    "Copies the snippet best matching the query to the clipboard: the note's \
+                     text after its title line, with {{date}}, {{time}}, {{datetime}}, \
+                     {{weekday}} and {{clipboard}} replaced and ${1:name} fields filled in. \
+                     Fields not given with --field are asked for. Without a query, lists the \
+                     snippets.",
+                )
+                .arg(Arg::new("query").help("Words of the snippet's title or tags").num_args(0..))
+                .arg(
+                    Arg::new("field")
+                        .short('f')
+                        .long("field")
+                        .value_name("NAME=VALUE")
+                        .help("Value of a field, by its name or number (repeatable)")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("print")
+                        .long("print")
+                        .help("Print the text instead of copying it")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("Print notes without launching the interface")
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Only list notes with this tag")
+                        .add(ArgValueCandidates::new(tag_names)),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .help("Maximum number of notes to print")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Search notes, printing matching lines grep-style")
+                .long_about(
+                    "Search notes using the same syntax as the interactive search: \
+                     words and \"quoted phrases\" must all match, #tag or tag:name \
+                     require a tag, and -term excludes notes.",
+                )
+                .arg(
+                    Arg::new("query")
+                        .help("Search query")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(format_arg())
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Same as --format json")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Print a single note")
+                .arg(
+                    Arg::new("id")
+                        .help("Note id or unique id prefix")
+                        .required(true)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(
+                    Arg::new("raw")
+                        .long("raw")
+                        .help("Print only the note content")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export notes as JSON, Markdown, HTML or a Joplin archive")
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Export format: json, markdown, html, jex (Joplin's archive format) or one a plugin adds")
+                        .default_value("markdown")
+                        .add(ArgValueCandidates::new(export_formats)),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("File to write (defaults to stdout)"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Only export notes with this tag (repeat to allow several)")
+                        .action(ArgAction::Append)
+                        .add(ArgValueCandidates::new(tag_names)),
+                )
+                .arg(
+                    Arg::new("id")
+                        .long("id")
+                        .help("Only export the note with this id or id prefix (repeatable)")
+                        .action(ArgAction::Append)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("DAY")
+                        .help("Only export notes created on or after this day, e.g. 2024-06-01, yesterday or last mon"),
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .value_name("DAY")
+                        .help("Only export notes created on or before this day, e.g. 2024-06-01, yesterday or last mon"),
+                )
+                .arg(
+                    Arg::new("search")
+                        .short('s')
+                        .long("search")
+                        .help("Only export notes matching a search query"),
+                )
+                .arg(
+                    Arg::new("per-note")
+                        .long("per-note")
+                        .help("Write one Markdown file per note, with frontmatter, into the --output directory")
+                        .requires("output")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("scheduled")
+                        .long("scheduled")
+                        .help("Run the scheduled export from the config now, ignoring other options")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("encrypt")
+                        .long("encrypt")
+                        .help("Write a password-protected zip (AES-256) holding notes.json and the chosen format; the password is read from TURBO_NOTES_PASSWORD or prompted for")
+                        .requires("output")
+                        .conflicts_with("per-note")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("feed")
+                .about("Write an Atom feed of recently updated public notes")
+                .long_about(
+                    "Write an Atom feed of the most recently updated notes tagged with the \
+                     config's feed.tag ('public' by default), for feed readers and static \
+                     sites. 'turbo-notes serve' also serves it at /v1/feed.atom.",
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("File to write (defaults to stdout)"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .help("Tag of the notes to include, instead of feed.tag")
+                        .add(ArgValueCandidates::new(tag_names)),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .help("Maximum number of notes, instead of feed.limit")
+                        .value_parser(value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Import notes from a Turbo Notes, Joplin, Simplenote, Google Keep or Notion export")
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
+                .arg(
+                    Arg::new("file")
+                        .help("File to import, or '-' for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Input format (guessed from the file extension by default)")
+                        .value_parser(PossibleValuesParser::new(import::importers().iter().map(|importer| importer.name()))),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Report what would be imported without saving")
+                        .global(true)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("allow-duplicates")
+                        .long("allow-duplicates")
+                        .help("Import notes even if a note with the same text is already there")
+                        .global(true)
+                        .conflicts_with("skip-duplicates")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    // Skipping is the default now; kept for scripts that pass it
+                    Arg::new("skip-duplicates")
+                        .long("skip-duplicates")
+                        .global(true)
+                        .hide(true)
+                        .action(ArgAction::SetTrue),
+                )
+                .subcommand(
+                    Command::new("dir")
+                        .about("Import each .md and .txt file in a directory as a note")
+                        .arg(
+                            Arg::new("path")
+                                .help("Directory to import")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("recursive")
+                                .short('r')
+                                .long("recursive")
+                                .help("Include subdirectories, using their paths as notebooks")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .short('t')
+                                .long("tag")
+                                .help("Tag to add to every imported note")
+                                .action(ArgAction::Append),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Manage tags")
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a tag to a note")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .help("Tag to add")
+                                .required(true)
+                                .add(ArgValueCandidates::new(tag_names)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rm")
+                        .about("Remove a tag from a note")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .help("Tag to remove")
+                                .required(true)
+                                .add(ArgValueCandidates::new(tag_names)),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List all tags with note counts"))
+                .subcommand(
+                    Command::new("rename")
+                        .about("Rename a tag on every note")
+                        .arg(
+                            Arg::new("old")
+                                .help("Current tag name")
+                                .required(true)
+                                .add(ArgValueCandidates::new(tag_names)),
+                        )
+                        .arg(Arg::new("new").help("New tag name").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("habit")
+                .about("Mark habits done and show their streaks")
+                .long_about(
+                    "Notes tagged #habit are habits. Mark one done for today, or another \
+                     day with --day, and list the habits with the days in a row they \
+                     were done.",
+                )
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(Command::new("list").about("List habits with their streaks"))
+                .subcommand(
+                    Command::new("done")
+                        .about("Mark a habit done")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(Arg::new("day").long("day").help("Day it was done, e.g. yesterday (default: today)")),
+                )
+                .subcommand(
+                    Command::new("undo")
+                        .about("Mark a habit not done")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(Arg::new("day").long("day").help("Day, e.g. yesterday (default: today)")),
+                ),
+        )
+        .subcommand(
+            Command::new("later")
+                .about("List the notes to read later, least left to read first")
+                .long_about(
+                    "List the read-later queue: notes tagged #readlater and notes started \
+                     in the note view but not read to the end, with how much of each was \
+                     read, least left to read first.",
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("graph")
+                .about("Print the wiki links between notes")
+                .long_about(
+                    "Print the notes joined by [[wiki links]] and the links between them. \
+                     A link names a note by its title, or by its id. 'dot' prints a \
+                     Graphviz graph, e.g. for 'turbo-notes graph -f dot | dot -Tsvg > \
+                     notes.svg'.",
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["plain", "json", "dot"])
+                        .default_value("plain"),
+                ),
+        )
+        .subcommand(
+            Command::new("remind")
+                .about("Set, list, snooze and finish reminders")
+                .long_about(
+                    "Set, list, snooze and finish reminders. Times are a length of time \
+                     (10m, 2h, 1d, 1w), a time of day (14:30), 'tomorrow', a date \
+                     (2026-10-20, at 09:00) or a date and time (2026-10-20 14:30). Reminders \
+                     show as desktop notifications while Turbo Notes runs; ones missed while \
+                     it was not running go off when it starts.",
+                )
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(
+                    Command::new("set")
+                        .about("Remind about a note")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(Arg::new("when").help("When, e.g. 10m, 14:30, tomorrow or next fri").required(true)),
+                )
+                .subcommand(Command::new("list").about("List missed and upcoming reminders"))
+                .subcommand(
+                    Command::new("snooze")
+                        .about("Put a reminder off")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(Arg::new("for").help("How long, e.g. 10m, 1h or 1d").default_value("10m")),
+                )
+                .subcommand(
+                    Command::new("done")
+                        .about("Clear a note's reminder")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("ics")
+                        .about("Write reminders as an iCalendar (.ics) file for calendar apps")
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .help("File to write (defaults to stdout)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Edit a note in $EDITOR (or the configured editor)")
+                .arg(
+                    Arg::new("id")
+                        .help("Note id or unique id prefix")
+                        .required(true)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("delete")
+                .about("Delete a note")
+                .arg(
+                    Arg::new("id")
+                        .help("Note id or unique id prefix")
+                        .required(true)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Delete without asking for confirmation")
+                        .action(ArgAction::SetTrue),
+                )
+                // -f is --force here
+                .arg(format_arg().short(None)),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Read and change settings")
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(
+                    Command::new("get")
+                        .about("Print a setting, or the whole config without a key")
+                        .arg(Arg::new("key").help("Setting name, e.g. theme.primary_color")),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Change a setting")
+                        .arg(Arg::new("key").help("Setting name, e.g. backup_interval_hours").required(true))
+                        .arg(Arg::new("value").help("New value ('null' unsets optional settings)").required(true)),
+                )
+                .subcommand(Command::new("path").about("Print the config file location")),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Manage backups of your notes")
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(Command::new("now").about("Back up the current notes"))
+                .subcommand(Command::new("list").about("List backups, newest first, with their total size"))
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check backups against their checksums")
+                        .long_about(
+                            "Check backups against the SHA-256 checksums written when they were \
+                             taken, and that they still parse. Exits with an error if any backup \
+                             is damaged. Backups from before checksums were added are only parsed.",
+                        )
+                        .arg(Arg::new("backup-id").help("Only check this backup (id or unique id prefix)")),
+                )
+                .subcommand(
+                    Command::new("prune")
+                        .about("Delete automatic backups outside the retention policy")
+                        .long_about(
+                            "Delete automatic backups outside backup_retention: all but the newest \
+                             keep_last, and the newest of each of the last keep_daily days, \
+                             keep_weekly weeks and keep_monthly months. This also runs after \
+                             every automatic backup. Backups made with 'backup now' or before a \
+                             restore are never pruned.",
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Show what would be deleted without deleting it")
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restore notes from a backup")
+                .long_about(
+                    "Restore notes from a backup. The backup is verified first, and the \
+                     current notes are saved as a 'pre-restore' backup before being replaced.",
+                )
+                .arg(
+                    Arg::new("backup-id")
+                        .help("Backup id or unique id prefix (see 'backup list')")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Verify the backup and show what would change without restoring")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Sync notes with the configured store")
+                .long_about(
+                    "Sync notes with the store set up under 'sync' in the config. Notes are \
+                     encrypted before upload with a key protected by the sync passphrase \
+                     (read from TURBO_NOTES_SYNC_PASSPHRASE, or asked for). Notes changed on \
+                     both sides are left alone and reported as conflicts; settle them with \
+                     'sync resolve'.",
+                )
+                .args_conflicts_with_subcommands(true)
+                .arg(format_arg().global(true))
+                .arg(
+                    Arg::new("watch")
+                        .short('w')
+                        .long("watch")
+                        .help("Keep running, syncing on local edits and on changes from other devices")
+                        .action(ArgAction::SetTrue),
+                )
+                .subcommand(Command::new("status").about("Show pending changes and conflicts without connecting"))
+                .subcommand(
+                    Command::new("resolve")
+                        .about("Settle sync conflicts, side by side or with --keep")
+                        .long_about(
+                            "Settle sync conflicts. Without arguments, opens a side-by-side view \
+                             of each conflict where changes can be merged hunk by hunk. The \
+                             outcome is uploaded by the next sync.",
+                        )
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .requires("keep")
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(
+                            Arg::new("keep")
+                                .long("keep")
+                                .help("Which side wins; 'both' keeps the local note as a copy")
+                                .value_parser(["local", "remote", "both"])
+                                .requires("id"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("history")
+                        .about("List stored versions of a note (needs bucket versioning)")
+                        .arg(
+                            Arg::new("id")
+                                .help("Note id or unique id prefix")
+                                .required(true)
+                                .add(ArgValueCandidates::new(note_ids)),
+                        )
+                        .arg(
+                            Arg::new("version")
+                                .long("restore")
+                                .value_name("VERSION")
+                                .help("Bring back this version as the current note"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("key")
+                        .about("Set up or change the sync encryption key")
+                        .long_about(
+                            "Set up or change the key notes are encrypted with. Notes are encrypted \
+                             with a random data key, which the store keeps encrypted with the sync \
+                             passphrase. A new passphrase is read from \
+                             TURBO_NOTES_SYNC_NEW_PASSPHRASE, or asked for.",
+                        )
+                        .subcommand_required(true)
+                        .subcommand(Command::new("status").about("Show how the store is encrypted, without the passphrase"))
+                        .subcommand(Command::new("init").about("Choose the passphrase for a new store before the first sync"))
+                        .subcommand(
+                            Command::new("passphrase")
+                                .about("Change the passphrase; other devices need the new one from then on"),
+                        )
+                        .subcommand(
+                            Command::new("rotate")
+                                .about("Re-encrypt every note with a new data key, keeping the passphrase")
+                                .long_about(
+                                    "Re-encrypt every note in the store with a new data key, keeping the \
+                                     passphrase. Also upgrades stores set up by older versions, whose \
+                                     object names were note ids. Other devices download every note once \
+                                     afterwards, and 'sync history' starts over. Run it while no other \
+                                     device is syncing.",
+                                ),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("share")
+                .about("Share a note as a read-only page on the sync server")
+                .long_about(
+                    "Publish a note as a read-only web page on the sync server and print its \
+                     secret link. Anyone with the link can read the note until it expires or \
+                     is revoked. Unlike synced notes, the page is stored on the server \
+                     unencrypted. Needs sync.backend set to server.",
+                )
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
+                .arg(format_arg().global(true))
+                .arg(
+                    Arg::new("id")
+                        .help("Note id or unique id prefix")
+                        .required(true)
+                        .add(ArgValueCandidates::new(note_ids)),
+                )
+                .arg(
+                    Arg::new("expires")
+                        .long("expires")
+                        .value_name("DURATION")
+                        .help("How long the link works, e.g. 1h, 7d or 4w")
+                        .default_value(remote::SHARE_EXPIRY),
+                )
+                .subcommand(Command::new("list").about("List shares that have not expired, with their links"))
+                .subcommand(
+                    Command::new("revoke")
+                        .about("Take a share down so its link stops working")
+                        .arg(Arg::new("share").help("Share id, as listed by 'share list'").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("copies")
+                .about("Review conflicted copies of the notes file made by file sync tools")
+                .long_about(
+                    "Review conflicted copies of the notes file that Syncthing, Dropbox or \
+                     Nextcloud save next to it when two devices change it at once. Without a \
+                     subcommand, lists them with what they would add. Handling a copy deletes \
+                     it. In the app, press c to review them side by side.",
+                )
+                .args_conflicts_with_subcommands(true)
+                .arg(format_arg().global(true))
+                .subcommand(
+                    Command::new("merge")
+                        .about("Add the notes only in the copy and settle notes edited differently")
+                        .arg(copy_arg())
+                        .arg(
+                            Arg::new("keep")
+                                .long("keep")
+                                .help("Which version of notes edited differently wins; 'both' adds the copy's as a new note")
+                                .value_parser(["local", "copy", "both"]),
+                        ),
+                )
+                .subcommand(
+                    Command::new("import")
+                        .about("Add every note that is new or different in the copy as a new note")
+                        .arg(copy_arg()),
+                )
+                .subcommand(Command::new("discard").about("Delete the copy without using it").arg(copy_arg())),
+        )
+        .subcommand(
+            Command::new("bridge")
+                .about("Keep notes in step with other tools")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("taskwarrior")
+                        .about("Sync checklist items and reminders with Taskwarrior")
+                        .long_about(
+                            "Sync with Taskwarrior. Each open checklist item ('- [ ] text') \
+                             becomes a task, as does a note with a reminder and no checklist, \
+                             with the note's tags, its notebook as the project and its reminder \
+                             as the due date. Tasks completed in Taskwarrior check their items \
+                             off (or clear the reminder), and items checked off or removed in \
+                             the notes complete or delete their tasks. The note's text wins \
+                             over edits made in Taskwarrior. Which task belongs to which item \
+                             is kept in taskwarrior.json in the notes directory.",
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Only report what would change")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("command")
+                                .long("command")
+                                .value_name("PATH")
+                                .help("Taskwarrior program to run")
+                                .default_value("task"),
+                        )
+                        .arg(format_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("compact")
+                .visible_alias("purge")
+                .about("Remove empty notes and leftover files, and rewrite the notes file")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Report what would be removed without changing anything")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("reindex")
+                .about("Rebuild the search index from the notes")
+                .long_about(
+                    "Rebuild the search index from the notes. The app keeps the index up to date \
+                     itself; rebuild it if searches there miss notes, or after restoring the notes \
+                     directory by hand.",
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the config, notes file and environment for problems")
+                .long_about(
+                    "Check the config, notes file and environment for problems, printing a \
+                     suggested fix for each. Exits with status 1 if any check fails; \
+                     warnings do not change the exit status.",
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Print statistics about your notes")
+                .arg(
+                    Arg::new("days")
+                        .short('d')
+                        .long("days")
+                        .help("Number of days in the activity chart")
+                        .value_parser(value_parser!(usize))
+                        .default_value("30"),
+                )
+                .arg(format_arg())
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Same as --format json")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve the notes over HTTP as a JSON API")
+                .long_about(
+                    "Serve the notes over HTTP as a JSON API for scripts, browser extensions \
+                     and other devices, until Ctrl+C. Clients send the token from api.token \
+                     in the config as 'Authorization: Bearer <token>'; one is made up and \
+                     saved on the first run. The API is read-only unless api.read_only is \
+                     set to false. The Atom feed of public notes at /v1/feed.atom needs no \
+                     token.",
+                )
+                .arg(
+                    Arg::new("port")
+                        .short('p')
+                        .long("port")
+                        .help("Port to listen on [default: api.port, 7777]")
+                        .value_parser(value_parser!(u16).range(1..)),
+                )
+                .arg(
+                    Arg::new("bind")
+                        .long("bind")
+                        .value_name("ADDRESS")
+                        .help("Address to listen on, 0.0.0.0 for other devices [default: api.bind, 127.0.0.1]"),
+                ),
+        )
+        .subcommand(
+            Command::new("ipc")
+                .about("Send a command to the running instance")
+                .long_about(
+                    "Send a command to the running instance (the interface, the widget or \
+                     'turbo-notes --startup'), over its control socket (a named pipe on \
+                     Windows). Exits with status 2 if no instance is running.",
+                )
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(Command::new("ping").about("Check that Turbo Notes is running"))
+                .subcommand(Command::new("show-widget").about("Open the quick note widget"))
+                .subcommand(
+                    Command::new("add-note")
+                        .about("Add a note through the running instance")
+                        .arg(
+                            Arg::new("text")
+                                .help("Note content, or '-' to read it from stdin"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .short('t')
+                                .long("tag")
+                                .help("Tag to attach (repeatable)")
+                                .add(ArgValueCandidates::new(tag_names))
+                                .action(ArgAction::Append),
+                        )
+                        .arg(
+                            Arg::new("notebook")
+                                .short('b')
+                                .long("notebook")
+                                .help("Notebook to file the note under"),
+                        )
+                        .arg(
+                            Arg::new("title")
+                                .long("title")
+                                .help("Title, stored as the first line of the note"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("autostart")
+                .about("Start Turbo Notes in the background on login")
+                .long_about(
+                    "Start Turbo Notes in the background on login. By default this adds a \
+                     login entry, like --setup-autostart. With --service it installs a \
+                     systemd user service (Linux) or launchd agent (macOS) instead, which \
+                     restarts the background instance if it crashes.",
+                )
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("service")
+                        .long("service")
+                        .help("Install a systemd user service or launchd agent")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(format_arg().global(true))
+                .subcommand(Command::new("status").about("Show how Turbo Notes starts on login and whether it is running"))
+                .subcommand(Command::new("uninstall").about("Remove the login entry and the background service")),
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("List, create and remove profiles")
+                .long_about(
+                    "List, create and remove profiles. Each profile has its own settings \
+                     and notes, e.g. for work and personal notes. Use one with --profile \
+                     <name> or TURBO_NOTES_PROFILE, or switch with P in the app. The default \
+                     profile is the usual config file and notes directory.",
+                )
+                .subcommand_required(true)
+                .arg(format_arg().global(true))
+                .subcommand(Command::new("list").about("List profiles, marking the one in use"))
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a profile with default settings")
+                        .arg(Arg::new("name").help("Profile name: letters, digits, '-' and '_'").required(true))
+                        .arg(
+                            Arg::new("notes-dir")
+                                .long("notes-dir")
+                                .help("Keep its notes here instead of in a directory of its own")
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a profile's settings; its notes are kept")
+                        .arg(
+                            Arg::new("name")
+                                .help("Profile name")
+                                .required(true)
+                                .add(ArgValueCandidates::new(profile_names)),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("shell-hook")
+                .about("Print a shell hook logging commands to a note for each day")
+                .long_about(
+                    "Print a hook that logs commands you run to a note for the day, \
+                     titled after shell_log.title ('Shell log 2024-06-01'), as a lab \
+                     notebook of terminal work. Only commands containing shell_log.marker \
+                     ('# note:') are logged, with what follows the marker as a remark: \
+                     'make flash # note: second board' logs the command and the remark, \
+                     and '# note: board is fried' just the remark. An empty marker logs \
+                     every command. Load it on shell startup, e.g. in ~/.bashrc:\n\n    \
+                     eval \"$(turbo-notes shell-hook bash)\"\n\n\
+                     zsh needs 'setopt interactivecomments' for lines that are only a \
+                     remark. Load the hook again after changing the marker.",
+                )
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("shell")
+                        .help("Shell to print the hook for")
+                        .value_parser(shell_hook::SHELLS),
+                )
+                .subcommand(
+                    Command::new("log")
+                        .about("Log a command to today's note; run by the hook")
+                        .arg(Arg::new("command").help("Command line as typed").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .long_about(
+                    "Print a shell completion script. By default the script calls back \
+                     into turbo-notes while completing, so tag names and note ids are \
+                     completed too. Load it on shell startup, e.g. in ~/.bashrc:\n\n    \
+                     source <(turbo-notes completions bash)",
+                )
+                .arg(
+                    Arg::new("shell")
+                        .help("Shell to generate completions for")
+                        .required(true)
+                        .value_parser(["bash", "zsh", "fish", "powershell", "elvish"]),
+                )
+                .arg(
+                    Arg::new("static")
+                        .long("static")
+                        .help("Print a self-contained script without tag and id completion")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+}
+
+/// `--format plain|json`, shared by every command that reports a result.
+fn format_arg() -> Arg {
+    Arg::new("format")
+        .short('f')
+        .long("format")
+        .help("Output format")
+        .value_parser(["plain", "json"])
+        .default_value("plain")
+}
+
+fn wants_json(args: &ArgMatches) -> bool {
+    let format = args.try_get_one::<String>("format").ok().flatten();
+    let flag = args.try_get_one::<bool>("json").ok().flatten();
+    format.is_some_and(|format| format == "json") || flag.copied().unwrap_or(false)
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<()> {
+    let text = serde_json::to_string_pretty(value)?;
+    outln!("{}", text)?;
+    Ok(())
+}
+
+/// Writes command output through the locked stdout. A reader that went
+/// away, as in `turbo-notes list | head -1`, fails it with
+/// [`io::ErrorKind::BrokenPipe`], which the command passes up so it still
+/// shuts down as usual; [`report_error`] then exits quietly.
+fn write_out(write: impl FnOnce(&mut io::StdoutLock) -> io::Result<()>) -> io::Result<()> {
+    write(&mut io::stdout().lock())
+}
+
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe))
+}
+
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_NOT_FOUND: i32 = 2;
+pub const EXIT_INVALID: i32 = 3;
+
+/// Exit status for a failed command: 2 when something the user named does
+/// not exist, 3 when their input was rejected, 1 for anything else. Output
+/// cut short by its reader, as by `head`, is not a failure.
+pub fn exit_status(error: &anyhow::Error) -> i32 {
+    if is_broken_pipe(error) {
+        return 0;
+    }
+    if let Some(error) = error.downcast_ref::<UserError>() {
+        return match error {
+            UserError::NotFound(_) => EXIT_NOT_FOUND,
+            UserError::Invalid(_) => EXIT_INVALID,
+        };
+    }
+    if let Some(error) = error.downcast_ref::<NoteLookupError>() {
+        return match error {
+            NoteLookupError::NotFound(_) => EXIT_NOT_FOUND,
+            NoteLookupError::Ambiguous { .. } => EXIT_INVALID,
+        };
+    }
+    match error.downcast_ref::<io::Error>() {
+        Some(error) if error.kind() == io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+        _ => EXIT_ERROR,
+    }
+}
+
+/// Prints `error` to stderr, as JSON if the failed command was asked for
+/// JSON output, and returns the exit status to use. A reader that went
+/// away is not reported.
+pub fn report_error(matches: &ArgMatches, error: &anyhow::Error) -> i32 {
+    if is_broken_pipe(error) {
+        return 0;
+    }
+    let status = exit_status(error);
+    let mut args = matches;
+    while let Some((_, sub)) = args.subcommand() {
+        args = sub;
+    }
+
+    if wants_json(args) {
+        eprintln!("{}", json!({ "error": format!("{:#}", error), "status": status }));
+    } else {
+        eprintln!("Error: {:#}", error);
+    }
+    status
+}
+
+/// Environment variable the dynamic completion scripts use to call back in.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Existing notes, read synchronously for shell completion.
+fn stored_notes() -> Vec<Note> {
+    Config::load()
+        .ok()
+        .and_then(|config| fs::read_to_string(config.notes_dir.join("notes.json")).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn note_ids() -> Vec<CompletionCandidate> {
+    stored_notes()
+        .iter()
+        .map(|note| {
+            let preview = note.preview(40).replace('\n', " ");
+            CompletionCandidate::new(&note.id).help(Some(preview.into()))
+        })
+        .collect()
+}
+
+fn copy_arg() -> Arg {
+    Arg::new("file")
+        .help("File name of the conflicted copy")
+        .required(true)
+        .add(ArgValueCandidates::new(copy_names))
+}
+
+fn copy_names() -> Vec<CompletionCandidate> {
+    let Ok(config) = Config::load() else {
+        return Vec::new();
+    };
+    copies::find(&config.notes_dir)
+        .iter()
+        .filter_map(|path| path.file_name()?.to_str().map(CompletionCandidate::new))
+        .collect()
+}
+
+fn tag_names() -> Vec<CompletionCandidate> {
+    let mut tags: Vec<String> = stored_notes().into_iter().flat_map(|note| note.tags).collect();
+    tags.sort();
+    tags.dedup();
+    tags.into_iter().map(CompletionCandidate::new).collect()
+}
+
+fn export_formats() -> Vec<CompletionCandidate> {
+    let plugins = Config::load().map(|config| Plugins::load(&config)).unwrap_or_default();
+    let added = plugins.loaded.iter().flat_map(|plugin| &plugin.manifest.export_formats).map(|format| format.name.as_str());
+    ["json", "markdown", "html", "jex"].into_iter().chain(added).map(CompletionCandidate::new).collect()
+}
+
+fn profile_names() -> Vec<CompletionCandidate> {
+    profile::list().unwrap_or_default().into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Switches to the profile given with `--profile`, which may follow a
+/// subcommand, or else `TURBO_NOTES_PROFILE`. Fails if it was never
+/// created, unless the `profile` command is what runs.
+pub fn select_profile(matches: &ArgMatches) -> Result<()> {
+    let mut given = matches.get_one::<String>("profile").cloned();
+    let mut args = matches;
+    while let Some((_, sub)) = args.subcommand() {
+        if let Some(name) = sub.try_get_one::<String>("profile").ok().flatten() {
+            given = Some(name.clone());
+        }
+        args = sub;
+    }
+    if let Some(name) = given {
+        profile::select(Some(&name))?;
+    }
+    let active = profile::active();
+    if !profile::exists(active.as_deref()) && !matches!(matches.subcommand(), Some(("profile", _))) {
+        let name = active.unwrap_or_default();
+        return Err(UserError::NotFound(format!(
+            "No profile '{}'; create it with 'turbo-notes profile create {}'",
+            name, name
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Runs a subcommand. Returns false if `matches` holds no subcommand, in
+/// which case the caller should start the interactive interface.
+pub async fn run_subcommand(matches: &ArgMatches) -> Result<bool> {
+    match matches.subcommand() {
+        Some(("add", args)) => add(args).await?,
+        Some(("quick", args)) => quick(args).await?,
+        Some(("append", args)) => append(args).await?,
+        Some(("attach", args)) => attach(args).await?,
+        Some(("bridge", args)) => bridge(args).await?,
+        Some(("tmux-capture", args)) => tmux_capture(args).await?,
+        Some(("pick", args)) => pick(args).await?,
+        Some(("snippet", args)) => snippet(args).await?,
+        Some(("habit", args)) => habit(args).await?,
+        Some(("later", args)) => later(args).await?,
+        Some(("graph", args)) => graph(args).await?,
+        Some(("list", args)) => list(args).await?,
+        Some(("search", args)) => search(args).await?,
+        Some(("show", args)) => show(args).await?,
+        Some(("export", args)) => export(args).await?,
+        Some(("feed", args)) => feed(args).await?,
+        Some(("import", args)) => import(args).await?,
+        Some(("tag", args)) => tag(args).await?,
+        Some(("remind", args)) => remind(args).await?,
+        Some(("edit", args)) => edit(args).await?,
+        Some(("delete", args)) => delete(args).await?,
+        Some(("config", args)) => config(args)?,
+        Some(("backup", args)) => backup(args)?,
+        Some(("restore", args)) => restore(args).await?,
+        Some(("sync", args)) => sync(args).await?,
+        Some(("share", args)) => share(args).await?,
+        Some(("copies", args)) => conflict_copies(args).await?,
+        Some(("compact", args)) => compact(args).await?,
+        Some(("reindex", _)) => reindex().await?,
+        Some(("doctor", args)) => doctor(args)?,
+        Some(("stats", args)) => stats(args).await?,
+        Some(("serve", args)) => serve(args).await?,
+        Some(("ipc", args)) => ipc_command(args).await?,
+        Some(("autostart", args)) => autostart(args).await?,
+        Some(("profile", args)) => profile_command(args)?,
+        Some(("shell-hook", args)) => shell_hook_command(args).await?,
+        Some(("completions", args)) => completions(args)?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// The config and notes; changes to the notes go through the running
+/// instance, see `ipc::open_notes`.
+async fn load_notes() -> Result<(Config, NotesManager)> {
+    let config = Config::load()?;
+    let notes_manager = ipc::open_notes(&config).await?;
+    Ok((config, notes_manager))
+}
+
+fn read_stdin() -> Result<String> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    Ok(input.trim_end().to_string())
+}
+
+async fn add(args: &ArgMatches) -> Result<()> {
+    let content = note_content(args)?;
+    save_new_note(content, args).await
+}
+
+/// Text from the `text` argument in `args`, or stdin.
+fn text_arg(args: &ArgMatches) -> Result<String> {
+    match args.get_one::<String>("text").map(String::as_str) {
+        Some("-") => read_stdin(),
+        Some(text) => Ok(text.to_string()),
+        None if !io::stdin().is_terminal() => read_stdin(),
+        None => Err(UserError::Invalid("No note content given; pass text or pipe it in with '-'".into()).into()),
+    }
+}
+
+/// Note text from the `text` and `--title` options in `args`, or stdin.
+fn note_content(args: &ArgMatches) -> Result<String> {
+    let text = text_arg(args)?;
+    let content = match args.get_one::<String>("title") {
+        Some(title) => format!("{}\n{}", title, text),
+        None => text,
+    };
+    if content.trim().is_empty() {
+        return Err(UserError::Invalid("Note content cannot be empty".into()).into());
+    }
+    Ok(content)
+}
+
+async fn append(args: &ArgMatches) -> Result<()> {
+    let target = args.get_one::<String>("note").expect("note is required");
+    let text = text_arg(args)?;
+    if text.trim().is_empty() {
+        return Err(UserError::Invalid("Nothing to append".into()).into());
+    }
+    let note = append_to(target, &text, args.get_flag("create")).await?;
+    if wants_json(args) {
+        return print_json(&note);
+    }
+    outln!("Appended to {}", note.id)?;
+    Ok(())
+}
+
+async fn attach(args: &ArgMatches) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let id = args.get_one::<String>("id").expect("id is required");
+    let index = notes_manager.resolve_id(id)?;
+    let note_id = notes_manager.notes[index].id.clone();
+
+    let mut attached = Vec::new();
+    for path in args.get_many::<String>("images").into_iter().flatten() {
+        if !images::is_image(path) {
+            return Err(UserError::Invalid(format!(
+                "{} is not an image; attach {} files",
+                path,
+                images::IMAGE_EXTENSIONS.join(", ")
+            ))
+            .into());
+        }
+        let data = fs::read(path).map_err(|e| anyhow::Error::new(e).context(format!("Could not read {}", path)))?;
+        let (mut image, stored) = images::store(notes_manager.get_notes_directory(), &note_id, path, &data)?;
+        if images::ocr_enabled(&config) {
+            // The image is kept without its text rather than not at all
+            match images::recognize(&config, &stored) {
+                Ok(text) => image.text = text,
+                Err(e) => eprintln!("Could not read the text in {}: {:#}", path, e),
+            }
+        }
+        attached.push((index, image));
+    }
+    let images: Vec<_> = attached.iter().map(|(_, image)| image.clone()).collect();
+    notes_manager.add_images(attached).await?;
+
+    if wants_json(args) {
+        return print_json(&json!({ "note": note_id, "images": images }));
+    }
+    for image in &images {
+        match image.text.lines().count() {
+            0 => outln!("Attached {}", image.file)?,
+            lines => outln!("Attached {} ({} lines of text)", image.file, lines)?,
+        }
+    }
+    Ok(())
+}
+
+/// Attaches the images that came with imported notes, `ids` being the ids
+/// the notes were read with, in the order they were added, and reads their
+/// text. Returns how many were attached.
+async fn attach_imported_images(
+    config: &Config,
+    notes_manager: &mut NotesManager,
+    ids: &[String],
+    files: Vec<import::ImportedFile>,
+) -> Result<usize> {
+    // Imported notes are added at the end, but may have been given new ids
+    let first = notes_manager.notes.len() - ids.len();
+    let index_of: std::collections::HashMap<&str, usize> =
+        ids.iter().enumerate().map(|(i, id)| (id.as_str(), first + i)).collect();
+    let ocr = images::ocr_enabled(config);
+    let show_progress = ocr && io::stderr().is_terminal();
+    let total = files.len();
+    let mut attached = Vec::new();
+    let mut unread: Option<(usize, anyhow::Error)> = None;
+    for (done, file) in files.into_iter().enumerate() {
+        let Some(&index) = index_of.get(file.note_id.as_str()) else {
+            continue;
+        };
+        let note_id = notes_manager.notes[index].id.clone();
+        let (mut image, stored) = images::store(notes_manager.get_notes_directory(), &note_id, &file.name, &file.data)?;
+        if ocr {
+            if show_progress {
+                eprint!("\rReading the text in images {}/{}...", done + 1, total);
+            }
+            match images::recognize(config, &stored) {
+                Ok(text) => image.text = text,
+                Err(e) => match &mut unread {
+                    Some((count, _)) => *count += 1,
+                    None => unread = Some((1, e)),
+                },
+            }
+        }
+        attached.push((index, image));
+    }
+    if show_progress {
+        eprint!("\r\x1b[2K");
+    }
+    if let Some((count, e)) = unread {
+        eprintln!("Could not read the text in {} images: {:#}", count, e);
+    }
+    let count = attached.len();
+    notes_manager.add_images(attached).await?;
+    Ok(count)
+}
+
+/// Appends `text` to the note with id, unique id prefix or title `target`.
+/// With `create`, a missing note is started with `target` as its title.
+/// A running instance does the appending on the notes it holds, so a note
+/// being edited there is appended to as it is now.
+async fn append_to(target: &str, text: &str, create: bool) -> Result<Note> {
+    let request = ipc::Request::AppendNote { target: target.to_string(), text: text.to_string(), create };
+    match ipc::send(&request).await? {
+        Some(reply) if reply.ok => {
+            return reply.note.ok_or_else(|| anyhow::anyhow!("The running instance did not send the note back"));
+        }
+        Some(reply) => return Err(anyhow::anyhow!(reply.message)),
+        None => {}
+    }
+    let (config, mut notes_manager) = load_notes().await?;
+    let index = match notes_manager.resolve_target(target) {
+        Ok(index) => index,
+        Err(NoteLookupError::NotFound(_)) if create => {
+            let mut note = Note::new(format!("{}\n{}", target.trim(), text));
+            capture::add_config_tags(&mut note, Source::Cli, &config.capture);
+            let id = note.id.clone();
+            notes_manager.add_note(note).await?;
+            return notes_manager
+                .get_note_by_id(&id)
+                .cloned()
+                .ok_or_else(|| UserError::NotFound(format!("Note {} not found after adding it", id)).into());
+        }
+        Err(NoteLookupError::NotFound(_)) => {
+            return Err(UserError::NotFound(format!("No note matches id or title '{}'", target)).into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    notes_manager.append_to_note(index, text).await?;
+    Ok(notes_manager.notes[index].clone())
+}
+
+/// Saves a note with its inline `#tag` and `@notebook` words and the
+/// `--tag` and `--notebook` options in `args` applied, then prints its id.
+async fn save_new_note(content: String, args: &ArgMatches) -> Result<()> {
+    let config = Config::load()?;
+    let note = new_note(content, args, &config)?;
+    let note = ipc::add_note(note, &config).await?;
+    if wants_json(args) {
+        return print_json(&note);
+    }
+    outln!("{}", note.id)?;
+    Ok(())
+}
+
+/// A note from `content` and the options in `args`, with the capture tags
+/// of commands.
+fn new_note(content: String, args: &ArgMatches, config: &Config) -> Result<Note> {
+    let raw = args.try_get_one::<bool>("raw").ok().flatten().copied().unwrap_or(false);
+    let mut note = if raw { Note::new(content) } else { Capture::parse(&content).into_note() };
+    if note.content.is_empty() {
+        return Err(UserError::Invalid("Note content cannot be empty".into()).into());
+    }
+    for tag in args.get_many::<String>("tag").into_iter().flatten() {
+        let tag = tag.trim_start_matches('#').to_string();
+        if !tag.is_empty() && !note.tags.contains(&tag) {
+            note.tags.push(tag);
+        }
+    }
+    if let Some(notebook) = args.get_one::<String>("notebook") {
+        note.notebook = Some(notebook.clone());
+    }
+    capture::add_config_tags(&mut note, Source::Cli, &config.capture);
+    Ok(note)
+}
+
+async fn serve(args: &ArgMatches) -> Result<()> {
+    let mut config = Config::load()?;
+    if api::ensure_token(&mut config)? {
+        eprintln!(
+            "Made up an API token and saved it as api.token in {}:\n  {}",
+            Config::config_file_path()?.display(),
+            config.api.token
+        );
+    }
+    let bind = args.get_one::<String>("bind").unwrap_or(&config.api.bind);
+    let port = args.get_one::<u16>("port").copied().unwrap_or(config.api.port);
+    // IPv6 addresses such as :: need brackets before the port
+    let address = if bind.contains(':') { format!("[{}]:{}", bind, port) } else { format!("{}:{}", bind, port) };
+    api::serve(config, &address).await
+}
+
+async fn quick(args: &ArgMatches) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        return Err(UserError::Invalid("quick needs a terminal; use 'turbo-notes add' in scripts".into()).into());
+    }
+    match quick::prompt("note> ")? {
+        Some(text) if !text.trim().is_empty() => save_new_note(text.trim().to_string(), args).await,
+        _ => Ok(()),
+    }
+}
+
+async fn snippet(args: &ArgMatches) -> Result<()> {
+    let (_, notes_manager) = load_notes().await?;
+    let query: Vec<&str> = args.get_many::<String>("query").into_iter().flatten().map(String::as_str).collect();
+    if query.is_empty() {
+        for i in snippets::search(&notes_manager.notes, "") {
+            let note = &notes_manager.notes[i];
+            outln!("{}\t{}", note.title(), snippets::body(note).lines().next().unwrap_or_default())?;
+        }
+        return Ok(());
+    }
+    let query = query.join(" ");
+    let Some(&index) = snippets::search(&notes_manager.notes, &query).first() else {
+        return Err(UserError::NotFound(format!(
+            "No snippet matches '{}'; tag a note #{} to make it one",
+            query,
+            snippets::SNIPPET_TAG
+        ))
+        .into());
+    };
+    let template = Template::parse(snippets::body(&notes_manager.notes[index]));
+
+    let given: Vec<(&str, &str)> = args
+        .get_many::<String>("field")
+        .into_iter()
+        .flatten()
+        .map(|field| {
+            field.split_once('=').ok_or_else(|| UserError::Invalid(format!("Expected NAME=VALUE, got '{}'", field)))
+        })
+        .collect::<Result<_, _>>()?;
+    let asking = io::stdin().is_terminal();
+    let mut values = Vec::new();
+    for field in template.fields() {
+        let value = given
+            .iter()
+            .find(|(name, _)| *name == field.default || name.parse() == Ok(field.number))
+            .map(|(_, value)| value.to_string());
+        let value = match value {
+            Some(value) => value,
+            // Left to the default, as when the field is left empty
+            None if !asking => String::new(),
+            None => match quick::prompt(&format!("{}> ", field.label()))? {
+                Some(value) => value,
+                None => return Ok(()),
+            },
+        };
+        values.push(value);
+    }
+
+    let pasted = if template.uses_clipboard() { clipboard::paste()? } else { String::new() };
+    let text = template.expand(&values, &pasted, Local::now());
+    if args.get_flag("print") {
+        outln!("{}", text)?;
+        return Ok(());
+    }
+    clipboard::copy(&text)?;
+    eprintln!("Copied '{}'", notes_manager.notes[index].title());
+    Ok(())
+}
+
+async fn bridge(args: &ArgMatches) -> Result<()> {
+    let Some(("taskwarrior", args)) = args.subcommand() else {
+        return Ok(());
+    };
+    let (_, mut notes_manager) = load_notes().await?;
+    let dry_run = args.get_flag("dry-run");
+    let command = args.get_one::<String>("command").expect("command has a default");
+    let report = taskwarrior::sync(&mut notes_manager, command, dry_run).await?;
+    if wants_json(args) {
+        return print_json(&report);
+    }
+    outln!(
+        "{} {} tasks, updated {}, checked off {} in notes, closed {} in Taskwarrior",
+        if dry_run { "Would add" } else { "Added" },
+        report.added,
+        report.updated,
+        report.done_in_notes,
+        report.closed_in_taskwarrior
+    )?;
+    Ok(())
+}
+
+/// `quick` for tmux popups: the pane underneath is kept with the note, and
+/// nothing is printed, so the popup closes as soon as the note is saved.
+async fn tmux_capture(args: &ArgMatches) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        return Err(UserError::Invalid("tmux-capture needs a terminal; run it with tmux display-popup".into()).into());
+    }
+    let origin = tmux::pane();
+    let label = match origin.as_ref().and_then(|origin| Path::new(&origin.path).file_name()) {
+        Some(dir) => format!("note ({})> ", dir.to_string_lossy()),
+        None => "note> ".to_string(),
+    };
+    let Some(text) = quick::prompt(&label)?.filter(|text| !text.trim().is_empty()) else {
+        return Ok(());
+    };
+    let config = Config::load()?;
+    let mut note = new_note(text.trim().to_string(), args, &config)?;
+    note.origin = origin;
+    ipc::add_note(note, &config).await?;
+    Ok(())
+}
+
+async fn list(args: &ArgMatches) -> Result<()> {
+    let (config, notes_manager) = load_notes().await?;
+
+    let mut notes: Vec<&Note> = match args.get_one::<String>("tag") {
+        Some(tag) => notes_manager.get_notes_by_tag(tag.trim_start_matches('#')),
+        None => notes_manager.notes.iter().collect(),
+    };
+    if let Some(limit) = args.get_one::<usize>("limit") {
+        notes.truncate(*limit);
+    }
+
+    if wants_json(args) {
+        return print_json(&notes);
+    }
+    print_table(&notes, &config)?;
+    Ok(())
+}
+
+fn print_table(notes: &[&Note], config: &Config) -> io::Result<()> {
+    let rows: Vec<[String; 4]> = notes
+        .iter()
+        .map(|note| {
+            [
+                note.id.clone(),
+                config.format_timestamp(note.created_at),
+                note.tags.join(","),
+                note.preview(60).replace('\n', " "),
+            ]
+        })
+        .collect();
+
+    let headers = ["ID", "CREATED", "TAGS", "NOTE"];
+    let mut widths = headers.map(|h| h.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: [&str; 4]| {
+        outln!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        )
+    };
+    print_row(headers)?;
+    for row in &rows {
+        print_row([&row[0], &row[1], &row[2], &row[3]])?;
+    }
+    Ok(())
+}
+
+async fn search(args: &ArgMatches) -> Result<()> {
+    let input: Vec<&str> = args.get_many::<String>("query").into_iter().flatten().map(String::as_str).collect();
+    let query = Query::parse(&input.join(" "));
+    let (config, notes_manager) = load_notes().await?;
+    let results = notes_manager.search_notes_with(&query);
+
+    if wants_json(args) {
+        let matches: Vec<_> = results
+            .iter()
+            .map(|(_, note)| {
+                let lines: Vec<_> = query
+                    .matching_lines(&note.content)
+                    .into_iter()
+                    .map(|(line, text)| json!({ "line": line, "text": text }))
+                    .collect();
+                json!({ "note": note, "lines": lines })
+            })
+            .collect();
+        return print_json(&matches);
+    }
+
+    let color = io::stdout().is_terminal();
+    for (_, note) in results {
+        let time = config.format_timestamp(note.created_at);
+        let mut lines = query.matching_lines(&note.content);
+        if lines.is_empty() {
+            // Matched on tags or text in images only; show the first line for context
+            lines.extend(note.content.lines().next().map(|line| (1, line)));
+        }
+        for (number, line) in lines {
+            let line = if color { highlight(line, &query) } else { line.to_string() };
+            outln!("{} {}:{}: {}", note.id, time, number, line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Wraps query matches in `line` with ANSI bold red.
+fn highlight(line: &str, query: &Query) -> String {
+    let mut output = String::new();
+    let mut last = 0;
+    for (start, end) in query.match_ranges(line) {
+        output.push_str(&line[last..start]);
+        output.push_str("\x1b[1;31m");
+        output.push_str(&line[start..end]);
+        output.push_str("\x1b[0m");
+        last = end;
+    }
+    output.push_str(&line[last..]);
+    output
+}
+
+async fn show(args: &ArgMatches) -> Result<()> {
+    let id = args.get_one::<String>("id").expect("id is required");
+    let (config, notes_manager) = load_notes().await?;
+    let note = &notes_manager.notes[notes_manager.resolve_id(id)?];
+
+    if wants_json(args) {
+        print_json(note)?;
+    } else if args.get_flag("raw") {
+        outln!("{}", note.content)?;
+    } else {
+        outln!("{}", note.id)?;
+        outln!("{}", metadata_line(note, &config))?;
+        outln!()?;
+        outln!("{}", note.content)?;
+    }
+    Ok(())
+}
+
+/// Line for a note in `pick`'s list: its title and tags, then its id in
+/// brackets, which is how a picked line is traced back to its note.
+fn pick_line(note: &Note) -> String {
+    let mut line = note.title().to_string();
+    for tag in &note.tags {
+        line.push_str(&format!(" #{}", tag));
+    }
+    format!("{}  [{}]", line, note.id)
+}
+
+/// Id of the note a `pick_line` was made for.
+fn picked_id(line: &str) -> Option<&str> {
+    let (_, id) = line.trim_end().strip_suffix(']')?.rsplit_once('[')?;
+    Note::is_valid_id(id).then_some(id)
+}
+
+async fn pick(args: &ArgMatches) -> Result<()> {
+    let acting = ["print", "edit", "open", "capture"].iter().any(|flag| args.get_flag(flag));
+    let choice = match args.get_one::<String>("choice") {
+        Some(choice) => choice.trim().to_string(),
+        None if acting => read_stdin()?.lines().next().unwrap_or_default().trim().to_string(),
+        None => {
+            let (_, notes_manager) = load_notes().await?;
+            for note in &notes_manager.notes {
+                outln!("{}", pick_line(note))?;
+            }
+            return Ok(());
+        }
+    };
+    if choice.is_empty() {
+        return Err(UserError::NotFound("Nothing was picked".into()).into());
+    }
+
+    let (_, notes_manager) = load_notes().await?;
+    let index = match picked_id(&choice).map(|id| notes_manager.resolve_id(id)) {
+        Some(Ok(index)) => index,
+        _ if args.get_flag("capture") => return save_new_note(choice, args).await,
+        _ => return Err(UserError::NotFound(format!("No note for '{}'; pick a line printed by 'turbo-notes pick'", choice)).into()),
+    };
+    let note = &notes_manager.notes[index];
+
+    if args.get_flag("edit") {
+        edit_note(&note.id, args).await
+    } else if args.get_flag("open") {
+        let request = Request::Open { note: Some(note.id.clone()) };
+        match ipc::send(&request).await? {
+            Some(reply) if reply.ok && !reply.released => {
+                outln!("{}", reply.message)?;
+                Ok(())
+            }
+            Some(reply) if !reply.ok => Err(anyhow::anyhow!(reply.message)),
+            _ => {
+                let mut app = App::new(false).await?;
+                app.show_note(&note.id);
+                app.run().await
+            }
+        }
+    } else if wants_json(args) {
+        print_json(note)
+    } else {
+        outln!("{}", note.content)?;
+        Ok(())
+    }
+}
+
+/// A day given as option `name`, read in the config's locale.
+fn day_arg(args: &ArgMatches, name: &str, config: &Config) -> Result<Option<NaiveDate>> {
+    let today = config.zone().day(Utc::now());
+    args.get_one::<String>(name)
+        .map(|text| natural::parse_day(text, today, config))
+        .transpose()
+}
+
+async fn feed(args: &ArgMatches) -> Result<()> {
+    let (config, notes_manager) = load_notes().await?;
+    let mut feed_config = config.feed.clone();
+    if let Some(tag) = args.get_one::<String>("tag") {
+        feed_config.tag = tag.clone();
+    }
+    if let Some(&limit) = args.get_one::<usize>("limit") {
+        feed_config.limit = limit;
+    }
+    let feed = atom::feed(&notes_manager.notes, &feed_config, None, Utc::now());
+    match args.get_one::<String>("output") {
+        Some(path) => {
+            fs::write(path, feed)?;
+            let count = atom::entries(&notes_manager.notes, &feed_config).len();
+            eprintln!("Wrote {} notes to {}", count, path);
+        }
+        None => out!("{}", feed)?,
+    }
+    Ok(())
+}
+
+async fn export(args: &ArgMatches) -> Result<()> {
+    if args.get_flag("scheduled") {
+        let config = Config::load()?;
+        let count = schedule::export_now(&config).await?;
+        eprintln!("Exported {} notes to {}", count, config.scheduled_export_dir().display());
+        return Ok(());
+    }
+
+    let name = args.get_one::<String>("format").map(String::as_str).unwrap_or("markdown");
+    let format = ExportFormat::from_name(name).unwrap_or(ExportFormat::Markdown);
+    let (config, notes_manager) = load_notes().await?;
+    // Formats not built in are a plugin's
+    let plugins = match plugins::is_builtin_format(name) {
+        true => None,
+        false => {
+            let plugins = Plugins::load(&config);
+            if plugins.export_format(name).is_none() {
+                return Err(UserError::Invalid(format!("Unknown export format '{}'; no plugin adds it", name)).into());
+            }
+            Some(plugins)
+        }
+    };
+
+    let mut filter = ExportFilter {
+        tags: args
+            .get_many::<String>("tag")
+            .unwrap_or_default()
+            .map(|tag| tag.trim_start_matches('#').to_string())
+            .collect(),
+        since: day_arg(args, "since", &config)?,
+        until: day_arg(args, "until", &config)?,
+        zone: config.zone(),
+        query: args.get_one::<String>("search").map(|search| Query::parse(search)),
+        ..Default::default()
+    };
+    for id in args.get_many::<String>("id").unwrap_or_default() {
+        let index = notes_manager.resolve_id(id)?;
+        filter.ids.push(notes_manager.notes[index].id.clone());
+    }
+    let notes = notes_manager.filter_notes(&filter);
+
+    if args.get_flag("encrypt") {
+        let path = args.get_one::<String>("output").expect("--encrypt requires --output");
+        if name == "jex" || plugins.is_some() {
+            return Err(UserError::Invalid(format!("--encrypt does not support the {} format", name)).into());
+        }
+        let password = archive::password(true)?;
+        fs::write(path, archive::write_archive(&notes, format, &config, &password)?)?;
+        eprintln!("Exported {} notes to encrypted archive {}", notes.len(), path);
+        return Ok(());
+    }
+
+    // Joplin archives are binary, so they bypass the text exporters
+    if name == "jex" {
+        match args.get_one::<String>("output") {
+            Some(path) => {
+                joplin::write_jex(&notes, fs::File::create(path)?)?;
+                eprintln!("Exported {} notes to {}", notes.len(), path);
+            }
+            None if io::stdout().is_terminal() => {
+                return Err(UserError::Invalid("Refusing to write a JEX archive to a terminal; use --output".into()).into());
+            }
+            None => joplin::write_jex(&notes, io::stdout().lock())?,
+        }
+        return Ok(());
+    }
+
+    if args.get_flag("per-note") {
+        if !matches!(format, ExportFormat::Markdown) || plugins.is_some() {
+            return Err(UserError::Invalid("--per-note only supports the markdown format".into()).into());
+        }
+        let dir = args.get_one::<String>("output").expect("--per-note requires --output");
+        vault::export_files(&notes, &config.notes_dir, Path::new(dir))?;
+        eprintln!("Exported {} notes to {}", notes.len(), dir);
+        return Ok(());
+    }
+
+    let output = match &plugins {
+        Some(plugins) => plugins.export(name, &notes)?,
+        None => NotesManager::export_selection(&notes, format, &config)?,
+    };
+
+    match args.get_one::<String>("output") {
+        Some(path) => {
+            fs::write(path, output)?;
+            eprintln!("Exported {} notes to {}", notes.len(), path);
+        }
+        None => out!("{}", output)?,
+    }
+    Ok(())
+}
+
+async fn import(args: &ArgMatches) -> Result<()> {
+    let show_progress = io::stderr().is_terminal();
+    let mut progress = |done: usize, total: usize| {
+        // Redrawing on every item would slow down large imports
+        if show_progress && done.is_multiple_of(50) {
+            match total {
+                0 => eprint!("\rReading {} items...", done),
+                total => eprint!("\rReading {}/{} items...", done, total),
+            }
+        }
+    };
+
+    let imported = if let Some(("dir", args)) = args.subcommand() {
+        let path = args.get_one::<String>("path").expect("path is required");
+        let mut imported = vault::import_files(Path::new(path), args.get_flag("recursive"), &mut progress)?;
+        for tag in args.get_many::<String>("tag").unwrap_or_default() {
+            let tag = tag.trim().trim_start_matches('#');
+            if tag.is_empty() || tag.contains(char::is_whitespace) {
+                return Err(UserError::Invalid(format!("Invalid tag '{}'", tag)).into());
+            }
+            for note in &mut imported.notes {
+                let updated_at = note.updated_at;
+                note.add_tag(tag.to_string());
+                note.updated_at = updated_at;
+            }
+        }
+        imported
+    } else {
+        let file = args.get_one::<String>("file").expect("file is required");
+        let mut input = Vec::new();
+        if file == "-" {
+            io::stdin().read_to_end(&mut input)?;
+        } else {
+            input = fs::read(file).map_err(|e| anyhow::Error::new(e).context(format!("Could not read {}", file)))?;
+        }
+
+        let importer = import::find_importer(
+            args.get_one::<String>("format").map(String::as_str),
+            Path::new(file),
+            &input,
+        );
+        importer.read(&input, &mut progress)?
+    };
+    if show_progress {
+        eprint!("\r\x1b[2K");
+    }
+    if imported.skipped_files > 0 {
+        eprintln!("Skipped {} attached files; links to them are kept in the note text", imported.skipped_files);
+    }
+    let images = imported.images;
+
+    let (config, mut notes_manager) = load_notes().await?;
+    // A note with images is not a repeat because its text is, or it has none
+    let (with_images, notes): (Vec<Note>, Vec<Note>) =
+        imported.notes.into_iter().partition(|note| images.iter().any(|image| image.note_id == note.id));
+    let (mut notes, duplicates) = if args.get_flag("allow-duplicates") {
+        (notes, Vec::new())
+    } else {
+        notes_manager.split_duplicates(notes)
+    };
+    notes.extend(with_images);
+    let near = duplicates.iter().filter(|(_, duplicate)| *duplicate == Duplicate::Near).count();
+    let skipped = match (duplicates.len(), near) {
+        (0, _) => String::new(),
+        (count, 0) => format!(" ({} duplicates skipped)", count),
+        (count, near) => format!(" ({} duplicates skipped, {} of them differing only in case, spacing or punctuation)", count, near),
+    };
+
+    if args.get_flag("dry-run") {
+        for note in &notes {
+            outln!("{}", note.preview(60).replace('\n', " "))?;
+        }
+        for (note, _) in &duplicates {
+            outln!("skip: {}", note.preview(60).replace('\n', " "))?;
+        }
+        match images.len() {
+            0 => outln!("Would import {} notes{}", notes.len(), skipped)?,
+            count => outln!("Would import {} notes with {} images{}", notes.len(), count, skipped)?,
+        }
+        return Ok(());
+    }
+
+    let ids: Vec<String> = notes.iter().map(|note| note.id.clone()).collect();
+    let imported = notes_manager.import_notes(notes).await?;
+    let attached = attach_imported_images(&config, &mut notes_manager, &ids, images).await?;
+    match attached {
+        0 => outln!("Imported {} notes{}", imported, skipped)?,
+        attached => outln!("Imported {} notes with {} images{}", imported, attached, skipped)?,
+    }
+    if !duplicates.is_empty() {
+        outln!("Run with --allow-duplicates to import them anyway")?;
+    }
+    Ok(())
+}
+
+fn tag_arg<'a>(args: &'a ArgMatches, name: &str) -> Result<&'a str> {
+    let tag = args
+        .get_one::<String>(name)
+        .map(|tag| tag.trim().trim_start_matches('#'))
+        .unwrap_or_default();
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(UserError::Invalid(format!("Invalid tag '{}'", tag)).into());
+    }
+    Ok(tag)
+}
+
+async fn tag(args: &ArgMatches) -> Result<()> {
+    let (_, mut notes_manager) = load_notes().await?;
+
+    match args.subcommand() {
+        Some(("add", args)) => {
+            let index = notes_manager.resolve_id(args.get_one::<String>("id").expect("id is required"))?;
+            let tag = tag_arg(args, "tag")?;
+            notes_manager.add_tag(index, tag.to_string()).await?;
+            if wants_json(args) {
+                return print_json(&notes_manager.notes[index]);
+            }
+            outln!("Tagged {} with #{}", notes_manager.notes[index].id, tag)?;
+        }
+        Some(("rm", args)) => {
+            let index = notes_manager.resolve_id(args.get_one::<String>("id").expect("id is required"))?;
+            let tag = tag_arg(args, "tag")?;
+            if !notes_manager.notes[index].tags.iter().any(|t| t == tag) {
+                let message = format!("Note {} has no tag #{}", notes_manager.notes[index].id, tag);
+                return Err(UserError::NotFound(message).into());
+            }
+            notes_manager.remove_tag(index, tag).await?;
+            if wants_json(args) {
+                return print_json(&notes_manager.notes[index]);
+            }
+            outln!("Removed #{} from {}", tag, notes_manager.notes[index].id)?;
+        }
+        Some(("list", args)) => {
+            let counts = notes_manager.tag_counts();
+            if wants_json(args) {
+                let tags: Vec<_> = counts.iter().map(|(tag, count)| json!({ "tag": tag, "notes": count })).collect();
+                return print_json(&tags);
+            }
+            for (tag, count) in counts {
+                outln!("{}\t{}", tag, count)?;
+            }
+        }
+        Some(("rename", args)) => {
+            let old = tag_arg(args, "old")?;
+            let new = tag_arg(args, "new")?;
+            let changed = notes_manager.rename_tag(old, new).await?;
+            if wants_json(args) {
+                return print_json(&json!({ "old": old, "new": new, "changed": changed }));
+            }
+            outln!("Renamed #{} to #{} on {} notes", old, new, changed)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn later(args: &ArgMatches) -> Result<()> {
+    let (_, notes_manager) = load_notes().await?;
+    let queue = reading::queue(&notes_manager.notes);
+
+    if wants_json(args) {
+        let json: Vec<_> = queue
+            .iter()
+            .map(|&i| &notes_manager.notes[i])
+            .map(|note| {
+                json!({
+                    "id": note.id,
+                    "title": note.title(),
+                    "progress": reading::progress(note).unwrap_or(0),
+                    "words_left": reading::unread(note).split_whitespace().count(),
+                })
+            })
+            .collect();
+        return print_json(&json);
+    }
+    if queue.is_empty() {
+        outln!("Nothing to read; tag a note #{} to queue it", reading::READ_LATER_TAG)?;
+    }
+    for note in queue.iter().map(|&i| &notes_manager.notes[i]) {
+        let progress = reading::progress(note).unwrap_or(0);
+        let left = reading::unread(note).split_whitespace().count();
+        outln!("{:>3}%  {:>6} words left  {}  [{}]", progress, left, note.title(), note.id)?;
+    }
+    Ok(())
+}
+
+async fn graph(args: &ArgMatches) -> Result<()> {
+    let (_, notes_manager) = load_notes().await?;
+    let notes = &notes_manager.notes;
+    let graph = Graph::build(notes);
+
+    if args.get_one::<String>("format").is_some_and(|format| format == "dot") {
+        out!("{}", graph.to_dot(notes))?;
+        return Ok(());
+    }
+    if wants_json(args) {
+        let nodes: Vec<_> = graph.nodes.iter().map(|&i| json!({ "id": notes[i].id, "title": notes[i].title() })).collect();
+        let links: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|&(from, to)| json!({ "from": notes[graph.nodes[from]].id, "to": notes[graph.nodes[to]].id }))
+            .collect();
+        return print_json(&json!({ "notes": nodes, "links": links }));
+    }
+    if graph.is_empty() {
+        outln!("No links yet: write [[Note title]] in a note to link to it")?;
+    }
+    for &(from, to) in &graph.edges {
+        outln!("{} -> {}", notes[graph.nodes[from]].title(), notes[graph.nodes[to]].title())?;
+    }
+    Ok(())
+}
+
+async fn habit(args: &ArgMatches) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let today = config.zone().day(Utc::now());
+
+    let (done, args) = match args.subcommand() {
+        Some(("done", args)) => (true, args),
+        Some(("undo", args)) => (false, args),
+        Some((_, args)) => {
+            let listed: Vec<_> = notes_manager
+                .notes
+                .iter()
+                .filter(|note| habits::is_habit(note))
+                .map(|note| {
+                    let streaks = habits::streaks(&note.habit_days, today);
+                    let done_today = note.habit_days.binary_search(&today).is_ok();
+                    (note, streaks, done_today)
+                })
+                .collect();
+            if wants_json(args) {
+                let json: Vec<_> = listed
+                    .iter()
+                    .map(|(note, streaks, done_today)| {
+                        json!({
+                            "id": note.id,
+                            "title": note.title(),
+                            "done_today": done_today,
+                            "current_streak": streaks.current,
+                            "longest_streak": streaks.longest,
+                            "days": note.habit_days,
+                        })
+                    })
+                    .collect();
+                return print_json(&json);
+            }
+            if listed.is_empty() {
+                outln!("No habits; tag a note #{} to track it", habits::HABIT_TAG)?;
+            }
+            for (note, streaks, done_today) in listed {
+                let mark = if done_today { "[x]" } else { "[ ]" };
+                outln!("{} {}  {} now, {} at most  [{}]", mark, note.title(), streaks.current, streaks.longest, note.id)?;
+            }
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+
+    let index = notes_manager.resolve_id(args.get_one::<String>("id").expect("id is required"))?;
+    if !habits::is_habit(&notes_manager.notes[index]) {
+        let message = format!("Note {} is not a habit; tag it #{} first", notes_manager.notes[index].id, habits::HABIT_TAG);
+        return Err(UserError::Invalid(message).into());
+    }
+    let day = day_arg(args, "day", &config)?.unwrap_or(today);
+    if day > today {
+        return Err(UserError::Invalid(format!("{} has not come yet", day)).into());
+    }
+    notes_manager.set_habit_day(index, day, done).await?;
+    let note = &notes_manager.notes[index];
+    let streaks = habits::streaks(&note.habit_days, today);
+    if wants_json(args) {
+        return print_json(&json!({ "id": note.id, "current_streak": streaks.current, "longest_streak": streaks.longest }));
+    }
+    let state = if done { "done" } else { "not done" };
+    outln!("{}: {} on {}; {} days in a row", note.title(), state, day, streaks.current)?;
+    Ok(())
+}
+
+async fn remind(args: &ArgMatches) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let now = Utc::now();
+
+    // Options like --format are read from the subcommand's matches
+    let (index, when, args) = match args.subcommand() {
+        Some(("set", args)) => {
+            let index = notes_manager.resolve_id(args.get_one::<String>("id").expect("id is required"))?;
+            let when = reminders::parse_when(args.get_one::<String>("when").expect("when is required"), now, &config)?;
+            (index, Some(when), args)
+        }
+        Some(("snooze", args)) => {
+            let index = notes_manager.resolve_id(args.get_one::<String>("id").expect("id is required"))?;
+            let text = args.get_one::<String>("for").expect("for has a default");
+            let length = reminders::parse_duration(text)
+                .ok_or_else(|| UserError::Invalid(format!("Could not read '{}' as a length of time; use e.g. 10m, 1h or 1d", text)))?;
+            (index, Some(now + length), args)
+        }
+        Some(("done", args)) => {
+            let index = notes_manager.resolve_id(args.get_one::<String>("id").expect("id is required"))?;
+            if notes_manager.notes[index].remind_at.is_none() {
+                let message = format!("Note {} has no reminder", notes_manager.notes[index].id);
+                return Err(UserError::NotFound(message).into());
+            }
+            (index, None, args)
+        }
+        Some(("list", args)) => {
+            let pending = reminders::pending(&notes_manager.notes);
+            if wants_json(args) {
+                return print_json(&pending);
+            }
+            for note in pending {
+                let Some(at) = note.remind_at else { continue };
+                let state = if reminders::is_due(note, now) { "missed" } else { "upcoming" };
+                outln!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    note.id,
+                    state,
+                    config.format_timestamp(at),
+                    reminders::until(at, now),
+                    note.preview(60).replace('\n', " ")
+                )?;
+            }
+            return Ok(());
+        }
+        Some(("ics", args)) => {
+            let calendar = ics::calendar(&notes_manager.notes, now);
+            match args.get_one::<String>("output") {
+                Some(path) => {
+                    fs::write(path, calendar)?;
+                    let count = notes_manager.notes.iter().filter(|note| note.remind_at.is_some()).count();
+                    eprintln!("Wrote {} reminders to {}", count, path);
+                }
+                None => out!("{}", calendar)?,
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+
+    notes_manager.set_reminder(index, when).await?;
+    let note = &notes_manager.notes[index];
+    if wants_json(args) {
+        return print_json(note);
+    }
+    match when {
+        Some(when) => outln!("Reminder for {} set for {} ({})", note.id, config.format_timestamp(when), reminders::until(when, now))?,
+        None => outln!("Cleared the reminder on {}", note.id)?,
+    }
+    Ok(())
+}
+
+async fn edit(args: &ArgMatches) -> Result<()> {
+    edit_note(args.get_one::<String>("id").expect("id is required"), args).await
+}
+
+/// Opens note `id` in the editor and saves the result if it changed.
+async fn edit_note(id: &str, args: &ArgMatches) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let index = notes_manager.resolve_id(id)?;
+    let note = &notes_manager.notes[index];
+    let original = note.content.clone();
+
+    let edited = editor::edit_text(&config, &note.id, &original)?;
+    let changed = edited != original;
+    if changed && edited.trim().is_empty() {
+        return Err(UserError::Invalid("Edited note is empty; not saving (use delete to remove it)".into()).into());
+    }
+    if changed {
+        let edited = match tidy::wanted(&notes_manager.notes[index].tags, &config.tidy) {
+            true => tidy::tidy(&edited, config.tidy.wrap),
+            false => edited,
+        };
+        let edited = Plugins::load(&config).transform(&notes_manager.notes[index], edited);
+        notes_manager.update_note(index, edited).await?;
+    }
+
+    let id = &notes_manager.notes[index].id;
+    if wants_json(args) {
+        print_json(&json!({ "id": id, "changed": changed }))?;
+    } else if changed {
+        outln!("Updated {}", id)?;
+    } else {
+        outln!("No changes")?;
+    }
+    Ok(())
+}
+
+async fn delete(args: &ArgMatches) -> Result<()> {
+    let id = args.get_one::<String>("id").expect("id is required");
+    let (_, mut notes_manager) = load_notes().await?;
+    let index = notes_manager.resolve_id(id)?;
+    let note = &notes_manager.notes[index];
+
+    if !args.get_flag("force") {
+        if !io::stdin().is_terminal() {
+            return Err(UserError::Invalid("Refusing to delete without --force when stdin is not a terminal".into()).into());
+        }
+        out!("Delete note {} \"{}\"? [y/N] ", note.id, note.preview(40).replace('\n', " "))?;
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            if wants_json(args) {
+                return print_json(&json!({ "deleted": null }));
+            }
+            outln!("Aborted")?;
+            return Ok(());
+        }
+    }
+
+    let id = note.id.clone();
+    notes_manager.delete_note(index).await?;
+    if wants_json(args) {
+        return print_json(&json!({ "deleted": id }));
+    }
+    outln!("Deleted {}", id)?;
+    Ok(())
+}
+
+fn config(args: &ArgMatches) -> Result<()> {
+    match args.subcommand() {
+        Some(("get", args)) => {
+            let config = Config::load()?;
+            let value = match args.get_one::<String>("key") {
+                Some(key) => config.get_value(key)?,
+                None => serde_json::to_value(&config)?,
+            };
+            match value {
+                serde_json::Value::String(value) if !wants_json(args) => outln!("{}", value)?,
+                value => print_json(&value)?,
+            }
+        }
+        Some(("set", args)) => {
+            let key = args.get_one::<String>("key").expect("key is required");
+            let value = args.get_one::<String>("value").expect("value is required");
+            let mut config = Config::load()?;
+            config.set_value(key, value)?;
+            let changed = match key.as_str() {
+                "auto_start" => autostart::apply(&config)?,
+                _ => None,
+            };
+            let value = config.get_value(key)?;
+            if wants_json(args) {
+                return print_json(&json!({ "key": key, "value": value }));
+            }
+            outln!("{} = {}", key, value)?;
+            if let Some(changed) = changed {
+                outln!("{}", changed)?;
+            }
+        }
+        Some(("path", args)) => {
+            let path = Config::config_file_path()?;
+            if wants_json(args) {
+                return print_json(&json!({ "path": path }));
+            }
+            outln!("{}", path.display())?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Notes directory of profile `name`, whether or not it is the one in use.
+fn profile_notes_dir(name: &str) -> Result<PathBuf> {
+    let name = Some(name).filter(|name| *name != profile::DEFAULT);
+    let path = profile::config_file(name)?;
+    if path.exists() {
+        Ok(Config::load_from(&path)?.notes_dir)
+    } else {
+        Ok(Config::default_notes_dir(name))
+    }
+}
+
+fn profile_command(args: &ArgMatches) -> Result<()> {
+    match args.subcommand() {
+        Some(("list", args)) => {
+            let active = profile::active_name();
+            let mut profiles = Vec::new();
+            for name in profile::list()? {
+                let notes_dir = profile_notes_dir(&name)?;
+                profiles.push((name, notes_dir));
+            }
+            if wants_json(args) {
+                let profiles: Vec<_> = profiles
+                    .iter()
+                    .map(|(name, notes_dir)| json!({ "name": name, "active": *name == active, "notes_dir": notes_dir }))
+                    .collect();
+                return print_json(&profiles);
+            }
+            for (name, notes_dir) in &profiles {
+                let marker = if *name == active { "*" } else { " " };
+                outln!("{} {:<16} {}", marker, name, notes_dir.display())?;
+            }
+        }
+        Some(("create", args)) => {
+            let name = args.get_one::<String>("name").expect("name is required");
+            let config = profile::create(name, args.get_one::<PathBuf>("notes-dir").cloned())?;
+            if wants_json(args) {
+                return print_json(&json!({ "name": name, "notes_dir": config.notes_dir }));
+            }
+            outln!("Created profile '{}', with notes in {}", name, config.notes_dir.display())?;
+            outln!("Use it with 'turbo-notes --profile {}', or press P in the app", name)?;
+        }
+        Some(("remove", args)) => {
+            let name = args.get_one::<String>("name").expect("name is required");
+            let notes_dir = profile_notes_dir(name)?;
+            profile::remove(name)?;
+            if wants_json(args) {
+                return print_json(&json!({ "removed": name, "notes_dir": notes_dir }));
+            }
+            outln!("Removed profile '{}'; its notes are still in {}", name, notes_dir.display())?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn ipc_command(args: &ArgMatches) -> Result<()> {
+    let (request, args) = match args.subcommand() {
+        Some(("ping", args)) => (Request::Ping, args),
+        Some(("show-widget", args)) => (Request::ShowWidget, args),
+        Some(("add-note", args)) => {
+            let request = Request::AddNote {
+                content: note_content(args)?,
+                tags: args.get_many::<String>("tag").into_iter().flatten().cloned().collect(),
+                notebook: args.get_one::<String>("notebook").cloned(),
+                origin: None,
+            };
+            (request, args)
+        }
+        _ => return Ok(()),
+    };
+
+    let reply = ipc::send(&request).await?.ok_or_else(|| {
+        UserError::NotFound("Turbo Notes is not running; start it in the background with 'turbo-notes --startup'".into())
+    })?;
+    if !reply.ok {
+        return Err(anyhow::anyhow!(reply.message));
+    }
+    if reply.released {
+        // The instance has no terminal, so the widget is shown here
+        let mut app = App::new(true).await?;
+        return app.run_widget().await;
+    }
+    if wants_json(args) {
+        return print_json(&reply);
+    }
+    match reply.id {
+        Some(id) => outln!("{}", id)?,
+        None => outln!("{}", reply.message)?,
+    }
+    Ok(())
+}
+
+async fn autostart(args: &ArgMatches) -> Result<()> {
+    match args.subcommand() {
+        Some(("status", args)) => {
+            let login_entry = autostart::is_autostart_enabled()?;
+            // Unsupported platforms have no service to report
+            let service = autostart::service_status().ok();
+            let running = ipc::send(&Request::Ping).await?.is_some();
+            let pid = daemon::running_pid().filter(|_| running);
+            if wants_json(args) {
+                return print_json(&json!({
+                    "login_entry": login_entry,
+                    "service": service,
+                    "running": running,
+                    "pid": pid,
+                }));
+            }
+            outln!("Login entry: {}", if login_entry { "enabled" } else { "disabled" })?;
+            match service {
+                Some(service) if service.installed => outln!(
+                    "Service:     {} ({})",
+                    service.state.as_deref().unwrap_or("unknown"),
+                    service.path.display()
+                )?,
+                Some(_) => outln!("Service:     not installed")?,
+                None => outln!("Service:     not supported here")?,
+            }
+            match (running, pid) {
+                (true, Some(pid)) => outln!("Running:     yes, in the background (pid {})", pid)?,
+                (true, None) => outln!("Running:     yes")?,
+                (false, _) => outln!("Running:     no")?,
+            }
+        }
+        Some(("uninstall", args)) => {
+            let (login_entry, service) = autostart::disable()?;
+            autostart::record(&mut Config::load()?, false)?;
+            if wants_json(args) {
+                return print_json(&json!({ "login_entry": login_entry, "service": service }));
+            }
+            if !login_entry && !service {
+                outln!("Nothing to remove")?;
+            }
+            if login_entry {
+                outln!("Removed the login entry")?;
+            }
+            if service {
+                outln!("Removed the background service")?;
+            }
+        }
+        _ if args.get_flag("service") => {
+            let was_running = ipc::send(&Request::Ping).await?.is_some();
+            let path = autostart::install_service()?;
+            // Both would start an instance on login
+            let replaced = autostart::is_autostart_enabled()?;
+            if replaced {
+                autostart::setup_autostart(false)?;
+            }
+            autostart::record(&mut Config::load()?, true)?;
+            if wants_json(args) {
+                return print_json(&json!({ "service": path, "replaced_login_entry": replaced }));
+            }
+            outln!("Installed and started the background service: {}", path.display())?;
+            if replaced {
+                outln!("It replaces the login entry")?;
+            }
+            if was_running {
+                outln!("Turbo Notes was already running; the service takes over once it exits")?;
+            }
+        }
+        _ => {
+            autostart::setup_autostart(true)?;
+            autostart::record(&mut Config::load()?, true)?;
+            if wants_json(args) {
+                return print_json(&json!({ "login_entry": true }));
+            }
+            outln!("Auto-start enabled successfully!")?;
+        }
+    }
+    Ok(())
+}
+
+async fn shell_hook_command(args: &ArgMatches) -> Result<()> {
+    let config = Config::load()?;
+    if let Some(("log", args)) = args.subcommand() {
+        let command = args.get_one::<String>("command").expect("command is required");
+        let now = config.zone().to_local(Utc::now());
+        let Some(line) = shell_hook::log_line(command, &config.shell_log.marker, now) else {
+            return Ok(());
+        };
+        let title = now.format(&config.shell_log.title).to_string();
+        append_to(&title, &line, true).await?;
+        return Ok(());
+    }
+    let shell = args
+        .get_one::<String>("shell")
+        .ok_or_else(|| UserError::Invalid("Name a shell: bash, zsh or fish".into()))?;
+    let script = shell_hook::script(shell, &config.shell_log.marker).expect("shells are checked by clap");
+    out!("{}", script)?;
+    Ok(())
+}
+
+fn completions(args: &ArgMatches) -> Result<()> {
+    let shell = args.get_one::<String>("shell").expect("shell is required");
+    let name = "turbo-notes";
+    let mut stdout = io::stdout();
+
+    if args.get_flag("static") {
+        let shell: clap_complete::Shell = shell.parse().map_err(|e| anyhow::anyhow!("{}", e))?;
+        clap_complete::generate(shell, &mut build_cli(), name, &mut stdout);
+    } else {
+        let shells = Shells::builtins();
+        let completer = shells
+            .completer(shell)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported shell '{}'", shell))?;
+        completer.write_registration(COMPLETE_VAR, name, name, name, &mut stdout)?;
+    }
+    Ok(())
+}
+
+async fn stats(args: &ArgMatches) -> Result<()> {
+    let days = *args.get_one::<usize>("days").expect("days has a default");
+    let (config, notes_manager) = load_notes().await?;
+    let stats = NotesStats::collect(&notes_manager.notes, &config.notes_dir, days, config.zone());
+
+    if wants_json(args) {
+        return print_json(&stats);
+    }
+
+    outln!("Notes:    {} ({} pinned, {} untagged)", stats.total_notes, stats.pinned_notes, stats.untagged_notes)?;
+    outln!("Words:    {}", stats.total_words)?;
+    outln!("Tags:     {}", stats.notes_per_tag.len())?;
+    outln!("Storage:  {}", note_stats::format_size(stats.storage_bytes))?;
+    match config.word_goal {
+        0 => outln!("Written:  {} words today", stats.words_today)?,
+        goal => outln!("Written:  {} of {} words today", stats.words_today, goal)?,
+    }
+
+    let counts: Vec<usize> = stats.notes_per_day.iter().map(|(_, count)| *count).collect();
+    let created: usize = counts.iter().sum();
+    let ascii = config.accessibility_mode || !io::stdout().is_terminal() || !Capabilities::detect().unicode;
+    outln!()?;
+    outln!("Created in the last {} days: {}", days, created)?;
+    outln!("  |{}|", note_stats::sparkline(&counts, ascii))?;
+
+    if !stats.notes_per_tag.is_empty() {
+        let width = stats.notes_per_tag.iter().map(|(tag, _)| tag.chars().count()).max().unwrap_or(0);
+        let max = stats.notes_per_tag[0].1;
+        let bar = if ascii { "#" } else { "█" };
+        outln!()?;
+        outln!("Notes per tag:")?;
+        for (tag, count) in &stats.notes_per_tag {
+            let len = (count * 30).div_ceil(max);
+            outln!("  #{:<width$} {:>4} {}", tag, count, bar.repeat(len), width = width)?;
+        }
+    }
+
+    let since = stats.notes_per_day.first().map(|(day, _)| *day).unwrap_or_default();
+    let reached: Vec<_> = stats.goals_reached.iter().filter(|(day, _)| *day >= since).collect();
+    if !reached.is_empty() {
+        outln!()?;
+        outln!("Word goal reached on {} of the last {} days:", reached.len(), days)?;
+        for (day, goal) in reached {
+            outln!("  {}  {} words", day, goal)?;
+        }
+    }
+    Ok(())
+}
+
+fn backup(args: &ArgMatches) -> Result<()> {
+    let config = Config::load()?;
+    let store = BackupStore::new(&config);
+
+    match args.subcommand() {
+        Some(("now", args)) => {
+            let backup = store.create(None)?;
+            if wants_json(args) {
+                return print_json(&backup);
+            }
+            outln!("Created backup {}", backup.id)?;
+        }
+        Some(("list", args)) => {
+            let backups = store.list()?;
+            if wants_json(args) {
+                return print_json(&backups);
+            }
+            for backup in &backups {
+                let created = backup
+                    .created_at
+                    .map(|time| config.format_timestamp(time.to_utc()))
+                    .unwrap_or_else(|| "-".to_string());
+                outln!("{}\t{}\t{}", backup.id, created, note_stats::format_size(backup.size))?;
+            }
+            if !backups.is_empty() {
+                let total: u64 = backups.iter().map(|backup| backup.size).sum();
+                let auto = backups.iter().filter(|backup| backup.is_auto()).count();
+                outln!(
+                    "{} backups ({} automatic) of {} of notes, taking {} on disk",
+                    backups.len(),
+                    auto,
+                    note_stats::format_size(total),
+                    note_stats::format_size(note_stats::dir_size(&config.backup_dir()))
+                )?;
+            }
+        }
+        Some(("verify", args)) => {
+            let backups = match args.get_one::<String>("backup-id") {
+                Some(id) => vec![store.find(id)?],
+                None => store.list()?,
+            };
+            let checked: Vec<(Backup, Integrity)> = backups
+                .into_iter()
+                .map(|backup| {
+                    let integrity = backup.integrity();
+                    (backup, integrity)
+                })
+                .collect();
+            let damaged = checked
+                .iter()
+                .filter(|(_, integrity)| matches!(integrity, Integrity::Damaged(_)))
+                .count();
+            if wants_json(args) {
+                let results: Vec<_> = checked
+                    .iter()
+                    .map(|(backup, integrity)| json!({ "backup": backup.id, "integrity": integrity }))
+                    .collect();
+                print_json(&results)?;
+            } else {
+                for (backup, integrity) in &checked {
+                    let status = match integrity {
+                        Integrity::Verified => "ok".to_string(),
+                        Integrity::Unverified => "ok (no checksums)".to_string(),
+                        Integrity::Damaged(error) => format!("DAMAGED: {}", error),
+                    };
+                    outln!("{}\t{}", backup.id, status)?;
+                }
+                outln!("{} backups checked, {} damaged", checked.len(), damaged)?;
+            }
+            if damaged > 0 {
+                return Err(UserError::Invalid(format!("{} backups are damaged", damaged)).into());
+            }
+        }
+        Some(("prune", args)) => {
+            let dry_run = args.get_flag("dry-run");
+            let before = note_stats::dir_size(&config.backup_dir());
+            let expired = if dry_run {
+                store.expired(&config.backup_retention)?
+            } else {
+                store.prune(&config.backup_retention)?
+            };
+            // Snapshots share their notes, so only a real prune knows the space freed
+            let freed = before.saturating_sub(note_stats::dir_size(&config.backup_dir()));
+            if wants_json(args) {
+                return print_json(&json!({ "dry_run": dry_run, "pruned": expired, "freed": freed }));
+            }
+            for backup in &expired {
+                outln!("{}\t{}", if dry_run { "would delete" } else { "deleted" }, backup.id)?;
+            }
+            if dry_run {
+                let kept = store.list()?.len() - expired.len();
+                outln!("Would prune {} backups; {} left", expired.len(), kept)?;
+            } else {
+                let kept = store.list()?.len();
+                outln!("Pruned {} backups, freeing {}; {} left", expired.len(), note_stats::format_size(freed), kept)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn restore(args: &ArgMatches) -> Result<()> {
+    let id = args.get_one::<String>("backup-id").expect("backup-id is required");
+    let (config, mut notes_manager) = load_notes().await?;
+    let store = BackupStore::new(&config);
+    let backup = store.find(id)?;
+    let notes = backup.verify()?;
+
+    if args.get_flag("dry-run") {
+        let current: std::collections::HashSet<&str> = notes_manager.notes.iter().map(|n| n.id.as_str()).collect();
+        let kept = notes.iter().filter(|note| current.contains(note.id.as_str())).count();
+        if wants_json(args) {
+            return print_json(&json!({
+                "backup": backup.id,
+                "notes": notes.len(),
+                "current": current.len(),
+                "kept": kept,
+                "removed": current.len() - kept,
+                "added": notes.len() - kept,
+            }));
+        }
+        outln!("Backup {} verified: {} notes", backup.id, notes.len())?;
+        outln!(
+            "Restoring would replace {} current notes ({} kept, {} removed, {} brought back)",
+            current.len(),
+            kept,
+            current.len() - kept,
+            notes.len() - kept
+        )?;
+        return Ok(());
+    }
+
+    // Through the notes manager, so a running instance takes the notes in
+    let safety = store.create_safety()?;
+    let restored: std::collections::HashSet<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+    let removed: Vec<String> = notes_manager
+        .notes
+        .iter()
+        .filter(|note| !restored.contains(note.id.as_str()))
+        .map(|note| note.id.clone())
+        .collect();
+    let count = notes.len();
+    notes_manager.merge_notes(notes, &removed).await?;
+    if wants_json(args) {
+        let safety = safety.map(|safety| safety.id);
+        return print_json(&json!({ "backup": backup.id, "restored": count, "safety_backup": safety }));
+    }
+    if let Some(safety) = safety {
+        outln!("Saved current notes as backup {}", safety.id)?;
+    }
+    outln!("Restored {} notes from backup {}", count, backup.id)?;
+    Ok(())
+}
+
+async fn share(args: &ArgMatches) -> Result<()> {
+    let (config, notes_manager) = load_notes().await?;
+    let server = remote::share_server(&config)?;
+    match args.subcommand() {
+        Some(("list", args)) => {
+            let shares = server.shares()?;
+            if wants_json(args) {
+                let shares: Vec<_> = shares
+                    .iter()
+                    .map(|share| json!({ "share": share, "link": server.link(share) }))
+                    .collect();
+                return print_json(&shares);
+            }
+            if shares.is_empty() {
+                outln!("No shares")?;
+            }
+            for share in &shares {
+                outln!("{}  {}", share.id, share.title)?;
+                outln!("  {}", server.link(share))?;
+                outln!("  expires {}", config.format_timestamp(share.expires))?;
+            }
+        }
+        Some(("revoke", args)) => {
+            let id = args.get_one::<String>("share").expect("share is required");
+            if !server.revoke(id)? {
+                return Err(UserError::NotFound(format!("No share {}", id)).into());
+            }
+            outln!("Revoked share {}", id)?;
+        }
+        _ => {
+            let prefix = args.get_one::<String>("id").expect("id is required");
+            let note = &notes_manager.notes[notes_manager.resolve_id(prefix)?];
+            let text = args.get_one::<String>("expires").expect("expires has a default");
+            let length = reminders::parse_duration(text).ok_or_else(|| {
+                UserError::Invalid(format!("Invalid --expires: {} (expected e.g. 1h, 7d or 4w)", text))
+            })?;
+            let share = server.share(&NewShare {
+                title: note.preview(60).replace('\n', " "),
+                html: html::render_shared(note, &config),
+                expires: Utc::now() + length,
+            })?;
+            if wants_json(args) {
+                return print_json(&json!({ "share": share, "link": server.link(&share) }));
+            }
+            outln!("{}", server.link(&share))?;
+            outln!("Expires {}; revoke with 'turbo-notes share revoke {}'", config.format_timestamp(share.expires), share.id)?;
+        }
+    }
+    Ok(())
+}
+
+async fn sync(args: &ArgMatches) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let mut state = SyncState::load(&config)?;
+    let note_id = |args: &ArgMatches| -> Result<String> {
+        let prefix = args.get_one::<String>("id").expect("id is required");
+        // Notes deleted here can still have a conflict or a history, so
+        // an id that matches nothing is used as given
+        match notes_manager.resolve_id(prefix) {
+            Ok(index) => Ok(notes_manager.notes[index].id.clone()),
+            Err(NoteLookupError::NotFound(_)) => Ok(prefix.clone()),
+            Err(e) => Err(e.into()),
+        }
+    };
+
+    match args.subcommand() {
+        Some(("status", args)) => {
+            let (changed, deleted) = state.pending(&notes_manager.notes);
+            let conflicts: Vec<&str> = state.conflicts.iter().map(|conflict| conflict.id.as_str()).collect();
+            if wants_json(args) {
+                let changed: Vec<&str> = changed.iter().map(|note| note.id.as_str()).collect();
+                return print_json(&json!({
+                    "backend": config.sync.backend,
+                    "last_sync": state.last_sync,
+                    "changed": changed,
+                    "deleted": deleted,
+                    "conflicts": conflicts,
+                    "failure": state.failure,
+                }));
+            }
+            let last = state
+                .last_sync
+                .map(|time| config.format_timestamp(time))
+                .unwrap_or_else(|| "never".to_string());
+            outln!("Last sync: {}", last)?;
+            if let Some(failure) = &state.failure {
+                outln!(
+                    "Last attempt failed {} ({} in a row): {}",
+                    config.format_timestamp(failure.at),
+                    failure.attempts,
+                    failure.message
+                )?;
+            }
+            outln!("Local changes: {} changed, {} deleted", changed.len(), deleted.len())?;
+            // The queue uploaded by the next sync
+            const SHOWN: usize = 10;
+            let queued = changed
+                .iter()
+                .map(|note| format!("  {}  {}", note.id, note.preview(40).replace('\n', " ")))
+                .chain(deleted.iter().map(|id| format!("  {}  (deleted)", id)));
+            let total = changed.len() + deleted.len();
+            for line in queued.take(SHOWN) {
+                outln!("{}", line)?;
+            }
+            if total > SHOWN {
+                outln!("  ... and {} more", total - SHOWN)?;
+            }
+            for id in &conflicts {
+                outln!("Conflict: {}", id)?;
+            }
+        }
+        Some(("resolve", args)) if !args.contains_id("id") => {
+            if state.conflicts.is_empty() {
+                outln!("No sync conflicts")?;
+                return Ok(());
+            }
+            if !io::stdout().is_terminal() {
+                return Err(UserError::Invalid("Give a note id and --keep, or run in a terminal".into()).into());
+            }
+            let mut app = App::new(false).await?;
+            app.open_conflicts()?;
+            app.run().await?;
+        }
+        Some(("resolve", args)) => {
+            let id = note_id(args)?;
+            let resolution = match args.get_one::<String>("keep").map(String::as_str) {
+                Some("local") => Resolution::Local,
+                Some("remote") => Resolution::Remote,
+                _ => Resolution::Both,
+            };
+            let copy = note_sync::resolve(&config, &mut notes_manager, &mut state, &id, resolution).await?;
+            if wants_json(args) {
+                return print_json(&json!({ "resolved": id, "copy": copy }));
+            }
+            outln!("Resolved conflict on {}; run 'turbo-notes sync' to upload it", id)?;
+            if let Some(copy) = copy {
+                outln!("Kept the local version as note {}", copy)?;
+            }
+        }
+        Some(("key", args)) => sync_key(args, &config, &mut state)?,
+        Some(("history", args)) => {
+            let id = note_id(args)?;
+            let backend = note_sync::open_backend(&config)?;
+            if let Some(version) = args.get_one::<String>("version") {
+                let note = note_sync::fetch_version(&backend, &id, version)?;
+                notes_manager.merge_notes(vec![note], &[]).await?;
+                if wants_json(args) {
+                    return print_json(&json!({ "restored": id, "version": version }));
+                }
+                outln!("Restored version {} of {}; it is uploaded on the next sync", version, id)?;
+                return Ok(());
+            }
+            let versions = note_sync::history(&backend, &id)?;
+            if wants_json(args) {
+                return print_json(&versions);
+            }
+            if versions.is_empty() {
+                outln!("No stored versions; enable versioning on the bucket to keep history")?;
+            }
+            for version in versions {
+                let modified = version
+                    .modified
+                    .map(|time| config.format_timestamp(time))
+                    .unwrap_or_else(|| "-".to_string());
+                let mark = match (version.latest, version.deleted) {
+                    (_, true) => "deleted",
+                    (true, false) => "current",
+                    _ => "",
+                };
+                outln!("{}\t{}\t{}", version.version, modified, mark)?;
+            }
+        }
+        _ if args.get_flag("watch") => watch_sync(args, &config).await?,
+        _ => {
+            // Network trouble often clears up in a few seconds; longer
+            // outages are left to the next sync or `sync --watch`
+            const SYNC_ATTEMPTS: u32 = 3;
+            const SYNC_RETRY_LIMIT: Duration = Duration::from_secs(30);
+            let mut backend = None;
+            for attempt in 1.. {
+                let result = match backend.as_ref() {
+                    Some(backend) => note_sync::sync(&config, &mut notes_manager, backend, &mut state).await,
+                    None => match note_sync::open_backend(&config) {
+                        Ok(opened) => note_sync::sync(&config, &mut notes_manager, backend.insert(Arc::new(opened)), &mut state).await,
+                        Err(e) => Err(e),
+                    },
+                };
+                let e = match result {
+                    Ok(report) => return print_sync_report(args, &report),
+                    Err(e) => e,
+                };
+                // Keeps the header indicator and 'sync status' up to date
+                state.record_failure(&e);
+                state.save(&config)?;
+                // A wrong passphrase or setting does not fix itself
+                if attempt == SYNC_ATTEMPTS || e.downcast_ref::<UserError>().is_some() {
+                    return Err(e);
+                }
+                let delay = state.failure.as_ref().map_or(SYNC_RETRY_LIMIT, |failure| failure.backoff().min(SYNC_RETRY_LIMIT));
+                eprintln!("Sync failed: {:#}; retrying in {} seconds", e, delay.as_secs());
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn sync_key(args: &ArgMatches, config: &Config, state: &mut SyncState) -> Result<()> {
+    match args.subcommand() {
+        Some(("status", args)) => {
+            let status = note_sync::key_status(config)?;
+            if wants_json(args) {
+                return print_json(&status);
+            }
+            if !status.initialized {
+                outln!("No key yet: the first sync or 'sync key init' sets one up")?;
+                return Ok(());
+            }
+            outln!("Key derivation: {}", status.kdf.unwrap_or_default())?;
+            let created = status.created.map(|time| config.format_timestamp(time));
+            outln!("Data key: {}", created.map(|time| format!("made {}", time)).unwrap_or_else(|| "-".to_string()))?;
+        }
+        Some(("init", args)) => {
+            note_sync::init_key(config)?;
+            if wants_json(args) {
+                return print_json(&json!({ "initialized": true }));
+            }
+            outln!("Sync key set up; use the same passphrase on your other devices")?;
+        }
+        Some(("passphrase", args)) => {
+            let mut backend = note_sync::open_backend(config)?;
+            let passphrase = quick::read_secret(note_sync::NEW_PASSPHRASE_VAR, "New sync passphrase", true)?;
+            backend.change_passphrase(&passphrase)?;
+            if wants_json(args) {
+                return print_json(&json!({ "changed": true }));
+            }
+            outln!("Sync passphrase changed; other devices will ask for the new one")?;
+        }
+        Some(("rotate", args)) => {
+            let mut backend = note_sync::open_backend(config)?;
+            let count = note_sync::rotate_key(config, &mut backend, state)?;
+            if wants_json(args) {
+                return print_json(&json!({ "rotated": count }));
+            }
+            outln!("Re-encrypted {} notes with a new key", count)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn print_sync_report(args: &ArgMatches, report: &SyncReport) -> Result<()> {
+    if wants_json(args) {
+        return print_json(report);
+    }
+    outln!(
+        "Uploaded {}, downloaded {}, deleted {} remote and {} local notes",
+        report.uploaded, report.downloaded, report.deleted_remote, report.deleted_local
+    )?;
+    if !report.conflicts.is_empty() {
+        outln!("{} conflicts, settle them with 'turbo-notes sync resolve':", report.conflicts.len())?;
+        for id in &report.conflicts {
+            outln!("  {}", id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Syncs, then again whenever the notes file changes, the sync server
+/// announces a change from another device, or `REMOTE_POLL` has passed
+/// (the only trigger for stores without a change feed). A failed sync is
+/// retried with growing delays until the store is reachable again. Runs
+/// until Ctrl+C. Changes go through the running instance, if there is one,
+/// like other commands'.
+async fn watch_sync(args: &ArgMatches, config: &Config) -> Result<()> {
+    const REMOTE_POLL: Duration = Duration::from_secs(300);
+    let notes_file = config.notes_dir.join("notes.json");
+    let modified = || fs::metadata(&notes_file).and_then(|meta| meta.modified()).ok();
+    let mut backend = None;
+    let mut feed: Option<ChangeFeed> = None;
+
+    loop {
+        let mut notes_manager = ipc::open_notes(config).await?;
+        let mut state = SyncState::load(config)?;
+        let result = match backend.as_ref() {
+            Some(backend) => note_sync::sync(config, &mut notes_manager, backend, &mut state).await,
+            None => match note_sync::open_backend(config) {
+                Ok(opened) => note_sync::sync(config, &mut notes_manager, backend.insert(Arc::new(opened)), &mut state).await,
+                // A wrong passphrase or setting does not fix itself
+                Err(e) if e.downcast_ref::<UserError>().is_some() => return Err(e),
+                Err(e) => Err(e),
+            },
+        };
+        // Keep watching through network trouble; the next round retries
+        let retry = match result {
+            Ok(report) => {
+                let changed = report.uploaded + report.downloaded + report.deleted_remote + report.deleted_local;
+                if changed > 0 || !report.conflicts.is_empty() {
+                    print_sync_report(args, &report)?;
+                }
+                None
+            }
+            Err(e) => {
+                state.record_failure(&e);
+                state.save(config)?;
+                let delay = state.failure.as_ref().map(|failure| failure.backoff()).unwrap_or(REMOTE_POLL);
+                eprintln!("Sync failed: {:#}; retrying in {} seconds", e, delay.as_secs());
+                Some(delay)
+            }
+        };
+        if retry.is_none() && feed.is_none() && config.sync.backend == SyncBackendKind::Server {
+            match ChangeFeed::connect(&config.sync.server).await {
+                Ok(connected) => feed = Some(connected),
+                Err(e) => eprintln!("{:#}; checking every {} minutes instead", e, REMOTE_POLL.as_secs() / 60),
+            }
+        }
+
+        let last_seen = modified();
+        let started = Instant::now();
+        let mut poll = tokio::time::interval(Duration::from_secs(2));
+        loop {
+            let next_change = async {
+                match feed.as_mut() {
+                    Some(feed) => feed.next().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+                change = next_change => {
+                    if change.is_none() {
+                        eprintln!("Lost the connection to the sync server's change feed");
+                        feed = None;
+                    }
+                    break;
+                }
+                _ = poll.tick() => {
+                    let waited = started.elapsed();
+                    if modified() != last_seen || waited >= REMOTE_POLL || retry.is_some_and(|delay| waited >= delay) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn doctor(args: &ArgMatches) -> Result<()> {
+    let checks = doctor::diagnose();
+    let failed = checks.iter().filter(|check| check.status == CheckStatus::Failed).count();
+    let warnings = checks.iter().filter(|check| check.status == CheckStatus::Warning).count();
+
+    if wants_json(args) {
+        print_json(&checks)?;
+    } else {
+        for check in &checks {
+            outln!("[{:<4}] {}: {}", check.status.label(), check.name, check.message)?;
+            if let Some(fix) = &check.fix {
+                outln!("       fix: {}", fix)?;
+            }
+        }
+        outln!()?;
+        outln!("{} checks, {} failed, {} warnings", checks.len(), failed, warnings)?;
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} checks failed", failed));
+    }
+    Ok(())
+}
+
+async fn conflict_copies(args: &ArgMatches) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let Some((action, args)) = args.subcommand() else {
+        let mut listed = Vec::new();
+        for path in copies::find(&config.notes_dir) {
+            let copy = ConflictCopy::load(&path)?;
+            let (added, changed) = copy.compare(&notes_manager.notes);
+            listed.push((copy.file_name(), copy.modified, added.len(), changed.len()));
+        }
+        if wants_json(args) {
+            let listed: Vec<_> = listed
+                .iter()
+                .map(|(file, modified, added, changed)| {
+                    json!({ "file": file, "modified": modified, "added": added, "changed": changed })
+                })
+                .collect();
+            return print_json(&listed);
+        }
+        if listed.is_empty() {
+            outln!("No conflicted copies of the notes file")?;
+        }
+        for (file, modified, added, changed) in listed {
+            let modified = modified.map(|time| config.format_timestamp(time)).unwrap_or_else(|| "-".to_string());
+            outln!("{}\t{}\t{} new, {} edited differently", file, modified, added, changed)?;
+        }
+        return Ok(());
+    };
+
+    let file = args.get_one::<String>("file").expect("file is required");
+    let copy = ConflictCopy::load(&copies::lookup(&config.notes_dir, file)?)?;
+    let name = copy.file_name();
+    let (message, count) = match action {
+        "merge" => {
+            let (_, changed) = copy.compare(&notes_manager.notes);
+            let changed: Vec<Note> = changed.into_iter().cloned().collect();
+            let resolution = match args.get_one::<String>("keep").map(String::as_str) {
+                Some("local") => Resolution::Local,
+                Some("copy") => Resolution::Remote,
+                Some(_) => Resolution::Both,
+                None if changed.is_empty() => Resolution::Local,
+                None => {
+                    return Err(UserError::Invalid(format!(
+                        "{} notes in {} were edited differently: pass --keep local|copy|both, or press c in the app to merge them side by side",
+                        changed.len(),
+                        name
+                    ))
+                    .into())
+                }
+            };
+            let added = copy.merge_added(&mut notes_manager).await?;
+            for theirs in &changed {
+                copies::resolve(&mut notes_manager, theirs, resolution.clone()).await?;
+            }
+            (format!("Added {} notes and settled {} edited differently from {}", added, changed.len(), name), added)
+        }
+        "import" => {
+            let imported = copy.import(&mut notes_manager).await?;
+            (format!("Imported {} notes from {}", imported, name), imported)
+        }
+        _ => (format!("Deleted {}", name), 0),
+    };
+    copy.remove()?;
+    if wants_json(args) {
+        return print_json(&json!({ "file": name, "action": action, "added": count }));
+    }
+    outln!("{}", message)?;
+    Ok(())
+}
+
+async fn compact(args: &ArgMatches) -> Result<()> {
+    // A running instance may hold bodies and memos it has not recorded in
+    // notes.json yet, which would look like leftovers here
+    if ipc::send(&Request::Ping).await?.is_some() {
+        return Err(UserError::Invalid("Quit Turbo Notes before compacting; it may be writing the files compact removes".into()).into());
+    }
+    let (config, mut notes_manager) = load_notes().await?;
+    let before = note_stats::dir_size(&config.notes_dir);
+
+    if args.get_flag("dry-run") {
+        let (empty, kept): (Vec<Note>, Vec<Note>) = notes_manager.notes.iter().cloned().partition(Note::is_empty);
+        let leftovers = leftover_files(&config, &kept);
+        if wants_json(args) {
+            return print_json(&json!({ "empty_notes": empty.len(), "leftover_files": leftovers.len() }));
+        }
+        outln!("Would remove {} empty notes and {} leftover files", empty.len(), leftovers.len())?;
+        return Ok(());
+    }
+
+    let removed = notes_manager.compact().await?;
+    notes_manager.flush().await?;
+    let leftovers = leftover_files(&config, &notes_manager.notes);
+    for path in &leftovers {
+        fs::remove_file(path)?;
+    }
+    let after = note_stats::dir_size(&config.notes_dir);
+    let reclaimed = before.saturating_sub(after);
+
+    if wants_json(args) {
+        return print_json(&json!({
+            "empty_notes": removed,
+            "leftover_files": leftovers.len(),
+            "reclaimed_bytes": reclaimed,
+        }));
+    }
+    outln!(
+        "Removed {} empty notes and {} leftover files, reclaimed {}",
+        removed,
+        leftovers.len(),
+        note_stats::format_size(reclaimed)
+    )?;
+    Ok(())
+}
+
+/// Probe files a crashed `doctor` run can leave behind, content of large
+/// notes no longer large or there, and memos of notes not in `notes`.
+fn leftover_files(config: &Config, notes: &[Note]) -> Vec<PathBuf> {
+    let mut leftovers: Vec<_> = [config.notes_dir.clone(), config.backup_dir()]
+        .iter()
+        .map(|dir| dir.join(".turbo-notes-doctor"))
+        .filter(|path| path.exists())
+        .collect();
+    leftovers.extend(notes::stray_bodies(&config.notes_dir, notes));
+    leftovers.extend(notes::stray_attachments(&config.notes_dir, notes));
+    leftovers
+}
+
+/// Adds `count` made-up notes and times what gets slower with many notes.
+/// Meant for a profile of its own, e.g. `--profile bench`.
+pub async fn bench_populate(count: usize) -> Result<()> {
+    let (config, mut notes_manager) = load_notes().await?;
+    let started = Instant::now();
+    notes_manager.import_notes(bench::synthetic_notes(count, Utc::now())).await?;
+    outln!("Added {} notes in {:.2?}", count, started.elapsed())?;
+
+    let started = Instant::now();
+    let mut notes_manager = NotesManager::new(&config.notes_dir).await?;
+    notes_manager.sort_notes(config.sort_mode);
+    outln!("Loaded {} notes in {:.2?}", notes_manager.notes.len(), started.elapsed())?;
+
+    let query = Query::parse("deploy friday #work");
+    let started = Instant::now();
+    let found = notes_manager.search_notes_with(&query).len();
+    outln!("Searched in {:.2?}, {} found", started.elapsed(), found)?;
+
+    let started = Instant::now();
+    notes_manager.use_search_index();
+    let opened = started.elapsed();
+    let started = Instant::now();
+    let found = notes_manager.search_notes_with(&query).len();
+    outln!("Searched with the index in {:.2?} (opened in {:.2?}), {} found", started.elapsed(), opened, found)?;
+
+    let started = Instant::now();
+    let tags = notes_manager.tag_counts().len();
+    outln!("Counted {} tags in {:.2?}", tags, started.elapsed())?;
+    Ok(())
+}
+
+async fn reindex() -> Result<()> {
+    let (_, mut notes_manager) = load_notes().await?;
+    let count = notes_manager.reindex()?;
+    outln!("Indexed {} notes for search", count)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picked_lines_lead_back_to_their_note() {
+        for id in ["1718000000000", "4e8c1f0a9b2d4c6e8f0a1b2c3d4e5f60", "simplenote_note-1"] {
+            let note = Note { id: id.to_string(), tags: vec!["work".to_string()], ..Note::new("Plan [draft]".to_string()) };
+            assert_eq!(picked_id(&pick_line(&note)), Some(id));
+        }
+        assert_eq!(picked_id("Plan  [a b]"), None);
+        assert_eq!(picked_id("Plan  []"), None);
+        assert_eq!(picked_id("Plan"), None);
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::error::UserError;
+
+/// Programs that put text on the clipboard, tried in order, with the ones
+/// that read it back
+fn tools() -> Vec<(&'static [&'static str], &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![(&["pbcopy"], &["pbpaste"])];
+    }
+    if cfg!(windows) {
+        return vec![(&["clip"], &["powershell", "-NoProfile", "-Command", "Get-Clipboard"])];
+    }
+    let mut tools: Vec<(&[&str], &[&str])> = Vec::new();
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push((&["wl-copy"], &["wl-paste", "--no-newline"]));
+    }
+    tools.push((&["xclip", "-selection", "clipboard"], &["xclip", "-selection", "clipboard", "-o"]));
+    tools.push((&["xsel", "--clipboard", "--input"], &["xsel", "--clipboard", "--output"]));
+    tools
+}
+
+fn no_tool() -> anyhow::Error {
+    UserError::Invalid("No clipboard tool found: install wl-clipboard, xclip or xsel".into()).into()
+}
+
+/// Puts `text` on the clipboard.
+pub fn copy(text: &str) -> Result<()> {
+    for (command, _) in tools() {
+        // xclip and wl-copy stay behind to hold the text, so their output is
+        // not waited for
+        let mut child = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(anyhow!("Could not run {}: {}", command[0], e)),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        return Err(anyhow!("{} could not copy ({})", command[0], status));
+    }
+    Err(no_tool())
+}
+
+/// The text on the clipboard.
+pub fn paste() -> Result<String> {
+    for (_, command) in tools() {
+        let output = match Command::new(command[0]).args(&command[1..]).stdin(Stdio::null()).output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(anyhow!("Could not run {}: {}", command[0], e)),
+        };
+        if !output.status.success() {
+            return Err(anyhow!("{} could not read the clipboard ({})", command[0], output.status));
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        // Get-Clipboard ends the text with a line break it did not hold
+        return Ok(if cfg!(windows) { text.trim_end_matches(['\r', '\n']).to_string() } else { text.into_owned() });
+    }
+    Err(no_tool())
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::UserError;
+use crate::notes::{self, Note, NotesManager};
+use crate::sync::Resolution;
+
+/// A copy of the notes file that a file sync tool saved next to it when
+/// two devices changed it at once, e.g. Syncthing's
+/// `notes.sync-conflict-20261016-123456-ABCDEFG.json` or Dropbox's
+/// `notes (Jane's conflicted copy 2026-10-16).json`.
+#[derive(Debug, Clone)]
+pub struct ConflictCopy {
+    pub path: PathBuf,
+    pub modified: Option<DateTime<Utc>>,
+    pub notes: Vec<Note>,
+}
+
+/// Whether `name` is a conflicted copy of `notes.json`.
+pub fn is_conflict_copy(name: &str) -> bool {
+    let Some(stem) = name.strip_prefix("notes").and_then(|rest| rest.strip_suffix(".json")) else {
+        return false;
+    };
+    // Syncthing, then Dropbox and Nextcloud
+    stem.starts_with(".sync-conflict-") || (stem.starts_with(" (") && stem.contains("conflicted copy"))
+}
+
+/// Conflicted copies of the notes file in `notes_dir`, oldest name first.
+pub fn find(notes_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(notes_dir) else {
+        return Vec::new();
+    };
+    let mut copies: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(is_conflict_copy))
+        .map(|entry| entry.path())
+        .collect();
+    copies.sort();
+    copies
+}
+
+/// Finds the copy named `name` (a file name or path) in `notes_dir`.
+pub fn lookup(notes_dir: &Path, name: &str) -> Result<PathBuf> {
+    let file_name = Path::new(name).file_name().and_then(|name| name.to_str()).unwrap_or(name);
+    find(notes_dir)
+        .into_iter()
+        .find(|path| path.file_name().and_then(|name| name.to_str()) == Some(file_name))
+        .ok_or_else(|| UserError::NotFound(format!("No conflicted copy named {}", file_name)).into())
+}
+
+impl ConflictCopy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let notes = notes::read_notes(path)
+            .map_err(|e| UserError::Invalid(format!("{} is not a notes file: {}", path.display(), e)))?;
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok().map(DateTime::<Utc>::from);
+        Ok(Self {
+            path: path.to_path_buf(),
+            modified,
+            notes,
+        })
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// Notes only in the copy, and notes whose text or tags differ from
+    /// the note with the same id in `notes`. Notes missing from the copy
+    /// are not reported: they may as well be new here as deleted there.
+    pub fn compare(&self, notes: &[Note]) -> (Vec<&Note>, Vec<&Note>) {
+        let local: HashMap<&str, &Note> = notes.iter().map(|note| (note.id.as_str(), note)).collect();
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for note in &self.notes {
+            match local.get(note.id.as_str()) {
+                None => added.push(note),
+                Some(here) if here.content != note.content || here.tags != note.tags => changed.push(note),
+                Some(_) => {}
+            }
+        }
+        (added, changed)
+    }
+
+    /// Adds the notes only in the copy. Returns how many were added.
+    pub async fn merge_added(&self, notes_manager: &mut NotesManager) -> Result<usize> {
+        notes_manager.load_all()?;
+        let (added, _) = self.compare(&notes_manager.notes);
+        let added: Vec<Note> = added.into_iter().cloned().collect();
+        let count = added.len();
+        if count > 0 {
+            notes_manager.merge_notes(added, &[]).await?;
+        }
+        Ok(count)
+    }
+
+    /// Adds every note that is only in the copy or differs from ours;
+    /// differing ones become new notes, leaving ours untouched. Returns how
+    /// many were added.
+    pub async fn import(&self, notes_manager: &mut NotesManager) -> Result<usize> {
+        notes_manager.load_all()?;
+        let (added, changed) = self.compare(&notes_manager.notes);
+        let notes: Vec<Note> = added.into_iter().chain(changed).cloned().collect();
+        if notes.is_empty() {
+            return Ok(0);
+        }
+        notes_manager.import_notes(notes).await
+    }
+
+    /// Deletes the copy once its notes are dealt with.
+    pub fn remove(&self) -> Result<()> {
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Settles one note edited differently in the copy: `Local` keeps ours,
+/// `Remote` takes the copy's, `Both` adds the copy's as a new note and
+/// `Merged` saves the given note. Returns the id of a note added by `Both`.
+pub async fn resolve(notes_manager: &mut NotesManager, theirs: &Note, resolution: Resolution) -> Result<Option<String>> {
+    match resolution {
+        Resolution::Local => Ok(None),
+        Resolution::Remote => {
+            notes_manager.merge_notes(vec![theirs.clone()], &[]).await?;
+            Ok(None)
+        }
+        Resolution::Both => {
+            let mut copy = theirs.clone();
+            copy.id = Note::new(String::new()).id;
+            let id = copy.id.clone();
+            notes_manager.merge_notes(vec![copy], &[]).await?;
+            Ok(Some(id))
+        }
+        Resolution::Merged(mut note) => {
+            note.id = theirs.id.clone();
+            notes_manager.merge_notes(vec![*note], &[]).await?;
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::ipc::{self, Request};
+use crate::logging;
+use crate::profile;
+
+/// How long `--daemon` waits for the new instance to answer
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `turbo-notes.pid` in the runtime directory, next to the control socket;
+/// `turbo-notes-<profile>.pid` for a profile other than the default.
+pub fn pid_path() -> Result<PathBuf> {
+    Ok(ipc::runtime_dir()?.join(format!("turbo-notes{}.pid", profile::suffix())))
+}
+
+/// `daemon.log` in the cache directory, where a detached instance writes
+/// what it would print; `daemon-<profile>.log` for other profiles.
+pub fn log_path() -> Result<PathBuf> {
+    let dir = dirs::cache_dir().ok_or_else(|| anyhow::anyhow!("Could not find a cache directory for the log"))?;
+    Ok(dir.join("turbo-notes").join(format!("daemon{}.log", profile::suffix())))
+}
+
+/// Pid of the running background instance, from its PID file.
+pub fn running_pid() -> Option<u32> {
+    fs::read_to_string(pid_path().ok()?).ok()?.trim().parse().ok()
+}
+
+/// The PID file, written while the background instance runs and removed
+/// when dropped.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create() -> Result<Self> {
+        let path = pid_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, format!("{}\n", std::process::id()))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Left alone if another instance has written its own since
+        if running_pid() == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Signals that ask the background instance to stop: SIGTERM, SIGINT and
+/// SIGHUP, or Ctrl+C and closing the console, logging off or shutting down
+/// on Windows. Registered up front so none arriving mid-task is lost.
+pub struct Shutdown {
+    #[cfg(unix)]
+    term: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    int: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hup: tokio::signal::unix::Signal,
+    #[cfg(windows)]
+    ctrl_c: tokio::signal::windows::CtrlC,
+    #[cfg(windows)]
+    ctrl_close: tokio::signal::windows::CtrlClose,
+    #[cfg(windows)]
+    ctrl_logoff: tokio::signal::windows::CtrlLogoff,
+    #[cfg(windows)]
+    ctrl_shutdown: tokio::signal::windows::CtrlShutdown,
+}
+
+impl Shutdown {
+    #[cfg(unix)]
+    pub fn listen() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            term: signal(SignalKind::terminate())?,
+            int: signal(SignalKind::interrupt())?,
+            hup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    #[cfg(windows)]
+    pub fn listen() -> Result<Self> {
+        use tokio::signal::windows;
+        Ok(Self {
+            ctrl_c: windows::ctrl_c()?,
+            ctrl_close: windows::ctrl_close()?,
+            ctrl_logoff: windows::ctrl_logoff()?,
+            ctrl_shutdown: windows::ctrl_shutdown()?,
+        })
+    }
+
+    /// Waits for the next signal and returns its name.
+    #[cfg(unix)]
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.term.recv() => "SIGTERM",
+            _ = self.int.recv() => "SIGINT",
+            _ = self.hup.recv() => "SIGHUP",
+        }
+    }
+
+    /// Waits for the next signal and returns its name.
+    #[cfg(windows)]
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.ctrl_c.recv() => "Ctrl+C",
+            _ = self.ctrl_close.recv() => "CTRL_CLOSE",
+            _ = self.ctrl_logoff.recv() => "CTRL_LOGOFF",
+            _ = self.ctrl_shutdown.recv() => "CTRL_SHUTDOWN",
+        }
+    }
+}
+
+/// Starts `turbo-notes --startup` detached from the terminal, writing its
+/// output to the log, and waits until it answers on the control socket.
+/// Returns its pid.
+pub async fn spawn() -> Result<u32> {
+    let log = log_path()?;
+    if let Some(dir) = log.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let output = OpenOptions::new().create(true).append(true).open(&log)?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command.arg("--startup");
+    if let Some(name) = profile::active() {
+        command.args(["--profile", &name]);
+    }
+    if let Some(level) = logging::level() {
+        command.env(logging::LEVEL_VAR, level);
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(output.try_clone()?)
+        .stderr(output);
+    // Out of the terminal's process group, so closing it or Ctrl+C there
+    // does not reach the daemon
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+    let mut child = command.spawn()?;
+
+    let started = std::time::Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow::anyhow!("The daemon exited with {}; see {}", status, log.display()));
+        }
+        if ipc::send(&Request::Ping).await?.is_some() {
+            tracing::info!(pid = child.id(), log = %log.display(), "Started the background instance");
+            return Ok(child.id());
+        }
+        if started.elapsed() > STARTUP_TIMEOUT {
+            return Err(anyhow::anyhow!("The daemon did not start listening; see {}", log.display()));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
@@ -0,0 +1,355 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::autostart::{expected_run_value, is_autostart_enabled, registered_run_value, service_status};
+use crate::backup::{BackupStore, Integrity};
+use crate::config::Config;
+use crate::copies;
+use crate::notes::{self, Note};
+use crate::plugins::Plugins;
+use crate::profile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Skipped,
+    Warning,
+    Failed,
+}
+
+impl CheckStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Skipped => "skip",
+            CheckStatus::Warning => "warn",
+            CheckStatus::Failed => "FAIL",
+        }
+    }
+}
+
+/// Outcome of one diagnostic, with a suggested fix when it did not pass.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Ok, message: message.into(), fix: None }
+    }
+
+    fn skipped(name: &'static str, message: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Skipped, message: message.into(), fix: None }
+    }
+
+    fn problem(name: &'static str, status: CheckStatus, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status, message: message.into(), fix: Some(fix.into()) }
+    }
+}
+
+/// Runs every diagnostic. Never fails itself: problems are reported as checks.
+pub fn diagnose() -> Vec<Check> {
+    let mut checks = Vec::new();
+    let config = check_config(&mut checks);
+    let notes = check_notes_file(&config, &mut checks);
+
+    checks.push(match &notes {
+        Some(notes) => check_duplicate_ids(notes),
+        None => Check::skipped("duplicate ids", "notes file could not be read"),
+    });
+    checks.push(check_conflict_copies(&config));
+    checks.push(check_backups(&config));
+    checks.push(check_plugins(&config));
+    checks.push(match &notes {
+        Some(notes) => check_attachments(&config, notes),
+        None => Check::skipped("attachments", "notes file could not be read"),
+    });
+    checks.push(Check::skipped("search index", "searches read notes directly; there is no index"));
+    checks.push(check_autostart(&config));
+    if cfg!(windows) {
+        checks.push(check_run_value());
+    }
+
+    checks.push(check_writable("notes directory", &config.notes_dir));
+    if let Some(dir) = Config::config_file_path().ok().as_deref().and_then(Path::parent) {
+        checks.push(check_writable("config directory", dir));
+    }
+    if config.backup_dir().exists() {
+        checks.push(check_writable("backup directory", &config.backup_dir()));
+    }
+    checks
+}
+
+fn check_config(checks: &mut Vec<Check>) -> Config {
+    const NAME: &str = "config";
+    let path = match Config::config_file_path() {
+        Ok(path) => path,
+        Err(e) => {
+            checks.push(Check::problem(NAME, CheckStatus::Failed, e.to_string(), "Set $HOME (or %APPDATA% on Windows)"));
+            return Config::default();
+        }
+    };
+    if !path.exists() {
+        checks.push(Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!("{} does not exist; using defaults", path.display()),
+            "Run turbo-notes once or use 'turbo-notes config set' to create it",
+        ));
+        return Config::default();
+    }
+
+    let parsed = fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_str::<Config>(&content)?));
+    let config = match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            checks.push(Check::problem(
+                NAME,
+                CheckStatus::Failed,
+                format!("{} could not be parsed: {}", path.display(), e),
+                "Fix the JSON by hand, or delete the file to go back to defaults",
+            ));
+            return Config::default();
+        }
+    };
+
+    checks.push(match config.validate() {
+        Ok(()) => Check::ok(NAME, path.display().to_string()),
+        Err(e) => Check::problem(
+            NAME,
+            CheckStatus::Failed,
+            e.to_string(),
+            "Correct the setting with 'turbo-notes config set <key> <value>'",
+        ),
+    });
+    config
+}
+
+fn check_notes_file(config: &Config, checks: &mut Vec<Check>) -> Option<Vec<Note>> {
+    const NAME: &str = "notes file";
+    let path = config.notes_dir.join("notes.json");
+    if !path.exists() {
+        checks.push(Check::ok(NAME, "no notes yet"));
+        return Some(Vec::new());
+    }
+
+    match notes::read_notes(&path) {
+        Ok(notes) => {
+            checks.push(Check::ok(NAME, format!("{} notes in {}", notes.len(), path.display())));
+            Some(notes)
+        }
+        Err(e) => {
+            checks.push(Check::problem(
+                NAME,
+                CheckStatus::Failed,
+                format!("{} is corrupt: {}", path.display(), e),
+                "Restore a backup with 'turbo-notes backup list' and 'turbo-notes restore <id>'",
+            ));
+            None
+        }
+    }
+}
+
+fn check_duplicate_ids(notes: &[Note]) -> Check {
+    let mut seen = HashSet::new();
+    let duplicates: Vec<&str> = notes
+        .iter()
+        .filter(|note| !seen.insert(note.id.as_str()))
+        .map(|note| note.id.as_str())
+        .collect();
+
+    if duplicates.is_empty() {
+        Check::ok("duplicate ids", "all note ids are unique")
+    } else {
+        Check::problem(
+            "duplicate ids",
+            CheckStatus::Failed,
+            format!("{} duplicated: {}", duplicates.len(), duplicates.join(", ")),
+            "Remove the extra copies with 'turbo-notes delete <id>', which deletes the first match",
+        )
+    }
+}
+
+fn check_attachments(config: &Config, notes: &[Note]) -> Check {
+    const NAME: &str = "attachments";
+    let files: Vec<PathBuf> = notes
+        .iter()
+        .flat_map(|note| {
+            let dir = notes::attachments_dir(&config.notes_dir, &note.id);
+            let memos = note.memos.iter().map(|memo| memo.file.as_str());
+            memos.chain(note.images.iter().map(|image| image.file.as_str())).map(move |file| dir.join(file))
+        })
+        .collect();
+    if files.is_empty() {
+        return Check::ok(NAME, "no voice memos or images");
+    }
+    let missing: Vec<String> = files.iter().filter(|path| !path.is_file()).map(|path| path.display().to_string()).collect();
+    if missing.is_empty() {
+        return Check::ok(NAME, format!("{} voice memos and images, all there", files.len()));
+    }
+    Check::problem(
+        NAME,
+        CheckStatus::Warning,
+        format!("{} of {} voice memos and images missing: {}", missing.len(), files.len(), missing.join(", ")),
+        "Attachments are not synced: copy the attachments directory over from the device that added them",
+    )
+}
+
+fn check_conflict_copies(config: &Config) -> Check {
+    const NAME: &str = "conflicted copies";
+    let found = copies::find(&config.notes_dir);
+    if found.is_empty() {
+        return Check::ok(NAME, "none next to the notes file");
+    }
+    let names: Vec<String> = found
+        .iter()
+        .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .collect();
+    Check::problem(
+        NAME,
+        CheckStatus::Warning,
+        format!("{} left by a file sync tool: {}", names.len(), names.join(", ")),
+        "Review them with 'turbo-notes copies', or press c in the app",
+    )
+}
+
+fn check_backups(config: &Config) -> Check {
+    const NAME: &str = "backups";
+    let backups = match BackupStore::new(config).list() {
+        Ok(backups) => backups,
+        Err(e) => return Check::problem(NAME, CheckStatus::Warning, e.to_string(), "Check the backup directory's permissions"),
+    };
+    if backups.is_empty() {
+        return Check::ok(NAME, "none yet");
+    }
+    let damaged: Vec<&str> = backups
+        .iter()
+        .filter(|backup| matches!(backup.integrity(), Integrity::Damaged(_)))
+        .map(|backup| backup.id.as_str())
+        .collect();
+    if damaged.is_empty() {
+        return Check::ok(NAME, format!("{} backups, all intact", backups.len()));
+    }
+    Check::problem(
+        NAME,
+        CheckStatus::Warning,
+        format!("{} of {} damaged: {}", damaged.len(), backups.len(), damaged.join(", ")),
+        "See 'turbo-notes backup verify', and take a fresh one with 'turbo-notes backup now'",
+    )
+}
+
+fn check_plugins(config: &Config) -> Check {
+    const NAME: &str = "plugins";
+    let plugins = Plugins::load(config);
+    if let Some((dir, error)) = plugins.failed.first() {
+        let names: Vec<String> = plugins
+            .failed
+            .iter()
+            .filter_map(|(dir, _)| dir.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .collect();
+        return Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!("{} could not be loaded: {}; {}: {}", names.len(), names.join(", "), dir.display(), error),
+            match cfg!(feature = "plugins") {
+                true => "Fix the plugin's plugin.json or module, or remove its directory",
+                false => "Install a build that runs plugins: cargo install --path . --features plugins",
+            },
+        );
+    }
+    match plugins.loaded.len() {
+        0 => Check::ok(NAME, "none installed"),
+        count => {
+            let names: Vec<&str> = plugins.loaded.iter().map(|plugin| plugin.manifest.name.as_str()).collect();
+            Check::ok(NAME, format!("{} loaded: {}", count, names.join(", ")))
+        }
+    }
+}
+
+fn check_autostart(config: &Config) -> Check {
+    const NAME: &str = "autostart";
+    if profile::active().is_some() {
+        return Check::skipped(NAME, "auto-start runs the default profile");
+    }
+    let service = service_status().is_ok_and(|service| service.installed);
+    match is_autostart_enabled().map(|enabled| enabled || service) {
+        Ok(enabled) if enabled == config.auto_start => Check::ok(
+            NAME,
+            match (enabled, service) {
+                (true, true) => "registered as a service",
+                (true, false) => "registered",
+                (false, _) => "not registered",
+            },
+        ),
+        Ok(true) => Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            "registered with the system but auto_start is false in the config",
+            "Run 'turbo-notes config set auto_start false' to remove it, or 'turbo-notes config set auto_start true' to keep it",
+        ),
+        Ok(false) => Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            "auto_start is true in the config but nothing is registered",
+            "Run 'turbo-notes config set auto_start true' to register it again, or 'turbo-notes autostart --service'",
+        ),
+        Err(e) => Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!("could not read registration state: {}", e),
+            "Re-run 'turbo-notes --setup-autostart' or '--disable-autostart'",
+        ),
+    }
+}
+
+/// The Run value must start this executable: after a move or an upgrade to
+/// another folder it points at a file that may no longer be there.
+fn check_run_value() -> Check {
+    const NAME: &str = "startup command";
+    let expected = match expected_run_value() {
+        Ok(expected) => expected,
+        Err(e) => return Check::problem(NAME, CheckStatus::Warning, e.to_string(), "Run turbo-notes from its install folder"),
+    };
+    match registered_run_value() {
+        Ok(None) => Check::skipped(NAME, "no Run value registered"),
+        Ok(Some(value)) if value == expected => Check::ok(NAME, value),
+        Ok(Some(value)) => Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!("Run value is {} but this executable is {}", value, expected),
+            "Run 'turbo-notes --setup-autostart' to register this executable",
+        ),
+        Err(e) => Check::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!("could not read the Run value: {}", e),
+            "Re-run 'turbo-notes --setup-autostart'",
+        ),
+    }
+}
+
+fn check_writable(name: &'static str, dir: &Path) -> Check {
+    let probe = dir.join(".turbo-notes-doctor");
+    let result = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => Check::ok(name, format!("{} is writable", dir.display())),
+        Err(e) => Check::problem(
+            name,
+            CheckStatus::Failed,
+            format!("cannot write to {}: {}", dir.display(), e),
+            format!("Fix the permissions on {}", dir.display()),
+        ),
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// Text typed into a new note or the widget that was not saved yet, kept
+/// on disk so a crash or a closed terminal does not lose it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub content: String,
+    pub saved_at: DateTime<Utc>,
+}
+
+impl Draft {
+    pub fn new(content: String) -> Self {
+        Self {
+            content,
+            saved_at: Utc::now(),
+        }
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.content.split_whitespace().count()
+    }
+}
+
+/// `draft.json` in the notes directory, so each profile has its own.
+pub fn path(config: &Config) -> PathBuf {
+    config.notes_dir.join("draft.json")
+}
+
+/// The draft left behind by the last run, if it has any text.
+pub fn load(config: &Config) -> Option<Draft> {
+    let draft: Draft = serde_json::from_str(&fs::read_to_string(path(config)).ok()?).ok()?;
+    (!draft.content.trim().is_empty()).then_some(draft)
+}
+
+/// Writes `draft` over the last one, through a temporary file so a crash
+/// mid-write leaves the previous draft.
+pub fn save(config: &Config, draft: &Draft) -> Result<()> {
+    let path = path(config);
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_string(draft)?)?;
+    fs::rename(&partial, &path)?;
+    Ok(())
+}
+
+pub fn discard(config: &Config) -> Result<()> {
+    match fs::remove_file(path(config)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::Config;
+
+/// Editor command to use: `Config.editor`, then `$VISUAL`, then `$EDITOR`,
+/// then a platform default.
+pub fn resolve_editor(config: &Config) -> String {
+    config
+        .editor
+        .clone()
+        .filter(|editor| !editor.trim().is_empty())
+        .or_else(|| env::var("VISUAL").ok().filter(|editor| !editor.trim().is_empty()))
+        .or_else(|| env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()))
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Runs `editor` on `path` through the shell, like git does, so the command
+/// may carry arguments and quoted paths (`code --wait`,
+/// `"/opt/My Editor/bin/edit" -w`).
+#[cfg(unix)]
+fn command(editor: &str, path: &Path) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(format!("{} \"$@\"", editor)).arg(editor).arg(path);
+    command
+}
+
+#[cfg(not(unix))]
+fn command(editor: &str, path: &Path) -> Command {
+    let mut parts = editor.split_whitespace();
+    let mut command = Command::new(parts.next().unwrap_or("notepad"));
+    command.args(parts).arg(path);
+    command
+}
+
+/// Opens `text` in the external editor and returns the edited text. The
+/// caller is responsible for releasing the terminal first.
+pub fn edit_text(config: &Config, name: &str, text: &str) -> Result<String> {
+    let path = env::temp_dir().join(format!("turbo-notes-{}-{}.md", name, std::process::id()));
+    fs::write(&path, text)?;
+
+    let editor = resolve_editor(config);
+    let status = command(&editor, &path).status();
+
+    let result = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path).map_err(Into::into),
+        // What the shell exits with when the command does not exist
+        Ok(status) if cfg!(unix) && status.code() == Some(127) => {
+            Err(anyhow::anyhow!("Could not launch editor '{}': command not found", editor))
+        }
+        Ok(status) => Err(anyhow::anyhow!("Editor '{}' exited with {}", editor, status)),
+        Err(err) => Err(anyhow::anyhow!("Could not launch editor '{}': {}", editor, err)),
+    };
+    let _ = fs::remove_file(&path);
+
+    // Editors usually add a trailing newline the note never had
+    result.map(|edited| edited.trim_end_matches(['\n', '\r']).to_string())
+}
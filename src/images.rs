@@ -0,0 +1,68 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+#[cfg(feature = "ocr")]
+use crate::error::UserError;
+use crate::notes::{self, Image};
+
+/// Extensions of the files that can be attached to notes as images
+pub const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// Whether `name` is an image's, by its extension.
+pub fn is_image(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.iter().any(|image| ext.eq_ignore_ascii_case(image)))
+}
+
+/// Saves `data` as an image attached to the note `note_id` in `notes_dir`,
+/// under the last part of `name`. A different file already there under that
+/// name is kept, and the new one numbered. The text is left to `recognize`.
+pub fn store(notes_dir: &Path, note_id: &str, name: &str, data: &[u8]) -> Result<(Image, PathBuf)> {
+    let dir = notes::attachments_dir(notes_dir, note_id);
+    fs::create_dir_all(&dir)?;
+    let name = Path::new(name).file_name().and_then(|name| name.to_str()).unwrap_or("image");
+    let (stem, ext) = name.rsplit_once('.').map_or((name, String::new()), |(stem, ext)| (stem, format!(".{}", ext)));
+    let mut file = name.to_string();
+    let mut number = 1;
+    while fs::read(dir.join(&file)).is_ok_and(|existing| existing != data) {
+        number += 1;
+        file = format!("{}-{}{}", stem, number, ext);
+    }
+    let path = dir.join(&file);
+    fs::write(&path, data)?;
+    Ok((Image { file, text: String::new() }, path))
+}
+
+/// Whether images get read for text: in builds with the `ocr` feature, unless
+/// turned off with `ocr.enabled`.
+pub fn ocr_enabled(config: &Config) -> bool {
+    cfg!(feature = "ocr") && config.ocr.enabled
+}
+
+/// The text in the image at `path`, without the blank lines Tesseract puts
+/// between blocks.
+#[cfg(feature = "ocr")]
+pub fn recognize(config: &Config, path: &Path) -> Result<String> {
+    let language = config.ocr.language.trim();
+    let mut tesseract = leptess::LepTess::new(None, language).map_err(|_| {
+        UserError::Invalid(format!(
+            "Tesseract could not load the '{}' language data; install it or change ocr.language",
+            language
+        ))
+    })?;
+    tesseract
+        .set_image(path)
+        .map_err(|e| UserError::Invalid(format!("Could not read {} as an image: {}", path.display(), e)))?;
+    let text = tesseract.get_utf8_text()?;
+    Ok(text.lines().map(str::trim_end).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n"))
+}
+
+/// Builds without the `ocr` feature read no text.
+#[cfg(not(feature = "ocr"))]
+pub fn recognize(_config: &Config, _path: &Path) -> Result<String> {
+    Ok(String::new())
+}
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::archive::ArchiveImporter;
+use crate::error::UserError;
+use crate::joplin::JexImporter;
+use crate::keep::KeepImporter;
+use crate::notes::Note;
+use crate::notion::NotionImporter;
+use crate::simplenote::SimplenoteImporter;
+
+/// Notes read by an [`Importer`].
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub notes: Vec<Note>,
+    /// Attached files, which notes cannot hold; links to them stay in the text
+    pub skipped_files: usize,
+    /// Images that came with the notes, attached once the notes are added
+    pub images: Vec<ImportedFile>,
+}
+
+/// A file that came with an imported note.
+#[derive(Debug)]
+pub struct ImportedFile {
+    /// Id the note was read with
+    pub note_id: String,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// A file format `turbo-notes import` can read. New formats are added by
+/// implementing this and listing the importer in [`importers`].
+pub trait Importer {
+    /// Name accepted by `import --format`.
+    fn name(&self) -> &'static str;
+
+    /// Whether `input`, read from `path`, looks like this format. Used to
+    /// guess the format when none is given.
+    fn detect(&self, path: &Path, input: &[u8]) -> bool;
+
+    /// Parses `input`, calling `progress(done, total)` as items are read;
+    /// `total` is 0 when it is not known up front.
+    fn read(&self, input: &[u8], progress: &mut dyn FnMut(usize, usize)) -> Result<Imported>;
+}
+
+/// All importers, in the order formats are guessed. Markdown comes last
+/// since it accepts any text.
+pub fn importers() -> Vec<Box<dyn Importer>> {
+    vec![
+        Box::new(JsonImporter),
+        Box::new(ArchiveImporter),
+        Box::new(SimplenoteImporter),
+        Box::new(JexImporter),
+        Box::new(KeepImporter),
+        Box::new(NotionImporter),
+        Box::new(MarkdownImporter),
+    ]
+}
+
+/// The importer for `--format name`, or the first one that detects `input`.
+pub fn find_importer(name: Option<&str>, path: &Path, input: &[u8]) -> Box<dyn Importer> {
+    let mut importers = importers();
+    let index = match name {
+        Some(name) => importers.iter().position(|importer| importer.name() == name),
+        None => importers.iter().position(|importer| importer.detect(path, input)),
+    };
+    importers.swap_remove(index.unwrap_or(importers.len() - 1))
+}
+
+/// Whether `path` has extension `ext`, ignoring case.
+pub fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+/// Opens `input` as a zip archive.
+pub fn open_zip(input: &[u8]) -> Result<zip::ZipArchive<Cursor<&[u8]>>> {
+    zip::ZipArchive::new(Cursor::new(input))
+        .map_err(|e| UserError::Invalid(format!("Not a valid zip archive: {}", e)).into())
+}
+
+/// Decodes `input` as UTF-8 text.
+pub fn text(input: &[u8]) -> Result<&str> {
+    std::str::from_utf8(input).map_err(|_| UserError::Invalid("Input is not UTF-8 text".into()).into())
+}
+
+/// Turbo Notes' own JSON export.
+pub struct JsonImporter;
+
+impl Importer for JsonImporter {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn detect(&self, path: &Path, input: &[u8]) -> bool {
+        has_extension(path, "json") && input.trim_ascii_start().starts_with(b"[")
+    }
+
+    fn read(&self, input: &[u8], _progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        Ok(Imported {
+            notes: parse_json(text(input)?)?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Turbo Notes' own Markdown export, or any Markdown file as a single note.
+pub struct MarkdownImporter;
+
+impl Importer for MarkdownImporter {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn detect(&self, _path: &Path, _input: &[u8]) -> bool {
+        true
+    }
+
+    fn read(&self, input: &[u8], _progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        Ok(Imported {
+            notes: parse_markdown(text(input)?),
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses a JSON export (an array of notes).
+pub fn parse_json(content: &str) -> Result<Vec<Note>> {
+    serde_json::from_str(content)
+        .map_err(|e| UserError::Invalid(format!("Not a valid JSON export: {}", e)).into())
+}
+
+/// Parses a Markdown export produced by `ExportFormat::Markdown`. Files
+/// without `## Note (...)` sections become a single note.
+pub fn parse_markdown(content: &str) -> Vec<Note> {
+    if !content.lines().any(is_note_heading) {
+        let body = content.trim();
+        return if body.is_empty() {
+            Vec::new()
+        } else {
+            vec![Note::new(body.to_string())]
+        };
+    }
+
+    let mut notes = Vec::new();
+    let mut section: Option<Vec<&str>> = None;
+    for line in content.lines() {
+        if is_note_heading(line) {
+            if let Some(lines) = section.take() {
+                notes.extend(parse_section(&lines));
+            }
+            section = Some(Vec::new());
+        } else if let Some(lines) = section.as_mut() {
+            lines.push(line);
+        }
+    }
+    if let Some(lines) = section {
+        notes.extend(parse_section(&lines));
+    }
+    notes
+}
+
+fn is_note_heading(line: &str) -> bool {
+    line.starts_with("## Note (") && line.ends_with(')')
+}
+
+fn parse_section(lines: &[&str]) -> Option<Note> {
+    let mut lines = lines.to_vec();
+
+    // Drop the trailing separator and surrounding blank lines
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    if lines.last().is_some_and(|line| line.trim() == "---") {
+        lines.pop();
+    }
+    while lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+
+    let mut metadata = None;
+    if let Some(first) = lines.first() {
+        let first = first.trim();
+        if first.len() > 1 && first.starts_with('_') && first.ends_with('_') {
+            metadata = Some(first[1..first.len() - 1].to_string());
+            lines.remove(0);
+        }
+    }
+
+    let content = lines.join("\n").trim().to_string();
+    if content.is_empty() {
+        return None;
+    }
+
+    let mut note = Note::new(content);
+    if let Some(metadata) = metadata {
+        for chip in metadata.split(" · ") {
+            if let Some(tag) = chip.strip_prefix('#') {
+                note.tags.push(tag.to_string());
+            } else if let Some(notebook) = chip.strip_prefix('@') {
+                note.notebook = Some(notebook.to_string());
+            } else if chip == "pinned" {
+                note.pinned = true;
+            } else if chip == "locked" {
+                note.locked = true;
+            }
+        }
+    }
+    Some(note)
+}
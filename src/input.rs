@@ -0,0 +1,182 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::ops::{Deref, Range};
+
+/// Text being typed, with a cursor and the other end of a selection.
+/// Positions are byte offsets into the text, always on a char boundary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Input {
+    text: String,
+    cursor: usize,
+    /// Where a selection made with Shift started
+    anchor: Option<usize>,
+}
+
+impl Input {
+    /// Replaces the text, putting the cursor at its end.
+    pub fn set(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.len();
+        self.anchor = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// The selected text's range, if anything is selected.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        (anchor != self.cursor).then(|| anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    /// Types `c` at the cursor, over the selection if there is one.
+    pub fn insert(&mut self, c: char) {
+        self.delete_selection();
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Replaces `range`, which ends at or before the cursor, with `text`,
+    /// the cursor staying after what follows it.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let after = self.cursor - range.end;
+        self.text.replace_range(range.clone(), text);
+        self.cursor = range.start + text.len() + after;
+        self.anchor = None;
+    }
+
+    pub fn backspace(&mut self) {
+        if !self.delete_selection() {
+            let start = self.prev(self.cursor);
+            self.remove(start..self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if !self.delete_selection() {
+            let end = self.next(self.cursor);
+            self.remove(self.cursor..end);
+        }
+    }
+
+    /// Deletes the word before the cursor, as Ctrl+W does in a shell.
+    pub fn delete_word(&mut self) {
+        if !self.delete_selection() {
+            let start = self.word_start(self.cursor);
+            self.remove(start..self.cursor);
+        }
+    }
+
+    /// Handles an editing key: typing, deleting and moving the cursor,
+    /// with Shift extending the selection and Ctrl working on words.
+    /// Returns false for keys it has no use for, such as Enter or Esc.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            // AltGr comes as Ctrl+Alt on Windows, e.g. for @ on German keyboards
+            KeyCode::Char(c) if ctrl == alt => self.insert(c),
+            KeyCode::Char('w') if ctrl => self.delete_word(),
+            KeyCode::Char('a') if ctrl => {
+                self.anchor = Some(0);
+                self.cursor = self.text.len();
+            }
+            KeyCode::Backspace if ctrl || alt => self.delete_word(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left => {
+                let to = match self.selection() {
+                    Some(selected) if !shift => selected.start,
+                    _ if ctrl => self.word_start(self.cursor),
+                    _ => self.prev(self.cursor),
+                };
+                self.move_to(to, shift);
+            }
+            KeyCode::Right => {
+                let to = match self.selection() {
+                    Some(selected) if !shift => selected.end,
+                    _ if ctrl => self.word_end(self.cursor),
+                    _ => self.next(self.cursor),
+                };
+                self.move_to(to, shift);
+            }
+            KeyCode::Home if ctrl => self.move_to(0, shift),
+            KeyCode::Home => {
+                let to = self.text[..self.cursor].rfind('\n').map_or(0, |newline| newline + 1);
+                self.move_to(to, shift);
+            }
+            KeyCode::End if ctrl => self.move_to(self.text.len(), shift),
+            KeyCode::End => {
+                let to = self.text[self.cursor..].find('\n').map_or(self.text.len(), |newline| self.cursor + newline);
+                self.move_to(to, shift);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn move_to(&mut self, to: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = to;
+    }
+
+    /// Deletes the selection. Returns false if there was none.
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some(selected) => {
+                self.remove(selected);
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    fn remove(&mut self, range: Range<usize>) {
+        self.cursor = range.start;
+        self.anchor = None;
+        self.text.replace_range(range, "");
+    }
+
+    fn prev(&self, at: usize) -> usize {
+        self.text[..at].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next(&self, at: usize) -> usize {
+        self.text[at..].chars().next().map_or(at, |c| at + c.len_utf8())
+    }
+
+    fn word_start(&self, at: usize) -> usize {
+        let before = self.text[..at].trim_end();
+        before.rfind(char::is_whitespace).map_or(0, |space| space + 1)
+    }
+
+    fn word_end(&self, at: usize) -> usize {
+        let after = &self.text[at..];
+        let word = after.len() - after.trim_start().len();
+        after[word..].find(char::is_whitespace).map_or(self.text.len(), |space| at + word + space)
+    }
+}
+
+impl Deref for Input {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
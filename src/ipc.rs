@@ -0,0 +1,337 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::error::UserError;
+use crate::notes::{Note, NotesManager, Origin};
+use crate::profile;
+
+/// Longest request accepted, well above any notes file
+const MAX_REQUEST: u64 = 256 * 1024 * 1024;
+/// How long a client waits for the running instance to answer
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A command for the running instance, sent as one line of JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Checks that an instance is listening
+    Ping,
+    /// Brings up the main interface, on note `note` if given
+    Open {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    ShowWidget,
+    AddNote {
+        content: String,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        notebook: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        origin: Option<Origin>,
+    },
+    /// Changes made by a command to the notes: notes added or changed, put
+    /// in place whole, and ids of notes removed
+    ChangeNotes {
+        #[serde(default)]
+        upserts: Vec<Note>,
+        #[serde(default)]
+        removed: Vec<String>,
+    },
+    /// Appends a line to the note with id, unique id prefix or title
+    /// `target`; with `create`, a missing note is started with `target` as
+    /// its title
+    AppendNote {
+        target: String,
+        text: String,
+        #[serde(default)]
+        create: bool,
+    },
+}
+
+/// The running instance's answer, one line of JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reply {
+    pub ok: bool,
+    #[serde(default)]
+    pub message: String,
+    /// Id of the note added by `add-note`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The note as `add-note` stored it, or as `append-note` left it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<Note>,
+    /// The instance has no terminal to show anything in, and gave up the
+    /// socket so the caller can take over
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub released: bool,
+}
+
+impl Reply {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Self { ok: true, message: message.into(), id: None, note: None, released: false }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { ok: false, message: message.into(), id: None, note: None, released: false }
+    }
+}
+
+/// A request waiting for the running instance, with where its reply goes.
+pub type Pending = (Request, oneshot::Sender<Reply>);
+
+/// Accepts connections until dropped; the socket is removed then.
+pub struct Listener {
+    /// Requests received, in order
+    pub requests: mpsc::Receiver<Pending>,
+    #[cfg(unix)]
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.task.abort();
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Directory for the running instance's socket and PID file:
+/// `$XDG_RUNTIME_DIR/turbo-notes`, or under the data directory where there
+/// is no runtime directory (macOS, Windows).
+pub fn runtime_dir() -> Result<PathBuf> {
+    let dir = dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_else(|| anyhow::anyhow!("Could not find a directory for the control socket"))?;
+    Ok(dir.join("turbo-notes"))
+}
+
+/// `control.sock` in the runtime directory, `control-<profile>.sock` for a
+/// profile other than the default; only its owner may connect.
+#[cfg(unix)]
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join(format!("control{}.sock", profile::suffix())))
+}
+
+/// Named pipe of the current user's instance of the profile in use.
+#[cfg(windows)]
+pub fn pipe_name() -> String {
+    format!(
+        r"\\.\pipe\turbo-notes-{}{}",
+        std::env::var("USERNAME").unwrap_or_default(),
+        profile::suffix()
+    )
+}
+
+fn already_running() -> anyhow::Error {
+    UserError::Invalid("Turbo Notes is already running".into()).into()
+}
+
+/// Starts listening for requests. Fails if another instance is already
+/// listening.
+#[cfg(unix)]
+pub async fn listen() -> Result<Listener> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = socket_path()?;
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(already_running());
+        }
+        // Left behind by an instance that did not exit cleanly
+        std::fs::remove_file(&path)?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+    let (sender, requests) = mpsc::channel(16);
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, sender.clone()));
+        }
+    });
+    Ok(Listener { requests, path, task })
+}
+
+/// Starts listening for requests. Fails if another instance is already
+/// listening.
+#[cfg(windows)]
+pub async fn listen() -> Result<Listener> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = pipe_name();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&name)
+        .map_err(|_| already_running())?;
+    let (sender, requests) = mpsc::channel(16);
+    let task = tokio::spawn(async move {
+        loop {
+            if server.connect().await.is_err() {
+                return;
+            }
+            // The next client connects to a fresh instance of the pipe
+            let Ok(next) = ServerOptions::new().create(&name) else { return };
+            let client = std::mem::replace(&mut server, next);
+            tokio::spawn(serve(client, sender.clone()));
+        }
+    });
+    Ok(Listener { requests, task })
+}
+
+/// Next request to `listener`; never resolves while there is none.
+pub async fn next(listener: &mut Option<Listener>) -> Option<Pending> {
+    match listener {
+        Some(listener) => listener.requests.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Answers one connection: reads a request, waits for the instance to
+/// handle it and writes back its reply.
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(stream: S, requests: mpsc::Sender<Pending>) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut line = String::new();
+    if BufReader::new(reader.take(MAX_REQUEST)).read_line(&mut line).await.is_err() {
+        return;
+    }
+
+    let reply = match serde_json::from_str::<Request>(&line) {
+        Ok(request) => {
+            tracing::debug!(?request, "Request from another instance");
+            let (sender, receiver) = oneshot::channel();
+            if requests.send((request, sender)).await.is_err() {
+                Reply::error("Turbo Notes is shutting down")
+            } else {
+                receiver.await.unwrap_or_else(|_| Reply::error("The request was dropped"))
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Bad request from another instance: {}", e);
+            Reply::error(format!("Bad request: {}", e))
+        }
+    };
+    let Ok(mut json) = serde_json::to_string(&reply) else { return };
+    json.push('\n');
+    let _ = writer.write_all(json.as_bytes()).await;
+}
+
+/// Sends `request` to the running instance and returns its reply, or
+/// `None` if no instance is running.
+pub async fn send(request: &Request) -> Result<Option<Reply>> {
+    let Some(stream) = connect().await? else {
+        return Ok(None);
+    };
+    tokio::time::timeout(TIMEOUT, exchange(stream, request))
+        .await
+        .map_err(|_| anyhow::anyhow!("The running instance did not answer"))?
+        .map(Some)
+}
+
+/// The notes, for a command that changes them. The running instance holds
+/// the notes in memory and would write over changes made behind its back,
+/// so they are sent to it as they are saved, and written to the notes file
+/// only when none is running.
+pub async fn open_notes(config: &Config) -> Result<NotesManager> {
+    let mut notes_manager = NotesManager::new(&config.notes_dir).await?;
+    notes_manager.set_webhooks(config.webhooks.clone());
+    notes_manager.set_forward(forward);
+    Ok(notes_manager)
+}
+
+/// Sends changes to the running instance; see [`open_notes`].
+fn forward(upserts: Vec<Note>, removed: Vec<String>) -> Pin<Box<dyn Future<Output = Result<bool>> + Send>> {
+    Box::pin(async move {
+        match send(&Request::ChangeNotes { upserts, removed }).await? {
+            Some(reply) if reply.ok => Ok(true),
+            Some(reply) => Err(anyhow::anyhow!(reply.message)),
+            None => Ok(false),
+        }
+    })
+}
+
+/// Adds `note` through the running instance, like [`open_notes`], or
+/// straight to the notes file when none is running. Returns the note as
+/// stored, or with `capture.skip_duplicates` set, a note with the same text
+/// if there is one.
+pub async fn add_note(note: Note, config: &Config) -> Result<Note> {
+    let request = Request::AddNote {
+        content: note.content.clone(),
+        tags: note.tags.clone(),
+        notebook: note.notebook.clone(),
+        origin: note.origin.clone(),
+    };
+    match send(&request).await? {
+        // The instance may not have written it yet, so it comes back in the reply
+        Some(reply) if reply.ok => reply
+            .note
+            .ok_or_else(|| anyhow::anyhow!("The running instance did not send back the note it added")),
+        Some(reply) => Err(anyhow::anyhow!(reply.message)),
+        None => {
+            let mut notes_manager = NotesManager::new(&config.notes_dir).await?;
+            notes_manager.set_webhooks(config.webhooks.clone());
+            if !config.capture.skip_duplicates {
+                notes_manager.add_note(note).await?;
+                return Ok(notes_manager.notes[0].clone());
+            }
+            let index = notes_manager.add_note_unless_duplicate(note).await?.unwrap_or(0);
+            Ok(notes_manager.notes[index].clone())
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn connect() -> Result<Option<tokio::net::UnixStream>> {
+    let path = socket_path()?;
+    match tokio::net::UnixStream::connect(&path).await {
+        Ok(stream) => Ok(Some(stream)),
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Could not connect to {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(windows)]
+async fn connect() -> Result<Option<tokio::net::windows::named_pipe::NamedPipeClient>> {
+    // ERROR_PIPE_BUSY: every instance is taken until the server opens the next
+    const PIPE_BUSY: i32 = 231;
+    let mut attempts = 0;
+    loop {
+        match tokio::net::windows::named_pipe::ClientOptions::new().open(pipe_name()) {
+            Ok(client) => return Ok(Some(client)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) if e.raw_os_error() == Some(PIPE_BUSY) && attempts < 20 => {
+                attempts += 1;
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            Err(e) => return Err(anyhow::anyhow!("Could not connect to the running instance: {}", e)),
+        }
+    }
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(stream: S, request: &Request) -> Result<Reply> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut json = serde_json::to_string(request)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    if line.is_empty() {
+        return Err(anyhow::anyhow!("The running instance closed the connection"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
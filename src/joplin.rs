@@ -0,0 +1,271 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::error::UserError;
+use crate::import::{has_extension, Imported, Importer};
+use crate::notes::Note;
+
+// Joplin item types, from its `ModelType` enum
+const TYPE_NOTE: &str = "1";
+const TYPE_FOLDER: &str = "2";
+const TYPE_RESOURCE: &str = "4";
+const TYPE_TAG: &str = "5";
+const TYPE_NOTE_TAG: &str = "6";
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// Joplin's JEX export archive.
+pub struct JexImporter;
+
+impl Importer for JexImporter {
+    fn name(&self) -> &'static str {
+        "jex"
+    }
+
+    fn detect(&self, path: &Path, _input: &[u8]) -> bool {
+        has_extension(path, "jex")
+    }
+
+    fn read(&self, input: &[u8], progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        read_jex(input, progress)
+    }
+}
+
+/// One serialized Joplin item: a title line, an optional body and a
+/// trailing block of `key: value` properties.
+struct Item {
+    title: String,
+    body: String,
+    props: HashMap<String, String>,
+}
+
+impl Item {
+    fn prop(&self, key: &str) -> &str {
+        self.props.get(key).map(String::as_str).unwrap_or("")
+    }
+
+    fn time(&self, keys: &[&str]) -> Option<DateTime<Utc>> {
+        keys.iter()
+            .filter_map(|key| DateTime::parse_from_rfc3339(self.prop(key)).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .next()
+    }
+}
+
+/// Reads a Joplin export archive (a tar of serialized items). Notebooks
+/// become `notebook` paths like `Work/Projects`, and tags are attached via
+/// Joplin's note-tag links. Notes in Joplin's trash are left out.
+pub fn read_jex(reader: impl Read, progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+    let mut archive = tar::Archive::new(reader);
+    let mut items = Vec::new();
+    for (done, entry) in archive.entries()?.enumerate() {
+        // The entry count is unknown until the end of the archive
+        progress(done, 0);
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let is_item = path.components().count() == 1 && path.extension().is_some_and(|ext| ext == "md");
+        if !is_item {
+            continue;
+        }
+        let mut text = String::new();
+        entry.read_to_string(&mut text)?;
+        items.extend(parse_item(&text));
+    }
+    if items.is_empty() {
+        return Err(UserError::Invalid("Not a Joplin JEX archive: no items found".into()).into());
+    }
+
+    let by_type = |kind: &'static str| items.iter().filter(move |item| item.prop("type_") == kind);
+    let folders: HashMap<&str, &Item> = by_type(TYPE_FOLDER).map(|item| (item.prop("id"), item)).collect();
+    let tags: HashMap<&str, &str> = by_type(TYPE_TAG).map(|item| (item.prop("id"), item.title.as_str())).collect();
+    let mut note_tags: HashMap<&str, Vec<String>> = HashMap::new();
+    for link in by_type(TYPE_NOTE_TAG) {
+        if let Some(tag) = tags.get(link.prop("tag_id")) {
+            note_tags.entry(link.prop("note_id")).or_default().push(tag.replace(char::is_whitespace, "-"));
+        }
+    }
+
+    let mut import = Imported {
+        skipped_files: by_type(TYPE_RESOURCE).count(),
+        ..Default::default()
+    };
+    for item in by_type(TYPE_NOTE) {
+        if !matches!(item.prop("deleted_time"), "" | "0") {
+            continue;
+        }
+        let content = match (item.title.trim(), item.body.trim_end()) {
+            (title, "") => title.to_string(),
+            ("", body) => body.to_string(),
+            (title, body) => format!("{}\n\n{}", title, body),
+        };
+        let mut note = Note::new(content);
+        note.id = item.prop("id").to_string();
+        note.created_at = item.time(&["user_created_time", "created_time"]).unwrap_or(note.created_at);
+        note.notebook = folder_path(&folders, item.prop("parent_id"));
+        for tag in note_tags.remove(item.prop("id")).unwrap_or_default() {
+            note.add_tag(tag);
+        }
+        // After the tags, which bump the modification time
+        note.updated_at = item.time(&["user_updated_time", "updated_time"]).unwrap_or(note.created_at);
+        import.notes.push(note);
+    }
+    Ok(import)
+}
+
+/// Writes notes as a Joplin export archive. Notebook paths become nested
+/// folders; ids are mapped to Joplin's 32-digit hex form.
+pub fn write_jex(notes: &[&Note], writer: impl Write) -> Result<()> {
+    let mut builder = tar::Builder::new(writer);
+    let now = Utc::now();
+
+    let mut folders: HashMap<String, String> = HashMap::new();
+    let mut tags: HashMap<&str, String> = HashMap::new();
+    for note in notes {
+        let mut parent = String::new();
+        let mut path = String::new();
+        for part in note.notebook.iter().flat_map(|notebook| notebook.split('/')) {
+            path = if path.is_empty() { part.to_string() } else { format!("{}/{}", path, part) };
+            if !folders.contains_key(&path) {
+                let id = joplin_id(&format!("folder:{}", path));
+                let props = [("id", id.clone()), ("parent_id", parent.clone()), ("type_", TYPE_FOLDER.to_string())];
+                append(&mut builder, &id, &serialize(part, "", &props, now, now))?;
+                folders.insert(path.clone(), id);
+            }
+            parent = folders[&path].clone();
+        }
+
+        let id = joplin_id(&note.id);
+        let (title, body) = match note.content.split_once('\n') {
+            Some((title, body)) => (title.trim_start_matches('#').trim(), body.trim_start_matches('\n')),
+            None => (note.content.as_str(), ""),
+        };
+        let props = [
+            ("id", id.clone()),
+            ("parent_id", parent),
+            ("is_todo", "0".to_string()),
+            ("markup_language", "1".to_string()),
+            ("source_application", "turbo-notes".to_string()),
+            ("type_", TYPE_NOTE.to_string()),
+        ];
+        append(&mut builder, &id, &serialize(title, body, &props, note.created_at, note.updated_at))?;
+
+        for tag in &note.tags {
+            if !tags.contains_key(tag.as_str()) {
+                let tag_id = joplin_id(&format!("tag:{}", tag));
+                let props = [("id", tag_id.clone()), ("type_", TYPE_TAG.to_string())];
+                append(&mut builder, &tag_id, &serialize(tag, "", &props, now, now))?;
+                tags.insert(tag, tag_id);
+            }
+            let link_id = joplin_id(&format!("note_tag:{}:{}", note.id, tag));
+            let props = [
+                ("id", link_id.clone()),
+                ("note_id", id.clone()),
+                ("tag_id", tags[tag.as_str()].clone()),
+                ("type_", TYPE_NOTE_TAG.to_string()),
+            ];
+            append(&mut builder, &link_id, &serialize("", "", &props, now, now))?;
+        }
+    }
+
+    builder.finish()?;
+    Ok(())
+}
+
+fn parse_item(text: &str) -> Option<Item> {
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    let mut props = HashMap::new();
+    let mut end = lines.len();
+    while end > 0 {
+        let Some((key, value)) = lines[end - 1].split_once(": ").or_else(|| {
+            // Empty values are written as "key: " and lose the space on trim
+            lines[end - 1].strip_suffix(':').map(|key| (key, ""))
+        }) else {
+            break;
+        };
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+            break;
+        }
+        props.insert(key.to_string(), value.to_string());
+        end -= 1;
+    }
+    if !props.contains_key("type_") {
+        return None;
+    }
+
+    let head = &lines[..end];
+    let title = head.first().copied().unwrap_or("").to_string();
+    let body = head.get(1..).unwrap_or(&[]).join("\n").trim_matches('\n').to_string();
+    Some(Item { title, body, props })
+}
+
+fn serialize(
+    title: &str,
+    body: &str,
+    props: &[(&str, String)],
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+) -> String {
+    let created = created.format(TIME_FORMAT).to_string();
+    let updated = updated.format(TIME_FORMAT).to_string();
+    let mut out = String::new();
+    if !title.is_empty() {
+        out.push_str(title);
+        out.push_str("\n\n");
+    }
+    if !body.is_empty() {
+        out.push_str(body);
+        out.push_str("\n\n");
+    }
+    for (key, value) in props.iter().filter(|(key, _)| *key != "type_") {
+        out.push_str(&format!("{}: {}\n", key, value));
+    }
+    for key in ["created_time", "updated_time", "user_created_time", "user_updated_time"] {
+        let value = if key.ends_with("created_time") { &created } else { &updated };
+        out.push_str(&format!("{}: {}\n", key, value));
+    }
+    // Joplin expects the type last
+    if let Some((_, kind)) = props.iter().find(|(key, _)| *key == "type_") {
+        out.push_str(&format!("type_: {}", kind));
+    }
+    out
+}
+
+fn append(builder: &mut tar::Builder<impl Write>, id: &str, content: &str) -> Result<()> {
+    let mut header = tar::Header::new_ustar();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, format!("{}.md", id), content.as_bytes())?;
+    Ok(())
+}
+
+fn folder_path(folders: &HashMap<&str, &Item>, id: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut current = id;
+    // Bounded walk in case of a parent cycle in a damaged export
+    while let Some(folder) = folders.get(current).filter(|_| parts.len() < 32) {
+        parts.push(folder.title.trim());
+        current = folder.prop("parent_id");
+    }
+    parts.reverse();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Joplin ids are 32 lowercase hex digits. Ids that already look like that
+/// are kept so notes round-trip; others are derived with a stable hash, so
+/// exporting the same notes twice gives the same ids.
+fn joplin_id(id: &str) -> String {
+    if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return id.to_ascii_lowercase();
+    }
+    // Two 64-bit FNV-1a hashes with different offset bases
+    let fnv = |basis: u64| {
+        id.bytes()
+            .fold(basis, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+    };
+    format!("{:016x}{:016x}", fnv(0xcbf2_9ce4_8422_2325), fnv(0x6c62_272e_07bb_0142))
+}
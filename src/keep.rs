@@ -0,0 +1,193 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::UserError;
+use crate::images;
+use crate::import::{has_extension, open_zip, text, Imported, ImportedFile, Importer};
+use crate::notes::Note;
+
+/// Google Keep's notes from Google Takeout: the Takeout zip, or a single
+/// note's JSON file from it. Checklists become Markdown task lists, labels
+/// become tags and archived notes get an `archived` tag; trashed notes are
+/// left out. Images come along as the notes' attached images.
+pub struct KeepImporter;
+
+impl Importer for KeepImporter {
+    fn name(&self) -> &'static str {
+        "keep"
+    }
+
+    fn detect(&self, path: &Path, input: &[u8]) -> bool {
+        if has_extension(path, "zip") {
+            return open_zip(input).is_ok_and(|zip| zip.file_names().any(is_keep_note));
+        }
+        has_extension(path, "json") && text(input).is_ok_and(|text| text.contains("\"userEditedTimestampUsec\""))
+    }
+
+    fn read(&self, input: &[u8], progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        if !input.starts_with(b"PK") {
+            let mut imported = Imported::default();
+            let note: KeepNote = serde_json::from_str(text(input)?)
+                .map_err(|e| UserError::Invalid(format!("Not a Google Keep note: {}", e)))?;
+            // The images are not in the file; a Takeout zip has them
+            if let Some((note, attachments)) = note.into_note(&mut HashSet::new()) {
+                imported.skipped_files = attachments.len();
+                imported.notes.push(note);
+            }
+            return Ok(imported);
+        }
+
+        let mut zip = open_zip(input)?;
+        let total = zip.len();
+        let mut notes = Vec::new();
+        let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+        for index in 0..total {
+            progress(index, total);
+            let mut file = zip.by_index(index)?;
+            let name = file.name().to_string();
+            if !name.contains("Keep/") || file.is_dir() {
+                continue;
+            }
+            let base = name.rsplit('/').next().unwrap_or_default().to_string();
+            if is_keep_note(&name) {
+                let mut json = String::new();
+                file.read_to_string(&mut json)?;
+                let note: KeepNote = serde_json::from_str(&json)
+                    .map_err(|e| UserError::Invalid(format!("Could not read {}: {}", name, e)))?;
+                notes.push(note);
+            } else if images::is_image(&base) {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                files.insert(base, data);
+            }
+        }
+        if notes.is_empty() {
+            return Err(UserError::Invalid("Not a Google Takeout export: no Keep notes found".into()).into());
+        }
+
+        let mut imported = Imported::default();
+        let mut ids = HashSet::new();
+        for note in notes {
+            let Some((note, attachments)) = note.into_note(&mut ids) else {
+                continue;
+            };
+            for path in attachments {
+                match find_file(&mut files, &path) {
+                    Some((name, data)) => imported.images.push(ImportedFile { note_id: note.id.clone(), name, data }),
+                    None => imported.skipped_files += 1,
+                }
+            }
+            imported.notes.push(note);
+        }
+        Ok(imported)
+    }
+}
+
+/// A note's JSON file in Takeout, which sits next to the images and an
+/// HTML copy of the note.
+fn is_keep_note(name: &str) -> bool {
+    name.contains("Keep/") && name.ends_with(".json") && !name.ends_with("/Labels.json")
+}
+
+/// The image at `path`, a name from the note. Takeout sometimes saves an
+/// image under another extension than the note gives, e.g. `.jpg` for
+/// `.jpeg`, so failing the name itself, one with the same stem is taken.
+fn find_file(files: &mut HashMap<String, Vec<u8>>, path: &str) -> Option<(String, Vec<u8>)> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if let Some(data) = files.remove(name) {
+        return Some((name.to_string(), data));
+    }
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let other = files.keys().find(|file| file.rsplit_once('.').is_some_and(|(file_stem, _)| file_stem == stem))?.clone();
+    files.remove_entry(&other)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepNote {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    list_content: Vec<ListItem>,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    is_archived: bool,
+    #[serde(default)]
+    is_trashed: bool,
+    #[serde(default)]
+    created_timestamp_usec: i64,
+    #[serde(default)]
+    user_edited_timestamp_usec: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListItem {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    is_checked: bool,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Attachment {
+    file_path: String,
+}
+
+impl KeepNote {
+    /// The note, unless it was trashed or is empty, with the names of its
+    /// attached files. Its id is made from when it was created, unique
+    /// among `ids`, so the files can be matched to it.
+    fn into_note(self, ids: &mut HashSet<String>) -> Option<(Note, Vec<String>)> {
+        let list: Vec<String> = self
+            .list_content
+            .iter()
+            .map(|item| format!("- [{}] {}", if item.is_checked { "x" } else { " " }, item.text.trim()))
+            .collect();
+        let body = if list.is_empty() { self.text_content.trim_end().to_string() } else { list.join("\n") };
+        let content = match (self.title.trim(), body.as_str()) {
+            (title, "") => title.to_string(),
+            ("", body) => body.to_string(),
+            (title, body) => format!("{}\n\n{}", title, body),
+        };
+        if self.is_trashed || (content.is_empty() && self.attachments.is_empty()) {
+            return None;
+        }
+
+        let mut note = Note::new(content);
+        note.created_at = DateTime::from_timestamp_micros(self.created_timestamp_usec).unwrap_or(note.created_at);
+        let mut id = note.created_at.timestamp_nanos_opt().unwrap_or(0);
+        while !ids.insert(id.to_string()) {
+            id += 1;
+        }
+        note.id = id.to_string();
+        note.pinned = self.is_pinned;
+        for label in &self.labels {
+            note.add_tag(label.name.trim().replace(char::is_whitespace, "-"));
+        }
+        if self.is_archived {
+            note.add_tag("archived".to_string());
+        }
+        // After the tags, which bump the modification time
+        note.updated_at = DateTime::<Utc>::from_timestamp_micros(self.user_edited_timestamp_usec).unwrap_or(note.created_at);
+        let attachments = self.attachments.into_iter().map(|attachment| attachment.file_path).collect();
+        Some((note, attachments))
+    }
+}
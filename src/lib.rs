@@ -1,14 +1,107 @@
+//! Turbo Notes as a library. The notes themselves live in
+//! [`turbo_notes_core`], re-exported here; the terminal UI and the
+//! background daemon come with the `tui` and `daemon` features, both on by
+//! default. Tools that only need the notes can depend on
+//! `turbo-notes-core`, or on this crate with `default-features = false`.
+
 #![allow(dead_code)]
 
+pub use turbo_notes_core::{
+    atom, backup, capture, config, crypto, dates, error, goals, graph, habits, heatmap, html, ics, links, meeting, merge, metadata, natural,
+    notes, outline, profile, query, reading, reminders, saver, search_index, shorthand, snippets, stats, suggest, tidy, webhooks,
+};
+
+pub mod logging;
+
+#[cfg(feature = "daemon")]
+pub mod autostart;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "daemon")]
+pub mod ipc;
+
+#[cfg(feature = "tui")]
 pub mod app;
-pub mod notes;
+#[cfg(feature = "tui")]
 pub mod ui;
-pub mod config;
-pub mod autostart;
+#[cfg(feature = "tui")]
+pub mod api;
+#[cfg(feature = "tui")]
+pub mod archive;
+#[cfg(feature = "tui")]
+pub mod bench;
+#[cfg(feature = "tui")]
+pub mod cli;
+#[cfg(feature = "tui")]
+pub mod clipboard;
+#[cfg(feature = "tui")]
+pub mod copies;
+#[cfg(feature = "tui")]
+pub mod doctor;
+#[cfg(feature = "tui")]
+pub mod draft;
+#[cfg(feature = "tui")]
+pub mod editor;
+#[cfg(feature = "tui")]
+pub mod images;
+#[cfg(feature = "tui")]
+pub mod import;
+#[cfg(feature = "tui")]
+pub mod input;
+#[cfg(feature = "tui")]
+pub mod joplin;
+#[cfg(feature = "tui")]
+pub mod keep;
+#[cfg(feature = "tui")]
+pub mod notion;
+#[cfg(feature = "tui")]
+pub mod onboarding;
+#[cfg(feature = "tui")]
+pub mod plugins;
+#[cfg(feature = "tui")]
+pub mod pomodoro;
+#[cfg(feature = "tui")]
+pub mod notify;
+#[cfg(feature = "tui")]
+pub mod qr;
+#[cfg(feature = "tui")]
+pub mod quick;
+#[cfg(feature = "tui")]
+pub mod recorder;
+#[cfg(feature = "tui")]
+pub mod remote;
+#[cfg(feature = "tui")]
+pub mod s3;
+#[cfg(feature = "tui")]
+pub mod schedule;
+#[cfg(feature = "tui")]
+pub mod shell_hook;
+#[cfg(feature = "tui")]
+pub mod simplenote;
+#[cfg(feature = "tui")]
+pub mod sync;
+#[cfg(feature = "tui")]
+pub mod taskwarrior;
+#[cfg(feature = "tui")]
+pub mod terminal;
+#[cfg(feature = "tui")]
+pub mod testing;
+#[cfg(feature = "tui")]
+pub mod tmux;
+#[cfg(feature = "tui")]
+pub mod tray;
+#[cfg(feature = "tui")]
+pub mod vault;
+#[cfg(feature = "tui")]
+pub mod wizard;
+#[cfg(feature = "tui")]
 pub mod widget;
 
+#[cfg(feature = "tui")]
 pub use app::*;
 pub use notes::*;
+#[cfg(feature = "tui")]
 pub use ui::*;
 pub use config::*;
+#[cfg(feature = "daemon")]
 pub use autostart::*;
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tracing::level_filters::LevelFilter;
+
+use crate::error::UserError;
+
+/// Log level when `--log-level` is not given, e.g. `debug`
+pub const LEVEL_VAR: &str = "TURBO_NOTES_LOG";
+/// Levels `--log-level` takes, least to most detailed
+pub const LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+/// Lines kept for the log view
+const KEEP: usize = 500;
+
+/// Where log lines go besides the log view.
+struct Sink {
+    level: LevelFilter,
+    file: Option<(PathBuf, Mutex<File>)>,
+    stderr: bool,
+}
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static LOGGED: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts logging at `level`, or the level in `TURBO_NOTES_LOG`, or info.
+/// The last lines are kept for the log view; they are also appended to
+/// `file` if given, or else written to stderr when `stderr` is set, as for
+/// the background instance whose stderr is its log.
+pub fn init(level: Option<&str>, file: Option<&Path>, stderr: bool) -> Result<()> {
+    let level = match level.map(str::to_string).or_else(|| std::env::var(LEVEL_VAR).ok()) {
+        Some(level) => LevelFilter::from_str(&level).map_err(|_| {
+            UserError::Invalid(format!("Invalid log level: {} (one of {})", level, LEVELS.join(", ")))
+        })?,
+        None => LevelFilter::INFO,
+    };
+    let file = match file {
+        Some(path) => {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            let opened = OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+                UserError::Invalid(format!("Could not open the log file {}: {}", path.display(), e))
+            })?;
+            Some((path.to_path_buf(), Mutex::new(opened)))
+        }
+        None => None,
+    };
+    let stderr = stderr && file.is_none();
+    if SINK.set(Sink { level, file, stderr }).is_err() {
+        return Ok(());
+    }
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_ansi(false)
+        .with_writer(|| Line(Vec::new()))
+        .try_init();
+    Ok(())
+}
+
+/// The level logged at, to pass on to the background instance.
+pub fn level() -> Option<String> {
+    SINK.get().map(|sink| sink.level.to_string().to_lowercase())
+}
+
+/// The file log lines are appended to, if any.
+pub fn file() -> Option<PathBuf> {
+    SINK.get()?.file.as_ref().map(|(path, _)| path.clone())
+}
+
+/// The lines logged lately, oldest first.
+pub fn recent() -> Vec<String> {
+    RECENT.lock().map(|lines| lines.iter().cloned().collect()).unwrap_or_default()
+}
+
+/// Number of lines logged so far, to tell when there are new ones.
+pub fn logged() -> usize {
+    LOGGED.load(Ordering::Relaxed)
+}
+
+/// One log line, passed on once it is complete.
+struct Line(Vec<u8>);
+
+impl Write for Line {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Line {
+    fn drop(&mut self) {
+        if self.0.is_empty() {
+            return;
+        }
+        if let Ok(mut lines) = RECENT.lock() {
+            if lines.len() == KEEP {
+                lines.pop_front();
+            }
+            lines.push_back(String::from_utf8_lossy(&self.0).trim_end().to_string());
+        }
+        LOGGED.fetch_add(1, Ordering::Relaxed);
+        let Some(sink) = SINK.get() else { return };
+        if let Some((_, file)) = &sink.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write_all(&self.0);
+            }
+        } else if sink.stderr {
+            let _ = io::stderr().write_all(&self.0);
+        }
+    }
+}
@@ -1,75 +1,180 @@
 #![allow(dead_code)]
 
 use anyhow::Result;
-use clap::{Arg, Command};
-use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use turbo_notes_core::{
+    atom, backup, capture, config, crypto, dates, error, goals, graph, habits, heatmap, html, ics, links, meeting, merge, metadata, natural,
+    notes, outline, profile, query, reading, reminders, saver, shorthand, snippets, stats, suggest, tidy, webhooks,
+};
 
 mod app;
-mod notes;
 mod ui;
-mod config;
+mod api;
+mod archive;
 mod autostart;
+mod bench;
+mod cli;
+mod clipboard;
+mod copies;
+mod daemon;
+mod doctor;
+mod draft;
+mod editor;
+mod images;
+mod import;
+mod input;
+mod ipc;
+mod joplin;
+mod keep;
+mod logging;
+mod notion;
+mod onboarding;
+mod plugins;
+mod pomodoro;
+mod notify;
+mod qr;
+mod quick;
+mod recorder;
+mod remote;
+mod s3;
+mod schedule;
+mod shell_hook;
+mod simplenote;
+mod sync;
+mod taskwarrior;
+mod terminal;
+mod tmux;
+mod tray;
+mod vault;
+mod wizard;
 
 use app::App;
+use error::UserError;
 use autostart::setup_autostart;
+use config::Config;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let matches = Command::new("turbo-notes")
-        .version("0.1.0")
-        .about("A minimalist cross-platform terminal-based notes application")
-        .arg(
-            Arg::new("setup-autostart")
-                .long("setup-autostart")
-                .help("Setup auto-start functionality")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("disable-autostart")
-                .long("disable-autostart")
-                .help("Disable auto-start functionality")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("widget")
-                .short('w')
-                .long("widget")
-                .help("Launch quick note widget")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .get_matches();
+    terminal::install_panic_hook();
+    // Answers shell completion requests from `turbo-notes completions` scripts
+    clap_complete::CompleteEnv::with_factory(cli::build_cli)
+        .var(cli::COMPLETE_VAR)
+        .complete();
+
+    let matches = match cli::build_cli().try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) => {
+            // Usage errors are validation failures; --help and --version are not
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { cli::EXIT_INVALID } else { 0 });
+        }
+    };
+
+    if let Err(e) = cli::select_profile(&matches) {
+        std::process::exit(cli::report_error(&matches, &e));
+    }
+    // A background instance logs to its stderr, which is its log file, and
+    // the API server to its terminal
+    let log_file = matches.get_one::<PathBuf>("log-file").map(PathBuf::as_path);
+    let serving = matches.subcommand_name() == Some("serve");
+    let log_to_stderr = serving || (matches.get_flag("startup") && !io::stderr().is_terminal());
+    if let Err(e) = logging::init(matches.get_one::<String>("log-level").map(String::as_str), log_file, log_to_stderr) {
+        std::process::exit(cli::report_error(&matches, &e));
+    }
+
+    match cli::run_subcommand(&matches).await {
+        Ok(true) => {
+            finish_webhooks();
+            return Ok(());
+        }
+        Ok(false) => {}
+        Err(e) => {
+            finish_webhooks();
+            std::process::exit(cli::report_error(&matches, &e));
+        }
+    }
 
     if matches.get_flag("setup-autostart") {
         setup_autostart(true)?;
+        autostart::record(&mut Config::load()?, true)?;
         println!("Auto-start enabled successfully!");
         return Ok(());
     }
 
     if matches.get_flag("disable-autostart") {
         setup_autostart(false)?;
+        autostart::record(&mut Config::load()?, false)?;
         println!("Auto-start disabled successfully!");
         return Ok(());
     }
 
+    if let Some(&count) = matches.get_one::<usize>("bench-populate") {
+        if let Err(e) = cli::bench_populate(count).await {
+            std::process::exit(cli::report_error(&matches, &e));
+        }
+        return Ok(());
+    }
+
     if matches.get_flag("widget") {
-        // Launch widget mode
+        if hand_over(ipc::Request::ShowWidget).await? {
+            return Ok(());
+        }
         let mut app = App::new(true).await?;
         app.run_widget().await?;
+        finish_webhooks();
+        return Ok(());
+    }
+
+    if matches.get_flag("daemon") {
+        if let Err(e) = start_daemon().await {
+            std::process::exit(cli::report_error(&matches, &e));
+        }
         return Ok(());
     }
 
-    // Check if launched on startup
-    let is_startup = env::args().any(|arg| arg == "--startup");
-    
-    if is_startup {
+    if matches.get_flag("startup") {
         // Run in background widget mode on startup
         let mut app = App::new(true).await?;
         app.run_background().await?;
-    } else {
+    } else if !hand_over(ipc::Request::Open { note: None }).await? {
         // Normal terminal UI mode
         let mut app = App::new(false).await?;
+        app.word_goal = matches.get_one::<usize>("goal").copied();
         app.run().await?;
     }
 
+    finish_webhooks();
+    Ok(())
+}
+
+/// Lets webhook deliveries started by the command finish before exiting.
+fn finish_webhooks() {
+    for failure in webhooks::finish() {
+        eprintln!("{}", failure);
+    }
+}
+
+/// Passes `request` to an instance that is already running, so only one
+/// process writes the notes. Returns whether it was handled there.
+async fn hand_over(request: ipc::Request) -> Result<bool> {
+    match ipc::send(&request).await? {
+        Some(reply) if reply.released => Ok(false),
+        Some(reply) if reply.ok => {
+            println!("{}", reply.message);
+            Ok(true)
+        }
+        Some(reply) => Err(anyhow::anyhow!(reply.message)),
+        None => Ok(false),
+    }
+}
+
+async fn start_daemon() -> Result<()> {
+    if ipc::send(&ipc::Request::Ping).await?.is_some() {
+        return Err(UserError::Invalid("Turbo Notes is already running".into()).into());
+    }
+    let pid = daemon::spawn().await?;
+    println!("Turbo Notes is running in the background (pid {})", pid);
+    println!("Log: {}", daemon::log_path()?.display());
     Ok(())
 }
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::process::{Command, Stdio};
+
+/// Shows a desktop notification: through `notify-send` on Linux and other
+/// Unixes, `osascript` on macOS and a tray balloon via PowerShell on Windows.
+/// Does not wait for it to be dismissed.
+pub fn send(title: &str, body: &str) -> Result<()> {
+    let mut command = notifier(title, body);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Could not run {:?} to show a notification: {}", command.get_program(), e))?;
+    // Reaped off the caller's thread; the Windows balloon stays up a while
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn notifier(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    ));
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(windows)]
+fn notifier(title: &str, body: &str) -> Command {
+    // Passed through the environment, so nothing needs quoting for PowerShell
+    let script = "Add-Type -AssemblyName System.Windows.Forms; \
+        $icon = New-Object System.Windows.Forms.NotifyIcon; \
+        $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+        $icon.Visible = $true; \
+        $icon.ShowBalloonTip(10000, $env:TURBO_NOTES_TITLE, $env:TURBO_NOTES_BODY, 'Info'); \
+        Start-Sleep -Seconds 10; \
+        $icon.Dispose()";
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", script])
+        .env("TURBO_NOTES_TITLE", title)
+        .env("TURBO_NOTES_BODY", body);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn notifier(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name=Turbo Notes", title, body]);
+    command
+}
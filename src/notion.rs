@@ -0,0 +1,239 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+
+use crate::import::{has_extension, open_zip, Imported, Importer};
+use crate::notes::Note;
+
+/// Date formats Notion writes in page properties, depending on the
+/// workspace's date settings
+const DATE_FORMATS: [&str; 4] = ["%B %d, %Y %I:%M %p", "%B %d, %Y %H:%M", "%Y/%m/%d %H:%M", "%d/%m/%Y %H:%M"];
+
+/// Notion's "Markdown & CSV" export zip. Pages become notes, with their
+/// folder path (minus Notion's ids) as the notebook and any `Tags` property
+/// as tags. Database rows that were exported without a page of their own
+/// are built from the database's CSV. Large exports split into nested
+/// `Part-N.zip` files are read too.
+pub struct NotionImporter;
+
+impl Importer for NotionImporter {
+    fn name(&self) -> &'static str {
+        "notion"
+    }
+
+    fn detect(&self, path: &Path, _input: &[u8]) -> bool {
+        has_extension(path, "zip")
+    }
+
+    fn read(&self, input: &[u8], progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        let mut export = Export::default();
+        export.read_zip(input, progress)?;
+
+        let mut imported = Imported {
+            skipped_files: export.skipped_files,
+            ..Default::default()
+        };
+        let mut titles: HashSet<(Option<String>, String)> = HashSet::new();
+        for (path, content) in &export.pages {
+            let note = page_note(path, content);
+            titles.insert((note.notebook.clone(), title(&note.content).to_string()));
+            imported.notes.push(note);
+        }
+        for (path, content) in &export.tables {
+            for note in table_notes(path, content) {
+                if titles.insert((note.notebook.clone(), title(&note.content).to_string())) {
+                    imported.notes.push(note);
+                }
+            }
+        }
+        Ok(imported)
+    }
+}
+
+#[derive(Default)]
+struct Export {
+    pages: Vec<(String, String)>,
+    tables: Vec<(String, String)>,
+    skipped_files: usize,
+}
+
+impl Export {
+    fn read_zip(&mut self, input: &[u8], progress: &mut dyn FnMut(usize, usize)) -> Result<()> {
+        let mut zip = open_zip(input)?;
+        let total = zip.len();
+        for index in 0..total {
+            progress(index, total);
+            let mut file = zip.by_index(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            let path = file.name().to_string();
+            let ext = path.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+            match ext.as_str() {
+                "md" | "csv" => {
+                    let mut content = String::new();
+                    file.read_to_string(&mut content)?;
+                    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+                    if ext == "md" {
+                        self.pages.push((path, content));
+                    } else {
+                        self.tables.push((path, content));
+                    }
+                }
+                "zip" => {
+                    let mut nested = Vec::new();
+                    file.read_to_end(&mut nested)?;
+                    drop(file);
+                    self.read_zip(&nested, &mut |_, _| {})?;
+                }
+                _ => self.skipped_files += 1,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn page_note(path: &str, content: &str) -> Note {
+    let (notebook, name) = split_path(path);
+    let body = content.trim();
+    let mut note = Note::new(if body.starts_with("# ") || name.is_empty() {
+        body.to_string()
+    } else {
+        format!("# {}\n\n{}", name, body).trim_end().to_string()
+    });
+    note.notebook = notebook;
+
+    // Database rows carry their properties as "Key: value" lines after the title
+    let properties = body
+        .lines()
+        .skip(1)
+        .skip_while(|line| line.trim().is_empty())
+        .map_while(|line| line.split_once(": "));
+    let (mut created, mut updated) = (None, None);
+    for (key, value) in properties {
+        apply_property(&mut note, key, value, &mut created, &mut updated);
+    }
+    note.created_at = created.unwrap_or(note.created_at);
+    note.updated_at = updated.or(created).unwrap_or(note.created_at);
+    note
+}
+
+fn table_notes(path: &str, content: &str) -> Vec<Note> {
+    let (parent, name) = split_path(path);
+    let name = name.strip_suffix("_all").unwrap_or(&name);
+    let notebook = match parent {
+        Some(parent) => format!("{}/{}", parent, name),
+        None => name.to_string(),
+    };
+
+    let mut rows = parse_csv(content).into_iter();
+    let Some(header) = rows.next() else {
+        return Vec::new();
+    };
+    let mut notes = Vec::new();
+    for row in rows {
+        let Some(title) = row.first().map(|title| title.trim()).filter(|title| !title.is_empty()) else {
+            continue;
+        };
+        let mut content = format!("# {}\n", title);
+        for (key, value) in header.iter().zip(&row).skip(1).filter(|(_, value)| !value.trim().is_empty()) {
+            content.push_str(&format!("\n{}: {}", key, value.trim()));
+        }
+        let mut note = Note::new(content.trim_end().to_string());
+        note.notebook = Some(notebook.clone());
+        let (mut created, mut updated) = (None, None);
+        for (key, value) in header.iter().zip(&row) {
+            apply_property(&mut note, key, value, &mut created, &mut updated);
+        }
+        note.created_at = created.unwrap_or(note.created_at);
+        note.updated_at = updated.or(created).unwrap_or(note.created_at);
+        notes.push(note);
+    }
+    notes
+}
+
+fn apply_property(
+    note: &mut Note,
+    key: &str,
+    value: &str,
+    created: &mut Option<DateTime<Utc>>,
+    updated: &mut Option<DateTime<Utc>>,
+) {
+    match key.trim().to_lowercase().as_str() {
+        "tags" | "tag" | "labels" => {
+            for tag in value.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+                note.add_tag(tag.replace(char::is_whitespace, "-"));
+            }
+        }
+        "created" | "created time" | "date created" => *created = parse_date(value).or(*created),
+        "updated" | "last edited time" | "last edited" => *updated = parse_date(value).or(*updated),
+        _ => {}
+    }
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    let naive = DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%B %d, %Y")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local.from_local_datetime(&naive).earliest().map(|time| time.with_timezone(&Utc))
+}
+
+/// Splits an export path into the notebook (parent folders) and the page
+/// name, dropping the ids Notion appends to every name.
+fn split_path(path: &str) -> (Option<String>, String) {
+    let mut parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let file = parts.pop().unwrap_or_default();
+    let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+    let folders: Vec<&str> = parts.into_iter().map(strip_id).collect();
+    let notebook = (!folders.is_empty()).then(|| folders.join("/"));
+    (notebook, strip_id(stem).to_string())
+}
+
+fn strip_id(name: &str) -> &str {
+    match name.rsplit_once(' ') {
+        Some((rest, id)) if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) => rest,
+        _ => name,
+    }
+}
+
+fn title(content: &str) -> &str {
+    content.lines().next().unwrap_or("").trim_start_matches('#').trim()
+}
+
+/// Minimal RFC 4180 reader: quoted fields may hold commas, newlines and
+/// doubled quotes.
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
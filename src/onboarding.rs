@@ -0,0 +1,83 @@
+/// Steps of the first-run tutorial, in order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnboardingStep {
+    Welcome,
+    CreateNote,
+    TagNote,
+    Search,
+    Done,
+}
+
+/// User actions the tutorial listens for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnboardingEvent {
+    Started,
+    NoteCreated,
+    NoteTagged,
+    SearchPerformed,
+}
+
+#[derive(Debug, Clone)]
+pub struct Onboarding {
+    pub step: OnboardingStep,
+}
+
+impl Default for Onboarding {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Onboarding {
+    pub fn new() -> Self {
+        Self {
+            step: OnboardingStep::Welcome,
+        }
+    }
+
+    /// Moves to the next step if `event` is what the current step waits for.
+    /// Returns true when the tutorial has just been completed.
+    pub fn advance(&mut self, event: OnboardingEvent) -> bool {
+        let next = match (self.step, event) {
+            (OnboardingStep::Welcome, OnboardingEvent::Started) => OnboardingStep::CreateNote,
+            (OnboardingStep::CreateNote, OnboardingEvent::NoteCreated) => OnboardingStep::TagNote,
+            (OnboardingStep::TagNote, OnboardingEvent::NoteTagged) => OnboardingStep::Search,
+            (OnboardingStep::Search, OnboardingEvent::SearchPerformed) => OnboardingStep::Done,
+            _ => return false,
+        };
+        self.step = next;
+        next == OnboardingStep::Done
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.step {
+            OnboardingStep::Welcome => " Welcome to Turbo Notes ",
+            OnboardingStep::CreateNote => " Tutorial 1/3: Create a note ",
+            OnboardingStep::TagNote => " Tutorial 2/3: Tag it ",
+            OnboardingStep::Search => " Tutorial 3/3: Search ",
+            OnboardingStep::Done => " All set ",
+        }
+    }
+
+    pub fn instructions(&self) -> &'static [&'static str] {
+        match self.step {
+            OnboardingStep::Welcome => &[
+                "A quick tour: create a note, tag it, then find it again.",
+                "Press Enter to start, or Esc to skip the tutorial for good.",
+            ],
+            OnboardingStep::CreateNote => &[
+                "Press 'n', type anything, then press Esc to save it.",
+            ],
+            OnboardingStep::TagNote => &[
+                "Select your note with ↑/↓, press 't', type a tag and press Enter.",
+            ],
+            OnboardingStep::Search => &[
+                "Press 's', type part of your note or its tag, then press Enter.",
+            ],
+            OnboardingStep::Done => &[
+                "That's it! Press 'w' any time for the quick widget.",
+                "Press any key to close this tutorial.",
+            ],
+        }
+    }
+}
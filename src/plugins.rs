@@ -0,0 +1,468 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::notes::{ExportFormat, Note};
+
+/// Directory of the notes directory plugins are loaded from, one
+/// directory each
+pub const PLUGINS_DIR: &str = "plugins";
+/// File of a plugin's directory describing it
+pub const MANIFEST_FILE: &str = "plugin.json";
+/// Version of the guest API this build provides
+pub const API_VERSION: u32 = 1;
+
+/// What a plugin may do, as its manifest declares. Each comes with the
+/// function of the same name the module must export, and only declared
+/// ones are called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Commands in the command palette, run on the selected note
+    Commands,
+    /// Changing a note's text as it is saved
+    Transform,
+    /// Export formats of its own
+    Export,
+    /// Badges next to notes in the list
+    Decorate,
+}
+
+impl Capability {
+    /// The module's function called for it
+    fn entry(self) -> &'static str {
+        match self {
+            Capability::Commands => "command",
+            Capability::Transform => "transform",
+            Capability::Export => "export",
+            Capability::Decorate => "decorate",
+        }
+    }
+}
+
+/// A plugin's `plugin.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Guest API the module was written for
+    pub api: u32,
+    /// WebAssembly module, binary or text, relative to the manifest
+    #[serde(default = "default_module")]
+    pub module: String,
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// Commands added to the palette, with the `commands` capability
+    #[serde(default)]
+    pub commands: Vec<PluginCommand>,
+    /// Formats added to `export --format`, with the `export` capability
+    #[serde(default)]
+    pub export_formats: Vec<PluginFormat>,
+}
+
+fn default_module() -> String {
+    "plugin.wasm".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginCommand {
+    /// Passed to the plugin when the command runs
+    pub id: String,
+    /// Shown in the palette
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginFormat {
+    /// Given with `--format`
+    pub name: String,
+    /// Of the file a scheduled export writes
+    pub extension: String,
+}
+
+impl Manifest {
+    fn validate(&self) -> Result<()> {
+        if self.api != API_VERSION {
+            return Err(anyhow!("written for plugin API {}, this build provides {}", self.api, API_VERSION));
+        }
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("no name"));
+        }
+        if !self.commands.is_empty() && !self.allows(Capability::Commands) {
+            return Err(anyhow!("lists commands without the commands capability"));
+        }
+        if !self.export_formats.is_empty() && !self.allows(Capability::Export) {
+            return Err(anyhow!("lists export formats without the export capability"));
+        }
+        if let Some(format) = self.export_formats.iter().find(|format| is_builtin_format(&format.name)) {
+            return Err(anyhow!("export format '{}' is built in", format.name));
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// Whether `export` writes `name` itself, without plugins.
+pub fn is_builtin_format(name: &str) -> bool {
+    name == "jex" || ExportFormat::from_name(name).is_some()
+}
+
+/// A plugin whose module is ready to run.
+pub struct Plugin {
+    pub manifest: Manifest,
+    pub dir: PathBuf,
+    module: runtime::Module,
+}
+
+impl Plugin {
+    /// Runs the module's function for `capability` on `input`, as JSON both
+    /// ways. Each call starts from a fresh instance of the module.
+    fn call<T: DeserializeOwned>(&self, capability: Capability, input: &impl Serialize) -> Result<T> {
+        let output = self
+            .module
+            .call(&self.manifest.name, capability.entry(), &serde_json::to_vec(input)?)
+            .with_context(|| format!("Plugin {} failed in {}", self.manifest.name, capability.entry()))?;
+        serde_json::from_slice(&output)
+            .with_context(|| format!("Plugin {} returned bad output from {}", self.manifest.name, capability.entry()))
+    }
+}
+
+/// A note as plugins see it, kept the same across versions whatever
+/// becomes of [`Note`].
+#[derive(Serialize)]
+struct GuestNote<'a> {
+    id: &'a str,
+    title: &'a str,
+    content: &'a str,
+    tags: &'a [String],
+    notebook: Option<&'a str>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl<'a> GuestNote<'a> {
+    fn new(note: &'a Note) -> Self {
+        GuestNote::with_content(note, &note.content)
+    }
+
+    fn with_content(note: &'a Note, content: &'a str) -> Self {
+        GuestNote {
+            id: &note.id,
+            title: note.title(),
+            content,
+            tags: &note.tags,
+            notebook: note.notebook.as_deref(),
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        }
+    }
+}
+
+/// What a palette command returns: the selected note's new text, and a
+/// line for the status bar, both optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct CommandOutput {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransformOutput {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct ExportOutput {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct DecorateOutput {
+    #[serde(default)]
+    badges: Vec<String>,
+}
+
+/// Plugins loaded from the notes directory, with those that could not be.
+#[derive(Default)]
+pub struct Plugins {
+    pub loaded: Vec<Plugin>,
+    /// Directories whose plugin failed to load, with why
+    pub failed: Vec<(PathBuf, String)>,
+    badges: Mutex<Badges>,
+}
+
+/// Badges of notes by id, with when the note was updated as they were made
+type Badges = HashMap<String, (DateTime<Utc>, Vec<String>)>;
+
+impl Plugins {
+    /// Loads the plugins in the notes directory's `plugins`, in the order
+    /// of their directories' names. A plugin that fails to load is left
+    /// out and recorded, without stopping the rest.
+    pub fn load(config: &Config) -> Plugins {
+        let mut plugins = Plugins::default();
+        let dir = config.notes_dir.join(PLUGINS_DIR);
+        let mut dirs: Vec<PathBuf> = match fs::read_dir(&dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).filter(|path| path.join(MANIFEST_FILE).is_file()).collect(),
+            Err(_) => return plugins,
+        };
+        dirs.sort();
+        let engine = match runtime::engine() {
+            Ok(engine) => engine,
+            Err(e) => {
+                tracing::warn!("{} plugins not loaded: {:#}", dirs.len(), e);
+                plugins.failed.extend(dirs.into_iter().map(|dir| (dir, format!("{:#}", e))));
+                return plugins;
+            }
+        };
+        for dir in dirs {
+            match plugins.load_one(&engine, &dir) {
+                Ok(plugin) => plugins.loaded.push(plugin),
+                Err(e) => {
+                    tracing::warn!("Could not load the plugin in {}: {:#}", dir.display(), e);
+                    plugins.failed.push((dir, format!("{:#}", e)));
+                }
+            }
+        }
+        if !plugins.loaded.is_empty() {
+            tracing::info!("Loaded {} plugins", plugins.loaded.len());
+        }
+        plugins
+    }
+
+    fn load_one(&self, engine: &runtime::Engine, dir: &Path) -> Result<Plugin> {
+        let manifest: Manifest = serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE))?)
+            .with_context(|| format!("Invalid {}", MANIFEST_FILE))?;
+        manifest.validate()?;
+        if self.loaded.iter().any(|plugin| plugin.manifest.name == manifest.name) {
+            return Err(anyhow!("another plugin is named {}", manifest.name));
+        }
+        for format in &manifest.export_formats {
+            if self.export_format(&format.name).is_some() {
+                return Err(anyhow!("another plugin adds export format '{}'", format.name));
+            }
+        }
+        let entries: Vec<&str> = manifest.capabilities.iter().map(|capability| capability.entry()).collect();
+        let module = runtime::Module::load(engine, &dir.join(&manifest.module), &entries)?;
+        Ok(Plugin { manifest, dir: dir.to_path_buf(), module })
+    }
+
+    fn with(&self, capability: Capability) -> impl Iterator<Item = &Plugin> {
+        self.loaded.iter().filter(move |plugin| plugin.manifest.allows(capability))
+    }
+
+    /// Palette commands of all plugins, as the plugin's index and the command.
+    pub fn commands(&self) -> Vec<(usize, &PluginCommand)> {
+        self.loaded
+            .iter()
+            .enumerate()
+            .filter(|(_, plugin)| plugin.manifest.allows(Capability::Commands))
+            .flat_map(|(i, plugin)| plugin.manifest.commands.iter().map(move |command| (i, command)))
+            .collect()
+    }
+
+    /// Runs command `id` of plugin `plugin` on `note`, the selected one.
+    pub fn run_command(&self, plugin: usize, id: &str, note: Option<&Note>) -> Result<CommandOutput> {
+        let plugin = self.loaded.get(plugin).ok_or_else(|| anyhow!("No such plugin"))?;
+        #[derive(Serialize)]
+        struct Input<'a> {
+            command: &'a str,
+            note: Option<GuestNote<'a>>,
+        }
+        plugin.call(Capability::Commands, &Input { command: id, note: note.map(GuestNote::new) })
+    }
+
+    /// `content`, about to be saved as `note`'s text, as the plugins that
+    /// transform notes leave it, one after another. A plugin that fails
+    /// is skipped, with a warning in the log.
+    pub fn transform(&self, note: &Note, content: String) -> String {
+        #[derive(Serialize)]
+        struct Input<'a> {
+            note: GuestNote<'a>,
+        }
+        self.with(Capability::Transform).fold(content, |content, plugin| {
+            match plugin.call::<TransformOutput>(Capability::Transform, &Input { note: GuestNote::with_content(note, &content) }) {
+                Ok(output) => output.content,
+                Err(e) => {
+                    tracing::warn!("{:#}", e);
+                    content
+                }
+            }
+        })
+    }
+
+    /// The plugin adding export format `name`, if any.
+    pub fn export_format(&self, name: &str) -> Option<(&Plugin, &PluginFormat)> {
+        self.with(Capability::Export)
+            .find_map(|plugin| plugin.manifest.export_formats.iter().find(|format| format.name == name).map(|format| (plugin, format)))
+    }
+
+    /// `notes` exported as format `name` of a plugin.
+    pub fn export(&self, name: &str, notes: &[&Note]) -> Result<String> {
+        let (plugin, _) = self.export_format(name).ok_or_else(|| anyhow!("No plugin adds export format '{}'", name))?;
+        #[derive(Serialize)]
+        struct Input<'a> {
+            format: &'a str,
+            notes: Vec<GuestNote<'a>>,
+        }
+        let input = Input { format: name, notes: notes.iter().map(|note| GuestNote::new(note)).collect() };
+        Ok(plugin.call::<ExportOutput>(Capability::Export, &input)?.text)
+    }
+
+    /// Badges the plugins put next to `note` in the list. Kept until the
+    /// note changes, so only notes drawn for the first time or edited call
+    /// the plugins; those failing show none.
+    pub fn badges(&self, note: &Note) -> Vec<String> {
+        if self.with(Capability::Decorate).next().is_none() {
+            return Vec::new();
+        }
+        let mut cache = self.badges.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((updated_at, badges)) = cache.get(&note.id) {
+            if *updated_at == note.updated_at {
+                return badges.clone();
+            }
+        }
+        #[derive(Serialize)]
+        struct Input<'a> {
+            note: GuestNote<'a>,
+        }
+        let badges: Vec<String> = self
+            .with(Capability::Decorate)
+            .flat_map(|plugin| match plugin.call::<DecorateOutput>(Capability::Decorate, &Input { note: GuestNote::new(note) }) {
+                Ok(output) => output.badges,
+                Err(e) => {
+                    tracing::warn!("{:#}", e);
+                    Vec::new()
+                }
+            })
+            .map(|badge| badge.trim().to_string())
+            .filter(|badge| !badge.is_empty())
+            .collect();
+        cache.insert(note.id.clone(), (note.updated_at, badges.clone()));
+        badges
+    }
+}
+
+/// Stand-in for the runtime in builds without the `plugins` feature:
+/// every plugin fails to load, saying so.
+#[cfg(not(feature = "plugins"))]
+mod runtime {
+    use anyhow::{anyhow, Result};
+    use std::path::Path;
+
+    pub struct Engine;
+
+    pub enum Module {}
+
+    pub fn engine() -> Result<Engine> {
+        Err(anyhow!("This build runs no plugins; build with --features plugins"))
+    }
+
+    impl Module {
+        pub fn load(_engine: &Engine, _path: &Path, _entries: &[&str]) -> Result<Module> {
+            unreachable!("there is no engine to load modules with")
+        }
+
+        pub fn call(&self, _plugin: &str, _entry: &str, _input: &[u8]) -> Result<Vec<u8>> {
+            match *self {}
+        }
+    }
+}
+
+/// The WebAssembly runtime. A module exports its `memory`, an
+/// `alloc(len: i32) -> i32` the input is written into, and a function per
+/// capability taking the input's pointer and length and returning the
+/// output's, as `pointer << 32 | length`. It may import
+/// `turbo_notes.log(pointer, length)`, logging a line, and nothing else.
+#[cfg(feature = "plugins")]
+mod runtime {
+    use anyhow::{anyhow, Context, Result};
+    use std::path::Path;
+    use wasmtime::{Caller, Config, Extern, InstancePre, Linker, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+    pub use wasmtime::Engine;
+
+    /// Roughly the instructions a call may run before it is stopped
+    const FUEL: u64 = 2_000_000_000;
+    /// Memory a call may use, in bytes
+    const MEMORY: usize = 64 * 1024 * 1024;
+
+    struct State {
+        plugin: String,
+        limits: StoreLimits,
+    }
+
+    pub struct Module {
+        instance: InstancePre<State>,
+    }
+
+    pub fn engine() -> Result<Engine> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    }
+
+    impl Module {
+        /// Compiles the module at `path`, checking that it exports the
+        /// functions in `entries` and imports nothing the host lacks.
+        pub fn load(engine: &Engine, path: &Path, entries: &[&str]) -> Result<Module> {
+            let module = wasmtime::Module::from_file(engine, path).with_context(|| format!("Could not load {}", path.display()))?;
+            for entry in ["memory", "alloc"].iter().chain(entries) {
+                if module.get_export(entry).is_none() {
+                    return Err(anyhow!("{} does not export {}", path.display(), entry));
+                }
+            }
+            let mut linker = Linker::new(engine);
+            linker.func_wrap("turbo_notes", "log", |mut caller: Caller<'_, State>, pointer: i32, length: i32| {
+                let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+                    return;
+                };
+                let mut line = vec![0; length.max(0) as usize];
+                if memory.read(&caller, pointer as u32 as usize, &mut line).is_ok() {
+                    tracing::info!("{}: {}", caller.data().plugin, String::from_utf8_lossy(&line));
+                }
+            })?;
+            Ok(Module { instance: linker.instantiate_pre(&module)? })
+        }
+
+        /// Calls `entry` of a fresh instance with `input`, returning its output.
+        pub fn call(&self, plugin: &str, entry: &str, input: &[u8]) -> Result<Vec<u8>> {
+            let limits = StoreLimitsBuilder::new().memory_size(MEMORY).build();
+            let mut store = Store::new(self.instance.module().engine(), State { plugin: plugin.to_string(), limits });
+            store.limiter(|state| &mut state.limits);
+            store.set_fuel(FUEL)?;
+            let instance = self.instance.instantiate(&mut store).map_err(trapped)?;
+            let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| anyhow!("memory is not a memory"))?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+            let function = instance.get_typed_func::<(i32, i32), i64>(&mut store, entry)?;
+
+            let length = i32::try_from(input.len()).context("input too long")?;
+            let pointer = alloc.call(&mut store, length).map_err(trapped)?;
+            memory.write(&mut store, pointer as u32 as usize, input)?;
+            let output = function.call(&mut store, (pointer, length)).map_err(trapped)? as u64;
+            let mut bytes = vec![0; (output & 0xffff_ffff) as usize];
+            memory.read(&store, (output >> 32) as usize, &mut bytes).context("output out of bounds")?;
+            Ok(bytes)
+        }
+    }
+
+    /// `error` without the WebAssembly backtrace when a trap stopped the
+    /// call, which is what it says, such as running out of fuel.
+    fn trapped(error: anyhow::Error) -> anyhow::Error {
+        match error.downcast_ref::<Trap>() {
+            Some(trap) => anyhow!("{}", trap),
+            None => error,
+        }
+    }
+}
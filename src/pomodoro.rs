@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::config::{Config, PomodoroConfig};
+use crate::dates;
+
+/// What a running timer is counting down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl Phase {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Phase::Work => "focus",
+            Phase::ShortBreak => "break",
+            Phase::LongBreak => "long break",
+        }
+    }
+
+    fn minutes(&self, config: &PomodoroConfig) -> u64 {
+        let minutes = match self {
+            Phase::Work => config.work_minutes,
+            Phase::ShortBreak => config.short_break_minutes,
+            Phase::LongBreak => config.long_break_minutes,
+        };
+        // The config file may be edited by hand, past validation
+        minutes.clamp(1, 24 * 60)
+    }
+}
+
+/// A pomodoro or the break after it, for the note being worked on.
+#[derive(Debug, Clone)]
+pub struct Timer {
+    pub note_id: String,
+    pub phase: Phase,
+    pub started: DateTime<Utc>,
+    pub ends: Instant,
+}
+
+impl Timer {
+    pub fn new(note_id: String, phase: Phase, config: &PomodoroConfig) -> Self {
+        Self {
+            note_id,
+            phase,
+            started: Utc::now(),
+            ends: Instant::now() + Duration::from_secs(phase.minutes(config) * 60),
+        }
+    }
+
+    /// The break after the `done`th pomodoro: a long one every
+    /// `long_break_every` pomodoros.
+    pub fn rest(note_id: String, done: u32, config: &PomodoroConfig) -> Self {
+        let phase = if done.is_multiple_of(config.long_break_every.max(1)) {
+            Phase::LongBreak
+        } else {
+            Phase::ShortBreak
+        };
+        Self::new(note_id, phase, config)
+    }
+
+    pub fn minutes(&self, config: &PomodoroConfig) -> u64 {
+        self.phase.minutes(config)
+    }
+
+    pub fn is_over(&self) -> bool {
+        Instant::now() >= self.ends
+    }
+
+    /// Time left as `mm:ss`, with the phase, e.g. `24:59 focus`.
+    pub fn countdown(&self) -> String {
+        let left = self.ends.saturating_duration_since(Instant::now()).as_secs();
+        format!("{:02}:{:02} {}", left / 60, left % 60, self.phase.label())
+    }
+}
+
+/// Resolves when `timer` runs out; never while there is none.
+pub async fn finished(timer: &Option<Timer>) {
+    match timer {
+        Some(timer) => tokio::time::sleep_until(timer.ends).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Line logged for a finished pomodoro, e.g.
+/// `- Pomodoro 10/16 13:00–13:25 (25 min)`, naming the note when it goes
+/// to a separate log note.
+pub fn log_line(timer: &Timer, note_title: Option<&str>, config: &Config) -> String {
+    let started = dates::format_absolute(timer.started, &config.date_format, config.zone());
+    let ended = config.zone().to_local(Utc::now()).format("%H:%M");
+    let minutes = timer.minutes(&config.pomodoro);
+    let mut line = format!("- Pomodoro {}–{} ({} min)", started, ended, minutes);
+    if let Some(title) = note_title {
+        line.push_str(&format!(": {}", title));
+    }
+    line
+}
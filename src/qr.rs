@@ -0,0 +1,78 @@
+use anyhow::Result;
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::error::UserError;
+
+/// Most bytes a QR code holds, at the lowest error correction
+pub const MAX_BYTES: usize = 2953;
+
+/// Light modules around the code, so a scanner finds its edges
+const QUIET_ZONE: usize = 2;
+
+/// A QR code ready to draw in the terminal.
+#[derive(Debug, Clone)]
+pub struct Code {
+    /// Modules on a side, with the quiet zone
+    size: usize,
+    /// Whether each module is dark, row by row
+    dark: Vec<bool>,
+}
+
+/// `text` as a QR code. Medium error correction is used while the text fits,
+/// so a code read off a screen survives glare; longer text drops to low.
+pub fn encode(text: &str) -> Result<Code> {
+    let code = QrCode::with_error_correction_level(text, EcLevel::M)
+        .or_else(|_| QrCode::with_error_correction_level(text, EcLevel::L))
+        .map_err(|_| {
+            UserError::Invalid(format!(
+                "Too long for a QR code ({} bytes, at most {}); share the note and show the link's code instead",
+                text.len(),
+                MAX_BYTES
+            ))
+        })?;
+    let width = code.width();
+    let size = width + 2 * QUIET_ZONE;
+    let colors = code.to_colors();
+    let mut dark = vec![false; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        let (row, column) = (i / width + QUIET_ZONE, i % width + QUIET_ZONE);
+        dark[row * size + column] = *color == Color::Dark;
+    }
+    Ok(Code { size, dark })
+}
+
+impl Code {
+    /// Columns and rows the code takes when drawn with `lines`.
+    pub fn area(&self, unicode: bool) -> (usize, usize) {
+        if unicode {
+            (self.size, self.size.div_ceil(2))
+        } else {
+            (self.size * 2, self.size)
+        }
+    }
+
+    /// The code as rows of text to draw dark on light. Half blocks fit two
+    /// modules in a character cell; without Unicode each module is `##`, as
+    /// a cell is about twice as tall as it is wide.
+    pub fn lines(&self, unicode: bool) -> Vec<String> {
+        let dark = |row: usize, column: usize| row < self.size && self.dark[row * self.size + column];
+        if !unicode {
+            return (0..self.size)
+                .map(|row| (0..self.size).map(|column| if dark(row, column) { "##" } else { "  " }).collect())
+                .collect();
+        }
+        (0..self.size)
+            .step_by(2)
+            .map(|row| {
+                (0..self.size)
+                    .map(|column| match (dark(row, column), dark(row + 1, column)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use crossterm::{
+    cursor::MoveToColumn,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, IsTerminal, Write};
+
+use crate::error::UserError;
+use crate::terminal::RawModeGuard;
+
+/// Reads one line on the current terminal line, without taking over the
+/// screen. Returns `None` if the user cancelled with Esc or Ctrl+C.
+pub fn prompt(label: &str) -> Result<Option<String>> {
+    read_line(label, false)
+}
+
+/// Like [`prompt`], but echoes `*` instead of the typed characters.
+pub fn prompt_password(label: &str) -> Result<Option<String>> {
+    read_line(label, true)
+}
+
+/// Reads a secret from the environment variable `var`, or prompts for it
+/// on the terminal (twice when `confirm` is set, e.g. for a new password).
+pub fn read_secret(var: &str, label: &str, confirm: bool) -> Result<String> {
+    if let Ok(secret) = std::env::var(var) {
+        if !secret.is_empty() {
+            return Ok(secret);
+        }
+    }
+    if !io::stdin().is_terminal() {
+        return Err(UserError::Invalid(format!("No {} given: set {} or run in a terminal", label.to_lowercase(), var)).into());
+    }
+
+    let cancelled = || UserError::Invalid("Cancelled".into());
+    let secret = prompt_password(&format!("{}: ", label))?.ok_or_else(cancelled)?;
+    if secret.is_empty() {
+        return Err(UserError::Invalid(format!("The {} cannot be empty", label.to_lowercase())).into());
+    }
+    if confirm && prompt_password(&format!("Repeat {}: ", label.to_lowercase()))?.ok_or_else(cancelled)? != secret {
+        return Err(UserError::Invalid(format!("The {}s do not match", label.to_lowercase())).into());
+    }
+    Ok(secret)
+}
+
+fn read_line(label: &str, hidden: bool) -> Result<Option<String>> {
+    let _raw = RawModeGuard::new()?;
+    let mut stdout = io::stdout();
+    let mut input = String::new();
+
+    let result = loop {
+        if hidden {
+            render(&mut stdout, label, &"*".repeat(input.chars().count()))?;
+        } else {
+            render(&mut stdout, label, &input)?;
+        }
+
+        let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+            continue;
+        };
+        if kind != KeyEventKind::Press {
+            continue;
+        }
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+
+        match code {
+            KeyCode::Enter => break Some(input),
+            KeyCode::Esc => break None,
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => break None,
+            KeyCode::Char('u') if ctrl => input.clear(),
+            KeyCode::Char('w') if ctrl => {
+                let trimmed = input.trim_end().len();
+                input.truncate(input[..trimmed].rfind(' ').map_or(0, |i| i + 1));
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) if !ctrl => input.push(c),
+            _ => {}
+        }
+    };
+
+    queue!(stdout, Print("\r\n"))?;
+    stdout.flush()?;
+    Ok(result)
+}
+
+/// Redraws the prompt line, scrolling the input horizontally so it never
+/// wraps (a wrapped line could not be cleared in place).
+fn render(stdout: &mut impl Write, label: &str, input: &str) -> Result<()> {
+    let width = match terminal::size() {
+        Ok((w, _)) if w > 0 => w as usize,
+        _ => 80,
+    };
+    let room = width.saturating_sub(label.chars().count() + 1).max(1);
+    let count = input.chars().count();
+    let visible: String = input.chars().skip(count.saturating_sub(room)).collect();
+
+    queue!(
+        stdout,
+        MoveToColumn(0),
+        Clear(ClearType::CurrentLine),
+        Print(label),
+        Print(visible)
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::UserError;
+use crate::notes::{self, Memo};
+
+/// Recorders looked for on the PATH when `recorder` is not set: the program
+/// and the command that records with it
+const RECORDERS: &[(&str, &str)] = &[
+    ("rec", "rec -q"),
+    #[cfg(target_os = "linux")]
+    ("arecord", "arecord -q -f cd"),
+    #[cfg(target_os = "linux")]
+    ("ffmpeg", "ffmpeg -loglevel quiet -f pulse -i default"),
+    #[cfg(target_os = "macos")]
+    ("ffmpeg", "ffmpeg -loglevel quiet -f avfoundation -i :0"),
+];
+
+/// How long a recorder gets to finish its file once asked to stop
+const STOP_GRACE: Duration = Duration::from_secs(3);
+
+/// Recorder command to use: `Config.recorder`, then the first of the known
+/// ones that is installed.
+pub fn resolve_recorder(config: &Config) -> Result<String> {
+    if let Some(recorder) = config.recorder.as_ref().filter(|recorder| !recorder.trim().is_empty()) {
+        return Ok(recorder.clone());
+    }
+    RECORDERS
+        .iter()
+        .find(|(program, _)| on_path(program))
+        .map(|(_, command)| command.to_string())
+        .ok_or_else(|| {
+            UserError::Invalid(
+                "No recorder found: install sox, or set one with 'turbo-notes config set recorder \"<command>\"'".into(),
+            )
+            .into()
+        })
+}
+
+fn on_path(program: &str) -> bool {
+    let name = format!("{}{}", program, env::consts::EXE_SUFFIX);
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(&name).is_file()))
+}
+
+/// Runs `recorder` on `path` through the shell, as the editor is, in a
+/// process group of its own so stopping it reaches the recorder itself.
+#[cfg(unix)]
+fn command(recorder: &str, path: &Path) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(format!("{} \"$@\"", recorder)).arg(recorder).arg(path).process_group(0);
+    command
+}
+
+#[cfg(not(unix))]
+fn command(recorder: &str, path: &Path) -> Command {
+    let mut parts = recorder.split_whitespace();
+    let mut command = Command::new(parts.next().unwrap_or_default());
+    command.args(parts).arg(path);
+    command
+}
+
+/// A recorder running for a voice memo of the note `note_id`.
+#[derive(Debug)]
+pub struct Recording {
+    pub note_id: String,
+    recorder: String,
+    child: Child,
+    file: PathBuf,
+    recorded_at: DateTime<Utc>,
+    started: Instant,
+}
+
+/// Starts recording a memo for the note `note_id` into its attachments
+/// directory in `notes_dir`.
+pub fn start(config: &Config, notes_dir: &Path, note_id: &str) -> Result<Recording> {
+    let recorder = resolve_recorder(config)?;
+    let dir = notes::attachments_dir(notes_dir, note_id);
+    fs::create_dir_all(&dir)?;
+    let recorded_at = Utc::now();
+    let file = dir.join(format!("{}.wav", recorded_at.format("%Y%m%d-%H%M%S")));
+    // Anything the recorder prints would land in the middle of the interface
+    let child = command(&recorder, &file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow!("Could not start the recorder '{}': {}", recorder, e))?;
+    Ok(Recording { note_id: note_id.to_string(), recorder, child, file, recorded_at, started: Instant::now() })
+}
+
+impl Recording {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Whether the recorder stopped by itself, e.g. finding no microphone.
+    pub fn has_ended(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(Some(_)))
+    }
+
+    /// Stops the recorder and returns the memo it recorded.
+    pub fn stop(mut self) -> Result<Memo> {
+        let seconds = self.started.elapsed().as_secs_f64().round() as u64;
+        match self.child.try_wait()? {
+            Some(status) if !status.success() => {
+                let _ = fs::remove_file(&self.file);
+                return Err(UserError::Invalid(format!(
+                    "The recorder '{}' stopped by itself ({}); set 'recorder' to one that works here",
+                    self.recorder, status
+                ))
+                .into());
+            }
+            // Done already, e.g. a recorder given a time limit
+            Some(_) => {}
+            None => {
+                // As Ctrl+C would, so the recorder finishes the file
+                self.signal("INT");
+                let asked = Instant::now();
+                while self.child.try_wait()?.is_none() {
+                    if asked.elapsed() > STOP_GRACE {
+                        self.signal("KILL");
+                        self.child.wait()?;
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+            }
+        }
+        if fs::metadata(&self.file).map_or(true, |metadata| metadata.len() == 0) {
+            let _ = fs::remove_file(&self.file);
+            return Err(anyhow!("The recorder '{}' wrote nothing", self.recorder));
+        }
+        let file = self.file.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+        Ok(Memo { file, recorded_at: self.recorded_at, seconds })
+    }
+
+    /// Stops the recorder and throws away what it recorded.
+    pub fn discard(mut self) {
+        self.signal("KILL");
+        let _ = self.child.wait();
+        let _ = fs::remove_file(&self.file);
+    }
+
+    /// Sends `signal` to the recorder's process group. Windows has no
+    /// signals, so there the recorder is ended outright.
+    #[cfg(unix)]
+    fn signal(&mut self, signal: &str) {
+        let (signal, group) = (format!("-{}", signal), format!("-{}", self.child.id()));
+        let _ = Command::new("kill").args([signal.as_str(), "--", group.as_str()]).status();
+    }
+
+    #[cfg(not(unix))]
+    fn signal(&mut self, _signal: &str) {
+        let _ = self.child.kill();
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        // Quitting mid-recording leaves no recorder running
+        if matches!(self.child.try_wait(), Ok(None)) {
+            self.signal("KILL");
+            let _ = self.child.wait();
+        }
+    }
+}
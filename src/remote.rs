@@ -0,0 +1,258 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::config::{Config, ServerSyncConfig, SyncBackendKind};
+use crate::error::UserError;
+use crate::s3::uri_encode;
+use crate::sync::{RemoteObject, RemoteVersion, SyncBackend};
+
+/// Environment variable read when `sync.server.token` is empty.
+pub const TOKEN_VAR: &str = "TURBO_NOTES_SYNC_TOKEN";
+
+const ATTEMPTS: u32 = 4;
+
+/// How long a share link works unless told otherwise
+pub const SHARE_EXPIRY: &str = "7d";
+
+/// A change to the server's store, as announced on its WebSocket feed and
+/// listed by `GET /v1/changes`. `seq` increases with every change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub seq: u64,
+    pub key: String,
+    pub version: String,
+    pub deleted: bool,
+}
+
+/// Reply to `PUT /v1/objects/<key>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stored {
+    pub version: String,
+}
+
+/// A note published on the server as a read-only page, which anyone with
+/// its link can read until it expires or is revoked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    /// Secret part of the link
+    pub id: String,
+    pub title: String,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+}
+
+/// Body of `POST /v1/shares`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewShare {
+    pub title: String,
+    /// The page as served, rendered by the client
+    pub html: String,
+    pub expires: DateTime<Utc>,
+}
+
+/// Client for `turbo-notes-server`. The server stores opaque objects with
+/// their history; what goes in them is up to the sync engine.
+pub struct ServerBackend {
+    agent: ureq::Agent,
+    url: String,
+    token: String,
+}
+
+impl ServerBackend {
+    pub fn new(config: &ServerSyncConfig) -> Result<Self> {
+        let url = config.url.trim_end_matches('/').to_string();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(UserError::Invalid("Set sync.server.url to the server's http(s) address".into()).into());
+        }
+        Ok(Self {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(60)).build(),
+            url,
+            token: token(config)?,
+        })
+    }
+
+    /// Sends an authenticated request, retrying temporary failures. Returns
+    /// `None` for 404; other errors fail.
+    fn request(&self, method: &str, path: &str, query: &[(&str, &str)], body: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut request = self
+                .agent
+                .request(method, &format!("{}{}", self.url, path))
+                .set("Authorization", &format!("Bearer {}", self.token));
+            for (name, value) in query {
+                request = request.query(name, value);
+            }
+            let result = request.send_bytes(body);
+            let retry = match &result {
+                Ok(_) => false,
+                Err(ureq::Error::Status(status, _)) => *status == 429 || *status >= 500,
+                Err(ureq::Error::Transport(_)) => true,
+            };
+            if retry && attempt < ATTEMPTS {
+                thread::sleep(Duration::from_millis(500 * 2u64.pow(attempt - 1)));
+                continue;
+            }
+
+            return match result {
+                Ok(response) => {
+                    let mut body = Vec::new();
+                    response.into_reader().read_to_end(&mut body)?;
+                    Ok(Some(body))
+                }
+                Err(ureq::Error::Status(404, _)) => Ok(None),
+                Err(ureq::Error::Status(401, _)) => {
+                    Err(UserError::Invalid("The sync server rejected the token".into()).into())
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    let message = response.into_string().unwrap_or_default();
+                    Err(anyhow::anyhow!("Sync server {} {} failed with status {}: {}", method, path, status, message.trim()))
+                }
+                Err(ureq::Error::Transport(error)) => {
+                    Err(anyhow::anyhow!("Could not reach the sync server at {}: {}", self.url, error))
+                }
+            };
+        }
+    }
+
+    fn json<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<Option<T>> {
+        match self.request("GET", path, query, &[])? {
+            Some(body) => Ok(Some(serde_json::from_slice(&body)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Publishes a page, returning its share.
+    pub fn share(&self, new: &NewShare) -> Result<Share> {
+        let body = self
+            .request("POST", "/v1/shares", &[], &serde_json::to_vec(new)?)?
+            .ok_or_else(|| anyhow::anyhow!("The sync server does not support sharing; upgrade it"))?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Shares that have not expired, newest first.
+    pub fn shares(&self) -> Result<Vec<Share>> {
+        self.json("/v1/shares", &[])?
+            .ok_or_else(|| anyhow::anyhow!("The sync server does not support sharing; upgrade it"))
+    }
+
+    /// Takes a share down. Returns false if there was no such share.
+    pub fn revoke(&self, id: &str) -> Result<bool> {
+        Ok(self.request("DELETE", &format!("/v1/shares/{}", uri_encode(id, false)), &[], &[])?.is_some())
+    }
+
+    /// The link to give out for `share`.
+    pub fn link(&self, share: &Share) -> String {
+        format!("{}/s/{}", self.url, share.id)
+    }
+}
+
+/// The sync server of `config`, for sharing notes. Fails unless sync is set
+/// up with a server.
+pub fn share_server(config: &Config) -> Result<ServerBackend> {
+    if config.sync.backend != SyncBackendKind::Server {
+        return Err(UserError::Invalid(
+            "Sharing needs a sync server: set sync.backend to server and sync.server.url".into(),
+        )
+        .into());
+    }
+    ServerBackend::new(&config.sync.server)
+}
+
+impl SyncBackend for ServerBackend {
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        Ok(self.json("/v1/objects", &[("prefix", prefix)])?.unwrap_or_default())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.request("GET", &object_path(key), &[], &[])
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let body = self
+            .request("PUT", &object_path(key), &[], data)?
+            .ok_or_else(|| anyhow::anyhow!("The sync server did not store {}", key))?;
+        let stored: Stored = serde_json::from_slice(&body)?;
+        Ok(stored.version)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.request("DELETE", &object_path(key), &[], &[])?;
+        Ok(())
+    }
+
+    fn versions(&self, key: &str) -> Result<Vec<RemoteVersion>> {
+        Ok(self.json(&format!("/v1/versions/{}", uri_encode(key, false)), &[])?.unwrap_or_default())
+    }
+
+    fn get_version(&self, key: &str, version: &str) -> Result<Vec<u8>> {
+        self.request("GET", &object_path(key), &[("version", version)], &[])?
+            .ok_or_else(|| UserError::NotFound(format!("No version {} of {}", version, key)).into())
+    }
+}
+
+/// The server's live feed of changes, so a client can sync as soon as
+/// another device uploads something.
+pub struct ChangeFeed {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl ChangeFeed {
+    pub async fn connect(config: &ServerSyncConfig) -> Result<Self> {
+        let url = config.url.trim_end_matches('/');
+        let url = match url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}/v1/ws", rest),
+            Some((_, rest)) => format!("ws://{}/v1/ws", rest),
+            None => return Err(UserError::Invalid("Set sync.server.url to the server's http(s) address".into()).into()),
+        };
+        let mut request = url.into_client_request()?;
+        request
+            .headers_mut()
+            .insert("Authorization", format!("Bearer {}", token(config)?).parse()?);
+        let (stream, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not open the sync server's change feed: {}", e))?;
+        Ok(Self { stream })
+    }
+
+    /// Next change announced by the server, or `None` once the connection
+    /// is closed.
+    pub async fn next(&mut self) -> Option<Change> {
+        while let Some(message) = self.stream.next().await {
+            match message.ok()? {
+                Message::Text(text) => {
+                    if let Ok(change) = serde_json::from_str(&text) {
+                        return Some(change);
+                    }
+                }
+                Message::Close(_) => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+fn token(config: &ServerSyncConfig) -> Result<String> {
+    match config.token.as_str() {
+        "" => std::env::var(TOKEN_VAR)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| UserError::Invalid(format!("No sync token: set sync.server.token or {}", TOKEN_VAR)).into()),
+        token => Ok(token.to_string()),
+    }
+}
+
+fn object_path(key: &str) -> String {
+    format!("/v1/objects/{}", uri_encode(key, false))
+}
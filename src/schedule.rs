@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::fs;
+
+use crate::config::Config;
+use crate::notes::{ExportFormat, Note, NotesManager};
+use crate::plugins::{self, Plugins};
+use crate::vault;
+
+/// File in the notes directory recording when the scheduled export last ran
+const STAMP_FILE: &str = ".scheduled-export";
+
+/// When the scheduled export last ran, if ever.
+pub fn last_export(config: &Config) -> Option<DateTime<Utc>> {
+    let stamp = fs::read_to_string(config.notes_dir.join(STAMP_FILE)).ok()?;
+    DateTime::parse_from_rfc3339(stamp.trim()).ok().map(|time| time.with_timezone(&Utc))
+}
+
+/// Whether the scheduled export is enabled and its interval has passed.
+pub fn export_due(config: &Config, now: DateTime<Utc>) -> bool {
+    let schedule = &config.scheduled_export;
+    let interval = Duration::hours(schedule.interval_hours.min(i64::MAX as u64) as i64);
+    schedule.enabled && last_export(config).is_none_or(|last| now - last >= interval)
+}
+
+/// Runs the scheduled export if it is due. Returns the number of notes
+/// exported, or `None` if it was not due.
+pub async fn export_if_due(config: &Config) -> Result<Option<usize>> {
+    if !export_due(config, Utc::now()) {
+        return Ok(None);
+    }
+    export_now(config).await.map(Some)
+}
+
+/// Exports all notes to the scheduled export directory. Markdown exports
+/// mirror the notes: files of notes that no longer exist are removed.
+/// Formats that are not built in are written by the plugin adding them.
+pub async fn export_now(config: &Config) -> Result<usize> {
+    // Read from disk, since other instances may have changed the notes
+    let notes_manager = NotesManager::new(&config.notes_dir).await?;
+    let notes: Vec<&Note> = notes_manager.notes.iter().collect();
+    let dir = config.scheduled_export_dir();
+    let name = &config.scheduled_export.format;
+    let format = ExportFormat::from_name(name).unwrap_or(ExportFormat::Markdown);
+
+    match format {
+        _ if !plugins::is_builtin_format(name) => {
+            let plugins = Plugins::load(config);
+            let (_, added) = plugins.export_format(name).ok_or_else(|| anyhow!("No plugin adds export format '{}'", name))?;
+            let path = dir.join(format!("notes.{}", added.extension));
+            fs::create_dir_all(&dir)?;
+            let partial = path.with_extension("partial");
+            fs::write(&partial, plugins.export(name, &notes)?)?;
+            fs::rename(&partial, &path)?;
+        }
+        ExportFormat::Markdown => {
+            let written = vault::export_files(&notes, &config.notes_dir, &dir)?;
+            vault::prune(&dir, &written)?;
+        }
+        format => {
+            fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("notes.{}", format.extension()));
+            // Replace the file in one step so indexers never see half of it
+            let partial = path.with_extension("partial");
+            fs::write(&partial, NotesManager::export_selection(&notes, format, config)?)?;
+            fs::rename(&partial, &path)?;
+        }
+    }
+
+    fs::write(config.notes_dir.join(STAMP_FILE), Utc::now().to_rfc3339())?;
+    Ok(notes.len())
+}
@@ -0,0 +1,456 @@
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use clap::{Arg, Command};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+
+use turbo_notes::crypto;
+use turbo_notes::remote::{Change, NewShare, Share, Stored};
+use turbo_notes::sync::{RemoteObject, RemoteVersion};
+
+/// Environment variable read when `--token` is not given.
+const TOKEN_VAR: &str = "TURBO_NOTES_SERVER_TOKEN";
+/// Largest object accepted, well above any note
+const MAX_OBJECT: usize = 64 * 1024 * 1024;
+
+/// Every version of every object, in `index.json`. Object contents live in
+/// `blobs/<seq>`, so keys never become file paths.
+#[derive(Default, Serialize, Deserialize)]
+struct Index {
+    seq: u64,
+    /// Versions of each key, oldest first
+    objects: BTreeMap<String, Vec<Entry>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    modified: DateTime<Utc>,
+    deleted: bool,
+}
+
+/// A note page shared by a client, in `shares.json`. Unlike objects, shared
+/// pages are readable without the token, by anyone who has the link.
+#[derive(Clone, Serialize, Deserialize)]
+struct SharedPage {
+    title: String,
+    html: String,
+    created: DateTime<Utc>,
+    expires: DateTime<Utc>,
+}
+
+struct Store {
+    dir: PathBuf,
+    index: Index,
+    /// Shared pages by their secret id
+    shares: BTreeMap<String, SharedPage>,
+}
+
+impl Store {
+    fn open(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(dir.join("blobs"))?;
+        let path = dir.join("index.json");
+        let index = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| anyhow::anyhow!("{} is damaged: {}", path.display(), e))?
+        } else {
+            Index::default()
+        };
+        let path = dir.join("shares.json");
+        let shares = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| anyhow::anyhow!("{} is damaged: {}", path.display(), e))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { dir, index, shares })
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = self.dir.join("index.json");
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec(&self.index)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    /// Newest version of `key`, unless it was deleted.
+    fn latest(&self, key: &str) -> Option<&Entry> {
+        self.index.objects.get(key)?.last().filter(|entry| !entry.deleted)
+    }
+
+    fn list(&self, prefix: &str) -> Vec<RemoteObject> {
+        self.index
+            .objects
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter_map(|(key, _)| {
+                let entry = self.latest(key)?;
+                Some(RemoteObject { key: key.clone(), version: entry.seq.to_string() })
+            })
+            .collect()
+    }
+
+    fn read(&self, key: &str, version: Option<&str>) -> Result<Option<Vec<u8>>> {
+        let entry = match version {
+            Some(version) => self
+                .index
+                .objects
+                .get(key)
+                .and_then(|entries| entries.iter().find(|entry| entry.seq.to_string() == version && !entry.deleted)),
+            None => self.latest(key),
+        };
+        match entry {
+            Some(entry) => Ok(Some(fs::read(self.dir.join("blobs").join(entry.seq.to_string()))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Records a new version of `key`: its contents, or a deletion.
+    fn record(&mut self, key: &str, data: Option<&[u8]>) -> Result<Change> {
+        let seq = self.index.seq + 1;
+        if let Some(data) = data {
+            let path = self.dir.join("blobs").join(seq.to_string());
+            let partial = path.with_extension("partial");
+            fs::write(&partial, data)?;
+            fs::rename(&partial, &path)?;
+        }
+        self.index.seq = seq;
+        self.index.objects.entry(key.to_string()).or_default().push(Entry {
+            seq,
+            modified: Utc::now(),
+            deleted: data.is_none(),
+        });
+        self.save()?;
+        Ok(Change {
+            seq,
+            key: key.to_string(),
+            version: seq.to_string(),
+            deleted: data.is_none(),
+        })
+    }
+
+    fn changes(&self, since: u64) -> Vec<Change> {
+        let mut changes: Vec<Change> = self
+            .index
+            .objects
+            .iter()
+            .flat_map(|(key, entries)| {
+                entries.iter().filter(move |entry| entry.seq > since).map(move |entry| Change {
+                    seq: entry.seq,
+                    key: key.clone(),
+                    version: entry.seq.to_string(),
+                    deleted: entry.deleted,
+                })
+            })
+            .collect();
+        changes.sort_by_key(|change| change.seq);
+        changes
+    }
+
+    /// Writes the shares, dropping expired ones.
+    fn save_shares(&mut self) -> Result<()> {
+        let now = Utc::now();
+        self.shares.retain(|_, page| page.expires > now);
+        let path = self.dir.join("shares.json");
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec(&self.shares)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    fn add_share(&mut self, new: NewShare) -> Result<Share> {
+        let id = hex::encode(crypto::random_bytes::<16>()?);
+        let page = SharedPage { title: new.title, html: new.html, created: Utc::now(), expires: new.expires };
+        let share = Share { id: id.clone(), title: page.title.clone(), created: page.created, expires: page.expires };
+        self.shares.insert(id, page);
+        self.save_shares()?;
+        Ok(share)
+    }
+
+    /// Shares that have not expired, newest first.
+    fn shares(&self) -> Vec<Share> {
+        let now = Utc::now();
+        let mut shares: Vec<Share> = self
+            .shares
+            .iter()
+            .filter(|(_, page)| page.expires > now)
+            .map(|(id, page)| Share { id: id.clone(), title: page.title.clone(), created: page.created, expires: page.expires })
+            .collect();
+        shares.sort_by_key(|share| std::cmp::Reverse(share.created));
+        shares
+    }
+
+    fn shared_page(&self, id: &str) -> Option<&SharedPage> {
+        self.shares.get(id).filter(|page| page.expires > Utc::now())
+    }
+
+    /// Takes a share down. Returns false if there was no such share.
+    fn revoke(&mut self, id: &str) -> Result<bool> {
+        if self.shares.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save_shares()?;
+        Ok(true)
+    }
+
+    fn versions(&self, key: &str) -> Vec<RemoteVersion> {
+        let entries = self.index.objects.get(key).map(Vec::as_slice).unwrap_or_default();
+        entries
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, entry)| RemoteVersion {
+                version: entry.seq.to_string(),
+                modified: Some(entry.modified),
+                latest: i + 1 == entries.len(),
+                deleted: entry.deleted,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    store: Arc<Mutex<Store>>,
+    token: Arc<String>,
+    changes: broadcast::Sender<Change>,
+}
+
+/// Turns any failure into a 500 with the error message as the body.
+struct ServerError(anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for ServerError {
+    fn from(error: E) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        eprintln!("error: {:#}", self.0);
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", self.0)).into_response()
+    }
+}
+
+type Reply<T> = std::result::Result<T, ServerError>;
+
+fn cli() -> Command {
+    Command::new("turbo-notes-server")
+        .about("Sync server for Turbo Notes")
+        .long_about(
+            "Sync server for Turbo Notes. Stores the encrypted notes uploaded by \
+             'turbo-notes sync' with their full history, and tells connected \
+             clients about changes as they happen. Clients authenticate with the \
+             token given here.",
+        )
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(
+            Arg::new("bind")
+                .short('b')
+                .long("bind")
+                .help("Address to listen on")
+                .default_value("127.0.0.1:7070"),
+        )
+        .arg(
+            Arg::new("data-dir")
+                .short('d')
+                .long("data-dir")
+                .help("Where to keep stored objects [default: <data dir>/turbo-notes-server]")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("token")
+                .long("token")
+                .help("Token clients must send (default: $TURBO_NOTES_SERVER_TOKEN)"),
+        )
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let matches = cli().get_matches();
+    let token = matches
+        .get_one::<String>("token")
+        .cloned()
+        .or_else(|| std::env::var(TOKEN_VAR).ok())
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("No token: pass --token or set {}", TOKEN_VAR))?;
+    let dir = match matches.get_one::<PathBuf>("data-dir") {
+        Some(dir) => dir.clone(),
+        None => dirs::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("No data directory; pass --data-dir"))?
+            .join("turbo-notes-server"),
+    };
+    let bind = matches.get_one::<String>("bind").expect("bind has a default");
+
+    let state = AppState {
+        store: Arc::new(Mutex::new(Store::open(dir.clone())?)),
+        token: Arc::new(token),
+        changes: broadcast::channel(256).0,
+    };
+    let api = Router::new()
+        .route("/v1/objects", get(list))
+        .route("/v1/objects/{*key}", get(read).put(write).delete(delete))
+        .route("/v1/versions/{*key}", get(versions))
+        .route("/v1/changes", get(changes))
+        .route("/v1/ws", get(feed))
+        .route("/v1/shares", get(list_shares).post(create_share))
+        .route("/v1/shares/{id}", axum::routing::delete(revoke_share))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .layer(DefaultBodyLimit::max(MAX_OBJECT));
+    let app = Router::new()
+        .route("/v1/health", get(|| async { "ok" }))
+        .route("/s/{id}", get(shared))
+        .merge(api)
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    println!("Serving {} on {}", dir.display(), listener.local_addr()?);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn authenticate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !crypto::same_bytes(given.as_bytes(), state.token.as_bytes()) {
+        return (StatusCode::UNAUTHORIZED, "Missing or wrong token").into_response();
+    }
+    next.run(request).await
+}
+
+async fn list(State(state): State<AppState>, Query(query): Query<HashMap<String, String>>) -> Json<Vec<RemoteObject>> {
+    let prefix = query.get("prefix").map(String::as_str).unwrap_or_default();
+    Json(state.store.lock().await.list(prefix))
+}
+
+async fn read(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Reply<Response> {
+    let version = query.get("version").map(String::as_str);
+    Ok(match state.store.lock().await.read(&key, version)? {
+        Some(data) => data.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
+}
+
+async fn write(State(state): State<AppState>, Path(key): Path<String>, body: Bytes) -> Reply<Json<Stored>> {
+    let change = state.store.lock().await.record(&key, Some(&body))?;
+    let version = change.version.clone();
+    // No receivers is fine: nobody is watching
+    let _ = state.changes.send(change);
+    Ok(Json(Stored { version }))
+}
+
+async fn delete(State(state): State<AppState>, Path(key): Path<String>) -> Reply<StatusCode> {
+    let mut store = state.store.lock().await;
+    if store.latest(&key).is_none() {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+    let change = store.record(&key, None)?;
+    let _ = state.changes.send(change);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn versions(State(state): State<AppState>, Path(key): Path<String>) -> Json<Vec<RemoteVersion>> {
+    Json(state.store.lock().await.versions(&key))
+}
+
+async fn changes(State(state): State<AppState>, Query(query): Query<HashMap<String, String>>) -> Json<Vec<Change>> {
+    let since = query.get("since").and_then(|since| since.parse().ok()).unwrap_or(0);
+    Json(state.store.lock().await.changes(since))
+}
+
+async fn list_shares(State(state): State<AppState>) -> Json<Vec<Share>> {
+    Json(state.store.lock().await.shares())
+}
+
+async fn create_share(State(state): State<AppState>, body: Bytes) -> Reply<Response> {
+    let new: NewShare = match serde_json::from_slice(&body) {
+        Ok(new) => new,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, format!("Not a share: {}", e)).into_response()),
+    };
+    if new.expires <= Utc::now() {
+        return Ok((StatusCode::BAD_REQUEST, "The share would already have expired").into_response());
+    }
+    let share = state.store.lock().await.add_share(new)?;
+    Ok((StatusCode::CREATED, Json(share)).into_response())
+}
+
+async fn revoke_share(State(state): State<AppState>, Path(id): Path<String>) -> Reply<StatusCode> {
+    Ok(match state.store.lock().await.revoke(&id)? {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    })
+}
+
+/// A shared page, for anyone with the link. The page may not run scripts or
+/// load anything, and is kept out of caches and search engines.
+async fn shared(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let store = state.store.lock().await;
+    let Some(page) = store.shared_page(&id) else {
+        return (StatusCode::NOT_FOUND, "This link has expired or was revoked").into_response();
+    };
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'; img-src https:"),
+            (header::CACHE_CONTROL, "no-store"),
+            (header::REFERRER_POLICY, "no-referrer"),
+            (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+        ],
+        page.html.clone(),
+    )
+        .into_response()
+}
+
+async fn feed(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    let receiver = state.changes.subscribe();
+    upgrade.on_upgrade(|socket| send_changes(socket, receiver))
+}
+
+/// Forwards every change to one client until it disconnects.
+async fn send_changes(mut socket: WebSocket, mut changes: broadcast::Receiver<Change>) {
+    loop {
+        tokio::select! {
+            change = changes.recv() => {
+                let change = match change {
+                    Ok(change) => change,
+                    // A slow client missed some; it syncs in full anyway
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let Ok(text) = serde_json::to_string(&change) else { continue };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                _ => {}
+            },
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::{DateTime, FixedOffset};
+
+/// Shells `script` has a hook for
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+const BASH: &str = r#"# turbo-notes shell hook: logs commands to a note for each day
+__turbo_notes_marker=MARKER
+__turbo_notes_log() {
+    local status=$? number command
+    read -r number command <<< "$(HISTTIMEFORMAT= builtin history 1)"
+    # Enter on an empty line shows the last entry again
+    if [[ -n $number && $number != "$__turbo_notes_last" ]]; then
+        __turbo_notes_last=$number
+        if [[ -z $__turbo_notes_marker || $command == *"$__turbo_notes_marker"* ]]; then
+            command turbo-notes shell-hook log -- "$command"
+        fi
+    fi
+    return $status
+}
+read -r __turbo_notes_last _ <<< "$(HISTTIMEFORMAT= builtin history 1)"
+PROMPT_COMMAND="__turbo_notes_log${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+"#;
+
+const ZSH: &str = r#"# turbo-notes shell hook: logs commands to a note for each day
+__turbo_notes_marker=MARKER
+__turbo_notes_log() {
+    if [[ -z $__turbo_notes_marker || $1 == *"$__turbo_notes_marker"* ]]; then
+        command turbo-notes shell-hook log -- "$1"
+    fi
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec __turbo_notes_log
+"#;
+
+const FISH: &str = r#"# turbo-notes shell hook: logs commands to a note for each day
+set -g __turbo_notes_marker MARKER
+function __turbo_notes_log --on-event fish_preexec
+    if test -z "$__turbo_notes_marker"; or test (count (string split -m1 -- "$__turbo_notes_marker" "$argv[1]")) -gt 1
+        command turbo-notes shell-hook log -- "$argv[1]"
+    end
+end
+"#;
+
+/// The hook for `shell` logging commands that contain `marker`, to be
+/// loaded from its startup file. The shell checks for the marker itself, so
+/// other commands do not start turbo-notes.
+pub fn script(shell: &str, marker: &str) -> Option<String> {
+    let (template, marker) = match shell {
+        "bash" => (BASH, posix_quote(marker)),
+        "zsh" => (ZSH, posix_quote(marker)),
+        "fish" => (FISH, fish_quote(marker)),
+        _ => return None,
+    };
+    Some(template.replace("MARKER", &marker))
+}
+
+fn posix_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// The log line for `command` run at `time`: the command as code, then
+/// what follows `marker` as a remark. A command that is only the marker and
+/// a remark, such as `# note: flashed the board`, logs just the remark.
+/// `None` if `command` lacks the marker or there is nothing to log.
+pub fn log_line(command: &str, marker: &str, time: DateTime<FixedOffset>) -> Option<String> {
+    let (run, remark) = match marker {
+        "" => (command.trim(), ""),
+        marker => {
+            let (run, remark) = command.split_once(marker)?;
+            (run.trim(), remark.trim())
+        }
+    };
+    let time = time.format("%H:%M");
+    match (run.is_empty(), remark.is_empty()) {
+        (true, true) => None,
+        (true, false) => Some(format!("- {} {}", time, remark)),
+        (false, true) => Some(format!("- {} {}", time, code(run))),
+        (false, false) => Some(format!("- {} {}: {}", time, code(run), remark)),
+    }
+}
+
+/// `text` as a Markdown code span, fenced with more backticks than it has
+/// in a row.
+fn code(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    if longest == 0 {
+        format!("{}{}{}", fence, text, fence)
+    } else {
+        format!("{} {} {}", fence, text, fence)
+    }
+}
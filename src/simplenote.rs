@@ -0,0 +1,92 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::UserError;
+use crate::import::{has_extension, open_zip, text, Imported, Importer};
+use crate::notes::Note;
+
+/// Path of the notes inside Simplenote's zip export
+const ZIP_NOTES: &str = "source/notes.json";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    active_notes: Vec<SimplenoteNote>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimplenoteNote {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    content: String,
+    creation_date: Option<DateTime<Utc>>,
+    last_modified: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    pinned: bool,
+}
+
+/// Simplenote's export: `notes.json`, or the zip the apps produce, which
+/// holds it as `source/notes.json`. Trashed notes are left out.
+pub struct SimplenoteImporter;
+
+impl Importer for SimplenoteImporter {
+    fn name(&self) -> &'static str {
+        "simplenote"
+    }
+
+    fn detect(&self, path: &Path, input: &[u8]) -> bool {
+        if has_extension(path, "zip") {
+            return open_zip(input).is_ok_and(|mut zip| zip.by_name(ZIP_NOTES).is_ok());
+        }
+        has_extension(path, "json") && text(input).is_ok_and(|text| text.contains("\"activeNotes\""))
+    }
+
+    fn read(&self, input: &[u8], progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+        let export: Export = if input.starts_with(b"PK") {
+            let mut zip = open_zip(input)?;
+            let mut file = zip
+                .by_name(ZIP_NOTES)
+                .map_err(|_| UserError::Invalid(format!("Not a Simplenote export: {} is missing", ZIP_NOTES)))?;
+            let mut json = String::new();
+            file.read_to_string(&mut json)?;
+            parse(&json)?
+        } else {
+            parse(text(input)?)?
+        };
+
+        let total = export.active_notes.len();
+        let mut notes = Vec::with_capacity(total);
+        for (done, item) in export.active_notes.into_iter().enumerate() {
+            progress(done, total);
+            if item.content.trim().is_empty() {
+                continue;
+            }
+            let mut note = Note::new(item.content.replace("\r\n", "\n"));
+            if !item.id.is_empty() {
+                note.id = item.id;
+            }
+            note.created_at = item.creation_date.unwrap_or(note.created_at);
+            note.pinned = item.pinned;
+            for tag in item.tags {
+                note.add_tag(tag.replace(char::is_whitespace, "-"));
+            }
+            note.updated_at = item.last_modified.unwrap_or(note.created_at);
+            notes.push(note);
+        }
+        Ok(Imported {
+            notes,
+            ..Default::default()
+        })
+    }
+}
+
+fn parse(json: &str) -> Result<Export> {
+    serde_json::from_str(json).map_err(|e| UserError::Invalid(format!("Not a valid Simplenote export: {}", e)).into())
+}
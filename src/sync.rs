@@ -0,0 +1,842 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::{Config, SyncBackendKind};
+use crate::crypto::{self, Cipher};
+use crate::error::UserError;
+use crate::notes::{Note, NotesManager};
+use crate::quick;
+use crate::remote::ServerBackend;
+use crate::s3::S3Backend;
+
+/// Environment variable read before prompting for the sync passphrase.
+pub const PASSPHRASE_VAR: &str = "TURBO_NOTES_SYNC_PASSPHRASE";
+/// Environment variable read before prompting for a new sync passphrase.
+pub const NEW_PASSPHRASE_VAR: &str = "TURBO_NOTES_SYNC_NEW_PASSPHRASE";
+
+/// Unencrypted record of how the store is encrypted: the key derivation
+/// salt and the data key, itself encrypted with the passphrase key.
+const KEY_OBJECT: &str = "key.json";
+const STATE_FILE: &str = "sync-state.json";
+/// Uploaded notes are padded to a multiple of this many bytes, so object
+/// sizes say little about how long notes are.
+const PAD_TO: usize = 512;
+
+/// An object in the store and its current version (an ETag for S3).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteObject {
+    pub key: String,
+    pub version: String,
+}
+
+/// One stored version of an object, from the store's own history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteVersion {
+    pub version: String,
+    pub modified: Option<DateTime<Utc>>,
+    pub latest: bool,
+    /// The object was deleted at this point
+    pub deleted: bool,
+}
+
+/// A place notes sync to. Keys are relative to the store's own prefix.
+/// Calls block on the network, so async code makes them on the blocking
+/// thread pool.
+pub trait SyncBackend: Send + Sync {
+    /// Objects whose key starts with `prefix`.
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>>;
+
+    /// Contents of `key`, or `None` if it does not exist.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Stores `data` under `key` and returns the new version.
+    fn put(&self, key: &str, data: &[u8]) -> Result<String>;
+
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Earlier versions of `key`, newest first. Empty when the store keeps
+    /// no history.
+    fn versions(&self, _key: &str) -> Result<Vec<RemoteVersion>> {
+        Ok(Vec::new())
+    }
+
+    /// Contents of `key` as of `version`.
+    fn get_version(&self, _key: &str, _version: &str) -> Result<Vec<u8>> {
+        Err(UserError::Invalid("This sync store keeps no history".into()).into())
+    }
+}
+
+/// Encrypts everything written to the wrapped store, so the store only
+/// ever sees ciphertext. Notes are encrypted with a random data key, which
+/// is stored encrypted with a key derived from the passphrase; object
+/// names are keyed hashes of note ids.
+pub struct Encrypted {
+    inner: Box<dyn SyncBackend>,
+    record: KeyRecord,
+    /// Derived from the passphrase; only encrypts the data key
+    passphrase_key: [u8; 32],
+    data_key: [u8; 32],
+    cipher: Cipher,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct KeyRecord {
+    kdf: String,
+    salt: String,
+    /// The data key, encrypted with the passphrase key
+    key: String,
+    prefix: String,
+    created: DateTime<Utc>,
+}
+
+/// How a store is encrypted, as shown by `sync key status`.
+#[derive(Debug, Serialize)]
+pub struct KeyStatus {
+    pub initialized: bool,
+    pub kdf: Option<String>,
+    /// When the current data key was made
+    pub created: Option<DateTime<Utc>>,
+}
+
+impl Encrypted {
+    /// Unlocks the store with the passphrase from `passphrase`, which is
+    /// told whether the store is new (so it can ask for confirmation). A new
+    /// store gets a fresh salt and data key.
+    pub fn open(inner: Box<dyn SyncBackend>, passphrase: impl FnOnce(bool) -> Result<String>) -> Result<Self> {
+        let Some(record) = read_record(inner.as_ref())? else {
+            let salt = crypto::random_bytes::<{ crypto::SALT_LEN }>()?;
+            let passphrase_key = crypto::derive_key(&passphrase(true)?, &salt)?;
+            let data_key = crypto::random_bytes::<32>()?;
+            let record = KeyRecord {
+                kdf: "argon2id".to_string(),
+                salt: hex::encode(salt),
+                key: hex::encode(Cipher::from_key(&passphrase_key).encrypt(&data_key)?),
+                prefix: new_prefix()?,
+                created: Utc::now(),
+            };
+            let store = Self::new(inner, record, passphrase_key, data_key);
+            store.write_record()?;
+            return Ok(store);
+        };
+
+        if record.kdf != "argon2id" {
+            return Err(UserError::Invalid(format!("Unsupported key derivation '{}' in the sync store", record.kdf)).into());
+        }
+        let salt = hex::decode(&record.salt).map_err(|_| damaged())?;
+        let passphrase_key = crypto::derive_key(&passphrase(false)?, &salt)?;
+        let key = hex::decode(&record.key).map_err(|_| damaged())?;
+        let data_key = Cipher::from_key(&passphrase_key)
+            .decrypt(&key)
+            .map_err(|_| UserError::Invalid("Wrong sync passphrase (was it changed on another device?)".into()))?
+            .try_into()
+            .map_err(|_| damaged())?;
+        Ok(Self::new(inner, record, passphrase_key, data_key))
+    }
+
+    fn new(inner: Box<dyn SyncBackend>, record: KeyRecord, passphrase_key: [u8; 32], data_key: [u8; 32]) -> Self {
+        Self {
+            inner,
+            record,
+            passphrase_key,
+            data_key,
+            cipher: Cipher::from_key(&data_key),
+        }
+    }
+
+    /// Prefix of the objects holding notes.
+    pub fn notes_prefix(&self) -> &str {
+        &self.record.prefix
+    }
+
+    /// Name of the object holding note `id`.
+    pub fn note_key(&self, id: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.data_key).expect("HMAC takes any key length");
+        mac.update(b"note-name:");
+        mac.update(id.as_bytes());
+        format!("{}{}", self.record.prefix, hex::encode(&mac.finalize().into_bytes()[..16]))
+    }
+
+    /// Re-encrypts the data key under a new passphrase. Notes stay as they
+    /// are; other devices need the new passphrase from their next sync on.
+    pub fn change_passphrase(&mut self, passphrase: &str) -> Result<()> {
+        let salt = crypto::random_bytes::<{ crypto::SALT_LEN }>()?;
+        self.passphrase_key = crypto::derive_key(passphrase, &salt)?;
+        self.record.salt = hex::encode(salt);
+        self.record.key = hex::encode(Cipher::from_key(&self.passphrase_key).encrypt(&self.data_key)?);
+        self.write_record()
+    }
+
+    fn write_record(&self) -> Result<()> {
+        self.inner.put(KEY_OBJECT, &serde_json::to_vec_pretty(&self.record)?)?;
+        Ok(())
+    }
+}
+
+impl SyncBackend for Encrypted {
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        self.inner.list(prefix)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.get(key)?.map(|data| self.cipher.decrypt(&data)).transpose()
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        self.inner.put(key, &self.cipher.encrypt(data)?)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(key)
+    }
+
+    fn versions(&self, key: &str) -> Result<Vec<RemoteVersion>> {
+        self.inner.versions(key)
+    }
+
+    fn get_version(&self, key: &str, version: &str) -> Result<Vec<u8>> {
+        self.cipher.decrypt(&self.inner.get_version(key, version)?)
+    }
+}
+
+fn damaged() -> UserError {
+    UserError::Invalid(format!("The sync store's {} is damaged", KEY_OBJECT))
+}
+
+fn read_record(store: &dyn SyncBackend) -> Result<Option<KeyRecord>> {
+    match store.get(KEY_OBJECT)? {
+        Some(data) => Ok(Some(serde_json::from_slice(&data).map_err(|_| damaged())?)),
+        None => Ok(None),
+    }
+}
+
+/// A prefix no earlier key used, so a rotation cut short leaves nothing
+/// behind that other devices would try to read.
+fn new_prefix() -> Result<String> {
+    Ok(format!("notes-{}/", hex::encode(crypto::random_bytes::<4>()?)))
+}
+
+/// The configured store, without encryption.
+fn open_store(config: &Config) -> Result<Box<dyn SyncBackend>> {
+    Ok(match config.sync.backend {
+        SyncBackendKind::None => {
+            return Err(UserError::Invalid("Sync is not set up: set sync.backend in the config".into()).into())
+        }
+        SyncBackendKind::S3 => Box::new(S3Backend::new(&config.sync.s3)?),
+        SyncBackendKind::Server => Box::new(ServerBackend::new(&config.sync.server)?),
+    })
+}
+
+/// Opens the configured store, asking for the passphrase if needed. A new
+/// store is set up with a passphrase chosen now.
+pub fn open_backend(config: &Config) -> Result<Encrypted> {
+    Encrypted::open(open_store(config)?, |new| {
+        let label = if new { "New sync passphrase" } else { "Sync passphrase" };
+        quick::read_secret(PASSPHRASE_VAR, label, new)
+    })
+}
+
+/// How the store is encrypted. Needs no passphrase.
+pub fn key_status(config: &Config) -> Result<KeyStatus> {
+    let record = read_record(open_store(config)?.as_ref())?;
+    Ok(KeyStatus {
+        initialized: record.is_some(),
+        kdf: record.as_ref().map(|record| record.kdf.clone()),
+        created: record.map(|record| record.created),
+    })
+}
+
+/// Sets up encryption for a store that has none yet.
+pub fn init_key(config: &Config) -> Result<()> {
+    let store = open_store(config)?;
+    if read_record(store.as_ref())?.is_some() {
+        return Err(UserError::Invalid(
+            "The sync store already has a key; use 'sync key passphrase' or 'sync key rotate' to change it".into(),
+        )
+        .into());
+    }
+    Encrypted::open(store, |_| quick::read_secret(PASSPHRASE_VAR, "New sync passphrase", true))?;
+    Ok(())
+}
+
+/// What a note looked like at the last sync, on both sides.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedNote {
+    pub version: String,
+    pub hash: String,
+}
+
+/// A note changed both here and in the store since the last sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub id: String,
+    pub remote_version: String,
+    pub remote: Note,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Sync bookkeeping, kept next to the notes. Written after every upload
+/// or delete, so an interrupted sync picks up where it stopped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub notes: HashMap<String, SyncedNote>,
+    #[serde(default)]
+    pub last_sync: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub conflicts: Vec<Conflict>,
+    /// Set while syncs keep failing, e.g. when offline
+    #[serde(default)]
+    pub failure: Option<SyncFailure>,
+}
+
+/// The last sync attempt failed; cleared by the next one that succeeds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncFailure {
+    pub message: String,
+    pub at: DateTime<Utc>,
+    /// Failed attempts in a row
+    pub attempts: u32,
+}
+
+impl SyncFailure {
+    /// How long to wait before trying again: doubling from 5 seconds, up
+    /// to 5 minutes.
+    pub fn backoff(&self) -> std::time::Duration {
+        let seconds = 5u64.saturating_mul(1 << self.attempts.saturating_sub(1).min(6));
+        std::time::Duration::from_secs(seconds.min(300))
+    }
+
+    /// Whether the backoff since this attempt has passed.
+    pub fn retry_due(&self, now: DateTime<Utc>) -> bool {
+        (now - self.at).to_std().is_ok_and(|waited| waited >= self.backoff())
+    }
+}
+
+/// Summary of where syncing stands, for the header indicator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncHealth {
+    Synced,
+    /// Local changes waiting for the next sync
+    Pending(usize),
+    Conflicts(usize),
+    /// The last sync failed, with this many local changes waiting
+    Failed(usize),
+}
+
+impl SyncState {
+    pub fn path(config: &Config) -> PathBuf {
+        config.notes_dir.join(STATE_FILE)
+    }
+
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = Self::path(config);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| UserError::Invalid(format!("{} is damaged: {}", path.display(), e)).into())
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let path = Self::path(config);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    /// Records a failed sync attempt.
+    pub fn record_failure(&mut self, error: &anyhow::Error) {
+        let attempts = self.failure.as_ref().map_or(0, |failure| failure.attempts) + 1;
+        self.failure = Some(SyncFailure {
+            message: format!("{:#}", error),
+            at: Utc::now(),
+            attempts,
+        });
+    }
+
+    pub fn health(&self, notes: &[Note]) -> SyncHealth {
+        let (changed, deleted) = self.pending(notes);
+        let pending = changed.len() + deleted.len();
+        if self.failure.is_some() {
+            SyncHealth::Failed(pending)
+        } else if !self.conflicts.is_empty() {
+            SyncHealth::Conflicts(self.conflicts.len())
+        } else if pending > 0 {
+            SyncHealth::Pending(pending)
+        } else {
+            SyncHealth::Synced
+        }
+    }
+
+    pub fn conflict(&self, id: &str) -> Option<&Conflict> {
+        self.conflicts.iter().find(|conflict| conflict.id == id)
+    }
+
+    /// Local notes added or changed since the last sync, and ids of synced
+    /// notes deleted here since.
+    pub fn pending<'a>(&self, notes: &'a [Note]) -> (Vec<&'a Note>, Vec<String>) {
+        let changed = notes
+            .iter()
+            .filter(|note| self.notes.get(&note.id).is_none_or(|synced| synced.hash != note_hash(note)))
+            .collect();
+        let mut deleted: Vec<String> = self
+            .notes
+            .keys()
+            .filter(|id| !notes.iter().any(|note| &note.id == *id))
+            .cloned()
+            .collect();
+        deleted.sort();
+        (changed, deleted)
+    }
+}
+
+/// Counts from one sync run.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub uploaded: usize,
+    pub downloaded: usize,
+    pub deleted_remote: usize,
+    pub deleted_local: usize,
+    /// Ids of notes changed on both sides, left untouched until resolved
+    pub conflicts: Vec<String>,
+}
+
+/// Two-way sync against the store, using the state from the last sync as
+/// the common base. A note changed on one side is copied to the other;
+/// a note deleted on one side and unchanged on the other is deleted; a
+/// note edited on one side and deleted on the other is kept. Notes
+/// changed differently on both sides become conflicts and are not touched.
+///
+/// The store is talked to on the blocking thread pool; only merging what
+/// was downloaded into the notes happens here.
+#[tracing::instrument(skip_all, fields(backend = ?config.sync.backend))]
+pub async fn sync(
+    config: &Config,
+    notes_manager: &mut NotesManager,
+    backend: &Arc<Encrypted>,
+    state: &mut SyncState,
+) -> Result<SyncReport> {
+    notes_manager.load_all()?;
+    let local: HashMap<String, Note> = notes_manager.notes.iter().map(|note| (note.id.clone(), note.clone())).collect();
+    let (config_copy, backend_copy, mut state_copy) = (config.clone(), Arc::clone(backend), std::mem::take(state));
+    // The state comes back even from a failed exchange, with what was
+    // done before it failed
+    let (state_copy, exchanged) = tokio::task::spawn_blocking(move || {
+        let exchanged = exchange(&config_copy, &backend_copy, &mut state_copy, local);
+        (state_copy, exchanged)
+    })
+    .await?;
+    *state = state_copy;
+    let Exchanged { report, upserts, removed, pulled } = exchanged?;
+    if !upserts.is_empty() || !removed.is_empty() {
+        notes_manager.merge_notes(upserts, &removed).await?;
+    }
+    for (id, version, hash) in pulled {
+        state.notes.insert(id, SyncedNote { version, hash });
+    }
+    state.last_sync = Some(Utc::now());
+    state.failure = None;
+    state.save(config)?;
+    tracing::info!(
+        uploaded = report.uploaded,
+        downloaded = report.downloaded,
+        deleted_remote = report.deleted_remote,
+        deleted_local = report.deleted_local,
+        conflicts = report.conflicts.len(),
+        "Synced"
+    );
+    Ok(report)
+}
+
+/// What [`exchange`] did with the store, and what is left to do to the
+/// notes.
+struct Exchanged {
+    report: SyncReport,
+    upserts: Vec<Note>,
+    removed: Vec<String>,
+    /// Id, version and hash of each note downloaded, recorded once merged
+    pulled: Vec<(String, String, String)>,
+}
+
+/// The store side of [`sync`]: uploads and deletes what changed here, and
+/// downloads what changed there. Blocks on the network.
+fn exchange(config: &Config, backend: &Encrypted, state: &mut SyncState, local: HashMap<String, Note>) -> Result<Exchanged> {
+    let mut unclaimed: HashMap<String, String> = backend
+        .list(backend.notes_prefix())?
+        .into_iter()
+        .map(|object| (object.key, object.version))
+        .collect();
+    let known = local.keys().chain(state.notes.keys()).chain(state.conflicts.iter().map(|conflict| &conflict.id));
+    let mut remote: HashMap<String, String> = HashMap::new();
+    for id in known {
+        if let Some(version) = unclaimed.remove(&backend.note_key(id)) {
+            remote.insert(id.clone(), version);
+        }
+    }
+    // Notes new to this machine: object names do not give their ids away,
+    // so they are read now
+    let mut fetched: HashMap<String, Note> = HashMap::new();
+    for (key, version) in unclaimed {
+        let note = read_note(backend, &key, None)?;
+        remote.insert(note.id.clone(), version);
+        fetched.insert(note.id.clone(), note);
+    }
+    let ids: BTreeSet<String> = local.keys().chain(remote.keys()).chain(state.notes.keys()).cloned().collect();
+
+    let mut report = SyncReport::default();
+    let mut upserts = Vec::new();
+    let mut removed = Vec::new();
+    let mut pulled = Vec::new();
+
+    for id in &ids {
+        if state.conflict(id).is_some() {
+            report.conflicts.push(id.clone());
+            continue;
+        }
+        let base = state.notes.get(id);
+        let local_note = local.get(id);
+        let local_hash = local_note.map(note_hash);
+        let remote_version = remote.get(id);
+        let local_changed = local_hash.as_deref() != base.map(|synced| synced.hash.as_str());
+        let remote_changed = remote_version != base.map(|synced| &synced.version);
+
+        match (local_changed, remote_changed, local_note, remote_version) {
+            (false, false, _, _) => {}
+            // Changed here, or edited here and deleted there
+            (true, false, Some(note), _) | (true, true, Some(note), None) => {
+                upload(backend, state, note)?;
+                state.save(config)?;
+                report.uploaded += 1;
+            }
+            (true, false, None, _) => {
+                if remote_version.is_some() {
+                    backend.delete(&backend.note_key(id))?;
+                    report.deleted_remote += 1;
+                }
+                state.notes.remove(id);
+                state.save(config)?;
+            }
+            // Changed there, or deleted here and edited there
+            (false, true, _, Some(version)) | (true, true, None, Some(version)) => {
+                let note = match fetched.remove(id) {
+                    Some(note) => note,
+                    None => read_note(backend, &backend.note_key(id), None)?,
+                };
+                pulled.push((id.clone(), version.clone(), note_hash(&note)));
+                upserts.push(note);
+                report.downloaded += 1;
+            }
+            (false, true, _, None) => {
+                if local_note.is_some() {
+                    removed.push(id.clone());
+                    report.deleted_local += 1;
+                }
+                state.notes.remove(id);
+            }
+            (true, true, Some(note), Some(version)) => {
+                let theirs = match fetched.remove(id) {
+                    Some(note) => note,
+                    None => read_note(backend, &backend.note_key(id), None)?,
+                };
+                let hash = note_hash(&theirs);
+                if Some(&hash) == local_hash.as_ref() {
+                    // Same edit made on both sides
+                    state.notes.insert(id.clone(), SyncedNote { version: version.clone(), hash });
+                } else {
+                    state.conflicts.push(Conflict {
+                        id: note.id.clone(),
+                        remote_version: version.clone(),
+                        remote: theirs,
+                        detected_at: Utc::now(),
+                    });
+                    report.conflicts.push(id.clone());
+                }
+            }
+            (true, true, None, None) => {
+                state.notes.remove(id);
+            }
+        }
+    }
+
+    Ok(Exchanged { report, upserts, removed, pulled })
+}
+
+/// How to settle a conflict.
+#[derive(Debug, Clone)]
+pub enum Resolution {
+    /// Overwrite the store with this machine's note
+    Local,
+    /// Replace this machine's note with the store's
+    Remote,
+    /// Take the store's note and keep this machine's as a new note
+    Both,
+    /// Replace both with this note, e.g. merged hunk by hunk
+    Merged(Box<Note>),
+}
+
+/// Settles the conflict on note `id` without touching the store: local
+/// notes and the sync state are updated so that the next sync uploads the
+/// outcome. If the store changed again meanwhile, that sync reports a new
+/// conflict instead. Returns the id of the copy made by `Resolution::Both`.
+pub async fn resolve(
+    config: &Config,
+    notes_manager: &mut NotesManager,
+    state: &mut SyncState,
+    id: &str,
+    resolution: Resolution,
+) -> Result<Option<String>> {
+    let conflict = state
+        .conflict(id)
+        .cloned()
+        .ok_or_else(|| UserError::NotFound(format!("Note {} has no sync conflict", id)))?;
+    notes_manager.load_all()?;
+    let local = notes_manager.notes.iter().find(|note| note.id == id).cloned();
+    // A base hash no note has, so the local side counts as changed
+    let upload_local = SyncedNote {
+        version: conflict.remote_version.clone(),
+        hash: String::new(),
+    };
+    let remote_synced = SyncedNote {
+        version: conflict.remote_version.clone(),
+        hash: note_hash(&conflict.remote),
+    };
+
+    let mut copy_id = None;
+    match resolution {
+        Resolution::Local => {
+            state.notes.insert(id.to_string(), upload_local);
+        }
+        Resolution::Remote => {
+            notes_manager.merge_notes(vec![conflict.remote.clone()], &[]).await?;
+            state.notes.insert(id.to_string(), remote_synced);
+        }
+        Resolution::Both => {
+            let mut upserts = vec![conflict.remote.clone()];
+            if let Some(mut copy) = local {
+                copy.id = Note::new(String::new()).id;
+                copy_id = Some(copy.id.clone());
+                upserts.push(copy);
+            }
+            notes_manager.merge_notes(upserts, &[]).await?;
+            state.notes.insert(id.to_string(), remote_synced);
+        }
+        Resolution::Merged(mut note) => {
+            note.id = id.to_string();
+            notes_manager.merge_notes(vec![*note], &[]).await?;
+            state.notes.insert(id.to_string(), upload_local);
+        }
+    }
+    state.conflicts.retain(|conflict| conflict.id != id);
+    state.save(config)?;
+    Ok(copy_id)
+}
+
+/// Stored versions of note `id`, newest first.
+pub fn history(backend: &Encrypted, id: &str) -> Result<Vec<RemoteVersion>> {
+    backend.versions(&backend.note_key(id))
+}
+
+/// Note `id` as it was at `version`.
+pub fn fetch_version(backend: &Encrypted, id: &str, version: &str) -> Result<Note> {
+    read_note(backend, &backend.note_key(id), Some(version))
+}
+
+/// Moves every note in the store to a new data key, under new object
+/// names, and records the new key. The passphrase stays the same. Returns
+/// how many notes were re-encrypted.
+///
+/// Nothing is deleted before the new key is recorded, so a rotation cut
+/// short leaves the store as it was. History from before the rotation is
+/// not carried over.
+pub fn rotate_key(config: &Config, backend: &mut Encrypted, state: &mut SyncState) -> Result<usize> {
+    let old = backend.list(backend.notes_prefix())?;
+    let mut notes = Vec::new();
+    for object in &old {
+        notes.push((read_note(backend, &object.key, None)?, object.version.clone()));
+    }
+
+    let data_key = crypto::random_bytes::<32>()?;
+    backend.record.key = hex::encode(Cipher::from_key(&backend.passphrase_key).encrypt(&data_key)?);
+    backend.record.prefix = new_prefix()?;
+    backend.record.created = Utc::now();
+    backend.data_key = data_key;
+    backend.cipher = Cipher::from_key(&data_key);
+
+    // Versions from the old store map to the new ones, so this machine
+    // does not see its own rotation as a change
+    let mut moved: HashMap<String, String> = HashMap::new();
+    for (note, version) in &notes {
+        moved.insert(version.clone(), put_note(backend, note)?);
+    }
+    backend.write_record()?;
+    for object in &old {
+        backend.delete(&object.key)?;
+    }
+
+    for synced in state.notes.values_mut() {
+        if let Some(version) = moved.get(&synced.version) {
+            synced.version = version.clone();
+        }
+    }
+    for conflict in &mut state.conflicts {
+        if let Some(version) = moved.get(&conflict.remote_version) {
+            conflict.remote_version = version.clone();
+        }
+    }
+    state.save(config)?;
+    Ok(notes.len())
+}
+
+/// Content hash used to tell whether a note changed since the last sync.
+/// Its place in the list, how far it was read and how it is stored here
+/// are this machine's own, so reordering changes no note.
+pub fn note_hash(note: &Note) -> String {
+    let synced = Note { sort_order: 0, reading: None, stored_apart: false, ..note.clone() };
+    let json = serde_json::to_vec(&synced).unwrap_or_default();
+    hex::encode(Sha256::digest(json))
+}
+
+/// Encrypts and uploads `note`, padded with trailing spaces (which JSON
+/// ignores). Returns the new version.
+fn put_note(backend: &Encrypted, note: &Note) -> Result<String> {
+    let mut json = serde_json::to_vec(note)?;
+    json.resize(json.len().div_ceil(PAD_TO) * PAD_TO, b' ');
+    backend.put(&backend.note_key(&note.id), &json)
+}
+
+fn upload(backend: &Encrypted, state: &mut SyncState, note: &Note) -> Result<()> {
+    let version = put_note(backend, note)?;
+    state.notes.insert(
+        note.id.clone(),
+        SyncedNote {
+            version,
+            hash: note_hash(note),
+        },
+    );
+    Ok(())
+}
+
+/// Reads and decrypts the note in object `key`, at `version` if given.
+fn read_note(backend: &Encrypted, key: &str, version: Option<&str>) -> Result<Note> {
+    let data = match version {
+        Some(version) => backend.get_version(key, version)?,
+        None => backend
+            .get(key)?
+            .ok_or_else(|| anyhow::anyhow!("{} disappeared from the sync store during the sync", key))?,
+    };
+    let note: Note = serde_json::from_slice(&data)
+        .map_err(|e| UserError::Invalid(format!("{} in the sync store is not a note: {}", key, e)))?;
+    if backend.note_key(&note.id) != key {
+        return Err(UserError::Invalid(format!("{} in the sync store holds another note", key)).into());
+    }
+    Ok(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Version and contents of each object
+    type Objects = BTreeMap<String, (String, Vec<u8>)>;
+
+    /// A store in memory, shared so the test can look inside it.
+    #[derive(Clone, Default)]
+    struct Memory {
+        objects: Arc<Mutex<Objects>>,
+    }
+
+    impl SyncBackend for Memory {
+        fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+            let objects = self.objects.lock().unwrap();
+            Ok(objects
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, (version, _))| RemoteObject { key: key.clone(), version: version.clone() })
+                .collect())
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.objects.lock().unwrap().get(key).map(|(_, data)| data.clone()))
+        }
+
+        fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+            let version = hex::encode(crypto::random_bytes::<8>()?);
+            self.objects.lock().unwrap().insert(key.to_string(), (version.clone(), data.to_vec()));
+            Ok(version)
+        }
+
+        fn delete(&self, key: &str) -> Result<()> {
+            self.objects.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn order_and_reading_do_not_change_the_hash() {
+        let note = Note::new("a note".to_string());
+        let moved = Note { sort_order: 7, reading: Some(crate::reading::Reading { line: 3, read: 40 }), ..note.clone() };
+        assert_eq!(note_hash(&moved), note_hash(&note));
+        let edited = Note { content: "a changed note".to_string(), ..note.clone() };
+        assert_ne!(note_hash(&edited), note_hash(&note));
+        let tagged = Note { tags: vec!["work".to_string()], ..note.clone() };
+        assert_ne!(note_hash(&tagged), note_hash(&note));
+    }
+
+    fn open(store: &Memory, passphrase: &str) -> Result<Encrypted> {
+        Encrypted::open(Box::new(store.clone()), |_| Ok(passphrase.to_string()))
+    }
+
+    #[test]
+    fn rotating_moves_every_note_to_the_new_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config { notes_dir: dir.path().to_path_buf(), ..Config::default() };
+        let store = Memory::default();
+        let mut backend = open(&store, "old passphrase").unwrap();
+        let mut state = SyncState::default();
+        let notes: Vec<Note> = ["first", "second", "third"]
+            .iter()
+            .map(|content| Note { id: content.to_string(), ..Note::new(content.to_string()) })
+            .collect();
+        for note in &notes {
+            upload(&backend, &mut state, note).unwrap();
+        }
+        let old_prefix = backend.notes_prefix().to_string();
+        let old_keys: Vec<String> = notes.iter().map(|note| backend.note_key(&note.id)).collect();
+
+        assert_eq!(rotate_key(&config, &mut backend, &mut state).unwrap(), 3);
+        assert_ne!(backend.notes_prefix(), old_prefix);
+        assert!(store.list(&old_prefix).unwrap().is_empty());
+        for key in &old_keys {
+            assert!(store.get(key).unwrap().is_none());
+        }
+
+        backend.change_passphrase("new passphrase").unwrap();
+        let wrong = open(&store, "old passphrase").err().unwrap();
+        assert!(wrong.to_string().starts_with("Wrong sync passphrase"));
+
+        // Read back as another device would, with the new passphrase
+        let reopened = open(&store, "new passphrase").unwrap();
+        assert_eq!(reopened.notes_prefix(), backend.notes_prefix());
+        let objects = reopened.list(reopened.notes_prefix()).unwrap();
+        assert_eq!(objects.len(), 3);
+        for note in &notes {
+            let key = reopened.note_key(&note.id);
+            assert!(!old_keys.contains(&key));
+            assert_eq!(read_note(&reopened, &key, None).unwrap().content, note.content);
+            // The versions this machine knows are the moved ones
+            let object = objects.iter().find(|object| object.key == key).unwrap();
+            assert_eq!(state.notes[&note.id].version, object.version);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::crypto;
+use crate::error::UserError;
+use crate::notes::{Note, NotesManager};
+
+/// Cross-reference of notes and Taskwarrior tasks, in the notes directory
+const LINKS_FILE: &str = "taskwarrior.json";
+
+/// A task made for a note's checklist item, or for the note itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    pub uuid: String,
+    pub note_id: String,
+    /// Text of the checklist item; empty for a note with a reminder and no
+    /// checklist
+    #[serde(default)]
+    pub item: String,
+}
+
+/// What a run changed, or with `--dry-run` would change.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Tasks added to Taskwarrior
+    pub added: usize,
+    /// Tasks whose text, tags, project or due date were brought up to date
+    pub updated: usize,
+    /// Items checked off, or reminders cleared, as done in Taskwarrior
+    pub done_in_notes: usize,
+    /// Tasks completed or deleted as their items were checked off or removed
+    pub closed_in_taskwarrior: usize,
+}
+
+/// A task as `task export` has it; only what is compared.
+#[derive(Debug, Deserialize)]
+struct Task {
+    uuid: String,
+    status: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    due: Option<String>,
+}
+
+/// Checklist items of `content`, `- [ ] text` or `- [x] text` (also with
+/// `*`), as text and whether they are checked.
+pub fn checklist(content: &str) -> Vec<(String, bool)> {
+    content.lines().filter_map(checklist_item).map(|(_, text, done)| (text.to_string(), done)).collect()
+}
+
+/// The marker before an item's text, its text, and whether it is checked.
+fn checklist_item(line: &str) -> Option<(&str, &str, bool)> {
+    let rest = line.trim_start().strip_prefix(['-', '*'])?.strip_prefix(' ')?;
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = rest[3..].trim();
+    (!text.is_empty()).then(|| (&line[..line.len() - rest.len()], text, done))
+}
+
+/// `content` with the first open item reading `item` checked off.
+fn check_off(content: &str, item: &str) -> Option<String> {
+    let mut found = false;
+    let lines: Vec<String> = content
+        .lines()
+        .map(|line| match checklist_item(line) {
+            Some((marker, text, false)) if !found && text == item => {
+                found = true;
+                format!("{}[x] {}", marker, text)
+            }
+            _ => line.to_string(),
+        })
+        .collect();
+    found.then(|| lines.join("\n"))
+}
+
+/// What should be open in Taskwarrior: each open checklist item, and each
+/// note with a reminder but no checklist, by note id and item text.
+fn open_items(notes: &[Note]) -> Vec<(&Note, String)> {
+    let mut open = Vec::new();
+    for note in notes {
+        let items = checklist(&note.content);
+        if items.is_empty() && note.remind_at.is_some() {
+            open.push((note, String::new()));
+        }
+        open.extend(items.into_iter().filter(|(_, done)| !done).map(|(text, _)| (note, text)));
+    }
+    open
+}
+
+fn links_path(notes_dir: &Path) -> PathBuf {
+    notes_dir.join(LINKS_FILE)
+}
+
+pub fn load_links(notes_dir: &Path) -> Result<Vec<Link>> {
+    match fs::read_to_string(links_path(notes_dir)) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_links(notes_dir: &Path, links: &[Link]) -> Result<()> {
+    fs::write(links_path(notes_dir), serde_json::to_string_pretty(links)?)?;
+    Ok(())
+}
+
+fn new_uuid() -> Result<String> {
+    let mut bytes = crypto::random_bytes::<16>()?;
+    // Version 4, RFC 4122 variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    Ok(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+fn task_date(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Runs Taskwarrior's `command` with `args`, without hooks or questions,
+/// feeding it `input`. Returns what it printed.
+fn run(command: &str, args: &[&str], input: Option<&str>) -> Result<String> {
+    let mut child = Command::new(command)
+        .args(["rc.confirmation=off", "rc.hooks=off", "rc.verbose=nothing", "rc.json.array=on"])
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                UserError::NotFound(format!("Could not find Taskwarrior's '{}'; install it or pass --command", command)).into()
+            }
+            _ => anyhow::anyhow!("Could not run {}: {}", command, e),
+        })?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("'{} {}' failed: {}", command, args.join(" "), error.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn is_open(status: &str) -> bool {
+    matches!(status, "pending" | "waiting")
+}
+
+/// The task for `item` of `note`, as `task import` takes it. Import
+/// replaces a task whole, so an existing one is started from, keeping what
+/// was added to it in Taskwarrior, such as annotations or a priority.
+fn task_json(uuid: &str, note: &Note, item: &str, existing: Option<&Value>) -> Value {
+    let mut task = existing.cloned().unwrap_or_else(|| {
+        json!({ "uuid": uuid, "status": "pending", "entry": task_date(note.created_at) })
+    });
+    if !task["status"].as_str().is_some_and(is_open) {
+        task["status"] = json!("pending");
+    }
+    task["description"] = json!(if item.is_empty() { note.title() } else { item });
+    task["tags"] = json!(note.tags);
+    let fields = task.as_object_mut().expect("tasks are objects");
+    match &note.notebook {
+        Some(notebook) => fields.insert("project".to_string(), json!(notebook)),
+        None => fields.remove("project"),
+    };
+    match note.remind_at {
+        Some(due) => fields.insert("due".to_string(), json!(task_date(due))),
+        None => fields.remove("due"),
+    };
+    task
+}
+
+/// Whether Taskwarrior's copy of a task differs from what the note says.
+fn differs(task: &Task, wanted: &Value) -> bool {
+    let text = |key: &str| wanted.get(key).and_then(Value::as_str).map(str::to_string);
+    let mut tags = task.tags.clone();
+    let mut wanted_tags: Vec<String> = serde_json::from_value(wanted["tags"].clone()).unwrap_or_default();
+    tags.sort();
+    wanted_tags.sort();
+    !is_open(&task.status)
+        || Some(task.description.clone()) != text("description")
+        || task.project != text("project")
+        || task.due != text("due")
+        || tags != wanted_tags
+}
+
+/// Brings Taskwarrior and the notes in line. Items done in Taskwarrior are
+/// checked off in their notes first (a note with no checklist has its
+/// reminder cleared); then open items become or update tasks, and tasks
+/// for items checked off or removed are completed or deleted. With
+/// `dry_run` only counts what would change.
+pub async fn sync(notes_manager: &mut NotesManager, command: &str, dry_run: bool) -> Result<Report> {
+    notes_manager.load_all()?;
+    let notes_dir = notes_manager.get_notes_directory().clone();
+    let mut links = load_links(&notes_dir)?;
+    let exported: Vec<Value> = serde_json::from_str(&run(command, &["export"], None)?)
+        .map_err(|e| anyhow::anyhow!("Could not read Taskwarrior's export: {}", e))?;
+    let mut tasks: HashMap<String, (Task, Value)> = HashMap::new();
+    for value in exported {
+        let task: Task = serde_json::from_value(value.clone())
+            .map_err(|e| anyhow::anyhow!("Could not read Taskwarrior's export: {}", e))?;
+        tasks.insert(task.uuid.clone(), (task, value));
+    }
+    let mut report = Report::default();
+
+    // Done in Taskwarrior: done in the note too, and no longer linked
+    let mut kept = Vec::new();
+    for link in links {
+        let completed = tasks.get(&link.uuid).is_some_and(|(task, _)| task.status == "completed");
+        let index = notes_manager.notes.iter().position(|note| note.id == link.note_id);
+        match (completed, index) {
+            (true, Some(index)) => {
+                let note = &notes_manager.notes[index];
+                if link.item.is_empty() {
+                    if note.remind_at.is_some() {
+                        report.done_in_notes += 1;
+                        if !dry_run {
+                            notes_manager.set_reminder(index, None).await?;
+                        }
+                    }
+                } else if let Some(content) = check_off(&note.content, &link.item) {
+                    report.done_in_notes += 1;
+                    if !dry_run {
+                        notes_manager.update_note(index, content).await?;
+                    }
+                }
+            }
+            (true, None) => {}
+            (false, _) => kept.push(link),
+        }
+    }
+    links = kept;
+
+    // Open in the notes: add or update their tasks
+    let mut import = Vec::new();
+    let mut wanted = Vec::new();
+    for (note, item) in open_items(&notes_manager.notes) {
+        // The same item twice in a note is one task
+        if wanted.iter().any(|link: &Link| link.note_id == note.id && link.item == item) {
+            continue;
+        }
+        let existing = links.iter().find(|link| link.note_id == note.id && link.item == item).cloned();
+        let link = match existing {
+            Some(link) => link,
+            None => {
+                let link = Link { uuid: new_uuid()?, note_id: note.id.clone(), item };
+                links.push(link.clone());
+                link
+            }
+        };
+        let existing = tasks.get(&link.uuid);
+        let task = task_json(&link.uuid, note, &link.item, existing.map(|(_, value)| value));
+        match existing {
+            // Deleted there on purpose: left alone while the item stays open
+            Some((existing, _)) if existing.status == "deleted" => {}
+            Some((existing, _)) if !differs(existing, &task) => {}
+            Some(_) => {
+                report.updated += 1;
+                import.push(task);
+            }
+            None => {
+                report.added += 1;
+                import.push(task);
+            }
+        }
+        wanted.push(link);
+    }
+
+    // Checked off or gone from the notes: closed in Taskwarrior
+    for link in links.iter().filter(|link| !wanted.contains(link)) {
+        let Some((_, task)) = tasks.get(&link.uuid).filter(|(task, _)| is_open(&task.status)) else {
+            continue;
+        };
+        let checked = notes_manager.get_note_by_id(&link.note_id).is_some_and(|note| {
+            checklist(&note.content).iter().any(|(text, done)| *done && *text == link.item)
+        });
+        let status = if checked { "completed" } else { "deleted" };
+        let mut closed = task.clone();
+        closed["status"] = json!(status);
+        if checked {
+            closed["end"] = json!(task_date(Utc::now()));
+        }
+        report.closed_in_taskwarrior += 1;
+        import.push(closed);
+    }
+
+    if !dry_run {
+        if !import.is_empty() {
+            run(command, &["import", "-"], Some(&serde_json::to_string(&import)?))?;
+        }
+        save_links(&notes_dir, &wanted)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,266 @@
+use anyhow::Result;
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{backend::CrosstermBackend, style::Color, Terminal};
+use std::io::{self, Stdout};
+use std::ops::{Deref, DerefMut};
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Number of live `TerminalGuard`s. Only the outermost guard touches the
+/// terminal state, so the widget can be opened from the main UI.
+static ACTIVE_GUARDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Owns the terminal while the TUI runs and restores it when dropped,
+/// including on early returns via `?`.
+pub struct TerminalGuard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl TerminalGuard {
+    pub fn new() -> Result<Self> {
+        if ACTIVE_GUARDS.fetch_add(1, Ordering::SeqCst) == 0 {
+            if let Err(err) = enter_terminal() {
+                ACTIVE_GUARDS.fetch_sub(1, Ordering::SeqCst);
+                let _ = restore_terminal();
+                return Err(err);
+            }
+        }
+
+        let terminal = match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => terminal,
+            Err(err) => {
+                release_guard();
+                return Err(err.into());
+            }
+        };
+        Ok(Self { terminal })
+    }
+}
+
+impl Deref for TerminalGuard {
+    type Target = Terminal<CrosstermBackend<Stdout>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.terminal
+    }
+}
+
+impl DerefMut for TerminalGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.terminal
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        release_guard();
+    }
+}
+
+fn release_guard() {
+    // The panic hook may already have reset the count to zero
+    let previous = ACTIVE_GUARDS.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+    if previous == Ok(1) {
+        let _ = restore_terminal();
+    }
+}
+
+fn enter_terminal() -> Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    Ok(())
+}
+
+/// Set while a `RawModeGuard` is alive.
+static RAW_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Raw mode without the alternate screen, for inline prompts that draw in
+/// place below the shell prompt. Restored on drop and by the panic hook.
+pub struct RawModeGuard(());
+
+impl RawModeGuard {
+    pub fn new() -> Result<Self> {
+        enable_raw_mode()?;
+        RAW_MODE.store(true, Ordering::SeqCst);
+        Ok(Self(()))
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        if RAW_MODE.swap(false, Ordering::SeqCst) {
+            let _ = disable_raw_mode();
+        }
+    }
+}
+
+/// Temporarily hands the terminal back, e.g. to run an external editor.
+/// Pair with `resume` and redraw everything afterwards.
+pub fn suspend() -> Result<()> {
+    if ACTIVE_GUARDS.load(Ordering::SeqCst) > 0 {
+        restore_terminal()?;
+    }
+    Ok(())
+}
+
+pub fn resume() -> Result<()> {
+    if ACTIVE_GUARDS.load(Ordering::SeqCst) > 0 {
+        enter_terminal()?;
+    }
+    Ok(())
+}
+
+/// Leaves raw mode and the alternate screen and shows the cursor again.
+pub fn restore_terminal() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, Show)?;
+    Ok(())
+}
+
+/// Installs a panic hook that restores the terminal before the panic message
+/// is printed. Release builds abort on panic, so guards never get dropped
+/// there and this hook is the only cleanup that runs.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if ACTIVE_GUARDS.swap(0, Ordering::SeqCst) > 0 {
+            let _ = restore_terminal();
+        }
+        if RAW_MODE.swap(false, Ordering::SeqCst) {
+            let _ = disable_raw_mode();
+        }
+        default_hook(info);
+    }));
+}
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Colors {
+    /// `NO_COLOR` is set, or the terminal is `dumb`
+    None,
+    /// The 16 ANSI colors
+    Basic,
+    /// The 256-color palette
+    Indexed,
+    TrueColor,
+}
+
+/// What the terminal can draw, so the UI can fall back to ASCII markers and
+/// fewer colors on old or minimal terminals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    pub colors: Colors,
+    /// Whether emoji and symbols such as arrows show up, rather than garbage
+    pub unicode: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self { colors: Colors::TrueColor, unicode: true }
+    }
+}
+
+/// Terminals that only draw ASCII
+const ASCII_TERMS: [&str; 4] = ["linux", "dumb", "ansi", "cons25"];
+
+impl Capabilities {
+    /// Reads the capabilities from `NO_COLOR`, `TERM`, `COLORTERM` and the
+    /// locale. Anything not known to be missing is assumed to work.
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let term = var("TERM").unwrap_or_default().to_lowercase();
+        let colorterm = var("COLORTERM").unwrap_or_default().to_lowercase();
+
+        let colors = if var("NO_COLOR").is_some() || term == "dumb" {
+            Colors::None
+        } else if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("direct") {
+            Colors::TrueColor
+        } else if term.contains("256color") {
+            Colors::Indexed
+        } else if term.is_empty() {
+            Colors::TrueColor
+        } else {
+            Colors::Basic
+        };
+
+        let locale = var("LC_ALL").or_else(|| var("LC_CTYPE")).or_else(|| var("LANG"));
+        let utf8 = locale.is_none_or(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+        let unicode = if cfg!(windows) {
+            // The old console host shows emoji as boxes; Windows Terminal does not
+            var("WT_SESSION").is_some() || var("TERM_PROGRAM").is_some()
+        } else {
+            utf8 && !ASCII_TERMS.contains(&term.as_str()) && !term.starts_with("vt")
+        };
+
+        Self { colors, unicode }
+    }
+
+    /// `color` as the terminal can show it: the nearest palette color, or
+    /// the terminal's own colors when there should be none.
+    pub fn fit(&self, color: Color) -> Color {
+        match (self.colors, color) {
+            (_, Color::Reset) | (Colors::TrueColor, _) => color,
+            (Colors::None, _) => Color::Reset,
+            (Colors::Indexed, Color::Rgb(r, g, b)) => {
+                let level = |value: u8| (value as u16 * 5 + 127) / 255;
+                Color::Indexed((16 + 36 * level(r) + 6 * level(g) + level(b)) as u8)
+            }
+            (Colors::Basic, Color::Rgb(..) | Color::Indexed(_)) => nearest_basic(rgb(color)),
+            _ => color,
+        }
+    }
+}
+
+/// xterm's values for the 16 ANSI colors.
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The RGB value of an RGB or 256-palette color.
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index @ 0..=15) => BASIC[index as usize].1,
+        Color::Indexed(index @ 16..=231) => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        Color::Indexed(index) => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+        _ => (0, 0, 0),
+    }
+}
+
+fn nearest_basic((r, g, b): (u8, u8, u8)) -> Color {
+    let distance = |(br, bg, bb): (u8, u8, u8)| {
+        let diff = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        diff(r, br) + diff(g, bg) + diff(b, bb)
+    };
+    BASIC.iter().min_by_key(|(_, value)| distance(*value)).map_or(Color::Reset, |(color, _)| *color)
+}
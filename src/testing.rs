@@ -0,0 +1,181 @@
+//! Drives the app without a terminal: keys go in from a script, and what it
+//! drew comes out as text, for checking flows from tests.
+//!
+//! ```
+//! use turbo_notes::config::Config;
+//! use turbo_notes::testing::Harness;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let dir = std::env::temp_dir().join(format!("turbo-notes-doc-{}", std::process::id()));
+//! let config = Config { notes_dir: dir.clone(), onboarding_completed: true, ..Config::default() };
+//! let mut harness = Harness::new(config, 100, 30).await?;
+//!
+//! harness.keys("nBuy milk<Esc>nCall the vendor<Esc>").await?;
+//! harness.keys("smilk<Enter>").await?;
+//! assert!(harness.contains("1 found"));
+//! harness.keys("<Delete><Esc>").await?;
+//! assert!(!harness.contains("Buy milk"));
+//! assert!(harness.contains("Call the vendor"));
+//!
+//! let app = harness.finish().await?;
+//! assert_eq!(app.notes_manager.notes.len(), 1);
+//! # std::fs::remove_dir_all(dir)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::Terminal;
+
+use crate::app::App;
+use crate::config::Config;
+use crate::error::UserError;
+use crate::terminal::Capabilities;
+
+/// The app on a screen in memory.
+pub struct Harness {
+    pub app: App,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Harness {
+    /// The app on `config`, drawn on a `width` by `height` screen. Point
+    /// `config.notes_dir` at a directory of its own, as the notes are saved
+    /// there as keys change them.
+    pub async fn new(config: Config, width: u16, height: u16) -> Result<Self> {
+        let mut app = App::with_config(config).await?;
+        // The screen is in memory, whatever terminal the tests run in
+        app.ui.capabilities = Capabilities::default();
+        let terminal = Terminal::new(TestBackend::new(width, height))?;
+        let mut harness = Self { app, terminal };
+        harness.app.draw(&mut harness.terminal)?;
+        Ok(harness)
+    }
+
+    /// Handles `key` as if it was pressed, then draws the screen again.
+    pub async fn press(&mut self, key: KeyEvent) -> Result<()> {
+        self.app.handle_key_event(key).await?;
+        if std::mem::take(&mut self.app.needs_clear) {
+            self.terminal.clear()?;
+        }
+        self.app.draw(&mut self.terminal)
+    }
+
+    /// Presses the keys of `script`, as read by [`parse_keys`].
+    pub async fn keys(&mut self, script: &str) -> Result<()> {
+        for key in parse_keys(script)? {
+            self.press(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Takes the control socket like the running app, so commands hand
+    /// their notes to this one.
+    pub async fn listen(&mut self) -> Result<()> {
+        self.app.claim().await
+    }
+
+    /// Answers the requests commands sent since the last call, then draws
+    /// the screen again. Returns whether there were any.
+    pub async fn answer_requests(&mut self) -> Result<bool> {
+        let answered = self.app.answer_pending().await;
+        self.app.draw(&mut self.terminal)?;
+        Ok(answered)
+    }
+
+    /// What was drawn last.
+    pub fn buffer(&self) -> &Buffer {
+        self.terminal.backend().buffer()
+    }
+
+    /// What was drawn last as text, a line per row without trailing spaces.
+    pub fn screen(&self) -> String {
+        let buffer = self.buffer();
+        let area = buffer.area;
+        (area.top()..area.bottom())
+            .map(|y| {
+                let row: String = (area.left()..area.right()).map(|x| buffer.get(x, y).symbol.as_str()).collect();
+                row.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Whether `text` is on the screen, within one row.
+    pub fn contains(&self, text: &str) -> bool {
+        self.screen().lines().any(|line| line.contains(text))
+    }
+
+    /// Waits for pending saves and hands back the app.
+    pub async fn finish(mut self) -> Result<App> {
+        self.app.notes_manager.save_in_foreground().await?;
+        Ok(self.app)
+    }
+}
+
+/// Reads a key script: characters stand for themselves, and named keys go
+/// in angle brackets, like `<Enter>`, `<Esc>`, `<Up>` or `<F11>`, with
+/// `S-`, `C-` and `A-` for Shift, Ctrl and Alt (`<C-c>`, `<S-Down>`).
+/// `<lt>` is a `<`.
+pub fn parse_keys(script: &str) -> Result<Vec<KeyEvent>> {
+    let mut keys = Vec::new();
+    let mut chars = script.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            keys.push(char_key(c, KeyModifiers::NONE));
+            continue;
+        }
+        let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+        keys.push(named_key(&name)?);
+    }
+    Ok(keys)
+}
+
+fn char_key(c: char, modifiers: KeyModifiers) -> KeyEvent {
+    // Terminals report Shift along with capital letters
+    let shift = if c.is_uppercase() { KeyModifiers::SHIFT } else { KeyModifiers::NONE };
+    KeyEvent::new(KeyCode::Char(c), modifiers | shift)
+}
+
+fn named_key(name: &str) -> Result<KeyEvent> {
+    let invalid = || UserError::Invalid(format!("Unknown key in script: <{}>", name));
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+        modifiers |= match prefix {
+            "S" => KeyModifiers::SHIFT,
+            "C" => KeyModifiers::CONTROL,
+            "A" => KeyModifiers::ALT,
+            _ => return Err(invalid().into()),
+        };
+        rest = key;
+    }
+    let code = match rest {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        "BackTab" => KeyCode::BackTab,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        _ if rest.chars().count() == 1 => return Ok(char_key(rest.chars().next().unwrap_or_default(), modifiers)),
+        _ => match rest.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => return Err(invalid().into()),
+        },
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
@@ -0,0 +1,25 @@
+use std::process::{Command, Stdio};
+
+use crate::notes::Origin;
+
+/// The tmux pane in focus: its title and working directory. Asked of tmux
+/// rather than read from `TMUX_PANE`, which a popup does not have, so from
+/// `display-popup` this is the pane the popup was opened over. `None`
+/// outside tmux.
+pub fn pane() -> Option<Origin> {
+    std::env::var_os("TMUX")?;
+    let output = Command::new("tmux")
+        .args(["display-message", "-p", "#{pane_title}\t#{pane_current_path}"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (title, path) = text.trim_end_matches(['\r', '\n']).split_once('\t')?;
+    let origin = Origin {
+        title: title.trim().to_string(),
+        path: path.trim().to_string(),
+    };
+    (origin != Origin::default()).then_some(origin)
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use std::process::{Child, Command};
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+#[cfg(not(windows))]
+use crate::error::UserError;
+
+/// Something picked from the tray icon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayAction {
+    /// The icon was clicked, or on Linux, Widget picked from its menu
+    ToggleWidget,
+    QuickNote,
+    OpenNotes,
+    ToggleAutostart,
+    Quit,
+}
+
+/// The tray icon of background mode, shown by a thread of its own while
+/// the process runs, with the terminal windows opened from it.
+pub struct Tray {
+    actions: mpsc::UnboundedReceiver<TrayAction>,
+    /// Window the widget was last opened in
+    widget: Option<Child>,
+    /// Other windows opened, reaped as they close
+    opened: Vec<Child>,
+}
+
+impl Tray {
+    /// Opens the widget in a terminal window, or closes the one opened last
+    /// if it is still there.
+    pub fn toggle_widget(&mut self, config: &Config) -> Result<()> {
+        if let Some(mut widget) = self.widget.take() {
+            if widget.try_wait()?.is_none() {
+                widget.kill()?;
+                widget.wait()?;
+                return Ok(());
+            }
+        }
+        self.widget = Some(open_in_terminal(config, &["--widget"])?);
+        Ok(())
+    }
+
+    /// Opens a terminal window running Turbo Notes with `args`.
+    pub fn open(&mut self, config: &Config, args: &[&str]) -> Result<()> {
+        self.opened.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+        self.opened.push(open_in_terminal(config, args)?);
+        Ok(())
+    }
+}
+
+/// Next action picked from `tray`; never resolves while there is none.
+pub async fn next(tray: &mut Option<Tray>) -> Option<TrayAction> {
+    match tray {
+        Some(tray) => tray.actions.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Runs this program with `args` in a new terminal window, the one
+/// `tray.terminal` opens. The background instance has no terminal to show
+/// anything in, so the new one takes over from it as a launch by hand
+/// would.
+#[cfg(not(windows))]
+fn open_in_terminal(config: &Config, args: &[&str]) -> Result<Child> {
+    let terminal = match config.tray.terminal.trim() {
+        "" => match std::env::var("TERMINAL") {
+            Ok(terminal) if !terminal.trim().is_empty() => format!("{} -e", terminal.trim()),
+            _ => "x-terminal-emulator -e".to_string(),
+        },
+        terminal => terminal.to_string(),
+    };
+    let mut words = terminal.split_whitespace();
+    let program = words.next().expect("the terminal command is not empty");
+    Command::new(program)
+        .args(words)
+        .arg(std::env::current_exe()?)
+        .args(args)
+        .spawn()
+        .map_err(|e| UserError::Invalid(format!("Could not open a terminal with '{}': {}; set tray.terminal", terminal, e)).into())
+}
+
+/// Runs this program with `args` in a console window of its own.
+#[cfg(windows)]
+fn open_in_terminal(_config: &Config, args: &[&str]) -> Result<Child> {
+    use std::os::windows::process::CommandExt;
+    // CREATE_NEW_CONSOLE
+    const NEW_CONSOLE: u32 = 0x0000_0010;
+    Ok(Command::new(std::env::current_exe()?).args(args).creation_flags(NEW_CONSOLE).spawn()?)
+}
+
+/// Shows the tray icon, unless `tray.enabled` is off. Builds without the
+/// `tray` feature show none.
+#[cfg(not(feature = "tray"))]
+pub fn show(_config: &Config) -> Result<Option<Tray>> {
+    Ok(None)
+}
+
+/// Shows the tray icon, unless `tray.enabled` is off. The icon lives on a
+/// thread running the event loop it needs, GTK's on Linux, and sends what
+/// is picked from it back here.
+#[cfg(feature = "tray")]
+pub fn show(config: &Config) -> Result<Option<Tray>> {
+    if !config.tray.enabled {
+        return Ok(None);
+    }
+    if cfg!(target_os = "macos") {
+        // The icon must be made on the main thread there, which runs tokio
+        return Err(anyhow::anyhow!("The tray icon is not available on macOS"));
+    }
+    let autostart = crate::autostart::is_autostart_enabled().unwrap_or(false);
+    let (sender, actions) = mpsc::unbounded_channel();
+    let (ready, started) = std::sync::mpsc::channel();
+    std::thread::Builder::new().name("tray".into()).spawn(move || icon::run(sender, autostart, ready))?;
+    started.recv().map_err(|_| anyhow::anyhow!("The tray icon thread stopped"))??;
+    tracing::info!("Showing the tray icon");
+    Ok(Some(Tray { actions, widget: None, opened: Vec::new() }))
+}
+
+#[cfg(feature = "tray")]
+mod icon {
+    use anyhow::Result;
+    use tokio::sync::mpsc::UnboundedSender;
+    use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+
+    use super::TrayAction;
+
+    /// Side of the icon drawn, in pixels
+    const SIZE: u32 = 32;
+
+    /// Menu entries, by id
+    const ENTRIES: [(&str, TrayAction); 5] = [
+        ("widget", TrayAction::ToggleWidget),
+        ("quick-note", TrayAction::QuickNote),
+        ("open-notes", TrayAction::OpenNotes),
+        ("autostart", TrayAction::ToggleAutostart),
+        ("quit", TrayAction::Quit),
+    ];
+
+    /// Makes the icon, reports on `ready` whether that worked, then runs
+    /// the event loop for good.
+    pub fn run(sender: UnboundedSender<TrayAction>, autostart: bool, ready: std::sync::mpsc::Sender<Result<()>>) {
+        #[cfg(target_os = "linux")]
+        if let Err(e) = gtk::init() {
+            let _ = ready.send(Err(anyhow::anyhow!("Could not start GTK for the tray icon: {}", e)));
+            return;
+        }
+        let menu_sender = sender.clone();
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            if let Some(&(_, action)) = ENTRIES.iter().find(|(id, _)| event.id == *id) {
+                let _ = menu_sender.send(action);
+            }
+        }));
+        TrayIconEvent::set_event_handler(Some(move |event: TrayIconEvent| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                let _ = sender.send(TrayAction::ToggleWidget);
+            }
+        }));
+        let icon = match build(autostart) {
+            Ok(icon) => icon,
+            Err(e) => {
+                let _ = ready.send(Err(e));
+                return;
+            }
+        };
+        let _ = ready.send(Ok(()));
+        event_loop();
+        drop(icon);
+    }
+
+    fn build(autostart: bool) -> Result<tray_icon::TrayIcon> {
+        let menu = Menu::new();
+        // Clicks on the icon never arrive on Linux, so the widget is in the menu
+        #[cfg(target_os = "linux")]
+        menu.append(&MenuItem::with_id("widget", "Widget", true, None))?;
+        menu.append_items(&[
+            &MenuItem::with_id("quick-note", "Quick Note", true, None),
+            &MenuItem::with_id("open-notes", "Open Notes", true, None),
+            &PredefinedMenuItem::separator(),
+            &CheckMenuItem::with_id("autostart", "Autostart", true, autostart, None),
+            &PredefinedMenuItem::separator(),
+            &MenuItem::with_id("quit", "Quit", true, None),
+        ])?;
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_menu_on_left_click(false)
+            .with_tooltip("Turbo Notes")
+            .with_icon(Icon::from_rgba(pixels(), SIZE, SIZE)?)
+            .build()?;
+        Ok(icon)
+    }
+
+    /// A note: a pale yellow page with its top right corner folded down,
+    /// and lines of text.
+    fn pixels() -> Vec<u8> {
+        let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE as i32 {
+            for x in 0..SIZE as i32 {
+                let on_page = (3..29).contains(&x) && (2..30).contains(&y);
+                let corner = x >= 21 && y < 10;
+                let pixel = match (on_page, corner) {
+                    (false, _) => [0, 0, 0, 0],
+                    (true, true) if x - 21 > y - 2 => [0, 0, 0, 0],
+                    (true, true) => [214, 180, 70, 255],
+                    _ if (8..25).contains(&x) && [13, 18, 23].contains(&y) => [70, 70, 80, 255],
+                    _ => [250, 226, 120, 255],
+                };
+                rgba.extend_from_slice(&pixel);
+            }
+        }
+        rgba
+    }
+
+    #[cfg(target_os = "linux")]
+    fn event_loop() {
+        gtk::main();
+    }
+
+    #[cfg(windows)]
+    fn event_loop() {
+        use winapi::um::winuser::{DispatchMessageW, GetMessageW, TranslateMessage, MSG};
+        // SAFETY: a plain message loop, on the thread that made the icon
+        unsafe {
+            let mut message: MSG = std::mem::zeroed();
+            while GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) > 0 {
+                TranslateMessage(&message);
+                DispatchMessageW(&message);
+            }
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", windows)))]
+    fn event_loop() {}
+}
@@ -1,4 +1,6 @@
+use chrono::{NaiveDate, TimeDelta, Utc};
 use ratatui::{
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
@@ -7,13 +9,164 @@ use ratatui::{
     },
     Frame,
 };
-use chrono::Local;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
 
 use crate::app::AppMode;
-use crate::notes::NotesManager;
+use crate::autostart::{AutostartSettings, Mode};
+use crate::backup::{BackupBrowser, NoteChange};
+use crate::config::{Config, Theme};
+use crate::natural;
+use crate::copies::ConflictCopy;
+use crate::draft::Draft;
+use crate::graph::{self, Graph};
+use crate::habits;
+use crate::heatmap;
+use crate::input::Input;
+use crate::links;
+use crate::merge::{ConflictSource, ConflictView, Pick, Segment};
+use crate::metadata::{note_metadata, MetadataChip};
+use crate::stats::{self, NotesStats};
+use crate::notes::{ExportFormat, Memo, Note, NotesManager};
+use crate::onboarding::Onboarding;
+use crate::outline;
+use crate::plugins::Plugins;
+use crate::pomodoro::Timer;
+use crate::profile;
+use crate::qr;
+use crate::reading;
+use crate::reminders;
+use crate::remote::Share;
+use crate::saver::SaveState;
+use crate::snippets::{self, Template};
+use crate::sync::SyncHealth;
+use crate::terminal::{Capabilities, Colors};
+use crate::wizard::{SetupStep, SetupWizard};
+
+/// Below this many columns or rows the compact layout is used
+const COMPACT_WIDTH: u16 = 60;
+const COMPACT_HEIGHT: u16 = 16;
+
+/// Characters of a note's title shown next to it on the graph
+const GRAPH_LABEL: usize = 12;
+
+/// Everything the main screen needs to render one frame.
+pub struct ViewState<'a> {
+    pub notes_manager: &'a NotesManager,
+    pub config: &'a Config,
+    pub current_input: &'a Input,
+    pub selected_note: Option<usize>,
+    pub mode: &'a AppMode,
+    pub onboarding: Option<&'a Onboarding>,
+    /// Indices into `notes_manager.notes` shown in the list
+    pub visible_notes: &'a [usize],
+    /// Text of the active search filter, if any
+    pub filter: Option<&'a str>,
+    /// Ids of notes selected with Space
+    pub marked: &'a HashSet<String>,
+    /// One-off message shown in place of the key hints
+    pub status: Option<&'a str>,
+    pub export_format: ExportFormat,
+    /// What the export dialog will export, e.g. "selected notes"
+    pub export_scope: &'a str,
+    /// Number of notes the export dialog will export
+    pub export_count: usize,
+    pub conflict: Option<&'a ConflictView>,
+    /// Conflicted copy of the notes file under review
+    pub copy: Option<&'a ConflictCopy>,
+    pub backups: Option<&'a BackupBrowser>,
+    pub autostart: Option<&'a AutostartSettings>,
+    pub setup: Option<&'a SetupWizard>,
+    /// Profile in use, `None` for the default one
+    pub profile: Option<&'a str>,
+    /// Profiles in the switcher, and the selected one
+    pub profiles: &'a [String],
+    pub profile_pick: usize,
+    /// Links of the selected note in the link picker, and the picked one
+    pub links: &'a [String],
+    pub link_pick: usize,
+    /// Shares on the sync server with their links, and the selected one
+    pub shares: &'a [(Share, String)],
+    pub share_pick: usize,
+    /// QR code being shown and what it holds
+    pub qr: Option<&'a (String, qr::Code)>,
+    /// How long the voice memo being recorded has run
+    pub recording: Option<Duration>,
+    /// Selected habit in the habits view
+    pub habit_pick: usize,
+    /// Numbers on the stats screen with the notes written or edited each
+    /// day, and the day picked on its heatmap
+    pub stats: Option<&'a (NotesStats, HashMap<NaiveDate, usize>)>,
+    pub stats_day: NaiveDate,
+    /// Notes of the day picked on the heatmap, and the selected one
+    pub day_notes: &'a [usize],
+    pub day_pick: usize,
+    /// Notes joined by wiki links, and the picked node
+    pub graph: Option<&'a Graph>,
+    pub graph_pick: usize,
+    /// Snippets matching the picker's query, and the picked one
+    pub snippets: &'a [usize],
+    pub snippet_pick: usize,
+    /// Snippet being filled in: its title, its text and the fields filled so far
+    pub snippet: Option<&'a (String, Template, Vec<String>)>,
+    /// Tags offered for the note being saved, with whether each is picked
+    pub tag_suggestions: &'a [(String, bool)],
+    pub suggestion_pick: usize,
+    /// Plugins, for their commands and the badges in the notes list
+    pub plugins: &'a Plugins,
+    /// Plugin commands matching the palette's query, and the picked one
+    pub palette: &'a [usize],
+    pub palette_pick: usize,
+    /// Sync indicator in the header; `None` when sync is not set up
+    pub sync_health: Option<&'a SyncHealth>,
+    /// Selected entry in the reminders inbox
+    pub reminder_pick: usize,
+    /// Running pomodoro, counted down in the footer
+    pub pomodoro: Option<&'a Timer>,
+    /// Words written today and the daily word goal, when there is one
+    pub word_goal: Option<(usize, usize)>,
+    /// Showing the read-later queue instead of all notes
+    pub read_later: bool,
+    /// Typing a meeting note, whose new lines get the time
+    pub meeting: bool,
+    /// Line the note view is scrolled to
+    pub detail_scroll: usize,
+    /// Outline shown next to the note view, its picked heading and the
+    /// collapsed ones
+    pub outline_open: bool,
+    pub outline_pick: usize,
+    pub outline_collapsed: &'a HashSet<usize>,
+    /// Recent log lines, filled in while the log view is open
+    pub log: &'a [String],
+    /// Lines the log view is scrolled up from its end
+    pub log_scroll: usize,
+    /// File the log is also written to
+    pub log_file: Option<&'a Path>,
+    /// Unsaved draft offered for restoring
+    pub draft: Option<&'a Draft>,
+}
 
 pub struct UI {
     pub list_state: ListState,
+    /// Area of the notes list from the last draw, used for mouse hit-testing
+    pub notes_area: Rect,
+    /// Area of the note view's text from the last draw, for scrolling it
+    /// and telling how much was read
+    pub detail_area: Rect,
+    /// Cells of the graph's nodes and the area they were laid out in, kept
+    /// while it is open and its size stays the same
+    pub graph_cells: Option<(Rect, Vec<(u16, u16)>)>,
+    /// Full-screen distraction-free editor, toggled with F11 or `z`
+    pub zen_mode: bool,
+    /// High-contrast output with text markers instead of color or emoji
+    pub accessible: bool,
+    /// Colors from the config
+    pub theme: Theme,
+    /// What the terminal can draw
+    pub capabilities: Capabilities,
+    /// Set while the terminal is too small for the full layout
+    compact: bool,
 }
 
 impl Default for UI {
@@ -26,33 +179,159 @@ impl UI {
     pub fn new() -> Self {
         Self {
             list_state: ListState::default(),
+            notes_area: Rect::default(),
+            detail_area: Rect::default(),
+            graph_cells: None,
+            zen_mode: false,
+            accessible: false,
+            theme: Theme::default(),
+            capabilities: Capabilities::detect(),
+            compact: false,
         }
     }
 
-    pub fn draw(
-        &mut self,
-        f: &mut Frame,
-        notes_manager: &NotesManager,
-        current_input: &str,
-        selected_note: Option<usize>,
-        mode: &AppMode,
-    ) {
+    /// Whether states must show without color: in accessible mode, or when
+    /// the terminal shows none.
+    fn monochrome(&self) -> bool {
+        self.accessible || self.capabilities.colors == Colors::None
+    }
+
+    /// Whether to use ASCII markers instead of emoji and symbols.
+    fn ascii(&self) -> bool {
+        self.accessible || !self.capabilities.unicode
+    }
+
+    /// Whether popups take the full width rather than a centered box.
+    fn full_width(&self) -> bool {
+        self.accessible || self.compact
+    }
+
+    /// Borders of the header and footer, which the compact layout drops.
+    fn bar_borders(&self) -> Borders {
+        if self.compact {
+            Borders::NONE
+        } else {
+            Borders::ALL
+        }
+    }
+
+    /// Side by side, or one above the other in the compact layout.
+    fn columns(&self) -> Direction {
+        if self.compact {
+            Direction::Vertical
+        } else {
+            Direction::Horizontal
+        }
+    }
+
+    /// Picks the compact layout for a terminal of `size`: no borders around
+    /// the header and footer, and one column instead of two.
+    fn fit(&mut self, size: Rect) {
+        self.compact = size.width < COMPACT_WIDTH || size.height < COMPACT_HEIGHT;
+    }
+
+    /// Brings what was drawn down to what the terminal shows: theme colors
+    /// given as `#rrggbb` on a 16-color terminal, box borders without
+    /// Unicode.
+    fn fit_buffer(&self, buffer: &mut Buffer) {
+        let capabilities = self.capabilities;
+        if capabilities.colors == Colors::TrueColor && capabilities.unicode {
+            return;
+        }
+        for cell in &mut buffer.content {
+            cell.fg = capabilities.fit(cell.fg);
+            cell.bg = capabilities.fit(cell.bg);
+            if !capabilities.unicode {
+                let plain = match cell.symbol.as_str() {
+                    "─" | "━" | "═" => "-",
+                    "│" | "┃" | "║" => "|",
+                    "┌" | "┐" | "└" | "┘" | "╭" | "╮" | "╰" | "╯" | "├" | "┤" | "┬" | "┴" | "┼" => "+",
+                    _ => continue,
+                };
+                cell.set_symbol(plain);
+            }
+        }
+    }
+
+    /// Style for key hints; accessible mode drops color-only distinctions.
+    fn key_style(&self, color: Color) -> Style {
+        if self.monochrome() {
+            Style::default().fg(Color::White).add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            Style::default().fg(color).add_modifier(Modifier::BOLD)
+        }
+    }
+
+    /// Style for secondary text such as timestamps and hints.
+    fn muted_style(&self) -> Style {
+        if self.monochrome() {
+            Style::default().fg(Color::White)
+        } else {
+            Style::default().fg(Color::Gray)
+        }
+    }
+
+    /// A color from the theme; `fallback` if it does not parse, which
+    /// validation normally rules out.
+    fn theme_color(&self, color: &str, fallback: Color) -> Color {
+        color.parse().unwrap_or(fallback)
+    }
+
+    /// Returns `plain` in accessible mode or on terminals without emoji,
+    /// `fancy` otherwise.
+    fn glyph<'a>(&self, fancy: &'a str, plain: &'a str) -> &'a str {
+        if self.ascii() {
+            plain
+        } else {
+            fancy
+        }
+    }
+
+    /// Maps a terminal cell to the index of the note rendered there.
+    pub fn note_at(&self, column: u16, row: u16) -> Option<usize> {
+        let inner = Block::default().borders(Borders::ALL).inner(self.notes_area);
+        if column < inner.x || column >= inner.x + inner.width || row < inner.y || row >= inner.y + inner.height {
+            return None;
+        }
+        Some(self.list_state.offset() + (row - inner.y) as usize)
+    }
+
+    pub fn draw(&mut self, f: &mut Frame, view: &ViewState) {
+        let ViewState {
+            notes_manager,
+            config,
+            current_input,
+            selected_note,
+            mode,
+            onboarding,
+            ..
+        } = *view;
+
+        self.fit(f.size());
+        if self.zen_mode && *mode == AppMode::Insert {
+            self.draw_zen_editor(f, f.size(), config, current_input);
+            self.fit_buffer(f.buffer_mut());
+            return;
+        }
+
+        let bars = if self.compact { 1 } else { 3 };
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(3),  // Header
-                Constraint::Min(0),     // Main content
-                Constraint::Length(3),  // Footer/Input
+                Constraint::Length(bars),  // Header
+                Constraint::Min(0),        // Main content
+                Constraint::Length(bars),  // Footer/Input
             ])
             .split(f.size());
 
         // Header
-        self.draw_header(f, chunks[0]);
+        let read_only = notes_manager.is_read_only().then(|| notes_manager.unsaved());
+        self.draw_header(f, chunks[0], view.sync_health, view.profile, read_only);
 
         // Main content
         match mode {
             AppMode::Normal => {
-                self.draw_notes_list(f, chunks[1], notes_manager, selected_note);
+                self.draw_notes_list(f, chunks[1], view);
             }
             AppMode::Insert => {
                 self.draw_input_mode(f, chunks[1], current_input, "INSERT MODE - Type your note:");
@@ -63,93 +342,345 @@ impl UI {
             AppMode::Widget => {
                 self.draw_widget_content(f, chunks[1], current_input);
             }
+            AppMode::Detail | AppMode::Outline => {
+                if let Some(note) = selected_note.and_then(|i| notes_manager.notes.get(i)) {
+                    self.draw_note_detail(f, chunks[1], note, view);
+                }
+            }
+            AppMode::Tag => {
+                self.draw_input_mode(f, chunks[1], current_input, "TAG - Enter a tag for the selected note:");
+            }
+            AppMode::Export => {
+                self.draw_notes_list(f, chunks[1], view);
+                self.draw_export_dialog(f, chunks[1], view);
+            }
+            AppMode::Conflict => {
+                if let Some(conflict) = view.conflict {
+                    self.draw_conflict(f, chunks[1], conflict, config);
+                }
+            }
+            AppMode::Copy => {
+                self.draw_notes_list(f, chunks[1], view);
+                if let Some(copy) = view.copy {
+                    self.draw_copy_dialog(f, chunks[1], copy, view);
+                }
+            }
+            AppMode::Backups => {
+                if let Some(browser) = view.backups {
+                    self.draw_backups(f, chunks[1], browser, config);
+                }
+            }
+            AppMode::Reminders => {
+                self.draw_reminders(f, chunks[1], &notes_manager.notes, view.reminder_pick, config);
+            }
+            AppMode::Shares => {
+                self.draw_shares(f, chunks[1], view.shares, view.share_pick, config);
+            }
+            AppMode::Recording => {
+                if let Some(note) = selected_note.and_then(|i| notes_manager.notes.get(i)) {
+                    self.draw_note_detail(f, chunks[1], note, view);
+                }
+                if let Some(elapsed) = view.recording {
+                    self.draw_recording(f, chunks[1], elapsed);
+                }
+            }
+            AppMode::Habits => {
+                self.draw_habits(f, chunks[1], &notes_manager.notes, view.habit_pick, config);
+            }
+            AppMode::Stats | AppMode::StatsDay => {
+                if let Some((numbers, activity)) = view.stats {
+                    self.draw_stats(f, chunks[1], numbers, activity, view.stats_day, config);
+                }
+                if *mode == AppMode::StatsDay {
+                    self.draw_day_notes(f, chunks[1], &notes_manager.notes, view.day_notes, view.day_pick, view.stats_day);
+                }
+            }
+            AppMode::Graph => {
+                if let Some(graph) = view.graph {
+                    self.draw_graph(f, chunks[1], graph, &notes_manager.notes, view.graph_pick);
+                }
+            }
+            AppMode::Snippets => {
+                self.draw_notes_list(f, chunks[1], view);
+                self.draw_snippets(f, chunks[1], &notes_manager.notes, view.snippets, view.snippet_pick, current_input);
+            }
+            AppMode::SnippetField => {
+                if let Some((title, template, values)) = view.snippet {
+                    let count = template.fields().len();
+                    if let Some(field) = template.fields().get(values.len()) {
+                        let keeps = if field.default.is_empty() { "" } else { ", empty keeps it" };
+                        let prompt =
+                            format!("SNIPPET {} - {} ({} of {}{}):", title, field.label(), values.len() + 1, count, keeps);
+                        self.draw_input_mode(f, chunks[1], current_input, &prompt);
+                    }
+                }
+            }
+            AppMode::TagSuggestions => {
+                self.draw_input_mode(f, chunks[1], current_input, "INSERT MODE - Type your note:");
+                self.draw_tag_suggestions(f, chunks[1], view.tag_suggestions, view.suggestion_pick, &notes_manager.tag_counts());
+            }
+            AppMode::Qr => {
+                if let Some((title, code)) = view.qr {
+                    self.draw_qr(f, chunks[1], title, code);
+                }
+            }
+            AppMode::Profiles => {
+                self.draw_profiles(f, chunks[1], view.profiles, view.profile_pick, view.profile);
+            }
+            AppMode::Palette => {
+                self.draw_notes_list(f, chunks[1], view);
+                self.draw_palette(f, chunks[1], view.plugins, view.palette, view.palette_pick, view.current_input);
+            }
+            AppMode::Draft => {
+                self.draw_notes_list(f, chunks[1], view);
+                if let Some(draft) = view.draft {
+                    self.draw_draft_dialog(f, chunks[1], draft, config);
+                }
+            }
+            AppMode::Log => {
+                self.draw_log(f, chunks[1], view.log, view.log_scroll, view.log_file);
+            }
+            AppMode::SaveElsewhere => {
+                self.draw_notes_list(f, chunks[1], view);
+                self.draw_save_elsewhere_dialog(f, chunks[1], view);
+            }
+            AppMode::Links => {
+                if let Some(note) = selected_note.and_then(|i| notes_manager.notes.get(i)) {
+                    self.draw_note_detail(f, chunks[1], note, view);
+                }
+                self.draw_links(f, chunks[1], view.links, view.link_pick);
+            }
+            AppMode::Setup => {
+                if let Some(wizard) = view.setup {
+                    self.draw_setup(f, chunks[1], wizard);
+                }
+            }
+            AppMode::Autostart => {
+                if let Some(settings) = view.autostart {
+                    self.draw_autostart(f, chunks[1], settings);
+                }
+            }
+            AppMode::Remind => {
+                self.draw_input_mode(
+                    f,
+                    chunks[1],
+                    current_input,
+                    "REMIND - When? e.g. 10m, 2h, 14:30, tomorrow, next fri or 2026-10-20 09:00 (empty clears it):",
+                );
+            }
+        }
+
+        if let Some(onboarding) = onboarding {
+            self.draw_onboarding(f, chunks[1], onboarding);
         }
 
         // Footer
-        self.draw_footer(f, chunks[2], mode);
+        self.draw_footer(f, chunks[2], view);
+        self.fit_buffer(f.buffer_mut());
     }
 
-    pub fn draw_widget(&self, f: &mut Frame, current_input: &str) {
+    /// Quick note widget: the input, then the most recent notes, numbered so
+    /// the input can be appended to one of them. `picked` is the selected
+    /// recent note while the list has focus, and `read_only` is set when
+    /// notes cannot be saved.
+    pub fn draw_widget(
+        &mut self,
+        f: &mut Frame,
+        current_input: &Input,
+        recent: &[&Note],
+        picked: Option<usize>,
+        config: &Config,
+        read_only: bool,
+    ) {
         // Center the widget on screen
-        let area = self.centered_rect(60, 20, f.size());
+        self.fit(f.size());
+        let area = if self.full_width() {
+            f.size()
+        } else {
+            self.centered_rect(60, 60, f.size())
+        };
         
         // Clear the background
         f.render_widget(Clear, area);
         
+        let title = if read_only { " Quick Note (read-only: notes cannot be saved) " } else { " Quick Note " };
         let block = Block::default()
-            .title(" Quick Note ")
+            .title(title)
             .borders(Borders::ALL)
-            .style(Style::default().fg(Color::Cyan));
+            .style(Style::default().fg(self.theme_color(&self.theme.primary_color, Color::Cyan)));
 
         let inner = block.inner(area);
         f.render_widget(block, area);
 
-        // Split into input and instructions
+        // Split into input, recent notes and instructions
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(3),  // Input
-                Constraint::Min(0),     // Instructions
+                Constraint::Min(3),     // Recent notes
+                Constraint::Length(2),  // Instructions
             ])
             .split(inner);
 
         // Input field
-        let input = Paragraph::new(current_input)
+        // The cursor is shown while typing, not while picking a note
+        let text = match picked {
+            Some(_) => vec![Line::from(current_input.to_string())],
+            None => input_lines(current_input),
+        };
+        let input = Paragraph::new(text)
             .block(
                 Block::default()
                     .title("Note")
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(Color::White)),
+                    .style(Style::default().fg(if picked.is_some() { Color::DarkGray } else { Color::White })),
             )
             .wrap(Wrap { trim: true });
         f.render_widget(input, chunks[0]);
 
+        let items: Vec<ListItem> = recent
+            .iter()
+            .enumerate()
+            .map(|(i, note)| {
+                let selected = picked == Some(i);
+                let mut spans = Vec::new();
+                if self.monochrome() {
+                    spans.push(Span::raw(if selected { "> " } else { "  " }));
+                }
+                spans.push(Span::styled(format!("{} ", i + 1), self.key_style(Color::Yellow)));
+                spans.push(Span::styled(format!("[{}] ", config.format_timestamp(note.updated_at)), self.muted_style()));
+                spans.push(Span::raw(note.preview(50)));
+                let style = match (selected, self.monochrome()) {
+                    (true, true) => Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                    (true, false) => Style::default().bg(Color::DarkGray).fg(Color::White),
+                    (false, _) => Style::default(),
+                };
+                ListItem::new(Line::from(spans)).style(style)
+            })
+            .collect();
+        let list = List::new(items).block(
+            Block::default()
+                .title(if picked.is_some() { "Append to" } else { "Recent" })
+                .borders(Borders::ALL)
+                .style(Style::default().fg(if picked.is_some() { Color::White } else { Color::DarkGray })),
+        );
+        f.render_widget(list, chunks[1]);
+
         // Instructions
-        let instructions = vec![
-            Line::from(vec![
-                Span::raw("Press "),
-                Span::styled("Enter", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                Span::raw(" to save note"),
-            ]),
-            Line::from(vec![
-                Span::raw("Press "),
-                Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                Span::raw(" to cancel"),
-            ]),
-        ];
+        let instructions = match picked {
+            None => vec![
+                Line::from(vec![
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(" save as a new note  "),
+                    Span::styled("Tab", self.key_style(Color::Yellow)),
+                    Span::raw(" append to a recent note"),
+                ]),
+                Line::from(vec![
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(" cancel"),
+                ]),
+            ],
+            Some(_) => vec![
+                Line::from(vec![
+                    Span::styled(format!("1-{}", recent.len()), self.key_style(Color::Yellow)),
+                    Span::raw(" or "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(" append to that note"),
+                ]),
+                Line::from(vec![
+                    Span::styled("Tab", self.key_style(Color::Yellow)),
+                    Span::raw("/"),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(" back to the input"),
+                ]),
+            ],
+        };
 
         let help = Paragraph::new(instructions)
             .alignment(Alignment::Center)
-            .style(Style::default().fg(Color::Gray));
-        f.render_widget(help, chunks[1]);
+            .style(self.muted_style());
+        f.render_widget(help, chunks[2]);
+        self.fit_buffer(f.buffer_mut());
     }
 
-    fn draw_header(&self, f: &mut Frame, area: Rect) {
-        let title = Paragraph::new("🚀 Turbo Notes")
+    /// The title, with sync health on the right and, when the notes cannot
+    /// be saved, the number of changes held on the left.
+    fn draw_header(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        sync_health: Option<&SyncHealth>,
+        profile: Option<&str>,
+        read_only: Option<usize>,
+    ) {
+        let block = Block::default()
+            .borders(self.bar_borders())
+            .style(Style::default().fg(self.theme_color(&self.theme.text_color, Color::White)));
+        let inner = block.inner(area);
+        let mut title = self.glyph("🚀 Turbo Notes", "Turbo Notes").to_string();
+        if let Some(profile) = profile {
+            title.push_str(&format!(" ({})", profile));
+        }
+        let title = Paragraph::new(title)
             .style(
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(self.theme_color(&self.theme.primary_color, Color::Cyan))
                     .add_modifier(Modifier::BOLD),
             )
             .alignment(Alignment::Center)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .style(Style::default().fg(Color::White)),
-            );
+            .block(block);
         f.render_widget(title, area);
+
+        if let Some(health) = sync_health {
+            let (text, color) = match health {
+                SyncHealth::Synced => (self.glyph("✓ synced", "synced").to_string(), Color::Green),
+                SyncHealth::Pending(count) => (format!("{}{} pending", self.glyph("↑ ", ""), count), Color::Yellow),
+                SyncHealth::Conflicts(count) => (format!("{}{} conflicts", self.glyph("⚠ ", ""), count), Color::Magenta),
+                SyncHealth::Failed(0) => (self.glyph("✗ sync error", "sync error").to_string(), Color::Red),
+                SyncHealth::Failed(count) => {
+                    (format!("{}sync error, {} pending", self.glyph("✗ ", ""), count), Color::Red)
+                }
+            };
+            let style = if self.monochrome() {
+                Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(color)
+            };
+            let indicator = Paragraph::new(format!("{} ", text))
+                .style(style)
+                .alignment(Alignment::Right);
+            f.render_widget(indicator, inner);
+        }
+
+        if let Some(unsaved) = read_only {
+            let text = match unsaved {
+                0 => " READ-ONLY".to_string(),
+                unsaved => format!(" READ-ONLY, {} unsaved", unsaved),
+            };
+            let style = if self.monochrome() {
+                Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            };
+            f.render_widget(Paragraph::new(text).style(style), inner);
+        }
     }
 
-    fn draw_notes_list(
-        &mut self,
-        f: &mut Frame,
-        area: Rect,
-        notes_manager: &NotesManager,
-        selected_note: Option<usize>,
-    ) {
+    fn draw_notes_list(&mut self, f: &mut Frame, area: Rect, view: &ViewState) {
+        let ViewState {
+            notes_manager,
+            config,
+            selected_note,
+            visible_notes,
+            filter,
+            marked,
+            ..
+        } = *view;
+        self.notes_area = area;
+
         if notes_manager.notes.is_empty() {
             let empty_msg = Paragraph::new("No notes yet. Press 'n' to create your first note!")
-                .style(Style::default().fg(Color::Gray))
+                .style(self.muted_style())
                 .alignment(Alignment::Center)
                 .block(
                     Block::default()
@@ -161,36 +692,82 @@ impl UI {
             return;
         }
 
-        let items: Vec<ListItem> = notes_manager
-            .notes
+        // Only the rows that fit are built, so drawing stays cheap however
+        // many notes there are.
+        let height = area.height.saturating_sub(2).max(1) as usize;
+        let position = selected_note.and_then(|selected| visible_notes.iter().position(|&i| i == selected));
+        let mut offset = self.list_state.offset().min(visible_notes.len().saturating_sub(height));
+        if let Some(position) = position {
+            offset = offset.clamp((position + 1).saturating_sub(height), position);
+        }
+        let window = &visible_notes[offset..(offset + height).min(visible_notes.len())];
+
+        let items: Vec<ListItem> = window
             .iter()
-            .enumerate()
+            .map(|&i| (i, &notes_manager.notes[i]))
             .map(|(i, note)| {
                 let preview = note.preview(60);
-                let time = note.created_at.with_timezone(&Local).format("%m/%d %H:%M");
+                let time = config.format_timestamp(note.created_at);
                 
-                let content = Line::from(vec![
-                    Span::styled(
-                        format!("[{}] ", time),
-                        Style::default().fg(Color::Gray),
-                    ),
-                    Span::raw(preview),
-                ]);
-
-                let style = if Some(i) == selected_note {
-                    Style::default().bg(Color::DarkGray).fg(Color::White)
-                } else {
-                    Style::default()
+                let selected = Some(i) == selected_note;
+                let mut spans = Vec::new();
+                if self.monochrome() {
+                    spans.push(Span::raw(if selected { "> " } else { "  " }));
+                }
+                if marked.contains(&note.id) {
+                    spans.push(Span::styled(self.glyph("● ", "[x] "), self.key_style(Color::Green)));
+                }
+                // Narrow terminals keep the room for the note itself
+                if !self.compact {
+                    spans.push(Span::styled(format!("[{}] ", time), self.muted_style()));
+                }
+                if note.pinned {
+                    spans.push(Span::raw(self.glyph("📌 ", "[PINNED] ")));
+                }
+                if note.locked {
+                    spans.push(Span::raw(self.glyph("🔒 ", "[LOCKED] ")));
+                }
+                if !note.memos.is_empty() {
+                    let length = Memo::format_length(note.memos.iter().map(|memo| memo.seconds).sum());
+                    spans.push(Span::styled(format!("{}{} ", self.glyph("🎙 ", "[MEMO] "), length), self.muted_style()));
+                }
+                if let Some(percent) = reading::progress(note).filter(|&percent| percent < 100) {
+                    spans.push(Span::styled(format!("{}{}% ", self.glyph("📖 ", "[READ] "), percent), self.muted_style()));
+                }
+                for badge in view.plugins.badges(note) {
+                    spans.push(Span::styled(format!("[{}] ", badge), self.key_style(Color::Magenta)));
+                }
+                spans.extend(self.link_line(&preview).spans);
+                let content = Line::from(spans);
+
+                let style = match (selected, self.monochrome()) {
+                    (true, true) => Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                    (true, false) => Style::default().bg(Color::DarkGray).fg(Color::White),
+                    (false, _) => Style::default(),
                 };
 
                 ListItem::new(content).style(style)
             })
             .collect();
 
+        let view_name = if view.read_later { "read later, least left first" } else { config.sort_mode.label() };
+        let mut title = match filter {
+            Some(filter) => format!(
+                " Notes ({}) - search: {} [{} found, Esc clears] ",
+                view_name,
+                filter,
+                visible_notes.len()
+            ),
+            None => format!(" Notes ({}) ", view_name),
+        };
+        if !marked.is_empty() {
+            title.push_str(&format!("- {} selected ", marked.len()));
+        }
+
         let list = List::new(items)
             .block(
                 Block::default()
-                    .title(" Notes ")
+                    .title(title)
                     .borders(Borders::ALL)
                     .style(Style::default().fg(Color::White)),
             )
@@ -200,116 +777,1933 @@ impl UI {
                     .add_modifier(Modifier::BOLD),
             );
 
-        if let Some(selected) = selected_note {
-            self.list_state.select(Some(selected));
+        let mut window_state = ListState::default().with_selected(position.map(|position| position - offset));
+        f.render_stateful_widget(list, area, &mut window_state);
+        self.list_state.select(position);
+        *self.list_state.offset_mut() = offset;
+    }
+
+    fn draw_note_detail(&mut self, f: &mut Frame, area: Rect, note: &Note, view: &ViewState) {
+        let scroll = view.detail_scroll;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(4),  // Metadata
+                Constraint::Min(0),     // Content
+            ])
+            .split(area);
+
+        // The header says which section is being read
+        let headings = outline::headings(&note.content);
+        let trail = outline::breadcrumb(&headings, scroll);
+        let title = match trail.is_empty() {
+            true => " Note ".to_string(),
+            false => format!(" Note {} {} ", self.glyph("›", ">"), trail.join(self.glyph(" › ", " > "))),
+        };
+        let header = Paragraph::new(Line::from(self.metadata_spans(&note_metadata(note, view.config))))
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::White)),
+            );
+        f.render_widget(header, chunks[0]);
+
+        let mut content_area = chunks[1];
+        if view.outline_open && !headings.is_empty() {
+            let width = (content_area.width / 3).min(32);
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(width), Constraint::Min(0)])
+                .split(content_area);
+            self.draw_outline(f, columns[0], &headings, view, scroll);
+            content_area = columns[1];
         }
 
-        f.render_stateful_widget(list, area, &mut self.list_state);
+        let mut block = Block::default().borders(Borders::ALL);
+        let inner = block.inner(content_area);
+        self.detail_area = inner;
+        let text = &note.content[reading::line_start(&note.content, scroll)..];
+        let shown = reading::shown_to(&note.content, scroll, inner.width as usize, inner.height as usize);
+        // Where the view is, for notes longer than a screen
+        if scroll > 0 || shown < note.content.len() {
+            block = block
+                .title(format!(" {}% ", shown * 100 / note.content.len().max(1)))
+                .title_alignment(Alignment::Right);
+        }
+        let lines: Vec<Line> = visible_part(text, inner).split('\n').map(|line| self.link_line(line)).collect();
+        let content = Paragraph::new(lines).wrap(Wrap { trim: false }).block(block);
+        f.render_widget(content, content_area);
     }
 
-    fn draw_input_mode(&self, f: &mut Frame, area: Rect, input: &str, title: &str) {
-        let input_widget = Paragraph::new(input)
-            .style(Style::default().fg(Color::Yellow))
+    /// Outline of the note's headings, indented by level, with a marker on
+    /// the ones with headings under them. The picked heading is highlighted
+    /// while the outline has the keys, and the section being read otherwise.
+    fn draw_outline(&self, f: &mut Frame, area: Rect, headings: &[outline::Heading], view: &ViewState, scroll: usize) {
+        let shown = outline::shown(headings, view.outline_collapsed);
+        let top = headings.iter().map(|heading| heading.level).min().unwrap_or(1);
+        let items: Vec<ListItem> = shown
+            .iter()
+            .map(|&i| {
+                let heading = &headings[i];
+                let marker = match (outline::has_children(headings, i), view.outline_collapsed.contains(&i)) {
+                    (false, _) => "  ",
+                    (true, true) => self.glyph("▸ ", "+ "),
+                    (true, false) => self.glyph("▾ ", "- "),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::raw("  ".repeat(heading.level - top)),
+                    Span::styled(marker, self.muted_style()),
+                    Span::raw(heading.text.clone()),
+                ]))
+            })
+            .collect();
+        let focused = *view.mode == AppMode::Outline;
+        let highlight = match (focused, self.monochrome()) {
+            (true, true) => Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            (true, false) => Style::default().bg(Color::DarkGray).fg(Color::White),
+            (false, _) => Style::default().add_modifier(Modifier::BOLD),
+        };
+        let picked = match focused {
+            true => view.outline_pick,
+            // The section being read, or the collapsed heading it is under
+            false => {
+                let mut at = outline::current(headings, scroll).unwrap_or(0);
+                while !shown.contains(&at) {
+                    match outline::parent(headings, at) {
+                        Some(parent) => at = parent,
+                        None => break,
+                    }
+                }
+                at
+            }
+        };
+        let mut state = ListState::default();
+        state.select(shown.iter().position(|&i| i == picked));
+        let list = List::new(items).highlight_style(highlight).block(
+            Block::default()
+                .title(" Outline ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White)),
+        );
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// `text` with its links underlined.
+    fn link_line(&self, text: &str) -> Line<'static> {
+        let style = if self.monochrome() {
+            Style::default().add_modifier(Modifier::UNDERLINED)
+        } else {
+            Style::default().fg(Color::LightBlue).add_modifier(Modifier::UNDERLINED)
+        };
+        let mut spans = Vec::new();
+        let mut at = 0;
+        for link in links::find(text) {
+            if link.start > at {
+                spans.push(Span::raw(text[at..link.start].to_string()));
+            }
+            spans.push(Span::styled(text[link.clone()].to_string(), style));
+            at = link.end;
+        }
+        if at < text.len() {
+            spans.push(Span::raw(text[at..].to_string()));
+        }
+        Line::from(spans)
+    }
+
+    /// Link picker over the note's detail view, numbered for opening with a
+    /// digit.
+    fn draw_links(&self, f: &mut Frame, area: Rect, links: &[String], picked: usize) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = (links.len() as u16 + 2).min(area.height);
+            let mut rect = self.centered_rect(70, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
+        let items: Vec<ListItem> = links
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                let number = if i < 9 { format!("{} ", i + 1) } else { "  ".to_string() };
+                ListItem::new(Line::from(vec![Span::styled(number, self.muted_style()), Span::raw(url.clone())]))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select(Some(picked));
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .block(Block::default().title(" Open link ").borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_widget(Clear, dialog);
+        f.render_stateful_widget(list, dialog, &mut state);
+    }
+
+    /// Renders metadata chips as styled spans, reusable anywhere a note's
+    /// metadata is shown on a single line.
+    pub fn metadata_spans(&self, chips: &[MetadataChip]) -> Vec<Span<'static>> {
+        let mut spans = Vec::new();
+        for chip in chips {
+            if self.monochrome() {
+                let label = match chip {
+                    MetadataChip::Pinned | MetadataChip::Locked => chip.label().to_uppercase(),
+                    _ => chip.label(),
+                };
+                spans.push(Span::styled(format!("[{}] ", label), Style::default().fg(Color::White)));
+                continue;
+            }
+
+            let style = match chip {
+                MetadataChip::Pinned => Style::default().fg(Color::Black).bg(Color::Yellow),
+                MetadataChip::Locked => Style::default().fg(Color::Black).bg(Color::Red),
+                MetadataChip::Notebook(_) => Style::default().fg(Color::Black).bg(Color::Blue),
+                MetadataChip::Tag(tag) => Style::default().fg(Color::Black).bg(Self::tag_color(tag)),
+                MetadataChip::Reminder(_) => Style::default().fg(Color::Black).bg(Color::Magenta),
+                MetadataChip::Memo(_) | MetadataChip::Images(_) | MetadataChip::Streak(_) => Style::default().fg(Color::Black).bg(Color::Green),
+                MetadataChip::Read(_) => Style::default().fg(Color::Black).bg(Color::Cyan),
+                MetadataChip::Origin(_)
+                | MetadataChip::Created(_) | MetadataChip::Updated(_) | MetadataChip::Words(_) => {
+                    Style::default().fg(Color::Gray)
+                }
+            };
+            spans.push(Span::styled(format!(" {} ", chip.label()), style));
+            spans.push(Span::raw(" "));
+        }
+        spans
+    }
+
+    /// Picks a stable color per tag so the same tag always looks the same.
+    fn tag_color(tag: &str) -> Color {
+        const PALETTE: [Color; 6] = [
+            Color::Cyan,
+            Color::Green,
+            Color::Magenta,
+            Color::LightBlue,
+            Color::LightGreen,
+            Color::LightMagenta,
+        ];
+        let hash = tag.bytes().fold(0usize, |acc, b| acc.wrapping_mul(31).wrapping_add(b as usize));
+        PALETTE[hash % PALETTE.len()]
+    }
+
+    fn draw_onboarding(&self, f: &mut Frame, area: Rect, onboarding: &Onboarding) {
+        let instructions = onboarding.instructions();
+        let height = (instructions.len() as u16 + 2).min(area.height);
+        let panel = Rect {
+            x: area.x + 1,
+            y: area.y + area.height.saturating_sub(height + 1),
+            width: area.width.saturating_sub(2),
+            height,
+        };
+
+        let lines: Vec<Line> = instructions.iter().map(|line| Line::from(*line)).collect();
+        let tutorial = Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
             .block(
                 Block::default()
-                    .title(title)
+                    .title(onboarding.title())
                     .borders(Borders::ALL)
                     .style(Style::default().fg(Color::Yellow)),
-            )
-            .wrap(Wrap { trim: true });
-        f.render_widget(input_widget, area);
+            );
+        f.render_widget(Clear, panel);
+        f.render_widget(tutorial, panel);
     }
 
-    fn draw_widget_content(&self, f: &mut Frame, area: Rect, input: &str) {
-        let widget_area = self.centered_rect(70, 50, area);
-        
-        let input_widget = Paragraph::new(input)
-            .style(Style::default().fg(Color::Green))
+    fn draw_export_dialog(&self, f: &mut Frame, area: Rect, view: &ViewState) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = 7.min(area.height);
+            let mut rect = self.centered_rect(70, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
+
+        let mut file = vec![Span::raw("File: ")];
+        for mut span in input_lines(view.current_input).into_iter().flat_map(|line| line.spans) {
+            span.patch_style(Style::default().fg(Color::Yellow));
+            file.push(span);
+        }
+        let lines = vec![
+            Line::from(format!("Export {} {}", view.export_count, view.export_scope)),
+            Line::from(vec![
+                Span::raw("Format: "),
+                Span::styled(view.export_format.name(), self.key_style(Color::Cyan)),
+            ]),
+            Line::from(file),
+        ];
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
             .block(
                 Block::default()
-                    .title(" Quick Note Widget ")
+                    .title(" Export ")
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(Color::Green)),
-            )
-            .wrap(Wrap { trim: true });
-        f.render_widget(input_widget, widget_area);
+                    .style(Style::default().fg(Color::White)),
+            );
+        f.render_widget(Clear, dialog);
+        f.render_widget(paragraph, dialog);
     }
 
-    fn draw_footer(&self, f: &mut Frame, area: Rect, mode: &AppMode) {
-        let help_text = match mode {
-            AppMode::Normal => {
-                vec![
-                    Span::raw("Controls: "),
-                    Span::styled("n", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                    Span::raw(":new "),
-                    Span::styled("s", Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
-                    Span::raw(":search "),
-                    Span::styled("w", Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)),
-                    Span::raw(":widget "),
-                    Span::styled("↑↓", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-                    Span::raw(":navigate "),
-                    Span::styled("Enter", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-                    Span::raw(":edit "),
-                    Span::styled("Del", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                    Span::raw(":delete "),
-                    Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                    Span::raw(":quit"),
-                ]
-            }
-            AppMode::Insert => {
-                vec![
-                    Span::raw("INSERT MODE - "),
-                    Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                    Span::raw(": save & exit"),
-                ]
-            }
-            AppMode::Search => {
-                vec![
-                    Span::raw("SEARCH MODE - "),
-                    Span::styled("Enter", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                    Span::raw(": search "),
-                    Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                    Span::raw(": cancel"),
-                ]
-            }
-            AppMode::Widget => {
-                vec![
-                    Span::raw("WIDGET MODE - "),
-                    Span::styled("Enter", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                    Span::raw(": save "),
-                    Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                    Span::raw(": cancel"),
-                ]
-            }
+    /// Summary of a conflicted copy of the notes file and what it would add.
+    fn draw_copy_dialog(&self, f: &mut Frame, area: Rect, copy: &ConflictCopy, view: &ViewState) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = 8.min(area.height);
+            let mut rect = self.centered_rect(70, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
         };
 
-        let help = Paragraph::new(Line::from(help_text))
-            .alignment(Alignment::Center)
+        let (added, changed) = copy.compare(&view.notes_manager.notes);
+        let saved = copy
+            .modified
+            .map(|time| format!("Saved {} by a file sync tool", view.config.format_timestamp(time)))
+            .unwrap_or_else(|| "Saved by a file sync tool".to_string());
+        let lines = vec![
+            Line::from(Span::styled(copy.file_name(), Style::default().fg(Color::Yellow))),
+            Line::from(Span::styled(saved, self.muted_style())),
+            Line::from(""),
+            Line::from(format!("{} notes only in the copy", added.len())),
+            Line::from(format!("{} notes edited differently", changed.len())),
+        ];
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
             .block(
                 Block::default()
+                    .title(" Conflicted copy ")
                     .borders(Borders::ALL)
                     .style(Style::default().fg(Color::White)),
             );
-        f.render_widget(help, area);
+        f.render_widget(Clear, dialog);
+        f.render_widget(paragraph, dialog);
     }
 
-    fn centered_rect(&self, percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-        let popup_layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Percentage((100 - percent_y) / 2),
-                Constraint::Percentage(percent_y),
-                Constraint::Percentage((100 - percent_y) / 2),
-            ])
-            .split(r);
+    fn draw_draft_dialog(&self, f: &mut Frame, area: Rect, draft: &Draft, config: &Config) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = 8.min(area.height);
+            let mut rect = self.centered_rect(70, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
 
-        Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage((100 - percent_x) / 2),
-                Constraint::Percentage(percent_x),
-                Constraint::Percentage((100 - percent_x) / 2),
-            ])
-            .split(popup_layout[1])[1]
+        let lines = vec![
+            Line::from("A note you were typing was not saved:"),
+            Line::from(Span::styled(
+                format!("Last kept {}, {} words", config.format_timestamp(draft.saved_at), draft.word_count()),
+                self.muted_style(),
+            )),
+            Line::from(""),
+            Line::from(Span::styled(draft.content.replace('\n', " "), Style::default().fg(Color::Yellow))),
+        ];
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::default()
+                    .title(" Unsaved draft ")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::White)),
+            );
+        f.render_widget(Clear, dialog);
+        f.render_widget(paragraph, dialog);
+    }
+
+    fn draw_recording(&self, f: &mut Frame, area: Rect, elapsed: Duration) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = 3.min(area.height);
+            let mut rect = self.centered_rect(40, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
+        let text = format!("{}{}", self.glyph("● ", "REC "), Memo::format_length(elapsed.as_secs()));
+        let paragraph = Paragraph::new(Span::styled(text, self.key_style(Color::Red)))
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .title(" Recording a voice memo ")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::White)),
+            );
+        f.render_widget(Clear, dialog);
+        f.render_widget(paragraph, dialog);
+    }
+
+    fn draw_save_elsewhere_dialog(&self, f: &mut Frame, area: Rect, view: &ViewState) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = 7.min(area.height);
+            let mut rect = self.centered_rect(70, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
+
+        let mut dir = vec![Span::raw("Save all notes to: ")];
+        for mut span in input_lines(view.current_input).into_iter().flat_map(|line| line.spans) {
+            span.patch_style(Style::default().fg(Color::Yellow));
+            dir.push(span);
+        }
+        let lines = vec![
+            Line::from(format!(
+                "{} cannot be written, so {} changes are not saved.",
+                view.config.notes_dir.display(),
+                view.notes_manager.unsaved()
+            )),
+            Line::from(""),
+            Line::from(dir),
+        ];
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::default()
+                    .title(" Save elsewhere ")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::White)),
+            );
+        f.render_widget(Clear, dialog);
+        f.render_widget(paragraph, dialog);
+    }
+
+    /// Backups on the left with the selected one compared against the
+    /// current notes on the right; once a backup is opened, its notes on
+    /// the left and the selected note's changes since on the right.
+    fn draw_backups(&self, f: &mut Frame, area: Rect, browser: &BackupBrowser, config: &Config) {
+        let columns = Layout::default()
+            .direction(self.columns())
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let change_label = |change: NoteChange| match change {
+            NoteChange::Unchanged => "same   ",
+            NoteChange::Changed => "changed",
+            NoteChange::Deleted => "deleted",
+        };
+
+        let (items, selected, title): (Vec<ListItem>, usize, String) = match browser.note {
+            None => {
+                let items = browser
+                    .backups
+                    .iter()
+                    .zip(&browser.counts)
+                    .map(|(backup, count)| {
+                        let created = backup
+                            .created_at
+                            .map(|time| config.format_timestamp(time.to_utc()))
+                            .unwrap_or_else(|| backup.id.clone());
+                        let count = match count {
+                            Some(count) => format!("{} notes", count),
+                            None => "damaged".to_string(),
+                        };
+                        let kind = if backup.is_auto() { "" } else { " (manual)" };
+                        ListItem::new(Line::from(vec![
+                            Span::raw(format!("{:<12} ", created)),
+                            Span::styled(format!("{:>10} {:>9}{}", count, stats::format_size(backup.size), kind), self.muted_style()),
+                        ]))
+                    })
+                    .collect();
+                (items, browser.selected, format!(" Backups ({}) ", browser.backups.len()))
+            }
+            Some(note) => {
+                let items = browser
+                    .notes
+                    .iter()
+                    .map(|(change, note)| {
+                        let picked = browser.picked.contains(&note.id);
+                        let style = match change {
+                            NoteChange::Unchanged => self.muted_style(),
+                            _ => Style::default(),
+                        };
+                        ListItem::new(Line::from(vec![
+                            Span::styled(if picked { self.glyph("● ", "[x] ") } else { self.glyph("  ", "[ ] ") }, self.key_style(Color::Green)),
+                            Span::styled(format!("{} ", change_label(*change)), self.key_style(Color::Yellow)),
+                            Span::styled(note.preview(40).replace('\n', " "), style),
+                        ]))
+                    })
+                    .collect();
+                (items, note, format!(" Notes in backup {} ", browser.backup().id))
+            }
+        };
+        let mut state = ListState::default();
+        state.select(Some(selected));
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .block(Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_stateful_widget(list, columns[0], &mut state);
+
+        let lines: Vec<Line> = match (browser.note, &browser.error) {
+            (_, Some(error)) => vec![Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red)))],
+            (None, None) => self.backup_summary(browser, config),
+            (Some(_), None) => {
+                let now = Style::default().fg(if self.monochrome() { Color::White } else { Color::Red });
+                let then = Style::default().fg(if self.monochrome() { Color::White } else { Color::Green });
+                let mut lines = Vec::new();
+                for segment in &browser.preview {
+                    match segment {
+                        Segment::Same(same) => lines.extend(
+                            same.iter().map(|line| Line::from(Span::styled(format!("  {}", line), self.muted_style()))),
+                        ),
+                        Segment::Changed { local, remote } => {
+                            lines.extend(local.iter().map(|line| Line::from(Span::styled(format!("- {}", line), now))));
+                            lines.extend(remote.iter().map(|line| Line::from(Span::styled(format!("+ {}", line), then))));
+                        }
+                    }
+                }
+                lines
+            }
+        };
+        let title = match browser.note {
+            None => " Compared with your notes now ",
+            Some(_) => " Changes since: - now, + in the backup ",
+        };
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(paragraph, columns[1]);
+    }
+
+    /// Reminders inbox: missed reminders, then upcoming ones, soonest first.
+    fn draw_reminders(&self, f: &mut Frame, area: Rect, notes: &[Note], picked: usize, config: &Config) {
+        let now = chrono::Utc::now();
+        let pending = reminders::pending(notes);
+        let missed = pending.iter().filter(|note| reminders::is_due(note, now)).count();
+        let items: Vec<ListItem> = pending
+            .iter()
+            .filter_map(|note| {
+                let at = note.remind_at?;
+                let (label, color) = if reminders::is_due(note, now) {
+                    ("missed  ", Color::Red)
+                } else {
+                    ("upcoming", Color::Green)
+                };
+                Some(ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", label), self.key_style(color)),
+                    Span::raw(format!("{:<12} ", config.format_timestamp(at))),
+                    Span::styled(format!("{:<9} ", reminders::until(at, now)), self.muted_style()),
+                    Span::raw(note.preview(60).replace('\n', " ")),
+                ])))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select(Some(picked.min(pending.len().saturating_sub(1))));
+        let title = format!(" Reminders ({} missed, {} upcoming) ", missed, pending.len() - missed);
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .block(Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Profiles to switch to, the one in use marked.
+    /// Habits with today's state and their streaks, and a calendar of the
+    /// days the picked one was done, as many weeks back as fit.
+    fn draw_habits(&self, f: &mut Frame, area: Rect, notes: &[Note], picked: usize, config: &Config) {
+        let today = config.zone().day(Utc::now());
+        let habits: Vec<&Note> = notes.iter().filter(|note| habits::is_habit(note)).collect();
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(10)])
+            .split(area);
+
+        let items: Vec<ListItem> = habits
+            .iter()
+            .map(|note| {
+                let streaks = habits::streaks(&note.habit_days, today);
+                let (mark, style) = match note.habit_days.binary_search(&today) {
+                    Ok(_) => (self.glyph("✓ ", "[x] "), self.key_style(Color::Green)),
+                    Err(_) => (self.glyph("· ", "[ ] "), self.muted_style()),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(mark, style),
+                    Span::raw(note.title().to_string()),
+                    Span::styled(format!("  {} now, {} at most", streaks.current, streaks.longest), self.muted_style()),
+                ]))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select(Some(picked.min(habits.len().saturating_sub(1))));
+        let list = List::new(items).highlight_style(highlight).block(
+            Block::default()
+                .title(format!(" Habits ({}) - days in a row ", habits.len()))
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White)),
+        );
+        f.render_stateful_widget(list, chunks[0], &mut state);
+
+        let Some(note) = habits.get(picked) else {
+            return;
+        };
+        let block = Block::default()
+            .title(format!(" {} ", note.title()))
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White));
+        let inner = block.inner(chunks[1]);
+        f.render_widget(block, chunks[1]);
+
+        // Weekday names take four columns, and each week two
+        let weeks = heatmap::weeks(today, (inner.width.saturating_sub(4) / 2).clamp(1, 53) as usize, natural::first_day_of_week(config));
+        let mut lines = vec![Line::from(Span::styled(self.month_row(&weeks), self.muted_style()))];
+        for row in 0..7 {
+            let name = weeks.iter().find_map(|week| week[row]).map(|day| day.format("%a ").to_string()).unwrap_or_default();
+            let mut spans = vec![Span::styled(name, self.muted_style())];
+            for week in &weeks {
+                spans.push(match week[row] {
+                    Some(day) if note.habit_days.binary_search(&day).is_ok() => {
+                        Span::styled(self.glyph("■ ", "# "), self.key_style(Color::Green))
+                    }
+                    Some(_) => Span::styled(self.glyph("· ", ". "), self.muted_style()),
+                    None => Span::raw("  "),
+                });
+            }
+            lines.push(Line::from(spans));
+        }
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    /// Graph of the notes joined by wiki links: a dot per note with the
+    /// start of its title, and dotted lines for the links, the picked note's
+    /// in yellow. The layout is worked out again only when the area changes.
+    fn draw_graph(&mut self, f: &mut Frame, area: Rect, graph: &Graph, notes: &[Note], picked: usize) {
+        let title = graph.nodes.get(picked).map_or("", |&i| notes[i].title());
+        let block = Block::default()
+            .title(format!(" Graph ({} notes, {} links) - {} ", graph.nodes.len(), graph.edges.len(), title))
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        if inner.width == 0 || inner.height == 0 {
+            return;
+        }
+        let fresh = self.graph_cells.as_ref().is_some_and(|(laid, cells)| *laid == inner && cells.len() == graph.nodes.len());
+        if !fresh {
+            // Dots stay far enough from the right for a label to follow
+            let room = inner.width.saturating_sub(GRAPH_LABEL as u16 + 2).max(1);
+            self.graph_cells = Some((inner, graph::layout(graph, room, inner.height)));
+        }
+        let Some((_, cells)) = &self.graph_cells else {
+            return;
+        };
+
+        // What each cell shows: 0 nothing, 1 a link, 2 a link of the picked
+        // note, 3 a label, 4 the picked note's label
+        let (width, height) = (inner.width as usize, inner.height as usize);
+        let mut grid = vec![vec![(' ', 0u8); width]; height];
+        for &(from, to) in &graph.edges {
+            let kind = if from == picked || to == picked { 2 } else { 1 };
+            for (x, y) in line(cells[from], cells[to]) {
+                let cell = &mut grid[y as usize][x as usize];
+                if cell.1 < kind {
+                    *cell = (if self.ascii() { '.' } else { '·' }, kind);
+                }
+            }
+        }
+        // The picked note goes last, over any label it would hide
+        let order = (0..cells.len()).filter(|&node| node != picked).chain([picked]);
+        for node in order.filter(|&node| node < cells.len()) {
+            let (x, y) = cells[node];
+            let kind = if node == picked { 4 } else { 3 };
+            let dot = if self.ascii() { 'o' } else { '●' };
+            let label = notes[graph.nodes[node]].title().chars().take(if node == picked { width } else { GRAPH_LABEL });
+            for (i, c) in [dot, ' '].into_iter().chain(label).enumerate() {
+                let Some(cell) = grid[y as usize].get_mut(x as usize + i) else {
+                    break;
+                };
+                *cell = (c, kind);
+            }
+        }
+
+        let style = |kind: u8| match kind {
+            1 => self.muted_style(),
+            2 => self.key_style(Color::Yellow),
+            4 if self.monochrome() => Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            4 => Style::default().bg(Color::DarkGray).fg(Color::White),
+            _ => Style::default(),
+        };
+        let lines: Vec<Line> = grid
+            .into_iter()
+            .map(|row| {
+                let mut spans = Vec::new();
+                let mut run = String::new();
+                let mut kind = row.first().map_or(0, |cell| cell.1);
+                for (c, cell_kind) in row {
+                    if cell_kind != kind {
+                        spans.push(Span::styled(std::mem::take(&mut run), style(kind)));
+                        kind = cell_kind;
+                    }
+                    run.push(c);
+                }
+                spans.push(Span::styled(run, style(kind)));
+                Line::from(spans)
+            })
+            .collect();
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    /// Stats screen: totals and the most used tags, then a heatmap of the
+    /// notes written or edited each day of the last year, as many weeks as
+    /// fit, with `day` picked.
+    fn draw_stats(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        numbers: &NotesStats,
+        activity: &HashMap<NaiveDate, usize>,
+        day: NaiveDate,
+        config: &Config,
+    ) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(5), Constraint::Length(11), Constraint::Min(0)])
+            .split(area);
+
+        let goals = match numbers.goals_reached.first() {
+            Some((last, goal)) => format!(
+                "{} words written today, word goal reached {} times (last {}, {} words)",
+                numbers.words_today,
+                numbers.goals_reached.len(),
+                last,
+                goal
+            ),
+            None => format!("{} words written today", numbers.words_today),
+        };
+        let tags: Vec<String> =
+            numbers.notes_per_tag.iter().take(8).map(|(tag, count)| format!("#{} {}", tag, count)).collect();
+        let summary = vec![
+            Line::from(format!(
+                "{} notes ({} pinned, {} untagged), {} words, {} tags, {}",
+                numbers.total_notes,
+                numbers.pinned_notes,
+                numbers.untagged_notes,
+                numbers.total_words,
+                numbers.notes_per_tag.len(),
+                stats::format_size(numbers.storage_bytes)
+            )),
+            Line::from(goals),
+            Line::from(Span::styled(format!("Most used: {}", tags.join(", ")), self.muted_style())),
+        ];
+        let block = Block::default().title(" Stats ").borders(Borders::ALL).style(Style::default().fg(Color::White));
+        f.render_widget(Paragraph::new(summary).block(block).wrap(Wrap { trim: true }), chunks[0]);
+
+        let today = config.zone().day(Utc::now());
+        let year: usize = (0..=364).filter_map(|ago| activity.get(&(today - TimeDelta::days(ago)))).sum();
+        let block = Block::default()
+            .title(format!(" Activity - {} notes written or edited in the last year ", year))
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White));
+        let inner = block.inner(chunks[1]);
+        f.render_widget(block, chunks[1]);
+
+        // Weekday names take four columns, and each week two; the picked
+        // day stays in view on narrow terminals
+        let count = (inner.width.saturating_sub(4) / 2).clamp(1, 53) as usize;
+        let last = today.min(day + TimeDelta::weeks(count as i64 - 1));
+        let weeks = heatmap::weeks(last, count, natural::first_day_of_week(config));
+        let max = weeks.iter().flatten().flatten().filter_map(|day| activity.get(day)).copied().max().unwrap_or(0);
+        let shades: [&str; heatmap::LEVELS] =
+            if self.ascii() { [".", "-", "+", "*", "#"] } else { ["·", "░", "▒", "▓", "█"] };
+
+        let mut lines = vec![Line::from(Span::styled(self.month_row(&weeks), self.muted_style()))];
+        for row in 0..7 {
+            let name = weeks.iter().find_map(|week| week[row]).map(|day| day.format("%a ").to_string()).unwrap_or_default();
+            let mut spans = vec![Span::styled(name, self.muted_style())];
+            for week in &weeks {
+                let Some(cell) = week[row] else {
+                    spans.push(Span::raw("  "));
+                    continue;
+                };
+                let level = heatmap::level(activity.get(&cell).copied().unwrap_or(0), max);
+                let style = match level {
+                    0 => self.muted_style(),
+                    _ => self.key_style(Color::Green),
+                };
+                let style = if cell == day { style.add_modifier(Modifier::REVERSED) } else { style };
+                spans.push(Span::styled(shades[level], style));
+                spans.push(Span::raw(" "));
+            }
+            lines.push(Line::from(spans));
+        }
+        let written = activity.get(&day).copied().unwrap_or(0);
+        lines.push(Line::from(vec![
+            Span::styled(format!("Less {} More   ", shades.concat()), self.muted_style()),
+            Span::raw(format!("{}: {} {}", day.format("%a %Y-%m-%d"), written, if written == 1 { "note" } else { "notes" })),
+        ]));
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    /// Month names over a heatmap's week columns, after four columns of
+    /// weekday names. A label is left out where the one before runs into its
+    /// column.
+    fn month_row(&self, weeks: &[heatmap::Week]) -> String {
+        let mut months = String::new();
+        for i in 0..weeks.len() {
+            let at = 4 + i * 2;
+            if months.len() <= at {
+                if let Some(label) = heatmap::month_label(weeks, i) {
+                    months.push_str(&" ".repeat(at - months.len()));
+                    months.push_str(&label);
+                }
+            }
+        }
+        months
+    }
+
+    /// Notes written or edited on `day`, over the stats screen.
+    fn draw_day_notes(&self, f: &mut Frame, area: Rect, notes: &[Note], indexes: &[usize], picked: usize, day: NaiveDate) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = (indexes.len() as u16 + 2).min(area.height);
+            let mut rect = self.centered_rect(70, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
+        let items: Vec<ListItem> = indexes
+            .iter()
+            .filter_map(|&i| notes.get(i))
+            .map(|note| ListItem::new(Line::from(note.preview(60).replace('\n', " "))))
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select(Some(picked));
+        let title = format!(" {} ({}) ", day.format("%a %Y-%m-%d"), indexes.len());
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .block(Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_widget(Clear, dialog);
+        f.render_stateful_widget(list, dialog, &mut state);
+    }
+
+    /// Snippet picker over the notes list: the query typed, then the
+    /// snippets matching it, best first.
+    fn draw_snippets(&self, f: &mut Frame, area: Rect, notes: &[Note], matches: &[usize], picked: usize, query: &Input) {
+        let dialog = if self.full_width() { area } else { self.centered_rect(70, 80, area) };
+        let title = match matches.len() {
+            0 => " Snippets - no match ".to_string(),
+            count => format!(" Snippets ({}) ", count),
+        };
+        let block = Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White));
+        let inner = block.inner(dialog);
+        f.render_widget(Clear, dialog);
+        f.render_widget(block, dialog);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+
+        let mut line = vec![Span::styled("> ", self.key_style(Color::Yellow))];
+        line.extend(input_lines(query).into_iter().next().map(|line| line.spans).unwrap_or_default());
+        f.render_widget(Paragraph::new(Line::from(line)), chunks[0]);
+
+        let items: Vec<ListItem> = matches
+            .iter()
+            .filter_map(|&i| notes.get(i))
+            .map(|note| {
+                let preview = snippets::body(note).lines().next().unwrap_or_default().to_string();
+                ListItem::new(Line::from(vec![
+                    Span::raw(note.title().to_string()),
+                    Span::styled(format!("  {}", preview), self.muted_style()),
+                ]))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select((!matches.is_empty()).then_some(picked));
+        f.render_stateful_widget(List::new(items).highlight_style(highlight), chunks[1], &mut state);
+    }
+
+    /// Tags that fit the note being saved, numbered for picking with a digit.
+    fn draw_tag_suggestions(&self, f: &mut Frame, area: Rect, tags: &[(String, bool)], picked: usize, counts: &[(String, usize)]) {
+        let dialog = if self.full_width() {
+            area
+        } else {
+            let height = (tags.len() as u16 + 2).min(area.height);
+            let mut rect = self.centered_rect(50, 100, area);
+            rect.y += (rect.height - height) / 2;
+            rect.height = height;
+            rect
+        };
+        let items: Vec<ListItem> = tags
+            .iter()
+            .enumerate()
+            .map(|(i, (tag, chosen))| {
+                let mark = match (chosen, self.ascii()) {
+                    (true, false) => "☑ ",
+                    (false, false) => "☐ ",
+                    (true, true) => "[x] ",
+                    (false, true) => "[ ] ",
+                };
+                let notes = match counts.iter().find(|(name, _)| name == tag).map_or(0, |(_, count)| *count) {
+                    1 => "  1 note".to_string(),
+                    count => format!("  {} notes", count),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", i + 1), self.muted_style()),
+                    Span::styled(mark, self.key_style(Color::Green)),
+                    Span::styled(format!("#{}", tag), self.key_style(Self::tag_color(tag))),
+                    Span::styled(notes, self.muted_style()),
+                ]))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select(Some(picked));
+        let list = List::new(items)
+            .block(Block::default().title(" Add tags? ").borders(Borders::ALL).style(Style::default().fg(Color::White)))
+            .highlight_style(highlight);
+        f.render_widget(Clear, dialog);
+        f.render_stateful_widget(list, dialog, &mut state);
+    }
+
+    fn draw_profiles(&self, f: &mut Frame, area: Rect, profiles: &[String], picked: usize, active: Option<&str>) {
+        let active = active.unwrap_or(profile::DEFAULT);
+        let items: Vec<ListItem> = profiles
+            .iter()
+            .map(|name| {
+                let mut spans = vec![Span::raw(name.clone())];
+                if name == active {
+                    spans.push(Span::styled(" (in use)", self.muted_style()));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select(Some(picked));
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .block(Block::default().title(" Profiles ").borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Command palette over the notes list: the query typed, then the
+    /// plugin commands matching it, with the plugin each is from.
+    fn draw_palette(&self, f: &mut Frame, area: Rect, plugins: &Plugins, matches: &[usize], picked: usize, query: &Input) {
+        let dialog = if self.full_width() { area } else { self.centered_rect(70, 80, area) };
+        let title = match matches.len() {
+            0 => " Commands - no match ".to_string(),
+            count => format!(" Commands ({}) ", count),
+        };
+        let block = Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White));
+        let inner = block.inner(dialog);
+        f.render_widget(Clear, dialog);
+        f.render_widget(block, dialog);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+
+        let mut line = vec![Span::styled("> ", self.key_style(Color::Yellow))];
+        line.extend(input_lines(query).into_iter().next().map(|line| line.spans).unwrap_or_default());
+        f.render_widget(Paragraph::new(Line::from(line)), chunks[0]);
+
+        let commands = plugins.commands();
+        let items: Vec<ListItem> = matches
+            .iter()
+            .filter_map(|&i| commands.get(i))
+            .map(|&(plugin, command)| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(command.title.clone()),
+                    Span::styled(format!("  {}", plugins.loaded[plugin].manifest.name), self.muted_style()),
+                ]))
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select((!matches.is_empty()).then_some(picked));
+        f.render_stateful_widget(List::new(items).highlight_style(highlight), chunks[1], &mut state);
+    }
+
+    fn draw_shares(&self, f: &mut Frame, area: Rect, shares: &[(Share, String)], picked: usize, config: &Config) {
+        let items: Vec<ListItem> = shares
+            .iter()
+            .map(|(share, link)| {
+                ListItem::new(vec![
+                    Line::from(share.title.clone()),
+                    Line::from(vec![
+                        Span::raw("  "),
+                        Span::styled(link.clone(), self.muted_style()),
+                        Span::styled(format!("  expires {}", config.format_timestamp(share.expires)), self.muted_style()),
+                    ]),
+                ])
+            })
+            .collect();
+        let highlight = if self.monochrome() {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        };
+        let mut state = ListState::default();
+        state.select((!shares.is_empty()).then_some(picked));
+        let title = match shares.len() {
+            0 => " Shares - none; press s on a note to share it ".to_string(),
+            count => format!(" Shares ({}) ", count),
+        };
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .block(Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// A QR code dark on light, whatever the theme, as scanners expect.
+    fn draw_qr(&self, f: &mut Frame, area: Rect, title: &str, code: &qr::Code) {
+        let unicode = self.capabilities.unicode;
+        let (width, height) = code.area(unicode);
+        let block = Block::default()
+            .title(format!(" QR - {} ", title))
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White));
+        let inner = block.inner(area);
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+        if width > inner.width as usize || height > inner.height as usize {
+            // Even the smallest code is too tall for the compact layout, so
+            // count the full header and footer around it
+            let hint = format!(
+                "Make the terminal at least {}x{} to show this code",
+                width + 2,
+                height + 2 + 2 * 3
+            );
+            f.render_widget(Paragraph::new(hint).style(self.muted_style()).wrap(Wrap { trim: true }), inner);
+            return;
+        }
+        let rect = Rect {
+            x: inner.x + (inner.width - width as u16) / 2,
+            y: inner.y + (inner.height - height as u16) / 2,
+            width: width as u16,
+            height: height as u16,
+        };
+        let lines: Vec<Line> = code.lines(unicode).into_iter().map(Line::from).collect();
+        f.render_widget(Paragraph::new(lines).style(Style::default().fg(Color::Black).bg(Color::White)), rect);
+    }
+
+    /// The log lines that fit, ending `scroll` lines before the newest.
+    fn draw_log(&self, f: &mut Frame, area: Rect, lines: &[String], scroll: usize, file: Option<&Path>) {
+        let height = area.height.saturating_sub(2) as usize;
+        let end = lines.len().saturating_sub(scroll);
+        let shown: Vec<Line> = lines[end.saturating_sub(height)..end].iter().map(|line| Line::from(line.as_str())).collect();
+        let title = match file {
+            Some(file) => format!(" Log - also in {} ", file.display()),
+            None => " Log ".to_string(),
+        };
+        let text = if lines.is_empty() {
+            Paragraph::new("Nothing logged yet; start with --log-level debug for more").style(self.muted_style())
+        } else {
+            Paragraph::new(shown)
+        };
+        let log = text.block(Block::default().title(title).borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_widget(log, area);
+    }
+
+    /// The current first-run question with its options, or the answers on
+    /// the last step.
+    fn draw_setup(&self, f: &mut Frame, area: Rect, wizard: &SetupWizard) {
+        let mut lines = vec![Line::from(wizard.question()), Line::from("")];
+        match wizard.step {
+            SetupStep::NotesDir => {
+                lines.push(Line::from(vec![
+                    Span::styled("> ", self.key_style(Color::Green)),
+                    Span::raw(wizard.notes_dir.clone()),
+                    Span::styled(self.glyph("█", "_"), self.key_style(Color::Yellow)),
+                ]));
+            }
+            SetupStep::Summary => {
+                for (label, value) in wizard.summary() {
+                    lines.push(Line::from(vec![
+                        Span::styled(format!("{:<18}", label), self.muted_style()),
+                        Span::raw(value),
+                    ]));
+                }
+            }
+            _ => {
+                for (index, (label, about)) in wizard.options().into_iter().enumerate() {
+                    let picked = index == wizard.choice;
+                    let marker = match (picked, self.ascii()) {
+                        (true, false) => "● ",
+                        (false, false) => "○ ",
+                        (true, true) => "(x) ",
+                        (false, true) => "( ) ",
+                    };
+                    let label_style = if picked {
+                        self.key_style(Color::Yellow)
+                    } else {
+                        Style::default()
+                    };
+                    lines.push(Line::from(vec![
+                        Span::styled(marker, self.key_style(Color::Green)),
+                        Span::styled(format!("{:<16}", label), label_style),
+                        Span::styled(about, self.muted_style()),
+                    ]));
+                }
+            }
+        }
+        if let Some(error) = &wizard.error {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))));
+        }
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(wizard.title()).borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_widget(paragraph, area);
+    }
+
+    /// How Turbo Notes starts on login, the two ways it can, and the exact
+    /// entry the picked one creates.
+    fn draw_autostart(&self, f: &mut Frame, area: Rect, settings: &AutostartSettings) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(7), Constraint::Min(0)])
+            .split(area);
+
+        let status = match settings.current {
+            Some(mode) => Span::styled(format!("on ({})", mode.label().to_lowercase()), self.key_style(Color::Green)),
+            None => Span::styled("off", self.key_style(Color::Red)),
+        };
+        let mut lines = vec![Line::from(vec![Span::raw("Start on login: "), status])];
+        if let Some(state) = settings.service_state.as_ref().filter(|_| settings.current == Some(Mode::Service)) {
+            lines.push(Line::from(Span::styled(format!("Service is {}", state), self.muted_style())));
+        }
+        lines.push(Line::from(""));
+        for (mode, about) in [
+            (Mode::LoginEntry, "started by the desktop when you log in"),
+            (Mode::Service, "run by systemd or launchd, restarted if it crashes"),
+        ] {
+            let picked = mode == settings.picked;
+            let marker = match (picked, self.ascii()) {
+                (true, false) => "● ",
+                (false, false) => "○ ",
+                (true, true) => "(x) ",
+                (false, true) => "( ) ",
+            };
+            let label = if picked {
+                self.key_style(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(marker, self.key_style(Color::Green)),
+                Span::styled(format!("{:<20}", mode.label()), label),
+                Span::styled(about, self.muted_style()),
+            ]));
+        }
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default().title(" Auto-start ").borders(Borders::ALL).style(Style::default().fg(Color::White)));
+        f.render_widget(paragraph, chunks[0]);
+
+        let verb = if settings.current == Some(settings.picked) { "Created" } else { "Will create" };
+        let (title, lines) = match &settings.entry {
+            Ok(entry) => (
+                format!(" {}: {} ", verb, entry.location),
+                entry.contents.lines().map(|line| Line::from(line.to_string())).collect(),
+            ),
+            Err(error) => (
+                " Not available ".to_string(),
+                vec![Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red)))],
+            ),
+        };
+        let paragraph = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(paragraph, chunks[1]);
+    }
+
+    /// What restoring the selected backup would change, with the notes
+    /// concerned.
+    fn backup_summary(&self, browser: &BackupBrowser, config: &Config) -> Vec<Line<'static>> {
+        let backup = browser.backup();
+        let count = |wanted: NoteChange| browser.notes.iter().filter(|(change, _)| *change == wanted).count();
+        let taken = backup
+            .created_at
+            .map(|time| format!("Taken {}", config.format_timestamp(time.to_utc())))
+            .unwrap_or_else(|| "Taken at an unknown time".to_string());
+        let mut lines = vec![
+            Line::from(Span::styled(backup.id.clone(), Style::default().fg(Color::Yellow))),
+            Line::from(Span::styled(
+                format!("{}, {} notes, {}", taken, browser.notes.len(), stats::format_size(backup.size)),
+                self.muted_style(),
+            )),
+            Line::from(""),
+            Line::from(format!("{} notes changed since", count(NoteChange::Changed))),
+            Line::from(format!("{} notes deleted since (restoring brings them back)", count(NoteChange::Deleted))),
+            Line::from(format!("{} notes new since (restoring the backup drops them)", browser.new_since.len())),
+            Line::from(""),
+        ];
+        let differing = browser
+            .notes
+            .iter()
+            .filter(|(change, _)| *change != NoteChange::Unchanged)
+            .map(|(change, note)| (if *change == NoteChange::Changed { "changed" } else { "deleted" }, note))
+            .chain(browser.new_since.iter().map(|note| ("new    ", note)));
+        for (label, note) in differing {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", label), self.key_style(Color::Yellow)),
+                Span::raw(note.preview(50).replace('\n', " ")),
+            ]));
+        }
+        lines
+    }
+
+    /// Both versions of a conflicted note side by side, aligned hunk by
+    /// hunk. Lines that the merge keeps are marked `+`, dropped ones `-`.
+    fn draw_conflict(&self, f: &mut Frame, area: Rect, view: &ConflictView, config: &Config) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(4), Constraint::Min(0)])
+            .split(area);
+
+        let title = view.remote.content.lines().find(|line| !line.trim().is_empty()).unwrap_or("(empty)");
+        let local_time = match &view.local {
+            Some(note) => format!("edited {}", config.format_timestamp(note.updated_at)),
+            None => "deleted".to_string(),
+        };
+        let (block_title, remote_title) = match view.source {
+            ConflictSource::Sync => (" Sync conflict ", " Other device "),
+            ConflictSource::Copy(_) => (" Conflicted copy ", " Copy "),
+        };
+        let header = Paragraph::new(vec![
+            Line::from(format!("{} (conflict 1 of {})", title.trim().trim_start_matches('#').trim(), view.remaining)),
+            Line::from(Span::styled(
+                format!(
+                    "Here: {}   {}: edited {}   {} changed hunks",
+                    local_time,
+                    remote_title.trim(),
+                    config.format_timestamp(view.remote.updated_at),
+                    view.picks.len()
+                ),
+                self.muted_style(),
+            )),
+        ])
+        .block(
+            Block::default()
+                .title(block_title)
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Yellow)),
+        );
+        f.render_widget(header, chunks[0]);
+
+        let kept = Style::default().fg(if self.monochrome() { Color::White } else { Color::Green });
+        let dropped = Style::default().fg(if self.monochrome() { Color::White } else { Color::Red });
+        let mut left: Vec<Line> = Vec::new();
+        let mut right: Vec<Line> = Vec::new();
+        let mut selected_row = 0;
+        let mut hunk = 0;
+        for segment in &view.segments {
+            match segment {
+                Segment::Same(lines) => {
+                    for line in lines {
+                        left.push(Line::from(Span::styled(format!("  {}", line), self.muted_style())));
+                        right.push(Line::from(Span::styled(format!("  {}", line), self.muted_style())));
+                    }
+                }
+                Segment::Changed { local, remote } => {
+                    let pick = view.picks[hunk];
+                    let selected = hunk == view.hunk;
+                    if selected {
+                        selected_row = left.len();
+                    }
+                    let side = |lines: &[String], keep: bool| -> Vec<Line<'static>> {
+                        let (mark, mut style) = if keep { ("+", kept) } else { ("-", dropped) };
+                        if selected {
+                            style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+                        }
+                        let mut out: Vec<Line> = lines
+                            .iter()
+                            .map(|line| Line::from(Span::styled(format!("{} {}", mark, line), style)))
+                            .collect();
+                        // An empty side still shows where the hunk is
+                        if out.is_empty() {
+                            out.push(Line::from(Span::styled(format!("{} {}", mark, self.glyph("∅", "(nothing)")), style)));
+                        }
+                        out
+                    };
+                    let mut local_lines = side(local, pick != Pick::Remote);
+                    let mut remote_lines = side(remote, pick != Pick::Local);
+                    let rows = local_lines.len().max(remote_lines.len());
+                    local_lines.resize(rows, Line::from(""));
+                    remote_lines.resize(rows, Line::from(""));
+                    left.extend(local_lines);
+                    right.extend(remote_lines);
+                    hunk += 1;
+                }
+            }
+        }
+
+        let columns = Layout::default()
+            .direction(self.columns())
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[1]);
+        // Keep the selected hunk in the upper third of the view
+        let scroll = selected_row.saturating_sub(columns[0].height.saturating_sub(2) as usize / 3) as u16;
+        let local_title = if view.local.is_some() { " This device " } else { " This device (deleted) " };
+        for (lines, area, title) in [(left, columns[0], local_title), (right, columns[1], remote_title)] {
+            let paragraph = Paragraph::new(lines)
+                .scroll((scroll, 0))
+                .block(Block::default().title(title).borders(Borders::ALL));
+            f.render_widget(paragraph, area);
+        }
+    }
+
+    fn draw_input_mode(&self, f: &mut Frame, area: Rect, input: &Input, title: &str) {
+        let input_widget = Paragraph::new(input_lines(input))
+            .style(Style::default().fg(Color::Yellow))
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::Yellow)),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(input_widget, area);
+    }
+
+    fn draw_zen_editor(&self, f: &mut Frame, area: Rect, config: &Config, input: &Input) {
+        let width = if self.full_width() {
+            area.width
+        } else {
+            area.width.min(config.zen_max_width)
+        };
+        let editor = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1,
+            width,
+            height: area.height.saturating_sub(2),
+        };
+
+        // Wrapped line count is approximated by characters; close enough to
+        // keep the end of the text in view.
+        let line_width = width.max(1) as usize;
+        let lines: usize = input
+            .split('\n')
+            .map(|line| line.chars().count().max(1).div_ceil(line_width))
+            .sum();
+        let anchor = if config.zen_typewriter_scrolling {
+            editor.height as usize / 2
+        } else {
+            editor.height.saturating_sub(1) as usize
+        };
+        let scroll = lines.saturating_sub(anchor + 1) as u16;
+
+        let text = Paragraph::new(input_lines(input))
+            .style(Style::default().fg(Color::White))
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0));
+        f.render_widget(text, editor);
+
+        let words = input.split_whitespace().count();
+        let count = Paragraph::new(format!("{} words", words))
+            .style(if self.monochrome() { self.muted_style() } else { Style::default().fg(Color::DarkGray) })
+            .alignment(Alignment::Right);
+        f.render_widget(
+            count,
+            Rect {
+                x: area.x,
+                y: area.y + area.height.saturating_sub(1),
+                width: area.width.saturating_sub(1),
+                height: 1,
+            },
+        );
+    }
+
+    fn draw_widget_content(&self, f: &mut Frame, area: Rect, input: &Input) {
+        let widget_area = if self.full_width() { area } else { self.centered_rect(70, 50, area) };
+        
+        let input_widget = Paragraph::new(input_lines(input))
+            .style(Style::default().fg(Color::Green))
+            .block(
+                Block::default()
+                    .title(" Quick Note Widget ")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::Green)),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(input_widget, widget_area);
+    }
+
+    fn draw_footer(&self, f: &mut Frame, area: Rect, view: &ViewState) {
+        let mut help_text = match (view.mode, view.status) {
+            (_, Some(status)) => vec![Span::raw(status.to_string())],
+            (AppMode::Normal, None) => {
+                vec![
+                    Span::raw("Controls: "),
+                    Span::styled("n", self.key_style(Color::Green)),
+                    Span::raw(":new "),
+                    Span::styled("s", self.key_style(Color::Blue)),
+                    Span::raw(":search "),
+                    Span::styled("w", self.key_style(Color::Magenta)),
+                    Span::raw(":widget "),
+                    Span::styled("z", self.key_style(Color::Magenta)),
+                    Span::raw(":zen "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(":navigate "),
+                    Span::styled("v", self.key_style(Color::Cyan)),
+                    Span::raw(":view "),
+                    Span::styled("t", self.key_style(Color::Cyan)),
+                    Span::raw(":tag "),
+                    Span::styled("m", self.key_style(Color::Cyan)),
+                    Span::raw(":remind "),
+                    Span::styled("p", self.key_style(Color::Magenta)),
+                    Span::raw(":pomodoro "),
+                    Span::styled("i", self.key_style(Color::Cyan)),
+                    Span::raw(":reminders "),
+                    Span::styled("Space", self.key_style(Color::Green)),
+                    Span::raw(":select "),
+                    Span::styled("x", self.key_style(Color::Cyan)),
+                    Span::raw(":export "),
+                    Span::styled(self.glyph("Shift+↑↓", "Shift+Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(":move "),
+                    Span::styled("o", self.key_style(Color::Yellow)),
+                    Span::raw(":sort "),
+                    Span::styled("O", self.key_style(Color::Cyan)),
+                    Span::raw(":open link "),
+                    Span::styled("S", self.key_style(Color::Cyan)),
+                    Span::raw(":shares "),
+                    Span::styled("y", self.key_style(Color::Cyan)),
+                    Span::raw(":snippets "),
+                    Span::styled("H", self.key_style(Color::Cyan)),
+                    Span::raw(":habits "),
+                    Span::styled("A", self.key_style(Color::Cyan)),
+                    Span::raw(":stats "),
+                    Span::styled("R", self.key_style(Color::Cyan)),
+                    Span::raw(":read later "),
+                    Span::styled("Enter", self.key_style(Color::Cyan)),
+                    Span::raw(":edit "),
+                    Span::styled("e", self.key_style(Color::Cyan)),
+                    Span::raw(":$EDITOR "),
+                    Span::styled("Del", self.key_style(Color::Red)),
+                    Span::raw(":delete "),
+                    Span::styled("q", self.key_style(Color::Red)),
+                    Span::raw(":quit"),
+                ]
+            }
+            (AppMode::Insert, None) if view.meeting => {
+                vec![
+                    Span::raw("MEETING - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": new line, with the time under the last heading "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": save & exit"),
+                ]
+            }
+            (AppMode::Insert, None) => {
+                vec![
+                    Span::raw("INSERT MODE - "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": save & exit "),
+                    Span::styled("F11", self.key_style(Color::Magenta)),
+                    Span::raw(": zen"),
+                ]
+            }
+            (AppMode::Search, None) => {
+                vec![
+                    Span::raw("SEARCH MODE - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": search "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::Tag, None) => {
+                vec![
+                    Span::raw("TAG MODE - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": add tag "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::Detail, None) => {
+                vec![
+                    Span::raw("DETAIL - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": scroll "),
+                    Span::styled("Enter", self.key_style(Color::Cyan)),
+                    Span::raw(": edit "),
+                    Span::styled("o", self.key_style(Color::Cyan)),
+                    Span::raw(": open link "),
+                    Span::styled("t", self.key_style(Color::Cyan)),
+                    Span::raw(": outline "),
+                    Span::styled("s", self.key_style(Color::Cyan)),
+                    Span::raw(": share "),
+                    Span::styled("Q", self.key_style(Color::Cyan)),
+                    Span::raw(": QR code "),
+                    Span::styled("r", self.key_style(Color::Cyan)),
+                    Span::raw(": record memo "),
+                    Span::styled("p", self.key_style(Color::Cyan)),
+                    Span::raw(": play "),
+                    Span::styled("d", self.key_style(Color::Green)),
+                    Span::raw(": habit done today "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Stats, None) => {
+                vec![
+                    Span::raw("STATS - "),
+                    Span::styled(self.glyph("←→", "Left/Right"), self.key_style(Color::Yellow)),
+                    Span::raw(": week "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": day "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": notes of the day "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Outline, None) => {
+                vec![
+                    Span::raw("OUTLINE - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": go to heading "),
+                    Span::styled(self.glyph("←→", "Left/Right"), self.key_style(Color::Yellow)),
+                    Span::raw(": collapse/expand "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": read from here "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": close"),
+                ]
+            }
+            (AppMode::Graph, None) => {
+                vec![
+                    Span::raw("GRAPH - "),
+                    Span::styled(self.glyph("←→↑↓", "Arrows"), self.key_style(Color::Yellow)),
+                    Span::raw(": nearest note that way "),
+                    Span::styled("Tab", self.key_style(Color::Yellow)),
+                    Span::raw(": next "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": view "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::StatsDay, None) => {
+                vec![
+                    Span::raw("DAY - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": view "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Habits, None) => {
+                vec![
+                    Span::raw("HABITS - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("d", self.key_style(Color::Green)),
+                    Span::raw("/"),
+                    Span::styled("Space", self.key_style(Color::Green)),
+                    Span::raw(": done today "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Snippets, None) => {
+                vec![
+                    Span::raw("SNIPPETS - type to filter "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": copy "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::SnippetField, None) => {
+                vec![
+                    Span::raw("SNIPPET - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": next field "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::TagSuggestions, None) => {
+                vec![
+                    Span::raw("TAGS - "),
+                    Span::styled("Space", self.key_style(Color::Yellow)),
+                    Span::raw(": pick "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": save with the picked tags "),
+                    Span::styled("Backspace", self.key_style(Color::Cyan)),
+                    Span::raw(": back to the note "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": save without"),
+                ]
+            }
+            (AppMode::Recording, None) => {
+                vec![
+                    Span::raw("RECORDING - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": stop and attach "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": discard"),
+                ]
+            }
+            (AppMode::Export, None) => {
+                vec![
+                    Span::raw("EXPORT - "),
+                    Span::styled("Tab", self.key_style(Color::Cyan)),
+                    Span::raw(": format "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": export "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::Conflict, None) => {
+                vec![
+                    Span::raw("CONFLICT - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": hunk "),
+                    Span::styled(self.glyph("←", "Left"), self.key_style(Color::Cyan)),
+                    Span::raw(": this device "),
+                    Span::styled(self.glyph("→", "Right"), self.key_style(Color::Cyan)),
+                    Span::raw(": other "),
+                    Span::styled("b", self.key_style(Color::Cyan)),
+                    Span::raw(": both "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": save merge "),
+                    Span::styled("L/R", self.key_style(Color::Green)),
+                    Span::raw(": keep one side "),
+                    Span::styled("D", self.key_style(Color::Green)),
+                    Span::raw(": duplicate "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": later"),
+                ]
+            }
+            (AppMode::Copy, None) => {
+                vec![
+                    Span::raw("COPY - "),
+                    Span::styled("m", self.key_style(Color::Green)),
+                    Span::raw(": merge "),
+                    Span::styled("i", self.key_style(Color::Cyan)),
+                    Span::raw(": import as new notes "),
+                    Span::styled("d", self.key_style(Color::Red)),
+                    Span::raw(": delete copy "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": later"),
+                ]
+            }
+            (AppMode::Backups, None) => {
+                vec![
+                    Span::raw("BACKUPS - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Cyan)),
+                    Span::raw(": open notes "),
+                    Span::styled("Space", self.key_style(Color::Green)),
+                    Span::raw(": pick "),
+                    Span::styled("r", self.key_style(Color::Green)),
+                    Span::raw(": restore notes "),
+                    Span::styled("R", self.key_style(Color::Red)),
+                    Span::raw(": restore whole backup "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Reminders, None) => {
+                vec![
+                    Span::raw("REMINDERS - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("1/2/3", self.key_style(Color::Cyan)),
+                    Span::raw(": snooze 10m/1h/1d "),
+                    Span::styled("d", self.key_style(Color::Green)),
+                    Span::raw(": done "),
+                    Span::styled("Enter", self.key_style(Color::Cyan)),
+                    Span::raw(": open note "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Links, None) => {
+                vec![
+                    Span::raw("LINKS - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw("/"),
+                    Span::styled("1-9", self.key_style(Color::Green)),
+                    Span::raw(": open "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Shares, None) => {
+                vec![
+                    Span::raw("SHARES - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": open "),
+                    Span::styled("Q", self.key_style(Color::Cyan)),
+                    Span::raw(": QR code "),
+                    Span::styled("d", self.key_style(Color::Red)),
+                    Span::raw(": revoke "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Qr, None) => {
+                vec![
+                    Span::raw("QR CODE - scan it with a phone "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Profiles, None) => {
+                vec![
+                    Span::raw("PROFILES - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": switch "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Palette, None) => {
+                vec![
+                    Span::raw("COMMANDS - type to filter "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": select "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": run on the selected note "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::Draft, None) => {
+                vec![
+                    Span::raw("DRAFT - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": restore and keep typing "),
+                    Span::styled("d", self.key_style(Color::Red)),
+                    Span::raw(": discard"),
+                ]
+            }
+            (AppMode::SaveElsewhere, None) => {
+                vec![
+                    Span::raw("SAVE ELSEWHERE - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": save there "),
+                    Span::styled("Esc", self.key_style(Color::Yellow)),
+                    Span::raw(": back "),
+                    Span::styled("Ctrl+Q", self.key_style(Color::Red)),
+                    Span::raw(": quit without saving"),
+                ]
+            }
+            (AppMode::Log, None) => {
+                vec![
+                    Span::raw("LOG - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": scroll "),
+                    Span::styled("Home/End", self.key_style(Color::Cyan)),
+                    Span::raw(": oldest/newest "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Setup, None) => {
+                vec![
+                    Span::raw("SETUP - "),
+                    Span::styled(self.glyph("↑↓", "Up/Down"), self.key_style(Color::Yellow)),
+                    Span::raw(": choose "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": next "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back, or skip with the defaults on the first step"),
+                ]
+            }
+            (AppMode::Autostart, None) => {
+                vec![
+                    Span::raw("AUTO-START - "),
+                    Span::styled("Tab", self.key_style(Color::Cyan)),
+                    Span::raw(": mode "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": turn on/off "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": back"),
+                ]
+            }
+            (AppMode::Remind, None) => {
+                vec![
+                    Span::raw("REMIND - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": set "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+            (AppMode::Widget, None) => {
+                vec![
+                    Span::raw("WIDGET MODE - "),
+                    Span::styled("Enter", self.key_style(Color::Green)),
+                    Span::raw(": save "),
+                    Span::styled("Esc", self.key_style(Color::Red)),
+                    Span::raw(": cancel"),
+                ]
+            }
+        };
+
+        let saving = match view.notes_manager.save_state() {
+            SaveState::Idle => None,
+            SaveState::Saving => Some((self.glyph("Saving…", "Saving...").to_string(), Color::Yellow)),
+            SaveState::Saved => Some(("Saved".to_string(), Color::Green)),
+            SaveState::Failed(error) => Some((format!("Not saved: {}", error), Color::Red)),
+        };
+        if let Some((text, color)) = saving {
+            help_text.insert(0, Span::styled(format!("{}  ", text), self.key_style(color)));
+        }
+
+        if let Some((words, goal)) = view.word_goal {
+            let color = if words >= goal { Color::Green } else { Color::Yellow };
+            let progress = format!("{}{}/{} words  ", self.glyph("✍ ", "Goal "), words, goal);
+            help_text.insert(0, Span::styled(progress, self.key_style(color)));
+        }
+
+        if let Some(timer) = view.pomodoro {
+            let countdown = format!("{}{}  ", self.glyph("🍅 ", "Pomodoro "), timer.countdown());
+            help_text.insert(0, Span::styled(countdown, self.key_style(Color::Magenta)));
+        }
+
+        let help = Paragraph::new(Line::from(help_text))
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(self.bar_borders())
+                    .style(Style::default().fg(Color::White)),
+            );
+        f.render_widget(help, area);
+    }
+
+    fn centered_rect(&self, percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+        let popup_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ])
+            .split(r);
+
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ])
+            .split(popup_layout[1])[1]
+    }
+}
+
+/// Cells on the line from `from` to `to`, leaving out both ends.
+fn line(from: (u16, u16), to: (u16, u16)) -> Vec<(u16, u16)> {
+    let (x0, y0, x1, y1) = (i32::from(from.0), i32::from(from.1), i32::from(to.0), i32::from(to.1));
+    let steps = (x1 - x0).abs().max((y1 - y0).abs());
+    (1..steps)
+        .map(|i| {
+            let x = x0 + ((x1 - x0) * i * 2 + steps).div_euclid(steps * 2);
+            let y = y0 + ((y1 - y0) * i * 2 + steps).div_euclid(steps * 2);
+            (x as u16, y as u16)
+        })
+        .collect()
+}
+
+/// The start of `content` that can show in `area`, so a note of megabytes
+/// is not laid out whole on every frame. Every line takes a row or more, and
+/// a row holds at most `width` characters.
+fn visible_part(content: &str, area: Rect) -> &str {
+    let rows = area.height as usize;
+    let end = content.match_indices('\n').nth(rows).map_or(content.len(), |(end, _)| end);
+    let end = content[..end].char_indices().nth(rows * area.width as usize).map_or(end, |(end, _)| end);
+    &content[..end]
+}
+
+/// The text being typed as lines, with the selection, or else the cursor,
+/// shown reversed.
+fn input_lines(input: &Input) -> Vec<Line<'static>> {
+    let cursor = input.cursor();
+    let marked = input.selection().unwrap_or(cursor..cursor);
+    let is_marked = |at: usize| marked.contains(&at) || (marked.is_empty() && at == cursor);
+    let reversed = Style::default().add_modifier(Modifier::REVERSED);
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in input.split('\n') {
+        let mut spans = Vec::new();
+        let mut run = String::new();
+        let mut run_marked = false;
+        for (i, c) in line.char_indices() {
+            let marked = is_marked(start + i);
+            if marked != run_marked && !run.is_empty() {
+                let style = if run_marked { reversed } else { Style::default() };
+                spans.push(Span::styled(std::mem::take(&mut run), style));
+            }
+            run_marked = marked;
+            run.push(c);
+        }
+        if !run.is_empty() {
+            spans.push(Span::styled(run, if run_marked { reversed } else { Style::default() }));
+        }
+        let end = start + line.len();
+        // Past the end of a line the cursor takes a blank cell
+        if is_marked(end) {
+            spans.push(Span::styled(" ", reversed));
+        }
+        lines.push(Line::from(spans));
+        start = end + 1;
     }
+    lines
 }
@@ -0,0 +1,398 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::UserError;
+use crate::import::Imported;
+use crate::notes::{self, Note};
+
+/// Directory of an export that attachments are copied to
+const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Where an export directory lists the files and directories
+/// `export_files` made in it, so `prune` removes nothing else
+pub const MANIFEST_FILE: &str = ".turbo-notes-export.json";
+
+/// What the exports to a directory wrote there, relative to it
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+impl Manifest {
+    /// The manifest in `dir`, without entries that could point outside it
+    /// or into hidden directories such as `.git`; empty if there is none.
+    fn load(dir: &Path) -> Self {
+        let manifest: Self = fs::read(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let inside = |path: &PathBuf| {
+            path.components().count() > 0
+                && path
+                    .components()
+                    .all(|part| matches!(part, Component::Normal(name) if !name.to_string_lossy().starts_with('.')))
+        };
+        Self {
+            files: manifest.files.into_iter().filter(inside).collect(),
+            dirs: manifest.dirs.into_iter().filter(inside).collect(),
+        }
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Records `path`, a file written under `dir`.
+    fn add_file(&mut self, dir: &Path, path: &Path) {
+        if let Ok(name) = path.strip_prefix(dir) {
+            if !self.files.iter().any(|file| file == name) {
+                self.files.push(name.to_path_buf());
+            }
+        }
+    }
+
+    /// Creates `name` under `dir`, recording it if the export made it.
+    fn add_dir(&mut self, dir: &Path, name: PathBuf) -> Result<PathBuf> {
+        let path = dir.join(&name);
+        if !path.exists() && !self.dirs.contains(&name) {
+            self.dirs.push(name);
+        }
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+}
+
+/// Writes one Markdown file per note under `dir`, with YAML frontmatter, so
+/// the directory can be opened as an Obsidian or Logseq vault. Notes in a
+/// notebook go into a subdirectory named after it. Images and memos
+/// attached to a note in `notes_dir` are copied to `attachments/<id>` and
+/// linked at the end of its file. Files from an earlier export to the same
+/// directory are overwritten. What was written is added to the directory's
+/// manifest. Returns the files written, attachments included.
+pub fn export_files(notes: &[&Note], notes_dir: &Path, dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut manifest = Manifest::load(dir);
+    let mut written: Vec<PathBuf> = Vec::with_capacity(notes.len());
+
+    for note in notes {
+        let (folder, up) = match &note.notebook {
+            Some(notebook) => (manifest.add_dir(dir, PathBuf::from(slug(notebook, "notebook")))?, "../"),
+            None => (dir.to_path_buf(), ""),
+        };
+
+        let stem = slug(note.title(), &note.id);
+        let mut path = folder.join(format!("{}.md", stem));
+        let mut n = 2;
+        while written.contains(&path) {
+            path = folder.join(format!("{}-{}.md", stem, n));
+            n += 1;
+        }
+
+        let mut links = Vec::new();
+        let images = note.images.iter().map(|image| (&image.file, true));
+        for (file, image) in images.chain(note.memos.iter().map(|memo| (&memo.file, false))) {
+            let source = notes::attachments_dir(notes_dir, &note.id).join(file);
+            if !source.is_file() {
+                continue;
+            }
+            manifest.add_dir(dir, PathBuf::from(ATTACHMENTS_DIR))?;
+            let copy = manifest.add_dir(dir, Path::new(ATTACHMENTS_DIR).join(&note.id))?.join(file);
+            fs::copy(&source, &copy)?;
+            manifest.add_file(dir, &copy);
+            written.push(copy);
+            let target = format!("{}{}/{}/{}", up, ATTACHMENTS_DIR, note.id, file.replace(' ', "%20"));
+            match image {
+                true => links.push(format!("![{}]({})", file, target)),
+                false => links.push(format!("[{}]({})", file, target)),
+            }
+        }
+        let body = match links.is_empty() {
+            true => note.content.clone(),
+            false => format!("{}\n\n{}", note.content, links.join("\n")),
+        };
+
+        fs::write(&path, format!("{}{}\n", frontmatter(note), body))?;
+        // Keep file dates meaningful for tools that sort by them
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(SystemTime::from(note.updated_at))?;
+        manifest.add_file(dir, &path);
+        written.push(path);
+    }
+    manifest.save(dir)?;
+    Ok(written)
+}
+
+/// Renders note metadata as a YAML frontmatter block. Strings are written
+/// JSON-quoted, which is also valid YAML.
+pub fn frontmatter(note: &Note) -> String {
+    let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    let mut out = String::from("---\n");
+    out.push_str(&format!("id: {}\n", quote(&note.id)));
+    out.push_str(&format!("title: {}\n", quote(note.title())));
+    out.push_str(&format!("created: {}\n", note.created_at.to_rfc3339()));
+    out.push_str(&format!("updated: {}\n", note.updated_at.to_rfc3339()));
+    let tags: Vec<String> = note.tags.iter().map(|tag| quote(tag)).collect();
+    out.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    if let Some(notebook) = &note.notebook {
+        out.push_str(&format!("notebook: {}\n", quote(notebook)));
+    }
+    if note.pinned {
+        out.push_str("pinned: true\n");
+    }
+    if note.locked {
+        out.push_str("locked: true\n");
+    }
+    out.push_str("---\n\n");
+    out
+}
+
+/// Removes the files in `dir`'s manifest that are not in `keep`, then the
+/// notebook and attachment directories the exports made that are left
+/// empty. Nothing
+/// else in `dir` is touched. Returns the number of files removed.
+pub fn prune(dir: &Path, keep: &[PathBuf]) -> Result<usize> {
+    let mut manifest = Manifest::load(dir);
+    let mut removed = 0;
+    let (kept, gone): (Vec<PathBuf>, Vec<PathBuf>) = manifest.files.drain(..).partition(|file| keep.contains(&dir.join(file)));
+    for file in gone {
+        let path = dir.join(file);
+        if path.is_file() {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    manifest.files = kept;
+    // Deepest first, so a directory is empty once those in it are gone
+    manifest.dirs.sort_by_key(|name| std::cmp::Reverse(name.components().count()));
+    manifest.dirs.retain(|name| {
+        let path = dir.join(name);
+        let empty = fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_none());
+        match empty {
+            true => fs::remove_dir(&path).is_err(),
+            false => path.is_dir(),
+        }
+    });
+    manifest.save(dir)?;
+    Ok(removed)
+}
+
+/// Turns each `.md` and `.txt` file under `dir` into a note. The file name
+/// becomes the title unless the text already starts with it, and the file
+/// times become the note times. Frontmatter, as written by `export_files`
+/// or tools like Obsidian, supplies the title, times, tags and notebook
+/// when present, and the id if this app wrote it. With `recursive`, notes
+/// in subdirectories go into a notebook named after the subdirectory path.
+/// Hidden entries are ignored and other files are counted as skipped.
+pub fn import_files(dir: &Path, recursive: bool, progress: &mut dyn FnMut(usize, usize)) -> Result<Imported> {
+    if !dir.is_dir() {
+        return Err(UserError::NotFound(format!("{} is not a directory", dir.display())).into());
+    }
+    let mut files = Vec::new();
+    let mut skipped_files = 0;
+    collect_files(dir, recursive, &mut files, &mut skipped_files)?;
+    files.sort();
+
+    let mut notes = Vec::with_capacity(files.len());
+    for (done, path) in files.iter().enumerate() {
+        progress(done, files.len());
+        let Ok(text) = fs::read_to_string(path) else {
+            skipped_files += 1;
+            continue;
+        };
+        let text = text.replace("\r\n", "\n");
+        let (meta, body) = parse_frontmatter(&text);
+        let meta = meta.unwrap_or_default();
+
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        let title = meta.title.as_deref().unwrap_or(stem).trim();
+        let body = body.trim();
+        let first_line = body.lines().next().unwrap_or("").trim_start_matches('#').trim();
+        let content = if title.is_empty() || first_line.eq_ignore_ascii_case(title) {
+            body.to_string()
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")) {
+            format!("# {}\n\n{}", title, body)
+        } else {
+            format!("{}\n\n{}", title, body)
+        };
+        if content.trim().is_empty() {
+            continue;
+        }
+
+        let mut note = Note::new(content.trim_end().to_string());
+        // Only ids this app gave, so another tool's `id` cannot name a file
+        if let Some(id) = meta.id.filter(|id| is_app_id(id)) {
+            note.id = id;
+        }
+        let file_meta = fs::metadata(path)?;
+        let modified = file_meta.modified().ok().map(DateTime::<Utc>::from);
+        // Copied files get a fresh creation time but keep their mtime
+        let created = match (file_meta.created().ok().map(DateTime::<Utc>::from), modified) {
+            (Some(created), Some(modified)) => Some(created.min(modified)),
+            (created, modified) => created.or(modified),
+        };
+        note.created_at = meta.created.or(created).unwrap_or(note.created_at);
+        note.notebook = meta.notebook.or_else(|| {
+            let folder = path.parent()?.strip_prefix(dir).ok()?;
+            let folder = folder.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+            (!folder.is_empty()).then_some(folder)
+        });
+        note.pinned = meta.pinned;
+        note.locked = meta.locked;
+        for tag in meta.tags {
+            note.add_tag(tag.replace(char::is_whitespace, "-"));
+        }
+        note.updated_at = meta.updated.or(modified).unwrap_or(note.created_at);
+        notes.push(note);
+    }
+    Ok(Imported { notes, skipped_files, ..Default::default() })
+}
+
+/// Whether `id` is one `Note::new` could have made: digits that fit an i64.
+fn is_app_id(id: &str) -> bool {
+    id.bytes().all(|b| b.is_ascii_digit()) && id.parse::<i64>().is_ok()
+}
+
+fn collect_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>, skipped: &mut usize) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            if recursive {
+                collect_files(&path, recursive, files, skipped)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("txt"))
+        {
+            files.push(path);
+        } else {
+            *skipped += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Metadata read back from a frontmatter block. Only the keys we write
+/// (and the common `tags` spellings other tools use) are understood.
+#[derive(Debug, Default)]
+pub struct Frontmatter {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub notebook: Option<String>,
+    pub pinned: bool,
+    pub locked: bool,
+}
+
+/// Splits a leading `---` frontmatter block from `content`. Returns the
+/// parsed metadata (if there was a block) and the remaining body.
+pub fn parse_frontmatter(content: &str) -> (Option<Frontmatter>, &str) {
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return (None, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (None, content);
+    };
+    let block = &rest[..end];
+    let body = rest[end + 4..].trim_start_matches(['\r', '\n']);
+
+    let mut meta = Frontmatter::default();
+    let mut in_tag_list = false;
+    for line in block.lines() {
+        // Block-style lists: "tags:" followed by "  - name" lines
+        if in_tag_list {
+            if let Some(item) = line.trim_start().strip_prefix("- ") {
+                meta.tags.push(unquote(item));
+                continue;
+            }
+            in_tag_list = false;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "id" => meta.id = Some(unquote(value)),
+            "title" => meta.title = Some(unquote(value)),
+            "created" | "date" => meta.created = parse_time(value),
+            "updated" | "modified" => meta.updated = parse_time(value),
+            "notebook" => meta.notebook = Some(unquote(value)),
+            "pinned" => meta.pinned = value == "true",
+            "locked" => meta.locked = value == "true",
+            "tags" | "tag" => {
+                if value.is_empty() {
+                    in_tag_list = true;
+                } else {
+                    let value = value.trim_start_matches('[').trim_end_matches(']');
+                    meta.tags.extend(
+                        value
+                            .split(',')
+                            .map(unquote)
+                            .filter(|tag| !tag.is_empty()),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    for tag in &mut meta.tags {
+        *tag = tag.trim_start_matches('#').to_string();
+    }
+    (Some(meta), body)
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.starts_with('"') {
+        if let Ok(unquoted) = serde_json::from_str::<String>(value) {
+            return unquoted;
+        }
+    }
+    value.trim_matches(['"', '\'']).to_string()
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = unquote(value);
+    DateTime::parse_from_rfc3339(&value)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|time| time.and_utc())
+        })
+}
+
+/// Lowercase, dash-separated file name part, at most 60 characters.
+fn slug(text: &str, fallback: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 60 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        fallback.to_string()
+    } else {
+        slug.to_string()
+    }
+}
@@ -0,0 +1,244 @@
+use crate::app::expand_home;
+use crate::config::{Config, Keymap, Theme};
+
+/// Steps of the first-run setup, in order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetupStep {
+    NotesDir,
+    Theme,
+    Keymap,
+    AutoStart,
+    Backups,
+    Summary,
+}
+
+const STEPS: [SetupStep; 6] = [
+    SetupStep::NotesDir,
+    SetupStep::Theme,
+    SetupStep::Keymap,
+    SetupStep::AutoStart,
+    SetupStep::Backups,
+    SetupStep::Summary,
+];
+
+/// Themes offered: name, then primary, secondary, background, text and
+/// accent colors.
+const THEMES: [(&str, [&str; 5]); 4] = [
+    ("Classic", ["cyan", "blue", "black", "white", "yellow"]),
+    ("Ocean", ["blue", "cyan", "black", "white", "magenta"]),
+    ("Forest", ["green", "yellow", "black", "white", "cyan"]),
+    ("Mono", ["white", "gray", "black", "white", "white"]),
+];
+
+/// Backup choices: label and interval in hours, none for off.
+const BACKUPS: [(&str, Option<u64>); 3] = [("Daily", Some(24)), ("Every 6 hours", Some(6)), ("Off", None)];
+
+/// First-run setup: a few questions whose answers make up the config that
+/// is written when it finishes.
+#[derive(Debug, Clone)]
+pub struct SetupWizard {
+    pub step: SetupStep,
+    pub config: Config,
+    /// Notes directory as typed
+    pub notes_dir: String,
+    /// Highlighted option of a multiple-choice step
+    pub choice: usize,
+    /// Why the answers could not be saved, shown until the next key
+    pub error: Option<String>,
+}
+
+impl SetupWizard {
+    pub fn new(config: Config) -> Self {
+        Self {
+            step: SetupStep::NotesDir,
+            notes_dir: config.notes_dir.display().to_string(),
+            config,
+            choice: 0,
+            error: None,
+        }
+    }
+
+    fn index(&self) -> usize {
+        STEPS.iter().position(|step| *step == self.step).unwrap_or(0)
+    }
+
+    pub fn title(&self) -> String {
+        let name = match self.step {
+            SetupStep::NotesDir => "Notes directory",
+            SetupStep::Theme => "Theme",
+            SetupStep::Keymap => "Keys",
+            SetupStep::AutoStart => "Start on login",
+            SetupStep::Backups => "Backups",
+            SetupStep::Summary => "All set",
+        };
+        format!(" Setup {}/{}: {} ", self.index() + 1, STEPS.len(), name)
+    }
+
+    pub fn question(&self) -> &'static str {
+        match self.step {
+            SetupStep::NotesDir => "Where should your notes be kept? Enter accepts, or type another directory.",
+            SetupStep::Theme => "Pick the interface colors. They can be changed under theme in the config.",
+            SetupStep::Keymap => "How do you like to move around the notes list?",
+            SetupStep::AutoStart => "Start Turbo Notes in the background when you log in, ready for the quick widget?",
+            SetupStep::Backups => "Back up your notes automatically?",
+            SetupStep::Summary => "Press Enter to save these settings and start, or Esc to change them.",
+        }
+    }
+
+    /// Options of a multiple-choice step: a label and what it means.
+    pub fn options(&self) -> Vec<(&'static str, String)> {
+        match self.step {
+            SetupStep::Theme => THEMES
+                .iter()
+                .map(|(name, colors)| (*name, format!("{} with {} highlights", colors[0], colors[4])))
+                .collect(),
+            SetupStep::Keymap => vec![
+                ("Standard", "arrow keys, Home and End".to_string()),
+                ("Vim", "also j/k to move, g/G for the first and last note, / to search".to_string()),
+            ],
+            SetupStep::AutoStart => vec![
+                ("No", "start it yourself".to_string()),
+                ("Yes", "adds a login entry; change it later with 'a' in the app".to_string()),
+            ],
+            SetupStep::Backups => BACKUPS
+                .iter()
+                .map(|(label, hours)| match hours {
+                    Some(_) => (*label, "kept in backups/ next to your notes".to_string()),
+                    None => (*label, "back up yourself with 'turbo-notes backup now'".to_string()),
+                })
+                .collect(),
+            SetupStep::NotesDir | SetupStep::Summary => Vec::new(),
+        }
+    }
+
+    /// The answers so far, for the last step.
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let theme = THEMES
+            .iter()
+            .find(|(_, colors)| colors[0] == self.config.theme.primary_color && colors[4] == self.config.theme.accent_color)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| "custom".to_string());
+        let keymap = match self.config.keymap {
+            Keymap::Standard => "Standard",
+            Keymap::Vim => "Vim",
+        };
+        let backups = match (self.config.backup_enabled, self.config.backup_interval_hours) {
+            (false, _) => "Off".to_string(),
+            (true, 24) => "Daily".to_string(),
+            (true, hours) => format!("Every {} hours", hours),
+        };
+        vec![
+            ("Notes directory", self.config.notes_dir.display().to_string()),
+            ("Theme", theme),
+            ("Keys", keymap.to_string()),
+            ("Start on login", if self.config.auto_start { "Yes" } else { "No" }.to_string()),
+            ("Backups", backups),
+        ]
+    }
+
+    /// The option matching the current settings, so going back keeps the
+    /// earlier answer highlighted.
+    fn current_choice(&self) -> usize {
+        match self.step {
+            SetupStep::Theme => THEMES
+                .iter()
+                .position(|(_, colors)| colors[0] == self.config.theme.primary_color)
+                .unwrap_or(0),
+            SetupStep::Keymap => usize::from(self.config.keymap == Keymap::Vim),
+            SetupStep::AutoStart => usize::from(self.config.auto_start),
+            SetupStep::Backups => BACKUPS
+                .iter()
+                .position(|(_, hours)| match hours {
+                    Some(hours) => self.config.backup_enabled && *hours == self.config.backup_interval_hours,
+                    None => !self.config.backup_enabled,
+                })
+                .unwrap_or(0),
+            SetupStep::NotesDir | SetupStep::Summary => 0,
+        }
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let count = self.options().len();
+        if count > 0 {
+            self.choice = (self.choice as isize + delta).clamp(0, count as isize - 1) as usize;
+        }
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        match (self.step, c) {
+            (SetupStep::NotesDir, c) => self.notes_dir.push(c),
+            (_, 'k') => self.select(-1),
+            (_, 'j') => self.select(1),
+            _ => {}
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if self.step == SetupStep::NotesDir {
+            self.notes_dir.pop();
+        }
+    }
+
+    /// Takes the answer to the current step and moves on. Returns true once
+    /// the summary has been confirmed.
+    pub fn answer(&mut self) -> bool {
+        self.error = None;
+        match self.step {
+            SetupStep::NotesDir => {
+                let dir = self.notes_dir.trim();
+                if dir.is_empty() {
+                    self.error = Some("Enter a directory".to_string());
+                    return false;
+                }
+                self.config.notes_dir = expand_home(dir);
+            }
+            SetupStep::Theme => {
+                let (_, colors) = THEMES[self.choice.min(THEMES.len() - 1)];
+                let [primary, secondary, background, text, accent] = colors.map(str::to_string);
+                self.config.theme = Theme {
+                    primary_color: primary,
+                    secondary_color: secondary,
+                    background_color: background,
+                    text_color: text,
+                    accent_color: accent,
+                };
+            }
+            SetupStep::Keymap => {
+                self.config.keymap = if self.choice == 1 { Keymap::Vim } else { Keymap::Standard };
+            }
+            SetupStep::AutoStart => self.config.auto_start = self.choice == 1,
+            SetupStep::Backups => {
+                let (_, hours) = BACKUPS[self.choice.min(BACKUPS.len() - 1)];
+                self.config.backup_enabled = hours.is_some();
+                if let Some(hours) = hours {
+                    self.config.backup_interval_hours = hours;
+                }
+            }
+            SetupStep::Summary => return true,
+        }
+        self.step = STEPS[self.index() + 1];
+        self.choice = self.current_choice();
+        false
+    }
+
+    /// Goes back a step. Returns false on the first one.
+    pub fn back(&mut self) -> bool {
+        self.error = None;
+        match self.index() {
+            0 => false,
+            index => {
+                self.step = STEPS[index - 1];
+                self.choice = self.current_choice();
+                true
+            }
+        }
+    }
+
+    /// Returns to the notes directory question, e.g. when it cannot be used.
+    pub fn fail(&mut self, error: String) {
+        self.step = SetupStep::NotesDir;
+        self.notes_dir = self.config.notes_dir.display().to_string();
+        self.choice = 0;
+        self.error = Some(error);
+    }
+}
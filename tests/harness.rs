@@ -0,0 +1,64 @@
+//! Flows through the app driven by the testing harness.
+#![cfg(feature = "tui")]
+
+use turbo_notes::app::AppMode;
+use turbo_notes::config::Config;
+use turbo_notes::notes;
+use turbo_notes::testing::Harness;
+
+async fn harness(dir: &tempfile::TempDir) -> anyhow::Result<Harness> {
+    let config = Config { notes_dir: dir.path().to_path_buf(), onboarding_completed: true, ..Config::default() };
+    Harness::new(config, 100, 30).await
+}
+
+#[tokio::test]
+async fn notes_are_created_searched_and_deleted() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut harness = harness(&dir).await?;
+
+    harness.keys("nBuy milk<Esc>nCall the vendor<Esc>").await?;
+    assert!(harness.contains("Buy milk"));
+    assert!(harness.contains("Call the vendor"));
+
+    harness.keys("smilk<Enter>").await?;
+    assert!(harness.contains("1 found"));
+    assert!(!harness.contains("Call the vendor"));
+
+    harness.keys("<Delete><Esc>").await?;
+    assert!(!harness.contains("Buy milk"));
+    assert!(harness.contains("Call the vendor"));
+
+    harness.finish().await?;
+    let saved = notes::read_notes(&dir.path().join("notes.json"))?;
+    let contents: Vec<&str> = saved.iter().map(|note| note.content.as_str()).collect();
+    assert_eq!(contents, ["Call the vendor"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn keys_are_text_in_insert_mode_and_commands_in_normal_mode() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut harness = harness(&dir).await?;
+
+    harness.keys("n").await?;
+    assert_eq!(harness.app.mode, AppMode::Insert);
+    assert!(harness.contains("INSERT MODE"));
+
+    // Command keys and capitals are typed, not run
+    harness.keys("Sync the queue now").await?;
+    assert_eq!(harness.app.mode, AppMode::Insert);
+    assert!(!harness.app.should_quit);
+    assert_eq!(harness.app.current_input.as_str(), "Sync the queue now");
+
+    harness.keys("<Esc>").await?;
+    assert_eq!(harness.app.mode, AppMode::Normal);
+    assert!(!harness.contains("INSERT MODE"));
+    assert!(harness.contains("Sync the queue now"));
+
+    harness.keys("q").await?;
+    assert!(harness.app.should_quit);
+
+    let app = harness.finish().await?;
+    assert_eq!(app.notes_manager.notes[0].content, "Sync the queue now");
+    Ok(())
+}
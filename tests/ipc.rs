@@ -0,0 +1,33 @@
+//! Commands run while the app is open hand their notes to it.
+#![cfg(all(target_os = "linux", feature = "tui"))]
+
+use std::time::Duration;
+use turbo_notes::config::Config;
+use turbo_notes::ipc;
+use turbo_notes::notes::Note;
+use turbo_notes::testing::Harness;
+
+#[tokio::test]
+async fn note_added_while_the_app_runs_comes_back_from_it() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    // The control socket goes in the runtime directory
+    std::env::set_var("XDG_RUNTIME_DIR", dir.path().join("run"));
+    let config = Config { notes_dir: dir.path().join("notes"), onboarding_completed: true, ..Config::default() };
+    let mut harness = Harness::new(config.clone(), 100, 30).await?;
+    harness.listen().await?;
+    // As while the app runs, so the note is not on disk yet when the reply goes
+    harness.app.notes_manager.save_in_background();
+
+    let adding = tokio::spawn(async move { ipc::add_note(Note::new("Sent from a command".into()), &config).await });
+    while !adding.is_finished() {
+        harness.answer_requests().await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let note = adding.await??;
+    assert_eq!(note.content, "Sent from a command");
+    assert!(harness.contains("Sent from a command"));
+
+    let app = harness.finish().await?;
+    assert_eq!(app.notes_manager.notes[0].id, note.id);
+    Ok(())
+}